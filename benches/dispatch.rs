@@ -0,0 +1,99 @@
+use picoview::{
+    Event, HeadlessWindow, Modifiers, MouseButton, Point, Window, WindowBuilder, WindowHandler,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const ITERATIONS: usize = 100_000;
+
+/// Counts every allocation made by the process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Measures the cost of delivering high-rate events (mouse moves with the
+/// occasional press, scroll and frame) through a headless window, and checks
+/// that the dispatch path does not allocate once the window is open.
+///
+/// Goes through the same handler wrappers as real windows (modifier scroll and
+/// the reentrancy guard), so it catches allocations that would show up on
+/// every mouse move there.
+fn main() {
+    struct Handler<'a> {
+        window: Window<'a>,
+        moves: usize,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn mouse_move(&mut self, _: Point) {
+            self.moves += 1;
+
+            // queued while the handler runs, delivered right after
+            if self.moves.is_multiple_of(64) {
+                self.window.redraw_now();
+            }
+        }
+    }
+
+    let window = WindowBuilder::new(|window| {
+        window.set_size((256, 256));
+        Ok(Box::new(Handler { window, moves: 0 }))
+    })
+    .with_modifier_scroll(true)
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    // warm up, so lazily grown buffers are at their final size
+    run(&window, 1000);
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    run(&window, ITERATIONS);
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+        "dispatch ({ITERATIONS} events): {:?} per event, {allocations} allocations",
+        elapsed / ITERATIONS as u32,
+    );
+
+    assert_eq!(allocations, 0, "event dispatch allocated");
+}
+
+fn run(window: &HeadlessWindow, count: usize) {
+    for i in 0..count {
+        let event = match i % 32 {
+            0 => Event::MousePress {
+                button: MouseButton::Left,
+                pressed: i.is_multiple_of(64),
+                position: Point::from((i as f64 % 256.0, 10.0)),
+            },
+            8 => Event::MouseScroll { x: 0.0, y: 1.0 },
+            16 => Event::Frame,
+            24 => Event::KeyModifiers(Modifiers::default()),
+            _ => Event::MouseMove(Point::from((i as f64 % 256.0, 10.0))),
+        };
+
+        window.dispatch(event);
+    }
+}
@@ -0,0 +1,62 @@
+use picoview::{Window, WindowBuilder, WindowHandler};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: usize = 20;
+
+/// Measures the time from opening a window to its first frame, which is
+/// dominated by round trips to the display server on remote connections.
+///
+/// Uses `harness = false` because some OSes require the windows to be created
+/// on the main thread.
+fn main() {
+    let mut samples = (0..ITERATIONS)
+        .map(|_| time_to_first_frame())
+        .collect::<Vec<_>>();
+    samples.sort();
+
+    let total = samples.iter().sum::<Duration>();
+    println!(
+        "time to first frame ({ITERATIONS} windows): min {:?}, median {:?}, mean {:?}, max {:?}",
+        samples[0],
+        samples[ITERATIONS / 2],
+        total / ITERATIONS as u32,
+        samples[ITERATIONS - 1],
+    );
+}
+
+fn time_to_first_frame() -> Duration {
+    struct Handler<'a> {
+        window: Window<'a>,
+        start: Instant,
+        sender: mpsc::Sender<Duration>,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            let _ = self.sender.send(self.start.elapsed());
+            self.window.close();
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let start = Instant::now();
+
+    WindowBuilder::new(move |window| {
+        window.set_title("picoview bench - startup");
+        window.set_size((256, 256));
+        window.set_visible(true);
+
+        Ok(Box::new(Handler {
+            window,
+            start,
+            sender,
+        }))
+    })
+    .open_blocking()
+    .expect("failed to open window");
+
+    receiver
+        .recv()
+        .expect("window closed before the first frame")
+}
@@ -4,20 +4,73 @@ use std::time::Instant;
 
 use crate::GainPluginShared;
 use clack_plugin::plugin::PluginError;
-use picoview::rwh_06::{HasRawWindowHandle, WindowHandle};
-use picoview::{GlConfig, Window, WindowBuilder, WindowHandler, WindowWaker};
+use picoview::rwh_06::{HasRawWindowHandle, RawWindowHandle, WindowHandle};
+use picoview::{GlConfig, Window, WindowBuilder, WindowGroup, WindowHandler};
 
 #[derive(Default)]
 pub struct GainPluginGui {
-    window: Option<WindowWaker>,
+    group: WindowGroup,
+    is_floating: bool,
+    transient: Option<RawWindowHandle>,
 }
 
 impl GainPluginGui {
-    pub fn open(
+    pub fn create(&mut self, is_floating: bool) {
+        self.is_floating = is_floating;
+    }
+
+    pub fn set_parent(
         &mut self,
         _state: &GainPluginShared,
         parent: clack_extensions::gui::Window<'_>,
     ) -> Result<(), PluginError> {
+        let parent = parent
+            .raw_window_handle()
+            .map_err(|_| PluginError::Message("Invalid parent window"))?;
+
+        Self::builder(&self.group)
+            .open_embedded(unsafe { WindowHandle::borrow_raw(parent) })
+            .map_err(|_| PluginError::Message("Failed to open the embedded window"))?;
+
+        Ok(())
+    }
+
+    pub fn set_transient(&mut self, parent: clack_extensions::gui::Window<'_>) {
+        self.transient = parent.raw_window_handle().ok();
+    }
+
+    pub fn show(&mut self) -> Result<(), PluginError> {
+        // embedded windows are shown and hidden together with the host window
+        if !self.is_floating || !self.group.is_empty() {
+            return Ok(());
+        }
+
+        // picoview can only open non-blocking top-level windows on top of another
+        // window, so the host has to give us one first
+        let parent = self.transient.ok_or(PluginError::Message(
+            "Floating window requires a transient parent",
+        ))?;
+
+        Self::builder(&self.group)
+            .open_transient(unsafe { WindowHandle::borrow_raw(parent) })
+            .map_err(|_| PluginError::Message("Failed to open the floating window"))?;
+
+        Ok(())
+    }
+
+    pub fn hide(&mut self) {
+        // closing is the simplest way to hide, the window is reopened on show
+        if self.is_floating {
+            self.group.close_all();
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.group.close_all();
+        self.transient = None;
+    }
+
+    fn builder(group: &WindowGroup) -> WindowBuilder {
         WindowBuilder::new(|window| {
             window.set_title("Gain Plugin");
             window.set_size((400, 200));
@@ -29,16 +82,7 @@ impl GainPluginGui {
             }))
         })
         .with_opengl(GlConfig::default())
-        .open_embedded(unsafe { WindowHandle::borrow_raw(parent.raw_window_handle().unwrap()) })
-        .unwrap();
-
-        Ok(())
-    }
-
-    pub fn close(&mut self) {
-        if let Some(window) = self.window.take() {
-            window.wakeup().unwrap();
-        }
+        .with_group(group.clone())
     }
 }
 
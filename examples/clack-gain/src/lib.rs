@@ -156,7 +156,6 @@ impl<'a> PluginGuiImpl for GainPluginMainThread<'a> {
     fn is_api_supported(&mut self, configuration: clack_extensions::gui::GuiConfiguration) -> bool {
         configuration.api_type
             == GuiApiType::default_for_current_platform().expect("Unsupported platform")
-            && !configuration.is_floating
     }
 
     fn get_preferred_api(&'_ mut self) -> Option<clack_extensions::gui::GuiConfiguration<'_>> {
@@ -168,8 +167,9 @@ impl<'a> PluginGuiImpl for GainPluginMainThread<'a> {
 
     fn create(
         &mut self,
-        _configuration: clack_extensions::gui::GuiConfiguration,
+        configuration: clack_extensions::gui::GuiConfiguration,
     ) -> Result<(), PluginError> {
+        self.gui.create(configuration.is_floating);
         Ok(())
     }
 
@@ -193,19 +193,20 @@ impl<'a> PluginGuiImpl for GainPluginMainThread<'a> {
     }
 
     fn set_parent(&mut self, window: clack_extensions::gui::Window) -> Result<(), PluginError> {
-        self.gui.open(self.shared, window)?;
-        Ok(())
+        self.gui.set_parent(self.shared, window)
     }
 
-    fn set_transient(&mut self, _window: clack_extensions::gui::Window) -> Result<(), PluginError> {
+    fn set_transient(&mut self, window: clack_extensions::gui::Window) -> Result<(), PluginError> {
+        self.gui.set_transient(window);
         Ok(())
     }
 
     fn show(&mut self) -> Result<(), PluginError> {
-        Ok(())
+        self.gui.show()
     }
 
     fn hide(&mut self) -> Result<(), PluginError> {
+        self.gui.hide();
         Ok(())
     }
 }
@@ -0,0 +1,431 @@
+use egui::{DroppedFile, Event, HoveredFile, Pos2, RawInput, Rect, Vec2, ViewportId};
+use picoview::{
+    DropEffect, Exchange, Key, Modifiers, MouseButton, MouseCursor, Point, Size, Window,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Turns picoview events into an egui [`RawInput`] and applies the
+/// [`egui::PlatformOutput`] back onto the window.
+///
+/// picoview reports positions and sizes in physical pixels, egui works in
+/// points, so everything is divided by the window scale on the way in.
+pub struct EguiInput {
+    raw: RawInput,
+    start: Instant,
+    scale: f32,
+    size: Size,
+    modifiers: egui::Modifiers,
+    pointer: Option<Pos2>,
+    cursor: MouseCursor,
+    dragged: Vec<PathBuf>,
+}
+
+impl EguiInput {
+    pub fn new(window: Window, size: Size) -> Self {
+        Self {
+            raw: RawInput::default(),
+            start: Instant::now(),
+            scale: window.scale() as f32,
+            size,
+            modifiers: egui::Modifiers::NONE,
+            pointer: None,
+            cursor: MouseCursor::Default,
+            dragged: Vec::new(),
+        }
+    }
+
+    /// Size of the window in physical pixels.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Whether any events arrived since the last [`Self::take`].
+    pub fn has_events(&self) -> bool {
+        !self.raw.events.is_empty() || !self.raw.dropped_files.is_empty()
+    }
+
+    /// Take the input gathered since the last call for the next egui pass.
+    pub fn take(&mut self) -> RawInput {
+        let screen = Vec2::new(self.size.width as f32, self.size.height as f32) / self.scale;
+
+        let mut raw = std::mem::take(&mut self.raw);
+        raw.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, screen));
+        raw.time = Some(self.start.elapsed().as_secs_f64());
+        raw.hovered_files = self
+            .dragged
+            .iter()
+            .map(|path| HoveredFile {
+                path: Some(path.clone()),
+                mime: String::new(),
+            })
+            .collect();
+        raw.viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.scale);
+
+        self.raw.focused = raw.focused;
+        raw
+    }
+
+    pub fn size_changed(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    pub fn scale_changed(&mut self, scale: f64) {
+        self.scale = scale as f32;
+    }
+
+    pub fn focus_changed(&mut self, focus: bool) {
+        self.raw.focused = focus;
+        self.raw.events.push(Event::WindowFocused(focus));
+    }
+
+    pub fn key_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = map_modifiers(modifiers);
+        self.raw
+            .events
+            .push(Event::ModifiersChanged(self.modifiers));
+    }
+
+    pub fn mouse_move(&mut self, point: Point) {
+        let pos = self.to_pos(point);
+        self.pointer = Some(pos);
+        self.raw.events.push(Event::PointerMoved(pos));
+    }
+
+    pub fn mouse_leave(&mut self) {
+        self.pointer = None;
+        self.raw.events.push(Event::PointerGone);
+    }
+
+    pub fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        let button = match button {
+            MouseButton::Left => egui::PointerButton::Primary,
+            MouseButton::Right => egui::PointerButton::Secondary,
+            MouseButton::Middle => egui::PointerButton::Middle,
+            MouseButton::Back => egui::PointerButton::Extra1,
+            MouseButton::Forward => egui::PointerButton::Extra2,
+            _ => return,
+        };
+
+        let pos = self.to_pos(point);
+        self.pointer = Some(pos);
+        self.raw.events.push(Event::PointerButton {
+            pos,
+            button,
+            pressed,
+            modifiers: self.modifiers,
+        });
+    }
+
+    /// picoview scrolls in wheel notches, egui in lines or pages, so the
+    /// user's scroll settings decide which one a notch is.
+    pub fn mouse_scroll(&mut self, window: Window, x: f64, y: f64) {
+        let settings = window.scroll_settings();
+        let (unit, delta) = match settings.lines_per_notch {
+            Some(lines) => (
+                egui::MouseWheelUnit::Line,
+                Vec2::new(
+                    (x * settings.chars_per_notch as f64) as f32,
+                    (y * lines as f64) as f32,
+                ),
+            ),
+            None => (egui::MouseWheelUnit::Page, Vec2::new(x as f32, y as f32)),
+        };
+
+        self.raw.events.push(Event::MouseWheel {
+            unit,
+            delta,
+            phase: egui::TouchPhase::Move,
+            modifiers: self.modifiers,
+        });
+    }
+
+    pub fn gesture_zoom(&mut self, scale: f64) {
+        self.raw.events.push(Event::Zoom(scale as f32));
+    }
+
+    pub fn gesture_rotate(&mut self, angle: f64) {
+        self.raw.events.push(Event::Rotate(angle as f32));
+    }
+
+    /// Clipboard shortcuts are turned into egui's copy, cut and paste events
+    /// here, egui does not read the clipboard itself.
+    pub fn key_press(
+        &mut self,
+        window: Window,
+        key: Key,
+        pressed: bool,
+        is_repeat: bool,
+        text: Option<char>,
+    ) {
+        if pressed && self.modifiers.command {
+            match key {
+                Key::C => return self.raw.events.push(Event::Copy),
+                Key::X => return self.raw.events.push(Event::Cut),
+                Key::V => {
+                    if let Ok(Exchange::Text(text)) = window.get_clipboard() {
+                        self.raw.events.push(Event::Paste(text));
+                    }
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(egui_key) = map_key(key) {
+            self.raw.events.push(Event::Key {
+                key: egui_key,
+                physical_key: Some(egui_key),
+                pressed,
+                repeat: is_repeat,
+                modifiers: self.modifiers,
+            });
+        }
+
+        // with Ctrl or Cmd held down the key is a shortcut, not text
+        if let Some(text) = text
+            && pressed
+            && !text.is_control()
+            && !self.modifiers.command
+            && !self.modifiers.ctrl
+        {
+            self.raw.events.push(Event::Text(text.to_string()));
+        }
+    }
+
+    /// Only lists of files are accepted, egui has no use for dropped text.
+    pub fn drag_enter(&mut self, data: Exchange, point: Point) -> DropEffect {
+        self.dragged = match data {
+            Exchange::Files(files) => files,
+            _ => Vec::new(),
+        };
+
+        self.drag_move(point)
+    }
+
+    pub fn drag_move(&mut self, point: Point) -> DropEffect {
+        if self.dragged.is_empty() {
+            return DropEffect::Reject;
+        }
+
+        self.mouse_move(point);
+        DropEffect::Copy
+    }
+
+    pub fn drag_leave(&mut self) {
+        self.dragged.clear();
+        self.mouse_leave();
+    }
+
+    pub fn drag_accept(&mut self) -> DropEffect {
+        if self.dragged.is_empty() {
+            return DropEffect::Reject;
+        }
+
+        self.raw.dropped_files.extend(
+            self.dragged
+                .drain(..)
+                .map(|path| Arc::new(NativeFile(path)) as egui::DroppedFileHandle),
+        );
+        DropEffect::Copy
+    }
+
+    /// Apply what egui wants from the platform: clipboard writes, opened links
+    /// and the cursor icon.
+    pub fn handle_output(&mut self, window: Window, output: egui::PlatformOutput) {
+        for command in output.commands {
+            match command {
+                egui::OutputCommand::CopyText(text) => {
+                    if let Err(error) = window.set_clipboard(text) {
+                        eprintln!("failed to copy text: {error}");
+                    }
+                }
+                egui::OutputCommand::OpenUrl(url) => {
+                    window.open_url(&url.url, |result| {
+                        if let Err(error) = result {
+                            eprintln!("failed to open url: {error}");
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // while the pointer is outside the window the cursor is not ours to set
+        let cursor = map_cursor(output.cursor_icon);
+        if self.pointer.is_some() && cursor != self.cursor {
+            self.cursor = cursor;
+            window.set_cursor_icon(cursor);
+        }
+    }
+
+    fn to_pos(&self, point: Point) -> Pos2 {
+        Pos2::new(point.x as f32, point.y as f32) / self.scale
+    }
+}
+
+/// A file dropped from the file manager, read from disk when egui asks.
+#[derive(Debug)]
+struct NativeFile(PathBuf);
+
+impl DroppedFile for NativeFile {
+    fn path(&self) -> &Path {
+        &self.0
+    }
+
+    fn bytes(&self) -> Result<Vec<u8>, String> {
+        std::fs::read(&self.0).map_err(|error| error.to_string())
+    }
+}
+
+/// picoview already reports Cmd as `ctrl` on macOS, which is what egui calls
+/// `command` there.
+fn map_modifiers(modifiers: Modifiers) -> egui::Modifiers {
+    egui::Modifiers {
+        alt: modifiers.alt,
+        ctrl: if cfg!(target_os = "macos") {
+            modifiers.meta
+        } else {
+            modifiers.ctrl
+        },
+        shift: modifiers.shift,
+        mac_cmd: cfg!(target_os = "macos") && modifiers.ctrl,
+        command: modifiers.ctrl,
+    }
+}
+
+fn map_key(key: Key) -> Option<egui::Key> {
+    use egui::Key as E;
+
+    Some(match key {
+        Key::Backquote => E::Backtick,
+        Key::Backslash => E::Backslash,
+        Key::BracketLeft => E::OpenBracket,
+        Key::BracketRight => E::CloseBracket,
+        Key::Comma | Key::NumpadComma => E::Comma,
+        Key::D0 | Key::Numpad0 => E::Num0,
+        Key::D1 | Key::Numpad1 => E::Num1,
+        Key::D2 | Key::Numpad2 => E::Num2,
+        Key::D3 | Key::Numpad3 => E::Num3,
+        Key::D4 | Key::Numpad4 => E::Num4,
+        Key::D5 | Key::Numpad5 => E::Num5,
+        Key::D6 | Key::Numpad6 => E::Num6,
+        Key::D7 | Key::Numpad7 => E::Num7,
+        Key::D8 | Key::Numpad8 => E::Num8,
+        Key::D9 | Key::Numpad9 => E::Num9,
+        Key::A => E::A,
+        Key::B => E::B,
+        Key::C => E::C,
+        Key::D => E::D,
+        Key::E => E::E,
+        Key::F => E::F,
+        Key::G => E::G,
+        Key::H => E::H,
+        Key::I => E::I,
+        Key::J => E::J,
+        Key::K => E::K,
+        Key::L => E::L,
+        Key::M => E::M,
+        Key::N => E::N,
+        Key::O => E::O,
+        Key::P => E::P,
+        Key::Q => E::Q,
+        Key::R => E::R,
+        Key::S => E::S,
+        Key::T => E::T,
+        Key::U => E::U,
+        Key::V => E::V,
+        Key::W => E::W,
+        Key::X => E::X,
+        Key::Y => E::Y,
+        Key::Z => E::Z,
+        Key::Equal | Key::NumpadEqual => E::Equals,
+        Key::Minus | Key::NumpadSubtract => E::Minus,
+        Key::NumpadAdd => E::Plus,
+        Key::Period | Key::NumpadDecimal => E::Period,
+        Key::Quote => E::Quote,
+        Key::Semicolon => E::Semicolon,
+        Key::Slash | Key::NumpadDivide => E::Slash,
+        Key::AltLeft => E::AltLeft,
+        Key::AltRight => E::AltRight,
+        Key::ControlLeft => E::ControlLeft,
+        Key::ControlRight => E::ControlRight,
+        Key::ShiftLeft => E::ShiftLeft,
+        Key::ShiftRight => E::ShiftRight,
+        Key::MetaLeft => E::SuperLeft,
+        Key::MetaRight => E::SuperRight,
+        Key::Backspace | Key::NumpadBackspace => E::Backspace,
+        Key::Enter | Key::NumpadEnter => E::Enter,
+        Key::Space => E::Space,
+        Key::Tab => E::Tab,
+        Key::Delete => E::Delete,
+        Key::End => E::End,
+        Key::Home => E::Home,
+        Key::Insert => E::Insert,
+        Key::PageDown => E::PageDown,
+        Key::PageUp => E::PageUp,
+        Key::ArrowDown => E::ArrowDown,
+        Key::ArrowLeft => E::ArrowLeft,
+        Key::ArrowRight => E::ArrowRight,
+        Key::ArrowUp => E::ArrowUp,
+        Key::Escape => E::Escape,
+        Key::F1 => E::F1,
+        Key::F2 => E::F2,
+        Key::F3 => E::F3,
+        Key::F4 => E::F4,
+        Key::F5 => E::F5,
+        Key::F6 => E::F6,
+        Key::F7 => E::F7,
+        Key::F8 => E::F8,
+        Key::F9 => E::F9,
+        Key::F10 => E::F10,
+        Key::F11 => E::F11,
+        Key::F12 => E::F12,
+        _ => return None,
+    })
+}
+
+fn map_cursor(icon: egui::CursorIcon) -> MouseCursor {
+    use egui::CursorIcon as C;
+
+    match icon {
+        C::Default => MouseCursor::Default,
+        C::None => MouseCursor::Hidden,
+        C::ContextMenu | C::Help => MouseCursor::Help,
+        C::PointingHand => MouseCursor::Hand,
+        C::Progress => MouseCursor::PtrWorking,
+        C::Wait => MouseCursor::Working,
+        C::Cell => MouseCursor::Cell,
+        C::Crosshair => MouseCursor::Crosshair,
+        C::Text => MouseCursor::Text,
+        C::VerticalText => MouseCursor::VerticalText,
+        C::Alias => MouseCursor::Alias,
+        C::Copy => MouseCursor::Copy,
+        C::Move => MouseCursor::Move,
+        C::NoDrop => MouseCursor::PtrNotAllowed,
+        C::NotAllowed => MouseCursor::NotAllowed,
+        C::Grab => MouseCursor::Hand,
+        C::Grabbing => MouseCursor::HandGrabbing,
+        C::AllScroll => MouseCursor::AllScroll,
+        C::ResizeHorizontal => MouseCursor::EwResize,
+        C::ResizeNeSw => MouseCursor::NeswResize,
+        C::ResizeNwSe => MouseCursor::NwseResize,
+        C::ResizeVertical => MouseCursor::NsResize,
+        C::ResizeEast => MouseCursor::EResize,
+        C::ResizeSouthEast => MouseCursor::SeResize,
+        C::ResizeSouth => MouseCursor::SResize,
+        C::ResizeSouthWest => MouseCursor::SwResize,
+        C::ResizeWest => MouseCursor::WResize,
+        C::ResizeNorthWest => MouseCursor::NwResize,
+        C::ResizeNorth => MouseCursor::NResize,
+        C::ResizeNorthEast => MouseCursor::NeResize,
+        C::ResizeColumn => MouseCursor::ColResize,
+        C::ResizeRow => MouseCursor::RowResize,
+        C::ZoomIn => MouseCursor::ZoomIn,
+        C::ZoomOut => MouseCursor::ZoomOut,
+    }
+}
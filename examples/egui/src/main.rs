@@ -0,0 +1,239 @@
+mod input;
+
+use egui_glow::glow;
+use input::EguiInput;
+use picoview::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn main() {
+    WindowBuilder::new(|window| {
+        let size = Size {
+            width: 480,
+            height: 360,
+        };
+
+        window.set_title("egui Example");
+        window.set_size(size);
+        window.set_visible(true);
+
+        let opengl = window.opengl()?;
+        opengl.make_current(true)?;
+
+        // SAFETY: the context is current and outlives the painter, which is
+        // destroyed in `destroyed`
+        let gl = unsafe {
+            glow::Context::from_loader_function_cstr(|name| opengl.get_proc_address(name))
+        };
+        let painter = egui_glow::Painter::new(Arc::new(gl), "", None, false)?;
+
+        Ok(Box::new(Handler {
+            window,
+            opengl,
+            painter: Some(painter),
+            ctx: egui::Context::default(),
+            input: EguiInput::new(window, size),
+            repaint_at: Some(Instant::now()),
+            app: App::default(),
+        }))
+    })
+    // egui_glow can draw with either, older drivers only have the latter
+    .with_opengl_fallback_chain(
+        [GlVersion::Core(3, 2), GlVersion::Compat(2, 1)].map(|version| GlConfig {
+            version,
+            ..Default::default()
+        }),
+    )
+    .open_blocking()
+    .expect("failed to open a window");
+}
+
+struct Handler<'a> {
+    window: Window<'a>,
+    opengl: GlContext<'a>,
+    painter: Option<egui_glow::Painter>,
+    ctx: egui::Context,
+    input: EguiInput,
+    repaint_at: Option<Instant>,
+    app: App,
+}
+
+impl WindowHandler for Handler<'_> {
+    fn close_requested(&mut self) {
+        self.window.close();
+    }
+
+    fn destroyed(&mut self, _reason: DestroyReason) {
+        if let Some(mut painter) = self.painter.take() {
+            let _ = self.opengl.make_current(true);
+            painter.destroy();
+        }
+    }
+
+    fn frame(&mut self) {
+        // only run egui when something happened or it asked for a repaint,
+        // an idle window should not cost anything
+        let due = self.repaint_at.is_some_and(|at| at <= Instant::now());
+        if !due && !self.input.has_events() {
+            return;
+        }
+
+        let Some(painter) = &mut self.painter else {
+            return;
+        };
+
+        let output = self.ctx.run_ui(self.input.take(), |ui| self.app.ui(ui));
+        let delay = output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map_or(Duration::MAX, |viewport| viewport.repaint_delay);
+        self.repaint_at = Instant::now().checked_add(delay);
+        self.input
+            .handle_output(self.window, output.platform_output);
+
+        let size = self.input.size();
+        let size = [size.width, size.height];
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        let mut textures = output.textures_delta;
+
+        if self.opengl.make_current(true).is_err() {
+            return;
+        }
+
+        painter.clear(size, [0.1, 0.1, 0.1, 1.0]);
+        painter.paint_and_update_textures(
+            size,
+            output.pixels_per_point,
+            &primitives,
+            &mut textures,
+        );
+
+        let _ = self.opengl.swap_buffers();
+    }
+
+    fn size_changed(&mut self, size: Size) {
+        self.input.size_changed(size);
+        self.repaint_at = Some(Instant::now());
+    }
+
+    fn scale_changed(&mut self, scale: f64) {
+        self.input.scale_changed(scale);
+        self.repaint_at = Some(Instant::now());
+    }
+
+    fn focus_changed(&mut self, focus: bool) {
+        self.input.focus_changed(focus);
+    }
+
+    fn mouse_leave(&mut self) {
+        self.input.mouse_leave();
+    }
+
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        self.input.mouse_press(button, pressed, point);
+    }
+
+    fn mouse_move(&mut self, point: Point) {
+        self.input.mouse_move(point);
+    }
+
+    fn mouse_scroll(&mut self, x: f64, y: f64) {
+        self.input.mouse_scroll(self.window, x, y);
+    }
+
+    fn gesture_rotate(&mut self, angle: f64) {
+        self.input.gesture_rotate(angle);
+    }
+
+    fn gesture_zoom(&mut self, scale: f64) {
+        self.input.gesture_zoom(scale);
+    }
+
+    fn drag_enter(&mut self, data: Exchange, point: Point) -> DropEffect {
+        self.input.drag_enter(data, point)
+    }
+
+    fn drag_move(&mut self, point: Point) -> DropEffect {
+        self.input.drag_move(point)
+    }
+
+    fn drag_leave(&mut self) {
+        self.input.drag_leave();
+    }
+
+    fn drag_accept(&mut self) -> DropEffect {
+        self.input.drag_accept()
+    }
+
+    fn key_modifiers(&mut self, modifiers: Modifiers) {
+        self.input.key_modifiers(modifiers);
+    }
+
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        self.input
+            .key_press(self.window, key, pressed, is_repeat, text);
+
+        // leave the key to the host when no egui widget has keyboard focus,
+        // this matters when embedded in a plugin host
+        self.ctx.egui_wants_keyboard_input()
+    }
+}
+
+struct App {
+    name: String,
+    gain: f32,
+    bypass: bool,
+    clicks: u32,
+    dropped: Vec<String>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            name: "picoview".to_string(),
+            gain: 0.0,
+            bypass: false,
+            clicks: 0,
+            dropped: Vec::new(),
+        }
+    }
+}
+
+impl App {
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::CentralPanel::default().show(ui, |ui| {
+            ui.heading(format!("Hello, {}!", self.name));
+
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut self.name);
+            });
+
+            ui.add(egui::Slider::new(&mut self.gain, -60.0..=12.0).text("Gain (dB)"));
+            ui.checkbox(&mut self.bypass, "Bypass");
+
+            if ui
+                .button(format!("Clicked {} times", self.clicks))
+                .clicked()
+            {
+                self.clicks += 1;
+            }
+
+            ui.hyperlink_to("picoview on GitHub", "https://github.com/blepfx/picoview");
+
+            let dropped = ui.input(|input| input.raw.dropped_files.clone());
+            self.dropped
+                .extend(dropped.iter().map(|file| file.path().display().to_string()));
+            if ui.input(|input| !input.raw.hovered_files.is_empty()) {
+                ui.label("Drop the files to list them below");
+            }
+
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for path in &self.dropped {
+                    ui.label(path);
+                }
+            });
+        });
+    }
+}
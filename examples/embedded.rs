@@ -74,8 +74,8 @@ impl WindowHandler for Parent<'_> {
         println!("parent.mouse_leave()");
     }
 
-    fn key_press(&mut self, key: Key, pressed: bool) -> bool {
-        println!("parent.key_press({key:?}, {pressed})");
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        println!("parent.key_press({key:?}, {pressed}, {is_repeat}, {text:?})");
         false
     }
 }
@@ -93,8 +93,11 @@ impl WindowHandler for Child<'_> {
         println!("{}.visibility_changed({:?})", self.name, state);
     }
 
-    fn mouse_press(&mut self, button: picoview::MouseButton, pressed: bool) {
-        println!("{}.mouse_press({button:?}, {pressed})", self.name);
+    fn mouse_press(&mut self, button: picoview::MouseButton, pressed: bool, point: Point) {
+        println!(
+            "{}.mouse_press({button:?}, {pressed}, {point:?})",
+            self.name
+        );
     }
 
     fn mouse_move(&mut self, point: Point) {
@@ -106,9 +109,12 @@ impl WindowHandler for Child<'_> {
         println!("{}.mouse_leave()", self.name);
     }
 
-    fn key_press(&mut self, key: Key, pressed: bool) -> bool {
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
         let capture = key == Key::Enter || key == Key::Escape;
-        println!("{}.key_press({key:?}, {pressed}) -> {}", self.name, capture);
+        println!(
+            "{}.key_press({key:?}, {pressed}, {is_repeat}, {text:?}) -> {}",
+            self.name, capture
+        );
         capture
     }
 }
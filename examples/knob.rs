@@ -0,0 +1,339 @@
+//! A knob and a slider the way an audio plugin would implement them.
+//!
+//! - Drag up/down on the knob or left/right on the slider to change the value.
+//!   The cursor is hidden while dragging and warped back to where the drag
+//!   started on every move, so the drag never runs into the edge of the screen.
+//! - Hold Shift while dragging or scrolling to adjust in finer steps.
+//! - Ctrl-click (Command-click on macOS) resets a control to its default.
+//! - The scroll wheel changes the value of the control under the cursor.
+//!
+//! Also doubles as a manual test for mouse capture, cursor hiding and warping
+//! on each platform: releasing a drag outside of the window must show the
+//! cursor again, and the value must not jump when the drag starts.
+
+use picoview::*;
+use std::f32::consts::PI;
+use std::mem::transmute;
+
+/// How much the value changes per logical pixel dragged.
+const DRAG_SPEED: f64 = 1.0 / 200.0;
+/// How much slower dragging is with Shift held.
+const FINE_FACTOR: f64 = 0.1;
+/// How much the value changes per scroll step.
+const SCROLL_STEP: f64 = 0.05;
+
+fn main() {
+    WindowBuilder::new(|window| {
+        window.set_title("picoview test - knob");
+        window.set_size((
+            (320.0 * window.scale()) as u32,
+            (160.0 * window.scale()) as u32,
+        ));
+        window.set_visible(true);
+
+        Ok(Box::new(Handler {
+            window,
+            opengl: window.opengl()?,
+            controls: [
+                Control {
+                    kind: ControlKind::Knob,
+                    center: Point { x: 80.0, y: 80.0 },
+                    value: 0.5,
+                    default: 0.5,
+                },
+                Control {
+                    kind: ControlKind::Slider,
+                    center: Point { x: 220.0, y: 80.0 },
+                    value: 0.0,
+                    default: 0.0,
+                },
+            ],
+            modifiers: Modifiers::default(),
+            hovered: None,
+            drag: None,
+        }))
+    })
+    .with_opengl(GlConfig {
+        version: GlVersion::Compat(2, 1),
+        ..Default::default()
+    })
+    .open_blocking()
+    .expect("failed to open a window");
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ControlKind {
+    /// Round, dragged vertically
+    Knob,
+    /// Horizontal, dragged horizontally
+    Slider,
+}
+
+struct Control {
+    kind: ControlKind,
+    /// In logical pixels
+    center: Point,
+    /// Normalized to `0.0..=1.0`
+    value: f64,
+    default: f64,
+}
+
+impl Control {
+    const KNOB_RADIUS: f64 = 40.0;
+    const SLIDER_WIDTH: f64 = 120.0;
+    const SLIDER_HEIGHT: f64 = 16.0;
+
+    fn contains(&self, point: Point) -> bool {
+        let delta = point - self.center;
+        match self.kind {
+            ControlKind::Knob => delta.x.hypot(delta.y) <= Self::KNOB_RADIUS,
+            ControlKind::Slider => {
+                delta.x.abs() <= Self::SLIDER_WIDTH / 2.0
+                    && delta.y.abs() <= Self::SLIDER_HEIGHT / 2.0
+            }
+        }
+    }
+
+    /// How much the value changes for a drag of `delta` logical pixels.
+    fn drag_amount(&self, delta: Point) -> f64 {
+        match self.kind {
+            ControlKind::Knob => -delta.y * DRAG_SPEED,
+            ControlKind::Slider => delta.x * DRAG_SPEED,
+        }
+    }
+
+    fn adjust(&mut self, amount: f64) {
+        self.value = (self.value + amount).clamp(0.0, 1.0);
+    }
+}
+
+/// An ongoing drag of a control.
+struct Drag {
+    control: usize,
+    /// Where the drag started, in physical pixels. The cursor is warped back
+    /// here after every move.
+    anchor: Point,
+}
+
+struct Handler<'a> {
+    window: Window<'a>,
+    opengl: GlContext<'a>,
+    controls: [Control; 2],
+    modifiers: Modifiers,
+    hovered: Option<usize>,
+    drag: Option<Drag>,
+}
+
+impl Handler<'_> {
+    fn to_logical(&self, point: Point) -> Point {
+        let scale = self.window.scale();
+        Point {
+            x: point.x / scale,
+            y: point.y / scale,
+        }
+    }
+
+    fn control_at(&self, point: Point) -> Option<usize> {
+        let point = self.to_logical(point);
+        self.controls.iter().position(|c| c.contains(point))
+    }
+
+    fn speed(&self) -> f64 {
+        if self.modifiers.shift {
+            FINE_FACTOR
+        } else {
+            1.0
+        }
+    }
+
+    fn update_cursor(&self) {
+        self.window
+            .set_cursor_icon(match (&self.drag, self.hovered) {
+                (Some(_), _) => MouseCursor::Hidden,
+                (None, Some(_)) => MouseCursor::Hand,
+                (None, None) => MouseCursor::Default,
+            });
+    }
+}
+
+impl WindowHandler for Handler<'_> {
+    fn frame(&mut self) {
+        let gl = self.opengl;
+        let clear_color: unsafe extern "system" fn(f32, f32, f32, f32) =
+            unsafe { transmute(gl.get_proc_address(c"glClearColor")) };
+        let clear: unsafe extern "system" fn(i32) =
+            unsafe { transmute(gl.get_proc_address(c"glClear")) };
+        let begin: unsafe extern "system" fn(i32) =
+            unsafe { transmute(gl.get_proc_address(c"glBegin")) };
+        let end: unsafe extern "system" fn() = unsafe { transmute(gl.get_proc_address(c"glEnd")) };
+        let vertex: unsafe extern "system" fn(f32, f32) =
+            unsafe { transmute(gl.get_proc_address(c"glVertex2f")) };
+        let viewport: unsafe extern "system" fn(i32, i32, i32, i32) =
+            unsafe { transmute(gl.get_proc_address(c"glViewport")) };
+        let color: unsafe extern "system" fn(f32, f32, f32, f32) =
+            unsafe { transmute(gl.get_proc_address(c"glColor4f")) };
+
+        if gl.make_current(true).is_err() {
+            return;
+        }
+
+        // everything below is in logical pixels
+        let scale = self.window.scale();
+        let size = self.window.inner_size();
+        let width = (size.width as f64 / scale) as f32;
+        let height = (size.height as f64 / scale) as f32;
+
+        unsafe {
+            (clear_color)(0.15, 0.15, 0.17, 1.0);
+            (clear)(0x00004000); // GL_COLOR_BUFFER_BIT
+            (viewport)(0, 0, size.width as i32, size.height as i32);
+
+            let point = |x: f32, y: f32| (vertex)(x / width * 2.0 - 1.0, 1.0 - y / height * 2.0);
+            let rect = |left: f32, top: f32, right: f32, bottom: f32| {
+                (begin)(0x0007); // GL_QUADS
+                point(left, top);
+                point(right, top);
+                point(right, bottom);
+                point(left, bottom);
+                (end)();
+            };
+
+            for (index, control) in self.controls.iter().enumerate() {
+                let active = self.drag.as_ref().map(|d| d.control) == Some(index)
+                    || (self.drag.is_none() && self.hovered == Some(index));
+                let (cx, cy) = (control.center.x as f32, control.center.y as f32);
+                let value = control.value as f32;
+
+                match control.kind {
+                    ControlKind::Knob => {
+                        // the knob turns through 270 degrees, starting at the bottom left
+                        let radius = Control::KNOB_RADIUS as f32;
+                        let angle = |value: f32| PI * (0.75 + 1.5 * value);
+
+                        (color)(0.3, 0.3, 0.35, 1.0);
+                        (begin)(0x0002); // GL_LINE_LOOP
+                        for step in 0..64 {
+                            let a = step as f32 / 64.0 * 2.0 * PI;
+                            point(cx + radius * a.cos(), cy + radius * a.sin());
+                        }
+                        (end)();
+
+                        match active {
+                            true => (color)(1.0, 0.75, 0.3, 1.0),
+                            false => (color)(0.9, 0.6, 0.2, 1.0),
+                        }
+                        (begin)(0x0003); // GL_LINE_STRIP
+                        for step in 0..=64 {
+                            let a = angle(value * step as f32 / 64.0);
+                            point(cx + radius * a.cos(), cy + radius * a.sin());
+                        }
+                        (end)();
+
+                        (begin)(0x0001); // GL_LINES
+                        point(cx, cy);
+                        let a = angle(value);
+                        point(cx + radius * a.cos(), cy + radius * a.sin());
+                        (end)();
+                    }
+                    ControlKind::Slider => {
+                        let half_width = (Control::SLIDER_WIDTH / 2.0) as f32;
+                        let half_height = (Control::SLIDER_HEIGHT / 2.0) as f32;
+                        let (left, right) = (cx - half_width, cx + half_width);
+                        let (top, bottom) = (cy - half_height, cy + half_height);
+
+                        (color)(0.3, 0.3, 0.35, 1.0);
+                        rect(left, top, right, bottom);
+
+                        match active {
+                            true => (color)(1.0, 0.75, 0.3, 1.0),
+                            false => (color)(0.9, 0.6, 0.2, 1.0),
+                        }
+                        rect(left, top, left + (right - left) * value, bottom);
+                    }
+                }
+            }
+        }
+
+        let _ = gl.swap_buffers();
+        let _ = gl.make_current(false);
+    }
+
+    fn close_requested(&mut self) {
+        self.window.close();
+    }
+
+    fn key_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+
+    fn mouse_move(&mut self, point: Point) {
+        let Some(drag) = &self.drag else {
+            self.hovered = self.control_at(point);
+            self.update_cursor();
+            return;
+        };
+
+        // the warp below comes back as a move to the anchor, which is a no-op
+        let delta = self.to_logical(point - drag.anchor);
+        if delta.x == 0.0 && delta.y == 0.0 {
+            return;
+        }
+
+        let speed = self.speed();
+        let control = &mut self.controls[drag.control];
+        control.adjust(control.drag_amount(delta) * speed);
+        self.window.set_cursor_position(drag.anchor);
+    }
+
+    fn mouse_leave(&mut self) {
+        self.hovered = None;
+        self.update_cursor();
+    }
+
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        if button != MouseButton::Left {
+            return;
+        }
+
+        if pressed {
+            let Some(index) = self.control_at(point) else {
+                return;
+            };
+
+            if self.modifiers.ctrl {
+                let control = &mut self.controls[index];
+                control.value = control.default;
+                return;
+            }
+
+            self.drag = Some(Drag {
+                control: index,
+                anchor: point,
+            });
+        } else if self.drag.take().is_some() {
+            // the cursor is back where the drag started, which may not be over the control
+            // anymore if the window moved in the meantime
+            self.hovered = self.control_at(point);
+        }
+
+        self.update_cursor();
+    }
+
+    fn mouse_scroll(&mut self, _x: f64, y: f64) {
+        if self.drag.is_some() {
+            return;
+        }
+
+        let speed = self.speed();
+        if let Some(index) = self.hovered {
+            self.controls[index].adjust(y * SCROLL_STEP * speed);
+        }
+    }
+
+    fn focus_changed(&mut self, focus: bool) {
+        // a drag can not survive losing the focus to another application
+        if !focus && self.drag.take().is_some() {
+            self.update_cursor();
+        }
+    }
+}
@@ -140,15 +140,17 @@ impl<'a> WindowHandler for Handler<'a> {
         println!("mouse_leave()");
     }
 
-    fn mouse_press(&mut self, button: MouseButton, pressed: bool) {
-        println!("mouse_press({button:?}, {pressed})");
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        println!("mouse_press({button:?}, {pressed}, {point:?})");
 
         if button == MouseButton::Right && pressed {
             self.window.set_visible(false);
             self.window.set_size((500, 500));
             self.window.set_visible(true);
             println!("clipboard contents: {:?}", self.window.get_clipboard());
-            self.window.set_clipboard("Hello from picoview! 🦀");
+            if let Err(err) = self.window.set_clipboard("Hello from picoview! 🦀") {
+                println!("failed to set clipboard: {err}");
+            }
         }
     }
 
@@ -168,8 +170,8 @@ impl<'a> WindowHandler for Handler<'a> {
         println!("key_modifiers({modifiers:?})");
     }
 
-    fn key_press(&mut self, key: Key, pressed: bool) -> bool {
-        println!("key_press({key:?}, {pressed})");
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        println!("key_press({key:?}, {pressed}, {is_repeat}, {text:?})");
         false
     }
 
@@ -0,0 +1,47 @@
+use picoview::{Event, Point, Size, run_simple};
+
+fn main() {
+    let mut buffer = Vec::new();
+    let mut size = Size::default();
+    let mut cursor = Point::default();
+    let mut dirty = false;
+
+    run_simple(
+        "picoview test - pixels",
+        (400, 300),
+        move |event, window| {
+            match event {
+                Event::SizeChanged(new_size) => {
+                    size = new_size;
+                    buffer.resize(size.width as usize * size.height as usize, 0);
+                    dirty = true;
+                }
+                Event::MouseMove(point) => {
+                    cursor = point;
+                    dirty = true;
+                }
+                Event::Damage(_) => dirty = true,
+                Event::Frame if dirty => {
+                    // a gradient, with a light spot following the cursor
+                    for (index, pixel) in buffer.iter_mut().enumerate() {
+                        let x = (index % size.width as usize) as f64;
+                        let y = (index / size.width as usize) as f64;
+                        let distance = (x - cursor.x).hypot(y - cursor.y);
+                        let light = (255.0 - distance).max(0.0) as u32;
+
+                        let red = (x / size.width as f64 * 255.0) as u32;
+                        let blue = (y / size.height as f64 * 255.0) as u32;
+                        *pixel = 0xff000000 | (red << 16) | (light << 8) | blue;
+                    }
+
+                    window
+                        .present_pixels(&buffer, size, &[])
+                        .expect("failed to present pixels");
+                    dirty = false;
+                }
+                _ => {}
+            }
+        },
+    )
+    .expect("failed to open a window");
+}
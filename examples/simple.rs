@@ -0,0 +1,22 @@
+use picoview::{Event, MouseCursor, run_simple};
+
+fn main() {
+    run_simple(
+        "picoview test - simple",
+        (400, 200),
+        |event, window| match event {
+            Event::Frame => {}
+            Event::MouseMove(point) => {
+                if point.x < 200.0 {
+                    window.set_cursor_icon(MouseCursor::Crosshair);
+                } else {
+                    window.set_cursor_icon(MouseCursor::Default);
+                }
+            }
+            event => println!("{event:?}"),
+        },
+    )
+    .expect("failed to open a window");
+
+    println!("Exiting loop");
+}
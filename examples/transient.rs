@@ -1,4 +1,6 @@
-use picoview::{Key, MouseButton, MouseCursor, Point, Window, WindowBuilder, WindowHandler};
+use picoview::{
+    Key, MonitorSelector, MouseButton, MouseCursor, Point, Window, WindowBuilder, WindowHandler,
+};
 
 fn main() {
     WindowBuilder::new(|window| {
@@ -9,6 +11,7 @@ fn main() {
 
         let child = WindowBuilder::new(|window| {
             window.set_size((200, 200));
+            window.set_position(MonitorSelector::Current);
             window.set_visible(true);
 
             Ok(Box::new(Child { window }))
@@ -55,8 +58,8 @@ impl WindowHandler for Parent<'_> {
         }
     }
 
-    fn key_press(&mut self, key: Key, pressed: bool) -> bool {
-        println!("parent.key_press({key:?}, {pressed})");
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        println!("parent.key_press({key:?}, {pressed}, {is_repeat}, {text:?})");
         false
     }
 }
@@ -74,14 +77,14 @@ impl WindowHandler for Child<'_> {
         println!("child.focus_changed({focus})");
     }
 
-    fn mouse_press(&mut self, button: MouseButton, pressed: bool) {
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, _point: Point) {
         if button == MouseButton::Right && pressed {
             self.window.set_position((1000, 200));
         }
     }
 
-    fn key_press(&mut self, key: Key, pressed: bool) -> bool {
-        println!("child.key_press({key:?}, {pressed})");
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        println!("child.key_press({key:?}, {pressed}, {is_repeat}, {text:?})");
 
         if key == Key::Escape && pressed {
             self.window.close();
@@ -0,0 +1,74 @@
+use picoview::{Window, WindowBuilder, WindowHandler, WindowWaker};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// State shared between the "audio" thread and the window.
+struct Shared {
+    peak: AtomicU32,
+    running: AtomicBool,
+}
+
+fn main() {
+    let shared = Arc::new(Shared {
+        peak: AtomicU32::new(0),
+        running: AtomicBool::new(true),
+    });
+
+    WindowBuilder::new({
+        let shared = shared.clone();
+        move |window| {
+            window.set_title("picoview test - waker");
+            window.set_size((400, 200));
+            window.set_visible(true);
+
+            spawn_audio_thread(shared.clone(), window.waker());
+
+            Ok(Box::new(Handler { window, shared }))
+        }
+    })
+    .open_blocking()
+    .expect("failed to open a window");
+
+    shared.running.store(false, Ordering::Relaxed);
+    println!("Exiting loop");
+}
+
+/// Simulates an audio thread that processes a block every ~10ms and notifies
+/// the window about new meter values without allocating or locking.
+fn spawn_audio_thread(shared: Arc<Shared>, waker: WindowWaker) {
+    thread::spawn(move || {
+        let mut phase = 0.0f32;
+        while shared.running.load(Ordering::Relaxed) {
+            phase = (phase + 0.05) % std::f32::consts::TAU;
+            shared
+                .peak
+                .store(phase.sin().abs().to_bits(), Ordering::Relaxed);
+
+            // the window is gone, stop processing
+            if waker.wakeup().is_err() {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    });
+}
+
+struct Handler<'a> {
+    window: Window<'a>,
+    shared: Arc<Shared>,
+}
+
+impl WindowHandler for Handler<'_> {
+    fn close_requested(&mut self) {
+        self.window.close();
+    }
+
+    fn wakeup(&mut self) {
+        let peak = f32::from_bits(self.shared.peak.load(Ordering::Relaxed));
+        self.window
+            .set_title(&format!("picoview test - waker (peak {peak:.2})"));
+    }
+}
@@ -0,0 +1,133 @@
+use picoview::{Size, Window, WindowBuilder, WindowHandler};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+fn main() {
+    WindowBuilder::new(|window| {
+        window.set_title("wgpu Example");
+        window.set_size((600, 600));
+        window.set_visible(true);
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+
+        // SAFETY: the surface is stored in the handler, which is dropped before the
+        // window handles become invalid
+        let surface = unsafe {
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::from_display_and_window(
+                &window, &window,
+            )?)?
+        };
+
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))?;
+
+        let (device, queue) = block_on(adapter.request_device(&Default::default()))?;
+        let config = surface
+            .get_default_config(&adapter, 600, 600)
+            .ok_or("surface is not supported by the adapter")?;
+        surface.configure(&device, &config);
+
+        Ok(Box::new(Handler {
+            window,
+            surface,
+            device,
+            queue,
+            config,
+            frame: 0,
+        }))
+    })
+    .open_blocking()
+    .unwrap();
+}
+
+struct Handler<'a> {
+    window: Window<'a>,
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    frame: u64,
+}
+
+impl WindowHandler for Handler<'_> {
+    fn close_requested(&mut self) {
+        self.window.close();
+    }
+
+    fn frame(&mut self) {
+        let texture = match self.surface.get_current_texture() {
+            wgpu::CurrentSurfaceTexture::Success(texture)
+            | wgpu::CurrentSurfaceTexture::Suboptimal(texture) => texture,
+            wgpu::CurrentSurfaceTexture::Outdated | wgpu::CurrentSurfaceTexture::Lost => {
+                self.surface.configure(&self.device, &self.config);
+                return;
+            }
+            _ => return,
+        };
+
+        let view = texture.texture.create_view(&Default::default());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+
+        self.frame += 1;
+        let t = self.frame as f64 / 60.0;
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.5 + 0.5 * t.sin(),
+                        g: 0.5 + 0.5 * (t * 0.7).sin(),
+                        b: 0.5 + 0.5 * (t * 1.3).sin(),
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        self.queue.submit([encoder.finish()]);
+        self.queue.present(texture);
+    }
+
+    fn size_changed(&mut self, size: Size) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        // the native surface follows the window size, we only have to reconfigure
+        // the swapchain
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+    }
+}
+
+/// Minimal executor for the few futures wgpu hands out during setup, they
+/// resolve immediately on native backends.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+#ifndef PICOVIEW_H
+#define PICOVIEW_H
+
+/* Generated by cbindgen from src/capi.rs, see there for the documentation. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define PICOVIEW_MODIFIER_ALT (1 << 0)
+
+#define PICOVIEW_MODIFIER_CTRL (1 << 1)
+
+#define PICOVIEW_MODIFIER_META (1 << 2)
+
+#define PICOVIEW_MODIFIER_SHIFT (1 << 3)
+
+#define PICOVIEW_MODIFIER_SCROLL_LOCK (1 << 4)
+
+#define PICOVIEW_MODIFIER_NUM_LOCK (1 << 5)
+
+#define PICOVIEW_MODIFIER_CAPS_LOCK (1 << 6)
+
+#define PICOVIEW_MODIFIER_FN (1 << 7)
+
+typedef struct PicoviewBuilder PicoviewBuilder;
+
+typedef struct PicoviewWaker PicoviewWaker;
+
+typedef struct PicoviewWindow PicoviewWindow;
+
+typedef struct PicoviewCallbacks {
+  size_t size;
+  void (*frame)(void*, const PicoviewWindow*);
+  void (*wakeup)(void*, const PicoviewWindow*);
+  void (*close_requested)(void*, const PicoviewWindow*);
+  void (*focus_changed)(void*, const PicoviewWindow*, bool);
+  void (*size_changed)(void*, const PicoviewWindow*, uint32_t, uint32_t);
+  void (*scale_changed)(void*, const PicoviewWindow*, double);
+  void (*mouse_leave)(void*, const PicoviewWindow*);
+  void (*mouse_move)(void*, const PicoviewWindow*, double, double);
+  void (*mouse_press)(void*, const PicoviewWindow*, uint8_t, bool);
+  void (*mouse_scroll)(void*, const PicoviewWindow*, double, double);
+  void (*key_modifiers)(void*, const PicoviewWindow*, uint32_t);
+  bool (*key_press)(void*, const PicoviewWindow*, uint32_t, bool, uint32_t);
+  void (*destroy)(void*);
+  void (*mouse_press_at)(void*, const PicoviewWindow*, uint8_t, bool, double, double);
+  bool (*key_press_repeat)(void*, const PicoviewWindow*, uint32_t, bool, bool, uint32_t);
+} PicoviewCallbacks;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+PicoviewBuilder *picoview_builder_new(const PicoviewCallbacks *callbacks, void *user_data);
+
+void picoview_builder_free(PicoviewBuilder *builder);
+
+void picoview_builder_set_title(PicoviewBuilder *builder, const char *title);
+
+void picoview_builder_set_size(PicoviewBuilder *builder, uint32_t width, uint32_t height);
+
+void picoview_builder_set_visible(PicoviewBuilder *builder, bool visible);
+
+void picoview_builder_set_transparent(PicoviewBuilder *builder, bool transparent);
+
+PicoviewWaker *picoview_open_parented(PicoviewBuilder *builder, void *parent);
+
+void picoview_window_close(const PicoviewWindow *window);
+
+void picoview_window_set_title(const PicoviewWindow *window, const char *title);
+
+void picoview_window_set_size(const PicoviewWindow *window, uint32_t width, uint32_t height);
+
+void picoview_window_set_visible(const PicoviewWindow *window, bool visible);
+
+double picoview_window_scale(const PicoviewWindow *window);
+
+PicoviewWaker *picoview_window_waker(const PicoviewWindow *window);
+
+bool picoview_waker_wakeup(const PicoviewWaker *waker);
+
+bool picoview_waker_close(const PicoviewWaker *waker);
+
+void picoview_waker_free(PicoviewWaker *waker);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+/* Mouse buttons passed to PicoviewCallbacks.mouse_press and mouse_press_at */
+enum {
+  PICOVIEW_MOUSE_BUTTON_LEFT = 0,
+  PICOVIEW_MOUSE_BUTTON_RIGHT = 1,
+  PICOVIEW_MOUSE_BUTTON_MIDDLE = 2,
+  PICOVIEW_MOUSE_BUTTON_FORWARD = 3,
+  PICOVIEW_MOUSE_BUTTON_BACK = 4,
+};
+
+/* Keys passed to PicoviewCallbacks.key_press, in the declaration order of picoview::Key */
+enum {
+  PICOVIEW_KEY_BACKQUOTE = 0,
+  PICOVIEW_KEY_BACKSLASH = 1,
+  PICOVIEW_KEY_BRACKET_LEFT = 2,
+  PICOVIEW_KEY_BRACKET_RIGHT = 3,
+  PICOVIEW_KEY_COMMA = 4,
+  PICOVIEW_KEY_D0 = 5,
+  PICOVIEW_KEY_D1 = 6,
+  PICOVIEW_KEY_D2 = 7,
+  PICOVIEW_KEY_D3 = 8,
+  PICOVIEW_KEY_D4 = 9,
+  PICOVIEW_KEY_D5 = 10,
+  PICOVIEW_KEY_D6 = 11,
+  PICOVIEW_KEY_D7 = 12,
+  PICOVIEW_KEY_D8 = 13,
+  PICOVIEW_KEY_D9 = 14,
+  PICOVIEW_KEY_A = 15,
+  PICOVIEW_KEY_B = 16,
+  PICOVIEW_KEY_C = 17,
+  PICOVIEW_KEY_D = 18,
+  PICOVIEW_KEY_E = 19,
+  PICOVIEW_KEY_F = 20,
+  PICOVIEW_KEY_G = 21,
+  PICOVIEW_KEY_H = 22,
+  PICOVIEW_KEY_I = 23,
+  PICOVIEW_KEY_J = 24,
+  PICOVIEW_KEY_K = 25,
+  PICOVIEW_KEY_L = 26,
+  PICOVIEW_KEY_M = 27,
+  PICOVIEW_KEY_N = 28,
+  PICOVIEW_KEY_O = 29,
+  PICOVIEW_KEY_P = 30,
+  PICOVIEW_KEY_Q = 31,
+  PICOVIEW_KEY_R = 32,
+  PICOVIEW_KEY_S = 33,
+  PICOVIEW_KEY_T = 34,
+  PICOVIEW_KEY_U = 35,
+  PICOVIEW_KEY_V = 36,
+  PICOVIEW_KEY_W = 37,
+  PICOVIEW_KEY_X = 38,
+  PICOVIEW_KEY_Y = 39,
+  PICOVIEW_KEY_Z = 40,
+  PICOVIEW_KEY_EQUAL = 41,
+  PICOVIEW_KEY_MINUS = 42,
+  PICOVIEW_KEY_PERIOD = 43,
+  PICOVIEW_KEY_QUOTE = 44,
+  PICOVIEW_KEY_SEMICOLON = 45,
+  PICOVIEW_KEY_SLASH = 46,
+  PICOVIEW_KEY_ALT_LEFT = 47,
+  PICOVIEW_KEY_ALT_RIGHT = 48,
+  PICOVIEW_KEY_BACKSPACE = 49,
+  PICOVIEW_KEY_CAPS_LOCK = 50,
+  PICOVIEW_KEY_CONTEXT_MENU = 51,
+  PICOVIEW_KEY_CONTROL_LEFT = 52,
+  PICOVIEW_KEY_CONTROL_RIGHT = 53,
+  PICOVIEW_KEY_ENTER = 54,
+  PICOVIEW_KEY_META_LEFT = 55,
+  PICOVIEW_KEY_META_RIGHT = 56,
+  PICOVIEW_KEY_SHIFT_LEFT = 57,
+  PICOVIEW_KEY_SHIFT_RIGHT = 58,
+  PICOVIEW_KEY_SPACE = 59,
+  PICOVIEW_KEY_TAB = 60,
+  PICOVIEW_KEY_DELETE = 61,
+  PICOVIEW_KEY_END = 62,
+  PICOVIEW_KEY_HOME = 63,
+  PICOVIEW_KEY_INSERT = 64,
+  PICOVIEW_KEY_PAGE_DOWN = 65,
+  PICOVIEW_KEY_PAGE_UP = 66,
+  PICOVIEW_KEY_ARROW_DOWN = 67,
+  PICOVIEW_KEY_ARROW_LEFT = 68,
+  PICOVIEW_KEY_ARROW_RIGHT = 69,
+  PICOVIEW_KEY_ARROW_UP = 70,
+  PICOVIEW_KEY_NUM_LOCK = 71,
+  PICOVIEW_KEY_NUMPAD_0 = 72,
+  PICOVIEW_KEY_NUMPAD_1 = 73,
+  PICOVIEW_KEY_NUMPAD_2 = 74,
+  PICOVIEW_KEY_NUMPAD_3 = 75,
+  PICOVIEW_KEY_NUMPAD_4 = 76,
+  PICOVIEW_KEY_NUMPAD_5 = 77,
+  PICOVIEW_KEY_NUMPAD_6 = 78,
+  PICOVIEW_KEY_NUMPAD_7 = 79,
+  PICOVIEW_KEY_NUMPAD_8 = 80,
+  PICOVIEW_KEY_NUMPAD_9 = 81,
+  PICOVIEW_KEY_NUMPAD_ADD = 82,
+  PICOVIEW_KEY_NUMPAD_BACKSPACE = 83,
+  PICOVIEW_KEY_NUMPAD_CLEAR = 84,
+  PICOVIEW_KEY_NUMPAD_CLEAR_ENTRY = 85,
+  PICOVIEW_KEY_NUMPAD_COMMA = 86,
+  PICOVIEW_KEY_NUMPAD_DECIMAL = 87,
+  PICOVIEW_KEY_NUMPAD_DIVIDE = 88,
+  PICOVIEW_KEY_NUMPAD_ENTER = 89,
+  PICOVIEW_KEY_NUMPAD_EQUAL = 90,
+  PICOVIEW_KEY_NUMPAD_HASH = 91,
+  PICOVIEW_KEY_NUMPAD_MEMORY_ADD = 92,
+  PICOVIEW_KEY_NUMPAD_MEMORY_CLEAR = 93,
+  PICOVIEW_KEY_NUMPAD_MEMORY_RECALL = 94,
+  PICOVIEW_KEY_NUMPAD_MEMORY_STORE = 95,
+  PICOVIEW_KEY_NUMPAD_MEMORY_SUBTRACT = 96,
+  PICOVIEW_KEY_NUMPAD_MULTIPLY = 97,
+  PICOVIEW_KEY_NUMPAD_PAREN_LEFT = 98,
+  PICOVIEW_KEY_NUMPAD_PAREN_RIGHT = 99,
+  PICOVIEW_KEY_NUMPAD_STAR = 100,
+  PICOVIEW_KEY_NUMPAD_SUBTRACT = 101,
+  PICOVIEW_KEY_ESCAPE = 102,
+  PICOVIEW_KEY_FN = 103,
+  PICOVIEW_KEY_FN_LOCK = 104,
+  PICOVIEW_KEY_PRINT_SCREEN = 105,
+  PICOVIEW_KEY_SCROLL_LOCK = 106,
+  PICOVIEW_KEY_F1 = 107,
+  PICOVIEW_KEY_F2 = 108,
+  PICOVIEW_KEY_F3 = 109,
+  PICOVIEW_KEY_F4 = 110,
+  PICOVIEW_KEY_F5 = 111,
+  PICOVIEW_KEY_F6 = 112,
+  PICOVIEW_KEY_F7 = 113,
+  PICOVIEW_KEY_F8 = 114,
+  PICOVIEW_KEY_F9 = 115,
+  PICOVIEW_KEY_F10 = 116,
+  PICOVIEW_KEY_F11 = 117,
+  PICOVIEW_KEY_F12 = 118,
+};
+
+#endif  /* PICOVIEW_H */
@@ -0,0 +1,917 @@
+//! A C ABI for opening picoview windows from other languages.
+//!
+//! The matching header is `include/picoview.h`, regenerate it with
+//! `cbindgen --config cbindgen.toml --output include/picoview.h` after
+//! changing this module. Only additions are allowed, existing functions and
+//! struct layouts are never changed. New callbacks are appended to
+//! [`PicoviewCallbacks`], which starts with its size so that hosts built
+//! against an older header keep working.
+//!
+//! The flow mirrors the Rust API: configure a [`PicoviewBuilder`], open it
+//! with [`picoview_open_parented`] and receive events through the function
+//! pointers in [`PicoviewCallbacks`]. The returned [`PicoviewWaker`] can be
+//! used from any thread.
+
+use crate::*;
+use std::ffi::{CStr, c_char, c_void};
+
+/// Alt key is held down (Option key on Mac), see
+/// [`PicoviewCallbacks::key_modifiers`]
+pub const PICOVIEW_MODIFIER_ALT: u32 = 1 << 0;
+/// Control key is held down (Command key on Mac)
+pub const PICOVIEW_MODIFIER_CTRL: u32 = 1 << 1;
+/// Meta key is held down (Control key on Mac)
+pub const PICOVIEW_MODIFIER_META: u32 = 1 << 2;
+/// Shift key is held down
+pub const PICOVIEW_MODIFIER_SHIFT: u32 = 1 << 3;
+/// Scroll lock is active
+pub const PICOVIEW_MODIFIER_SCROLL_LOCK: u32 = 1 << 4;
+/// Num lock is active
+pub const PICOVIEW_MODIFIER_NUM_LOCK: u32 = 1 << 5;
+/// Caps lock is active
+pub const PICOVIEW_MODIFIER_CAPS_LOCK: u32 = 1 << 6;
+/// Fn key is held down (macOS only)
+pub const PICOVIEW_MODIFIER_FN: u32 = 1 << 7;
+
+/// Event callbacks of a window, all of them are optional (`NULL`).
+///
+/// Every callback receives the `user_data` passed to
+/// [`picoview_builder_new`] and the window the event is for. The window
+/// pointer is only valid for the duration of the callback.
+///
+/// See [`WindowHandler`] for the meaning of each event. Keys are passed as
+/// the declaration index of [`Key`] (`PICOVIEW_KEY_*` in the header), mouse
+/// buttons as [`MouseButton`] values, characters as Unicode scalar values (0
+/// if none) and modifiers as `PICOVIEW_MODIFIER_*` flags.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PicoviewCallbacks {
+    /// `sizeof(PicoviewCallbacks)` of the header the host was built with.
+    /// Callbacks past it are not read and stay `NULL`
+    pub size: usize,
+    /// See [`WindowHandler::frame`]
+    pub frame: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow)>,
+    /// See [`WindowHandler::wakeup`]
+    pub wakeup: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow)>,
+    /// See [`WindowHandler::close_requested`]
+    pub close_requested: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow)>,
+    /// See [`WindowHandler::focus_changed`]
+    pub focus_changed: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, bool)>,
+    /// See [`WindowHandler::size_changed`]
+    pub size_changed: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, u32, u32)>,
+    /// See [`WindowHandler::scale_changed`]
+    pub scale_changed: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, f64)>,
+    /// See [`WindowHandler::mouse_leave`]
+    pub mouse_leave: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow)>,
+    /// See [`WindowHandler::mouse_move`]
+    pub mouse_move: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, f64, f64)>,
+    /// See [`WindowHandler::mouse_press`]
+    pub mouse_press: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, u8, bool)>,
+    /// See [`WindowHandler::mouse_scroll`]
+    pub mouse_scroll: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, f64, f64)>,
+    /// See [`WindowHandler::key_modifiers`]
+    pub key_modifiers: Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, u32)>,
+    /// See [`WindowHandler::key_press`]
+    pub key_press:
+        Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, u32, bool, u32) -> bool>,
+    /// Called exactly once when `user_data` is no longer used: after the
+    /// window is closed, or when the builder is freed or fails to open
+    pub destroy: Option<unsafe extern "C" fn(*mut c_void)>,
+    /// See [`WindowHandler::mouse_press`], with the position of the button
+    /// event. Called instead of [`PicoviewCallbacks::mouse_press`] if set and
+    /// covered by `size`
+    pub mouse_press_at:
+        Option<unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, u8, bool, f64, f64)>,
+    /// See [`WindowHandler::key_press`], with `is_repeat` after `pressed`.
+    /// Called instead of [`PicoviewCallbacks::key_press`] if set and covered
+    /// by `size`
+    pub key_press_repeat: Option<
+        unsafe extern "C" fn(*mut c_void, *const PicoviewWindow, u32, bool, bool, u32) -> bool,
+    >,
+}
+
+impl Default for PicoviewCallbacks {
+    fn default() -> Self {
+        Self {
+            size: size_of::<Self>(),
+            frame: None,
+            wakeup: None,
+            close_requested: None,
+            focus_changed: None,
+            size_changed: None,
+            scale_changed: None,
+            mouse_leave: None,
+            mouse_move: None,
+            mouse_press: None,
+            mouse_scroll: None,
+            key_modifiers: None,
+            key_press: None,
+            destroy: None,
+            mouse_press_at: None,
+            key_press_repeat: None,
+        }
+    }
+}
+
+/// The size of the first version of [`PicoviewCallbacks`], which ended with
+/// `destroy`.
+const MIN_CALLBACKS_SIZE: usize =
+    std::mem::offset_of!(PicoviewCallbacks, destroy) + size_of::<Option<unsafe extern "C" fn()>>();
+
+/// An unopened window, see [`picoview_builder_new`].
+#[derive(Debug)]
+pub struct PicoviewBuilder {
+    callbacks: Callbacks,
+    title: String,
+    size: Size,
+    visible: bool,
+    transparent: bool,
+}
+
+/// An open window, only valid inside of a [`PicoviewCallbacks`] callback.
+#[derive(Debug)]
+#[allow(missing_copy_implementations)] // opaque, only used behind pointers
+pub struct PicoviewWindow {
+    _private: [u8; 0],
+}
+
+/// A thread-safe handle to an open window, see [`WindowWaker`].
+#[derive(Debug)]
+pub struct PicoviewWaker(WindowWaker);
+
+/// The callback table together with its user data, calls
+/// [`PicoviewCallbacks::destroy`] when dropped.
+#[derive(Debug)]
+struct Callbacks {
+    table: PicoviewCallbacks,
+    user_data: *mut c_void,
+}
+
+// SAFETY: the caller of `picoview_builder_new` promises that the callbacks can
+// be called from a thread other than the one that created them. On X11 an
+// embedded window opened outside of a running picoview loop gets its own event
+// loop thread, and every callback (`destroy` included) runs there
+unsafe impl Send for Callbacks {}
+
+impl Drop for Callbacks {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.table.destroy {
+            unsafe { destroy(self.user_data) }
+        }
+    }
+}
+
+struct Handler<'a> {
+    window: Window<'a>,
+    callbacks: Callbacks,
+}
+
+impl Handler<'_> {
+    fn window(&self) -> *const PicoviewWindow {
+        &self.window as *const Window<'_> as *const PicoviewWindow
+    }
+}
+
+impl WindowHandler for Handler<'_> {
+    fn frame(&mut self) {
+        if let Some(frame) = self.callbacks.table.frame {
+            unsafe { frame(self.callbacks.user_data, self.window()) }
+        }
+    }
+
+    fn wakeup(&mut self) {
+        if let Some(wakeup) = self.callbacks.table.wakeup {
+            unsafe { wakeup(self.callbacks.user_data, self.window()) }
+        }
+    }
+
+    fn close_requested(&mut self) {
+        match self.callbacks.table.close_requested {
+            Some(close_requested) => unsafe {
+                close_requested(self.callbacks.user_data, self.window())
+            },
+            None => self.window.close(),
+        }
+    }
+
+    fn focus_changed(&mut self, focus: bool) {
+        if let Some(focus_changed) = self.callbacks.table.focus_changed {
+            unsafe { focus_changed(self.callbacks.user_data, self.window(), focus) }
+        }
+    }
+
+    fn size_changed(&mut self, size: Size) {
+        if let Some(size_changed) = self.callbacks.table.size_changed {
+            unsafe {
+                size_changed(
+                    self.callbacks.user_data,
+                    self.window(),
+                    size.width,
+                    size.height,
+                )
+            }
+        }
+    }
+
+    fn scale_changed(&mut self, scale: f64) {
+        if let Some(scale_changed) = self.callbacks.table.scale_changed {
+            unsafe { scale_changed(self.callbacks.user_data, self.window(), scale) }
+        }
+    }
+
+    fn mouse_leave(&mut self) {
+        if let Some(mouse_leave) = self.callbacks.table.mouse_leave {
+            unsafe { mouse_leave(self.callbacks.user_data, self.window()) }
+        }
+    }
+
+    fn mouse_move(&mut self, point: Point) {
+        if let Some(mouse_move) = self.callbacks.table.mouse_move {
+            unsafe { mouse_move(self.callbacks.user_data, self.window(), point.x, point.y) }
+        }
+    }
+
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        if let Some(mouse_press_at) = self.callbacks.table.mouse_press_at {
+            unsafe {
+                mouse_press_at(
+                    self.callbacks.user_data,
+                    self.window(),
+                    button as u8,
+                    pressed,
+                    point.x,
+                    point.y,
+                )
+            }
+        } else if let Some(mouse_press) = self.callbacks.table.mouse_press {
+            unsafe {
+                mouse_press(
+                    self.callbacks.user_data,
+                    self.window(),
+                    button as u8,
+                    pressed,
+                )
+            }
+        }
+    }
+
+    fn mouse_scroll(&mut self, x: f64, y: f64) {
+        if let Some(mouse_scroll) = self.callbacks.table.mouse_scroll {
+            unsafe { mouse_scroll(self.callbacks.user_data, self.window(), x, y) }
+        }
+    }
+
+    fn key_modifiers(&mut self, modifiers: Modifiers) {
+        if let Some(key_modifiers) = self.callbacks.table.key_modifiers {
+            let flags = [
+                (modifiers.alt, PICOVIEW_MODIFIER_ALT),
+                (modifiers.ctrl, PICOVIEW_MODIFIER_CTRL),
+                (modifiers.meta, PICOVIEW_MODIFIER_META),
+                (modifiers.shift, PICOVIEW_MODIFIER_SHIFT),
+                (modifiers.scroll_lock, PICOVIEW_MODIFIER_SCROLL_LOCK),
+                (modifiers.num_lock, PICOVIEW_MODIFIER_NUM_LOCK),
+                (modifiers.caps_lock, PICOVIEW_MODIFIER_CAPS_LOCK),
+                (modifiers.fn_key, PICOVIEW_MODIFIER_FN),
+            ]
+            .into_iter()
+            .filter(|(set, _)| *set)
+            .fold(0, |flags, (_, flag)| flags | flag);
+
+            unsafe { key_modifiers(self.callbacks.user_data, self.window(), flags) }
+        }
+    }
+
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        if let Some(key_press_repeat) = self.callbacks.table.key_press_repeat {
+            unsafe {
+                key_press_repeat(
+                    self.callbacks.user_data,
+                    self.window(),
+                    key as u32,
+                    pressed,
+                    is_repeat,
+                    text.map_or(0, u32::from),
+                )
+            }
+        } else if let Some(key_press) = self.callbacks.table.key_press {
+            unsafe {
+                key_press(
+                    self.callbacks.user_data,
+                    self.window(),
+                    key as u32,
+                    pressed,
+                    text.map_or(0, u32::from),
+                )
+            }
+        } else {
+            false
+        }
+    }
+}
+
+/// Create a new [`PicoviewBuilder`] with the given callbacks. The window
+/// starts with a size of 0, invisible and not transparent.
+///
+/// Returns `NULL` if `callbacks` is `NULL` or its `size` is smaller than the
+/// first version of [`PicoviewCallbacks`].
+///
+/// # Safety
+/// `callbacks` must be `NULL` or point to a valid [`PicoviewCallbacks`] of
+/// at least `size` bytes, it is copied and does not need to outlive the call. The callbacks and
+/// `user_data` must be safe to use from another thread: on X11,
+/// [`picoview_open_parented`] runs the window on a thread of its own and all
+/// callbacks, `destroy` included, are called from there.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_builder_new(
+    callbacks: *const PicoviewCallbacks,
+    user_data: *mut c_void,
+) -> *mut PicoviewBuilder {
+    if callbacks.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let size = unsafe { (*callbacks).size };
+    if size < MIN_CALLBACKS_SIZE {
+        return std::ptr::null_mut();
+    }
+
+    // only the callbacks the host knows about, the rest stay `NULL`
+    let mut table = PicoviewCallbacks::default();
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            callbacks as *const u8,
+            &mut table as *mut PicoviewCallbacks as *mut u8,
+            size.min(size_of::<PicoviewCallbacks>()),
+        );
+    }
+
+    Box::into_raw(Box::new(PicoviewBuilder {
+        callbacks: Callbacks { table, user_data },
+        title: String::new(),
+        size: Size::default(),
+        visible: false,
+        transparent: false,
+    }))
+}
+
+/// Free a [`PicoviewBuilder`] that was not opened, calling
+/// [`PicoviewCallbacks::destroy`]. Does nothing if `builder` is `NULL`.
+///
+/// # Safety
+/// `builder` must be `NULL` or a builder returned by
+/// [`picoview_builder_new`] that was not freed or opened yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_builder_free(builder: *mut PicoviewBuilder) {
+    if !builder.is_null() {
+        drop(unsafe { Box::from_raw(builder) });
+    }
+}
+
+/// Set the initial title of the window. `title` is UTF-8, invalid sequences
+/// are replaced.
+///
+/// # Safety
+/// `builder` must be a valid builder, `title` a valid null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_builder_set_title(
+    builder: *mut PicoviewBuilder,
+    title: *const c_char,
+) {
+    unsafe {
+        (*builder).title = CStr::from_ptr(title).to_string_lossy().into_owned();
+    }
+}
+
+/// Set the initial size of the window in physical pixels.
+///
+/// # Safety
+/// `builder` must be a valid builder.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_builder_set_size(
+    builder: *mut PicoviewBuilder,
+    width: u32,
+    height: u32,
+) {
+    unsafe {
+        (*builder).size = Size { width, height };
+    }
+}
+
+/// Set whether the window is initially visible.
+///
+/// # Safety
+/// `builder` must be a valid builder.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_builder_set_visible(
+    builder: *mut PicoviewBuilder,
+    visible: bool,
+) {
+    unsafe {
+        (*builder).visible = visible;
+    }
+}
+
+/// See [`WindowBuilder::with_transparency`].
+///
+/// # Safety
+/// `builder` must be a valid builder.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_builder_set_transparent(
+    builder: *mut PicoviewBuilder,
+    transparent: bool,
+) {
+    unsafe {
+        (*builder).transparent = transparent;
+    }
+}
+
+/// Open the window embedded into `parent`, see
+/// [`WindowBuilder::open_embedded`]. The builder is consumed, even on failure.
+///
+/// `parent` is a `HWND` on Windows, an `NSView*` on macOS and an X11 `Window`
+/// id (cast to a pointer) on Linux.
+///
+/// Returns a waker for the window that must be freed with
+/// [`picoview_waker_free`], or `NULL` if the window failed to open.
+///
+/// # Safety
+/// `builder` must be a valid builder, `parent` a valid window of the current
+/// platform. Must be called from the thread that runs the event loop of
+/// `parent`. On X11 that loop is not picoview's, so the window gets an event
+/// loop thread of its own and the callbacks are called from that thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_open_parented(
+    builder: *mut PicoviewBuilder,
+    parent: *mut c_void,
+) -> *mut PicoviewWaker {
+    let builder = unsafe { Box::from_raw(builder) };
+    let Some(parent) = raw_parent_handle(parent) else {
+        return std::ptr::null_mut();
+    };
+
+    let PicoviewBuilder {
+        callbacks,
+        title,
+        size,
+        visible,
+        transparent,
+    } = *builder;
+
+    let result = WindowBuilder::new(move |window| {
+        window.set_title(&title);
+        window.set_size(size);
+        window.set_visible(visible);
+
+        Ok(Box::new(Handler { window, callbacks }))
+    })
+    .with_transparency(transparent)
+    .open_embedded(unsafe { rwh_06::WindowHandle::borrow_raw(parent) });
+
+    match result {
+        Ok(waker) => Box::into_raw(Box::new(PicoviewWaker(waker))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+fn raw_parent_handle(parent: *mut c_void) -> Option<rwh_06::RawWindowHandle> {
+    #[cfg(target_os = "windows")]
+    {
+        std::num::NonZeroIsize::new(parent as isize)
+            .map(|hwnd| rwh_06::Win32WindowHandle::new(hwnd).into())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::ptr::NonNull::new(parent).map(|view| rwh_06::AppKitWindowHandle::new(view).into())
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let window = parent as usize as std::ffi::c_ulong;
+        (window != 0).then(|| rwh_06::XlibWindowHandle::new(window).into())
+    }
+}
+
+/// Close the window, see [`Window::close`].
+///
+/// # Safety
+/// `window` must be the window passed to the current callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_window_close(window: *const PicoviewWindow) {
+    unsafe { window_ref(window).close() }
+}
+
+/// Set the title of the window, see [`Window::set_title`].
+///
+/// # Safety
+/// `window` must be the window passed to the current callback, `title` a
+/// valid null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_window_set_title(
+    window: *const PicoviewWindow,
+    title: *const c_char,
+) {
+    unsafe { window_ref(window).set_title(&CStr::from_ptr(title).to_string_lossy()) }
+}
+
+/// Set the size of the window in physical pixels, see [`Window::set_size`].
+///
+/// # Safety
+/// `window` must be the window passed to the current callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_window_set_size(
+    window: *const PicoviewWindow,
+    width: u32,
+    height: u32,
+) {
+    unsafe { window_ref(window).set_size(Size { width, height }) }
+}
+
+/// Show or hide the window, see [`Window::set_visible`].
+///
+/// # Safety
+/// `window` must be the window passed to the current callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_window_set_visible(window: *const PicoviewWindow, visible: bool) {
+    unsafe { window_ref(window).set_visible(visible) }
+}
+
+/// Get the scale factor of the window, see [`Window::scale`].
+///
+/// # Safety
+/// `window` must be the window passed to the current callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_window_scale(window: *const PicoviewWindow) -> f64 {
+    unsafe { window_ref(window).scale() }
+}
+
+/// Get a new waker for the window, it must be freed with
+/// [`picoview_waker_free`].
+///
+/// # Safety
+/// `window` must be the window passed to the current callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_window_waker(
+    window: *const PicoviewWindow,
+) -> *mut PicoviewWaker {
+    let waker = unsafe { window_ref(window).waker() };
+    Box::into_raw(Box::new(PicoviewWaker(waker)))
+}
+
+unsafe fn window_ref<'a>(window: *const PicoviewWindow) -> &'a Window<'a> {
+    unsafe { &*(window as *const Window<'a>) }
+}
+
+/// Send a [`PicoviewCallbacks::wakeup`] to the window, see
+/// [`WindowWaker::wakeup`]. Can be called from any thread.
+///
+/// Returns `false` if the window is already closed.
+///
+/// # Safety
+/// `waker` must be a valid waker that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_waker_wakeup(waker: *const PicoviewWaker) -> bool {
+    unsafe { (*waker).0.wakeup().is_ok() }
+}
+
+/// Close the window from any thread. Like [`picoview_waker_wakeup`], this
+/// does not wait for the window to actually close.
+///
+/// Returns `false` if the window is already closed.
+///
+/// # Safety
+/// `waker` must be a valid waker that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_waker_close(waker: *const PicoviewWaker) -> bool {
+    unsafe { (*waker).0.0.close().is_ok() }
+}
+
+/// Free a waker. The window stays open. Does nothing if `waker` is `NULL`.
+///
+/// # Safety
+/// `waker` must be `NULL` or a valid waker that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn picoview_waker_free(waker: *mut PicoviewWaker) {
+    if !waker.is_null() {
+        drop(unsafe { Box::from_raw(waker) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    unsafe extern "C" fn count_destroy(user_data: *mut c_void) {
+        unsafe { (*(user_data as *const AtomicUsize)).fetch_add(1, Ordering::Relaxed) };
+    }
+
+    fn counting_callbacks() -> PicoviewCallbacks {
+        PicoviewCallbacks {
+            destroy: Some(count_destroy),
+            ..Default::default()
+        }
+    }
+
+    /// The identifiers right after every `marker` in `source` that are
+    /// followed by `end`, with `prefix` put in front.
+    fn identifiers(source: &str, marker: &str, end: char, prefix: &str) -> Vec<String> {
+        source
+            .match_indices(marker)
+            .filter_map(|(index, _)| {
+                let rest = source.get(index + marker.len()..)?;
+                let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+                rest.get(len..)?.starts_with(end).then_some(())?;
+                Some(format!("{prefix}{}", rest.get(..len)?))
+            })
+            .collect()
+    }
+
+    /// The part of `source` from `start` to the next closing brace.
+    fn section<'a>(source: &'a str, start: &str) -> &'a str {
+        let (_, rest) = source.split_once(start).unwrap_or_default();
+        rest.split_once('}').unwrap_or_default().0
+    }
+
+    #[test]
+    fn test_builder_round_trip() {
+        let destroyed = AtomicUsize::new(0);
+        let user_data = &destroyed as *const AtomicUsize as *mut c_void;
+
+        unsafe {
+            let builder = picoview_builder_new(&counting_callbacks(), user_data);
+            assert!(!builder.is_null());
+
+            picoview_builder_set_title(builder, c"title".as_ptr());
+            picoview_builder_set_size(builder, 320, 240);
+            picoview_builder_set_visible(builder, true);
+            picoview_builder_set_transparent(builder, true);
+            assert_eq!((*builder).title, "title");
+            assert_eq!(
+                (*builder).size,
+                Size {
+                    width: 320,
+                    height: 240
+                }
+            );
+            assert!((*builder).visible && (*builder).transparent);
+
+            picoview_builder_free(builder);
+            picoview_builder_free(std::ptr::null_mut());
+        }
+
+        assert_eq!(destroyed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_builder_without_callbacks() {
+        let builder = unsafe { picoview_builder_new(std::ptr::null(), std::ptr::null_mut()) };
+        assert!(builder.is_null());
+    }
+
+    #[test]
+    fn test_builder_callbacks_size() {
+        let destroyed = AtomicUsize::new(0);
+        let user_data = &destroyed as *const AtomicUsize as *mut c_void;
+
+        // from a header older than the first version
+        let table = PicoviewCallbacks {
+            size: MIN_CALLBACKS_SIZE - 1,
+            ..counting_callbacks()
+        };
+        assert!(unsafe { picoview_builder_new(&table, user_data) }.is_null());
+
+        // from a newer header, with callbacks this version does not know
+        let mut table = [0usize; size_of::<PicoviewCallbacks>() / size_of::<usize>() + 8];
+        unsafe {
+            let callbacks = table.as_mut_ptr() as *mut PicoviewCallbacks;
+            callbacks.write(counting_callbacks());
+            (*callbacks).size = size_of_val(&table);
+        }
+
+        let builder = unsafe { picoview_builder_new(table.as_ptr().cast(), user_data) };
+        assert!(!builder.is_null());
+        unsafe { picoview_builder_free(builder) };
+        assert_eq!(destroyed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_open_without_parent() {
+        let destroyed = AtomicUsize::new(0);
+        let user_data = &destroyed as *const AtomicUsize as *mut c_void;
+
+        unsafe {
+            let builder = picoview_builder_new(&counting_callbacks(), user_data);
+            let waker = picoview_open_parented(builder, std::ptr::null_mut());
+            assert!(waker.is_null());
+        }
+
+        // the builder was consumed, and the callbacks with it
+        assert_eq!(destroyed.load(Ordering::Relaxed), 1);
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn test_mouse_press_at() {
+        use std::sync::Mutex;
+
+        type Presses = Mutex<Vec<(u8, bool, Option<(f64, f64)>)>>;
+
+        unsafe extern "C" fn record_press(
+            user_data: *mut c_void,
+            _: *const PicoviewWindow,
+            button: u8,
+            pressed: bool,
+        ) {
+            let presses = unsafe { &*(user_data as *const Presses) };
+            if let Ok(mut presses) = presses.lock() {
+                presses.push((button, pressed, None));
+            }
+        }
+
+        unsafe extern "C" fn record_press_at(
+            user_data: *mut c_void,
+            _: *const PicoviewWindow,
+            button: u8,
+            pressed: bool,
+            x: f64,
+            y: f64,
+        ) {
+            let presses = unsafe { &*(user_data as *const Presses) };
+            if let Ok(mut presses) = presses.lock() {
+                presses.push((button, pressed, Some((x, y))));
+            }
+        }
+
+        let presses = Presses::default();
+        let user_data = &presses as *const Presses as *mut c_void;
+
+        let press = |table: PicoviewCallbacks| {
+            let builder = unsafe { Box::from_raw(picoview_builder_new(&table, user_data)) };
+            let callbacks = builder.callbacks;
+            let window =
+                WindowBuilder::new(move |window| Ok(Box::new(Handler { window, callbacks })))
+                    .open_headless()
+                    .expect("failed to open a headless window");
+
+            window.dispatch(Event::MousePress {
+                button: MouseButton::Right,
+                pressed: true,
+                position: Point { x: 12.0, y: 34.0 },
+            });
+        };
+
+        // the position is only passed on to the new callback, which takes
+        // precedence over the old one
+        press(PicoviewCallbacks {
+            mouse_press: Some(record_press),
+            ..Default::default()
+        });
+        press(PicoviewCallbacks {
+            mouse_press: Some(record_press),
+            mouse_press_at: Some(record_press_at),
+            ..Default::default()
+        });
+
+        // a host built before the new callback existed
+        press(PicoviewCallbacks {
+            size: std::mem::offset_of!(PicoviewCallbacks, mouse_press_at),
+            mouse_press: Some(record_press),
+            mouse_press_at: Some(record_press_at),
+            ..Default::default()
+        });
+
+        let right = MouseButton::Right as u8;
+        assert_eq!(
+            presses.into_inner().unwrap_or_default(),
+            [
+                (right, true, None),
+                (right, true, Some((12.0, 34.0))),
+                (right, true, None)
+            ],
+        );
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn test_key_press_repeat() {
+        use std::sync::Mutex;
+
+        type Presses = Mutex<Vec<(u32, bool, Option<bool>)>>;
+
+        unsafe extern "C" fn record_press(
+            user_data: *mut c_void,
+            _: *const PicoviewWindow,
+            key: u32,
+            pressed: bool,
+            _: u32,
+        ) -> bool {
+            let presses = unsafe { &*(user_data as *const Presses) };
+            if let Ok(mut presses) = presses.lock() {
+                presses.push((key, pressed, None));
+            }
+            false
+        }
+
+        unsafe extern "C" fn record_press_repeat(
+            user_data: *mut c_void,
+            _: *const PicoviewWindow,
+            key: u32,
+            pressed: bool,
+            is_repeat: bool,
+            _: u32,
+        ) -> bool {
+            let presses = unsafe { &*(user_data as *const Presses) };
+            if let Ok(mut presses) = presses.lock() {
+                presses.push((key, pressed, Some(is_repeat)));
+            }
+            true
+        }
+
+        let presses = Presses::default();
+        let user_data = &presses as *const Presses as *mut c_void;
+
+        let press = |table: PicoviewCallbacks| {
+            let builder = unsafe { Box::from_raw(picoview_builder_new(&table, user_data)) };
+            let callbacks = builder.callbacks;
+            let window =
+                WindowBuilder::new(move |window| Ok(Box::new(Handler { window, callbacks })))
+                    .open_headless()
+                    .expect("failed to open a headless window");
+
+            window.dispatch(Event::KeyPress {
+                key: Key::A,
+                pressed: true,
+                is_repeat: true,
+                text: Some('a'),
+            })
+        };
+
+        assert!(!press(PicoviewCallbacks {
+            key_press: Some(record_press),
+            ..Default::default()
+        }));
+        assert!(press(PicoviewCallbacks {
+            key_press: Some(record_press),
+            key_press_repeat: Some(record_press_repeat),
+            ..Default::default()
+        }));
+
+        // a host built before the new callback existed
+        assert!(!press(PicoviewCallbacks {
+            size: std::mem::offset_of!(PicoviewCallbacks, key_press_repeat),
+            key_press: Some(record_press),
+            key_press_repeat: Some(record_press_repeat),
+            ..Default::default()
+        }));
+
+        let a = Key::A as u32;
+        assert_eq!(
+            presses.into_inner().unwrap_or_default(),
+            [(a, true, None), (a, true, Some(true)), (a, true, None)],
+        );
+    }
+
+    #[test]
+    fn test_header_in_sync() {
+        let header = include_str!("../include/picoview.h");
+        let source = include_str!("capi.rs");
+
+        let declared = identifiers(header, "picoview_", '(', "picoview_");
+        let exported = identifiers(source, "extern \"C\" fn picoview_", '(', "picoview_");
+        assert!(!exported.is_empty());
+        assert_eq!(
+            declared.into_iter().collect::<BTreeSet<_>>(),
+            exported.into_iter().collect::<BTreeSet<_>>(),
+        );
+
+        // the callbacks are a struct, so their order matters too
+        let declared = identifiers(
+            section(header, "typedef struct PicoviewCallbacks {"),
+            "(*",
+            ')',
+            "",
+        );
+        let exported = identifiers(
+            section(source, "pub struct PicoviewCallbacks {"),
+            "pub ",
+            ':',
+            "",
+        );
+        assert_eq!(exported.first().map(String::as_str), Some("size"));
+        assert!(
+            section(header, "typedef struct PicoviewCallbacks {")
+                .trim_start()
+                .starts_with("size_t size;")
+        );
+        assert_eq!(declared, exported.get(1..).unwrap_or_default());
+
+        let declared = identifiers(header, "#define PICOVIEW_", ' ', "PICOVIEW_");
+        let exported = identifiers(source, "pub const PICOVIEW_", ':', "PICOVIEW_");
+        assert_eq!(
+            declared.into_iter().collect::<BTreeSet<_>>(),
+            exported.into_iter().collect::<BTreeSet<_>>(),
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::*;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Wraps a [`WindowHandler`] and holds back size and position changes until
+/// the next frame or other event, delivering only the latest of each, see
+/// [`WindowBuilder::with_geometry_compression`].
+pub(crate) struct GeometryCompression<'a> {
+    handler: Box<dyn WindowHandler + 'a>,
+    size: Option<Size>,
+    position: Option<Point>,
+}
+
+impl<'a> GeometryCompression<'a> {
+    pub(crate) fn new(handler: Box<dyn WindowHandler + 'a>) -> Self {
+        Self {
+            handler,
+            size: None,
+            position: None,
+        }
+    }
+
+    /// Deliver the changes held back so far, so the handler sees them before
+    /// any event that follows.
+    fn flush(&mut self) -> &mut dyn WindowHandler {
+        if let Some(size) = self.size.take() {
+            self.handler.size_changed(size);
+        }
+
+        if let Some(position) = self.position.take() {
+            self.handler.position_changed(position);
+        }
+
+        &mut *self.handler
+    }
+}
+
+impl WindowHandler for GeometryCompression<'_> {
+    fn size_changed(&mut self, size: Size) {
+        self.size = Some(size);
+    }
+
+    fn position_changed(&mut self, position: Point) {
+        self.position = Some(position);
+    }
+
+    // everything else delivers the held back changes first
+
+    fn frame(&mut self) {
+        self.flush().frame();
+    }
+
+    fn wakeup(&mut self) {
+        self.flush().wakeup();
+    }
+
+    fn idle(&mut self) {
+        self.flush().idle();
+    }
+
+    fn timer(&mut self, id: u32) {
+        self.flush().timer(id);
+    }
+
+    fn stalled(&mut self, duration: Duration) {
+        self.flush().stalled(duration);
+    }
+
+    fn frame_pacing_changed(&mut self, pacing: FramePacing) {
+        self.flush().frame_pacing_changed(pacing);
+    }
+
+    fn system_resumed(&mut self) {
+        self.flush().system_resumed();
+    }
+
+    fn system_metrics_changed(&mut self) {
+        self.flush().system_metrics_changed();
+    }
+
+    fn damage(&mut self, region: Rect) {
+        self.flush().damage(region);
+    }
+
+    fn close_requested(&mut self) {
+        self.flush().close_requested();
+    }
+
+    fn popup_dismissed(&mut self) {
+        self.flush().popup_dismissed();
+    }
+
+    fn destroyed(&mut self, reason: DestroyReason) {
+        self.flush().destroyed(reason);
+    }
+
+    fn focus_changed(&mut self, focus: bool) {
+        self.flush().focus_changed(focus);
+    }
+
+    fn scale_changed(&mut self, scale: f64) {
+        self.flush().scale_changed(scale);
+    }
+
+    fn monitor_changed(&mut self, monitor: MonitorId) {
+        self.flush().monitor_changed(monitor);
+    }
+
+    fn color_profile_changed(&mut self) {
+        self.flush().color_profile_changed();
+    }
+
+    fn visibility_changed(&mut self, state: WindowVisibility) {
+        self.flush().visibility_changed(state);
+    }
+
+    fn safe_area_changed(&mut self, insets: Insets) {
+        self.flush().safe_area_changed(insets);
+    }
+
+    fn mouse_leave(&mut self) {
+        self.flush().mouse_leave();
+    }
+
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        self.flush().mouse_press(button, pressed, point);
+    }
+
+    fn mouse_move(&mut self, point: Point) {
+        self.flush().mouse_move(point);
+    }
+
+    fn mouse_scroll(&mut self, x: f64, y: f64) {
+        self.flush().mouse_scroll(x, y);
+    }
+
+    fn gesture_rotate(&mut self, angle: f64) {
+        self.flush().gesture_rotate(angle);
+    }
+
+    fn gesture_zoom(&mut self, scale: f64) {
+        self.flush().gesture_zoom(scale);
+    }
+
+    fn pen_proximity(&mut self, entering: bool, inverted: bool) {
+        self.flush().pen_proximity(entering, inverted);
+    }
+
+    fn key_modifiers(&mut self, modifiers: Modifiers) {
+        self.flush().key_modifiers(modifiers);
+    }
+
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        self.flush().key_press(key, pressed, is_repeat, text)
+    }
+
+    fn composition_start(&mut self) {
+        self.flush().composition_start();
+    }
+
+    fn composition_update(&mut self, text: &str, cursor_range: Range<usize>) {
+        self.flush().composition_update(text, cursor_range);
+    }
+
+    fn composition_end(&mut self, text: Option<&str>) {
+        self.flush().composition_end(text);
+    }
+
+    fn drag_enter(&mut self, data: Exchange, point: Point) -> DropEffect {
+        self.flush().drag_enter(data, point)
+    }
+
+    fn drag_move(&mut self, point: Point) -> DropEffect {
+        self.flush().drag_move(point)
+    }
+
+    fn drag_leave(&mut self) {
+        self.flush().drag_leave();
+    }
+
+    fn drag_accept(&mut self) -> DropEffect {
+        self.flush().drag_accept()
+    }
+
+    fn drag_finished(&mut self, effect: DropEffect) {
+        self.flush().drag_finished(effect);
+    }
+}
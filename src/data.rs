@@ -1,4 +1,9 @@
+use std::num::NonZeroU64;
+use std::ops::{Add, Sub};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 #[allow(unused_imports)] // docs
 use crate::*;
@@ -13,6 +18,17 @@ pub struct Point {
     pub y: f64,
 }
 
+/// A fractional point in logical pixels (physical pixels divided by the scale
+/// factor, see [`Window::scale`]) with top-left origin
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct LogicalPoint {
+    /// The x coordinate
+    pub x: f64,
+
+    /// The y coordinate
+    pub y: f64,
+}
+
 /// A pixel-aligned size in physical pixels
 #[derive(Copy, Clone, Debug, PartialEq, Default)]
 pub struct Size {
@@ -23,6 +39,16 @@ pub struct Size {
     pub height: u32,
 }
 
+/// A fractional size in logical pixels, see [`LogicalPoint`]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct LogicalSize {
+    /// The width in logical pixels
+    pub width: f64,
+
+    /// The height in logical pixels
+    pub height: f64,
+}
+
 /// A pixel-aligned rectangle in physical pixels.
 #[derive(Copy, Clone, Debug, PartialEq, Default)]
 pub struct Rect {
@@ -52,18 +78,74 @@ impl Size {
     /// Create a new [`Size`] from logical pixels and a scale factor.
     #[must_use]
     #[inline]
-    pub fn from_logical(width: f64, height: f64, scale: f64) -> Self {
-        Self {
-            width: (width * scale).round() as u32,
-            height: (height * scale).round() as u32,
-        }
+    pub fn from_logical(size: impl Into<LogicalSize>, scale: f64) -> Self {
+        size.into().to_physical(scale)
     }
 
     /// Convert this [`Size`] to logical pixels using a scale factor.
     #[must_use]
     #[inline]
-    pub fn to_logical(&self, scale: f64) -> (f64, f64) {
-        (self.width as f64 / scale, self.height as f64 / scale)
+    pub fn to_logical(&self, scale: f64) -> LogicalSize {
+        LogicalSize {
+            width: self.width as f64 / scale,
+            height: self.height as f64 / scale,
+        }
+    }
+}
+
+impl LogicalSize {
+    /// Convert this [`LogicalSize`] to physical pixels using a scale factor,
+    /// rounding to the nearest pixel.
+    #[must_use]
+    #[inline]
+    pub fn to_physical(&self, scale: f64) -> Size {
+        Size {
+            width: (self.width * scale).round() as u32,
+            height: (self.height * scale).round() as u32,
+        }
+    }
+}
+
+impl Point {
+    /// Create a new [`Point`] from logical pixels and a scale factor.
+    #[must_use]
+    #[inline]
+    pub fn from_logical(point: impl Into<LogicalPoint>, scale: f64) -> Self {
+        point.into().to_physical(scale)
+    }
+
+    /// Convert this [`Point`] to logical pixels using a scale factor.
+    #[must_use]
+    #[inline]
+    pub fn to_logical(&self, scale: f64) -> LogicalPoint {
+        LogicalPoint {
+            x: self.x / scale,
+            y: self.y / scale,
+        }
+    }
+
+    /// Round this [`Point`] to the nearest whole pixel.
+    ///
+    /// Coordinates are signed: a point left of or above the origin is valid
+    /// (a monitor placed left of the primary one, or a captured mouse dragged
+    /// out of the window). Coordinates outside of the `i32` range saturate
+    /// instead of wrapping around, `NaN` becomes `0`.
+    #[must_use]
+    #[inline]
+    pub fn to_pixels(&self) -> (i32, i32) {
+        (self.x.round() as i32, self.y.round() as i32)
+    }
+}
+
+impl LogicalPoint {
+    /// Convert this [`LogicalPoint`] to physical pixels using a scale factor.
+    #[must_use]
+    #[inline]
+    pub fn to_physical(&self, scale: f64) -> Point {
+        Point {
+            x: self.x * scale,
+            y: self.y * scale,
+        }
     }
 }
 
@@ -93,13 +175,16 @@ impl Rect {
         }
     }
 
-    /// Size of the rectangle.
+    /// Size of the rectangle, zero along an axis where it is inverted.
     #[must_use]
     #[inline]
     pub fn size(&self) -> Size {
+        // the span of two i32 coordinates always fits in an u32, but not in an i32
+        let span = |start: i32, end: i32| if end > start { end.abs_diff(start) } else { 0 };
+
         Size {
-            width: (self.right - self.left).try_into().unwrap_or(0),
-            height: (self.bottom - self.top).try_into().unwrap_or(0),
+            width: span(self.left, self.right),
+            height: span(self.top, self.bottom),
         }
     }
 
@@ -113,6 +198,32 @@ impl Rect {
         }
     }
 
+    /// Smallest rectangle that contains both this and the other rectangle.
+    #[must_use]
+    #[inline]
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            top: self.top.min(other.top),
+            left: self.left.min(other.left),
+            bottom: self.bottom.max(other.bottom),
+            right: self.right.max(other.right),
+        }
+    }
+
+    /// Largest rectangle contained in both this and the other rectangle. If
+    /// they do not overlap, the result is inverted and its [`Self::size`] is
+    /// zero.
+    #[must_use]
+    #[inline]
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            top: self.top.max(other.top),
+            left: self.left.max(other.left),
+            bottom: self.bottom.min(other.bottom),
+            right: self.right.min(other.right),
+        }
+    }
+
     /// Offset the rectangle by the given amounts in the x and y directions.
     #[must_use]
     #[inline]
@@ -170,6 +281,496 @@ impl From<(f32, f32)> for Point {
     }
 }
 
+impl From<Point> for Position {
+    #[inline]
+    fn from(point: Point) -> Self {
+        Self::At(point)
+    }
+}
+
+impl From<(u32, u32)> for Position {
+    #[inline]
+    fn from(point: (u32, u32)) -> Self {
+        Self::At(point.into())
+    }
+}
+
+impl From<(i32, i32)> for Position {
+    #[inline]
+    fn from(point: (i32, i32)) -> Self {
+        Self::At(point.into())
+    }
+}
+
+impl From<(f64, f64)> for Position {
+    #[inline]
+    fn from(point: (f64, f64)) -> Self {
+        Self::At(point.into())
+    }
+}
+
+impl From<(f32, f32)> for Position {
+    #[inline]
+    fn from(point: (f32, f32)) -> Self {
+        Self::At(point.into())
+    }
+}
+
+impl From<MonitorSelector> for Position {
+    #[inline]
+    fn from(monitor: MonitorSelector) -> Self {
+        Self::Centered(monitor)
+    }
+}
+
+impl From<(f64, f64)> for LogicalPoint {
+    #[inline]
+    fn from((x, y): (f64, f64)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<(f64, f64)> for LogicalSize {
+    #[inline]
+    fn from((width, height): (f64, f64)) -> Self {
+        Self { width, height }
+    }
+}
+
+impl From<LogicalSize> for (f64, f64) {
+    #[inline]
+    fn from(size: LogicalSize) -> Self {
+        (size.width, size.height)
+    }
+}
+
+impl Add for Point {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for Point {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl Add for LogicalPoint {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for LogicalPoint {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+/// Distances from the edges of a rectangle in physical pixels, see
+/// [`Window::safe_area_insets`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Insets {
+    /// The distance from the top edge
+    pub top: u32,
+    /// The distance from the left edge
+    pub left: u32,
+    /// The distance from the bottom edge
+    pub bottom: u32,
+    /// The distance from the right edge
+    pub right: u32,
+}
+
+/// Resize constraints of a window, mirroring the CLAP `clap_gui_resize_hints`
+/// so plugin adapters can answer host queries from a single source of truth.
+///
+/// See [`Window::set_resize_hints`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResizeHints {
+    /// Whether the user can change the width of the window
+    pub can_resize_horizontally: bool,
+
+    /// Whether the user can change the height of the window
+    pub can_resize_vertically: bool,
+
+    /// The width to height ratio to preserve when resizing, if any
+    pub aspect_ratio: Option<(u32, u32)>,
+
+    /// The size must be a multiple of this step on each axis, if any
+    pub step: Option<Size>,
+}
+
+impl ResizeHints {
+    /// Adjust a requested size so it satisfies the minimum and maximum size
+    /// and these hints (resize step and aspect ratio, in that order).
+    ///
+    /// The aspect ratio is applied last and always shrinks the size, so the
+    /// result might end up below `min`.
+    #[must_use]
+    pub fn constrain(&self, size: Size, min: Size, max: Size) -> Size {
+        let mut width = size.width.clamp(min.width, max.width.max(min.width));
+        let mut height = size.height.clamp(min.height, max.height.max(min.height));
+
+        if let Some(step) = self.step {
+            if step.width > 1 {
+                width = (width / step.width).max(1) * step.width;
+            }
+
+            if step.height > 1 {
+                height = (height / step.height).max(1) * step.height;
+            }
+        }
+
+        if let Some((ratio_width, ratio_height)) = self.aspect_ratio
+            && ratio_width > 0
+            && ratio_height > 0
+        {
+            let (ratio_width, ratio_height) = (ratio_width as u64, ratio_height as u64);
+            let width_for_height = height as u64 * ratio_width / ratio_height;
+            if width_for_height <= width as u64 {
+                width = width_for_height as u32;
+            } else {
+                height = (width as u64 * ratio_height / ratio_width) as u32;
+            }
+        }
+
+        Size { width, height }
+    }
+}
+
+impl Default for ResizeHints {
+    fn default() -> Self {
+        Self {
+            can_resize_horizontally: true,
+            can_resize_vertically: true,
+            aspect_ratio: None,
+            step: None,
+        }
+    }
+}
+
+/// How the mouse cursor is held by a window, see [`Window::set_cursor_grab`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CursorGrab {
+    /// The cursor moves freely
+    #[default]
+    None,
+    /// The cursor can not leave the client area of the window
+    Confined,
+    /// The cursor stays where it is, and the mouse reports a virtual position
+    /// that moves without bounds
+    Locked,
+}
+
+/// An edge or a corner of a window to resize it by, see
+/// [`Window::begin_drag_resize`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResizeEdge {
+    /// The top edge
+    Top,
+    /// The bottom edge
+    Bottom,
+    /// The left edge
+    Left,
+    /// The right edge
+    Right,
+    /// The top-left corner
+    TopLeft,
+    /// The top-right corner
+    TopRight,
+    /// The bottom-left corner
+    BottomLeft,
+    /// The bottom-right corner, where resize grips usually are
+    BottomRight,
+}
+
+/// The scroll wheel settings of the system, see [`Window::scroll_settings`].
+///
+/// [`WindowHandler::mouse_scroll`] reports wheel notches, multiply them with
+/// these to scroll text the way the user configured.
+///
+/// More settings can be added later, to make one (like in tests) start from
+/// [`ScrollSettings::default`] and change the fields.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ScrollSettings {
+    /// How many lines of text one notch of the wheel scrolls, or `None` if
+    /// the user set it to scroll a whole page instead
+    pub lines_per_notch: Option<u32>,
+
+    /// How many characters one notch of horizontal scrolling moves text
+    pub chars_per_notch: u32,
+
+    /// Whether the user turned on natural scrolling (content follows the
+    /// fingers). Only reported on macOS, where scroll events already have it
+    /// applied by the system.
+    pub natural: bool,
+}
+
+impl Default for ScrollSettings {
+    fn default() -> Self {
+        Self {
+            lines_per_notch: Some(3),
+            chars_per_notch: 3,
+            natural: false,
+        }
+    }
+}
+
+/// Diagnostics of how events and frames reached a window, see
+/// [`Window::frame_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FrameStats {
+    /// How much the time between [`WindowHandler::frame`] calls varies, see
+    /// [`Window::frame_jitter`]
+    pub jitter: Duration,
+
+    /// How many events were dropped because too many queued up while the
+    /// handler was busy. Mouse moves and size changes that a later one
+    /// reports again go first, then scrolls, gestures, timer ticks and
+    /// wakeups, see [`Window::frame_stats`].
+    pub dropped_events: u64,
+
+    /// How many events were merged into the next one of the same kind while
+    /// the handler was busy, like consecutive mouse moves
+    pub coalesced_events: u64,
+}
+
+/// Metrics of the native controls of the system, for GUIs that draw their
+/// own but want them to feel native, see [`system_metrics`].
+///
+/// Lengths are in logical pixels, multiply them with [`Window::scale`].
+/// Anything the system does not report keeps its [`Default`] value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SystemMetrics {
+    /// The width of a vertical scrollbar, and the height of a horizontal one
+    pub scrollbar_width: f64,
+
+    /// Whether scrollbars are drawn over the content and only show up while
+    /// scrolling. Only reported on macOS.
+    pub overlay_scrollbars: bool,
+
+    /// The longest time between two clicks that still counts as a double
+    /// click
+    pub double_click_time: Duration,
+
+    /// How long the text caret stays visible, and then hidden, while it
+    /// blinks. `None` if the user turned blinking off.
+    pub caret_blink_interval: Option<Duration>,
+
+    /// How far the mouse has to move with a button held before it starts a
+    /// drag
+    pub drag_threshold: f64,
+}
+
+impl Default for SystemMetrics {
+    fn default() -> Self {
+        Self {
+            scrollbar_width: 16.0,
+            overlay_scrollbars: false,
+            double_click_time: Duration::from_millis(500),
+            caret_blink_interval: Some(Duration::from_millis(530)),
+            drag_threshold: 4.0,
+        }
+    }
+}
+
+/// A scroll delta in the unit the platform reported it in, before it becomes
+/// a [`WindowHandler::mouse_scroll`] event.
+///
+/// The same turn of the wheel reports different amounts on every platform,
+/// so by default picoview normalizes them: [`Self::normalized`] turns every
+/// delta into wheel notches, without any acceleration. That way `1.0` means
+/// the same on every platform, and [`ScrollSettings`] says how far to scroll
+/// for it. [`WindowBuilder::with_raw_scroll`] opts out of this and reports
+/// [`Self::raw`] instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ScrollDelta {
+    /// Notches of the wheel, with fractions from high resolution wheels and
+    /// touchpads that pretend to be one. Windows and X11 report these, so they
+    /// are never changed.
+    Notches {
+        /// Horizontal notches
+        x: f64,
+        /// Vertical notches
+        y: f64,
+    },
+
+    /// Lines of a mouse wheel, as reported by macOS. A slow turn of the wheel
+    /// reports one line per notch, faster turns are accelerated to many lines
+    /// per notch.
+    Lines {
+        /// Horizontal lines
+        x: f64,
+        /// Vertical lines
+        y: f64,
+    },
+
+    /// Touchpad movement in logical pixels, as reported by macOS and the X11
+    /// touchpad gestures.
+    Pixels {
+        /// Horizontal movement
+        x: f64,
+        /// Vertical movement
+        y: f64,
+    },
+}
+
+impl ScrollDelta {
+    /// How far a touchpad has to move to scroll one notch: three lines of
+    /// 16 pixels, which is what one notch scrolls by default.
+    pub const PIXELS_PER_NOTCH: f64 = 48.0;
+
+    /// The delta in notches of the wheel, see [`WindowHandler::mouse_scroll`].
+    ///
+    /// Acceleration can only make a notch scroll further than a line, so
+    /// lines are capped at one notch each. Pixels are divided by
+    /// [`Self::PIXELS_PER_NOTCH`].
+    pub fn normalized(self) -> (f64, f64) {
+        match self {
+            Self::Notches { x, y } => (x, y),
+            Self::Lines { x, y } => (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0)),
+            Self::Pixels { x, y } => (x / Self::PIXELS_PER_NOTCH, y / Self::PIXELS_PER_NOTCH),
+        }
+    }
+
+    /// The delta as the platform reported it, in whatever unit that is.
+    pub fn raw(self) -> (f64, f64) {
+        match self {
+            Self::Notches { x, y } | Self::Lines { x, y } | Self::Pixels { x, y } => (x, y),
+        }
+    }
+
+    /// The delta to report, see [`WindowBuilder::with_raw_scroll`].
+    pub(crate) fn resolve(self, raw: bool) -> (f64, f64) {
+        match raw {
+            true => self.raw(),
+            false => self.normalized(),
+        }
+    }
+}
+
+/// Identifies the most recent native input event received by a window, for
+/// hosts and platform APIs that need the original event metadata (e.g.
+/// `XSetInputFocus` requires the X server time of the triggering event).
+///
+/// See [`Window::last_event_token`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct EventToken {
+    /// The native timestamp of the event in milliseconds. This is the X server
+    /// time on X11, `GetMessageTime` on Windows and the `NSEvent` timestamp
+    /// (time since system startup) on macOS.
+    pub time: u64,
+
+    /// The native serial number of the event. Only X11 has one, it is `0` on
+    /// other platforms.
+    pub serial: u64,
+}
+
+/// A process-unique identifier of a window, assigned when the window is opened
+/// and never reused.
+///
+/// See [`Window::id`] and [`window_at`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct WindowId(NonZeroU64);
+
+impl WindowId {
+    /// Allocate the next unused [`WindowId`]
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NonZeroU64::MIN.saturating_add(NEXT.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    /// Get the numeric value of the identifier, never zero.
+    #[must_use]
+    pub fn get(self) -> u64 {
+        self.0.get()
+    }
+}
+
+/// Identifies a monitor, see [`WindowHandler::monitor_changed`].
+///
+/// Holds the handle the platform uses for the monitor, so [`Self::get`] can be
+/// passed to platform APIs to query the refresh rate or color profile: an
+/// `HMONITOR` on Windows, a `CGDirectDisplayID` on macOS and a RandR CRTC on
+/// X11.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MonitorId(u64);
+
+impl MonitorId {
+    /// Wrap a platform handle, for example to send a
+    /// [`Event::MonitorChanged`] to a headless window in tests.
+    #[must_use]
+    pub fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    /// Get the platform handle of the monitor.
+    #[must_use]
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+/// An ICC color profile, see [`Window::color_profile`].
+///
+/// This is the raw profile as provided by the system. Parsing it and
+/// converting colors is left to the renderer, for example to a color
+/// management library or a shader generated from the profile.
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub struct IccProfile(Arc<[u8]>);
+
+impl IccProfile {
+    /// Wrap the bytes of an ICC profile, for example to give a headless
+    /// window a profile in tests. The data is not validated.
+    #[must_use]
+    pub fn new(data: impl Into<Arc<[u8]>>) -> Self {
+        Self(data.into())
+    }
+
+    /// The bytes of the profile.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for IccProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IccProfile")
+            .field("len", &self.0.len())
+            .finish_non_exhaustive()
+    }
+}
+
 /// The visibility state of a window.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[non_exhaustive]
@@ -185,6 +786,58 @@ pub enum WindowVisibility {
     Occluded,
 }
 
+/// How [`WindowHandler::frame`] events are paced, see
+/// [`WindowHandler::frame_pacing_changed`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FramePacing {
+    /// Frames follow the vertical blank of the display
+    VSync,
+    /// Frames come from a timer running at the refresh rate of the display,
+    /// because waiting for the vertical blank failed or did not block
+    Timer,
+}
+
+/// Where [`Window::set_position`] places the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Position {
+    /// At the given point, in physical pixels
+    At(Point),
+    /// Centered in the work area of a monitor (the part not covered by the
+    /// taskbar, dock or panels), or in the parent window if embedded
+    Centered(MonitorSelector),
+}
+
+/// The monitor a window is placed on, see [`Position::Centered`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MonitorSelector {
+    /// The monitor the window is on, or the monitor of the parent window for
+    /// transient windows
+    #[default]
+    Current,
+    /// The primary monitor
+    Primary,
+    /// The monitor under the mouse cursor
+    Cursor,
+}
+
+/// Why a window went away, see [`WindowHandler::destroyed`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DestroyReason {
+    /// The window was closed with [`Window::close`], a [`WindowWaker`] or a
+    /// [`WindowGroup`]
+    Closed,
+    /// The window was destroyed without being closed, usually together with
+    /// its parent window, or because the host released an embedded view
+    Destroyed,
+    /// The connection to the windowing system failed, the error is returned
+    /// by `WindowBuilder::open_blocking` for blocking windows
+    Failed,
+}
+
 /// A mouse button.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u8)]
@@ -272,9 +925,15 @@ pub struct Modifiers {
     pub num_lock: bool,
     /// Caps lock is active
     pub caps_lock: bool,
+    /// Fn key is held down. Only reported on macOS, elsewhere the keyboard
+    /// handles the key itself and the system never sees it
+    pub fn_key: bool,
 }
 
 /// A logical key of a keyboard.
+///
+/// With the `capi` feature the declaration order is part of the C ABI, new
+/// keys are only added at the end.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[allow(missing_docs)]
 #[non_exhaustive]
@@ -330,6 +989,8 @@ pub enum Key {
     AltRight,
     Backspace,
     CapsLock,
+    /// The context menu key (also called the application or menu key). It is
+    /// reported like any other key, opening the menu is up to the handler
     ContextMenu,
     ControlLeft,
     ControlRight,
@@ -382,7 +1043,12 @@ pub enum Key {
     NumpadStar,
     NumpadSubtract,
     Escape,
+    /// The Fn key. Only reported on macOS, where its state is also tracked in
+    /// [`Modifiers::fn_key`]. Other keyboards handle it without telling the
+    /// system
     Fn,
+    /// The Fn lock key. Not reported on any platform, keyboards handle it
+    /// without telling the system
     FnLock,
     PrintScreen,
     ScrollLock,
@@ -412,6 +1078,32 @@ pub enum Exchange {
 
     /// A list of files (for example, a list of files from a file explorer)
     Files(Vec<PathBuf>),
+
+    /// An image. Only supported by the clipboard
+    Image(Image),
+
+    /// Data in a format of the application (like a preset), only supported
+    /// by the clipboard. See [`ClipboardFormat::Custom`] for the format name
+    Custom {
+        /// The name of the format
+        format: String,
+        /// The data, as is
+        data: Vec<u8>,
+    },
+}
+
+impl Exchange {
+    /// The format of the data, `None` for [`Exchange::Empty`].
+    #[must_use]
+    pub fn format(&self) -> Option<ClipboardFormat> {
+        match self {
+            Exchange::Empty => None,
+            Exchange::Text(_) => Some(ClipboardFormat::Text),
+            Exchange::Files(_) => Some(ClipboardFormat::Files),
+            Exchange::Image(_) => Some(ClipboardFormat::Image),
+            Exchange::Custom { format, .. } => Some(ClipboardFormat::Custom(format.clone())),
+        }
+    }
 }
 
 impl From<String> for Exchange {
@@ -438,6 +1130,83 @@ impl From<&[PathBuf]> for Exchange {
     }
 }
 
+impl From<Image> for Exchange {
+    fn from(image: Image) -> Self {
+        Exchange::Image(image)
+    }
+}
+
+/// The format to read the clipboard in, see [`Window::get_clipboard_as`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ClipboardFormat {
+    /// Plain text, read as [`Exchange::Text`]
+    Text,
+
+    /// A list of files, read as [`Exchange::Files`]
+    Files,
+
+    /// An image, read as [`Exchange::Image`]. Images are exchanged as PNG, and
+    /// as a DIB on Windows and TIFF on macOS for applications that do not
+    /// know PNG
+    Image,
+
+    /// A format of the application, read as [`Exchange::Custom`].
+    ///
+    /// The name is handed to the system as is: it is the target (a MIME type)
+    /// on X11, the pasteboard type (a UTI) on macOS and the name of a
+    /// registered clipboard format on Windows. Any name without NULs works
+    /// for exchanging data between applications using the same name, like
+    /// `com.example.preset`. Data of other applications has to be read by the
+    /// name they use on that platform.
+    ///
+    /// Windows rounds the size of the data up, so it may end with padding
+    /// there.
+    Custom(String),
+}
+
+/// An image for the clipboard, see [`Exchange::Image`].
+#[derive(Clone, PartialEq)]
+pub struct Image {
+    size: Size,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Wrap pixels stored from the top row to the bottom one, 4 bytes per
+    /// pixel: red, green, blue and alpha (not premultiplied). `None` if the
+    /// size is empty or `pixels` does not hold exactly the pixels of the size.
+    #[must_use]
+    pub fn new(size: impl Into<Size>, pixels: Vec<u8>) -> Option<Self> {
+        let size = size.into();
+        let len = (size.width as usize)
+            .checked_mul(size.height as usize)
+            .and_then(|len| len.checked_mul(4))?;
+
+        (len != 0 && pixels.len() == len).then_some(Self { size, pixels })
+    }
+
+    /// The size in pixels.
+    #[must_use]
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// The pixels, as given to [`Image::new`].
+    #[must_use]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+impl std::fmt::Debug for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
 /// The effect a drag-and-drop operation is expected to have
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
@@ -454,3 +1223,73 @@ pub enum DropEffect {
     /// Operation accepted (generic).
     Generic,
 }
+
+/// Tweaks of how the native window is created, for hosts that probe it or
+/// need it to fit into their own window hierarchy, see
+/// [`WindowBuilder::with_platform_options`].
+///
+/// Each platform ignores the options of the others, but all of them are
+/// validated on every platform when the window is opened, failing with
+/// [`WindowError::InvalidOptions`]. Anything picoview manages itself (whether
+/// the window is a child, its visibility) can not be changed here.
+///
+/// More options can be added later, so start from
+/// [`PlatformOptions::default`] and change the fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PlatformOptions {
+    /// The name of the Win32 window class, instead of a unique generated one.
+    ///
+    /// Windows opened with the same name share the class, it is unregistered
+    /// with the last of them. At most 255 UTF-16 units, without NULs.
+    pub windows_class_name: Option<String>,
+
+    /// Extra `WS_*` styles of the Win32 window, on top of the ones picoview
+    /// sets. `WS_CHILD`, `WS_POPUP` and `WS_VISIBLE` are not allowed.
+    pub windows_style: u32,
+
+    /// Extra `WS_EX_*` extended styles of the Win32 window, like
+    /// `WS_EX_CONTROLPARENT` to take part in the tab order of the host's
+    /// dialog. `WS_EX_LAYERED` and `WS_EX_MDICHILD` are not allowed.
+    pub windows_ex_style: u32,
+
+    /// The `NSAutoresizingMaskOptions` of the `NSView`, instead of none, so it
+    /// follows the size of its superview. Only the six flexible margin and
+    /// size flags (`0x3f`) are allowed.
+    pub macos_autoresizing_mask: u64,
+}
+
+impl PlatformOptions {
+    /// `WS_CHILD | WS_POPUP | WS_VISIBLE`
+    const WINDOWS_STYLE_RESERVED: u32 = 0x4000_0000 | 0x8000_0000 | 0x1000_0000;
+    /// `WS_EX_LAYERED | WS_EX_MDICHILD`
+    const WINDOWS_EX_STYLE_RESERVED: u32 = 0x0008_0000 | 0x0000_0040;
+    /// `NSViewWidthSizable` and friends
+    const MACOS_AUTORESIZING_MASK: u64 = 0x3f;
+
+    /// Check that the options are within what the platforms allow, see
+    /// [`WindowError::InvalidOptions`].
+    pub(crate) fn validate(&self) -> Result<(), WindowError> {
+        let invalid = |message: &str| Err(WindowError::InvalidOptions(message.into()));
+
+        if let Some(name) = &self.windows_class_name
+            && (name.is_empty() || name.contains('\0') || name.encode_utf16().count() > 255)
+        {
+            return invalid("windows_class_name must be 1 to 255 UTF-16 units without NULs");
+        }
+
+        if self.windows_style & Self::WINDOWS_STYLE_RESERVED != 0 {
+            return invalid("windows_style must not contain WS_CHILD, WS_POPUP or WS_VISIBLE");
+        }
+
+        if self.windows_ex_style & Self::WINDOWS_EX_STYLE_RESERVED != 0 {
+            return invalid("windows_ex_style must not contain WS_EX_LAYERED or WS_EX_MDICHILD");
+        }
+
+        if self.macos_autoresizing_mask & !Self::MACOS_AUTORESIZING_MASK != 0 {
+            return invalid("macos_autoresizing_mask must only contain the flags in 0x3f");
+        }
+
+        Ok(())
+    }
+}
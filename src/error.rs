@@ -2,6 +2,7 @@ use std::error::Error;
 use std::fmt;
 
 /// An error that can occur when creating an OpenGL context.
+#[cfg(feature = "opengl")]
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum OpenGlError {
@@ -14,17 +15,32 @@ pub enum OpenGlError {
     /// Requested OpenGL version is not supported.
     VersionUnsupported,
 
+    /// The window already has an incompatible pixel format (set by the host,
+    /// for example) and no dedicated surface could be created instead.
+    PixelFormatInUse,
+
     /// A platform-specific error occurred.
     Platform(String),
 }
 
 /// An error that can occur when making an OpenGL context current or
 /// not-current.
-#[derive(Debug, Clone, Copy)]
+#[cfg(feature = "opengl")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
-pub struct MakeCurrentError;
+pub enum MakeCurrentError {
+    /// The context could not be made current right now, but may be later (for
+    /// example while the driver recovers from a device reset). Worth another
+    /// try on the next frame.
+    Transient,
+
+    /// The context or its surface is gone for good (for example because the
+    /// window was destroyed under it), retrying will not help.
+    Lost,
+}
 
 /// An error that can occur when swapping the OpenGL buffers.
+#[cfg(feature = "opengl")]
 #[derive(Debug, Clone, Copy)]
 #[non_exhaustive]
 pub struct SwapBuffersError;
@@ -41,6 +57,14 @@ pub enum WindowError {
 
     /// The parent window handle that was passed is invalid.
     InvalidParent,
+
+    /// The [`PlatformOptions`](crate::PlatformOptions) that were passed are
+    /// invalid, the message says which of them and why.
+    InvalidOptions(String),
+
+    /// The event loop of this thread is already running, see
+    /// [`EventLoop::run`](crate::EventLoop::run).
+    EventLoopRunning,
 }
 
 /// An error that can occur when waking up a event loop from another thread.
@@ -48,6 +72,119 @@ pub enum WindowError {
 #[non_exhaustive]
 pub struct WakeupError;
 
+/// An error that can occur when opening a URL, see
+/// [`Window::open_url`](crate::Window::open_url).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum OpenUrlError {
+    /// The URL is empty, contains control characters, has a malformed scheme,
+    /// or starts with `-` and could be mistaken for a command line option.
+    InvalidUrl,
+
+    /// No application is registered to open the URL.
+    NoHandler,
+
+    /// The URL is a file path that does not exist.
+    NotFound,
+
+    /// Opening URLs is not supported by this backend.
+    Unsupported,
+
+    /// A platform-specific error occurred.
+    Platform(String),
+}
+
+/// An error that can occur when accessing the system clipboard, see
+/// [`Window::get_clipboard`](crate::Window::get_clipboard).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ClipboardError {
+    /// The clipboard holds nothing.
+    Empty,
+
+    /// The clipboard holds data in no format picoview can read, or the data
+    /// can not be represented on the clipboard.
+    UnsupportedFormat,
+
+    /// Access to the clipboard was denied, because another application holds
+    /// it or because the host sandboxes it. Some hosts only grant access while
+    /// handling a user gesture, like a key or mouse press.
+    AccessDenied,
+
+    /// The window was closed, see
+    /// [`Window::is_closed`](crate::Window::is_closed).
+    Closed,
+
+    /// A platform-specific error occurred.
+    Platform(String),
+}
+
+/// An error that can occur when starting a drag, see
+/// [`Window::start_drag`](crate::Window::start_drag) and
+/// [`Window::begin_drag_resize`](crate::Window::begin_drag_resize).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum DragError {
+    /// There is nothing to drag: no text, or an empty list of files.
+    Empty,
+
+    /// No mouse button pressed inside of the window is held. The system only
+    /// lets a drag start from a press.
+    NoButtonHeld,
+
+    /// Another drag started by this window has not finished yet.
+    InProgress,
+
+    /// The window was closed, see
+    /// [`Window::is_closed`](crate::Window::is_closed).
+    Closed,
+
+    /// Dragging out of the window is not supported, either by the backend or
+    /// because the host keeps the thread from using drag and drop.
+    Unsupported,
+
+    /// A platform-specific error occurred.
+    Platform(String),
+}
+
+/// An error that can occur when grabbing the mouse cursor, see
+/// [`Window::set_cursor_grab`](crate::Window::set_cursor_grab).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum CursorGrabError {
+    /// The window is not visible, or another application holds the mouse
+    /// (X11 only).
+    Unavailable,
+
+    /// The window was closed, see
+    /// [`Window::is_closed`](crate::Window::is_closed).
+    Closed,
+}
+
+/// An error that can occur when presenting pixels, see
+/// [`Window::present_pixels`](crate::Window::present_pixels).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum PresentError {
+    /// The buffer holds fewer pixels than its size says.
+    BufferTooSmall,
+
+    /// The window has an OpenGL context, which owns what is shown in the
+    /// window.
+    OpenGlInUse,
+
+    /// Presenting pixels is not supported, either by the backend or by the
+    /// pixel format of the window.
+    Unsupported,
+
+    /// The window was closed, see
+    /// [`Window::is_closed`](crate::Window::is_closed).
+    Closed,
+
+    /// A platform-specific error occurred.
+    Platform(String),
+}
+
 impl Error for WindowError {}
 impl fmt::Display for WindowError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -55,6 +192,71 @@ impl fmt::Display for WindowError {
             WindowError::Factory(err) => write!(f, "{}", err),
             WindowError::Platform(err) => write!(f, "platform error: {}", err),
             WindowError::InvalidParent => write!(f, "invalid parent window handle"),
+            WindowError::InvalidOptions(err) => write!(f, "invalid platform options: {}", err),
+            WindowError::EventLoopRunning => write!(f, "event loop is already running"),
+        }
+    }
+}
+
+impl Error for OpenUrlError {}
+impl fmt::Display for OpenUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenUrlError::InvalidUrl => write!(f, "invalid url"),
+            OpenUrlError::NoHandler => write!(f, "no application can open the url"),
+            OpenUrlError::NotFound => write!(f, "file not found"),
+            OpenUrlError::Unsupported => write!(f, "opening urls is not supported"),
+            OpenUrlError::Platform(err) => write!(f, "failed to open url: {}", err),
+        }
+    }
+}
+
+impl Error for ClipboardError {}
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClipboardError::Empty => write!(f, "clipboard is empty"),
+            ClipboardError::UnsupportedFormat => write!(f, "unsupported clipboard format"),
+            ClipboardError::AccessDenied => write!(f, "clipboard access denied"),
+            ClipboardError::Closed => write!(f, "window is closed"),
+            ClipboardError::Platform(err) => write!(f, "failed to access clipboard: {}", err),
+        }
+    }
+}
+
+impl Error for DragError {}
+impl fmt::Display for DragError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DragError::Empty => write!(f, "nothing to drag"),
+            DragError::NoButtonHeld => write!(f, "no mouse button is held"),
+            DragError::InProgress => write!(f, "another drag is in progress"),
+            DragError::Closed => write!(f, "window is closed"),
+            DragError::Unsupported => write!(f, "dragging is not supported"),
+            DragError::Platform(err) => write!(f, "failed to start drag: {}", err),
+        }
+    }
+}
+
+impl Error for CursorGrabError {}
+impl fmt::Display for CursorGrabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CursorGrabError::Unavailable => write!(f, "the mouse can not be grabbed"),
+            CursorGrabError::Closed => write!(f, "window is closed"),
+        }
+    }
+}
+
+impl Error for PresentError {}
+impl fmt::Display for PresentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresentError::BufferTooSmall => write!(f, "pixel buffer is too small"),
+            PresentError::OpenGlInUse => write!(f, "window has an opengl context"),
+            PresentError::Closed => write!(f, "window is closed"),
+            PresentError::Unsupported => write!(f, "presenting pixels is not supported"),
+            PresentError::Platform(err) => write!(f, "failed to present pixels: {}", err),
         }
     }
 }
@@ -66,21 +268,32 @@ impl fmt::Display for WakeupError {
     }
 }
 
+#[cfg(feature = "opengl")]
 impl Error for SwapBuffersError {}
+#[cfg(feature = "opengl")]
 impl fmt::Display for SwapBuffersError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "failed to swap opengl buffers")
     }
 }
 
+#[cfg(feature = "opengl")]
 impl Error for MakeCurrentError {}
+#[cfg(feature = "opengl")]
 impl fmt::Display for MakeCurrentError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "failed to make opengl context current")
+        match self {
+            MakeCurrentError::Transient => {
+                write!(f, "opengl context is temporarily unable to become current")
+            }
+            MakeCurrentError::Lost => write!(f, "opengl context was lost"),
+        }
     }
 }
 
+#[cfg(feature = "opengl")]
 impl Error for OpenGlError {}
+#[cfg(feature = "opengl")]
 impl fmt::Display for OpenGlError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -91,6 +304,9 @@ impl fmt::Display for OpenGlError {
             OpenGlError::VersionUnsupported => {
                 write!(f, "requested opengl version is unsupported")
             }
+            OpenGlError::PixelFormatInUse => {
+                write!(f, "window already has an incompatible pixel format")
+            }
             OpenGlError::Platform(err) => write!(f, "failed to create opengl context: {}", err),
         }
     }
@@ -0,0 +1,78 @@
+use crate::*;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+/// Runs any number of top-level windows on the calling thread.
+///
+/// Open the windows with [`EventLoop::open`], then hand the thread over with
+/// [`EventLoop::run`]. Windows opened later from any handler, with
+/// [`EventLoop::open`], [`Window::open_child`] or [`Window::open_popup`], are
+/// run by the same loop.
+///
+/// ```no_run
+/// use picoview::{EventLoop, WindowBuilder};
+///
+/// let event_loop = EventLoop::new();
+/// for title in ["left", "right"] {
+///     event_loop
+///         .open(WindowBuilder::new(move |window| {
+///             window.set_title(title);
+///             window.set_visible(true);
+///             Ok(Box::new(()))
+///         }))
+///         .expect("failed to open a window");
+/// }
+///
+/// event_loop.run().expect("a window failed");
+/// ```
+///
+/// There is one event loop per thread, all `EventLoop`s created on a thread
+/// refer to it. On macOS the loop is the one of `NSApplication`, which only
+/// runs on the main thread.
+#[derive(Default)]
+#[allow(missing_copy_implementations)] // `run` takes it, so it is only run once
+pub struct EventLoop {
+    /// The windows are tied to the thread they were opened on.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl EventLoop {
+    /// Get the event loop of the calling thread.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a top-level window. It does not receive events until
+    /// [`EventLoop::run`] is called, unless the loop is already running.
+    ///
+    /// # Errors
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
+    /// - [`WindowError::Factory`] if the factory function returned an error.
+    /// - [`WindowError::Platform`] if a platform-specific error occurred.
+    pub fn open(&self, builder: WindowBuilder) -> Result<WindowWaker, WindowError> {
+        builder.platform_options.validate()?;
+
+        unsafe { platform::open_window(builder.prepare(), platform::OpenMode::TopLevel) }
+    }
+
+    /// Run the event loop until all of its windows are closed. Returns right
+    /// away if there are none.
+    ///
+    /// # Errors
+    /// - [`WindowError::EventLoopRunning`] if the loop already runs, when
+    ///   called from a window handler.
+    /// - [`WindowError::Platform`] if the loop can not run on this thread
+    ///   (macOS only runs it on the main thread), or if an error occurred during
+    ///   the lifetime of any of the windows (X11 only, when the connection to
+    ///   the server fails). The other windows keep running, the first error is
+    ///   returned once they are closed as well.
+    pub fn run(self) -> Result<(), WindowError> {
+        unsafe { platform::run_event_loop() }
+    }
+}
+
+impl Debug for EventLoop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventLoop").finish_non_exhaustive()
+    }
+}
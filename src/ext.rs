@@ -0,0 +1,101 @@
+use crate::*;
+use std::ffi::c_void;
+use std::ptr::null_mut;
+
+/// Keeps the extension traits implemented for [`Window`] only, so new methods
+/// can be added without breaking anyone.
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for crate::Window<'_> {}
+}
+
+/// Direct access to the Win32 objects behind a [`Window`].
+///
+/// These are stable escape hatches for the occasional host API that needs
+/// them. The handles are borrowed: picoview still owns the window, so do not
+/// destroy it, replace its window procedure or change its styles.
+#[cfg(target_os = "windows")]
+pub trait WindowExtWindows: sealed::Sealed {
+    /// The `HWND` of the window, or null if it is not backed by Win32 (like a
+    /// headless window).
+    fn hwnd(&self) -> *mut c_void;
+
+    /// The `HINSTANCE` the window class was registered with, or null.
+    fn hinstance(&self) -> *mut c_void;
+}
+
+#[cfg(target_os = "windows")]
+impl WindowExtWindows for Window<'_> {
+    fn hwnd(&self) -> *mut c_void {
+        match self.0.window_handle() {
+            rwh_06::RawWindowHandle::Win32(handle) => handle.hwnd.get() as *mut c_void,
+            _ => null_mut(),
+        }
+    }
+
+    fn hinstance(&self) -> *mut c_void {
+        match self.0.window_handle() {
+            rwh_06::RawWindowHandle::Win32(handle) => handle
+                .hinstance
+                .map_or(null_mut(), |h| h.get() as *mut c_void),
+            _ => null_mut(),
+        }
+    }
+}
+
+/// Direct access to the AppKit objects behind a [`Window`].
+///
+/// These are stable escape hatches for things picoview does not wrap, like
+/// attaching a tooltip or a context menu. The view is borrowed: picoview still
+/// owns it, so do not remove it from its superview or replace its layer.
+#[cfg(target_os = "macos")]
+pub trait WindowExtMacOS: sealed::Sealed {
+    /// The `NSView` picoview draws into and receives events with, or null if
+    /// the window is not backed by AppKit (like a headless
+    /// window).
+    fn ns_view(&self) -> *mut c_void;
+}
+
+#[cfg(target_os = "macos")]
+impl WindowExtMacOS for Window<'_> {
+    fn ns_view(&self) -> *mut c_void {
+        match self.0.window_handle() {
+            rwh_06::RawWindowHandle::AppKit(handle) => handle.ns_view.as_ptr(),
+            _ => null_mut(),
+        }
+    }
+}
+
+/// Direct access to the Xlib objects behind a [`Window`].
+///
+/// These are stable escape hatches for host APIs that need the window id. The
+/// window and the connection are borrowed: picoview still owns them, so do not
+/// destroy the window, close the display or change the selected events.
+#[cfg(target_os = "linux")]
+pub trait WindowExtX11: sealed::Sealed {
+    /// The XID of the window, or 0 if it is not backed by X11 (like a headless
+    /// window).
+    fn xid(&self) -> std::ffi::c_ulong;
+
+    /// The Xlib `Display` connection the window was created on, or null.
+    fn display(&self) -> *mut c_void;
+}
+
+#[cfg(target_os = "linux")]
+impl WindowExtX11 for Window<'_> {
+    fn xid(&self) -> std::ffi::c_ulong {
+        match self.0.window_handle() {
+            rwh_06::RawWindowHandle::Xlib(handle) => handle.window,
+            _ => 0,
+        }
+    }
+
+    fn display(&self) -> *mut c_void {
+        match self.0.display_handle() {
+            rwh_06::RawDisplayHandle::Xlib(handle) => {
+                handle.display.map_or(null_mut(), |d| d.as_ptr())
+            }
+            _ => null_mut(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::*;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+/// A set of windows that belong together, like the editor and the detached
+/// meters of a single plugin instance.
+///
+/// Windows join a group when opened with [`WindowBuilder::with_group`] and
+/// leave it when closed. The first window in the group is the leader:
+/// top-level windows opened later are owned by the leader's top-level window
+/// (owner `HWND` on Windows, child window on macOS, `WM_TRANSIENT_FOR` on
+/// X11), so they are minimized and restored together with it. On X11 all
+/// members also share the ICCCM window group of the leader.
+///
+/// `WindowGroup` is `Send + Sync` and cheap to clone, all clones refer to the
+/// same group.
+#[derive(Clone, Default)]
+pub struct WindowGroup(Arc<Mutex<Vec<WindowGroupMember>>>);
+
+struct WindowGroupMember {
+    /// The top-level window of the member, used as the owner for windows
+    /// opened after it
+    handle: rwh_06::RawWindowHandle,
+    waker: WindowWaker,
+}
+
+// SAFETY: the handle is only compared and passed to the OS, never dereferenced
+unsafe impl Send for WindowGroupMember {}
+
+impl WindowGroup {
+    /// Create a new empty [`WindowGroup`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of open windows in the group.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.members().len()
+    }
+
+    /// Check if there are no open windows in the group.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members().is_empty()
+    }
+
+    /// Get the wakers of all open windows in the group, in the order they were
+    /// opened (the leader first).
+    #[must_use]
+    pub fn wakers(&self) -> Vec<WindowWaker> {
+        self.members()
+            .iter()
+            .map(|member| member.waker.clone())
+            .collect()
+    }
+
+    /// Close all windows in the group, as if [`Window::close`] was called on
+    /// each of them. Useful when unloading a plugin instance.
+    ///
+    /// Like [`WindowWaker::wakeup`], this does not wait for the windows to
+    /// actually close.
+    pub fn close_all(&self) {
+        for waker in self.wakers() {
+            let _ = waker.0.close();
+        }
+    }
+
+    /// The top-level window of the group leader, if the group is not empty.
+    pub(crate) fn leader(&self) -> Option<rwh_06::RawWindowHandle> {
+        self.members().first().map(|member| member.handle)
+    }
+
+    /// Add an opened window to the group. `handle` must be its top-level
+    /// window (the host window for embedded windows).
+    pub(crate) fn insert(&self, handle: rwh_06::RawWindowHandle, waker: WindowWaker) {
+        self.members().push(WindowGroupMember { handle, waker });
+    }
+
+    /// Remove a closed window from the group, see [`WindowGroup::insert`].
+    pub(crate) fn remove(&self, waker: &WindowWaker) {
+        self.members()
+            .retain(|member| !Arc::ptr_eq(&member.waker.0, &waker.0));
+    }
+
+    fn members(&self) -> std::sync::MutexGuard<'_, Vec<WindowGroupMember>> {
+        // the members are always left in a valid state, ignore the poison
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Debug for WindowGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WindowGroup")
+            .field("len", &self.len())
+            .finish()
+    }
+}
@@ -1,6 +1,16 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![allow(clippy::identity_op)]
+// without a backend for the target only the public API is compiled, so the
+// internals are unused
+#![cfg_attr(
+    not(any(
+        all(target_os = "linux", feature = "x11"),
+        all(target_os = "windows", feature = "win32"),
+        all(target_os = "macos", feature = "appkit"),
+    )),
+    allow(dead_code, unused_imports)
+)]
 #![deny(clippy::unwrap_used, clippy::unimplemented, clippy::indexing_slicing)]
 #![warn(
     missing_docs,
@@ -14,15 +24,33 @@
     clippy::invalid_upcast_comparisons
 )]
 
+#[cfg(feature = "capi")]
+pub mod capi;
+mod compress;
 mod data;
 mod error;
+#[cfg(feature = "standalone")]
+mod event_loop;
+mod ext;
+mod group;
+#[cfg(feature = "opengl")]
 mod opengl;
 mod platform;
+mod scroll;
+mod simple;
 mod window;
 
 pub use data::*;
 pub use error::*;
+#[cfg(feature = "standalone")]
+pub use event_loop::*;
+pub use ext::*;
+pub use group::*;
+#[cfg(feature = "opengl")]
 pub use opengl::*;
+#[cfg(feature = "headless")]
+pub use platform::headless::HeadlessWindow;
+pub use simple::*;
 pub use window::*;
 
 pub use raw_window_handle as rwh_06;
@@ -110,6 +110,86 @@ impl Default for GlConfig {
     }
 }
 
+/// An OpenGL context owned by the host, see
+/// [`WindowBuilder::with_external_opengl`].
+///
+/// Some hosts hand the plugin a context that is already current and only
+/// expect draw calls from inside their own frame callback. Windows opened with
+/// an external context do not create one of their own, [`Window::opengl`]
+/// returns a [`GlContext`] that forwards to the callbacks given here instead.
+pub struct ExternalGlContext {
+    get_proc_address: Box<dyn Fn(&CStr) -> *const c_void + Send>,
+    make_current: Box<dyn Fn(bool) -> Result<(), MakeCurrentError> + Send>,
+    swap_buffers: Box<dyn Fn() -> Result<(), SwapBuffersError> + Send>,
+}
+
+impl ExternalGlContext {
+    /// Create an external context that loads functions with
+    /// `get_proc_address`.
+    ///
+    /// Making the context current and swapping the buffers do nothing unless
+    /// callbacks are set for them, the host usually takes care of both.
+    pub fn new(get_proc_address: impl Fn(&CStr) -> *const c_void + Send + 'static) -> Self {
+        Self {
+            get_proc_address: Box::new(get_proc_address),
+            make_current: Box::new(|_| Ok(())),
+            swap_buffers: Box::new(|| Ok(())),
+        }
+    }
+
+    /// Set the callback for [`GlContext::make_current`]
+    #[must_use]
+    pub fn with_make_current(
+        self,
+        make_current: impl Fn(bool) -> Result<(), MakeCurrentError> + Send + 'static,
+    ) -> Self {
+        Self {
+            make_current: Box::new(make_current),
+            ..self
+        }
+    }
+
+    /// Set the callback for [`GlContext::swap_buffers`]
+    #[must_use]
+    pub fn with_swap_buffers(
+        self,
+        swap_buffers: impl Fn() -> Result<(), SwapBuffersError> + Send + 'static,
+    ) -> Self {
+        Self {
+            swap_buffers: Box::new(swap_buffers),
+            ..self
+        }
+    }
+}
+
+impl platform::PlatformOpenGl for ExternalGlContext {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        (self.swap_buffers)()
+    }
+
+    fn make_current(&self, current: bool) -> Result<(), MakeCurrentError> {
+        (self.make_current)(current)
+    }
+
+    fn get_proc_address(&self, name: &CStr) -> *const c_void {
+        (self.get_proc_address)(name)
+    }
+
+    fn version(&self) -> Option<GlVersion> {
+        None
+    }
+}
+
+impl fmt::Debug for ExternalGlContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalGlContext").finish_non_exhaustive()
+    }
+}
+
+/// How many times [`GlContext::make_current`] tries before giving up on a
+/// transient failure.
+const MAKE_CURRENT_ATTEMPTS: usize = 3;
+
 /// OpenGL context belonging to a window
 #[derive(Clone, Copy)]
 pub struct GlContext<'a>(pub(crate) &'a dyn platform::PlatformOpenGl);
@@ -117,13 +197,26 @@ pub struct GlContext<'a>(pub(crate) &'a dyn platform::PlatformOpenGl);
 impl<'a> GlContext<'a> {
     /// Make this OpenGL context current or not current.
     ///
-    /// Does nothing if the context is already in the requested state.
+    /// Does nothing if the context is already in the requested state. A
+    /// [`MakeCurrentError::Transient`] failure is retried a few times before
+    /// it is returned.
     ///
-    /// # Errors    
+    /// # Errors
     ///
-    /// Returns [`MakeCurrentError`] if the context could not be made current.
+    /// Returns [`MakeCurrentError`] if the context could not be made current,
+    /// telling apart failures worth retrying on a later frame from a context
+    /// that is gone for good.
     pub fn make_current(&self, current: bool) -> Result<(), MakeCurrentError> {
-        self.0.make_current(current)
+        let mut attempts = 1;
+        loop {
+            match self.0.make_current(current) {
+                Err(MakeCurrentError::Transient) if attempts < MAKE_CURRENT_ATTEMPTS => {
+                    attempts += 1;
+                    std::thread::yield_now();
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Swap the front and back buffers if double buffering is enabled
@@ -133,6 +226,9 @@ impl<'a> GlContext<'a> {
     /// It might be a good idea to skip drawing if the window has a size of 0
     /// (some drivers do not handle this well).
     ///
+    /// Buffers are never swapped automatically, frames where nothing was drawn
+    /// can simply skip this call.
+    ///
     /// # Errors
     ///
     /// Returns [`SwapBuffersError`] if the buffers could not be swapped.
@@ -144,6 +240,16 @@ impl<'a> GlContext<'a> {
     pub fn get_proc_address(&self, name: &CStr) -> *const c_void {
         self.0.get_proc_address(name)
     }
+
+    /// Get the version of the [`GlConfig`] this context was created with, which
+    /// tells which entry of [`WindowBuilder::with_opengl_fallback_chain`] was
+    /// picked.
+    ///
+    /// This is the requested version, the actual one may differ as described
+    /// for [`GlConfig::version`]. `None` for an [`ExternalGlContext`].
+    pub fn version(&self) -> Option<GlVersion> {
+        self.0.version()
+    }
 }
 
 impl<'a> fmt::Debug for GlContext<'a> {
@@ -0,0 +1,941 @@
+#[cfg(feature = "opengl")]
+use super::PlatformOpenGl;
+use super::{
+    Damage, EventQueue, KeyState, OpenUrlCallback, PlatformWaker, PlatformWindow, QueueKind,
+    Timers, Watchdog, copy_pixels, filter_clipboard, report_reentrancy, validate_url,
+};
+use crate::*;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::ptr::NonNull;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+/// The interval between two [`WindowHandler::frame`] events on the virtual
+/// clock (60 Hz)
+const FRAME_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+
+/// Ids for the raw window handles, 0 is reserved by the web handle
+static NEXT_HANDLE_ID: AtomicU32 = AtomicU32::new(1);
+
+/// A window that exists only in memory, for testing [`WindowHandler`]s on
+/// machines without a display server.
+///
+/// Opened with [`WindowBuilder::open_headless`]. Nothing happens on its own:
+/// input is delivered with [`HeadlessWindow::dispatch`], while frames, timers
+/// and wakeups are delivered by moving the virtual clock with
+/// [`HeadlessWindow::advance`]. Whatever the handler sets through [`Window`]
+/// can be read back with the getters.
+///
+/// The raw window handle is a [`rwh_06::WebWindowHandle`] with a unique id,
+/// there is no surface to render into.
+pub struct HeadlessWindow(Box<WindowImpl>);
+
+impl HeadlessWindow {
+    pub(crate) fn open(options: WindowBuilder) -> Result<Self, WindowError> {
+        let window = Box::new(WindowImpl {
+            id: WindowId::next(),
+            handle_id: NEXT_HANDLE_ID.fetch_add(1, Ordering::Relaxed),
+            waker: Arc::new(WindowWakerImpl {
+                wakeup_pending: AtomicBool::new(false),
+                close_pending: AtomicBool::new(false),
+                is_closed: AtomicBool::new(false),
+            }),
+
+            event_deferred: EventQueue::new(),
+            event_handler: RefCell::new(None),
+            user_data: RefCell::new(None),
+            watchdog: Watchdog::new(options.watchdog),
+            group: options.group,
+            #[cfg(feature = "opengl")]
+            opengl: !options.opengl.is_empty(),
+            #[cfg(feature = "opengl")]
+            external_opengl: options.external_opengl,
+
+            now: Cell::new(Duration::ZERO),
+            next_frame: Cell::new(FRAME_INTERVAL),
+            timers: Timers::new(),
+            last_event_token: Cell::new(EventToken::default()),
+            buttons_held: Cell::new(0),
+            unmatched_mouse_release: options.unmatched_mouse_release,
+            keys_down: KeyState::new(),
+            redraw_pending: Cell::new(false),
+
+            title: RefCell::new(String::new()),
+            clipboard: RefCell::new(Exchange::Empty),
+            clipboard_denied: Cell::new(false),
+            drag: RefCell::new(None),
+            resize_edge: Cell::new(None),
+            surface: RefCell::new((Size::default(), Vec::new())),
+            cursor_icon: Cell::new(options.cursor),
+            cursor_position: Cell::new(None),
+            cursor_grab: Cell::new(CursorGrab::None),
+            focused: Cell::new(false),
+            ime_cursor_area: Cell::new(None),
+            decorations: Cell::new(true),
+            visible: Cell::new(false),
+            size: Cell::new(Size::default()),
+            min_size: Cell::new(Size::MIN),
+            max_size: Cell::new(Size::MAX),
+            resize_hints: Cell::new(ResizeHints::default()),
+            position: Cell::new(Point::default()),
+            safe_area: Cell::new(Insets::default()),
+            scale: Cell::new(1.0),
+            color_profile: RefCell::new(None),
+
+            is_closed: Cell::new(false),
+        });
+
+        // SAFETY: we erase the lifetime of our WindowImpl; it should be safe to do so
+        // because it is boxed (so it has a stable address) and the handler is dropped
+        // before the box is, see `WindowImpl::drop_handler_if_closed`
+        let handler = unsafe {
+            let this = &*(&*window as *const WindowImpl);
+            (options.factory)(Window(this)).map_err(WindowError::Factory)?
+        };
+
+        window.event_handler.replace(Some(handler));
+
+        if let Some(group) = &window.group {
+            group.insert(window.window_handle(), window.waker());
+        }
+
+        // deliver the events deferred by the factory, like the initial size
+        let window = Self(window);
+        window.0.event(|_| {});
+        Ok(window)
+    }
+
+    /// Deliver an event to the handler, as if it came from the OS.
+    ///
+    /// Events that describe the window state ([`Event::SizeChanged`],
+    /// [`Event::ScaleChanged`], [`Event::PositionChanged`],
+    /// [`Event::SafeAreaChanged`], [`Event::MouseMove`], [`Event::MousePress`],
+    /// [`Event::KeyPress`], [`Event::FocusChanged`] and
+    /// [`Event::DragFinished`]) also update it. Input events update
+    /// [`Window::last_event_token`] with the virtual time in milliseconds and
+    /// an increasing serial.
+    ///
+    /// Like on the real backends, the release of a button that was never
+    /// pressed is dropped unless
+    /// [`WindowBuilder::with_unmatched_mouse_release`] is set.
+    ///
+    /// [`Event::PopupDismissed`] only reaches the handler. Headless windows can
+    /// not be opened with [`WindowBuilder::open_popup`], so unlike a dismissed
+    /// popup they stay open.
+    ///
+    /// Returns the result of [`WindowHandler::key_press`] for
+    /// [`Event::KeyPress`], `false` otherwise. Does nothing if the window is
+    /// closed.
+    pub fn dispatch(&self, event: Event) -> bool {
+        let window = &*self.0;
+
+        match event {
+            Event::SizeChanged(size) => window.size.set(size),
+            Event::ScaleChanged(scale) => window.scale.set(scale),
+            Event::PositionChanged(point) => window.position.set(point),
+            Event::SafeAreaChanged(insets) => window.safe_area.set(insets),
+            Event::MouseMove(point) => window.cursor_position.set(Some(point)),
+            Event::MouseLeave => window.cursor_position.set(None),
+            Event::MousePress {
+                button,
+                pressed,
+                position,
+            } => {
+                window.cursor_position.set(Some(position));
+
+                let mask = 1 << button as u32;
+                let held = window.buttons_held.get();
+                window
+                    .buttons_held
+                    .set(if pressed { held | mask } else { held & !mask });
+
+                // a release of a button pressed somewhere else
+                if !pressed && held & mask == 0 && !window.unmatched_mouse_release {
+                    return false;
+                }
+            }
+            Event::KeyPress { key, pressed, .. } => window.keys_down.set(key, pressed),
+            Event::FocusChanged(true) => window.focused.set(true),
+            Event::FocusChanged(false) => {
+                window.focused.set(false);
+                window.keys_down.reset([]);
+                window.cursor_grab.set(CursorGrab::None);
+            }
+            Event::DragFinished(_) => drop(window.drag.take()),
+            _ => {}
+        }
+
+        if matches!(
+            event,
+            Event::MouseLeave
+                | Event::MousePress { .. }
+                | Event::MouseMove(_)
+                | Event::MouseScroll { .. }
+                | Event::GestureRotate(_)
+                | Event::GestureZoom(_)
+                | Event::PenProximity { .. }
+                | Event::KeyModifiers(_)
+                | Event::KeyPress { .. }
+        ) {
+            let token = window.last_event_token.get();
+            window.last_event_token.set(EventToken {
+                time: window.now.get().as_millis() as u64,
+                serial: token.serial + 1,
+            });
+        }
+
+        window
+            .event(|handler| {
+                match event {
+                    Event::Frame => handler.frame(),
+                    Event::Wakeup => handler.wakeup(),
+                    Event::Idle => handler.idle(),
+                    Event::Timer { id } => handler.timer(id),
+                    Event::Stalled(duration) => handler.stalled(duration),
+                    Event::FramePacingChanged(pacing) => handler.frame_pacing_changed(pacing),
+                    Event::SystemResumed => handler.system_resumed(),
+                    Event::SystemMetricsChanged => handler.system_metrics_changed(),
+                    Event::Damage(region) => handler.damage(region),
+                    Event::CloseRequested => handler.close_requested(),
+                    Event::PopupDismissed => handler.popup_dismissed(),
+                    Event::Destroyed(reason) => handler.destroyed(reason),
+                    Event::FocusChanged(focus) => handler.focus_changed(focus),
+                    Event::SizeChanged(size) => handler.size_changed(size),
+                    Event::ScaleChanged(scale) => handler.scale_changed(scale),
+                    Event::MonitorChanged { monitor_id } => handler.monitor_changed(monitor_id),
+                    Event::ColorProfileChanged => handler.color_profile_changed(),
+                    Event::PositionChanged(point) => handler.position_changed(point),
+                    Event::VisibilityChanged(state) => handler.visibility_changed(state),
+                    Event::SafeAreaChanged(insets) => handler.safe_area_changed(insets),
+                    Event::MouseLeave => handler.mouse_leave(),
+                    Event::MousePress {
+                        button,
+                        pressed,
+                        position,
+                    } => handler.mouse_press(button, pressed, position),
+                    Event::MouseMove(point) => handler.mouse_move(point),
+                    Event::MouseScroll { x, y } => handler.mouse_scroll(x, y),
+                    Event::GestureRotate(angle) => handler.gesture_rotate(angle),
+                    Event::GestureZoom(scale) => handler.gesture_zoom(scale),
+                    Event::PenProximity { entering, inverted } => {
+                        handler.pen_proximity(entering, inverted)
+                    }
+                    Event::KeyModifiers(modifiers) => handler.key_modifiers(modifiers),
+                    Event::KeyPress {
+                        key,
+                        pressed,
+                        is_repeat,
+                        text,
+                    } => {
+                        return handler.key_press(key, pressed, is_repeat, text);
+                    }
+                    Event::CompositionStart => handler.composition_start(),
+                    Event::CompositionUpdate { text, cursor_range } => {
+                        handler.composition_update(&text, cursor_range)
+                    }
+                    Event::CompositionEnd { text } => handler.composition_end(text.as_deref()),
+                    Event::DragFinished(effect) => handler.drag_finished(effect),
+                }
+
+                false
+            })
+            .unwrap_or(false)
+    }
+
+    /// Move the virtual clock forward by `duration`.
+    ///
+    /// Delivers the wakeups and close requests sent through the window's
+    /// [`WindowWaker`]s, then a [`WindowHandler::frame`] for every frame
+    /// interval (1/60 of a second) that passes and a [`WindowHandler::timer`]
+    /// for every expiration of the timers set with [`Window::set_timer`], in
+    /// the order they are due. The first frame is at 1/60 of a second after
+    /// the window was opened.
+    pub fn advance(&self, duration: Duration) {
+        let window = &*self.0;
+        let target = window.now.get() + duration;
+
+        loop {
+            window.handle_waker();
+
+            // timers due at the same time as a frame go first
+            let next_frame = window.next_frame.get();
+            if let Some(deadline) = window.timers.next_deadline()
+                && deadline <= next_frame.min(target)
+            {
+                window.now.set(deadline);
+                if let Some(id) = window.timers.take_expired(deadline) {
+                    window.event(|handler| handler.timer(id));
+                }
+
+                continue;
+            }
+
+            if next_frame > target {
+                break;
+            }
+
+            window.now.set(next_frame);
+            window.next_frame.set(next_frame + FRAME_INTERVAL);
+            window.event(|handler| handler.frame());
+        }
+
+        window.now.set(target);
+    }
+
+    /// The current time of the virtual clock, starting at zero when the window
+    /// was opened.
+    #[must_use]
+    pub fn now(&self) -> Duration {
+        self.0.now.get()
+    }
+
+    /// Get a [`WindowWaker`] for the window, see [`HeadlessWindow::advance`].
+    #[must_use]
+    pub fn waker(&self) -> WindowWaker {
+        self.0.waker()
+    }
+
+    /// Check if the window was closed by the handler or its waker. A closed
+    /// window has no handler and ignores all events.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed.get()
+    }
+
+    /// The id of the window, see [`Window::id`].
+    #[must_use]
+    pub fn id(&self) -> WindowId {
+        self.0.id
+    }
+
+    /// The token of the last input event, see [`Window::last_event_token`].
+    #[must_use]
+    pub fn last_event_token(&self) -> EventToken {
+        self.0.last_event_token.get()
+    }
+
+    /// The last title set with [`Window::set_title`].
+    #[must_use]
+    pub fn title(&self) -> String {
+        self.0.title.borrow().clone()
+    }
+
+    /// The current size, set with [`Window::set_size`] or
+    /// [`Event::SizeChanged`].
+    #[must_use]
+    pub fn size(&self) -> Size {
+        self.0.size.get()
+    }
+
+    /// The last minimum size set with [`Window::set_min_size`].
+    #[must_use]
+    pub fn min_size(&self) -> Size {
+        self.0.min_size.get()
+    }
+
+    /// The last maximum size set with [`Window::set_max_size`].
+    #[must_use]
+    pub fn max_size(&self) -> Size {
+        self.0.max_size.get()
+    }
+
+    /// The current position, set with [`Window::set_position`] or
+    /// [`Event::PositionChanged`].
+    #[must_use]
+    pub fn position(&self) -> Point {
+        self.0.position.get()
+    }
+
+    /// The last visibility set with [`Window::set_visible`].
+    #[must_use]
+    pub fn is_visible(&self) -> bool {
+        self.0.visible.get()
+    }
+
+    /// The last decorations setting set with [`Window::set_decorations`].
+    #[must_use]
+    pub fn has_decorations(&self) -> bool {
+        self.0.decorations.get()
+    }
+
+    /// The last cursor icon set with [`Window::set_cursor_icon`].
+    #[must_use]
+    pub fn cursor_icon(&self) -> MouseCursor {
+        self.0.cursor_icon.get()
+    }
+
+    /// The cursor position, set with [`Window::set_cursor_position`] or
+    /// [`Event::MouseMove`]. `None` if the cursor is outside of the window.
+    #[must_use]
+    pub fn cursor_position(&self) -> Option<Point> {
+        self.0.cursor_position.get()
+    }
+
+    /// The last area set with [`Window::set_ime_cursor_area`], `None` if it
+    /// was never set.
+    #[must_use]
+    pub fn ime_cursor_area(&self) -> Option<Rect> {
+        self.0.ime_cursor_area.get()
+    }
+
+    /// The contents of the clipboard of the window. Headless windows do not
+    /// share a clipboard, and it only holds data in one format.
+    #[must_use]
+    pub fn clipboard(&self) -> Exchange {
+        self.0.clipboard.borrow().clone()
+    }
+
+    /// Replace the contents of the clipboard, as if another application
+    /// copied something.
+    pub fn set_clipboard(&self, data: impl Into<Exchange>) {
+        self.0.clipboard.replace(data.into());
+    }
+
+    /// The data of the drag started with [`Window::start_drag`], `None` if
+    /// there is none. A drag lasts until it is ended by dispatching
+    /// [`Event::DragFinished`].
+    #[must_use]
+    pub fn drag_data(&self) -> Option<Exchange> {
+        self.0.drag.borrow().clone()
+    }
+
+    /// The edge or corner of the last resize started with
+    /// [`Window::begin_drag_resize`], `None` if there was none. Headless
+    /// windows do not follow the mouse, dispatch [`Event::SizeChanged`] to
+    /// simulate the resize.
+    #[must_use]
+    pub fn last_resize_edge(&self) -> Option<ResizeEdge> {
+        self.0.resize_edge.get()
+    }
+
+    /// The grab set with [`Window::set_cursor_grab`], until the window is
+    /// hidden or loses the focus. Headless windows have no cursor to hold,
+    /// positions in [`Event::MouseMove`] are delivered as dispatched.
+    #[must_use]
+    pub fn cursor_grab(&self) -> CursorGrab {
+        self.0.cursor_grab.get()
+    }
+
+    /// What the window shows, as presented with [`Window::present_pixels`]:
+    /// the rows of [`Self::size`] from top to bottom. Anything not presented
+    /// since the window was last resized is zero.
+    #[must_use]
+    pub fn pixels(&self) -> Vec<u32> {
+        let size = self.0.size.get();
+        match &*self.0.surface.borrow() {
+            (presented, pixels) if *presented == size => pixels.clone(),
+            _ => vec![0; size.width as usize * size.height as usize],
+        }
+    }
+
+    /// Replace the color profile of the monitor, as if the user picked a
+    /// different one or the window moved to another monitor. Dispatches
+    /// [`Event::ColorProfileChanged`] if the profile is different.
+    pub fn set_color_profile(&self, profile: Option<IccProfile>) {
+        if self.0.color_profile.replace(profile.clone()) != profile {
+            self.dispatch(Event::ColorProfileChanged);
+        }
+    }
+
+    /// Deny the window access to the clipboard, as if the host sandboxed it.
+    /// [`Window::get_clipboard`] and [`Window::set_clipboard`] fail with
+    /// [`ClipboardError::AccessDenied`] until access is granted again.
+    pub fn set_clipboard_denied(&self, denied: bool) {
+        self.0.clipboard_denied.set(denied);
+    }
+}
+
+impl Drop for HeadlessWindow {
+    fn drop(&mut self) {
+        self.0.close();
+        self.0.drop_handler_if_closed();
+    }
+}
+
+impl std::fmt::Debug for HeadlessWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeadlessWindow")
+            .field("now", &self.now())
+            .field("is_closed", &self.is_closed())
+            .finish_non_exhaustive()
+    }
+}
+
+struct WindowImpl {
+    id: WindowId,
+    handle_id: u32,
+    waker: Arc<WindowWakerImpl>,
+
+    #[allow(clippy::type_complexity)]
+    event_deferred: EventQueue<dyn FnOnce(&mut dyn WindowHandler)>,
+    event_handler: RefCell<Option<Box<dyn WindowHandler>>>,
+    user_data: RefCell<Option<Rc<dyn Any>>>,
+    watchdog: Watchdog,
+    group: Option<WindowGroup>,
+    #[cfg(feature = "opengl")]
+    opengl: bool,
+    #[cfg(feature = "opengl")]
+    external_opengl: Option<ExternalGlContext>,
+
+    now: Cell<Duration>,
+    next_frame: Cell<Duration>,
+    /// See [`Window::set_timer`], on the virtual clock
+    timers: Timers<Duration>,
+    last_event_token: Cell<EventToken>,
+    /// Mouse buttons pressed and not yet released, as a mask of `1 << button`
+    buttons_held: Cell<u32>,
+    /// See [`WindowBuilder::with_unmatched_mouse_release`]
+    unmatched_mouse_release: bool,
+    keys_down: KeyState,
+    /// Set by [`Window::redraw_now`] while the handler is busy
+    redraw_pending: Cell<bool>,
+
+    title: RefCell<String>,
+    clipboard: RefCell<Exchange>,
+    clipboard_denied: Cell<bool>,
+    /// The data of the drag started with [`Window::start_drag`], until it is
+    /// finished
+    drag: RefCell<Option<Exchange>>,
+    /// The edge of the last resize started with [`Window::begin_drag_resize`]
+    resize_edge: Cell<Option<ResizeEdge>>,
+    /// The pixels presented with [`Window::present_pixels`], and the window
+    /// size they were presented at
+    surface: RefCell<(Size, Vec<u32>)>,
+    cursor_icon: Cell<MouseCursor>,
+    cursor_position: Cell<Option<Point>>,
+    cursor_grab: Cell<CursorGrab>,
+    /// Follows the dispatched [`Event::FocusChanged`]
+    focused: Cell<bool>,
+    ime_cursor_area: Cell<Option<Rect>>,
+    decorations: Cell<bool>,
+    visible: Cell<bool>,
+    size: Cell<Size>,
+    min_size: Cell<Size>,
+    max_size: Cell<Size>,
+    resize_hints: Cell<ResizeHints>,
+    position: Cell<Point>,
+    safe_area: Cell<Insets>,
+    scale: Cell<f64>,
+    color_profile: RefCell<Option<IccProfile>>,
+
+    is_closed: Cell<bool>,
+}
+
+struct WindowWakerImpl {
+    wakeup_pending: AtomicBool,
+    close_pending: AtomicBool,
+    is_closed: AtomicBool,
+}
+
+impl WindowImpl {
+    /// Run a closure with exclusive access to the window's event handler, then
+    /// run the events deferred while it was running.
+    ///
+    /// Returns `None` if called from inside of the handler, see the reentrancy
+    /// notes on [`WindowHandler`]. Once the handler closes the window, the
+    /// rest is dropped and only [`WindowHandler::destroyed`] follows.
+    fn event<R>(&self, call: impl FnOnce(&mut dyn WindowHandler) -> R) -> Option<R> {
+        if self.is_closed.get() {
+            self.drop_handler_if_closed();
+            return None;
+        }
+
+        let result = 'dispatch: {
+            let Ok(mut handler) = self.event_handler.try_borrow_mut() else {
+                report_reentrancy(false);
+                return None;
+            };
+            let handler = handler.as_mut()?;
+
+            let result = self.watchdog.measure(|| call(&mut **handler));
+
+            while !self.is_closed.get() {
+                let Some(event) = self.event_deferred.pop() else {
+                    break;
+                };
+
+                self.watchdog.measure(|| event(&mut **handler));
+            }
+
+            if self.is_closed.get() {
+                break 'dispatch result;
+            }
+
+            if self.redraw_pending.take() {
+                self.watchdog.measure(|| handler.frame());
+            }
+
+            if let Some(stall) = self.watchdog.take_stall() {
+                handler.stalled(stall);
+            }
+
+            result
+        };
+
+        self.drop_handler_if_closed();
+        Some(result)
+    }
+
+    /// Queue an event to be delivered after the current one, the handler is
+    /// always busy when this is called.
+    fn deferred_event(&self, event: impl FnOnce(&mut dyn WindowHandler) + 'static) {
+        self.event_deferred
+            .push(QueueKind::Critical, Box::new(event));
+    }
+
+    /// Report the `held` buttons as released, after the system took over the
+    /// mouse for a drag or a resize, like on the real backends.
+    fn release_buttons(&self, held: u32) {
+        self.buttons_held.set(0);
+
+        let point = self.cursor_position.get().unwrap_or_default();
+        for button in [
+            MouseButton::Left,
+            MouseButton::Right,
+            MouseButton::Middle,
+            MouseButton::Forward,
+            MouseButton::Back,
+        ] {
+            if held & (1 << button as u32) != 0 {
+                self.deferred_event(move |e| e.mouse_press(button, false, point));
+            }
+        }
+    }
+
+    /// Deliver the requests sent through the wakers since the last call.
+    fn handle_waker(&self) {
+        if self.waker.close_pending.swap(false, Ordering::Relaxed) {
+            self.close();
+            self.drop_handler_if_closed();
+        }
+
+        if self.waker.wakeup_pending.swap(false, Ordering::Relaxed) {
+            self.event(|handler| handler.wakeup());
+        }
+    }
+
+    /// Drop the handler once the window is closed, unless it is running.
+    fn drop_handler_if_closed(&self) {
+        if !self.is_closed.get() {
+            return;
+        }
+
+        // take it out first, so the handler can still use the window while dropping
+        let handler = self
+            .event_handler
+            .try_borrow_mut()
+            .ok()
+            .and_then(|mut handler| handler.take());
+
+        if let Some(mut handler) = handler {
+            handler.destroyed(DestroyReason::Closed);
+        }
+
+        self.event_deferred.clear();
+    }
+}
+
+impl PlatformWindow for WindowImpl {
+    fn window_handle(&self) -> rwh_06::RawWindowHandle {
+        rwh_06::WebWindowHandle::new(self.handle_id).into()
+    }
+
+    fn display_handle(&self) -> rwh_06::RawDisplayHandle {
+        rwh_06::WebDisplayHandle::new().into()
+    }
+
+    fn close(&self) {
+        if self.is_closed.replace(true) {
+            return;
+        }
+
+        // subsequent wakeups should fail
+        self.waker.is_closed.store(true, Ordering::Relaxed);
+
+        if let Some(group) = &self.group {
+            group.remove(&self.waker());
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.is_closed.get()
+    }
+
+    fn waker(&self) -> WindowWaker {
+        WindowWaker(self.waker.clone())
+    }
+
+    #[cfg(feature = "opengl")]
+    fn opengl(&self) -> Result<&dyn PlatformOpenGl, OpenGlError> {
+        if let Some(gl) = &self.external_opengl {
+            Ok(gl)
+        } else if self.opengl {
+            Err(OpenGlError::Platform(
+                "OpenGL is not available in headless windows".into(),
+            ))
+        } else {
+            Err(OpenGlError::NotRequested)
+        }
+    }
+
+    fn scale(&self) -> f64 {
+        self.scale.get()
+    }
+
+    fn color_profile(&self) -> Option<IccProfile> {
+        self.color_profile.borrow().clone()
+    }
+
+    fn metal_layer(&self) -> Option<NonNull<c_void>> {
+        None
+    }
+
+    fn user_data(&self) -> &RefCell<Option<Rc<dyn Any>>> {
+        &self.user_data
+    }
+
+    fn set_title(&self, title: &str) {
+        // reuse the buffer, some handlers update the title every frame
+        let mut current = self.title.borrow_mut();
+        current.clear();
+        current.push_str(title);
+    }
+
+    fn set_decorations(&self, decorations: bool) {
+        self.decorations.set(decorations);
+    }
+
+    fn set_cursor_icon(&self, icon: MouseCursor) {
+        self.cursor_icon.set(icon);
+    }
+
+    fn set_cursor_position(&self, pos: Point) {
+        self.cursor_position.set(Some(pos));
+    }
+
+    fn set_visible(&self, visible: bool) {
+        self.visible.set(visible);
+        if !visible {
+            self.cursor_grab.set(CursorGrab::None);
+        }
+    }
+
+    fn set_size(&self, size: Size) {
+        let (min, max) = (self.min_size.get(), self.max_size.get());
+        let size = Size {
+            width: size.width.min(max.width).max(min.width),
+            height: size.height.min(max.height).max(min.height),
+        };
+
+        if self.size.replace(size) != size {
+            self.deferred_event(move |handler| handler.size_changed(size));
+        }
+    }
+
+    fn set_min_size(&self, size: Size) {
+        self.min_size.set(size);
+    }
+
+    fn set_max_size(&self, size: Size) {
+        self.max_size.set(size);
+    }
+
+    fn set_resize_hints(&self, hints: ResizeHints) {
+        self.resize_hints.set(hints);
+    }
+
+    fn resize_hints(&self) -> ResizeHints {
+        self.resize_hints.get()
+    }
+
+    fn set_position(&self, pos: Point) {
+        self.position.set(pos);
+    }
+
+    fn center(&self, _: MonitorSelector) {
+        // there are no monitors to center on
+    }
+
+    fn open_url(&self, url: &str, on_done: OpenUrlCallback) {
+        on_done(validate_url(url).and(Err(OpenUrlError::Unsupported)));
+    }
+
+    fn get_clipboard(&self, format: Option<&ClipboardFormat>) -> Result<Exchange, ClipboardError> {
+        match self.clipboard_denied.get() {
+            true => Err(ClipboardError::AccessDenied),
+            false => filter_clipboard(self.clipboard.borrow().clone(), format),
+        }
+    }
+
+    fn set_clipboard(&self, data: Exchange) -> Result<(), ClipboardError> {
+        match self.clipboard_denied.get() {
+            true => Err(ClipboardError::AccessDenied),
+            false => {
+                self.clipboard.replace(data);
+                Ok(())
+            }
+        }
+    }
+
+    fn start_drag(&self, data: Exchange) -> Result<(), DragError> {
+        if self.drag.borrow().is_some() {
+            return Err(DragError::InProgress);
+        }
+
+        let held = self.buttons_held.get();
+        if held == 0 {
+            return Err(DragError::NoButtonHeld);
+        }
+
+        self.drag.replace(Some(data));
+        self.release_buttons(held);
+
+        Ok(())
+    }
+
+    fn begin_drag_resize(&self, edge: ResizeEdge) -> Result<(), DragError> {
+        let held = self.buttons_held.get();
+        if held == 0 {
+            return Err(DragError::NoButtonHeld);
+        }
+
+        self.resize_edge.set(Some(edge));
+        self.release_buttons(held);
+
+        Ok(())
+    }
+
+    fn set_cursor_grab(&self, mode: CursorGrab) -> Result<(), CursorGrabError> {
+        if mode != CursorGrab::None && !self.visible.get() {
+            return Err(CursorGrabError::Unavailable);
+        }
+
+        self.cursor_grab.set(mode);
+        Ok(())
+    }
+
+    fn has_mouse_capture(&self) -> bool {
+        self.buttons_held.get() != 0
+    }
+
+    fn has_keyboard_focus(&self) -> bool {
+        self.focused.get()
+    }
+
+    fn cursor_grab(&self) -> CursorGrab {
+        self.cursor_grab.get()
+    }
+
+    fn last_event_token(&self) -> EventToken {
+        self.last_event_token.get()
+    }
+
+    fn id(&self) -> WindowId {
+        self.id
+    }
+
+    fn to_screen(&self, point: Point) -> Point {
+        self.position.get() + point
+    }
+
+    fn safe_area_insets(&self) -> Insets {
+        self.safe_area.get()
+    }
+
+    fn inner_size(&self) -> Size {
+        self.size.get()
+    }
+
+    fn frame_insets(&self) -> Insets {
+        // there is no system frame around a headless window
+        Insets::default()
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        // frames are delivered exactly on the virtual clock
+        FrameStats {
+            jitter: Duration::ZERO,
+            dropped_events: self.event_deferred.dropped(),
+            coalesced_events: self.event_deferred.coalesced(),
+        }
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.is_down(key)
+    }
+
+    fn set_ime_cursor_area(&self, area: Rect) {
+        self.ime_cursor_area.set(Some(area));
+    }
+
+    fn scroll_settings(&self) -> ScrollSettings {
+        ScrollSettings::default()
+    }
+
+    fn present_pixels(
+        &self,
+        buffer: &[u32],
+        size: Size,
+        damage: Damage<'_>,
+    ) -> Result<(), PresentError> {
+        let window_size = self.size.get();
+        let mut surface = self.surface.borrow_mut();
+        if surface.0 != window_size {
+            let pixels = window_size.width as usize * window_size.height as usize;
+            *surface = (window_size, vec![0; pixels]);
+        }
+
+        for rect in damage.iter() {
+            copy_pixels(buffer, size, &mut surface.1, window_size, rect);
+        }
+
+        Ok(())
+    }
+
+    fn redraw_now(&self) {
+        // from inside the handler, or from the factory before there is one
+        if matches!(self.event_handler.try_borrow().as_deref(), Ok(Some(_))) {
+            self.event(|e| e.frame());
+        } else {
+            self.redraw_pending.set(true);
+        }
+    }
+
+    fn set_timer(&self, id: u32, interval: Duration) {
+        self.timers.set(id, interval, self.now.get());
+    }
+
+    fn cancel_timer(&self, id: u32) {
+        self.timers.cancel(id);
+    }
+}
+
+impl PlatformWaker for WindowWakerImpl {
+    fn wakeup(&self) -> Result<(), WakeupError> {
+        if self.is_closed.load(Ordering::Relaxed) {
+            return Err(WakeupError);
+        }
+
+        self.wakeup_pending.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn close(&self) -> Result<(), WakeupError> {
+        if self.is_closed.load(Ordering::Relaxed) {
+            return Err(WakeupError);
+        }
+
+        self.close_pending.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn close_and_wait(&self) {
+        // the window is only reachable from its owner, which closes it on the next
+        // `HeadlessWindow::advance`
+        let _ = self.close();
+    }
+}
@@ -50,29 +50,43 @@ pub struct DisplayLink {
     source: CFRetained<CFRunLoopSource>,
 }
 
+/// Create a new run loop source attached to the main run loop, calling
+/// `runner` on the main thread every time the source is signalled.
+///
+/// Signalling the source is allocation-free and can be done from any thread.
+pub fn main_run_loop_source(
+    runner: Box<dyn Fn()>,
+) -> Result<CFRetained<CFRunLoopSource>, WindowError> {
+    unsafe {
+        let state = Rc::new(DisplayState { runner });
+        let mut context = CFRunLoopSourceContext {
+            version: 0,
+            info: Rc::into_raw(state) as *mut c_void,
+            retain: Some(retain),
+            release: Some(release),
+            copyDescription: None,
+            equal: None,
+            hash: None,
+            schedule: None,
+            cancel: None,
+            perform: Some(perform),
+        };
+
+        let source = CFRunLoopSource::new(None, 0, &mut context)
+            .ok_or_else(|| WindowError::Platform("CFRunLoopSource::new".to_owned()))?;
+        let run_loop =
+            CFRunLoop::main().ok_or_else(|| WindowError::Platform("CFRunLoop::main".to_owned()))?;
+        run_loop.add_source(Some(&source), kCFRunLoopCommonModes);
+
+        Ok(source)
+    }
+}
+
 impl DisplayLink {
     #[allow(deprecated)] // smh
     pub fn new(runner: Box<dyn Fn()>) -> Result<DisplayLink, WindowError> {
         unsafe {
-            let state = Rc::new(DisplayState { runner });
-            let mut context = CFRunLoopSourceContext {
-                version: 0,
-                info: Rc::into_raw(state) as *mut c_void,
-                retain: Some(retain),
-                release: Some(release),
-                copyDescription: None,
-                equal: None,
-                hash: None,
-                schedule: None,
-                cancel: None,
-                perform: Some(perform),
-            };
-
-            let source = CFRunLoopSource::new(None, 0, &mut context)
-                .ok_or_else(|| WindowError::Platform("CFRunLoopSource::new".to_owned()))?;
-            let run_loop = CFRunLoop::main()
-                .ok_or_else(|| WindowError::Platform("CFRunLoop::main".to_owned()))?;
-            run_loop.add_source(Some(&source), kCFRunLoopCommonModes);
+            let source = main_run_loop_source(runner)?;
 
             let mut link = null_mut();
             let result =
@@ -109,6 +123,22 @@ impl DisplayLink {
     }
 }
 
+impl DisplayLink {
+    /// Follow the refresh rate of the given display instead of the main one.
+    #[allow(deprecated)]
+    pub fn set_display(&self, display_id: u32) {
+        self.link.set_current_cg_display(display_id);
+    }
+
+    /// Stop and start the link again, it can stop calling back after the
+    /// system wakes up from sleep.
+    #[allow(deprecated)]
+    pub fn restart(&self) {
+        self.link.stop();
+        self.link.start();
+    }
+}
+
 impl Drop for DisplayLink {
     #[allow(deprecated)]
     fn drop(&mut self) {
@@ -0,0 +1,80 @@
+use crate::WindowError;
+use objc2_app_kit::{NSApp, NSApplication, NSEvent, NSEventModifierFlags, NSEventType};
+use objc2_foundation::{MainThreadMarker, NSPoint};
+use std::cell::Cell;
+
+thread_local! {
+    /// The number of open top-level windows, [`run`] returns once all of them
+    /// are closed.
+    static WINDOWS: Cell<usize> = const { Cell::new(0) };
+    /// Is [`run`] running?
+    static IS_RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Check if [`run`] runs on this thread.
+pub fn is_running() -> bool {
+    IS_RUNNING.get()
+}
+
+/// Count an opened top-level window.
+pub fn add() {
+    WINDOWS.set(WINDOWS.get() + 1);
+}
+
+/// Stop counting a closed top-level window, see [`add`]. Stops the event loop
+/// if it was the last one.
+pub fn remove(app: &NSApplication) {
+    let windows = WINDOWS.get().saturating_sub(1);
+    WINDOWS.set(windows);
+
+    if windows > 0 || !IS_RUNNING.get() {
+        return;
+    }
+
+    app.stop(Some(app));
+
+    // it is stupid that we have to send a dummy event to _actually_ stop the event
+    // loop but here we are, thank you apple!!!!
+    app.postEvent_atStart(&NSEvent::otherEventWithType_location_modifierFlags_timestamp_windowNumber_context_subtype_data1_data2(
+        NSEventType::ApplicationDefined,
+        NSPoint::new(0.0, 0.0),
+        NSEventModifierFlags::empty(),
+        0.0,
+        0,
+        None,
+        0,
+        0,
+        0,
+    ).expect("Failed to create dummy event"), false);
+}
+
+/// Run `NSApplication` until all top-level windows are closed. Windows of all
+/// kinds are run by it, not just the counted ones.
+pub unsafe fn run() -> Result<(), WindowError> {
+    /// Resets [`IS_RUNNING`], even if a handler panics.
+    struct Running;
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            IS_RUNNING.set(false);
+        }
+    }
+
+    let main_thread = MainThreadMarker::new()
+        .ok_or_else(|| WindowError::Platform("not on main thread".into()))?;
+
+    // `stop` only ends the innermost run, the outer one would never return
+    if IS_RUNNING.get() {
+        return Err(WindowError::EventLoopRunning);
+    }
+
+    if WINDOWS.get() == 0 {
+        return Ok(());
+    }
+
+    IS_RUNNING.set(true);
+    let _running = Running;
+
+    NSApp(main_thread).run();
+    Ok(())
+}
@@ -13,12 +13,16 @@ pub struct GlContext {
     bundle: CFRetained<CFBundle>,
     context: Retained<NSOpenGLContext>,
     view: Retained<NSOpenGLView>,
+    version: GlVersion,
 }
 
 impl GlContext {
+    /// Only the `last_resort` may settle for the legacy 2.1 profile when a
+    /// newer compatibility profile was asked for, there are no others.
     pub fn new(
         parent: &NSView,
         config: GlConfig,
+        last_resort: bool,
         mtm: MainThreadMarker,
     ) -> Result<Self, OpenGlError> {
         let version = match config.version {
@@ -29,10 +33,20 @@ impl GlContext {
                 objc2_app_kit::NSOpenGLProfileVersion4_1Core
             }
             GlVersion::Core(_, _) => objc2_app_kit::NSOpenGLProfileVersion3_2Core,
+            GlVersion::Compat(a, b) if (a, b) > (2, 1) && !last_resort => {
+                return Err(OpenGlError::VersionUnsupported);
+            }
             GlVersion::Compat(_, _) => objc2_app_kit::NSOpenGLProfileVersionLegacy,
             GlVersion::ES(_, _) => return Err(OpenGlError::VersionUnsupported),
         };
 
+        let bundle = {
+            CFBundle::bundle_with_identifier(Some(&CFString::from_static_str("com.apple.opengl")))
+                .ok_or_else(|| {
+                OpenGlError::Platform("Failed to get the 'com.apple.opengl' CFBundle".into())
+            })?
+        };
+
         let attrs = {
             let (r, g, b, a, d, s) = config.format.as_rgbads();
             let mut attrs = vec![
@@ -94,26 +108,24 @@ impl GlContext {
 
         parent.addSubview(&view);
 
-        let context = view.openGLContext().ok_or_else(|| {
-            OpenGlError::Platform("Failed to get NSOpenGLContext from NSOpenGLView".into())
-        })?;
+        // do not leave the view behind for the next config of a fallback chain
+        let Some(context) = view.openGLContext() else {
+            view.removeFromSuperview();
+            return Err(OpenGlError::Platform(
+                "Failed to get NSOpenGLContext from NSOpenGLView".into(),
+            ));
+        };
 
         unsafe {
             context
                 .setValues_forParameter(NonNull::from(&0), objc2_app_kit::NSOpenGLCPSwapInterval);
         }
 
-        let bundle = {
-            CFBundle::bundle_with_identifier(Some(&CFString::from_static_str("com.apple.opengl")))
-                .ok_or_else(|| {
-                OpenGlError::Platform("Failed to get the 'com.apple.opengl' CFBundle".into())
-            })?
-        };
-
         Ok(Self {
             context,
             view,
             bundle,
+            version: config.version,
         })
     }
 
@@ -161,6 +173,10 @@ impl PlatformOpenGl for GlContext {
             }
         }
     }
+
+    fn version(&self) -> Option<GlVersion> {
+        Some(self.version)
+    }
 }
 
 impl Drop for GlContext {
@@ -0,0 +1,58 @@
+use objc2::rc::Retained;
+use objc2_app_kit::NSView;
+use objc2_core_foundation::CGSize;
+use objc2_quartz_core::{CAMetalLayer, CATransaction};
+use std::ffi::c_void;
+use std::ptr::NonNull;
+
+/// A `CAMetalLayer` covering the view, see
+/// [`Window::metal_layer`](crate::Window::metal_layer).
+///
+/// It is a sublayer of the (layer-backed) view instead of its backing layer,
+/// so AppKit keeps managing the view, and the layer stays attached when the
+/// view moves to another window.
+pub struct MetalLayer {
+    layer: Retained<CAMetalLayer>,
+}
+
+impl MetalLayer {
+    /// Add a new layer on top of the contents of the view, or `None` if the
+    /// view is not layer-backed.
+    pub fn new(view: &NSView) -> Option<Self> {
+        let parent = view.layer()?;
+        let layer = CAMetalLayer::new();
+        parent.addSublayer(&layer);
+
+        Some(Self { layer })
+    }
+
+    /// The layer as a `CAMetalLayer *`.
+    pub fn as_ptr(&self) -> NonNull<c_void> {
+        NonNull::from(&*self.layer).cast()
+    }
+
+    /// Stretch the layer over the view, with a drawable of its size in pixels.
+    /// `scale` is the backing scale factor of the window.
+    pub fn update(&self, view: &NSView, scale: f64) {
+        let bounds = view.bounds();
+
+        // changes to standalone layers are animated by default, resize right away
+        CATransaction::begin();
+        CATransaction::setDisableActions(true);
+
+        self.layer.setFrame(bounds);
+        self.layer.setContentsScale(scale);
+        self.layer.setDrawableSize(CGSize::new(
+            (bounds.size.width * scale).round(),
+            (bounds.size.height * scale).round(),
+        ));
+
+        CATransaction::commit();
+    }
+}
+
+impl Drop for MetalLayer {
+    fn drop(&mut self) {
+        self.layer.removeFromSuperlayer();
+    }
+}
@@ -1,5 +1,9 @@
 mod display;
+mod event_loop;
+#[cfg(feature = "opengl")]
 mod gl;
+mod metal;
+mod present;
 mod util;
 mod view;
 
@@ -9,3 +13,21 @@ pub unsafe fn open_window(
 ) -> Result<crate::WindowWaker, crate::WindowError> {
     unsafe { view::WindowImpl::open(options, mode) }
 }
+
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub unsafe fn run_event_loop() -> Result<(), crate::WindowError> {
+    unsafe { event_loop::run() }
+}
+
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub fn is_event_loop_running() -> bool {
+    event_loop::is_running()
+}
+
+pub fn find_window_at(point: crate::Point) -> Option<crate::WindowId> {
+    view::WindowImpl::window_at(point)
+}
+
+pub fn query_system_metrics() -> crate::SystemMetrics {
+    util::query_system_metrics()
+}
@@ -0,0 +1,92 @@
+use crate::{PresentError, Size};
+use objc2::rc::Retained;
+use objc2_app_kit::NSView;
+use objc2_core_foundation::{CFData, CFRetained, CFType};
+use objc2_core_graphics::{
+    CGBitmapInfo, CGColorRenderingIntent, CGColorSpace, CGDataProvider, CGImage, CGImageAlphaInfo,
+    CGImageByteOrderInfo, kCGColorSpaceSRGB,
+};
+use objc2_quartz_core::{CALayer, CATransaction};
+use std::ptr::null;
+
+/// A layer covering the view, showing the pixels of
+/// [`Window::present_pixels`](crate::Window::present_pixels) as its contents.
+///
+/// Layer contents can only be replaced as a whole, so every present copies
+/// all of the buffer into a new `CGImage`.
+pub struct PixelLayer {
+    layer: Retained<CALayer>,
+    color_space: CFRetained<CGColorSpace>,
+}
+
+impl PixelLayer {
+    /// Add a new layer on top of the contents of the (layer-backed) view.
+    pub fn new(view: &NSView) -> Result<Self, PresentError> {
+        let parent = view.layer().ok_or(PresentError::Unsupported)?;
+        let color_space = CGColorSpace::with_name(Some(unsafe { kCGColorSpaceSRGB }))
+            .ok_or_else(|| PresentError::Platform("CGColorSpaceCreateWithName failed".into()))?;
+
+        let layer = CALayer::new();
+        parent.addSublayer(&layer);
+
+        Ok(Self { layer, color_space })
+    }
+
+    /// Show `buffer`, stretched over the view. `scale` is the backing scale
+    /// factor of the window, the number of pixels per point.
+    pub fn present(
+        &self,
+        view: &NSView,
+        buffer: &[u32],
+        size: Size,
+        scale: f64,
+    ) -> Result<(), PresentError> {
+        let (width, height) = (size.width as usize, size.height as usize);
+        let pixels = buffer
+            .get(..width * height)
+            .ok_or(PresentError::BufferTooSmall)?;
+
+        let image = unsafe {
+            let data = CFData::new(None, pixels.as_ptr() as *const u8, size_of_val(pixels) as _)
+                .ok_or_else(|| PresentError::Platform("CFDataCreate failed".into()))?;
+            let provider = CGDataProvider::with_cf_data(Some(&data))
+                .ok_or_else(|| PresentError::Platform("CGDataProvider failed".into()))?;
+
+            // 0xAARRGGBB in native (little endian) byte order
+            CGImage::new(
+                width,
+                height,
+                8,
+                32,
+                width * 4,
+                Some(&self.color_space),
+                CGBitmapInfo(
+                    CGImageAlphaInfo::PremultipliedFirst.0 | CGImageByteOrderInfo::Order32Little.0,
+                ),
+                Some(&provider),
+                null(),
+                false,
+                CGColorRenderingIntent::RenderingIntentDefault,
+            )
+            .ok_or_else(|| PresentError::Platform("CGImageCreate failed".into()))?
+        };
+
+        // changes to standalone layers are animated by default, show them right away
+        CATransaction::begin();
+        CATransaction::setDisableActions(true);
+
+        let contents: &CFType = &image;
+        self.layer.setFrame(view.bounds());
+        self.layer.setContentsScale(scale);
+        unsafe { self.layer.setContents(Some(contents.as_ref())) };
+
+        CATransaction::commit();
+        Ok(())
+    }
+}
+
+impl Drop for PixelLayer {
+    fn drop(&mut self) {
+        self.layer.removeFromSuperlayer();
+    }
+}
@@ -1,12 +1,12 @@
-use crate::MouseCursor;
+use crate::{IccProfile, MouseCursor, OpenUrlError, Point, SystemMetrics};
 use objc2::rc::Retained;
-use objc2::runtime::{MessageReceiver, Sel};
-use objc2::{ClassType, sel};
-use objc2_app_kit::{NSCursor, NSHorizontalDirections, NSVerticalDirections};
-use std::os::unix::process::CommandExt;
+use objc2::runtime::{AnyObject, MessageReceiver, Sel};
+use objc2::{ClassType, MainThreadMarker, class, msg_send, sel};
+use objc2_app_kit::{NSCursor, NSEvent, NSHorizontalDirections, NSScreen, NSVerticalDirections};
+use objc2_foundation::{NSPoint, NSString, ns_string};
 use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 pub fn best_cursor_icon_for(cursor: MouseCursor) -> Retained<NSCursor> {
     fn load(selector: Sel) -> Retained<NSCursor> {
@@ -66,6 +66,113 @@ pub fn best_cursor_icon_for(cursor: MouseCursor) -> Retained<NSCursor> {
     }
 }
 
+/// Get the `CGDirectDisplayID` of the display the given screen is on.
+pub fn screen_display_id(screen: &NSScreen) -> Option<u32> {
+    let number = screen
+        .deviceDescription()
+        .objectForKey(ns_string!("NSScreenNumber"))?;
+    Some(unsafe { msg_send![&*number, unsignedIntValue] })
+}
+
+/// Get the ICC profile of the color space the given screen is calibrated to.
+pub fn screen_color_profile(screen: &NSScreen) -> Option<IccProfile> {
+    let data = screen.colorSpace()?.ICCProfileData()?;
+    Some(IccProfile::new(data.to_vec())).filter(|profile| !profile.as_bytes().is_empty())
+}
+
+/// Get a value from the user defaults, including the global ones set in the
+/// system settings. `None` if it is not set.
+fn query_user_default(key: &NSString) -> Option<Retained<AnyObject>> {
+    unsafe {
+        let defaults: Option<Retained<AnyObject>> =
+            msg_send![class!(NSUserDefaults), standardUserDefaults];
+        msg_send![&*defaults?, objectForKey: key]
+    }
+}
+
+/// Check whether natural scrolling is turned on in the system settings. It is
+/// on by default, so the key is missing until the user turns it off.
+pub fn query_natural_scrolling() -> bool {
+    match query_user_default(ns_string!("com.apple.swipescrolldirection")) {
+        Some(value) => unsafe { msg_send![&*value, boolValue] },
+        None => true,
+    }
+}
+
+/// Read the metrics of the native controls. The scrollbars can only be
+/// queried on the main thread, elsewhere they keep their defaults.
+pub fn query_system_metrics() -> SystemMetrics {
+    let default = SystemMetrics::default();
+    let mut metrics = SystemMetrics {
+        double_click_time: Duration::try_from_secs_f64(NSEvent::doubleClickInterval())
+            .unwrap_or(default.double_click_time),
+        ..default
+    };
+
+    // not set unless the user changed it with `defaults write`, in milliseconds
+    if let Some(value) = query_user_default(ns_string!("NSTextInsertionPointBlinkPeriodOn")) {
+        let millis: f64 = unsafe { msg_send![&*value, doubleValue] };
+        if millis > 0.0 {
+            metrics.caret_blink_interval = Duration::try_from_secs_f64(millis / 1000.0).ok();
+        }
+    }
+
+    if MainThreadMarker::new().is_some() {
+        unsafe {
+            let style: isize = msg_send![class!(NSScroller), preferredScrollerStyle];
+            metrics.scrollbar_width = msg_send![
+                class!(NSScroller),
+                // `NSControlSizeRegular`
+                scrollerWidthForControlSize: 0usize,
+                scrollerStyle: style
+            ];
+            // `NSScrollerStyleOverlay`
+            metrics.overlay_scrollbars = style == 1;
+        }
+    }
+
+    metrics
+}
+
+/// Get the height and backing scale of the primary screen (the one with the
+/// menu bar), which define our screen coordinates, see
+/// [`crate::Window::to_screen`].
+fn primary_screen_metrics(mtm: MainThreadMarker) -> (f64, f64) {
+    NSScreen::screens(mtm)
+        .firstObject()
+        .map(|screen| (screen.frame().size.height, screen.backingScaleFactor()))
+        .unwrap_or((0.0, 1.0))
+}
+
+// not bound by objc2-core-graphics without its libc feature
+#[link(name = "CoreGraphics", kind = "framework")]
+unsafe extern "C" {
+    fn CGAssociateMouseAndMouseCursorPosition(connected: u32) -> i32;
+}
+
+/// Detach the cursor from the mouse, so it stays in place while mouse events
+/// keep reporting the motion deltas. Attaching it again also ends the short
+/// freeze that follows a cursor warp.
+pub fn set_cursor_attached(attached: bool) {
+    unsafe {
+        CGAssociateMouseAndMouseCursorPosition(u32::from(attached));
+    }
+}
+
+/// Convert a point from AppKit screen coordinates (points, bottom-left origin)
+/// to ours (physical pixels, top-left origin).
+pub fn screen_point_from_cocoa(point: NSPoint, mtm: MainThreadMarker) -> Point {
+    let (height, scale) = primary_screen_metrics(mtm);
+    Point::from_logical((point.x, height - point.y), scale)
+}
+
+/// The inverse of [`screen_point_from_cocoa`].
+pub fn screen_point_to_cocoa(point: Point, mtm: MainThreadMarker) -> NSPoint {
+    let (height, scale) = primary_screen_metrics(mtm);
+    let point = point.to_logical(scale);
+    NSPoint::new(point.x, height - point.y)
+}
+
 pub fn random_id() -> u32 {
     static STATE: AtomicU32 = AtomicU32::new(1);
     STATE
@@ -81,28 +188,35 @@ pub fn random_id() -> u32 {
         .unwrap_or_default()
 }
 
-pub fn spawn_detached(cmd: &mut Command) -> std::io::Result<()> {
-    cmd.stdin(Stdio::null())
+/// Open the given URL with the default system handler via `/usr/bin/open`.
+/// Blocks until `open` exits, so it should run on a worker thread.
+pub fn open_url(url: &str) -> Result<(), OpenUrlError> {
+    let output = Command::new("/usr/bin/open")
+        .arg(url)
+        .stdin(Stdio::null())
         .stdout(Stdio::null())
-        .stderr(Stdio::null());
+        .output()
+        .map_err(|err| OpenUrlError::Platform(format!("/usr/bin/open: {err}")))?;
 
-    unsafe {
-        cmd.pre_exec(move || {
-            match libc::fork() {
-                -1 => return Err(std::io::Error::last_os_error()),
-                0 => (),
-                _ => libc::_exit(0),
-            }
+    if output.status.success() {
+        return Ok(());
+    }
 
-            if libc::setsid() == -1 {
-                return Err(std::io::Error::last_os_error());
-            }
+    let message = String::from_utf8_lossy(&output.stderr);
+    let message = message.trim();
 
-            Ok(())
-        });
+    if message.contains("No application knows how to open") {
+        Err(OpenUrlError::NoHandler)
+    } else if message.contains("does not exist") {
+        Err(OpenUrlError::NotFound)
+    } else if message.is_empty() {
+        Err(OpenUrlError::Platform(format!(
+            "/usr/bin/open: {}",
+            output.status
+        )))
+    } else {
+        Err(OpenUrlError::Platform(message.to_owned()))
     }
-
-    cmd.spawn().map(|_| ())
 }
 
 pub use clipboard::*;
@@ -110,7 +224,9 @@ pub use keyboard::*;
 
 mod keyboard {
     use crate::{Key, Modifiers};
-    use objc2_app_kit::NSEventModifierFlags;
+    use objc2::runtime::AnyObject;
+    use objc2_app_kit::{NSEvent, NSEventModifierFlags};
+    use objc2_foundation::{NSAttributedString, NSString};
 
     pub fn flags_to_modifiers(flags: NSEventModifierFlags) -> Modifiers {
         Modifiers {
@@ -121,9 +237,28 @@ mod keyboard {
             caps_lock: flags.contains(NSEventModifierFlags::CapsLock),
             num_lock: flags.contains(NSEventModifierFlags::NumericPad),
             scroll_lock: false,
+            fn_key: flags.contains(NSEventModifierFlags::Function),
         }
     }
 
+    /// The held state of each side specific modifier key, taken from the
+    /// device dependent bits of the flags (see IOLLEvent.h). Fn has no side,
+    /// so it comes from the device independent bit.
+    pub fn flags_to_modifier_keys(flags: NSEventModifierFlags) -> [(Key, bool); 9] {
+        let bit = |mask: usize| flags.0 & mask != 0;
+        [
+            (Key::MetaLeft, bit(0x1)), // map control -> meta
+            (Key::ShiftLeft, bit(0x2)),
+            (Key::ShiftRight, bit(0x4)),
+            (Key::ControlLeft, bit(0x8)),   // map command -> ctrl
+            (Key::ControlRight, bit(0x10)), // map command -> ctrl
+            (Key::AltLeft, bit(0x20)),      // map option -> alt
+            (Key::AltRight, bit(0x40)),     // map option -> alt
+            (Key::MetaRight, bit(0x2000)),  // map control -> meta
+            (Key::Fn, flags.contains(NSEventModifierFlags::Function)),
+        ]
+    }
+
     pub fn keycode_to_key(key: u16) -> Option<Key> {
         Some(match key {
             0x00 => Key::A,
@@ -234,54 +369,223 @@ mod keyboard {
             _ => return None,
         })
     }
+
+    /// Get the printable character a key down event would type with the
+    /// current keyboard layout, if any.
+    pub fn event_to_char(event: &NSEvent) -> Option<char> {
+        let ch = event.characters()?.to_string().chars().next()?;
+
+        // function keys are reported as characters in the private use area
+        match ch {
+            '\u{f700}'..='\u{f8ff}' => None,
+            _ if ch.is_control() => None,
+            _ => Some(ch),
+        }
+    }
+
+    /// Get the text passed to `insertText:replacementRange:` or
+    /// `setMarkedText:selectedRange:replacementRange:`, which is either an
+    /// `NSString` or an `NSAttributedString`.
+    pub fn input_string(string: &AnyObject) -> String {
+        if let Some(string) = string.downcast_ref::<NSAttributedString>() {
+            return string.string().to_string();
+        }
+
+        string
+            .downcast_ref::<NSString>()
+            .map(NSString::to_string)
+            .unwrap_or_default()
+    }
 }
 
 mod clipboard {
-    use crate::{DropEffect, Exchange};
-    use objc2::ClassType;
+    use crate::{ClipboardError, ClipboardFormat, DropEffect, Exchange, Image, Size};
     use objc2::rc::Retained;
     use objc2::runtime::ProtocolObject;
+    use objc2::{AllocAnyThread, ClassType};
     use objc2_app_kit::{
-        NSDragOperation, NSPasteboard, NSPasteboardTypeString,
-        NSPasteboardURLReadingFileURLsOnlyKey, NSPasteboardWriting,
+        NSBitmapFormat, NSBitmapImageFileType, NSBitmapImageRep, NSDeviceRGBColorSpace,
+        NSDragOperation, NSPasteboard, NSPasteboardTypePNG, NSPasteboardTypeString,
+        NSPasteboardTypeTIFF, NSPasteboardURLReadingFileURLsOnlyKey, NSPasteboardWriting,
     };
-    use objc2_foundation::{NSArray, NSDictionary, NSNumber, NSString, NSURL};
+    use objc2_foundation::{NSArray, NSData, NSDictionary, NSNumber, NSString, NSURL};
     use std::path::PathBuf;
+    use std::ptr::NonNull;
 
-    pub fn set_pasteboard(pasteboard: &NSPasteboard, data: Exchange) -> bool {
+    pub fn set_pasteboard(pasteboard: &NSPasteboard, data: Exchange) -> Result<(), ClipboardError> {
         pasteboard.clearContents();
 
-        match data {
+        let is_written = match data {
             Exchange::Empty => true,
-            Exchange::Text(text) => {
-                let string = ProtocolObject::from_retained(NSString::from_str(&text));
-                pasteboard.writeObjects(&NSArray::from_retained_slice(&[string]))
-            }
-            Exchange::Files(files) => {
-                let uri_list = encode_uri_list(&files);
-                if uri_list.is_empty() {
-                    return false;
+            // TIFF for the applications that do not read PNG
+            Exchange::Image(image) => unsafe {
+                let bitmap = encode_image(&image);
+                let png = bitmap.as_ref().and_then(|bitmap| {
+                    bitmap.representationUsingType_properties(
+                        NSBitmapImageFileType::PNG,
+                        &NSDictionary::new(),
+                    )
+                });
+                let tiff = bitmap.and_then(|bitmap| bitmap.TIFFRepresentation());
+
+                png.is_some_and(|png| pasteboard.setData_forType(Some(&png), NSPasteboardTypePNG))
+                    && tiff.is_some_and(|tiff| {
+                        pasteboard.setData_forType(Some(&tiff), NSPasteboardTypeTIFF)
+                    })
+            },
+            Exchange::Custom { format, data } => pasteboard.setData_forType(
+                Some(&NSData::with_bytes(&data)),
+                &NSString::from_str(&format),
+            ),
+            _ => {
+                // none of the files exist
+                let writers = pasteboard_writers(&data);
+                if writers.is_empty() {
+                    return Err(ClipboardError::UnsupportedFormat);
                 }
 
-                pasteboard.writeObjects(&NSArray::from_retained_slice(&uri_list))
+                pasteboard.writeObjects(&NSArray::from_retained_slice(&writers))
             }
+        };
+
+        match is_written {
+            true => Ok(()),
+            false => Err(ClipboardError::AccessDenied),
+        }
+    }
+
+    /// Checks if the pasteboard holds no data of any type.
+    pub fn is_pasteboard_empty(pasteboard: &NSPasteboard) -> bool {
+        pasteboard.types().is_none_or(|types| types.count() == 0)
+    }
+
+    /// Read the first of `formats` the pasteboard holds, [`Exchange::Empty`]
+    /// if it holds none of them.
+    pub fn get_pasteboard(pasteboard: &NSPasteboard, formats: &[ClipboardFormat]) -> Exchange {
+        formats
+            .iter()
+            .find_map(|format| unsafe {
+                match format {
+                    ClipboardFormat::Files => decode_uri_list(pasteboard).map(Exchange::Files),
+                    ClipboardFormat::Text => pasteboard
+                        .stringForType(NSPasteboardTypeString)
+                        .map(|string| Exchange::Text(string.to_string())),
+                    // most applications provide TIFF, which decodes just like PNG
+                    ClipboardFormat::Image => pasteboard
+                        .dataForType(NSPasteboardTypePNG)
+                        .or_else(|| pasteboard.dataForType(NSPasteboardTypeTIFF))
+                        .and_then(|data| NSBitmapImageRep::imageRepWithData(&data))
+                        .and_then(|bitmap| decode_image(&bitmap))
+                        .map(Exchange::Image),
+                    ClipboardFormat::Custom(name) => pasteboard
+                        .dataForType(&NSString::from_str(name))
+                        .map(|data| Exchange::Custom {
+                            format: name.clone(),
+                            data: data.to_vec(),
+                        }),
+                }
+            })
+            .unwrap_or(Exchange::Empty)
+    }
+
+    /// The objects to put on a pasteboard for the data, one per file. Files
+    /// that do not exist are left out.
+    pub fn pasteboard_writers(
+        data: &Exchange,
+    ) -> Vec<Retained<ProtocolObject<dyn NSPasteboardWriting>>> {
+        match data {
+            Exchange::Empty => Vec::new(),
+            Exchange::Text(text) => vec![ProtocolObject::from_retained(NSString::from_str(text))],
+            Exchange::Files(files) => encode_uri_list(files),
+            // written by `set_pasteboard` itself, and never dragged
+            Exchange::Image(_) | Exchange::Custom { .. } => Vec::new(),
         }
     }
 
-    pub fn get_pasteboard(pasteboard: &NSPasteboard) -> Exchange {
+    /// A bitmap of the pixels of the image, to be encoded by the system.
+    fn encode_image(image: &Image) -> Option<Retained<NSBitmapImageRep>> {
+        let Size { width, height } = image.size();
         unsafe {
-            if let Some(files) = decode_uri_list(pasteboard) {
-                return Exchange::Files(files);
-            }
+            // with no planes given the bitmap allocates its own memory
+            let bitmap = NSBitmapImageRep::initWithBitmapDataPlanes_pixelsWide_pixelsHigh_bitsPerSample_samplesPerPixel_hasAlpha_isPlanar_colorSpaceName_bitmapFormat_bytesPerRow_bitsPerPixel(
+                NSBitmapImageRep::alloc(),
+                std::ptr::null_mut(),
+                width as isize,
+                height as isize,
+                8,
+                4,
+                true,
+                false,
+                NSDeviceRGBColorSpace,
+                NSBitmapFormat::AlphaNonpremultiplied,
+                width as isize * 4,
+                32,
+            )?;
 
-            if let Some(string) = pasteboard.stringForType(NSPasteboardTypeString) {
-                return Exchange::Text(string.to_string());
+            let data = bitmap.bitmapData();
+            if data.is_null() {
+                return None;
             }
 
-            Exchange::Empty
+            std::ptr::copy_nonoverlapping(image.pixels().as_ptr(), data, image.pixels().len());
+            Some(bitmap)
         }
     }
 
+    /// The pixels of a bitmap the system decoded as 8-bit RGBA, whatever its
+    /// layout. `None` for floating point samples and for color spaces other
+    /// than gray and RGB.
+    fn decode_image(bitmap: &NSBitmapImageRep) -> Option<Image> {
+        let width = u32::try_from(bitmap.pixelsWide()).ok()?;
+        let height = u32::try_from(bitmap.pixelsHigh()).ok()?;
+        let format = bitmap.bitmapFormat();
+        let depth = bitmap.bitsPerSample();
+        let has_alpha = bitmap.hasAlpha();
+        let colors = (bitmap.samplesPerPixel() as usize).checked_sub(has_alpha as usize)?;
+        if format.contains(NSBitmapFormat::FloatingPointSamples)
+            || !(1..=16).contains(&depth)
+            || !matches!(colors, 1 | 3)
+        {
+            return None;
+        }
+
+        let max = (1 << depth) - 1;
+        let premultiplied = has_alpha && !format.contains(NSBitmapFormat::AlphaNonpremultiplied);
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                // one value per sample, planar or not
+                let mut samples = [0usize; 4];
+                unsafe { bitmap.getPixel_atX_y(NonNull::from(&mut samples).cast(), x, y) };
+
+                let (alpha, color) = match (has_alpha, format.contains(NSBitmapFormat::AlphaFirst))
+                {
+                    (false, _) => (max, samples.get(..colors)?),
+                    (true, true) => (*samples.first()?, samples.get(1..=colors)?),
+                    (true, false) => (*samples.get(colors)?, samples.get(..colors)?),
+                };
+
+                let alpha = alpha.min(max);
+                let to_8bit = |value: usize| {
+                    let value = match premultiplied && alpha > 0 {
+                        true => value * max / alpha,
+                        false => value,
+                    };
+                    (value.min(max) * 255 / max) as u8
+                };
+
+                rgba.extend(match *color {
+                    [gray] => [to_8bit(gray), to_8bit(gray), to_8bit(gray)],
+                    [r, g, b] => [to_8bit(r), to_8bit(g), to_8bit(b)],
+                    _ => return None,
+                });
+                rgba.push((alpha * 255 / max) as u8);
+            }
+        }
+
+        Image::new((width, height), rgba)
+    }
+
     fn encode_uri_list(
         files: &[PathBuf],
     ) -> Vec<Retained<ProtocolObject<dyn NSPasteboardWriting>>> {
@@ -1,7 +1,17 @@
 use super::display::*;
+use super::event_loop;
+#[cfg(feature = "opengl")]
 use crate::platform::mac::gl::GlContext;
+use crate::platform::mac::metal::MetalLayer;
+use crate::platform::mac::present::PixelLayer;
 use crate::platform::mac::util::*;
-use crate::platform::{OpenMode, PlatformOpenGl, PlatformWaker, PlatformWindow};
+use crate::platform::{
+    Damage, EventQueue, FrameJitter, KeyState, OpenMode, OpenUrlCallback, PlatformWaker,
+    PlatformWindow, QueueKind, Watchdog, WindowEntry, clipboard_formats, open_url_in_background,
+    report_reentrancy, utf16_range_to_bytes,
+};
+#[cfg(feature = "opengl")]
+use crate::platform::{PlatformOpenGl, create_opengl};
 use crate::*;
 use block2::RcBlock;
 use objc2::declare::ClassBuilder;
@@ -14,58 +24,160 @@ use objc2::{
 };
 use objc2_app_kit::{
     NSApp, NSApplication, NSApplicationActivationPolicy, NSAutoresizingMaskOptions,
-    NSBackingStoreType, NSCursor, NSDragOperation, NSDraggingInfo, NSEvent, NSEventMask,
-    NSEventModifierFlags, NSEventType, NSPasteboard, NSPasteboardTypeFileURL,
-    NSPasteboardTypeString, NSTrackingArea, NSTrackingAreaOptions, NSView,
-    NSViewFrameDidChangeNotification, NSWindow, NSWindowDelegate,
-    NSWindowDidChangeOcclusionStateNotification, NSWindowDidResignKeyNotification,
-    NSWindowOcclusionState, NSWindowOrderingMode, NSWindowStyleMask,
+    NSBackingStoreType, NSCursor, NSDragOperation, NSDraggingContext, NSDraggingInfo,
+    NSDraggingItem, NSDraggingSession, NSDraggingSource, NSEvent, NSEventMask, NSEventType,
+    NSPasteboard, NSPasteboardTypeFileURL, NSPasteboardTypeString, NSPointingDeviceType, NSScreen,
+    NSTrackingArea, NSTrackingAreaOptions, NSView, NSViewFrameDidChangeNotification, NSWindow,
+    NSWindowDelegate, NSWindowDidChangeOcclusionStateNotification,
+    NSWindowDidChangeScreenNotification, NSWindowDidChangeScreenProfileNotification,
+    NSWindowDidResignKeyNotification, NSWindowOcclusionState, NSWindowOrderingMode,
+    NSWindowStyleMask, NSWindowWillCloseNotification, NSWorkspace, NSWorkspaceDidWakeNotification,
 };
-use objc2_core_foundation::{CGPoint, CGSize};
+use objc2_core_foundation::{CFRetained, CFRunLoop, CFRunLoopSource, CGPoint, CGSize};
 use objc2_core_graphics::CGWarpMouseCursorPosition;
 use objc2_foundation::{
-    NSArray, NSNotification, NSNotificationCenter, NSObjectNSThreadPerformAdditions, NSPoint,
-    NSRect, NSSize, NSString,
+    NSArray, NSAttributedString, NSNotFound, NSNotification, NSNotificationCenter, NSPoint,
+    NSRange, NSRect, NSRunLoop, NSRunLoopCommonModes, NSSize, NSString, NSTimer, ns_string,
 };
+use std::any::{Any, TypeId};
 use std::cell::{Cell, RefCell};
-use std::collections::VecDeque;
 use std::ffi::{CString, c_void};
 use std::ops::Deref;
 use std::ptr::{NonNull, null, null_mut};
+use std::rc::Rc;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 const STYLE_MASK_NORMAL: NSWindowStyleMask = NSWindowStyleMask::Titled
     .union(NSWindowStyleMask::Closable)
     .union(NSWindowStyleMask::Miniaturizable)
     .union(NSWindowStyleMask::Resizable);
 
+/// `NSViewNoIntrinsicMetric`, an axis without an intrinsic size
+const NO_INTRINSIC_METRIC: f64 = -1.0;
+
 #[repr(C)]
 pub struct WindowImpl {
     view: NSView,
 }
 
 pub struct WindowImplInner {
-    _display_link: DisplayLink,
+    display_link: DisplayLink,
     key_event_monitor: Option<Retained<AnyObject>>,
+    /// Set for top-level windows, which keep the event loop running until
+    /// they are closed.
     application: RefCell<Option<Retained<NSApplication>>>,
 
+    #[cfg(feature = "opengl")]
     gl_context: Result<GlContext, OpenGlError>,
+    #[cfg(feature = "opengl")]
+    external_gl_context: Option<ExternalGlContext>,
+    /// See [`PlatformWindow::metal_layer`], only created if requested.
+    metal_layer: Option<MetalLayer>,
     waker: Arc<WindowWakerImpl>,
 
+    /// Events sent while the handler is busy or not set yet, see
+    /// [`WindowImpl::deferred_event`].
     #[allow(clippy::type_complexity)]
-    event_deferred: RefCell<VecDeque<Box<dyn FnOnce(&WindowImpl, &mut dyn WindowHandler)>>>,
+    event_deferred: EventQueue<dyn FnOnce(&WindowImpl, &mut dyn WindowHandler)>,
     event_handler: RefCell<Option<Box<dyn WindowHandler>>>,
+    user_data: RefCell<Option<Rc<dyn Any>>>,
+    watchdog: Watchdog,
+    frame_jitter: FrameJitter,
+    /// See [`Window::is_key_down`].
+    keys_down: KeyState,
+    /// Set by [`Window::redraw_now`] while the handler is busy, the frame is
+    /// dispatched once the current event returns.
+    redraw_pending: Cell<bool>,
+    group: Option<WindowGroup>,
+    /// Registers the view for [`crate::window_at`], provides
+    /// [`PlatformWindow::id`].
+    entry: WindowEntry,
 
     last_cursor_icon: Cell<MouseCursor>,
     last_window_size: Cell<Size>,
     last_view_hidden: Cell<bool>,
+    last_safe_area: Cell<Insets>,
+    last_event_token: Cell<EventToken>,
+    /// Mouse buttons pressed inside of the view and not yet released, as a
+    /// mask of `1 << button`. AppKit keeps sending mouse events to the view
+    /// while any of them is held.
+    last_buttons_held: Cell<u32>,
+    /// Whether the view is the first responder, see
+    /// [`WindowHandler::focus_changed`]
+    last_view_focused: Cell<bool>,
+    /// The last mouse down event, AppKit starts a dragging session from it,
+    /// see [`PlatformWindow::start_drag`].
+    last_mouse_down: RefCell<Option<Retained<NSEvent>>>,
+    /// Whether a dragging session we started is running.
+    is_dragging: Cell<bool>,
+    /// The edge, the mouse location and the window frame a resize started
+    /// with, see [`PlatformWindow::begin_drag_resize`]. AppKit has no call to
+    /// let the system do it, so the view follows the mouse until the release.
+    resize_drag: Cell<Option<(ResizeEdge, NSPoint, NSRect)>>,
+    /// See [`PlatformWindow::set_cursor_grab`].
+    cursor_grab: Cell<CursorGrab>,
+    /// While the cursor is locked, the virtual position reported instead of
+    /// the real one. The cursor is detached from the mouse meanwhile.
+    cursor_lock: Cell<Option<Point>>,
+    /// The `CGDirectDisplayID` of the screen the window was last seen on.
+    last_display_id: Cell<Option<u32>>,
+    /// The ICC profile of that screen, see [`PlatformWindow::color_profile`].
+    last_color_profile: RefCell<Option<IccProfile>>,
+    /// Shows what [`PlatformWindow::present_pixels`] presented, created on
+    /// first use.
+    pixel_layer: RefCell<Option<PixelLayer>>,
+    /// The timers set with [`PlatformWindow::set_timer`] by their id.
+    timers: RefCell<Vec<(u32, Retained<NSTimer>)>>,
+    /// Whether the mouse is inside of the view, between `mouseEntered:` and
+    /// `mouseExited:`.
+    cursor_inside: Cell<bool>,
+    /// Whether we called `NSCursor::hide` without a matching `unhide`.
+    cursor_hidden: Cell<bool>,
+    /// The text being composed by the input method (the marked text), `None`
+    /// if there is no composition in progress.
+    marked_text: RefCell<Option<String>>,
+    /// Set while the key event monitor lets the input context handle a key
+    /// down. Text inserted outside of it comes from elsewhere (like the emoji
+    /// picker) and is not covered by a key press.
+    is_handling_key: Cell<bool>,
+    /// The area set with [`Window::set_ime_cursor_area`], reported to the
+    /// input method by `firstRectForCharacterRange:actualRange:`.
+    ime_cursor_area: Cell<Option<Rect>>,
+
+    min_window_size: Cell<Size>,
+    max_window_size: Cell<Size>,
+    resize_hints: Cell<ResizeHints>,
 
     is_closed: Cell<bool>,
+    /// Set when the window is closed because its parent window closed, see
+    /// [`DestroyReason::Destroyed`]
+    is_parent_closed: Cell<bool>,
     is_embedded: bool,
+    /// See [`WindowBuilder::with_unmatched_mouse_release`].
+    unmatched_mouse_release: bool,
+    /// See [`WindowBuilder::with_raw_scroll`].
+    raw_scroll: bool,
+    /// Position of an embedded view in physical pixels, relative to the
+    /// top-left corner of the superview whichever way its y axis points
+    embedded_position: Cell<Point>,
+    /// Should the view become the first responder once it is attached to a
+    /// window? See [`WindowBuilder::with_initial_keyboard_focus`].
+    is_focus_pending: Cell<bool>,
 }
 
+/// Signals a run loop source on the main thread, which is allocation-free and
+/// does not take any locks (unlike `performSelectorOnMainThread`).
 struct WindowWakerImpl {
-    weak: Weak<WindowImpl>,
+    source: CFRetained<CFRunLoopSource>,
+    is_closed: AtomicBool,
+    /// Set by [`PlatformWaker::close`], the source closes the window instead
+    /// of calling [`WindowHandler::wakeup`]
+    close_requested: AtomicBool,
+    /// Our view, only loaded on the main thread by
+    /// [`PlatformWaker::close_and_wait`]
+    view: Weak<WindowImpl>,
 }
 
 unsafe impl Send for WindowWakerImpl {}
@@ -86,30 +198,70 @@ impl Deref for WindowImpl {
 
 // rust methods stuff
 impl WindowImpl {
-    pub unsafe fn open(options: WindowBuilder, mode: OpenMode) -> Result<WindowWaker, WindowError> {
+    /// See [`crate::window_at`]. Only our own windows can be hit tested, and
+    /// only on the main thread.
+    pub fn window_at(point: Point) -> Option<WindowId> {
+        let main_thread = MainThreadMarker::new()?;
+        let point = screen_point_to_cocoa(point, main_thread);
+
+        let number =
+            NSWindow::windowNumberAtPoint_belowWindowWithWindowNumber(point, 0, main_thread);
+        let window = NSApp(main_thread).windowWithWindowNumber(number)?;
+
+        // hit testing starts from the frame view, its coordinates are the window's
+        let root = window.contentView()?;
+        let root = unsafe { root.superview() }.unwrap_or(root);
+        let mut view = root.hitTest(window.convertPointFromScreen(point));
+
+        while let Some(current) = view {
+            if let Some(id) = WindowEntry::find(Retained::as_ptr(&current) as usize) {
+                return Some(id);
+            }
+
+            view = unsafe { current.superview() };
+        }
+
+        None
+    }
+
+    pub unsafe fn open(
+        mut options: WindowBuilder,
+        mode: OpenMode,
+    ) -> Result<WindowWaker, WindowError> {
         let main_thread = MainThreadMarker::new()
             .ok_or_else(|| WindowError::Platform("not on main thread".into()))?;
 
         match mode {
-            OpenMode::Blocking => unsafe {
+            OpenMode::TopLevel => unsafe {
                 let app = NSApp(main_thread);
                 app.setActivationPolicy(NSApplicationActivationPolicy::Regular);
 
-                let window = Self::create_window(main_thread)?;
-                let view = Self::create_view(&options, Some(app.clone()), false, main_thread)?;
+                let window = Self::create_window(STYLE_MASK_NORMAL, main_thread)?;
+                let view = Self::create_view(&mut options, false, main_thread)?;
 
                 window.setContentView(Some(&view.view));
                 window.makeFirstResponder(Some(&view.view));
                 window.setReleasedWhenClosed(true);
                 window.setDelegate(Some(view.as_ns_window_delegate()));
 
+                // top-level windows in a group are children of the group leader's window
+                if let Some(leader) = Self::group_leader_window(&options) {
+                    leader.addChildWindow_ordered(&window, NSWindowOrderingMode::Above);
+                }
+
                 WindowImpl::init_handler(&view, options.factory)?;
+                view.join_group(options.group);
+
+                // counted until closed, the event loop runs until then
+                if !view.is_closed() {
+                    view.application.replace(Some(app));
+                    event_loop::add();
+                }
 
-                app.run();
-                Ok(WindowWaker::default())
+                Ok(view.waker())
             },
 
-            OpenMode::Transient(parent) => unsafe {
+            OpenMode::Transient(parent) | OpenMode::Popup(parent, _) => unsafe {
                 let parent_view = match parent {
                     rwh_06::RawWindowHandle::AppKit(window) => {
                         &*(window.ns_view.as_ptr() as *mut NSView)
@@ -117,17 +269,61 @@ impl WindowImpl {
                     _ => return Err(WindowError::InvalidParent),
                 };
 
-                let window = Self::create_window(main_thread)?;
-                let view = Self::create_view(&options, None, false, main_thread)?;
+                let style = match mode {
+                    OpenMode::Popup(..) => NSWindowStyleMask::Borderless,
+                    _ => STYLE_MASK_NORMAL,
+                };
+
+                let window = Self::create_window(style, main_thread)?;
+                let view = Self::create_view(&mut options, false, main_thread)?;
 
                 window.setContentView(Some(&view.view));
                 window.makeFirstResponder(Some(&view.view));
                 window.setDelegate(Some(view.as_ns_window_delegate()));
 
                 WindowImpl::init_handler(&view, options.factory)?;
+                view.join_group(options.group);
 
                 if let Some(parent_window) = parent_view.window() {
+                    // popups open right below their anchor. placed after the factory, resizing
+                    // keeps the bottom-left corner in place
+                    if let OpenMode::Popup(_, anchor) = mode {
+                        let point = parent_view.convertPointFromBacking(NSPoint::new(
+                            anchor.left as _,
+                            anchor.bottom as _,
+                        ));
+                        let point = parent_view.convertPoint_toView(point, None);
+                        window.setFrameTopLeftPoint(parent_window.convertPointToScreen(point));
+                    }
+
                     parent_window.addChildWindow_ordered(&window, NSWindowOrderingMode::Above);
+
+                    // child windows outlive their parent in AppKit, close ourselves with it
+                    let center = NSNotificationCenter::defaultCenter();
+                    let object: &AnyObject = &parent_window;
+                    center.addObserver_selector_name_object(
+                        &view.view,
+                        sel!(parentWindowWillClose:),
+                        Some(NSWindowWillCloseNotification),
+                        Some(object),
+                    );
+
+                    // popups are dismissed when the window they belong to loses the focus.
+                    // popups of popups never have it, follow them up to a window that can
+                    if let OpenMode::Popup(..) = mode {
+                        let mut owner = parent_window.clone();
+                        while let Some(window) = owner.parentWindow() {
+                            owner = window;
+                        }
+
+                        let object: &AnyObject = &owner;
+                        center.addObserver_selector_name_object(
+                            &view.view,
+                            sel!(popupOwnerDidResignKey:),
+                            Some(NSWindowDidResignKeyNotification),
+                            Some(object),
+                        );
+                    }
                 }
 
                 Ok(view.waker())
@@ -141,16 +337,37 @@ impl WindowImpl {
                     _ => return Err(WindowError::InvalidParent),
                 };
 
-                let view = Self::create_view(&options, None, true, main_thread)?;
+                let view = Self::create_view(&mut options, true, main_thread)?;
                 WindowImpl::init_handler(&view, options.factory)?;
                 parent_view.addSubview(&view.view);
+                view.join_group(options.group);
 
                 Ok(view.waker())
             },
         }
     }
 
+    /// The window of the group leader, if the window is opened in a group
+    /// that already has one.
+    unsafe fn group_leader_window(options: &WindowBuilder) -> Option<Retained<NSWindow>> {
+        match options.group.as_ref()?.leader()? {
+            rwh_06::RawWindowHandle::AppKit(handle) => unsafe {
+                (*(handle.ns_view.as_ptr() as *mut NSView)).window()
+            },
+            _ => None,
+        }
+    }
+
+    /// Add the opened window to `group`, if any. Our view is used as the
+    /// member handle, the leader window is looked up from it when needed.
+    fn join_group(&self, group: Option<WindowGroup>) {
+        if let Some(group) = group {
+            group.insert(self.window_handle(), self.waker());
+        }
+    }
+
     unsafe fn create_window(
+        style: NSWindowStyleMask,
         main_thread: MainThreadMarker,
     ) -> Result<Retained<NSWindow>, WindowError> {
         unsafe {
@@ -158,7 +375,7 @@ impl WindowImpl {
             let window = NSWindow::initWithContentRect_styleMask_backing_defer(
                 window,
                 NSRect::new(CGPoint::default(), NSSize::new(1.0, 1.0)),
-                STYLE_MASK_NORMAL,
+                style,
                 NSBackingStoreType::Buffered,
                 false,
             );
@@ -168,8 +385,7 @@ impl WindowImpl {
     }
 
     unsafe fn create_view(
-        options: &WindowBuilder,
-        blocking: Option<Retained<NSApplication>>,
+        options: &mut WindowBuilder,
         is_embedded: bool,
         main_thread: MainThreadMarker,
     ) -> Result<Retained<Self>, WindowError> {
@@ -195,25 +411,14 @@ impl WindowImpl {
             view.view.addTrackingArea(&tracking_area);
             view.view.registerForDraggedTypes(&dragged_types);
             view.view.setPostsFrameChangedNotifications(true);
-            view.view
-                .setAutoresizingMask(NSAutoresizingMaskOptions::empty());
+            view.view.setAutoresizingMask(NSAutoresizingMaskOptions(
+                options.platform_options.macos_autoresizing_mask as _,
+            ));
             view.view.setAutoresizesSubviews(false);
 
-            if is_embedded {
-                NSNotificationCenter::defaultCenter().addObserver_selector_name_object(
-                    &view.view,
-                    sel!(windowDidResignKey:),
-                    Some(NSWindowDidResignKeyNotification),
-                    None,
-                );
-
-                NSNotificationCenter::defaultCenter().addObserver_selector_name_object(
-                    &view.view,
-                    sel!(windowDidChangeOcclusionState:),
-                    Some(NSWindowDidChangeOcclusionStateNotification),
-                    None,
-                );
-            }
+            // always layer-backed, so layers attached by graphics APIs (Metal surfaces from
+            // wgpu and the like) keep working when the view is moved to another window
+            view.view.setWantsLayer(true);
 
             NSNotificationCenter::defaultCenter().addObserver_selector_name_object(
                 &view.view,
@@ -222,20 +427,42 @@ impl WindowImpl {
                 None,
             );
 
+            // the only one of the system metrics with a notification, the others are
+            // plain user defaults
+            NSNotificationCenter::defaultCenter().addObserver_selector_name_object(
+                &view.view,
+                sel!(preferredScrollerStyleDidChange:),
+                Some(ns_string!("NSPreferredScrollerStyleDidChangeNotification")),
+                None,
+            );
+
+            // workspace notifications are only posted to the workspace center
+            NSWorkspace::sharedWorkspace()
+                .notificationCenter()
+                .addObserver_selector_name_object(
+                    &view.view,
+                    sel!(workspaceDidWake:),
+                    Some(NSWorkspaceDidWakeNotification),
+                    None,
+                );
+
             view
         };
 
         // opengl context if requested
-        let gl_context = options
-            .opengl
-            .map(|opts| GlContext::new(&view.view, opts, main_thread))
-            .unwrap_or_else(|| Err(OpenGlError::NotRequested));
+        #[cfg(feature = "opengl")]
+        let gl_context = create_opengl(&options.opengl, |config, last_resort| {
+            GlContext::new(&view.view, *config, last_resort, main_thread)
+        });
+        #[cfg(not(feature = "opengl"))]
+        let _ = main_thread;
 
         // vsync synced [`WindowFrame`] events
         let display_link = {
             let view = Weak::from_retained(&view);
             DisplayLink::new(Box::new(move || {
                 if let Some(view) = view.load() {
+                    view.frame_jitter.measure(Instant::now());
                     view.non_reentrant_event(|e| e.frame());
                 }
             }))?
@@ -253,14 +480,33 @@ impl WindowImpl {
                         return NonNull::from(event).as_ptr();
                     };
 
+                    view.track_event(event);
+
+                    // keys that drive a composition belong to the input method
+                    if event.r#type() == NSEventType::KeyDown && view.handle_text_input(event) {
+                        return null_mut();
+                    }
+
                     let Some(key) = keycode_to_key(event.keyCode()) else {
                         return NonNull::from(event).as_ptr();
                     };
 
                     let is_down = event.r#type() == NSEventType::KeyDown;
-                    let capture = view
-                        .non_reentrant_event(|e| e.key_press(key, is_down))
-                        .unwrap_or(false);
+                    let is_repeat = is_down && event.isARepeat();
+                    view.keys_down.set(key, is_down);
+                    let text = if is_down { event_to_char(event) } else { None };
+                    let capture = match view.event_handler.try_borrow().map(|x| x.is_none()) {
+                        // the factory is still running, replay the key once the handler is set
+                        Ok(true) => {
+                            view.deferred_event(move |_, e| {
+                                e.key_press(key, is_down, is_repeat, text);
+                            });
+                            false
+                        }
+                        _ => view
+                            .non_reentrant_event(|e| e.key_press(key, is_down, is_repeat, text))
+                            .unwrap_or(false),
+                    };
 
                     match capture {
                         true => null_mut(),
@@ -271,25 +517,80 @@ impl WindowImpl {
         };
 
         view.set_inner(Some(Box::new(WindowImplInner {
-            _display_link: display_link,
+            display_link,
             key_event_monitor,
 
-            application: RefCell::new(blocking),
+            application: RefCell::new(None),
+            #[cfg(feature = "opengl")]
             gl_context,
+            #[cfg(feature = "opengl")]
+            external_gl_context: options.external_opengl.take(),
+            metal_layer: options
+                .metal_layer
+                .then(|| MetalLayer::new(&view.view))
+                .flatten(),
 
             waker: Arc::new(WindowWakerImpl {
-                weak: Weak::from_retained(&view),
+                source: {
+                    let view = Weak::from_retained(&view);
+                    main_run_loop_source(Box::new(move || {
+                        if let Some(view) = view.load() {
+                            if view.waker.close_requested.swap(false, Ordering::Relaxed) {
+                                view.close();
+                            } else {
+                                view.deferred_lossy_event(|_, e| e.wakeup());
+                            }
+                        }
+                    }))?
+                },
+                is_closed: AtomicBool::new(false),
+                close_requested: AtomicBool::new(false),
+                view: Weak::from_retained(&view),
             }),
 
-            event_deferred: RefCell::new(VecDeque::new()),
+            event_deferred: EventQueue::new(),
             event_handler: RefCell::new(None),
-
-            last_cursor_icon: Cell::new(MouseCursor::Default),
+            user_data: RefCell::new(None),
+            watchdog: Watchdog::new(options.watchdog),
+            frame_jitter: FrameJitter::new(),
+            keys_down: KeyState::new(),
+            redraw_pending: Cell::new(false),
+            group: options.group.clone(),
+            entry: WindowEntry::new(Retained::as_ptr(&view) as usize),
+
+            last_cursor_icon: Cell::new(options.cursor),
             last_window_size: Cell::new(Size::default()),
             last_view_hidden: Cell::new(false),
+            last_safe_area: Cell::new(Insets::default()),
+            last_event_token: Cell::new(EventToken::default()),
+            last_buttons_held: Cell::new(0),
+            last_view_focused: Cell::new(false),
+            last_mouse_down: RefCell::new(None),
+            resize_drag: Cell::new(None),
+            cursor_grab: Cell::new(CursorGrab::None),
+            cursor_lock: Cell::new(None),
+            is_dragging: Cell::new(false),
+            last_display_id: Cell::new(None),
+            last_color_profile: RefCell::new(None),
+            pixel_layer: RefCell::new(None),
+            timers: RefCell::new(Vec::new()),
+            cursor_inside: Cell::new(false),
+            cursor_hidden: Cell::new(false),
+            marked_text: RefCell::new(None),
+            is_handling_key: Cell::new(false),
+            ime_cursor_area: Cell::new(None),
+
+            min_window_size: Cell::new(Size::MIN),
+            max_window_size: Cell::new(Size::MAX),
+            resize_hints: Cell::new(ResizeHints::default()),
 
             is_closed: Cell::new(false),
+            is_parent_closed: Cell::new(false),
             is_embedded,
+            unmatched_mouse_release: options.unmatched_mouse_release,
+            raw_scroll: options.raw_scroll,
+            embedded_position: Cell::new(options.parent_relative_position),
+            is_focus_pending: Cell::new(is_embedded && options.initial_keyboard_focus),
         })));
 
         Ok(view)
@@ -313,33 +614,58 @@ impl WindowImpl {
         };
 
         this.event_handler.replace(Some(handler));
+
+        // deliver the events that arrived while the factory was running
+        this.deferred_event(|_, _| {});
         Ok(())
     }
 
-    /// Run a closure with exclusive access to the window's event handler.
+    /// Run a closure with exclusive access to the window's event handler, then
+    /// run the tasks deferred while it was running.
+    ///
+    /// Returns `None` without running the closure if the handler is not set or
+    /// already running (see the reentrancy notes on [`WindowHandler`]). To
+    /// deliver an event later instead, use [`Self::deferred_event`].
     ///
-    /// Panics if [`Self::non_reentrant_event`] is called inside of another
-    /// [`Self::non_reentrant_event`]. To safely post a task, use
-    /// [`Self::post_deferred`].
+    /// Once the window is closing nothing is delivered anymore, the handler
+    /// only gets [`WindowHandler::destroyed`] before the window goes away.
     fn non_reentrant_event<R>(&self, call: impl FnOnce(&mut dyn WindowHandler) -> R) -> Option<R> {
-        let mut handler = self
-            .event_handler
-            .try_borrow_mut()
-            .expect("unhandled callback reentrancy");
+        if self.is_closed.get() {
+            return None;
+        }
+
+        let Ok(mut handler) = self.event_handler.try_borrow_mut() else {
+            report_reentrancy(false);
+            return None;
+        };
 
         // handler might be None if the window is being dropped, in which case we return
         // None
         if let Some(handler) = handler.as_mut() {
-            let result = Some(call(&mut **handler));
+            let result = Some(self.watchdog.measure(|| call(&mut **handler)));
 
-            loop {
+            // the handler may close the window at any point, stop right there
+            while !self.is_closed.get() {
                 // event_queue must NOT be borrowed while calling the handler, so we have to
                 // reborrow it every time
-                let Some(event) = self.event_deferred.borrow_mut().pop_front() else {
+                let Some(event) = self.event_deferred.pop() else {
                     break;
                 };
 
-                event(self, &mut **handler);
+                self.watchdog.measure(|| event(self, &mut **handler));
+            }
+
+            if self.is_closed.get() {
+                self.event_deferred.clear();
+                return result;
+            }
+
+            if self.redraw_pending.take() {
+                self.watchdog.measure(|| handler.frame());
+            }
+
+            if let Some(stall) = self.watchdog.take_stall() {
+                handler.stalled(stall);
             }
 
             result
@@ -350,20 +676,79 @@ impl WindowImpl {
 
     /// Run a closure with exclusive access to the window's event handler.
     ///
-    /// Unlike [`Self::non_reentrant_event`], this function will not panic if
-    /// called inside of another [`Self::non_reentrant_event`]. Instead, the
-    /// closure will be deferred and run later.
+    /// Unlike [`Self::non_reentrant_event`], the closure is not skipped if
+    /// called inside of another [`Self::non_reentrant_event`]. Instead, it is
+    /// deferred and run in order once the handler returns.
     ///
     /// For that reason it cannot return a value, and the closure must be
     /// `'static`.
     fn deferred_event(&self, task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static) {
-        if self.event_handler.try_borrow_mut().is_ok() {
-            self.non_reentrant_event(|handler| task(self, handler));
-        } else {
-            self.event_deferred.borrow_mut().push_back(Box::new(task));
+        self.queue_event(QueueKind::Critical, task);
+    }
+
+    /// Like [`Self::deferred_event`], for events that only report the latest
+    /// state of something. If deferred, it replaces the same event right
+    /// before it in the queue, see [`EventQueue::push`].
+    fn deferred_latest_event<F>(&self, task: F)
+    where
+        F: FnOnce(&Self, &mut dyn WindowHandler) + 'static,
+    {
+        self.queue_event(QueueKind::Latest(TypeId::of::<F>()), task);
+    }
+
+    /// Like [`Self::deferred_event`], for events that can be lost without
+    /// the handler missing a change of state, like scrolls and timer ticks. If
+    /// deferred, it is dropped first when the queue is full, see
+    /// [`EventQueue`].
+    fn deferred_lossy_event(&self, task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static) {
+        self.queue_event(QueueKind::Lossy, task);
+    }
+
+    fn queue_event(
+        &self,
+        kind: QueueKind,
+        task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static,
+    ) {
+        match self.event_handler.try_borrow_mut().map(|x| x.is_some()) {
+            Ok(true) => {
+                self.non_reentrant_event(|handler| task(self, handler));
+            }
+            Ok(false) => {
+                // not initialized yet, delivered once the handler is set
+                self.event_deferred.push(kind, Box::new(task));
+            }
+            Err(_) => {
+                report_reentrancy(true);
+                self.event_deferred.push(kind, Box::new(task));
+            }
         }
     }
 
+    /// Move an embedded view to [`WindowImplInner::embedded_position`]. AppKit
+    /// places views by their bottom-left corner unless the superview is
+    /// flipped, so this has to be redone whenever the size changes.
+    fn update_embedded_origin(&self) {
+        let Some(superview) = (unsafe { self.view.superview() }) else {
+            return;
+        };
+
+        let position = self.embedded_position.get();
+        let offset = self.view.convertSizeFromBacking(CGSize {
+            width: position.x,
+            height: position.y,
+        });
+
+        let area = superview.bounds();
+        let y = if superview.isFlipped() {
+            area.origin.y + offset.height
+        } else {
+            area.origin.y + area.size.height - offset.height - self.view.frame().size.height
+        };
+
+        self.view
+            .setFrameOrigin(NSPoint::new(area.origin.x + offset.width, y));
+    }
+
     fn set_inner(&self, context: Option<Box<WindowImplInner>>) {
         unsafe {
             self.view
@@ -395,6 +780,308 @@ impl WindowImpl {
         }
     }
 
+    /// Stop observing notifications from the window we are in, see
+    /// [`Self::view_did_move_to_window`].
+    fn unobserve_window(&self) {
+        let center = NSNotificationCenter::defaultCenter();
+        unsafe {
+            for name in [
+                NSWindowDidResignKeyNotification,
+                NSWindowDidChangeOcclusionStateNotification,
+                NSWindowDidChangeScreenNotification,
+                NSWindowDidChangeScreenProfileNotification,
+                NSWindowWillCloseNotification,
+            ] {
+                center.removeObserver_name_object(&self.view, Some(name), None);
+            }
+        }
+    }
+
+    /// Let the input context see a key down first, for input methods and dead
+    /// keys. Returns `true` if the key was part of a composition.
+    fn handle_text_input(&self, event: &NSEvent) -> bool {
+        // only the focused view talks to the input method
+        let view = &self.view as *const NSView;
+        let is_focused = self.view.window().is_some_and(|window| {
+            event
+                .window(self.view.mtm())
+                .is_some_and(|target| Retained::as_ptr(&target) == Retained::as_ptr(&window))
+                && window
+                    .firstResponder()
+                    .is_some_and(|responder| Retained::as_ptr(&responder).cast() == view)
+        });
+
+        let Some(context) = is_focused.then(|| self.view.inputContext()).flatten() else {
+            return false;
+        };
+
+        let was_composing = self.marked_text.borrow().is_some();
+        self.is_handling_key.set(true);
+        context.handleEvent(event);
+        self.is_handling_key.set(false);
+
+        was_composing || self.marked_text.borrow().is_some()
+    }
+
+    /// End the composition in progress, if any, with the given text.
+    fn end_composition(&self, text: Option<String>) {
+        if self.marked_text.take().is_some() {
+            self.deferred_event(move |_, e| e.composition_end(text.as_deref()));
+        }
+    }
+
+    /// Remember the timestamp of an input event, see
+    /// [`PlatformWindow::last_event_token`].
+    fn track_event(&self, event: &NSEvent) {
+        self.last_event_token.set(EventToken {
+            time: (event.timestamp() * 1000.0) as u64,
+            serial: 0,
+        });
+    }
+
+    /// Follow the window to the screen it is on now, pointing the display link
+    /// at it and notifying the handler if it changed.
+    fn update_screen(&self) {
+        let Some(display_id) = self
+            .view
+            .window()
+            .and_then(|window| window.screen())
+            .and_then(|screen| screen_display_id(&screen))
+        else {
+            return;
+        };
+
+        if self.last_display_id.replace(Some(display_id)) == Some(display_id) {
+            return;
+        }
+
+        self.display_link.set_display(display_id);
+
+        let monitor = MonitorId::from_raw(display_id.into());
+        self.deferred_event(move |_, e| e.monitor_changed(monitor));
+        self.update_color_profile();
+    }
+
+    /// Report the held buttons as released where the last one was pressed,
+    /// once a dragging session or a resize took over the mouse.
+    fn release_buttons(&self) {
+        let point = self
+            .last_mouse_down
+            .take()
+            .map(|event| self.convert_point_to_picoview(event.locationInWindow()))
+            .unwrap_or_default();
+        let held = self.last_buttons_held.replace(0);
+
+        for button in [
+            MouseButton::Left,
+            MouseButton::Right,
+            MouseButton::Middle,
+            MouseButton::Back,
+            MouseButton::Forward,
+        ] {
+            if held & (1 << button as u32) != 0 {
+                self.deferred_event(move |_, e| e.mouse_press(button, false, point));
+            }
+        }
+
+        self.update_cursor();
+    }
+
+    /// Resize our window for the current mouse location, moving the dragged
+    /// edges and keeping the opposite ones in place.
+    fn update_resize_drag(&self, (edge, start, frame): (ResizeEdge, NSPoint, NSRect)) {
+        let Some(window) = self.own_window() else {
+            return;
+        };
+
+        // which way the dragged edges move the window sides, cocoa coordinates grow upwards
+        let (x, y) = match edge {
+            ResizeEdge::Top => (0.0, 1.0),
+            ResizeEdge::Bottom => (0.0, -1.0),
+            ResizeEdge::Left => (-1.0, 0.0),
+            ResizeEdge::Right => (1.0, 0.0),
+            ResizeEdge::TopLeft => (-1.0, 1.0),
+            ResizeEdge::TopRight => (1.0, 1.0),
+            ResizeEdge::BottomLeft => (-1.0, -1.0),
+            ResizeEdge::BottomRight => (1.0, -1.0),
+        };
+
+        let hints = self.resize_hints.get();
+        let mouse = NSEvent::mouseLocation();
+        let content = window.contentRectForFrameRect(frame);
+        let mut size = content.size;
+
+        if hints.can_resize_horizontally {
+            size.width += x * (mouse.x - start.x);
+        }
+
+        if hints.can_resize_vertically {
+            size.height += y * (mouse.y - start.y);
+        }
+
+        let backing = self.view.convertSizeToBacking(size);
+        let constrained = hints.constrain(
+            Size {
+                width: backing.width.max(0.0).round() as u32,
+                height: backing.height.max(0.0).round() as u32,
+            },
+            self.min_window_size.get(),
+            self.max_window_size.get(),
+        );
+        let size = self.view.convertSizeFromBacking(CGSize {
+            width: constrained.width as f64,
+            height: constrained.height as f64,
+        });
+
+        // keep the opposite sides in place
+        let mut rect = content;
+        if x < 0.0 {
+            rect.origin.x += content.size.width - size.width;
+        }
+
+        if y < 0.0 {
+            rect.origin.y += content.size.height - size.height;
+        }
+
+        rect.size = size;
+        window.setFrame_display(window.frameRectForContentRect(rect), true);
+    }
+
+    /// Keep the Metal layer, if any, covering the view at the current scale.
+    fn update_metal_layer(&self) {
+        if let Some(layer) = &self.metal_layer {
+            layer.update(&self.view, self.scale());
+        }
+    }
+
+    /// Re-read the color profile of the screen the window is on, notifying
+    /// the handler if it changed.
+    fn update_color_profile(&self) {
+        let profile = self
+            .view
+            .window()
+            .and_then(|window| window.screen())
+            .and_then(|screen| screen_color_profile(&screen));
+
+        if *self.last_color_profile.borrow() != profile {
+            self.last_color_profile.replace(profile);
+            self.deferred_event(|_, e| e.color_profile_changed());
+        }
+    }
+
+    /// Re-read the safe area of the view, notifying the handler if it changed.
+    fn update_safe_area(&self) {
+        // safe areas were added in macOS 11
+        let supported: bool =
+            unsafe { msg_send![&*self.view, respondsToSelector: sel!(safeAreaInsets)] };
+        if !supported {
+            return;
+        }
+
+        let logical = self.view.safeAreaInsets();
+        let top_left = self
+            .view
+            .convertSizeToBacking(NSSize::new(logical.left, logical.top));
+        let bottom_right = self
+            .view
+            .convertSizeToBacking(NSSize::new(logical.right, logical.bottom));
+
+        let insets = Insets {
+            top: top_left.height.abs().round() as u32,
+            left: top_left.width.abs().round() as u32,
+            bottom: bottom_right.height.abs().round() as u32,
+            right: bottom_right.width.abs().round() as u32,
+        };
+
+        if self.last_safe_area.replace(insets) != insets {
+            self.deferred_event(move |_, e| e.safe_area_changed(insets));
+        }
+    }
+
+    /// Apply the size limits and resize hints to our own window, locking the
+    /// axes that can not be resized to `size`.
+    fn update_size_constraints(&self, size: Size) {
+        // hosts read the constraints of embedded views through the intrinsic size
+        let _: () = unsafe { msg_send![&self.view, invalidateIntrinsicContentSize] };
+
+        let Some(window) = self.own_window() else {
+            return;
+        };
+
+        let hints = self.resize_hints.get();
+        let (mut min, mut max) = (self.min_window_size.get(), self.max_window_size.get());
+
+        if !hints.can_resize_horizontally {
+            (min.width, max.width) = (size.width, size.width);
+        }
+
+        if !hints.can_resize_vertically {
+            (min.height, max.height) = (size.height, size.height);
+        }
+
+        let to_points = |size: Size| {
+            self.view.convertSizeFromBacking(CGSize {
+                width: size.width as f64,
+                height: size.height as f64,
+            })
+        };
+
+        window.setContentMinSize(to_points(min));
+        window.setContentMaxSize(to_points(max));
+
+        // the aspect ratio and the resize increments override each other, resetting the
+        // increments to 1 clears the aspect ratio
+        match (hints.aspect_ratio, hints.step) {
+            (Some((width, height)), _) => window.setContentAspectRatio(NSSize {
+                width: width as f64,
+                height: height as f64,
+            }),
+            (None, Some(step)) => window.setContentResizeIncrements(to_points(step)),
+            (None, None) => window.setResizeIncrements(NSSize {
+                width: 1.0,
+                height: 1.0,
+            }),
+        }
+    }
+
+    /// Apply the cursor icon set by the handler.
+    ///
+    /// `NSCursor::hide` hides the cursor for the whole application, not just
+    /// over the view, so a hidden cursor is only kept hidden while the mouse is
+    /// inside of the view or dragging from it. Once it leaves (or the view
+    /// closes) the host gets a visible cursor back.
+    /// Lets go of the cursor held with [`PlatformWindow::set_cursor_grab`],
+    /// leaving it where it was locked.
+    fn release_cursor_grab(&self) {
+        if self.cursor_grab.replace(CursorGrab::None) != CursorGrab::None
+            && self.cursor_lock.take().is_some()
+        {
+            set_cursor_attached(true);
+        }
+    }
+
+    fn update_cursor(&self) {
+        let active = !self.is_closed.get()
+            && (self.cursor_inside.get() || self.last_buttons_held.get() != 0);
+        let cursor = match active {
+            true => self.last_cursor_icon.get(),
+            false => MouseCursor::Default,
+        };
+
+        let hidden = cursor == MouseCursor::Hidden;
+        if self.cursor_hidden.replace(hidden) != hidden {
+            if hidden {
+                NSCursor::hide();
+            } else {
+                NSCursor::unhide();
+            }
+        }
+
+        if !hidden {
+            best_cursor_icon_for(cursor).set();
+        }
+    }
+
     fn own_window(&self) -> Option<Retained<NSWindow>> {
         if self.is_embedded {
             None
@@ -424,6 +1111,15 @@ impl WindowImpl {
             std::mem::transmute::<&Self, &ProtocolObject<dyn NSWindowDelegate>>(self)
         }
     }
+
+    fn as_ns_dragging_source(&self) -> &ProtocolObject<dyn NSDraggingSource> {
+        // SAFETY: same as in [`Self::as_ns_window_delegate`], we implement the
+        // NSDraggingSource protocol too
+        #[allow(clippy::transmute_ptr_to_ptr)]
+        unsafe {
+            std::mem::transmute::<&Self, &ProtocolObject<dyn NSDraggingSource>>(self)
+        }
+    }
 }
 
 // objective c class stuff
@@ -443,16 +1139,45 @@ impl WindowImpl {
                 self.set_inner(None);
 
                 // we need to drop this before WindowView gets dropped, see the safety comment
-                // at the handler initialization place
-                inner.event_handler.take();
+                // at the handler initialization place. the view is only released without
+                // being closed when the host lets go of it
+                if let Some(mut handler) = inner.event_handler.take() {
+                    handler.destroyed(
+                        match inner.is_closed.get() && !inner.is_parent_closed.get() {
+                            true => DestroyReason::Closed,
+                            false => DestroyReason::Destroyed,
+                        },
+                    );
+                }
+
+                // the host may release the view without closing it
+                if inner.cursor_hidden.get() {
+                    NSCursor::unhide();
+                }
 
                 // Remove notification observers we registered earlier
                 NSNotificationCenter::defaultCenter().removeObserver(&self.view);
+                NSWorkspace::sharedWorkspace()
+                    .notificationCenter()
+                    .removeObserver(&self.view);
 
                 // Remove our key event monitor if we set one up
                 if let Some(monitor) = inner.key_event_monitor.take() {
                     NSEvent::removeMonitor(&monitor);
                 }
+
+                // the run loop keeps the timers alive, they would fire forever
+                for (_, timer) in inner.timers.take() {
+                    timer.invalidate();
+                }
+
+                // Subsequent wakeups should fail
+                inner.waker.is_closed.store(true, Ordering::Relaxed);
+                inner.waker.source.invalidate();
+
+                if let Some(group) = &inner.group {
+                    group.remove(&WindowWaker(inner.waker.clone()));
+                }
             }
 
             let _: () = msg_send![super(self, NSView::class()), dealloc];
@@ -460,12 +1185,132 @@ impl WindowImpl {
         }
     }
 
+    unsafe extern "C" fn view_did_move_to_window(&self, _: Sel) {
+        if self.inner().is_none() {
+            return;
+        }
+
+        // the host can move us into a different window at any time (for example, when
+        // re-docking the editor), drop everything tied to the old one
+        self.unobserve_window();
+
+        let Some(window) = self.view.window() else {
+            return;
+        };
+
+        if self.is_focus_pending.replace(false) {
+            window.makeFirstResponder(Some(&self.view));
+        }
+
+        if self.is_embedded {
+            let center = NSNotificationCenter::defaultCenter();
+            let object: &AnyObject = &window;
+
+            unsafe {
+                for (selector, name) in [
+                    (sel!(windowDidResignKey:), NSWindowDidResignKeyNotification),
+                    (
+                        sel!(windowDidChangeOcclusionState:),
+                        NSWindowDidChangeOcclusionStateNotification,
+                    ),
+                    (
+                        sel!(windowDidChangeScreen:),
+                        NSWindowDidChangeScreenNotification,
+                    ),
+                    (
+                        sel!(windowDidChangeScreenProfile:),
+                        NSWindowDidChangeScreenProfileNotification,
+                    ),
+                    (sel!(windowWillClose:), NSWindowWillCloseNotification),
+                ] {
+                    center.addObserver_selector_name_object(
+                        &self.view,
+                        selector,
+                        Some(name),
+                        Some(object),
+                    );
+                }
+            }
+        }
+
+        // the new window might be on a different display
+        self.update_screen();
+        self.update_safe_area();
+    }
+
+    unsafe extern "C" fn view_did_move_to_superview(&self, _: Sel) {
+        if self.inner().is_none() || !self.is_embedded {
+            return;
+        }
+
+        if unsafe { self.view.superview() }.is_some() {
+            // the top-left corner of the new superview might be somewhere else
+            self.update_embedded_origin();
+            return;
+        }
+
+        // hosts also detach the view for a moment when re-docking the editor, only treat
+        // it as a close if it is still detached once the run loop comes around
+        unsafe {
+            let _: () = msg_send![
+                &self.view,
+                performSelector: sel!(viewDetachedCheck),
+                withObject: None::<&AnyObject>,
+                afterDelay: 0.0f64
+            ];
+        }
+    }
+
+    unsafe extern "C" fn view_detached_check(&self, _: Sel) {
+        if self.inner().is_some()
+            && !self.is_closed.get()
+            && unsafe { self.view.superview() }.is_none()
+        {
+            self.deferred_event(|_, e| e.close_requested());
+        }
+    }
+
+    unsafe extern "C" fn workspace_did_wake(&self, _: Sel, _: &NSNotification) {
+        if self.inner().is_some() {
+            self.display_link.restart();
+            self.deferred_event(|_, e| e.system_resumed());
+        }
+    }
+
+    unsafe extern "C" fn preferred_scroller_style_did_change(&self, _: Sel, _: &NSNotification) {
+        if self.inner().is_some() {
+            self.deferred_event(|_, e| e.system_metrics_changed());
+        }
+    }
+
+    unsafe extern "C" fn window_will_close(&self, _: Sel, _: &NSNotification) {
+        self.unobserve_window();
+    }
+
+    unsafe extern "C" fn parent_window_will_close(&self, _: Sel, _: &NSNotification) {
+        if self.inner().is_some() {
+            self.is_parent_closed.set(!self.is_closed.get());
+            self.close();
+        }
+    }
+
+    unsafe extern "C" fn popup_owner_did_resign_key(&self, _: Sel, _: &NSNotification) {
+        if self.inner().is_some() && !self.is_closed.get() {
+            self.deferred_event(|this, e| {
+                e.popup_dismissed();
+                this.close();
+            });
+        }
+    }
+
     unsafe extern "C" fn view_did_change_backing_properties(&self, _: Sel, _: Option<&AnyObject>) {
         // keep physical size
         self.set_size(self.last_window_size.replace(Size::default()));
+        self.update_metal_layer();
 
         // let the handler handle it now
         self.deferred_event(|this, e| e.scale_changed(this.scale()));
+        self.update_safe_area();
     }
 
     unsafe extern "C" fn window_should_close(&self, _: Sel, _: Option<&AnyObject>) -> Bool {
@@ -481,10 +1326,18 @@ impl WindowImpl {
                 y: position.y,
             };
 
-            self.deferred_event(move |_, e| e.position_changed(position));
+            self.deferred_latest_event(move |_, e| e.position_changed(position));
         }
     }
 
+    unsafe extern "C" fn window_did_change_screen(&self, _: Sel, _: Option<&AnyObject>) {
+        self.update_screen();
+    }
+
+    unsafe extern "C" fn window_did_change_screen_profile(&self, _: Sel, _: Option<&AnyObject>) {
+        self.update_color_profile();
+    }
+
     unsafe extern "C" fn window_did_resign_key(&self, _: Sel, _notif: &NSNotification) {
         if let Some(window) = self.view.window() {
             window.makeFirstResponder(None);
@@ -533,19 +1386,25 @@ impl WindowImpl {
         let logical = self.view.frame();
         let backing = self.view.convertRectToBacking(logical);
         let size = Size {
-            width: backing.size.width as u32,
-            height: backing.size.height as u32,
+            width: backing.size.width.round() as u32,
+            height: backing.size.height.round() as u32,
         };
 
+        // entering fullscreen on a notched display changes the safe area, but
+        // not necessarily the size
+        self.update_safe_area();
+        self.update_metal_layer();
+
         if self.last_window_size.replace(size) == size {
             return;
         }
 
+        #[cfg(feature = "opengl")]
         if let Ok(gl) = &self.gl_context {
             gl.resize(logical.size.width, logical.size.height);
         }
 
-        self.deferred_event(|this, e| e.size_changed(this.last_window_size.get()));
+        self.deferred_latest_event(|this, e| e.size_changed(this.last_window_size.get()));
     }
 
     unsafe extern "C" fn accepts_first_mouse(&self, _: Sel, _event: &NSEvent) -> Bool {
@@ -557,11 +1416,31 @@ impl WindowImpl {
     }
 
     unsafe extern "C" fn become_first_responder(&self, _: Sel) -> Bool {
+        // only modifiers can be queried without an event, other keys show up as pressed
+        let modifier_keys = flags_to_modifier_keys(NSEvent::modifierFlags_class());
+        self.keys_down.reset(
+            modifier_keys
+                .into_iter()
+                .filter(|(_, down)| *down)
+                .map(|(key, _)| key),
+        );
+        self.last_view_focused.set(true);
         self.deferred_event(|_, e| e.focus_changed(true));
         Bool::YES
     }
 
     unsafe extern "C" fn resign_first_responder(&self, _: Sel) -> Bool {
+        if self.marked_text.borrow().is_some() {
+            if let Some(context) = self.view.inputContext() {
+                context.discardMarkedText();
+            }
+
+            self.end_composition(None);
+        }
+
+        self.keys_down.reset([]);
+        self.release_cursor_grab();
+        self.last_view_focused.set(false);
         self.deferred_event(|_, e| e.focus_changed(false));
         Bool::YES
     }
@@ -570,17 +1449,84 @@ impl WindowImpl {
         Bool::YES
     }
 
+    unsafe extern "C" fn intrinsic_content_size(&self, _: Sel) -> NSSize {
+        // axes that the user can not resize report their size, so hosts that lay out
+        // embedded views can size their containers to fit
+        let hints = match self.inner() {
+            Some(inner) => inner.resize_hints.get(),
+            None => ResizeHints::default(),
+        };
+
+        let size = self.view.frame().size;
+        NSSize {
+            width: match hints.can_resize_horizontally {
+                true => NO_INTRINSIC_METRIC,
+                false => size.width,
+            },
+            height: match hints.can_resize_vertically {
+                true => NO_INTRINSIC_METRIC,
+                false => size.height,
+            },
+        }
+    }
+
     unsafe extern "C" fn flags_changed(&self, _: Sel, event: &NSEvent) {
+        self.track_event(event);
+
         let modifiers = flags_to_modifiers((*event).modifierFlags());
         self.deferred_event(move |_, e| e.key_modifiers(modifiers));
+
+        // modifier keys (Fn included) never get a key down or up of their own,
+        // this is the only place to report them as key presses like the other
+        // backends do
+        for (key, down) in flags_to_modifier_keys(event.modifierFlags()) {
+            if self.keys_down.is_down(key) != down {
+                self.keys_down.set(key, down);
+                self.deferred_event(move |_, e| {
+                    e.key_press(key, down, false, None);
+                });
+            }
+        }
     }
 
     unsafe extern "C" fn mouse_moved(&self, _: Sel, event: &NSEvent) {
-        let point = self.convert_point_to_picoview(event.locationInWindow());
-        self.deferred_event(move |_, e| e.mouse_move(point));
+        self.track_event(event);
+
+        if let Some(drag) = self.resize_drag.get() {
+            self.update_resize_drag(drag);
+            return;
+        }
+
+        let mut point = self.convert_point_to_picoview(event.locationInWindow());
+        if let Some(position) = self.cursor_lock.get() {
+            // the cursor stays put, only the deltas tell how far the mouse went
+            let scale = self.scale();
+            point = Point {
+                x: position.x + event.deltaX() * scale,
+                y: position.y + event.deltaY() * scale,
+            };
+            self.cursor_lock.set(Some(point));
+        } else if self.cursor_grab.get() == CursorGrab::Confined {
+            // there is no call to confine the cursor, so move it back as soon as it leaves
+            let size = self.view.convertSizeToBacking(self.view.frame().size);
+            let inside = Point {
+                x: point.x.clamp(0.0, size.width),
+                y: point.y.clamp(0.0, size.height),
+            };
+
+            if inside != point {
+                point = inside;
+                self.set_cursor_position(point);
+                set_cursor_attached(true);
+            }
+        }
+
+        self.deferred_latest_event(move |_, e| e.mouse_move(point));
     }
 
     unsafe extern "C" fn mouse_button(&self, _: Sel, event: &NSEvent) {
+        self.track_event(event);
+
         let is_down = event.r#type() == NSEventType::LeftMouseDown
             || event.r#type() == NSEventType::RightMouseDown
             || event.r#type() == NSEventType::OtherMouseDown;
@@ -594,46 +1540,101 @@ impl WindowImpl {
             _ => return,
         };
 
+        // the buttons were reported as released when the resize started
+        if self.resize_drag.get().is_some() {
+            if !is_down {
+                self.resize_drag.take();
+            }
+            return;
+        }
+
         if is_down && let Some(window) = self.view.window() {
             window.makeFirstResponder(Some(&self.view));
         }
 
-        let point = self.convert_point_to_picoview(event.locationInWindow());
+        let mask = 1 << button as u32;
+        let held = self.last_buttons_held.get();
+        self.last_buttons_held
+            .set(if is_down { held | mask } else { held & !mask });
+
+        if is_down {
+            self.last_mouse_down.replace(Some(event.retain()));
+        } else if self.last_buttons_held.get() == 0 {
+            self.last_mouse_down.take();
+        }
+        let delivered = is_down || held & mask != 0 || self.unmatched_mouse_release;
+
+        // a drag released outside of the view gives the cursor back
+        if !is_down {
+            self.update_cursor();
+        }
+
+        // while locked, presses happen at the virtual position
+        let point = match self.cursor_lock.get() {
+            Some(position) => position,
+            None => self.convert_point_to_picoview(event.locationInWindow()),
+        };
         self.deferred_event(move |_, e| {
             e.mouse_move(point);
-            e.mouse_press(button, is_down);
+            if delivered {
+                e.mouse_press(button, is_down, point);
+            }
         });
     }
 
+    unsafe extern "C" fn mouse_entered(&self, _: Sel, _event: &NSEvent) {
+        // the cursor the handler set last applies again
+        self.cursor_inside.set(true);
+        self.update_cursor();
+    }
+
     unsafe extern "C" fn mouse_exited(&self, _: Sel, _event: &NSEvent) {
         self.deferred_event(|_, e| e.mouse_leave());
-        self.set_cursor_icon(MouseCursor::Default);
+        self.cursor_inside.set(false);
+        self.update_cursor();
+    }
+
+    unsafe extern "C" fn tablet_proximity(&self, _: Sel, event: &NSEvent) {
+        self.track_event(event);
+
+        let entering = event.isEnteringProximity();
+        let inverted = event.pointingDeviceType() == NSPointingDeviceType::Eraser;
+        self.deferred_event(move |_, e| e.pen_proximity(entering, inverted));
     }
 
     unsafe extern "C" fn scroll_wheel(&self, _: Sel, event: &NSEvent) {
-        let mut x = -event.scrollingDeltaX();
-        let mut y = event.scrollingDeltaY();
+        self.track_event(event);
 
-        if event.hasPreciseScrollingDeltas() {
-            x /= 10.0;
-            y /= 10.0;
-        }
+        let x = -event.scrollingDeltaX();
+        let y = event.scrollingDeltaY();
+
+        // touchpads and magic mice report points, wheels report accelerated lines
+        let delta = match event.hasPreciseScrollingDeltas() {
+            true => ScrollDelta::Pixels { x, y },
+            false => ScrollDelta::Lines { x, y },
+        };
+
+        let (x, y) = delta.resolve(self.raw_scroll);
 
         let point = self.convert_point_to_picoview(event.locationInWindow());
-        self.deferred_event(move |_, e| {
+        self.deferred_lossy_event(move |_, e| {
             e.mouse_move(point);
             e.mouse_scroll(x, y);
         });
     }
 
     unsafe extern "C" fn magnify_with_event(&self, _: Sel, event: &NSEvent) {
+        self.track_event(event);
+
         let delta = event.magnification();
-        self.deferred_event(move |_, e| e.gesture_zoom(delta));
+        self.deferred_lossy_event(move |_, e| e.gesture_zoom(delta));
     }
 
     unsafe extern "C" fn rotate_with_event(&self, _: Sel, event: &NSEvent) {
+        self.track_event(event);
+
         let delta = event.rotation() as f64;
-        self.deferred_event(move |_, e| e.gesture_rotate(delta));
+        self.deferred_lossy_event(move |_, e| e.gesture_rotate(delta));
     }
 
     unsafe extern "C" fn draw_rect(&self, _: Sel, _: NSRect) {
@@ -662,10 +1663,6 @@ impl WindowImpl {
         }
     }
 
-    unsafe extern "C" fn wakeup(&self, _: Sel) {
-        self.deferred_event(|_, e| e.wakeup());
-    }
-
     // NSDraggingDestination
     unsafe extern "C" fn wants_periodic_dragging_updates(&self, _: Sel) -> Bool {
         Bool::YES
@@ -676,7 +1673,10 @@ impl WindowImpl {
         _: Sel,
         info: &ProtocolObject<dyn NSDraggingInfo>,
     ) -> NSDragOperation {
-        let data = get_pasteboard(&info.draggingPasteboard());
+        let data = get_pasteboard(
+            &info.draggingPasteboard(),
+            &[ClipboardFormat::Files, ClipboardFormat::Text],
+        );
         let point = self.convert_point_to_picoview(info.draggingLocation());
         let effect = self
             .non_reentrant_event(|e| e.drag_enter(data, point))
@@ -737,6 +1737,161 @@ impl WindowImpl {
         accept.into()
     }
 
+    // NSDraggingSource
+    unsafe extern "C" fn dragging_source_operation_mask(
+        &self,
+        _: Sel,
+        _session: &NSDraggingSession,
+        _context: NSDraggingContext,
+    ) -> NSDragOperation {
+        NSDragOperation::Copy
+    }
+
+    unsafe extern "C" fn dragging_session_ended(
+        &self,
+        _: Sel,
+        _session: &NSDraggingSession,
+        _point: NSPoint,
+        operation: NSDragOperation,
+    ) {
+        self.is_dragging.set(false);
+
+        let effect = match operation.contains(NSDragOperation::Copy) {
+            true => DropEffect::Copy,
+            false => DropEffect::Reject,
+        };
+
+        self.deferred_event(move |_, e| e.drag_finished(effect));
+    }
+
+    unsafe extern "C" fn has_marked_text(&self, _: Sel) -> Bool {
+        Bool::new(
+            self.inner()
+                .is_some_and(|inner| inner.marked_text.borrow().is_some()),
+        )
+    }
+
+    unsafe extern "C" fn marked_range(&self, _: Sel) -> NSRange {
+        match self
+            .inner()
+            .and_then(|inner| inner.marked_text.borrow().clone())
+        {
+            Some(text) => NSRange::new(0, text.encode_utf16().count()),
+            None => NSRange::new(NSNotFound as usize, 0),
+        }
+    }
+
+    unsafe extern "C" fn selected_range(&self, _: Sel) -> NSRange {
+        // there is no text around the composition, the caret is at its end
+        match self
+            .inner()
+            .and_then(|inner| inner.marked_text.borrow().clone())
+        {
+            Some(text) => NSRange::new(text.encode_utf16().count(), 0),
+            None => NSRange::new(NSNotFound as usize, 0),
+        }
+    }
+
+    unsafe extern "C" fn set_marked_text(
+        &self,
+        _: Sel,
+        string: &AnyObject,
+        selected_range: NSRange,
+        _: NSRange,
+    ) {
+        if self.inner().is_none() {
+            return;
+        }
+
+        let text = input_string(string);
+        if text.is_empty() {
+            self.end_composition(None);
+            return;
+        }
+
+        if self.marked_text.replace(Some(text.clone())).is_none() {
+            self.deferred_event(|_, e| e.composition_start());
+        }
+
+        let cursor_range = utf16_range_to_bytes(
+            &text,
+            selected_range.location..selected_range.location + selected_range.length,
+        );
+        self.deferred_event(move |_, e| e.composition_update(&text, cursor_range));
+    }
+
+    unsafe extern "C" fn unmark_text(&self, _: Sel) {
+        if let Some(inner) = self.inner() {
+            let text = inner.marked_text.borrow().clone();
+            self.end_composition(text);
+        }
+    }
+
+    unsafe extern "C" fn valid_attributes_for_marked_text(&self, _: Sel) -> *mut NSArray {
+        Retained::autorelease_return(NSArray::new())
+    }
+
+    unsafe extern "C" fn attributed_substring_for_proposed_range(
+        &self,
+        _: Sel,
+        _: NSRange,
+        _: *mut NSRange,
+    ) -> *mut NSAttributedString {
+        // the text around the composition belongs to the handler
+        null_mut()
+    }
+
+    unsafe extern "C" fn insert_text(&self, _: Sel, string: &AnyObject, _: NSRange) {
+        let Some(inner) = self.inner() else {
+            return;
+        };
+
+        let text = input_string(string);
+        if inner.marked_text.borrow().is_none() {
+            // plain typing, already reported as key presses
+            if inner.is_handling_key.get() {
+                return;
+            }
+
+            inner.marked_text.replace(Some(String::new()));
+            self.deferred_event(|_, e| e.composition_start());
+        }
+
+        self.end_composition(Some(text));
+    }
+
+    unsafe extern "C" fn character_index_for_point(&self, _: Sel, _: NSPoint) -> usize {
+        NSNotFound as usize
+    }
+
+    unsafe extern "C" fn first_rect_for_character_range(
+        &self,
+        _: Sel,
+        _: NSRange,
+        _: *mut NSRange,
+    ) -> NSRect {
+        let area = self
+            .inner()
+            .and_then(|inner| inner.ime_cursor_area.get())
+            .unwrap_or_default();
+        let Size { width, height } = area.size();
+
+        let rect = self.view.convertRectFromBacking(NSRect::new(
+            NSPoint::new(area.left as _, area.top as _),
+            NSSize::new(width as _, height as _),
+        ));
+        let rect = self.view.convertRect_toView(rect, None);
+
+        match self.view.window() {
+            Some(window) => window.convertRectToScreen(rect),
+            None => rect,
+        }
+    }
+
+    unsafe extern "C" fn do_command_by_selector(&self, _: Sel, _: Sel) {
+        // commands (like `insertNewline:`) are reported as key presses
+    }
+
     fn register_class() -> Result<&'static AnyClass, WindowError> {
         let class_name =
             CString::new(format!("picoview-{}", random_id())).expect("unexpected nul terminator?");
@@ -786,10 +1941,18 @@ impl WindowImpl {
                 sel!(isFlipped),
                 Self::is_flipped as unsafe extern "C" fn(_, _) -> _,
             );
+            builder.add_method(
+                sel!(intrinsicContentSize),
+                Self::intrinsic_content_size as unsafe extern "C" fn(_, _) -> _,
+            );
             builder.add_method(
                 sel!(flagsChanged:),
                 Self::flags_changed as unsafe extern "C" fn(_, _, _) -> _,
             );
+            builder.add_method(
+                sel!(mouseEntered:),
+                Self::mouse_entered as unsafe extern "C" fn(_, _, _) -> _,
+            );
             builder.add_method(
                 sel!(mouseMoved:),
                 Self::mouse_moved as unsafe extern "C" fn(_, _, _) -> _,
@@ -834,6 +1997,10 @@ impl WindowImpl {
                 sel!(mouseExited:),
                 Self::mouse_exited as unsafe extern "C" fn(_, _, _) -> _,
             );
+            builder.add_method(
+                sel!(tabletProximity:),
+                Self::tablet_proximity as unsafe extern "C" fn(_, _, _) -> _,
+            );
             builder.add_method(
                 sel!(scrollWheel:),
                 Self::scroll_wheel as unsafe extern "C" fn(_, _, _) -> _,
@@ -855,6 +2022,38 @@ impl WindowImpl {
                 sel!(viewFrameDidChange:),
                 Self::view_frame_did_change_notification as unsafe extern "C" fn(_, _, _) -> _,
             );
+            builder.add_method(
+                sel!(viewDidMoveToWindow),
+                Self::view_did_move_to_window as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(viewDidMoveToSuperview),
+                Self::view_did_move_to_superview as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(viewDetachedCheck),
+                Self::view_detached_check as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(workspaceDidWake:),
+                Self::workspace_did_wake as unsafe extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(popupOwnerDidResignKey:),
+                Self::popup_owner_did_resign_key as unsafe extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(preferredScrollerStyleDidChange:),
+                Self::preferred_scroller_style_did_change as unsafe extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(windowWillClose:),
+                Self::window_will_close as unsafe extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(parentWindowWillClose:),
+                Self::parent_window_will_close as unsafe extern "C" fn(_, _, _) -> _,
+            );
             builder.add_method(
                 sel!(viewDidHide),
                 Self::view_did_hide as unsafe extern "C" fn(_, _) -> _,
@@ -864,12 +2063,6 @@ impl WindowImpl {
                 Self::view_did_unhide as unsafe extern "C" fn(_, _) -> _,
             );
 
-            // custom
-            builder.add_method(
-                sel!(picoview_wakeup),
-                Self::wakeup as unsafe extern "C" fn(_, _) -> _,
-            );
-
             // NSWindowDelegate methods & NSNotification handlers
             builder.add_method(
                 sel!(windowShouldClose:),
@@ -887,6 +2080,14 @@ impl WindowImpl {
                 sel!(windowDidResignKey:),
                 Self::window_did_resign_key as unsafe extern "C" fn(_, _, _) -> _,
             );
+            builder.add_method(
+                sel!(windowDidChangeScreen:),
+                Self::window_did_change_screen as unsafe extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(windowDidChangeScreenProfile:),
+                Self::window_did_change_screen_profile as unsafe extern "C" fn(_, _, _) -> _,
+            );
             builder.add_method(
                 sel!(windowDidChangeOcclusionState:),
                 Self::window_did_change_occlusion_state as unsafe extern "C" fn(_, _, _) -> _,
@@ -918,6 +2119,16 @@ impl WindowImpl {
                 Self::perform_drag_operation as unsafe extern "C" fn(_, _, _) -> _,
             );
 
+            // NSDraggingSource
+            builder.add_method(
+                sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+                Self::dragging_source_operation_mask as unsafe extern "C" fn(_, _, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(draggingSession:endedAtPoint:operation:),
+                Self::dragging_session_ended as unsafe extern "C" fn(_, _, _, _, _) -> _,
+            );
+
             builder.add_protocol(
                 <dyn objc2_app_kit::NSWindowDelegate>::protocol()
                     .expect("unknown protocol: NSWindowDelegate"),
@@ -926,6 +2137,61 @@ impl WindowImpl {
                 <dyn objc2_app_kit::NSDraggingDestination>::protocol()
                     .expect("unknown protocol: NSDraggingDestination"),
             );
+            // NSTextInputClient
+            builder.add_method(
+                sel!(hasMarkedText),
+                Self::has_marked_text as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(markedRange),
+                Self::marked_range as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(selectedRange),
+                Self::selected_range as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(setMarkedText:selectedRange:replacementRange:),
+                Self::set_marked_text as unsafe extern "C" fn(_, _, _, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(unmarkText),
+                Self::unmark_text as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(validAttributesForMarkedText),
+                Self::valid_attributes_for_marked_text as unsafe extern "C" fn(_, _) -> _,
+            );
+            builder.add_method(
+                sel!(attributedSubstringForProposedRange:actualRange:),
+                Self::attributed_substring_for_proposed_range
+                    as unsafe extern "C" fn(_, _, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(insertText:replacementRange:),
+                Self::insert_text as unsafe extern "C" fn(_, _, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(characterIndexForPoint:),
+                Self::character_index_for_point as unsafe extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(firstRectForCharacterRange:actualRange:),
+                Self::first_rect_for_character_range as unsafe extern "C" fn(_, _, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(doCommandBySelector:),
+                Self::do_command_by_selector as unsafe extern "C" fn(_, _, _) -> _,
+            );
+
+            builder.add_protocol(
+                <dyn objc2_app_kit::NSDraggingSource>::protocol()
+                    .expect("unknown protocol: NSDraggingSource"),
+            );
+            builder.add_protocol(
+                <dyn objc2_app_kit::NSTextInputClient>::protocol()
+                    .expect("unknown protocol: NSTextInputClient"),
+            );
         }
 
         Ok(builder.register())
@@ -938,6 +2204,13 @@ impl PlatformWindow for WindowImpl {
             return;
         }
 
+        self.update_cursor();
+        self.release_cursor_grab();
+
+        for (_, timer) in self.timers.take() {
+            timer.invalidate();
+        }
+
         if let Some(window) = self.own_window() {
             window.setDelegate(None);
             window.close();
@@ -946,29 +2219,24 @@ impl PlatformWindow for WindowImpl {
         self.view.removeFromSuperview();
 
         if let Some(app) = self.application.take() {
-            app.stop(Some(&app));
-
-            // it is stupid that we have to send a dummy event to _actually_ stop the event
-            // loop but here we are, thank you apple!!!!
-            app.postEvent_atStart(&NSEvent::otherEventWithType_location_modifierFlags_timestamp_windowNumber_context_subtype_data1_data2(
-                NSEventType::ApplicationDefined,
-                NSPoint::new(0.0, 0.0),
-                NSEventModifierFlags::empty(),
-                0.0,
-                0,
-                None,
-                0,
-                0,
-                0,
-            ).expect("Failed to create dummy event"), false);
+            event_loop::remove(&app);
         }
     }
 
+    fn is_closed(&self) -> bool {
+        self.is_closed.get()
+    }
+
     fn waker(&self) -> WindowWaker {
         WindowWaker(self.waker.clone())
     }
 
+    #[cfg(feature = "opengl")]
     fn opengl(&self) -> Result<&dyn PlatformOpenGl, OpenGlError> {
+        if let Some(gl) = &self.external_gl_context {
+            return Ok(gl);
+        }
+
         match &self.gl_context {
             Ok(gl) => Ok(gl),
             Err(err) => Err(err.clone()),
@@ -983,7 +2251,8 @@ impl PlatformWindow for WindowImpl {
 
     fn set_decorations(&self, decorations: bool) {
         if let Some(window) = self.own_window() {
-            let mut style = window.styleMask();
+            let old_style = window.styleMask();
+            let mut style = old_style;
 
             if decorations {
                 style.insert(STYLE_MASK_NORMAL);
@@ -991,22 +2260,21 @@ impl PlatformWindow for WindowImpl {
                 style.remove(STYLE_MASK_NORMAL);
             }
 
+            if style == old_style {
+                return;
+            }
+
+            // the frame stays put when the style changes, so the content would grow into
+            // (or give up space for) the title bar. keep the content size instead
+            let content = window.contentRectForFrameRect(window.frame());
             window.setStyleMask(style);
+            window.setContentSize(content.size);
         }
     }
 
     fn set_cursor_icon(&self, cursor: MouseCursor) {
-        let old_cursor = self.last_cursor_icon.replace(cursor);
-        if old_cursor != cursor {
-            if old_cursor == MouseCursor::Hidden {
-                NSCursor::unhide();
-            }
-
-            if cursor == MouseCursor::Hidden {
-                NSCursor::hide();
-            } else {
-                best_cursor_icon_for(cursor).set();
-            }
+        if self.last_cursor_icon.replace(cursor) != cursor {
+            self.update_cursor();
         }
     }
 
@@ -1023,15 +2291,26 @@ impl PlatformWindow for WindowImpl {
     }
 
     fn set_size(&self, size: Size) {
+        let size = self.resize_hints.get().constrain(
+            size,
+            self.min_window_size.get(),
+            self.max_window_size.get(),
+        );
+
         if self.last_window_size.get() == size {
             return;
         }
 
+        // locked axes follow the requested size, otherwise the window would refuse
+        // the new content size
+        self.update_size_constraints(size);
+
         let size = self.view.convertSizeFromBacking(CGSize {
             width: size.width as f64,
             height: size.height as f64,
         });
 
+        #[cfg(feature = "opengl")]
         if let Ok(gl) = &self.gl_context {
             gl.resize(size.width, size.height);
         }
@@ -1041,24 +2320,30 @@ impl PlatformWindow for WindowImpl {
         }
 
         self.view.setFrameSize(size);
+
+        // keep the top-left corner in place
+        if self.is_embedded {
+            self.update_embedded_origin();
+        }
     }
 
     fn set_min_size(&self, size: Size) {
-        if let Some(window) = self.own_window() {
-            window.setMinSize(CGSize {
-                width: size.width as _,
-                height: size.height as _,
-            });
-        }
+        self.min_window_size.set(size);
+        self.update_size_constraints(self.last_window_size.get());
     }
 
     fn set_max_size(&self, size: Size) {
-        if let Some(window) = self.own_window() {
-            window.setMaxSize(CGSize {
-                width: size.width as _,
-                height: size.height as _,
-            });
-        }
+        self.max_window_size.set(size);
+        self.update_size_constraints(self.last_window_size.get());
+    }
+
+    fn set_resize_hints(&self, hints: ResizeHints) {
+        self.resize_hints.set(hints);
+        self.update_size_constraints(self.last_window_size.get());
+    }
+
+    fn resize_hints(&self) -> ResizeHints {
+        self.resize_hints.get()
     }
 
     fn set_position(&self, point: Point) {
@@ -1068,14 +2353,64 @@ impl PlatformWindow for WindowImpl {
                 y: point.y as _,
             });
         } else {
-            self.view.setFrameOrigin(NSPoint {
-                x: point.x as _,
-                y: point.y as _,
-            });
+            self.embedded_position.set(point);
+            self.update_embedded_origin();
         }
     }
 
+    fn center(&self, monitor: MonitorSelector) {
+        let Some(window) = self.own_window() else {
+            // embedded views are centered in their superview
+            if let Some(superview) = unsafe { self.view.superview() } {
+                let area = self.view.convertSizeToBacking(superview.bounds().size);
+                let size = self.last_window_size.get();
+                self.set_position(Point {
+                    x: (area.width - size.width as f64) / 2.0,
+                    y: (area.height - size.height as f64) / 2.0,
+                });
+            }
+
+            return;
+        };
+
+        let mtm = self.view.mtm();
+        let screen = match monitor {
+            MonitorSelector::Primary => NSScreen::screens(mtm).firstObject(),
+            MonitorSelector::Cursor => {
+                let point = NSEvent::mouseLocation();
+                let screens = NSScreen::screens(mtm);
+                (0..screens.count())
+                    .map(|i| screens.objectAtIndex(i))
+                    .find(|screen| {
+                        let frame = screen.frame();
+                        point.x >= frame.origin.x
+                            && point.y >= frame.origin.y
+                            && point.x < frame.origin.x + frame.size.width
+                            && point.y < frame.origin.y + frame.size.height
+                    })
+            }
+            // transient windows are child windows, they go on the screen of their parent
+            MonitorSelector::Current => window.parentWindow().unwrap_or(window.clone()).screen(),
+        };
+
+        let Some(screen) = screen.or_else(|| NSScreen::mainScreen(mtm)) else {
+            return;
+        };
+
+        // both rects are in cocoa screen coordinates, so no flipping is needed
+        let area = screen.visibleFrame();
+        let size = window.frame().size;
+        window.setFrameOrigin(NSPoint::new(
+            area.origin.x + (area.size.width - size.width) / 2.0,
+            area.origin.y + (area.size.height - size.height) / 2.0,
+        ));
+    }
+
     fn set_visible(&self, visible: bool) {
+        if !visible {
+            self.release_cursor_grab();
+        }
+
         if let Some(window) = self.own_window() {
             if visible {
                 window.makeKeyAndOrderFront(None);
@@ -1087,6 +2422,14 @@ impl PlatformWindow for WindowImpl {
         self.view.setHidden(!visible);
     }
 
+    fn color_profile(&self) -> Option<IccProfile> {
+        self.last_color_profile.borrow().clone()
+    }
+
+    fn metal_layer(&self) -> Option<NonNull<c_void>> {
+        self.metal_layer.as_ref().map(MetalLayer::as_ptr)
+    }
+
     fn scale(&self) -> f64 {
         self.view
             .window()
@@ -1094,31 +2437,278 @@ impl PlatformWindow for WindowImpl {
             .unwrap_or(1.0)
     }
 
-    fn open_url(&self, url: &str) -> bool {
-        spawn_detached(std::process::Command::new("/usr/bin/open").arg(url)).is_ok()
+    fn user_data(&self) -> &RefCell<Option<Rc<dyn Any>>> {
+        &self.user_data
+    }
+
+    fn open_url(&self, url: &str, on_done: OpenUrlCallback) {
+        open_url_in_background(url, on_done, open_url);
     }
 
-    fn set_clipboard(&self, data: Exchange) -> bool {
+    fn set_clipboard(&self, data: Exchange) -> Result<(), ClipboardError> {
         unsafe {
             let pasteboard: Option<Retained<NSPasteboard>> =
                 msg_send![NSPasteboard::class(), generalPasteboard];
 
             match pasteboard {
                 Some(pasteboard) => set_pasteboard(&pasteboard, data),
-                None => false,
+                None => Err(ClipboardError::AccessDenied),
             }
         }
     }
 
-    fn get_clipboard(&self) -> Exchange {
+    fn get_clipboard(&self, format: Option<&ClipboardFormat>) -> Result<Exchange, ClipboardError> {
         unsafe {
             let pasteboard: Option<Retained<NSPasteboard>> =
                 msg_send![NSPasteboard::class(), generalPasteboard];
 
-            match pasteboard {
-                Some(pasteboard) => get_pasteboard(&pasteboard),
-                None => Exchange::Empty,
+            let Some(pasteboard) = pasteboard else {
+                return Err(ClipboardError::AccessDenied);
+            };
+
+            match get_pasteboard(&pasteboard, clipboard_formats(format)) {
+                Exchange::Empty if is_pasteboard_empty(&pasteboard) => Err(ClipboardError::Empty),
+                Exchange::Empty => Err(ClipboardError::UnsupportedFormat),
+                exchange => Ok(exchange),
+            }
+        }
+    }
+
+    fn start_drag(&self, data: Exchange) -> Result<(), DragError> {
+        if self.is_dragging.get() {
+            return Err(DragError::InProgress);
+        }
+
+        let Some(event) = self.last_mouse_down.borrow().clone() else {
+            return Err(DragError::NoButtonHeld);
+        };
+
+        let writers = pasteboard_writers(&data);
+        if writers.is_empty() {
+            return Err(DragError::Platform("none of the files exist".into()));
+        }
+
+        // no image, the system cursor shows the drag
+        let point = self
+            .view
+            .convertPoint_fromView(event.locationInWindow(), None);
+        let frame = NSRect::new(point, NSSize::new(1.0, 1.0));
+        let items = writers
+            .iter()
+            .map(|writer| {
+                let item =
+                    NSDraggingItem::initWithPasteboardWriter(NSDraggingItem::alloc(), writer);
+                unsafe { item.setDraggingFrame_contents(frame, None) };
+                item
+            })
+            .collect::<Vec<_>>();
+
+        self.view.beginDraggingSessionWithItems_event_source(
+            &NSArray::from_retained_slice(&items),
+            &event,
+            self.as_ns_dragging_source(),
+        );
+        self.is_dragging.set(true);
+
+        // the session takes over the mouse, the view will not see the buttons released
+        self.release_buttons();
+        Ok(())
+    }
+
+    fn set_cursor_grab(&self, mode: CursorGrab) -> Result<(), CursorGrabError> {
+        if mode == CursorGrab::None {
+            self.release_cursor_grab();
+            return Ok(());
+        }
+
+        let Some(window) = self.view.window() else {
+            return Err(CursorGrabError::Unavailable);
+        };
+
+        if self.view.isHiddenOrHasHiddenAncestor() {
+            return Err(CursorGrabError::Unavailable);
+        }
+
+        self.cursor_grab.set(mode);
+        match mode {
+            CursorGrab::Locked if self.cursor_lock.get().is_none() => {
+                let point = window.mouseLocationOutsideOfEventStream();
+                self.cursor_lock
+                    .set(Some(self.convert_point_to_picoview(point)));
+                set_cursor_attached(false);
+            }
+            CursorGrab::Locked => {}
+            _ => {
+                if self.cursor_lock.take().is_some() {
+                    set_cursor_attached(true);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn begin_drag_resize(&self, edge: ResizeEdge) -> Result<(), DragError> {
+        let Some(window) = self.own_window() else {
+            return Err(DragError::Unsupported);
+        };
+
+        if self.last_buttons_held.get() == 0 {
+            return Err(DragError::NoButtonHeld);
+        }
+
+        self.resize_drag
+            .set(Some((edge, NSEvent::mouseLocation(), window.frame())));
+        self.release_buttons();
+        Ok(())
+    }
+
+    fn has_mouse_capture(&self) -> bool {
+        self.last_buttons_held.get() != 0
+    }
+
+    fn has_keyboard_focus(&self) -> bool {
+        self.last_view_focused.get()
+    }
+
+    fn cursor_grab(&self) -> CursorGrab {
+        self.cursor_grab.get()
+    }
+
+    fn last_event_token(&self) -> EventToken {
+        self.last_event_token.get()
+    }
+
+    fn id(&self) -> WindowId {
+        self.entry.id()
+    }
+
+    fn safe_area_insets(&self) -> Insets {
+        self.last_safe_area.get()
+    }
+
+    fn inner_size(&self) -> Size {
+        self.last_window_size.get()
+    }
+
+    fn frame_insets(&self) -> Insets {
+        let Some(window) = self.own_window() else {
+            return Insets::default();
+        };
+
+        let frame = window.frame();
+        let content = window.contentRectForFrameRect(frame);
+        let scale = self.scale();
+        let to_pixels = |points: f64| (points * scale).round().max(0.0) as u32;
+
+        // AppKit coordinates grow upwards, the title bar is at the top
+        Insets {
+            top: to_pixels(
+                frame.origin.y + frame.size.height - content.origin.y - content.size.height,
+            ),
+            left: to_pixels(content.origin.x - frame.origin.x),
+            bottom: to_pixels(content.origin.y - frame.origin.y),
+            right: to_pixels(
+                frame.origin.x + frame.size.width - content.origin.x - content.size.width,
+            ),
+        }
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            jitter: self.frame_jitter.get(),
+            dropped_events: self.event_deferred.dropped(),
+            coalesced_events: self.event_deferred.coalesced(),
+        }
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.is_down(key)
+    }
+
+    fn set_ime_cursor_area(&self, area: Rect) {
+        self.ime_cursor_area.set(Some(area));
+
+        // the input method asks for the area again with firstRectForCharacterRange
+        if let Some(context) = self.view.inputContext() {
+            context.invalidateCharacterCoordinates();
+        }
+    }
+
+    fn scroll_settings(&self) -> ScrollSettings {
+        ScrollSettings {
+            natural: query_natural_scrolling(),
+            ..ScrollSettings::default()
+        }
+    }
+
+    fn present_pixels(
+        &self,
+        buffer: &[u32],
+        size: Size,
+        _damage: Damage<'_>,
+    ) -> Result<(), PresentError> {
+        let mut layer = self.pixel_layer.borrow_mut();
+        let layer = match &mut *layer {
+            Some(layer) => layer,
+            layer => layer.insert(PixelLayer::new(&self.view)?),
+        };
+
+        layer.present(&self.view, buffer, size, self.scale())
+    }
+
+    fn redraw_now(&self) {
+        // from inside the handler, or from the factory before there is one
+        if matches!(self.event_handler.try_borrow().as_deref(), Ok(Some(_))) {
+            self.non_reentrant_event(|e| e.frame());
+        } else {
+            self.redraw_pending.set(true);
+        }
+    }
+
+    fn set_timer(&self, id: u32, interval: Duration) {
+        self.cancel_timer(id);
+
+        let view = Weak::new(self);
+        let timer = unsafe {
+            NSTimer::timerWithTimeInterval_repeats_block(
+                interval.as_secs_f64(),
+                true,
+                &RcBlock::new(move |_: NonNull<NSTimer>| {
+                    if let Some(view) = view.load() {
+                        view.non_reentrant_event(|e| e.timer(id));
+                    }
+                }),
+            )
+        };
+
+        // the common modes keep it running during live resizes and menu tracking,
+        // unlike the default mode of `scheduledTimer...`
+        unsafe { NSRunLoop::currentRunLoop().addTimer_forMode(&timer, NSRunLoopCommonModes) };
+        self.timers.borrow_mut().push((id, timer));
+    }
+
+    fn cancel_timer(&self, id: u32) {
+        self.timers.borrow_mut().retain(|(timer_id, timer)| {
+            if *timer_id == id {
+                timer.invalidate();
+            }
+
+            *timer_id != id
+        });
+    }
+
+    fn to_screen(&self, point: Point) -> Point {
+        let point = self
+            .view
+            .convertPointFromBacking(NSPoint::new(point.x as _, point.y as _));
+        let point = self.view.convertPoint_toView(point, None);
+
+        match self.view.window() {
+            Some(window) => {
+                screen_point_from_cocoa(window.convertPointToScreen(point), self.view.mtm())
             }
+            None => Point::default(),
         }
     }
 
@@ -1137,19 +2727,37 @@ impl PlatformWindow for WindowImpl {
 
 impl PlatformWaker for WindowWakerImpl {
     fn wakeup(&self) -> Result<(), WakeupError> {
-        if let Some(view) = self.weak.load() {
-            unsafe {
-                view.view
-                    .performSelectorOnMainThread_withObject_waitUntilDone(
-                        sel!(picoview_wakeup),
-                        None,
-                        false,
-                    );
-            }
+        if self.is_closed.load(Ordering::Relaxed) {
+            return Err(WakeupError);
+        }
 
-            Ok(())
-        } else {
-            Err(WakeupError)
+        self.source.signal();
+        if let Some(run_loop) = CFRunLoop::main() {
+            run_loop.wake_up();
+        }
+
+        Ok(())
+    }
+
+    fn close(&self) -> Result<(), WakeupError> {
+        if self.is_closed.load(Ordering::Relaxed) {
+            return Err(WakeupError);
+        }
+
+        self.close_requested.store(true, Ordering::Relaxed);
+        self.wakeup()
+    }
+
+    fn close_and_wait(&self) {
+        // AppKit windows live on the main thread, there is nothing to wait for on
+        // other threads
+        if MainThreadMarker::new().is_none() {
+            let _ = self.close();
+            return;
+        }
+
+        if let Some(view) = self.view.load() {
+            view.close();
         }
     }
 }
@@ -1,18 +1,34 @@
 use crate::*;
-use std::ffi::{CStr, c_void};
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+#[cfg(feature = "opengl")]
+use std::ffi::CStr;
+use std::ffi::c_void;
+use std::ops::{Add, Range};
+use std::ptr::NonNull;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "headless")]
+pub mod headless;
+// macOS has a PNG codec of its own
+#[cfg(not(target_os = "macos"))]
+mod png;
 
 cfg_select! {
-    target_os = "linux" => {
+    all(target_os = "linux", feature = "x11") => {
         pub mod x11;
         pub use x11::*;
     },
 
-    target_os = "windows" => {
+    all(target_os = "windows", feature = "win32") => {
         pub mod win;
         pub use win::*;
     },
 
-    target_os = "macos" => {
+    all(target_os = "macos", feature = "appkit") => {
         pub mod mac;
         pub use mac::*;
     },
@@ -23,17 +39,39 @@ cfg_select! {
             _: OpenMode,
         ) -> Result<crate::WindowWaker, crate::WindowError> {
             Err(crate::WindowError::Platform(
-                "unsupported platform".to_string(),
+                "unsupported platform or backend feature disabled".to_string(),
             ))
         }
+
+        pub unsafe fn run_event_loop() -> Result<(), crate::WindowError> {
+            Ok(())
+        }
+
+        pub fn is_event_loop_running() -> bool {
+            false
+        }
+
+        pub fn find_window_at(_: crate::Point) -> Option<crate::WindowId> {
+            None
+        }
+
+        pub fn query_system_metrics() -> crate::SystemMetrics {
+            crate::SystemMetrics::default()
+        }
     },
 }
 
 #[derive(Clone, Copy)]
 pub enum OpenMode {
-    Blocking,
+    /// A top-level window run by the event loop of the calling thread, see
+    /// [`EventLoop`].
+    // the event loop code is small, so we keep it around instead of gating every match on it
+    #[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+    TopLevel,
     Embedded(rwh_06::RawWindowHandle),
     Transient(rwh_06::RawWindowHandle),
+    /// See [`WindowBuilder::open_popup`], with the anchor.
+    Popup(rwh_06::RawWindowHandle, Rect),
 }
 
 unsafe impl Send for OpenMode {}
@@ -43,9 +81,14 @@ pub trait PlatformWindow /* : !Send + !Sync */ {
     fn display_handle(&self) -> rwh_06::RawDisplayHandle;
 
     fn close(&self);
+    fn is_closed(&self) -> bool;
     fn waker(&self) -> WindowWaker;
+    #[cfg(feature = "opengl")]
     fn opengl(&self) -> Result<&dyn PlatformOpenGl, OpenGlError>;
     fn scale(&self) -> f64;
+    fn color_profile(&self) -> Option<IccProfile>;
+    fn metal_layer(&self) -> Option<NonNull<c_void>>;
+    fn user_data(&self) -> &RefCell<Option<Rc<dyn Any>>>;
 
     fn set_title(&self, title: &str);
     fn set_decorations(&self, decorations: bool);
@@ -55,26 +98,844 @@ pub trait PlatformWindow /* : !Send + !Sync */ {
     fn set_size(&self, size: Size);
     fn set_min_size(&self, size: Size);
     fn set_max_size(&self, size: Size);
+    fn set_resize_hints(&self, hints: ResizeHints);
+    fn resize_hints(&self) -> ResizeHints;
     fn set_position(&self, pos: Point);
+    fn center(&self, monitor: MonitorSelector);
+
+    fn open_url(&self, url: &str, on_done: OpenUrlCallback);
+
+    /// See [`Window::get_clipboard_as`], `None` for [`Window::get_clipboard`]
+    /// which reads the [`CLIPBOARD_FORMATS`].
+    fn get_clipboard(&self, format: Option<&ClipboardFormat>) -> Result<Exchange, ClipboardError>;
+    fn set_clipboard(&self, data: Exchange) -> Result<(), ClipboardError>;
+    fn start_drag(&self, data: Exchange) -> Result<(), DragError>;
+    fn begin_drag_resize(&self, edge: ResizeEdge) -> Result<(), DragError>;
+    fn set_cursor_grab(&self, mode: CursorGrab) -> Result<(), CursorGrabError>;
 
-    fn open_url(&self, url: &str) -> bool;
+    fn has_mouse_capture(&self) -> bool;
+    fn has_keyboard_focus(&self) -> bool;
+    fn cursor_grab(&self) -> CursorGrab;
+    fn last_event_token(&self) -> EventToken;
 
-    fn get_clipboard(&self) -> Exchange;
-    fn set_clipboard(&self, data: Exchange) -> bool;
+    fn id(&self) -> WindowId;
+    fn to_screen(&self, point: Point) -> Point;
+    fn safe_area_insets(&self) -> Insets;
+    fn inner_size(&self) -> Size;
+    fn frame_insets(&self) -> Insets;
+    fn frame_stats(&self) -> FrameStats;
+    fn is_key_down(&self, key: Key) -> bool;
+    fn redraw_now(&self);
+    fn set_timer(&self, id: u32, interval: Duration);
+    fn cancel_timer(&self, id: u32);
+    fn set_ime_cursor_area(&self, area: Rect);
+    fn scroll_settings(&self) -> ScrollSettings;
+
+    /// See [`Window::present_pixels`]. `buffer` is big enough for `size`, and
+    /// `damage` is not empty.
+    fn present_pixels(
+        &self,
+        buffer: &[u32],
+        size: Size,
+        damage: Damage<'_>,
+    ) -> Result<(), PresentError>;
 }
 
+#[cfg(feature = "opengl")]
 pub trait PlatformOpenGl {
     fn swap_buffers(&self) -> Result<(), SwapBuffersError>;
     fn make_current(&self, current: bool) -> Result<(), MakeCurrentError>;
     fn get_proc_address(&self, name: &CStr) -> *const c_void;
+    fn version(&self) -> Option<GlVersion>;
+}
+
+/// Create an OpenGL context with the first of `configs` that works, see
+/// [`WindowBuilder::with_opengl_fallback_chain`]. If none do, the error of the
+/// last attempt is returned.
+///
+/// `create` is told whether the config is the last resort: only then may a
+/// backend settle for a legacy context of whatever version the driver hands
+/// out. Before that it has to fail, so the next config gets its turn.
+#[cfg(feature = "opengl")]
+pub fn create_opengl<T>(
+    configs: &[GlConfig],
+    mut create: impl FnMut(&GlConfig, bool) -> Result<T, OpenGlError>,
+) -> Result<T, OpenGlError> {
+    let mut result = Err(OpenGlError::NotRequested);
+    for (index, config) in configs.iter().enumerate() {
+        result = create(config, index + 1 == configs.len());
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
 }
 
 pub trait PlatformWaker: Send + Sync + 'static {
     fn wakeup(&self) -> Result<(), WakeupError>;
+    fn close(&self) -> Result<(), WakeupError>;
+    fn close_and_wait(&self);
 }
 
 impl PlatformWaker for () {
     fn wakeup(&self) -> Result<(), WakeupError> {
         Err(WakeupError)
     }
+
+    fn close(&self) -> Result<(), WakeupError> {
+        Err(WakeupError)
+    }
+
+    fn close_and_wait(&self) {}
+}
+
+/// Receives the result of [`Window::open_url`].
+pub type OpenUrlCallback = Box<dyn FnOnce(Result<(), OpenUrlError>) + Send>;
+
+/// Check that `url` is safe to hand to the system opener: either a URI with a
+/// well formed scheme (RFC 3986, `https:`, `mailto:`, custom schemes...) or a
+/// file path.
+pub fn validate_url(url: &str) -> Result<(), OpenUrlError> {
+    if url.trim().is_empty() || url.starts_with('-') || url.chars().any(char::is_control) {
+        return Err(OpenUrlError::InvalidUrl);
+    }
+
+    let Some((scheme, rest)) = url.split_once(':') else {
+        return Ok(());
+    };
+
+    // single letters are drive letters (`C:\`), anything with a separator in
+    // front of the colon is a path too
+    if scheme.len() < 2 || scheme.contains(['/', '\\']) {
+        return Ok(());
+    }
+
+    let mut chars = scheme.chars();
+    let is_valid_scheme = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+
+    match is_valid_scheme && !rest.is_empty() {
+        true => Ok(()),
+        false => Err(OpenUrlError::InvalidUrl),
+    }
+}
+
+/// Validate `url` and `launch` it on a worker thread, so slow openers never
+/// block the event loop. `on_done` gets the result on that thread, or right
+/// away if the url is invalid or the thread could not be started.
+pub fn open_url_in_background(
+    url: &str,
+    on_done: OpenUrlCallback,
+    launch: fn(&str) -> Result<(), OpenUrlError>,
+) {
+    if let Err(err) = validate_url(url) {
+        return on_done(Err(err));
+    }
+
+    // kept out here so it can still be called if the thread never starts
+    let on_done = Arc::new(Mutex::new(Some(on_done)));
+    let take = |slot: &Mutex<Option<OpenUrlCallback>>| {
+        slot.lock().unwrap_or_else(PoisonError::into_inner).take()
+    };
+
+    let url = url.to_owned();
+    let result = std::thread::Builder::new().spawn({
+        let on_done = on_done.clone();
+        move || {
+            if let Some(on_done) = take(&on_done) {
+                on_done(launch(&url));
+            }
+        }
+    });
+
+    if let Err(err) = result
+        && let Some(on_done) = take(&on_done)
+    {
+        on_done(Err(OpenUrlError::Platform(format!(
+            "failed to start a thread: {err}"
+        ))));
+    }
+}
+
+/// The formats [`Window::get_clipboard`] reads, in order.
+pub static CLIPBOARD_FORMATS: [ClipboardFormat; 3] = [
+    ClipboardFormat::Files,
+    ClipboardFormat::Text,
+    ClipboardFormat::Image,
+];
+
+/// The formats to read for [`PlatformWindow::get_clipboard`], in order.
+pub fn clipboard_formats(format: Option<&ClipboardFormat>) -> &[ClipboardFormat] {
+    format.map_or(&CLIPBOARD_FORMATS, std::slice::from_ref)
+}
+
+/// Check that `name` can be used as a [`ClipboardFormat::Custom`] on every
+/// platform: X11 atoms and macOS pasteboard types are C strings.
+pub fn is_valid_format_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('\0')
+}
+
+/// Get the `format` from clipboard `data` the window holds itself, the way
+/// [`PlatformWindow::get_clipboard`] reads it from the system.
+#[cfg_attr(
+    not(any(feature = "headless", all(feature = "x11", target_os = "linux"))),
+    allow(dead_code)
+)]
+pub fn filter_clipboard(
+    data: Exchange,
+    format: Option<&ClipboardFormat>,
+) -> Result<Exchange, ClipboardError> {
+    match data.format() {
+        None => Err(ClipboardError::Empty),
+        Some(data_format) if clipboard_formats(format).contains(&data_format) => Ok(data),
+        Some(_) => Err(ClipboardError::UnsupportedFormat),
+    }
+}
+
+/// Called when an event arrives while the handler is running, see the
+/// reentrancy notes on [`WindowHandler`]. Prints where the event came from with
+/// the `debug-reentrancy` feature.
+#[inline]
+pub fn report_reentrancy(deferred: bool) {
+    #[cfg(feature = "debug-reentrancy")]
+    eprintln!(
+        "picoview: event {} because the handler is running, at:\n{}",
+        if deferred { "deferred" } else { "skipped" },
+        std::backtrace::Backtrace::force_capture()
+    );
+
+    #[cfg(not(feature = "debug-reentrancy"))]
+    let _ = deferred;
+}
+
+/// Measures how long the [`WindowHandler`] takes to handle events, see
+/// [`WindowBuilder::with_watchdog`].
+pub struct Watchdog {
+    threshold: Option<Duration>,
+    last_stall: Cell<Option<Duration>>,
+}
+
+impl Watchdog {
+    pub fn new(threshold: Option<Duration>) -> Self {
+        Self {
+            threshold,
+            last_stall: Cell::new(None),
+        }
+    }
+
+    /// Run `f` and remember how long it took if it was over the threshold.
+    pub fn measure<R>(&self, f: impl FnOnce() -> R) -> R {
+        let Some(threshold) = self.threshold else {
+            return f();
+        };
+
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+
+        if elapsed >= threshold {
+            self.last_stall.set(Some(elapsed));
+        }
+
+        result
+    }
+
+    /// Take the duration of the last stall measured by
+    /// [`Watchdog::measure`], if any.
+    pub fn take_stall(&self) -> Option<Duration> {
+        self.last_stall.take()
+    }
+}
+
+/// Events held back while the handler is busy, delivered in order once it
+/// returns. Used by the backends that get events while the handler runs.
+///
+/// A handler stuck in a modal loop (like a drag) could let it grow without
+/// bounds, so it only keeps about [`EventQueue::CAPACITY`] events: past that,
+/// the oldest event that only reports a state that a later event reports
+/// again is dropped, or else the oldest [`QueueKind::Lossy`] event. What was
+/// dropped and merged is counted for [`Window::frame_stats`].
+///
+/// Critical events are only dropped past [`EventQueue::LIMIT`], oldest first:
+/// losing a key or button release would leave the handler believing it is
+/// still held. They only arrive as fast as the user types and clicks, so it
+/// takes a very long modal loop to get there.
+#[cfg_attr(
+    not(any(
+        feature = "headless",
+        all(feature = "win32", target_os = "windows"),
+        all(feature = "appkit", target_os = "macos"),
+        all(feature = "x11", target_os = "linux")
+    )),
+    allow(dead_code)
+)]
+pub struct EventQueue<T: ?Sized> {
+    events: RefCell<VecDeque<QueuedEvent<T>>>,
+    /// The sequence numbers of the queued events of each kind of `latest`
+    /// events, oldest first. All but the last of each are superseded.
+    kinds: RefCell<Vec<(TypeId, VecDeque<u64>)>>,
+    /// The sequence numbers of the queued lossy events, oldest first.
+    lossy: RefCell<VecDeque<u64>>,
+    next_seq: Cell<u64>,
+    dropped: Cell<u64>,
+    coalesced: Cell<u64>,
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "headless",
+        all(feature = "win32", target_os = "windows"),
+        all(feature = "appkit", target_os = "macos"),
+        all(feature = "x11", target_os = "linux")
+    )),
+    allow(dead_code)
+)]
+impl<T: ?Sized> EventQueue<T> {
+    /// Enough for a few frames worth of high rate mouse input.
+    pub const CAPACITY: usize = 256;
+
+    /// The most events ever queued, critical ones included.
+    pub const LIMIT: usize = Self::CAPACITY * 4;
+
+    pub fn new() -> Self {
+        Self {
+            events: RefCell::new(VecDeque::new()),
+            kinds: RefCell::new(Vec::new()),
+            lossy: RefCell::new(VecDeque::new()),
+            next_seq: Cell::new(0),
+            dropped: Cell::new(0),
+            coalesced: Cell::new(0),
+        }
+    }
+
+    /// Queue an event after the others.
+    ///
+    /// A [`QueueKind::Latest`] event replaces the same one right before it,
+    /// and can be dropped when the queue is full if the same one follows, so
+    /// the latest state always arrives. The other kinds are dropped when the
+    /// queue is full as described in [`EventQueue`].
+    pub fn push(&self, kind: QueueKind, event: Box<T>) {
+        let mut events = self.events.borrow_mut();
+        let mut kinds = self.kinds.borrow_mut();
+        let mut lossy = self.lossy.borrow_mut();
+
+        if let QueueKind::Latest(_) = kind
+            && let Some(last) = events.back_mut()
+            && last.kind == kind
+        {
+            last.event = event;
+            self.coalesced.set(self.coalesced.get().saturating_add(1));
+            return;
+        }
+
+        let seq = self.next_seq.get();
+        self.next_seq.set(seq.wrapping_add(1));
+
+        match kind {
+            QueueKind::Latest(latest) => match kinds.iter_mut().find(|(kind, _)| *kind == latest) {
+                Some((_, seqs)) => seqs.push_back(seq),
+                None => kinds.push((latest, VecDeque::from([seq]))),
+            },
+            QueueKind::Lossy => lossy.push_back(seq),
+            QueueKind::Critical => {}
+        }
+
+        events.push_back(QueuedEvent { kind, seq, event });
+
+        if events.len() <= Self::CAPACITY {
+            return;
+        }
+
+        let removed = match Self::oldest_superseded(&mut kinds).or_else(|| lossy.pop_front()) {
+            Some(seq) => events
+                .binary_search_by_key(&seq, |queued| queued.seq)
+                .ok()
+                .and_then(|index| events.remove(index)),
+            None if events.len() > Self::LIMIT => events.pop_front().inspect(|queued| {
+                Self::forget(&mut kinds, &mut lossy, queued.kind);
+            }),
+            None => None,
+        };
+
+        if removed.is_some() {
+            self.dropped.set(self.dropped.get().saturating_add(1));
+        }
+
+        // dropped outside of the borrows, in case the closure owns something
+        // that queues
+        drop((events, kinds, lossy));
+        drop(removed);
+    }
+
+    /// Forget the oldest queued event of `kind` after it was taken from the
+    /// front of the queue.
+    fn forget(kinds: &mut [(TypeId, VecDeque<u64>)], lossy: &mut VecDeque<u64>, kind: QueueKind) {
+        match kind {
+            QueueKind::Latest(latest) => {
+                if let Some((_, seqs)) = kinds.iter_mut().find(|(kind, _)| *kind == latest) {
+                    seqs.pop_front();
+                }
+            }
+            QueueKind::Lossy => {
+                lossy.pop_front();
+            }
+            QueueKind::Critical => {}
+        }
+    }
+
+    /// Forget the oldest event that a later one of the same kind supersedes
+    /// and return its sequence number. Only the first queued event of each
+    /// kind is a candidate, so this does not look at the events themselves.
+    fn oldest_superseded(kinds: &mut [(TypeId, VecDeque<u64>)]) -> Option<u64> {
+        let (_, seqs) = kinds
+            .iter_mut()
+            .filter(|(_, seqs)| seqs.len() > 1)
+            .min_by_key(|(_, seqs)| seqs.front().copied())?;
+
+        seqs.pop_front()
+    }
+
+    /// Take the next event. The queue is not borrowed afterwards, so the
+    /// event may queue more.
+    pub fn pop(&self) -> Option<Box<T>> {
+        let queued = self.events.borrow_mut().pop_front()?;
+
+        Self::forget(
+            &mut self.kinds.borrow_mut(),
+            &mut self.lossy.borrow_mut(),
+            queued.kind,
+        );
+
+        Some(queued.event)
+    }
+
+    pub fn clear(&self) {
+        self.kinds.borrow_mut().clear();
+        self.lossy.borrow_mut().clear();
+
+        // dropped outside of the borrow, in case a closure owns something that queues
+        let events = self.events.take();
+        drop(events);
+    }
+
+    /// The events dropped because the queue was full so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.get()
+    }
+
+    /// The events replaced by a later one of the same kind so far.
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.get()
+    }
+}
+
+/// How an event queued in an [`EventQueue`] may be dropped when it is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueueKind {
+    /// Only reports the latest state of something, like the mouse position.
+    /// Holds the type of the closure delivering it.
+    Latest(TypeId),
+    /// Losing it leaves the handler with nothing to correct, like a scroll or
+    /// a timer tick.
+    Lossy,
+    /// Everything else, like key and button events.
+    Critical,
+}
+
+struct QueuedEvent<T: ?Sized> {
+    kind: QueueKind,
+    /// Increasing along the queue, to find an event after others were removed
+    seq: u64,
+    event: Box<T>,
+}
+
+/// The regions passed to [`Window::present_pixels`], clipped to the buffer
+/// while iterating over them so presenting a frame does not allocate.
+#[derive(Clone, Copy)]
+pub struct Damage<'a> {
+    rects: &'a [Rect],
+    bounds: Rect,
+}
+
+impl<'a> Damage<'a> {
+    /// The `rects` of a buffer of `size`, all of it if there are none.
+    pub fn new(rects: &'a [Rect], size: Size) -> Self {
+        Self {
+            rects,
+            bounds: Rect::from_size(size),
+        }
+    }
+
+    /// The non-empty rectangles inside of the buffer.
+    pub fn iter(&self) -> impl Iterator<Item = Rect> + 'a {
+        let bounds = self.bounds;
+        self.rects
+            .iter()
+            .copied()
+            .chain(self.rects.is_empty().then_some(bounds))
+            .map(move |rect| rect.intersection(&bounds))
+            .filter(|rect| rect.size().width > 0 && rect.size().height > 0)
+    }
+
+    /// Is nothing inside of the buffer damaged?
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+/// Copy the `rect` of a `src_size` pixel buffer into a `dst_size` one at the
+/// same position, skipping what does not fit into either.
+#[cfg_attr(
+    not(any(feature = "headless", all(feature = "win32", target_os = "windows"))),
+    allow(dead_code)
+)]
+pub fn copy_pixels(src: &[u32], src_size: Size, dst: &mut [u32], dst_size: Size, rect: Rect) {
+    let rect = rect
+        .intersection(&Rect::from_size(src_size))
+        .intersection(&Rect::from_size(dst_size));
+
+    let Size { width, height } = rect.size();
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    // inside of both buffers, so never negative
+    let (left, top) = (rect.left as usize, rect.top as usize);
+    for row in top..top + height as usize {
+        let src_start = row * src_size.width as usize + left;
+        let dst_start = row * dst_size.width as usize + left;
+
+        if let (Some(src), Some(dst)) = (
+            src.get(src_start..src_start + width as usize),
+            dst.get_mut(dst_start..dst_start + width as usize),
+        ) {
+            dst.copy_from_slice(src);
+        }
+    }
+}
+
+/// Convert a `range` of UTF-16 code units in `text`, the way the input
+/// methods of Windows and macOS count, to a range of bytes. Indices past the
+/// end or inside of a character move to the next character boundary.
+#[cfg_attr(
+    not(any(
+        all(feature = "win32", target_os = "windows"),
+        all(feature = "appkit", target_os = "macos")
+    )),
+    allow(dead_code)
+)]
+pub fn utf16_range_to_bytes(text: &str, range: Range<usize>) -> Range<usize> {
+    let offset = |index: usize| {
+        let mut units = 0;
+        for (offset, ch) in text.char_indices() {
+            if units >= index {
+                return offset;
+            }
+
+            units += ch.len_utf16();
+        }
+
+        text.len()
+    };
+
+    let start = offset(range.start);
+    start..offset(range.end).max(start)
+}
+
+/// The keys held down while a window is focused, see [`Window::is_key_down`].
+pub struct KeyState(Cell<[u64; 4]>);
+
+impl KeyState {
+    pub fn new() -> Self {
+        Self(Cell::new([0; 4]))
+    }
+
+    pub fn set(&self, key: Key, down: bool) {
+        let index = key as usize;
+        let mut keys = self.0.get();
+
+        if let Some(word) = keys.get_mut(index / 64) {
+            let mask = 1 << (index % 64);
+            *word = if down { *word | mask } else { *word & !mask };
+        }
+
+        self.0.set(keys);
+    }
+
+    pub fn is_down(&self, key: Key) -> bool {
+        let index = key as usize;
+        self.0
+            .get()
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    /// Forget all keys and start over with `keys`, the keys the system reports
+    /// as held when the window gains focus (or none when it loses it).
+    pub fn reset(&self, keys: impl IntoIterator<Item = Key>) {
+        self.0.set([0; 4]);
+        for key in keys {
+            self.set(key, true);
+        }
+    }
+}
+
+/// Measures how regularly [`WindowHandler::frame`] is called, see
+/// [`Window::frame_jitter`].
+pub struct FrameJitter {
+    last_frame: Cell<Option<Instant>>,
+    last_interval: Cell<Option<Duration>>,
+    jitter: Cell<Duration>,
+}
+
+impl FrameJitter {
+    /// How many frames the jitter is smoothed over, same as the interarrival
+    /// jitter estimate of RFC 3550.
+    const SMOOTHING: f64 = 16.0;
+
+    pub fn new() -> Self {
+        Self {
+            last_frame: Cell::new(None),
+            last_interval: Cell::new(None),
+            jitter: Cell::new(Duration::ZERO),
+        }
+    }
+
+    /// Record a frame delivered at `now`.
+    pub fn measure(&self, now: Instant) {
+        let Some(last_frame) = self.last_frame.replace(Some(now)) else {
+            return;
+        };
+
+        let interval = now.saturating_duration_since(last_frame);
+        let Some(last_interval) = self.last_interval.replace(Some(interval)) else {
+            return;
+        };
+
+        let deviation = interval.abs_diff(last_interval).as_secs_f64();
+        let jitter = self.jitter.get().as_secs_f64();
+        let jitter = jitter + (deviation - jitter) / Self::SMOOTHING;
+        self.jitter.set(Duration::from_secs_f64(jitter.max(0.0)));
+    }
+
+    /// The smoothed jitter measured so far.
+    pub fn get(&self) -> Duration {
+        self.jitter.get()
+    }
+}
+
+/// The timers set with [`Window::set_timer`], for backends that run them from
+/// their own event loop. `T` is the clock, [`Instant`] or the virtual time of
+/// headless windows.
+#[cfg_attr(
+    not(any(feature = "headless", all(feature = "x11", target_os = "linux"))),
+    allow(dead_code)
+)]
+pub struct Timers<T>(RefCell<Vec<(u32, Duration, T)>>);
+
+#[cfg_attr(
+    not(any(feature = "headless", all(feature = "x11", target_os = "linux"))),
+    allow(dead_code)
+)]
+impl<T: Copy + Ord + Add<Duration, Output = T>> Timers<T> {
+    pub fn new() -> Self {
+        Self(RefCell::new(Vec::new()))
+    }
+
+    /// Start the timer `id`, first expiring one `interval` after `now`.
+    pub fn set(&self, id: u32, interval: Duration, now: T) {
+        let mut timers = self.0.borrow_mut();
+        timers.retain(|(timer, ..)| *timer != id);
+        timers.push((id, interval, now + interval));
+    }
+
+    pub fn cancel(&self, id: u32) {
+        self.0.borrow_mut().retain(|(timer, ..)| *timer != id);
+    }
+
+    /// When the next timer expires, if there are any.
+    pub fn next_deadline(&self) -> Option<T> {
+        self.0.borrow().iter().map(|(.., deadline)| *deadline).min()
+    }
+
+    /// Take the id of the earliest timer that expired at `now` and schedule
+    /// its next expiration. A timer that fell behind skips the expirations it
+    /// missed instead of catching up on them.
+    pub fn take_expired(&self, now: T) -> Option<u32> {
+        let mut timers = self.0.borrow_mut();
+        let (id, interval, deadline) = timers
+            .iter_mut()
+            .filter(|(.., deadline)| *deadline <= now)
+            .min_by_key(|(.., deadline)| *deadline)?;
+
+        *deadline = match *deadline + *interval {
+            next if next > now => next,
+            _ => now + *interval,
+        };
+
+        Some(*id)
+    }
+}
+
+/// Open windows of the process by their native handle, see [`WindowEntry`].
+static WINDOWS: Mutex<Vec<(usize, WindowId)>> = Mutex::new(Vec::new());
+
+/// Registers an open window for [`crate::window_at`] until dropped.
+pub struct WindowEntry(WindowId);
+
+impl WindowEntry {
+    /// Register a window by its native handle (`HWND`, X11 window or `NSView`
+    /// pointer) under a new [`WindowId`].
+    pub fn new(handle: usize) -> Self {
+        let id = WindowId::next();
+        Self::windows().push((handle, id));
+        Self(id)
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.0
+    }
+
+    /// Find the registered window with the given native handle.
+    pub fn find(handle: usize) -> Option<WindowId> {
+        Self::windows()
+            .iter()
+            .find(|(entry, _)| *entry == handle)
+            .map(|(_, id)| *id)
+    }
+
+    fn windows() -> std::sync::MutexGuard<'static, Vec<(usize, WindowId)>> {
+        // the list is always left in a valid state, ignore the poison
+        WINDOWS.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Drop for WindowEntry {
+    fn drop(&mut self) {
+        Self::windows().retain(|(_, id)| *id != self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventQueue, QueueKind, utf16_range_to_bytes};
+    use std::any::TypeId;
+
+    fn drain(queue: &EventQueue<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| queue.pop().map(|event| *event)).collect()
+    }
+
+    #[test]
+    fn test_event_queue_coalesces_consecutive_latest() {
+        let queue = EventQueue::new();
+        let mouse = QueueKind::Latest(TypeId::of::<u8>());
+
+        queue.push(mouse, Box::new(1));
+        queue.push(mouse, Box::new(2));
+        queue.push(QueueKind::Critical, Box::new(3));
+        queue.push(mouse, Box::new(4));
+        queue.push(mouse, Box::new(5));
+
+        assert_eq!(drain(&queue), [2, 3, 5]);
+        assert_eq!(queue.coalesced(), 2);
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn test_event_queue_drops_oldest_superseded() {
+        let queue = EventQueue::new();
+        let mouse = QueueKind::Latest(TypeId::of::<u8>());
+        let resize = QueueKind::Latest(TypeId::of::<u16>());
+
+        // interleaved so that nothing coalesces
+        queue.push(resize, Box::new(0));
+        queue.push(mouse, Box::new(1));
+        for index in 2..EventQueue::<u32>::CAPACITY as u32 {
+            queue.push(QueueKind::Critical, Box::new(index));
+        }
+        queue.push(resize, Box::new(1000));
+        queue.push(mouse, Box::new(1001));
+
+        let events = drain(&queue);
+        assert_eq!(events.len(), EventQueue::<u32>::CAPACITY);
+        assert_eq!(events.first(), Some(&2));
+        assert_eq!(events.get(events.len() - 2..), Some(&[1000, 1001][..]));
+        assert_eq!(queue.dropped(), 2);
+    }
+
+    #[test]
+    fn test_event_queue_keeps_other_events() {
+        let queue = EventQueue::new();
+        let mouse = QueueKind::Latest(TypeId::of::<u8>());
+
+        let count = EventQueue::<u32>::CAPACITY as u32 * 2;
+        queue.push(mouse, Box::new(count));
+        for index in 0..count {
+            queue.push(QueueKind::Critical, Box::new(index));
+        }
+
+        let events = drain(&queue);
+        assert_eq!(events.len(), count as usize + 1);
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn test_event_queue_drops_oldest_lossy() {
+        let queue = EventQueue::new();
+
+        // a scroll among key presses, then more than fits
+        queue.push(QueueKind::Critical, Box::new(0));
+        queue.push(QueueKind::Lossy, Box::new(1));
+        for index in 2..EventQueue::<u32>::CAPACITY as u32 + 1 {
+            queue.push(QueueKind::Critical, Box::new(index));
+        }
+
+        let events = drain(&queue);
+        assert_eq!(events.len(), EventQueue::<u32>::CAPACITY);
+        assert_eq!(events.get(..2), Some(&[0, 2][..]));
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[test]
+    fn test_event_queue_limit() {
+        let queue = EventQueue::new();
+
+        let count = EventQueue::<u32>::LIMIT as u32 + 10;
+        for index in 0..count {
+            queue.push(QueueKind::Critical, Box::new(index));
+        }
+
+        let events = drain(&queue);
+        assert_eq!(events.len(), EventQueue::<u32>::LIMIT);
+        assert_eq!(events.first(), Some(&10));
+        assert_eq!(events.last(), Some(&(count - 1)));
+        assert_eq!(queue.dropped(), 10);
+    }
+
+    #[test]
+    fn test_utf16_range_ascii() {
+        assert_eq!(utf16_range_to_bytes("hello", 1..3), 1..3);
+        assert_eq!(utf16_range_to_bytes("hello", 0..5), 0..5);
+    }
+
+    #[test]
+    fn test_utf16_range_multi_byte() {
+        // "é" is one UTF-16 unit but two bytes, "あ" one unit and three bytes
+        assert_eq!(utf16_range_to_bytes("éあx", 1..2), 2..5);
+        assert_eq!(utf16_range_to_bytes("éあx", 2..3), 5..6);
+    }
+
+    #[test]
+    fn test_utf16_range_surrogate_pair() {
+        // "😀" is two UTF-16 units, an index between them moves past it
+        assert_eq!(utf16_range_to_bytes("a😀b", 1..3), 1..5);
+        assert_eq!(utf16_range_to_bytes("a😀b", 2..3), 5..5);
+    }
+
+    #[test]
+    fn test_utf16_range_out_of_bounds() {
+        assert_eq!(utf16_range_to_bytes("abc", 2..10), 2..3);
+        assert_eq!(utf16_range_to_bytes("abc", 5..7), 3..3);
+    }
 }
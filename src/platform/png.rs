@@ -0,0 +1,987 @@
+use crate::{Image, Size};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The most pixels a decoded image may have, so a small file can not make us
+/// allocate gigabytes.
+const MAX_PIXELS: usize = 1 << 26;
+
+/// Encode an image as an 8-bit RGBA PNG, the format images are exchanged in.
+///
+/// The pixels are compressed with the fixed Huffman codes of deflate, which
+/// keeps the encoder small while still shrinking the flat areas most images
+/// pasted between applications have.
+pub fn encode(image: &Image) -> Vec<u8> {
+    let Size { width, height } = image.size();
+    let stride = width as usize * 4;
+
+    // every row starts with its filter, none
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in image.pixels().chunks_exact(stride) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // 8 bits per sample, RGBA, deflate, adaptive filters, not interlaced
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    for data in zlib_compress(&raw).chunks(1 << 20) {
+        write_chunk(&mut png, b"IDAT", data);
+    }
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Where the pixels of every pass of Adam7 interlacing start and how far
+/// apart they are, as `(x, y, dx, dy)`.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Decode a PNG of any color type, bit depth and interlacing into 8-bit
+/// RGBA. `None` if the data is not a PNG or is damaged.
+pub fn decode(data: &[u8]) -> Option<Image> {
+    let mut chunks = data.strip_prefix(&SIGNATURE)?;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+
+    while let Some((kind, body, rest)) = read_chunk(chunks) {
+        match kind {
+            b"IHDR" => header = Some(body),
+            b"PLTE" => palette = body,
+            b"tRNS" => transparency = body,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+
+        chunks = rest;
+    }
+
+    let header = header?;
+    let width = u32::from_be_bytes(header.get(0..4)?.try_into().ok()?);
+    let height = u32::from_be_bytes(header.get(4..8)?.try_into().ok()?);
+    let (depth, color_type) = (*header.get(8)?, *header.get(9)?);
+    let passes: &[_] = match header.get(10..13)? {
+        [0, 0, 0] => &[(0, 0, 1, 1)],
+        [0, 0, 1] => &ADAM7,
+        _ => return None,
+    };
+
+    let channels = match (color_type, depth) {
+        (0, 1 | 2 | 4 | 8 | 16) => 1,
+        (2, 8 | 16) => 3,
+        (3, 1 | 2 | 4 | 8) => 1,
+        (4, 8 | 16) => 2,
+        (6, 8 | 16) => 4,
+        _ => return None,
+    };
+
+    let pixels = (width as usize).checked_mul(height as usize)?;
+    if pixels == 0 || pixels > MAX_PIXELS {
+        return None;
+    }
+
+    // the size of a pass in pixels and its bytes per row, passes that are
+    // empty in small images have no rows at all
+    let bits_per_pixel = channels * depth as usize;
+    let pass_size = |&(x, y, dx, dy): &(usize, usize, usize, usize)| {
+        let pass_width = (width as usize).saturating_sub(x).div_ceil(dx);
+        let pass_height = match pass_width {
+            0 => 0,
+            _ => (height as usize).saturating_sub(y).div_ceil(dy),
+        };
+        (
+            pass_width,
+            pass_height,
+            (pass_width * bits_per_pixel).div_ceil(8),
+        )
+    };
+
+    let len = passes
+        .iter()
+        .map(pass_size)
+        .map(|(_, height, stride)| (stride + 1) * height)
+        .sum();
+    let data = inflate(&compressed, len)?;
+    if data.len() != len {
+        return None;
+    }
+
+    // a sample at the bit depth of the image
+    let sample = |row: &[u8], index: usize| -> Option<u16> {
+        Some(match depth {
+            16 => u16::from_be_bytes(row.get(index * 2..index * 2 + 2)?.try_into().ok()?),
+            8 => *row.get(index)? as u16,
+            _ => {
+                let bit = index * depth as usize;
+                let shift = 8 - depth as usize - bit % 8;
+                (*row.get(bit / 8)? as u16 >> shift) & ((1 << depth) - 1)
+            }
+        })
+    };
+
+    let to_8bit = |value: u16| -> u8 {
+        match depth {
+            16 => (value >> 8) as u8,
+            8 => value as u8,
+            _ => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+        }
+    };
+
+    // the single color that is transparent, for the types without alpha
+    let transparent = |samples: &[u16]| -> bool {
+        matches!(color_type, 0 | 2)
+            && transparency.len() == samples.len() * 2
+            && transparency
+                .chunks_exact(2)
+                .zip(samples)
+                .all(|(key, sample)| {
+                    <[u8; 2]>::try_from(key).is_ok_and(|key| u16::from_be_bytes(key) == *sample)
+                })
+    };
+
+    let mut rgba = vec![0; pixels * 4];
+    let mut rest = data.as_slice();
+    for pass @ &(x0, y0, dx, dy) in passes {
+        let (pass_width, pass_height, stride) = pass_size(pass);
+        let (filtered, next) = rest.split_at_checked((stride + 1) * pass_height)?;
+        rest = next;
+        if pass_height == 0 {
+            continue;
+        }
+
+        let rows = unfilter(filtered, stride, bits_per_pixel.div_ceil(8))?;
+        for (y, row) in rows.chunks_exact(stride).enumerate() {
+            for x in 0..pass_width {
+                let mut samples = [0; 4];
+                for (channel, value) in samples.iter_mut().take(channels).enumerate() {
+                    *value = sample(row, x * channels + channel)?;
+                }
+
+                let [a, b, c, d] = samples;
+                let alpha = |samples: &[u16]| if transparent(samples) { 0 } else { 255 };
+                let pixel = match color_type {
+                    0 => [to_8bit(a), to_8bit(a), to_8bit(a), alpha(&[a])],
+                    2 => [to_8bit(a), to_8bit(b), to_8bit(c), alpha(&[a, b, c])],
+                    3 => {
+                        let index = a as usize;
+                        let color = palette.get(index * 3..index * 3 + 3)?;
+                        let alpha = transparency.get(index).copied().unwrap_or(255);
+                        [*color.first()?, *color.get(1)?, *color.get(2)?, alpha]
+                    }
+                    4 => [to_8bit(a), to_8bit(a), to_8bit(a), to_8bit(b)],
+                    _ => [to_8bit(a), to_8bit(b), to_8bit(c), to_8bit(d)],
+                };
+
+                let index = ((y0 + y * dy) * width as usize + x0 + x * dx) * 4;
+                rgba.get_mut(index..index + 4)?.copy_from_slice(&pixel);
+            }
+        }
+    }
+
+    Image::new((width, height), rgba)
+}
+
+/// Split the next chunk off of `data`, into its type, its data and the rest.
+fn read_chunk(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let len = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) as usize;
+    let kind = data.get(4..8)?;
+    let body = data.get(8..len.checked_add(8)?)?;
+    // the CRC is not checked, clipboard data does not get corrupted in transit
+    let rest = data.get(len.checked_add(12)?..)?;
+    Some((kind, body, rest))
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let start = png.len() + 4;
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(png.get(start..).unwrap_or_default());
+    png.extend(crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
+
+/// How far back deflate matches may reach.
+const WINDOW_SIZE: usize = 1 << 15;
+/// How many earlier positions with the same hash are tried for a match.
+const MAX_CHAIN: usize = 32;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// Compress `data` into a zlib stream (RFC 1950, 1951) of a single block with
+/// the fixed Huffman codes, finding matches through chains of earlier
+/// positions with the same hash.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut matcher = Matcher {
+        data,
+        head: vec![usize::MAX; 1 << 15],
+        prev: vec![usize::MAX; WINDOW_SIZE],
+    };
+
+    let mut bits = BitWriter::default();
+    // deflate with a 32K window, no dictionary, fastest
+    bits.bytes.extend([0x78, 0x01]);
+    // the last block, fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut pos = 0;
+    while let Some(&byte) = data.get(pos) {
+        match matcher.longest_match(pos) {
+            Some((len, distance)) => {
+                bits.write_length(len, distance);
+                for pos in pos..pos + len {
+                    matcher.insert(pos);
+                }
+                pos += len;
+            }
+            None => {
+                bits.write_literal(byte as u16);
+                matcher.insert(pos);
+                pos += 1;
+            }
+        }
+    }
+
+    bits.write_literal(256);
+    let mut zlib = bits.finish();
+    zlib.extend(adler32(data).to_be_bytes());
+    zlib
+}
+
+/// Finds earlier occurrences of the bytes at a position for deflate.
+struct Matcher<'a> {
+    data: &'a [u8],
+    /// The last position of every hash
+    head: Vec<usize>,
+    /// For every position in the window, the one before it with the same hash
+    prev: Vec<usize>,
+}
+
+impl Matcher<'_> {
+    fn hash(&self, pos: usize) -> Option<usize> {
+        let bytes = self.data.get(pos..pos + MIN_MATCH)?;
+        let value = bytes
+            .iter()
+            .fold(0u32, |value, &byte| value << 8 | byte as u32);
+        Some((value.wrapping_mul(0x9e3779b1) >> 17) as usize)
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if let Some(hash) = self.hash(pos)
+            && let Some(head) = self.head.get_mut(hash)
+            && let Some(prev) = self.prev.get_mut(pos % WINDOW_SIZE)
+        {
+            *prev = std::mem::replace(head, pos);
+        }
+    }
+
+    /// The longest match for the bytes at `pos`, as its length and distance.
+    fn longest_match(&self, pos: usize) -> Option<(usize, usize)> {
+        let rest = self.data.get(pos..)?;
+        let mut candidate = *self.head.get(self.hash(pos)?)?;
+        let mut best: Option<(usize, usize)> = None;
+
+        for _ in 0..MAX_CHAIN {
+            // the chain only goes back, anything else was overwritten by a later position
+            if candidate >= pos || pos - candidate > WINDOW_SIZE {
+                break;
+            }
+
+            let len = self
+                .data
+                .get(candidate..)?
+                .iter()
+                .zip(rest)
+                .take(MAX_MATCH)
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if len >= MIN_MATCH && best.is_none_or(|(best, _)| len > best) {
+                best = Some((len, pos - candidate));
+                if len == MAX_MATCH {
+                    break;
+                }
+            }
+
+            candidate = *self.prev.get(candidate % WINDOW_SIZE)?;
+        }
+
+        best
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(4096) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+
+        (a, b) = (a % 65521, b % 65521);
+    }
+
+    (b << 16) | a
+}
+
+/// Writes a deflate stream, starting with the least significant bit of every
+/// byte.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    pending: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.pending |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.pending as u8);
+            self.pending >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which starts with its most significant bit.
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    /// Write a literal or the end of block with the fixed literal code.
+    fn write_literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..144 => self.write_code(0x30 + symbol, 8),
+            144..256 => self.write_code(0x190 + symbol - 144, 9),
+            256..280 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    /// Write a match of `len` bytes starting `distance` bytes back.
+    fn write_length(&mut self, len: usize, distance: usize) {
+        // 258 has a code of its own, which is the last one
+        let index = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= len)
+            .unwrap_or_default();
+        self.write_literal(257 + index as u16);
+        if let (Some(&base), Some(&extra)) = (LENGTH_BASE.get(index), LENGTH_EXTRA.get(index)) {
+            self.write((len - base as usize) as u32, extra as u32);
+        }
+
+        let index = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap_or_default();
+        self.write_code(index as u32, 5);
+        if let (Some(&base), Some(&extra)) = (DISTANCE_BASE.get(index), DISTANCE_EXTRA.get(index)) {
+            self.write((distance - base as usize) as u32, extra as u32);
+        }
+    }
+
+    /// Pad the last byte with zeros and return the bytes written.
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.pending as u8);
+        }
+
+        self.bytes
+    }
+}
+
+/// Undo the filters of the rows in `data`, each of them `stride` bytes after
+/// the filter type. `bpp` is the number of bytes per pixel, rounded up.
+fn unfilter(data: &[u8], stride: usize, bpp: usize) -> Option<Vec<u8>> {
+    let rows = data.len() / (stride + 1);
+    let mut out = vec![0u8; rows * stride];
+    let mut previous = vec![0u8; stride];
+
+    for (line, row) in data
+        .chunks_exact(stride + 1)
+        .zip(out.chunks_exact_mut(stride))
+    {
+        let (&filter, line) = line.split_first()?;
+        for i in 0..stride {
+            let left = i.checked_sub(bpp).and_then(|i| row.get(i)).copied();
+            let up_left = i.checked_sub(bpp).and_then(|i| previous.get(i)).copied();
+            let (a, b, c) = (
+                left.unwrap_or(0),
+                previous.get(i).copied().unwrap_or(0),
+                up_left.unwrap_or(0),
+            );
+
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => {
+                    let p = a as i16 + b as i16 - c as i16;
+                    let (pa, pb, pc) = (
+                        (p - a as i16).abs(),
+                        (p - b as i16).abs(),
+                        (p - c as i16).abs(),
+                    );
+                    if pa <= pb && pa <= pc {
+                        a
+                    } else if pb <= pc {
+                        b
+                    } else {
+                        c
+                    }
+                }
+                _ => return None,
+            };
+
+            if let (Some(out), Some(value)) = (row.get_mut(i), line.get(i)) {
+                *out = value.wrapping_add(predicted);
+            }
+        }
+
+        previous.copy_from_slice(row);
+    }
+
+    Some(out)
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order the lengths of the code length code are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompress a zlib stream (RFC 1950, 1951) that is expected to hold
+/// `limit` bytes. `None` if it is damaged or holds more than that.
+fn inflate(data: &[u8], limit: usize) -> Option<Vec<u8>> {
+    let (&method, data) = data.split_first()?;
+    let (&flags, data) = data.split_first()?;
+    if method & 0x0f != 8
+        || flags & 0x20 != 0
+        || !(method as u16 * 256 + flags as u16).is_multiple_of(31)
+    {
+        return None;
+    }
+
+    let mut bits = Bits { data, pos: 0 };
+    let mut out = Vec::with_capacity(limit);
+
+    loop {
+        let is_last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                let start = bits.pos.div_ceil(8);
+                let header = data.get(start..start + 4)?;
+                let len = u16::from_le_bytes([*header.first()?, *header.get(1)?]);
+                let nlen = u16::from_le_bytes([*header.get(2)?, *header.get(3)?]);
+                if len != !nlen || out.len() + len as usize > limit {
+                    return None;
+                }
+
+                out.extend_from_slice(data.get(start + 4..start + 4 + len as usize)?);
+                bits.pos = (start + 4 + len as usize) * 8;
+            }
+            1 => {
+                let mut lengths = [0u8; 288 + 30];
+                for (symbol, length) in lengths.iter_mut().enumerate() {
+                    *length = match symbol {
+                        0..144 => 8,
+                        144..256 => 9,
+                        256..280 => 7,
+                        288.. => 5,
+                        _ => 8,
+                    };
+                }
+
+                let (literals, distances) = lengths.split_at(288);
+                inflate_block(
+                    &mut bits,
+                    &mut out,
+                    limit,
+                    &Huffman::new(literals)?,
+                    &Huffman::new(distances)?,
+                )?;
+            }
+            2 => {
+                let literal_count = bits.read(5)? as usize + 257;
+                let distance_count = bits.read(5)? as usize + 1;
+                let code_length_count = bits.read(4)? as usize + 4;
+
+                let mut code_lengths = [0u8; 19];
+                for &symbol in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+                    *code_lengths.get_mut(symbol)? = bits.read(3)? as u8;
+                }
+
+                let code_lengths = Huffman::new(&code_lengths)?;
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (length, repeat) = match code_lengths.decode(&mut bits)? {
+                        symbol @ 0..16 => (symbol as u8, 1),
+                        16 => (*lengths.last()?, 3 + bits.read(2)?),
+                        17 => (0, 3 + bits.read(3)?),
+                        _ => (0, 11 + bits.read(7)?),
+                    };
+
+                    lengths.extend(std::iter::repeat_n(length, repeat as usize));
+                }
+
+                let (literals, distances) = lengths.split_at_checked(literal_count)?;
+                inflate_block(
+                    &mut bits,
+                    &mut out,
+                    limit,
+                    &Huffman::new(literals)?,
+                    &Huffman::new(distances.get(..distance_count)?)?,
+                )?;
+            }
+            _ => return None,
+        }
+
+        if is_last {
+            return Some(out);
+        }
+    }
+}
+
+/// Decode the symbols of a compressed block until its end.
+fn inflate_block(
+    bits: &mut Bits<'_>,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Option<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Some(());
+        } else {
+            let index = symbol - 257;
+            let length =
+                *LENGTH_BASE.get(index)? as usize + bits.read(*LENGTH_EXTRA.get(index)?)? as usize;
+
+            let index = distances.decode(bits)? as usize;
+            let distance = *DISTANCE_BASE.get(index)? as usize
+                + bits.read(*DISTANCE_EXTRA.get(index)?)? as usize;
+
+            let start = out.len().checked_sub(distance)?;
+            for i in start..start + length {
+                out.push(*out.get(i)?);
+            }
+        }
+
+        if out.len() > limit {
+            return None;
+        }
+    }
+}
+
+/// Reads a deflate stream bit by bit, starting with the least significant
+/// bit of every byte.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Bits<'_> {
+    fn read(&mut self, count: u8) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos / 8)?;
+            value |= ((byte as u32 >> (self.pos % 8)) & 1) << i;
+            self.pos += 1;
+        }
+
+        Some(value)
+    }
+}
+
+/// A canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    /// The number of codes of every length
+    counts: [u16; 16],
+    /// The symbols ordered by their code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            *counts.get_mut(length as usize)? += 1;
+        }
+
+        // more codes of a length than there are left over by the shorter ones
+        // can not be told apart, incomplete codes are fine
+        let mut left = 1i32;
+        for &count in counts.iter().skip(1) {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return None;
+            }
+        }
+
+        // where the symbols of every length start
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            *offsets.get_mut(length + 1)? = *offsets.get(length)? + *counts.get(length)?;
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = offsets.get_mut(length as usize)?;
+                *symbols.get_mut(*offset as usize)? = symbol as u16;
+                *offset += 1;
+            }
+        }
+
+        Some(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits<'_>) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in self.counts.iter().skip(1) {
+            code |= bits.read(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ADAM7, BitWriter, SIGNATURE, decode, encode, inflate, write_chunk, zlib_compress};
+    use crate::Image;
+
+    /// A 3x2 RGB image from another encoder, with rows filtered by Sub and
+    /// Paeth.
+    const FOREIGN_PNG: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02, 0x08, 0x02, 0x00, 0x00, 0x00, 0x12,
+        0x16, 0xf1, 0x4d, 0x00, 0x00, 0x00, 0x1c, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xe4,
+        0x12, 0x91, 0x63, 0x65, 0x65, 0xfd, 0xc5, 0xc0, 0xc0, 0xc2, 0xc0, 0xc0, 0xc0, 0xc8, 0xc4,
+        0xcc, 0xc9, 0xc9, 0x09, 0x00, 0x12, 0x59, 0x01, 0x6c, 0x43, 0xe1, 0x70, 0xcd, 0x00, 0x00,
+        0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    /// Pixels that have a bit of everything: runs, repeats and noise.
+    fn test_image(width: u32, height: u32) -> Image {
+        let mut state = 1u32;
+        let pixels = (0..width * height * 4)
+            .map(|i| match (i / 4) % 7 {
+                0..3 => 200,
+                3 => (i / (width * 4)) as u8,
+                _ => {
+                    state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                    (state >> 16) as u8
+                }
+            })
+            .collect();
+
+        Image::new((width, height), pixels).expect("valid image")
+    }
+
+    /// A PNG with the given header and zlib stream.
+    fn build_png(header: [u8; 13], zlib: &[u8]) -> Vec<u8> {
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", zlib);
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    fn rgba_header(width: u32, height: u32) -> [u8; 13] {
+        let [w0, w1, w2, w3] = width.to_be_bytes();
+        let [h0, h1, h2, h3] = height.to_be_bytes();
+        [w0, w1, w2, w3, h0, h1, h2, h3, 8, 6, 0, 0, 0]
+    }
+
+    /// An Adam7 interlaced PNG of the image, with every other row of a pass
+    /// filtered by Sub.
+    fn interlaced_png(image: &Image) -> Vec<u8> {
+        let (width, height) = (image.size().width as usize, image.size().height as usize);
+        let pixel = |x: usize, y: usize| {
+            let index = (y * width + x) * 4;
+            image.pixels().get(index..index + 4).unwrap_or_default()
+        };
+
+        let mut raw = Vec::new();
+        for (x0, y0, dx, dy) in ADAM7 {
+            if x0 >= width {
+                continue;
+            }
+
+            for (row, y) in (y0..height).step_by(dy).enumerate() {
+                let filter = (row % 2) as u8;
+                raw.push(filter);
+                let mut left = [0u8; 4];
+                for x in (x0..width).step_by(dx) {
+                    for (byte, left) in pixel(x, y).iter().zip(&mut left) {
+                        raw.push(byte.wrapping_sub(*left * filter));
+                        *left = *byte;
+                    }
+                }
+            }
+        }
+
+        let mut header = rgba_header(width as u32, height as u32);
+        if let Some(interlace) = header.last_mut() {
+            *interlace = 1;
+        }
+        build_png(header, &zlib_compress(&raw))
+    }
+
+    /// Pseudo-random numbers for the fuzz tests, the same on every run.
+    fn random(state: &mut u64) -> usize {
+        *state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (*state >> 33) as usize
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (width, height) in [(1, 1), (3, 2), (64, 48), (300, 7)] {
+            let image = test_image(width, height);
+            let decoded = decode(&encode(&image)).expect("decodes");
+            assert_eq!(decoded.size(), image.size());
+            assert_eq!(decoded.pixels(), image.pixels());
+        }
+    }
+
+    #[test]
+    fn test_compresses_flat_images() {
+        let image = Image::new((256, 256), vec![128; 256 * 256 * 4]).expect("valid image");
+        let png = encode(&image);
+        assert!(png.len() < image.pixels().len() / 50);
+        assert_eq!(decode(&png).expect("decodes").pixels(), image.pixels());
+    }
+
+    #[test]
+    fn test_inflate_long_matches() {
+        let data = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let zlib = zlib_compress(&data);
+        assert_eq!(inflate(&zlib, data.len()).as_deref(), Some(data.as_slice()));
+        assert!(inflate(&zlib, data.len() - 1).is_none());
+    }
+
+    #[test]
+    fn test_decode_foreign() {
+        let image = decode(FOREIGN_PNG).expect("decodes");
+        assert_eq!(image.size(), (3, 2).into());
+        assert_eq!(
+            image.pixels(),
+            [
+                10, 20, 30, 255, 15, 25, 35, 255, 9, 25, 35, 255, //
+                10, 20, 30, 255, 16, 27, 38, 255, 18, 36, 47, 255,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_stored_block() {
+        // a 1x1 image, no filter, in a final stored block
+        let raw = [0, 1, 2, 3, 4];
+        let mut zlib = vec![0x78, 0x01, 1, 5, 0, !5, !0];
+        zlib.extend(raw);
+        zlib.extend(super::adler32(&raw).to_be_bytes());
+
+        let image = decode(&build_png(rgba_header(1, 1), &zlib)).expect("decodes");
+        assert_eq!(image.pixels(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let png = encode(&test_image(8, 8));
+        // anything that cuts into the image data, the end chunk is optional
+        for len in 0..png.len() - 12 {
+            assert!(
+                decode(png.get(..len).unwrap_or_default()).is_none(),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_bad_lengths() {
+        let mut png = encode(&test_image(2, 2));
+        // the length of the IDAT chunk, right after the header chunk
+        let idat = SIGNATURE.len() + 25;
+        for len in [u32::MAX, u32::MAX - 11, 1 << 24] {
+            png.splice(idat..idat + 4, len.to_be_bytes());
+            assert!(decode(&png).is_none());
+        }
+
+        // a stored block whose length does not match its complement
+        let zlib = [0x78, 0x01, 1, 5, 0, 0, 0, 0, 1, 2, 3, 4];
+        assert!(decode(&build_png(rgba_header(1, 1), &zlib)).is_none());
+    }
+
+    #[test]
+    fn test_decode_bad_huffman_tables() {
+        // a dynamic block with 19 code length codes of one bit each
+        let mut bits = BitWriter::default();
+        bits.bytes.extend([0x78, 0x01]);
+        bits.write(1, 1);
+        bits.write(2, 2);
+        bits.write(0, 5);
+        bits.write(0, 5);
+        bits.write(15, 4);
+        for _ in 0..19 {
+            bits.write(1, 3);
+        }
+        bits.write(0, 32);
+
+        let zlib = bits.finish();
+        assert!(inflate(&zlib, 100).is_none());
+        assert!(decode(&build_png(rgba_header(1, 1), &zlib)).is_none());
+
+        // a block type that does not exist
+        assert!(inflate(&[0x78, 0x01, 0x07, 0, 0, 0], 100).is_none());
+    }
+
+    #[test]
+    fn test_decode_oversized_header() {
+        let zlib = zlib_compress(&[0, 1, 2, 3, 4]);
+        for (width, height) in [(1 << 16, 1 << 16), (u32::MAX, u32::MAX), (0, 1), (1, 0)] {
+            assert!(decode(&build_png(rgba_header(width, height), &zlib)).is_none());
+        }
+
+        // an interlace method that does not exist
+        let mut header = rgba_header(1, 1);
+        if let Some(interlace) = header.last_mut() {
+            *interlace = 2;
+        }
+        assert!(decode(&build_png(header, &zlib)).is_none());
+        assert!(decode(&build_png(rgba_header(1, 1), &zlib)).is_some());
+    }
+
+    #[test]
+    fn test_decode_interlaced() {
+        // small images leave some of the passes empty
+        for (width, height) in [(1, 1), (2, 3), (5, 1), (8, 8), (9, 17), (33, 10)] {
+            let image = test_image(width, height);
+            let decoded = decode(&interlaced_png(&image)).expect("decodes");
+            assert_eq!(decoded.size(), image.size());
+            assert_eq!(decoded.pixels(), image.pixels(), "{width}x{height}");
+        }
+
+        // the data of a pass cut short
+        let png = interlaced_png(&test_image(9, 9));
+        let mut header = rgba_header(9, 10);
+        if let Some(interlace) = header.last_mut() {
+            *interlace = 1;
+        }
+        let (_, _, rest) = super::read_chunk(png.get(SIGNATURE.len()..).unwrap_or_default())
+            .expect("header chunk");
+        let (_, zlib, _) = super::read_chunk(rest).expect("data chunk");
+        assert!(decode(&build_png(header, zlib)).is_none());
+    }
+
+    #[test]
+    fn test_decode_mutated() {
+        let originals = [
+            encode(&test_image(13, 7)),
+            interlaced_png(&test_image(11, 9)),
+            FOREIGN_PNG.to_vec(),
+        ];
+
+        // damaged files must be rejected or decode to something, never panic
+        let mut state = 1;
+        for _ in 0..3000 {
+            let mut png = originals
+                .get(random(&mut state) % originals.len())
+                .cloned()
+                .unwrap_or_default();
+
+            for _ in 0..1 + random(&mut state) % 4 {
+                let pos = random(&mut state) % png.len().max(1);
+                match random(&mut state) % 4 {
+                    0 => png.truncate(pos),
+                    1 => png.insert(pos, random(&mut state) as u8),
+                    2 => drop(png.drain(pos..png.len().min(pos + 8))),
+                    _ => {
+                        if let Some(byte) = png.get_mut(pos) {
+                            *byte ^= 1 << (random(&mut state) % 8);
+                        }
+                    }
+                }
+            }
+
+            if let Some(image) = decode(&png) {
+                let size = image.size();
+                assert_eq!(
+                    image.pixels().len(),
+                    size.width as usize * size.height as usize * 4
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflate_random() {
+        let mut state = 2;
+        for _ in 0..3000 {
+            let len = random(&mut state) % 64;
+            let mut zlib = vec![0x78, 0x01];
+            zlib.extend((0..len).map(|_| random(&mut state) as u8));
+            if let Some(data) = inflate(&zlib, 1024) {
+                assert!(data.len() <= 1024);
+            }
+        }
+    }
+}
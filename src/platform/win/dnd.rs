@@ -1,20 +1,37 @@
-use crate::Exchange;
-use crate::platform::win::util::exchange::decode_hdrop;
+use crate::platform::win::util::error::Win32Error;
+use crate::platform::win::util::exchange::{decode_hdrop, encode_hdrop, global_copy};
 use crate::platform::win::util::widestr::WideString;
 use crate::platform::win::window::{
     WM_USER_DND_ACCEPT, WM_USER_DND_ENTER, WM_USER_DND_HOVER, WM_USER_DND_LEAVE,
 };
-use com::{IDataObject, IDropTarget, IDropTargetVtbl, IUnknown, IUnknownVtbl};
+use crate::{DropEffect, Exchange};
+use com::{
+    IDataObject, IDropSource, IDropSourceVtbl, IDropTarget, IDropTargetVtbl, IUnknown, IUnknownVtbl,
+};
 use std::ffi::c_void;
 use std::mem::zeroed;
-use std::ptr::null_mut;
+use std::ptr::{null, null_mut};
 use std::sync::Arc;
-use windows_sys::Win32::Foundation::{E_NOINTERFACE, HWND, POINT, S_OK};
-use windows_sys::Win32::System::Com::{DVASPECT_CONTENT, FORMATETC, STGMEDIUM, TYMED_HGLOBAL};
+use windows_sys::Win32::Foundation::{
+    DRAGDROP_S_CANCEL, DRAGDROP_S_DROP, DRAGDROP_S_USEDEFAULTCURSORS, E_NOINTERFACE, GlobalFree,
+    HWND, POINT, S_OK,
+};
+use windows_sys::Win32::System::Com::{
+    DVASPECT_CONTENT, FORMATETC, STGMEDIUM, STGMEDIUM_0, TYMED_HGLOBAL,
+};
 use windows_sys::Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock};
-use windows_sys::Win32::System::Ole::{CF_HDROP, CF_UNICODETEXT, ReleaseStgMedium};
+use windows_sys::Win32::System::Ole::{
+    CF_HDROP, CF_UNICODETEXT, DROPEFFECT_COPY, DROPEFFECT_NONE, DoDragDrop, ReleaseStgMedium,
+};
+use windows_sys::Win32::System::SystemServices::{
+    MK_LBUTTON, MK_MBUTTON, MK_RBUTTON, MK_XBUTTON1, MK_XBUTTON2,
+};
+use windows_sys::Win32::UI::Shell::SHCreateDataObject;
 use windows_sys::Win32::UI::WindowsAndMessaging::{PostMessageW, SendMessageW};
-use windows_sys::core::{GUID, HRESULT};
+use windows_sys::core::{BOOL, GUID, HRESULT};
+
+/// `IID_IDataObject`, the interface we ask [`SHCreateDataObject`] for.
+const IID_IDATA_OBJECT: GUID = GUID::from_u128(0x0000010e_0000_0000_c000_000000000046);
 
 /// COM implementation of [`IDropTarget`] that forwards events to a window.
 #[repr(C)]
@@ -29,9 +46,9 @@ unsafe impl Sync for DropTargetImpl {}
 impl DropTargetImpl {
     const VTABLE: IDropTargetVtbl = IDropTargetVtbl {
         unknown: IUnknownVtbl {
-            query_interface: Self::query_interface,
-            add_ref: Self::add_ref,
-            release: Self::release,
+            query_interface,
+            add_ref: add_ref::<Self>,
+            release: release::<Self>,
         },
         drag_enter: Self::drag_enter,
         drag_over: Self::drag_over,
@@ -52,35 +69,6 @@ impl DropTargetImpl {
         Arc::as_ptr(data) as *mut IDropTarget
     }
 
-    unsafe extern "system" fn query_interface(
-        _: *mut IUnknown,
-        _: *const GUID,
-        _: *mut *mut c_void,
-    ) -> HRESULT {
-        E_NOINTERFACE
-    }
-
-    unsafe extern "system" fn add_ref(this: *mut IUnknown) -> u32 {
-        unsafe {
-            let this = this as *const Self;
-            Arc::increment_strong_count(this);
-            let this = Arc::from_raw(this);
-            let count = Arc::strong_count(&this);
-            let _ = Arc::into_raw(this); // prevent dropping
-            count as u32
-        }
-    }
-
-    unsafe extern "system" fn release(this: *mut IUnknown) -> u32 {
-        unsafe {
-            let this = this as *const Self;
-            let this = Arc::from_raw(this);
-            let count = Arc::strong_count(&this) - 1;
-            drop(this); // drop the Arc, which may deallocate if count reaches 0
-            count as u32
-        }
-    }
-
     unsafe extern "system" fn drag_enter(
         this: *mut IDropTarget,
         data: *const IDataObject,
@@ -206,11 +194,184 @@ impl DropTargetImpl {
     }
 }
 
+/// COM implementation of [`IDropSource`] for a drag started with
+/// [`PlatformWindow::start_drag`](crate::platform::PlatformWindow::start_drag).
+/// Drops the data once all mouse buttons are released, cancels on escape.
+#[repr(C)]
+pub struct DropSourceImpl {
+    base: IDropSource,
+}
+
+unsafe impl Send for DropSourceImpl {}
+unsafe impl Sync for DropSourceImpl {}
+
+impl DropSourceImpl {
+    const VTABLE: IDropSourceVtbl = IDropSourceVtbl {
+        unknown: IUnknownVtbl {
+            query_interface,
+            add_ref: add_ref::<Self>,
+            release: release::<Self>,
+        },
+        query_continue_drag: Self::query_continue_drag,
+        give_feedback: Self::give_feedback,
+    };
+
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            base: IDropSource {
+                vtbl: &Self::VTABLE,
+            },
+        })
+    }
+
+    pub fn as_raw(data: &Arc<Self>) -> *mut IDropSource {
+        Arc::as_ptr(data) as *mut IDropSource
+    }
+
+    unsafe extern "system" fn query_continue_drag(
+        _: *mut IDropSource,
+        escape_pressed: BOOL,
+        key_state: u32,
+    ) -> HRESULT {
+        let buttons = MK_LBUTTON | MK_RBUTTON | MK_MBUTTON | MK_XBUTTON1 | MK_XBUTTON2;
+        if escape_pressed != 0 {
+            DRAGDROP_S_CANCEL
+        } else if key_state & buttons == 0 {
+            DRAGDROP_S_DROP
+        } else {
+            S_OK
+        }
+    }
+
+    unsafe extern "system" fn give_feedback(_: *mut IDropSource, _: u32) -> HRESULT {
+        DRAGDROP_S_USEDEFAULTCURSORS
+    }
+
+    /// Drags the data out of the window. [`DoDragDrop`] runs its own modal
+    /// loop, so this only returns once the data was dropped or the drag was
+    /// cancelled. OLE must be initialized on the current thread.
+    pub unsafe fn drag(data: &Exchange) -> Result<DropEffect, Win32Error> {
+        unsafe {
+            let (format, handle) = match data {
+                // images and custom formats are never dragged
+                Exchange::Empty | Exchange::Image(_) | Exchange::Custom { .. } => {
+                    return Ok(DropEffect::Reject);
+                }
+                Exchange::Files(files) => (CF_HDROP, global_copy(&encode_hdrop(files))),
+                Exchange::Text(text) => (
+                    CF_UNICODETEXT,
+                    global_copy(WideString::from(text.as_str()).as_bytes_with_nul()),
+                ),
+            };
+
+            let Some(handle) = handle else {
+                return Err(Win32Error::last_error().with_context("GlobalAlloc"));
+            };
+
+            // the shell data object takes any format we give it
+            let mut object: *mut IDataObject = null_mut();
+            let result = SHCreateDataObject(
+                null(),
+                0,
+                null(),
+                null_mut(),
+                &IID_IDATA_OBJECT,
+                &mut object as *mut *mut IDataObject as *mut *mut c_void,
+            );
+
+            if result < 0 || object.is_null() {
+                GlobalFree(handle);
+                return Err(hresult_error(result, "SHCreateDataObject"));
+            }
+
+            let format = FORMATETC {
+                cfFormat: format,
+                dwAspect: DVASPECT_CONTENT,
+                tymed: TYMED_HGLOBAL as _,
+                ptd: null_mut(),
+                lindex: -1,
+            };
+
+            let medium = STGMEDIUM {
+                tymed: TYMED_HGLOBAL as _,
+                u: STGMEDIUM_0 { hGlobal: handle },
+                pUnkForRelease: null_mut(),
+            };
+
+            // the data object owns the memory only if this succeeds
+            let result = ((*(*object).vtbl).set_data)(object, &format, &medium, 1);
+            let effect = if result < 0 {
+                GlobalFree(handle);
+                Err(hresult_error(result, "IDataObject::SetData"))
+            } else {
+                let source = Self::new();
+                let mut effect = DROPEFFECT_NONE;
+                let result = DoDragDrop(
+                    object as *mut c_void,
+                    Self::as_raw(&source) as *mut c_void,
+                    DROPEFFECT_COPY,
+                    &mut effect,
+                );
+
+                match result {
+                    DRAGDROP_S_DROP if effect & DROPEFFECT_COPY != 0 => Ok(DropEffect::Copy),
+                    DRAGDROP_S_DROP | DRAGDROP_S_CANCEL => Ok(DropEffect::Reject),
+                    _ => Err(hresult_error(result, "DoDragDrop")),
+                }
+            };
+
+            ((*(*object).vtbl).parent.release)(object as *mut IUnknown);
+            effect
+        }
+    }
+}
+
+/// Wraps a failed [`HRESULT`], these are formatted like any other system
+/// error code.
+fn hresult_error(result: HRESULT, context: &str) -> Win32Error {
+    Win32Error {
+        code: result as u32,
+        context: None,
+    }
+    .with_context(context)
+}
+
+unsafe extern "system" fn query_interface(
+    _: *mut IUnknown,
+    _: *const GUID,
+    _: *mut *mut c_void,
+) -> HRESULT {
+    E_NOINTERFACE
+}
+
+/// `AddRef` for COM objects that live in an [`Arc`].
+unsafe extern "system" fn add_ref<T>(this: *mut IUnknown) -> u32 {
+    unsafe {
+        let this = this as *const T;
+        Arc::increment_strong_count(this);
+        let this = Arc::from_raw(this);
+        let count = Arc::strong_count(&this);
+        let _ = Arc::into_raw(this); // prevent dropping
+        count as u32
+    }
+}
+
+/// `Release` for COM objects that live in an [`Arc`].
+unsafe extern "system" fn release<T>(this: *mut IUnknown) -> u32 {
+    unsafe {
+        let this = this as *const T;
+        let this = Arc::from_raw(this);
+        let count = Arc::strong_count(&this) - 1;
+        drop(this); // drop the Arc, which may deallocate if count reaches 0
+        count as u32
+    }
+}
+
 mod com {
     use std::ffi::c_void;
     use windows_sys::Win32::Foundation::POINT;
     use windows_sys::Win32::System::Com::{FORMATETC, STGMEDIUM};
-    use windows_sys::core::{GUID, HRESULT};
+    use windows_sys::core::{BOOL, GUID, HRESULT};
 
     pub type IUnknown = *mut c_void;
 
@@ -259,11 +420,38 @@ mod com {
             pformatetc_in: *const FORMATETC,
             pmedium: *mut STGMEDIUM,
         ) -> HRESULT,
+        // placeholders for the methods we do not call
+        _get_data_here: *const c_void,
+        _query_get_data: *const c_void,
+        _get_canonical_format_etc: *const c_void,
+        pub set_data: unsafe extern "system" fn(
+            this: *mut IDataObject,
+            pformatetc: *const FORMATETC,
+            pmedium: *const STGMEDIUM,
+            f_release: BOOL,
+        ) -> HRESULT,
 
         // there are other methods but we dont need them
         _private: (),
     }
 
+    #[repr(C)]
+    pub struct IDropSourceVtbl {
+        pub unknown: IUnknownVtbl,
+        pub query_continue_drag: unsafe extern "system" fn(
+            this: *mut IDropSource,
+            f_escape_pressed: BOOL,
+            grf_key_state: u32,
+        ) -> HRESULT,
+        pub give_feedback:
+            unsafe extern "system" fn(this: *mut IDropSource, dw_effect: u32) -> HRESULT,
+    }
+
+    #[repr(C)]
+    pub struct IDropSource {
+        pub vtbl: *const IDropSourceVtbl,
+    }
+
     #[repr(C)]
     pub struct IDropTarget {
         pub vtbl: *const IDropTargetVtbl,
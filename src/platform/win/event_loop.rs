@@ -0,0 +1,77 @@
+use crate::WindowError;
+use crate::platform::win::util::error::Win32Error;
+use std::cell::Cell;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    DispatchMessageW, GetMessageW, MSG, PostQuitMessage, TranslateMessage,
+};
+
+thread_local! {
+    /// The number of top-level windows open on this thread, [`run`] returns
+    /// once all of them are closed.
+    static WINDOWS: Cell<usize> = const { Cell::new(0) };
+    /// Is [`run`] running on this thread?
+    static IS_RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Check if [`run`] runs on this thread.
+pub fn is_running() -> bool {
+    IS_RUNNING.get()
+}
+
+/// Count a top-level window that was created on this thread.
+pub fn add() {
+    WINDOWS.set(WINDOWS.get() + 1);
+}
+
+/// Stop counting a destroyed top-level window, see [`add`]. Quits the event
+/// loop if it was the last one.
+pub fn remove() {
+    let windows = WINDOWS.get().saturating_sub(1);
+    WINDOWS.set(windows);
+
+    // a quit message left behind would end the next message loop, even the host's
+    if windows == 0 && IS_RUNNING.get() {
+        unsafe { PostQuitMessage(0) };
+    }
+}
+
+/// Pump the messages of this thread until all top-level windows are closed.
+/// Windows of all kinds are run by it, not just the counted ones. Fails only
+/// if the message queue can't be read, windows do not fail once created.
+pub unsafe fn run() -> Result<(), WindowError> {
+    /// Resets [`IS_RUNNING`], even if a handler panics.
+    struct Running;
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            IS_RUNNING.set(false);
+        }
+    }
+
+    // the quit message of a nested loop would end the outer one as well
+    if IS_RUNNING.get() {
+        return Err(WindowError::EventLoopRunning);
+    }
+
+    if WINDOWS.get() == 0 {
+        return Ok(());
+    }
+
+    IS_RUNNING.set(true);
+    let _running = Running;
+
+    unsafe {
+        // our favorite - win32 event pump
+        let mut msg: MSG = std::mem::zeroed();
+        loop {
+            match GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) {
+                0 => return Ok(()),
+                -1 => return Err(Win32Error::last_error().with_context("GetMessageW").into()),
+                _ => {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            }
+        }
+    }
+}
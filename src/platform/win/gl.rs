@@ -4,20 +4,32 @@ use crate::platform::win::util::wgl::{
     create_context_arb, create_context_fallback, create_pixel_format_arb,
     create_pixel_format_fallback, try_set_swap_interval,
 };
-use crate::{MakeCurrentError, OpenGlError, SwapBuffersError};
+use crate::platform::win::util::window::{WindowProc, create_window};
+use crate::{GlVersion, MakeCurrentError, OpenGlError, Size, SwapBuffersError};
+use std::cell::Cell;
 use std::ffi::{CStr, c_void};
+use std::mem::zeroed;
 use std::ptr::{null, null_mut};
-use windows_sys::Win32::Foundation::{FreeLibrary, HMODULE, HWND};
+use std::rc::Rc;
+use windows_sys::Win32::Foundation::{
+    ERROR_DC_NOT_FOUND, ERROR_INVALID_HANDLE, ERROR_INVALID_PIXEL_FORMAT,
+    ERROR_INVALID_WINDOW_HANDLE, FreeLibrary, GetLastError, HMODULE, HWND, LPARAM, LRESULT, WPARAM,
+};
 use windows_sys::Win32::Graphics::Gdi::{GetDC, HDC, ReleaseDC};
 use windows_sys::Win32::Graphics::OpenGL::{
-    HGLRC, SetPixelFormat, SwapBuffers, wglDeleteContext, wglGetCurrentContext, wglGetProcAddress,
-    wglMakeCurrent,
+    GetPixelFormat, HGLRC, SetPixelFormat, SwapBuffers, wglDeleteContext, wglGetCurrentContext,
+    wglGetProcAddress, wglMakeCurrent,
 };
 use windows_sys::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryA};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    DefWindowProcW, DestroyWindow, GetClientRect, HTTRANSPARENT, MoveWindow, WM_ERASEBKGND,
+    WM_NCHITTEST, WS_CHILD, WS_CLIPSIBLINGS, WS_VISIBLE,
+};
 
 /// WGL based [`PlatformOpenGl`] implementation
 pub struct GlContext {
-    /// The window our context was created for
+    /// The window our context renders into, either the picoview window or
+    /// `surface`
     hwnd: HWND,
     /// Window device context
     hdc: HDC,
@@ -26,10 +38,54 @@ pub struct GlContext {
     /// Windows OpenGL module (used as a fallback for `wglGetProcAddress` when
     /// it returns null)
     hmodule: HMODULE,
+    /// A child window dedicated to OpenGL, created when the picoview window
+    /// already had an incompatible pixel format
+    surface: Option<HWND>,
+    /// The version of the config the context was created with
+    version: GlVersion,
 }
 
 impl GlContext {
-    pub unsafe fn new(hwnd: HWND, config: crate::GlConfig) -> Result<Self, OpenGlError> {
+    /// Create a context for `hwnd`. Falls back to a legacy context of any
+    /// version if the requested one is not available, but only if this is the
+    /// `last_resort`.
+    ///
+    /// `chain_format` is the pixel format an earlier config of the fallback
+    /// chain set. A window only gets a pixel format once, so later configs
+    /// keep that one and only fall back on the context attributes.
+    pub unsafe fn new(
+        hwnd: HWND,
+        config: crate::GlConfig,
+        last_resort: bool,
+        chain_format: &Cell<Option<i32>>,
+    ) -> Result<Self, OpenGlError> {
+        unsafe {
+            // a window can only get a pixel format once, if the host (or a hook) got
+            // there first we render into a child window of our own instead
+            match Self::create(hwnd, &config, last_resort, chain_format) {
+                Err(OpenGlError::PixelFormatInUse) => {
+                    let surface =
+                        GlSurface::create(hwnd).map_err(|_| OpenGlError::PixelFormatInUse)?;
+
+                    let mut context = Self::create(surface, &config, last_resort, chain_format)
+                        .inspect_err(|_| {
+                            DestroyWindow(surface);
+                        })?;
+
+                    context.surface = Some(surface);
+                    Ok(context)
+                }
+                result => result,
+            }
+        }
+    }
+
+    unsafe fn create(
+        hwnd: HWND,
+        config: &crate::GlConfig,
+        last_resort: bool,
+        chain_format: &Cell<Option<i32>>,
+    ) -> Result<Self, OpenGlError> {
         unsafe {
             let hmodule = LoadLibraryA(c"opengl32.dll".as_ptr() as _);
             if hmodule.is_null() {
@@ -38,26 +94,49 @@ impl GlContext {
 
             let hdc = GetDC(hwnd);
             if hdc.is_null() {
+                FreeLibrary(hmodule);
                 return Err(Win32Error::last_error().into());
             }
 
-            let (format_id, format_desc) = create_pixel_format_arb(hdc, &config)
-                .or_else(|_| create_pixel_format_fallback(hdc, &config))
-                .map_err(|_| {
-                    ReleaseDC(hwnd, hdc);
-                    FreeLibrary(hmodule);
-                    OpenGlError::FormatUnsupported
-                })?;
+            let cleanup = |error| {
+                ReleaseDC(hwnd, hdc);
+                FreeLibrary(hmodule);
+                error
+            };
 
-            SetPixelFormat(hdc, format_id, &format_desc);
+            let (format_id, format_desc) = create_pixel_format_arb(hdc, config)
+                .or_else(|_| create_pixel_format_fallback(hdc, config))
+                .map_err(|_| cleanup(OpenGlError::FormatUnsupported))?;
 
-            let hglrc = create_context_arb(hdc, &config)
-                .or_else(|_| create_context_fallback(hdc))
-                .map_err(|_| {
-                    ReleaseDC(hwnd, hdc);
-                    FreeLibrary(hmodule);
-                    OpenGlError::VersionUnsupported
-                })?;
+            // SetPixelFormat fails when a format is already set, even an identical one, so
+            // only an identical one or the one of an earlier config of the chain can be reused
+            let current_format_id = GetPixelFormat(hdc);
+            let is_format_set = match current_format_id {
+                0 => {
+                    let is_set = SetPixelFormat(hdc, format_id, &format_desc) != 0;
+                    if is_set {
+                        chain_format.set(Some(format_id));
+                    }
+                    is_set
+                }
+                _ => {
+                    current_format_id == format_id || chain_format.get() == Some(current_format_id)
+                }
+            };
+
+            if !is_format_set {
+                return Err(cleanup(OpenGlError::PixelFormatInUse));
+            }
+
+            let hglrc = create_context_arb(hdc, config)
+                .or_else(|error| {
+                    if last_resort {
+                        create_context_fallback(hdc)
+                    } else {
+                        Err(error)
+                    }
+                })
+                .map_err(|_| cleanup(OpenGlError::VersionUnsupported))?;
 
             try_set_swap_interval(hdc, hglrc, 0);
 
@@ -66,9 +145,66 @@ impl GlContext {
                 hdc,
                 hglrc,
                 hmodule,
+                surface: None,
+                version: config.version,
             })
         }
     }
+
+    /// Keep the dedicated child window, if any, covering the client area.
+    pub fn resize(&self, size: Size) {
+        if let Some(surface) = self.surface {
+            unsafe {
+                MoveWindow(
+                    surface,
+                    0,
+                    0,
+                    size.width.try_into().unwrap_or(i32::MAX),
+                    size.height.try_into().unwrap_or(i32::MAX),
+                    0,
+                );
+            }
+        }
+    }
+}
+
+/// The window procedure of a dedicated OpenGL child window: it never paints
+/// nor takes input, mouse messages go right through to the picoview window.
+struct GlSurface;
+
+impl GlSurface {
+    /// Create a child window of `parent` that covers its client area.
+    unsafe fn create(parent: HWND) -> Result<HWND, Win32Error> {
+        unsafe {
+            let mut surface = null_mut();
+            create_window(
+                WS_CHILD | WS_VISIBLE | WS_CLIPSIBLINGS,
+                0,
+                None,
+                parent,
+                |hwnd| {
+                    surface = hwnd;
+                    Ok::<_, Win32Error>(Rc::new(GlSurface))
+                },
+            )?;
+
+            let mut rect = zeroed();
+            GetClientRect(parent, &mut rect);
+            MoveWindow(surface, 0, 0, rect.right, rect.bottom, 0);
+
+            Ok(surface)
+        }
+    }
+}
+
+impl WindowProc for GlSurface {
+    unsafe fn window_proc(&self, hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        match msg {
+            WM_NCHITTEST => HTTRANSPARENT as LRESULT,
+            WM_ERASEBKGND => 1,
+            _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+        }
+    }
 }
 
 impl PlatformOpenGl for GlContext {
@@ -98,12 +234,24 @@ impl PlatformOpenGl for GlContext {
                 wglMakeCurrent(self.hdc, if current { self.hglrc } else { null_mut() }) != 0;
 
             if result {
-                Ok(())
-            } else {
-                Err(MakeCurrentError)
+                return Ok(());
+            }
+
+            // a destroyed window or context does not come back, anything else (like the
+            // driver going away during a device reset) might
+            match GetLastError() {
+                ERROR_INVALID_HANDLE
+                | ERROR_INVALID_WINDOW_HANDLE
+                | ERROR_DC_NOT_FOUND
+                | ERROR_INVALID_PIXEL_FORMAT => Err(MakeCurrentError::Lost),
+                _ => Err(MakeCurrentError::Transient),
             }
         }
     }
+
+    fn version(&self) -> Option<GlVersion> {
+        Some(self.version)
+    }
 }
 
 impl Drop for GlContext {
@@ -113,6 +261,10 @@ impl Drop for GlContext {
             wglDeleteContext(self.hglrc);
             ReleaseDC(self.hwnd, self.hdc);
             FreeLibrary(self.hmodule);
+
+            if let Some(surface) = self.surface {
+                DestroyWindow(surface);
+            }
         }
     }
 }
@@ -1,6 +1,9 @@
 /// Drag and drop COM interface implementation.
 mod dnd;
+/// Counts the top-level windows and runs the message loop until they close.
+mod event_loop;
 /// OpenGL context creation and management.
+#[cfg(feature = "opengl")]
 mod gl;
 /// Various utility functions.
 mod util;
@@ -13,3 +16,21 @@ pub unsafe fn open_window(
 ) -> Result<crate::WindowWaker, crate::WindowError> {
     unsafe { window::WindowImpl::open(options, mode) }
 }
+
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub unsafe fn run_event_loop() -> Result<(), crate::WindowError> {
+    unsafe { event_loop::run() }
+}
+
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub fn is_event_loop_running() -> bool {
+    event_loop::is_running()
+}
+
+pub fn find_window_at(point: crate::Point) -> Option<crate::WindowId> {
+    window::WindowImpl::window_at(point)
+}
+
+pub fn query_system_metrics() -> crate::SystemMetrics {
+    util::metrics::query_system_metrics()
+}
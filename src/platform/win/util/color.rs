@@ -0,0 +1,55 @@
+use crate::IccProfile;
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use std::ptr::{null, null_mut};
+use windows_sys::Win32::Graphics::Gdi::{
+    CreateDCW, DeleteDC, GetMonitorInfoW, HMONITOR, MONITORINFOEXW,
+};
+use windows_sys::Win32::UI::ColorSystem::GetICMProfileW;
+
+/// Get the ICC profile assigned to a monitor in the color management
+/// settings, `None` if there is none or it can not be read.
+///
+/// Windows only reports the path of the profile, the file is read on every
+/// call.
+///
+/// # Safety
+/// - The `monitor` must be a valid monitor handle at the time of the call.
+pub unsafe fn query_color_profile(monitor: HMONITOR) -> Option<IccProfile> {
+    unsafe {
+        let mut info = MONITORINFOEXW::default();
+        info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as _;
+        if GetMonitorInfoW(monitor, &mut info as *mut _ as *mut _) == 0 {
+            return None;
+        }
+
+        // a window DC only ever reports the profile of the primary monitor
+        let dc = CreateDCW(
+            info.szDevice.as_ptr(),
+            info.szDevice.as_ptr(),
+            null(),
+            null(),
+        );
+        if dc.is_null() {
+            return None;
+        }
+
+        // the first call only reports the length of the path, in characters
+        let mut len = 0;
+        GetICMProfileW(dc, &mut len, null_mut());
+
+        let mut path = vec![0u16; len as usize];
+        let success = len > 0 && GetICMProfileW(dc, &mut len, path.as_mut_ptr()) != 0;
+        DeleteDC(dc);
+
+        if !success {
+            return None;
+        }
+
+        let end = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+        let path = OsString::from_wide(path.get(..end)?);
+        let data = std::fs::read(path).ok()?;
+
+        (!data.is_empty()).then(|| IccProfile::new(data))
+    }
+}
@@ -1,5 +1,7 @@
 use crate::MouseCursor;
+use crate::platform::win::util::window::hinstance;
 use std::ptr::null_mut;
+use std::sync::OnceLock;
 use windows_sys::Win32::UI::WindowsAndMessaging::*;
 use windows_sys::core::PCWSTR;
 
@@ -13,6 +15,38 @@ impl WinCursor {
         unsafe { Self(LoadCursorW(null_mut(), cursor)) }
     }
 
+    /// Creates a cursor from a [`Drawing`]. Each one is drawn once and then
+    /// shared for the lifetime of the process, like the system cursors.
+    ///
+    /// Falls back to the arrow if the cursor could not be created, a null
+    /// handle would hide the cursor. Failures are not cached, so the next
+    /// use tries again.
+    fn drawn(drawing: Drawing) -> Self {
+        // handles are stored as integers to be `Sync`
+        static CACHE: [OnceLock<usize>; 6] = [const { OnceLock::new() }; 6];
+
+        let Some(cache) = CACHE.get(drawing as usize) else {
+            return unsafe { Self::shared(IDC_ARROW) };
+        };
+
+        if let Some(&cursor) = cache.get() {
+            return Self(cursor as HCURSOR);
+        }
+
+        let (canvas, hotspot) = drawing.draw();
+        let cursor = canvas.create(hotspot);
+        if cursor.is_null() {
+            return unsafe { Self::shared(IDC_ARROW) };
+        }
+
+        // another thread may have created the same cursor in the meantime
+        if cache.set(cursor as usize).is_err() {
+            unsafe { DestroyCursor(cursor) };
+        }
+
+        Self(*cache.get_or_init(|| cursor as usize) as HCURSOR)
+    }
+
     /// Sets the current cursor icon to this.
     pub fn apply(&self) {
         unsafe {
@@ -36,10 +70,10 @@ impl From<MouseCursor> for WinCursor {
                 MouseCursor::PtrWorking => Self::shared(IDC_APPSTARTING),
                 MouseCursor::NotAllowed => Self::shared(IDC_NO),
                 MouseCursor::PtrNotAllowed => Self::shared(IDC_NO), // fallback
-                MouseCursor::ZoomIn => Self::shared(IDC_SIZEALL),   // fallback
-                MouseCursor::ZoomOut => Self::shared(IDC_SIZEALL),  // fallback
-                MouseCursor::Alias => Self::shared(IDC_ARROW),      // fallback
-                MouseCursor::Copy => Self::shared(IDC_ARROW),       // fallback
+                MouseCursor::ZoomIn => Self::drawn(Drawing::ZoomIn),
+                MouseCursor::ZoomOut => Self::drawn(Drawing::ZoomOut),
+                MouseCursor::Alias => Self::drawn(Drawing::Alias),
+                MouseCursor::Copy => Self::drawn(Drawing::Copy),
                 MouseCursor::Move => Self::shared(IDC_SIZEALL),
                 MouseCursor::Cell => Self::shared(IDC_CROSS), // fallback
                 MouseCursor::Crosshair => Self::shared(IDC_CROSS),
@@ -57,11 +91,365 @@ impl From<MouseCursor> for WinCursor {
                 MouseCursor::NwseResize => Self::shared(IDC_SIZENWSE),
                 MouseCursor::NeswResize => Self::shared(IDC_SIZENESW),
 
+                MouseCursor::RowResize => Self::drawn(Drawing::RowResize),
+                MouseCursor::ColResize => Self::drawn(Drawing::ColResize),
+
                 // https://learn.microsoft.com/en-us/windows/win32/menurc/about-cursors
-                MouseCursor::RowResize => Self::shared(32652 as *const _),
-                MouseCursor::ColResize => Self::shared(32653 as *const _),
                 MouseCursor::AllScroll => Self::shared(32654 as *const _),
             }
         }
     }
 }
+
+/// Cursor shapes Windows has no cursor of its own for, drawn at runtime so
+/// they do not have to fall back to a look-alike.
+#[derive(Clone, Copy)]
+enum Drawing {
+    ZoomIn,
+    ZoomOut,
+    Alias,
+    Copy,
+    RowResize,
+    ColResize,
+}
+
+impl Drawing {
+    /// Draws the cursor, returning it with its hotspot. The hotspot is where
+    /// the shape points to, the center of the lens for the zoom cursors.
+    fn draw(self) -> (Canvas, (i32, i32)) {
+        let mut canvas = Canvas::default();
+
+        let hotspot = match self {
+            Drawing::ZoomIn | Drawing::ZoomOut => {
+                let lens = |x: f32, y: f32| (x - 10.5).hypot(y - 10.5);
+                canvas.paint(Color::White, |x, y| lens(x, y) < 7.0);
+                canvas.paint(Color::Black, |x, y| (7.0..9.0).contains(&lens(x, y)));
+                canvas.paint(Color::Black, |x, y| {
+                    x > 16.0 && y > 16.0 && x < 26.0 && (x - y).abs() < 1.5
+                });
+
+                // minus, and plus
+                canvas.paint(Color::Black, |x, y| {
+                    let (dx, dy) = ((x - 10.5).abs(), (y - 10.5).abs());
+                    (dy < 1.0 && dx < 3.5)
+                        || (matches!(self, Drawing::ZoomIn) && dx < 1.0 && dy < 3.5)
+                });
+
+                canvas.outline();
+                (10, 10)
+            }
+
+            Drawing::Alias | Drawing::Copy => {
+                canvas.paint_art(ARROW);
+
+                // a badge in the bottom right corner, with a plus or a little arrow
+                let (left, top) = (10.0, 18.0);
+                canvas.paint(Color::Black, |x, y| {
+                    (left..left + 11.0).contains(&x) && (top..top + 11.0).contains(&y)
+                });
+                canvas.paint(Color::White, |x, y| {
+                    (left + 1.0..left + 10.0).contains(&x) && (top + 1.0..top + 10.0).contains(&y)
+                });
+                canvas.paint(Color::Black, |x, y| {
+                    let (x, y) = (x - left, y - top);
+                    match self {
+                        Drawing::Copy => {
+                            let (dx, dy) = ((x - 5.5).abs(), (y - 5.5).abs());
+                            (dx < 1.0 && dy < 3.5) || (dy < 1.0 && dx < 3.5)
+                        }
+                        _ => {
+                            let head = (2.0..9.0).contains(&x) && (2.0..9.0).contains(&y);
+                            let shaft = (x + y - 11.0).abs() < 0.5;
+                            let tip = (y - 2.5).abs() < 0.5 && x > 4.0
+                                || (x - 8.5).abs() < 0.5 && y < 7.0;
+                            head && (shaft || tip)
+                        }
+                    }
+                });
+
+                (0, 0)
+            }
+
+            Drawing::RowResize | Drawing::ColResize => {
+                // drawn as a column resize, with the axes swapped for rows
+                let swap = |x: f32, y: f32| match self {
+                    Drawing::RowResize => (y, x),
+                    _ => (x, y),
+                };
+
+                canvas.paint(Color::White, |x, y| {
+                    let (x, y) = swap(x, y);
+                    (x - 15.5).abs() < 2.0 && (y - 15.5).abs() < 8.0
+                });
+                canvas.paint(Color::Black, |x, y| {
+                    let (x, y) = swap(x, y);
+                    let (dx, dy) = ((x - 15.5).abs(), (y - 15.5).abs());
+                    let bars = (dx - 2.0).abs() < 0.5 && dy < 8.0;
+                    let shafts = dy < 1.0 && (3.0..11.0).contains(&dx);
+                    let heads = (7.0..11.0).contains(&dx) && dy < dx - 6.5;
+                    bars || shafts || heads
+                });
+
+                canvas.outline();
+                (15, 15)
+            }
+        };
+
+        (canvas, hotspot)
+    }
+}
+
+/// The standard arrow, black on white.
+const ARROW: &[&str] = &[
+    "#",
+    "##",
+    "#-#",
+    "#--#",
+    "#---#",
+    "#----#",
+    "#-----#",
+    "#------#",
+    "#-------#",
+    "#--------#",
+    "#---------#",
+    "#----------#",
+    "#------#####",
+    "#---#--#",
+    "#--# #--#",
+    "#-#  #--#",
+    "##    #--#",
+    "      #--#",
+    "       ##",
+];
+
+/// The width and height of drawn cursors, the standard cursor size.
+const SIZE: usize = 32;
+
+#[derive(Clone, Copy)]
+enum Color {
+    Black,
+    White,
+}
+
+/// A monochrome cursor image, one row per entry with the leftmost pixel in
+/// the highest bit. Pixels in neither are transparent.
+#[derive(Default)]
+struct Canvas {
+    black: [u32; SIZE],
+    white: [u32; SIZE],
+}
+
+impl Canvas {
+    /// Paints every pixel whose center is inside of `shape`.
+    fn paint(&mut self, color: Color, shape: impl Fn(f32, f32) -> bool) {
+        let rows = self.black.iter_mut().zip(&mut self.white);
+        for (y, (black, white)) in rows.enumerate() {
+            for x in 0..SIZE {
+                if !shape(x as f32 + 0.5, y as f32 + 0.5) {
+                    continue;
+                }
+
+                let bit = 1 << (SIZE - 1 - x);
+                match color {
+                    Color::Black => (*black, *white) = (*black | bit, *white & !bit),
+                    Color::White => (*black, *white) = (*black & !bit, *white | bit),
+                }
+            }
+        }
+    }
+
+    /// Paints `art` into the top left corner, `#` is black and `-` is white.
+    fn paint_art(&mut self, art: &[&str]) {
+        let rows = self.black.iter_mut().zip(&mut self.white);
+        for (line, (black, white)) in art.iter().zip(rows) {
+            for (x, pixel) in line.bytes().take(SIZE).enumerate() {
+                let bit = 1 << (SIZE - 1 - x);
+                match pixel {
+                    b'#' => *black |= bit,
+                    b'-' => *white |= bit,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Surrounds the black pixels with white ones, so the shape stays visible
+    /// on dark backgrounds.
+    fn outline(&mut self) {
+        let mut spread = [0u32; SIZE];
+        for (y, spread) in spread.iter_mut().enumerate() {
+            let around = self
+                .black
+                .iter()
+                .skip(y.saturating_sub(1))
+                .take(y.min(1) + 2);
+            for row in around {
+                *spread |= row | row << 1 | row >> 1;
+            }
+        }
+
+        let rows = self.white.iter_mut().zip(&self.black).zip(spread);
+        for ((white, black), spread) in rows {
+            *white |= spread & !black;
+        }
+    }
+
+    /// Builds the AND and XOR masks passed to `CreateCursor`, rows top to
+    /// bottom with the leftmost pixel in the highest bit of the first byte.
+    fn planes(&self) -> ([u8; SIZE * SIZE / 8], [u8; SIZE * SIZE / 8]) {
+        // transparent pixels are the ones left alone by the AND mask, white ones are
+        // then flipped on by the XOR mask
+        let mut and_plane = [0u8; SIZE * SIZE / 8];
+        let mut xor_plane = [0u8; SIZE * SIZE / 8];
+
+        let planes = and_plane
+            .chunks_exact_mut(4)
+            .zip(xor_plane.chunks_exact_mut(4));
+        for ((and, xor), (black, white)) in planes.zip(self.black.iter().zip(&self.white)) {
+            and.copy_from_slice(&(!(black | white)).to_be_bytes());
+            xor.copy_from_slice(&white.to_be_bytes());
+        }
+
+        (and_plane, xor_plane)
+    }
+
+    fn create(&self, (x, y): (i32, i32)) -> HCURSOR {
+        let (and_plane, xor_plane) = self.planes();
+        unsafe {
+            CreateCursor(
+                hinstance(),
+                x,
+                y,
+                SIZE as i32,
+                SIZE as i32,
+                and_plane.as_ptr() as *const _,
+                xor_plane.as_ptr() as *const _,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ARROW, Canvas, Color, Drawing, SIZE};
+
+    /// The pixel at `(x, y)`, `#` for black, `-` for white and a space for
+    /// transparent, like the art.
+    fn pixel(canvas: &Canvas, x: usize, y: usize) -> char {
+        let bit = 1 << (SIZE - 1 - x);
+        let black = canvas.black.get(y).copied().unwrap_or_default();
+        let white = canvas.white.get(y).copied().unwrap_or_default();
+        match (black & bit != 0, white & bit != 0) {
+            (true, false) => '#',
+            (false, true) => '-',
+            (false, false) => ' ',
+            (true, true) => '?',
+        }
+    }
+
+    #[test]
+    fn test_canvas_paint() {
+        let mut canvas = Canvas::default();
+        canvas.paint(Color::White, |x, y| x < 4.0 && y < 4.0);
+        canvas.paint(Color::Black, |x, y| x < 2.0 && y < 2.0);
+
+        assert_eq!(pixel(&canvas, 0, 0), '#');
+        assert_eq!(pixel(&canvas, 1, 1), '#');
+        assert_eq!(pixel(&canvas, 2, 1), '-');
+        assert_eq!(pixel(&canvas, 3, 3), '-');
+        assert_eq!(pixel(&canvas, 4, 0), ' ');
+        assert_eq!(pixel(&canvas, SIZE - 1, SIZE - 1), ' ');
+    }
+
+    #[test]
+    fn test_canvas_paint_art() {
+        let mut canvas = Canvas::default();
+        canvas.paint_art(ARROW);
+
+        for (y, line) in ARROW.iter().enumerate() {
+            for (x, expected) in line.chars().enumerate() {
+                assert_eq!(pixel(&canvas, x, y), expected, "at {x}, {y}");
+            }
+        }
+        assert_eq!(pixel(&canvas, 0, ARROW.len()), ' ');
+    }
+
+    #[test]
+    fn test_canvas_outline() {
+        let mut canvas = Canvas::default();
+        canvas.paint(Color::Black, |x, y| {
+            (x - 5.5).abs() < 1.0 && (y - 5.5).abs() < 1.0
+        });
+        canvas.paint(Color::Black, |x, y| x < 1.0 && y < 1.0);
+        canvas.outline();
+
+        assert_eq!(pixel(&canvas, 5, 5), '#');
+        for (x, y) in (4..7).flat_map(|y| (4..7).map(move |x| (x, y))) {
+            if (x, y) != (5, 5) {
+                assert_eq!(pixel(&canvas, x, y), '-', "at {x}, {y}");
+            }
+        }
+        assert_eq!(pixel(&canvas, 3, 5), ' ');
+        assert_eq!(pixel(&canvas, 5, 7), ' ');
+
+        // corners do not wrap around to the other side
+        assert_eq!(pixel(&canvas, 0, 0), '#');
+        assert_eq!(pixel(&canvas, 1, 1), '-');
+        assert_eq!(pixel(&canvas, SIZE - 1, 0), ' ');
+        assert_eq!(pixel(&canvas, 0, SIZE - 1), ' ');
+    }
+
+    #[test]
+    fn test_canvas_planes() {
+        let mut canvas = Canvas::default();
+        canvas.paint(Color::Black, |x, y| x < 1.0 && y < 1.0);
+        canvas.paint(Color::White, |x, y| (1.0..2.0).contains(&x) && y < 1.0);
+
+        let (and_plane, xor_plane) = canvas.planes();
+
+        // black clears the AND mask, white also sets the XOR mask
+        assert_eq!(and_plane.first(), Some(&0b0011_1111));
+        assert_eq!(xor_plane.first(), Some(&0b0100_0000));
+
+        // the rest is transparent
+        assert!(and_plane.iter().skip(1).all(|&byte| byte == 0xff));
+        assert!(xor_plane.iter().skip(1).all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_drawings_hotspot() {
+        let drawings = [
+            Drawing::ZoomIn,
+            Drawing::ZoomOut,
+            Drawing::Alias,
+            Drawing::Copy,
+            Drawing::RowResize,
+            Drawing::ColResize,
+        ];
+
+        for drawing in drawings {
+            let (canvas, (x, y)) = drawing.draw();
+            let hotspot = pixel(&canvas, x as usize, y as usize);
+            assert!(matches!(hotspot, '#' | '-'), "hotspot is transparent");
+
+            // painting a color clears the other one, no pixel is ever both
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    assert_ne!(pixel(&canvas, x, y), '?');
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_zoom_drawings_differ() {
+        let (zoom_in, _) = Drawing::ZoomIn.draw();
+        let (zoom_out, _) = Drawing::ZoomOut.draw();
+
+        // the plus has a vertical bar through the center of the lens, the minus does not
+        assert_eq!(pixel(&zoom_in, 10, 8), '#');
+        assert_eq!(pixel(&zoom_out, 10, 8), '-');
+        assert_eq!(pixel(&zoom_in, 8, 10), '#');
+        assert_eq!(pixel(&zoom_out, 8, 10), '#');
+    }
+}
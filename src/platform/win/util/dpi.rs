@@ -1,7 +1,7 @@
 use windows_sys::Win32::Foundation::{FreeLibrary, HMODULE, HWND, RECT};
 use windows_sys::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryA};
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    AdjustWindowRectEx, WINDOW_EX_STYLE, WINDOW_STYLE,
+    AdjustWindowRectEx, GetSystemMetrics, SYSTEM_METRICS_INDEX, WINDOW_EX_STYLE, WINDOW_STYLE,
 };
 use windows_sys::core::BOOL;
 
@@ -15,6 +15,7 @@ pub struct DpiContext {
     adjust_window_rect_ex_for_dpi: Option<
         unsafe extern "system" fn(*mut RECT, WINDOW_STYLE, BOOL, WINDOW_EX_STYLE, u32) -> BOOL,
     >,
+    get_system_metrics_for_dpi: Option<unsafe extern "system" fn(SYSTEM_METRICS_INDEX, u32) -> i32>,
 }
 
 /// A RAII guard that sets the DPI awareness for the current thread, and
@@ -45,12 +46,16 @@ impl DpiContext {
             let adjust_window_rect_ex_for_dpi =
                 GetProcAddress(user32, c"AdjustWindowRectExForDpi".as_ptr() as *const _)
                     .map(|x| std::mem::transmute_copy(&x));
+            let get_system_metrics_for_dpi =
+                GetProcAddress(user32, c"GetSystemMetricsForDpi".as_ptr() as *const _)
+                    .map(|x| std::mem::transmute_copy(&x));
 
             Self {
                 user32,
                 get_dpi_for_window,
                 set_thread_dpi_awareness_context,
                 adjust_window_rect_ex_for_dpi,
+                get_system_metrics_for_dpi,
             }
         }
     }
@@ -89,6 +94,18 @@ impl DpiContext {
         }
     }
 
+    /// Gets a system metric scaled for the given DPI.
+    ///
+    /// Uses `GetSystemMetricsForDpi` if available, otherwise falls back to
+    /// `GetSystemMetrics`, which scales for the DPI awareness of the thread
+    /// instead (not at all for DPI-unaware ones).
+    pub fn system_metrics_for_dpi(&self, index: SYSTEM_METRICS_INDEX, dpi: u32) -> i32 {
+        match self.get_system_metrics_for_dpi {
+            Some(get) => unsafe { get(index, dpi) },
+            None => unsafe { GetSystemMetrics(index) },
+        }
+    }
+
     /// Set the thread DPI awareness to
     /// `DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2` for the duration of the
     /// guard, if supported.
@@ -1,5 +1,5 @@
+use crate::WindowError;
 use crate::platform::win::util::widestr::WideString;
-use crate::{OpenGlError, WindowError};
 use std::fmt::Display;
 use std::ptr::null_mut;
 use windows_sys::Win32::Foundation::GetLastError;
@@ -77,7 +77,14 @@ impl From<Win32Error> for WindowError {
     }
 }
 
-impl From<Win32Error> for OpenGlError {
+impl From<Win32Error> for crate::PresentError {
+    fn from(err: Win32Error) -> Self {
+        Self::Platform(err.to_string())
+    }
+}
+
+#[cfg(feature = "opengl")]
+impl From<Win32Error> for crate::OpenGlError {
     fn from(err: Win32Error) -> Self {
         Self::Platform(err.to_string())
     }
@@ -1,12 +1,15 @@
-use crate::DropEffect;
+use crate::platform::win::util::widestr::WideString;
+use crate::{DropEffect, Image, Size};
 use std::ffi::OsString;
 use std::marker::PhantomData;
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
 use std::path::PathBuf;
 use std::ptr::{copy_nonoverlapping, null_mut};
-use windows_sys::Win32::Foundation::{HWND, POINT};
+use windows_sys::Win32::Foundation::{GlobalFree, HGLOBAL, HWND, POINT};
+use windows_sys::Win32::Graphics::Gdi::{BI_BITFIELDS, BI_RGB, BITMAPV5HEADER, LCS_GM_IMAGES};
 use windows_sys::Win32::System::DataExchange::{
-    CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
+    CloseClipboard, CountClipboardFormats, EmptyClipboard, GetClipboardData, OpenClipboard,
+    RegisterClipboardFormatW, SetClipboardData,
 };
 use windows_sys::Win32::System::Memory::{
     GMEM_MOVEABLE, GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock,
@@ -14,6 +17,7 @@ use windows_sys::Win32::System::Memory::{
 use windows_sys::Win32::System::Ole::{
     CLIPBOARD_FORMAT, DROPEFFECT_COPY, DROPEFFECT_LINK, DROPEFFECT_MOVE, DROPEFFECT_NONE,
 };
+use windows_sys::Win32::UI::ColorSystem::LCS_sRGB;
 use windows_sys::Win32::UI::Shell::{DROPFILES, DragQueryFileW, HDROP};
 
 pub struct Clipboard(PhantomData<*const ()>);
@@ -35,11 +39,15 @@ impl Clipboard {
         }
     }
 
-    /// Empties the clipboard, removing all data.
-    pub fn empty(&self) {
-        unsafe {
-            EmptyClipboard();
-        }
+    /// Empties the clipboard, removing all data and taking ownership of it.
+    /// Returns `false` if the clipboard could not be emptied.
+    pub fn empty(&self) -> bool {
+        unsafe { EmptyClipboard() != 0 }
+    }
+
+    /// Checks if the clipboard holds no data in any format.
+    pub fn is_empty(&self) -> bool {
+        unsafe { CountClipboardFormats() == 0 }
     }
 
     /// Gets the clipboard data for the given format. Returns `None` if the data
@@ -72,23 +80,13 @@ impl Clipboard {
     /// - The data must match the specified format.
     pub unsafe fn set(&self, format: CLIPBOARD_FORMAT, data: &[u8]) -> bool {
         unsafe {
-            let buf = GlobalAlloc(GMEM_MOVEABLE, std::mem::size_of_val(data));
-            if buf.is_null() {
-                return false;
-            }
-
-            let buf = GlobalLock(buf) as *mut u8;
-            if buf.is_null() {
-                return false;
-            }
-
-            copy_nonoverlapping(data.as_ptr(), buf, data.len());
-
-            if GlobalUnlock(buf as *mut _) == 0 {
+            let Some(handle) = global_copy(data) else {
                 return false;
-            }
+            };
 
-            if SetClipboardData(format as _, buf as *mut _).is_null() {
+            // the clipboard owns the memory only if this succeeds
+            if SetClipboardData(format as _, handle).is_null() {
+                GlobalFree(handle);
                 return false;
             }
 
@@ -105,6 +103,159 @@ impl Drop for Clipboard {
     }
 }
 
+/// The clipboard format registered under `name`, registering it if this is
+/// the first time the system sees it. `None` if the name is invalid.
+pub fn register_format(name: &str) -> Option<CLIPBOARD_FORMAT> {
+    let name = WideString::from(name);
+    let format = unsafe { RegisterClipboardFormatW(name.as_ptr()) };
+    CLIPBOARD_FORMAT::try_from(format)
+        .ok()
+        .filter(|&format| format != 0)
+}
+
+/// Encode an image as a DIB with a [`BITMAPV5HEADER`], for `CF_DIBV5`. The
+/// rows are stored from the bottom to the top, top-down DIBs confuse a lot of
+/// applications.
+pub fn encode_dib(image: &Image) -> Vec<u8> {
+    let Size { width, height } = image.size();
+    let header = BITMAPV5HEADER {
+        bV5Size: size_of::<BITMAPV5HEADER>() as u32,
+        bV5Width: width.try_into().unwrap_or(i32::MAX),
+        bV5Height: height.try_into().unwrap_or(i32::MAX),
+        bV5Planes: 1,
+        bV5BitCount: 32,
+        bV5Compression: BI_BITFIELDS,
+        bV5SizeImage: image.pixels().len() as u32,
+        bV5RedMask: 0x00ff0000,
+        bV5GreenMask: 0x0000ff00,
+        bV5BlueMask: 0x000000ff,
+        bV5AlphaMask: 0xff000000,
+        bV5CSType: LCS_sRGB as u32,
+        bV5Intent: LCS_GM_IMAGES as u32,
+        ..Default::default()
+    };
+
+    let mut result = Vec::with_capacity(header.bV5Size as usize + image.pixels().len());
+    result.extend_from_slice(unsafe {
+        std::slice::from_raw_parts(
+            &header as *const BITMAPV5HEADER as *const u8,
+            size_of::<BITMAPV5HEADER>(),
+        )
+    });
+
+    for row in image.pixels().chunks_exact(width as usize * 4).rev() {
+        for pixel in row.chunks_exact(4) {
+            if let &[red, green, blue, alpha] = pixel {
+                result.extend([blue, green, red, alpha]);
+            }
+        }
+    }
+
+    result
+}
+
+/// Decode a 24 or 32-bit DIB, as found in `CF_DIB` and `CF_DIBV5`. Without an
+/// alpha channel, or one that is zero everywhere (the reserved byte of most
+/// 32-bit DIBs), the image is opaque.
+pub fn decode_dib(data: &[u8]) -> Option<Image> {
+    let read = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+        ))
+    };
+
+    let header_size = read(0)? as usize;
+    let (width, height) = (read(4)? as i32, read(8)? as i32);
+    let bit_count = read(12)? >> 16;
+    let (compression, colors) = (read(16)?, read(32)? as usize);
+    if header_size < 40 {
+        return None;
+    }
+
+    // the masks follow a BITMAPINFOHEADER, later versions include them and the
+    // alpha mask in the header
+    let (masks, masks_size) = match (compression, bit_count) {
+        (BI_RGB, 24) => ([0xff0000, 0xff00, 0xff, 0], 0),
+        (BI_RGB, 32) => ([0xff0000, 0xff00, 0xff, 0xff000000], 0),
+        (BI_BITFIELDS, 32) if header_size == 40 => {
+            ([read(40)?, read(44)?, read(48)?, 0xff000000], 12)
+        }
+        (BI_BITFIELDS, 32) if header_size >= 56 => {
+            ([read(40)?, read(44)?, read(48)?, read(52)?], 0)
+        }
+        _ => return None,
+    };
+
+    let (columns, rows) = (
+        width.unsigned_abs() as usize,
+        height.unsigned_abs() as usize,
+    );
+    let bytes_per_pixel = bit_count as usize / 8;
+    let row_len = columns.checked_mul(bytes_per_pixel)?;
+    let stride = row_len.checked_next_multiple_of(4)?;
+    let start = header_size
+        .checked_add(masks_size)?
+        .checked_add(colors.checked_mul(4)?)?;
+
+    // all of the rows have to be there, before allocating for them
+    let end = rows.checked_mul(stride)?.checked_add(start)?;
+    if columns == 0 || rows == 0 || end > data.len() {
+        return None;
+    }
+
+    let channel = |value: u32, mask: u32| match mask {
+        0 => 0,
+        mask => ((value & mask) >> mask.trailing_zeros()) as u8,
+    };
+
+    let mut rgba = Vec::with_capacity(columns * rows * 4);
+    for y in 0..rows {
+        // negative heights are top-down, the offsets are inside of `end`
+        let row = if height < 0 { y } else { rows - 1 - y };
+        let row = data.get(start + row * stride..)?.get(..row_len)?;
+
+        for pixel in row.chunks_exact(bytes_per_pixel) {
+            let value = pixel
+                .iter()
+                .rev()
+                .fold(0, |value, &byte| value << 8 | byte as u32);
+            rgba.extend(masks.map(|mask| channel(value, mask)));
+        }
+    }
+
+    if rgba.chunks_exact(4).all(|pixel| pixel.get(3) == Some(&0)) {
+        for pixel in rgba.chunks_exact_mut(4) {
+            if let Some(alpha) = pixel.get_mut(3) {
+                *alpha = 255;
+            }
+        }
+    }
+
+    Image::new((columns as u32, rows as u32), rgba)
+}
+
+/// Copies the data into newly allocated global memory, the way the clipboard
+/// and OLE want it. The caller owns the returned handle and frees it with
+/// [`GlobalFree`] unless the ownership was passed on.
+pub fn global_copy(data: &[u8]) -> Option<HGLOBAL> {
+    unsafe {
+        let handle = GlobalAlloc(GMEM_MOVEABLE, std::mem::size_of_val(data));
+        if handle.is_null() {
+            return None;
+        }
+
+        let buf = GlobalLock(handle) as *mut u8;
+        if buf.is_null() {
+            GlobalFree(handle);
+            return None;
+        }
+
+        copy_nonoverlapping(data.as_ptr(), buf, data.len());
+        GlobalUnlock(handle);
+        Some(handle)
+    }
+}
+
 /// Encodes a list of paths into an [`HDROP`] structure, which can be used to
 /// set the clipboard data or for drag-and-drop operations.
 pub fn encode_hdrop(paths: &[PathBuf]) -> Vec<u8> {
@@ -162,3 +313,76 @@ pub fn encode_drop_effect(effect: DropEffect) -> u32 {
         DropEffect::Generic => DROPEFFECT_COPY | DROPEFFECT_MOVE | DROPEFFECT_LINK,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BI_RGB, decode_dib, encode_dib};
+    use crate::Image;
+
+    /// A `BITMAPINFOHEADER` without masks.
+    fn info_header(width: i32, height: i32, bit_count: u16, colors: u32) -> Vec<u8> {
+        let mut header = Vec::with_capacity(40);
+        header.extend(40u32.to_le_bytes());
+        header.extend(width.to_le_bytes());
+        header.extend(height.to_le_bytes());
+        header.extend(1u16.to_le_bytes());
+        header.extend(bit_count.to_le_bytes());
+        header.extend(BI_RGB.to_le_bytes());
+        header.extend([0; 12]);
+        header.extend(colors.to_le_bytes());
+        header.extend([0; 4]);
+        header
+    }
+
+    #[test]
+    fn test_dib_round_trip() {
+        let pixels = (0..2 * 3 * 4).map(|i| i as u8 * 10).collect::<Vec<_>>();
+        let image = Image::new((2, 3), pixels.clone()).expect("valid image");
+
+        let decoded = decode_dib(&encode_dib(&image)).expect("decodes");
+        assert_eq!(decoded.pixels(), pixels);
+    }
+
+    #[test]
+    fn test_dib_24_bit_padded_rows() {
+        // bottom-up, every 3 byte row padded to 4 bytes
+        let mut dib = info_header(1, 2, 24, 0);
+        dib.extend([3, 2, 1, 0]);
+        dib.extend([6, 5, 4, 0]);
+
+        let decoded = decode_dib(&dib).expect("decodes");
+        assert_eq!(decoded.pixels(), [4, 5, 6, 255, 1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn test_dib_zero_alpha_is_opaque() {
+        let mut dib = info_header(1, -1, 32, 0);
+        dib.extend([3, 2, 1, 0]);
+
+        let decoded = decode_dib(&dib).expect("decodes");
+        assert_eq!(decoded.pixels(), [1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn test_dib_truncated() {
+        let mut dib = info_header(2, 2, 32, 0);
+        dib.extend([0; 12]);
+        assert!(decode_dib(&dib).is_none());
+        assert!(decode_dib(dib.get(..20).unwrap_or_default()).is_none());
+        assert!(decode_dib(&[]).is_none());
+    }
+
+    #[test]
+    fn test_dib_oversized() {
+        let mut dib = info_header(i32::MAX, i32::MIN, 32, 0);
+        dib.extend([0; 16]);
+        assert!(decode_dib(&dib).is_none());
+
+        let mut dib = info_header(1, 1, 32, u32::MAX);
+        dib.extend([0; 4]);
+        assert!(decode_dib(&dib).is_none());
+
+        let dib = info_header(0, 0, 32, 0);
+        assert!(decode_dib(&dib).is_none());
+    }
+}
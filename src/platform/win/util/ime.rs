@@ -0,0 +1,138 @@
+use crate::Rect;
+use crate::platform::utf16_range_to_bytes;
+use std::ops::Range;
+use std::ptr::null_mut;
+use windows_sys::Win32::Foundation::{HWND, POINT, RECT};
+use windows_sys::Win32::UI::Input::Ime::{
+    ATTR_TARGET_CONVERTED, ATTR_TARGET_NOTCONVERTED, CANDIDATEFORM, CFS_EXCLUDE, CFS_POINT,
+    COMPOSITIONFORM, GCS_COMPATTR, GCS_COMPSTR, GCS_CURSORPOS, GCS_RESULTSTR, HIMC,
+    IME_COMPOSITION_STRING, ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext,
+    ImmSetCandidateWindow, ImmSetCompositionWindow,
+};
+
+/// Move the composition and candidate windows of the input method next to
+/// the given area of the client area, so they follow the caret instead of
+/// sitting in the corner of the window.
+///
+/// Does nothing if the window has no input context (no IME is active).
+pub fn set_ime_cursor_area(hwnd: HWND, area: Rect) {
+    unsafe {
+        let himc = ImmGetContext(hwnd);
+        if himc.is_null() {
+            return;
+        }
+
+        let area = RECT {
+            left: area.left,
+            top: area.top,
+            right: area.right,
+            bottom: area.bottom,
+        };
+
+        // the composition string starts at the caret
+        let composition = COMPOSITIONFORM {
+            dwStyle: CFS_POINT,
+            ptCurrentPos: POINT {
+                x: area.left,
+                y: area.top,
+            },
+            rcArea: area,
+        };
+
+        // the candidate list goes below the caret, but must not cover it
+        let candidate = CANDIDATEFORM {
+            dwIndex: 0,
+            dwStyle: CFS_EXCLUDE,
+            ptCurrentPos: POINT {
+                x: area.left,
+                y: area.bottom,
+            },
+            rcArea: area,
+        };
+
+        ImmSetCompositionWindow(himc, &composition);
+        ImmSetCandidateWindow(himc, &candidate);
+        ImmReleaseContext(hwnd, himc);
+    }
+}
+
+/// Read the string being composed on `WM_IME_COMPOSITION` with
+/// [`GCS_COMPSTR`], along with the byte range of it that is being converted
+/// (the target clause), or an empty range at the caret if there is none.
+pub fn composition_string(hwnd: HWND) -> Option<(String, Range<usize>)> {
+    unsafe {
+        let himc = ImmGetContext(hwnd);
+        if himc.is_null() {
+            return None;
+        }
+
+        let text = read_string(himc, GCS_COMPSTR);
+        let attributes = read_bytes(himc, GCS_COMPATTR).unwrap_or_default();
+        let caret = ImmGetCompositionStringW(himc, GCS_CURSORPOS, null_mut(), 0);
+        ImmReleaseContext(hwnd, himc);
+
+        // one attribute per UTF-16 code unit
+        let text = text?;
+        let is_target = |attribute: &u8| {
+            matches!(
+                u32::from(*attribute),
+                ATTR_TARGET_CONVERTED | ATTR_TARGET_NOTCONVERTED
+            )
+        };
+
+        let range = match (
+            attributes.iter().position(is_target),
+            attributes.iter().rposition(is_target),
+        ) {
+            (Some(first), Some(last)) => first..last + 1,
+            _ => {
+                let caret = usize::try_from(caret).unwrap_or(0);
+                caret..caret
+            }
+        };
+
+        let range = utf16_range_to_bytes(&text, range);
+        Some((text, range))
+    }
+}
+
+/// Read the string committed on `WM_IME_COMPOSITION` with [`GCS_RESULTSTR`].
+pub fn composition_result(hwnd: HWND) -> Option<String> {
+    unsafe {
+        let himc = ImmGetContext(hwnd);
+        if himc.is_null() {
+            return None;
+        }
+
+        let text = read_string(himc, GCS_RESULTSTR);
+        ImmReleaseContext(hwnd, himc);
+        text
+    }
+}
+
+/// Read a string of the composition, `None` if the input method does not
+/// have it.
+unsafe fn read_string(himc: HIMC, index: IME_COMPOSITION_STRING) -> Option<String> {
+    let bytes = unsafe { read_bytes(himc, index)? };
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .filter_map(|unit| unit.try_into().ok())
+        .map(u16::from_ne_bytes)
+        .collect();
+
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Read the raw data of the composition, `None` if the input method does not
+/// have it.
+unsafe fn read_bytes(himc: HIMC, index: IME_COMPOSITION_STRING) -> Option<Vec<u8>> {
+    unsafe {
+        // the size in bytes, or a negative IMM_ERROR_* code
+        let size = ImmGetCompositionStringW(himc, index, null_mut(), 0);
+        let mut buffer = vec![0u8; usize::try_from(size).ok()?];
+        let size = ImmGetCompositionStringW(himc, index, buffer.as_mut_ptr().cast(), size as u32);
+
+        buffer.truncate(usize::try_from(size).ok()?);
+        Some(buffer)
+    }
+}
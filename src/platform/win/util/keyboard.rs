@@ -28,6 +28,72 @@ pub fn query_modifiers() -> Modifiers {
         caps_lock: is_toggled(VK_CAPITAL),
         num_lock: is_toggled(VK_NUMLOCK),
         scroll_lock: is_toggled(VK_SCROLL),
+        fn_key: false,
+    }
+}
+
+/// Query the keys currently held down from the thread-local OS state.
+pub fn query_keys_down() -> impl Iterator<Item = Key> {
+    let mut state = [0u8; 256];
+    if unsafe { GetKeyboardState(state.as_mut_ptr()) } == 0 {
+        state = [0; 256];
+    }
+
+    (0..256u32)
+        .filter(move |&vk| state.get(vk as usize).is_some_and(|s| s & 0x80 != 0))
+        // the generic shift/ctrl/alt keys alias their left/right variants
+        .filter(|&vk| !matches!(vk as VIRTUAL_KEY, VK_SHIFT | VK_CONTROL | VK_MENU))
+        .filter_map(|vk| {
+            let scan_code = unsafe { MapVirtualKeyW(vk, MAPVK_VK_TO_VSC_EX) };
+            // extended keys are reported as 0xE0xx, lparam style is 0x1xx
+            let scan_code = match scan_code & 0xFF00 {
+                0xE000 | 0xE100 => (scan_code & 0xFF) | 0x100,
+                _ => scan_code,
+            };
+
+            scan_code_to_key(scan_code)
+        })
+}
+
+/// Get the printable character a [`WM_KEYDOWN`] message would type with the
+/// current keyboard layout, if any.
+///
+/// The keyboard state is left untouched (where supported), so dead keys still
+/// compose when the message is translated later.
+pub fn virtual_key_to_char(virtual_key: u32, scan_code: u32) -> Option<char> {
+    unsafe {
+        let mut state = [0u8; 256];
+        if GetKeyboardState(state.as_mut_ptr()) == 0 {
+            return None;
+        }
+
+        // bit 2: do not change the keyboard state (Windows 10 1607+)
+        let mut buffer = [0u16; 8];
+        let len = ToUnicodeEx(
+            virtual_key,
+            scan_code,
+            state.as_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len() as i32,
+            1 << 2,
+            GetKeyboardLayout(0),
+        );
+
+        // negative values are dead keys, zero means no translation
+        if len <= 0 {
+            return None;
+        }
+
+        char::decode_utf16(
+            buffer
+                .get(..len as usize)
+                .unwrap_or_default()
+                .iter()
+                .copied(),
+        )
+        .next()?
+        .ok()
+        .filter(|ch| !ch.is_control())
     }
 }
 
@@ -148,6 +214,10 @@ pub fn scan_code_to_key(scan_code: u32) -> Option<Key> {
 
 /// A keyboard hook, used to capture key events in case a DAW
 /// tries to capture the events meant for us.
+///
+/// There is at most one hook installed per thread, shared by all of our windows
+/// on that thread. Hooks installed by anyone else on the same thread (the host,
+/// other plugins, another copy of `picoview`) are always chained to.
 pub struct KeyboardHook {
     hook: Rc<HookInner>,
     hwnd: HWND,
@@ -194,7 +264,7 @@ impl HookInner {
     /// new one and return it.
     fn get_or_install() -> Rc<Self> {
         // take the hook
-        let hook = match HOOK.replace(Weak::new()).upgrade() {
+        let hook = match Self::current() {
             Some(hook) => hook,
 
             // if we dont have one, create it
@@ -215,6 +285,60 @@ impl HookInner {
         HOOK.set(Rc::downgrade(&hook));
         hook
     }
+
+    /// Gets the current hook for this thread, without installing a new one.
+    fn current() -> Option<Rc<Self>> {
+        let hook = HOOK.replace(Weak::new());
+        let current = hook.upgrade();
+        HOOK.set(hook);
+        current
+    }
+
+    /// Dispatches a key message to our windows, starting from the target
+    /// window and going up the parent chain until one of them captures it.
+    ///
+    /// Returns `true` if the message was captured by one of our windows.
+    unsafe fn dispatch(&self, message: &mut MSG) -> bool {
+        unsafe {
+            // send modifier change messages (we do it here because WM_KEYDOWN and WM_KEYUP
+            // can be consumed by the host, and polling it per frame is not good)
+            for &hwnd in self.windows.borrow().iter() {
+                // key event happened, modifiers likely changed...
+                PostMessageW(hwnd, WM_USER_KEY_MODIFIERS, 0, 0);
+            }
+
+            // if the window is one of ours...
+            while self.windows.borrow().contains(&message.hwnd) {
+                // let our window handle it and see if it wants to capture it
+                let capture = SendMessageW(
+                    message.hwnd,
+                    if matches!(message.message, WM_KEYDOWN | WM_SYSKEYDOWN) {
+                        WM_USER_KEY_DOWN
+                    } else {
+                        WM_USER_KEY_UP
+                    },
+                    message.wParam,
+                    message.lParam,
+                ) != 0;
+
+                if capture {
+                    return true;
+                }
+
+                // otherwise, we check the parent window to see if it wants to capture it
+                // instead
+                let parent = GetParent(message.hwnd);
+                if parent.is_null() {
+                    break;
+                }
+
+                message.hwnd = parent;
+            }
+
+            // if it wasn't meant for us, we let it pass through
+            false
+        }
+    }
 }
 
 impl Drop for HookInner {
@@ -236,59 +360,27 @@ impl Drop for HookInner {
 unsafe extern "system" fn keyboard_hook_proc(msg: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     unsafe {
         if msg == HC_ACTION as i32 && wparam == PM_REMOVE as usize {
-            let message = lparam as *mut MSG;
-
-            // if its a key event...
-            if matches!((*message).message, WM_KEYDOWN | WM_KEYUP) {
-                let hook = HookInner::get_or_install(); // should be already installed, just a query.
-
-                // send modifier change messages (we do it here because WM_KEYDOWN and WM_KEYUP
-                // can be consumed by the host, and polling it per frame is not good)
-                for &hwnd in hook.windows.borrow().iter() {
-                    // key event happened, modifiers likely changed...
-                    PostMessageW(hwnd, WM_USER_KEY_MODIFIERS, 0, 0);
-                }
-
-                // if the window is one of ours...
-                while hook.windows.borrow().contains(&(*message).hwnd) {
-                    // let our window handle it and see if it wants to capture it
-                    let capture = SendMessageW(
-                        (*message).hwnd,
-                        if (*message).message == WM_KEYDOWN {
-                            WM_USER_KEY_DOWN
-                        } else {
-                            WM_USER_KEY_UP
-                        },
-                        (*message).wParam,
-                        (*message).lParam,
-                    ) != 0;
-
-                    // if it does, we stop the message from being dispatched (replace it with a zero
-                    // message)
-                    if capture {
-                        *message = MSG {
-                            message: WM_USER,
-                            ..zeroed()
-                        };
-
-                        return 0;
-                    } else {
-                        // otherwise, we check the parent window to see if it wants to capture it
-                        // instead
-                        let parent = GetParent((*message).hwnd);
-                        if parent.is_null() {
-                            break;
-                        } else {
-                            (*message).hwnd = parent;
-                        }
-                    }
-                }
+            let message = &mut *(lparam as *mut MSG);
 
-                // if it wasn't meant for us, we let it pass through
+            // if its a key event... (system keys are the ones pressed while Alt is held, or F10)
+            if matches!(
+                message.message,
+                WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP
+            ) && let Some(hook) = HookInner::current()
+                && hook.dispatch(message)
+            {
+                // if one of our windows captured it, we stop the message from being
+                // dispatched (replace it with a zero message)
+                *message = MSG {
+                    message: WM_USER,
+                    ..zeroed()
+                };
             }
         }
 
-        // pass the message, normal hook stuff
+        // always pass the message along, other hooks on this thread (other plugins, or
+        // the host itself) expect to be called. a captured message is seen by them as
+        // the zero message, so nobody handles it twice.
         CallNextHookEx(null_mut(), msg, wparam, lparam)
     }
 }
@@ -0,0 +1,39 @@
+use super::dpi::DpiContext;
+use crate::SystemMetrics;
+use std::time::Duration;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::GetDoubleClickTime;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    GetCaretBlinkTime, SM_CXDRAG, SM_CXVSCROLL, USER_DEFAULT_SCREEN_DPI,
+};
+
+/// `GetCaretBlinkTime` value meaning the caret does not blink (`INFINITE`)
+const BLINK_DISABLED: u32 = u32::MAX;
+
+/// Read the metrics from the system settings, falling back to the defaults
+/// for the ones that can not be read.
+pub fn query_system_metrics() -> SystemMetrics {
+    let dpi_context = DpiContext::new();
+    let default = SystemMetrics::default();
+
+    // logical pixels are the metrics at the default DPI
+    let metric =
+        |index, default| match dpi_context.system_metrics_for_dpi(index, USER_DEFAULT_SCREEN_DPI) {
+            0 => default,
+            value => value as f64,
+        };
+
+    SystemMetrics {
+        scrollbar_width: metric(SM_CXVSCROLL, default.scrollbar_width),
+        overlay_scrollbars: false,
+        double_click_time: match unsafe { GetDoubleClickTime() } {
+            0 => default.double_click_time,
+            value => Duration::from_millis(value.into()),
+        },
+        caret_blink_interval: match unsafe { GetCaretBlinkTime() } {
+            0 => default.caret_blink_interval,
+            BLINK_DISABLED => None,
+            value => Some(Duration::from_millis(value.into())),
+        },
+        drag_threshold: metric(SM_CXDRAG, default.drag_threshold),
+    }
+}
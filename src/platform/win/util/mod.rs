@@ -1,3 +1,5 @@
+/// Color management (ICC profiles).
+pub mod color;
 /// Cursor icon utilities.
 pub mod cursor;
 /// DPI awareness management.
@@ -6,11 +8,26 @@ pub mod dpi;
 pub mod error;
 /// Inter-process data exchange (clipboard and drag-and-drop).
 pub mod exchange;
+/// Input method (IME) window placement.
+pub mod ime;
 /// Keyboard utilities and event capture.
 pub mod keyboard;
+/// Metrics of the native controls, like the scrollbar width.
+pub mod metrics;
+/// OLE apartment initialization.
+pub mod ole;
+/// Pointer (`WM_POINTER`) input utilities.
+pub mod pointer;
+/// System power (suspend and resume) notifications.
+pub mod power;
+/// Software presentation of pixel buffers.
+pub mod present;
+/// Shell integration, like opening URLs.
+pub mod shell;
 /// Vertical synchronization thread.
 pub mod vsync;
 /// WGL utilities for OpenGL context creation.
+#[cfg(feature = "opengl")]
 pub mod wgl;
 /// Wide string utilities.
 pub mod widestr;
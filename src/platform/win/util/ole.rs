@@ -0,0 +1,33 @@
+use std::marker::PhantomData;
+use std::ptr::null;
+use windows_sys::Win32::System::Ole::{OleInitialize, OleUninitialize};
+
+/// Keeps OLE (and COM with it) initialized on the current thread, in the
+/// single-threaded apartment needed for drag and drop.
+///
+/// OLE initialization is reference counted per thread by the system, so every
+/// successful `OleInitialize` (including `S_FALSE`, when the host already
+/// initialized the thread) is balanced with `OleUninitialize` when this is
+/// dropped. Not `Send`, as it must be dropped on the thread that created it.
+pub struct OleApartment(PhantomData<*const ()>);
+
+impl OleApartment {
+    /// Initialize OLE on the current thread.
+    ///
+    /// Returns `None` if that is not possible, most commonly because the host
+    /// already put the thread into a multithreaded apartment
+    /// (`RPC_E_CHANGED_MODE`). The apartment is left as is in that case, and
+    /// drag and drop is not available.
+    pub fn enter() -> Option<Self> {
+        let result = unsafe { OleInitialize(null()) };
+        (result >= 0).then_some(Self(PhantomData))
+    }
+}
+
+impl Drop for OleApartment {
+    fn drop(&mut self) {
+        unsafe {
+            OleUninitialize();
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{Point, ScrollSettings};
+use std::mem::zeroed;
+use windows_sys::Win32::Foundation::{FreeLibrary, HMODULE, LPARAM};
+use windows_sys::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryA};
+use windows_sys::Win32::UI::Input::Pointer::{POINTER_INFO, POINTER_PEN_INFO};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    PEN_FLAG_ERASER, PEN_FLAG_INVERTED, POINTER_INPUT_TYPE, SPI_GETWHEELSCROLLCHARS,
+    SPI_GETWHEELSCROLLLINES, SystemParametersInfoW,
+};
+use windows_sys::core::BOOL;
+
+/// `SPI_GETWHEELSCROLLLINES` value meaning one notch scrolls a whole page
+const WHEEL_PAGESCROLL: u32 = u32::MAX;
+
+/// Read the wheel settings from the control panel, falling back to the
+/// defaults for the ones that can not be read.
+pub fn query_scroll_settings() -> ScrollSettings {
+    let query = |action| unsafe {
+        let mut value = 0u32;
+        match SystemParametersInfoW(action, 0, &mut value as *mut u32 as *mut _, 0) {
+            0 => None,
+            _ => Some(value),
+        }
+    };
+
+    let default = ScrollSettings::default();
+    ScrollSettings {
+        lines_per_notch: match query(SPI_GETWHEELSCROLLLINES) {
+            Some(WHEEL_PAGESCROLL) => None,
+            Some(lines) => Some(lines),
+            None => default.lines_per_notch,
+        },
+        chars_per_notch: query(SPI_GETWHEELSCROLLCHARS).unwrap_or(default.chars_per_notch),
+        natural: false,
+    }
+}
+
+/// Client coordinates of a legacy mouse message (`GET_X_LPARAM` and
+/// `GET_Y_LPARAM`).
+///
+/// Both are packed as signed 16-bit values, so they have to be sign extended:
+/// a captured mouse left of or above the window reports negative coordinates.
+/// The messages have no room for coordinates past ±32767, use the pointer
+/// messages when that matters.
+pub fn point_from_lparam(lparam: LPARAM) -> Point {
+    Point {
+        x: (lparam & 0xffff) as u16 as i16 as f64,
+        y: ((lparam >> 16) & 0xffff) as u16 as i16 as f64,
+    }
+}
+
+/// A context for querying `WM_POINTER` input information on Windows.
+#[derive(Default)]
+pub struct PointerContext {
+    user32: HMODULE,
+    enable_mouse_in_pointer: Option<unsafe extern "system" fn(BOOL) -> BOOL>,
+    is_mouse_in_pointer_enabled: Option<unsafe extern "system" fn() -> BOOL>,
+    get_pointer_type: Option<unsafe extern "system" fn(u32, *mut POINTER_INPUT_TYPE) -> BOOL>,
+    get_pointer_info: Option<unsafe extern "system" fn(u32, *mut POINTER_INFO) -> BOOL>,
+    get_pointer_pen_info: Option<unsafe extern "system" fn(u32, *mut POINTER_PEN_INFO) -> BOOL>,
+}
+
+impl PointerContext {
+    /// Creates a new [`PointerContext`], loading the necessary functions from
+    /// `user32.dll` at runtime (so it can work on older versions of Windows,
+    /// like Windows 7, where pointer input functions are not available).
+    pub fn new() -> Self {
+        unsafe {
+            let user32 = LoadLibraryA(c"user32.dll".as_ptr() as *const _);
+            if user32.is_null() {
+                return Self::default();
+            }
+
+            let enable_mouse_in_pointer =
+                GetProcAddress(user32, c"EnableMouseInPointer".as_ptr() as *const _)
+                    .map(|x| std::mem::transmute_copy(&x));
+            let is_mouse_in_pointer_enabled =
+                GetProcAddress(user32, c"IsMouseInPointerEnabled".as_ptr() as *const _)
+                    .map(|x| std::mem::transmute_copy(&x));
+            let get_pointer_type = GetProcAddress(user32, c"GetPointerType".as_ptr() as *const _)
+                .map(|x| std::mem::transmute_copy(&x));
+            let get_pointer_info = GetProcAddress(user32, c"GetPointerInfo".as_ptr() as *const _)
+                .map(|x| std::mem::transmute_copy(&x));
+            let get_pointer_pen_info =
+                GetProcAddress(user32, c"GetPointerPenInfo".as_ptr() as *const _)
+                    .map(|x| std::mem::transmute_copy(&x));
+
+            Self {
+                user32,
+                enable_mouse_in_pointer,
+                is_mouse_in_pointer_enabled,
+                get_pointer_type,
+                get_pointer_info,
+                get_pointer_pen_info,
+            }
+        }
+    }
+
+    /// Route mouse input through `WM_POINTER` messages for the whole process.
+    ///
+    /// This can not be undone. Returns `true` if mouse-in-pointer mode is
+    /// enabled, either by this call or by someone else before us.
+    pub fn enable_mouse_in_pointer(&self) -> bool {
+        unsafe {
+            if let Some(is_enabled) = self.is_mouse_in_pointer_enabled
+                && is_enabled() != 0
+            {
+                return true;
+            }
+
+            match self.enable_mouse_in_pointer {
+                Some(enable) => enable(1) != 0,
+                None => false,
+            }
+        }
+    }
+
+    /// Gets the type of the given pointer, if available.
+    pub fn pointer_type(&self, pointer_id: u32) -> Option<POINTER_INPUT_TYPE> {
+        unsafe {
+            let get_pointer_type = self.get_pointer_type?;
+            let mut pointer_type = 0;
+            (get_pointer_type(pointer_id, &mut pointer_type) != 0).then_some(pointer_type)
+        }
+    }
+
+    /// Gets the current state of the given pointer, if available.
+    pub fn pointer_info(&self, pointer_id: u32) -> Option<POINTER_INFO> {
+        unsafe {
+            let get_pointer_info = self.get_pointer_info?;
+            let mut info: POINTER_INFO = zeroed();
+            (get_pointer_info(pointer_id, &mut info) != 0).then_some(info)
+        }
+    }
+
+    /// Is the given pen pointer used with its eraser end?
+    pub fn is_pen_inverted(&self, pointer_id: u32) -> bool {
+        unsafe {
+            let Some(get_pointer_pen_info) = self.get_pointer_pen_info else {
+                return false;
+            };
+
+            let mut info: POINTER_PEN_INFO = zeroed();
+            get_pointer_pen_info(pointer_id, &mut info) != 0
+                && info.penFlags & (PEN_FLAG_INVERTED | PEN_FLAG_ERASER) != 0
+        }
+    }
+}
+
+impl Drop for PointerContext {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.user32.is_null() {
+                FreeLibrary(self.user32);
+            }
+        }
+    }
+}
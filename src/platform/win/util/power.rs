@@ -0,0 +1,67 @@
+use windows_sys::Win32::Foundation::{FreeLibrary, HMODULE, HWND};
+use windows_sys::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryA};
+use windows_sys::Win32::UI::WindowsAndMessaging::DEVICE_NOTIFY_WINDOW_HANDLE;
+use windows_sys::core::BOOL;
+
+/// A registration for `WM_POWERBROADCAST` suspend and resume messages.
+///
+/// Windows only broadcasts those to top-level windows, child windows (like
+/// embedded editors) have to ask for them with
+/// `RegisterSuspendResumeNotification`. That function is only available
+/// starting with Windows 8, so it is loaded at runtime, on older versions
+/// this does nothing. Unregisters when dropped.
+#[derive(Default)]
+pub struct ResumeNotification {
+    user32: HMODULE,
+    handle: isize,
+    unregister: Option<unsafe extern "system" fn(isize) -> BOOL>,
+}
+
+impl ResumeNotification {
+    /// Registers the window for suspend and resume messages.
+    ///
+    /// # Safety
+    /// - The `hwnd` must be a valid window handle for the lifetime of the
+    ///   registration.
+    pub unsafe fn register(hwnd: HWND) -> Self {
+        unsafe {
+            let user32 = LoadLibraryA(c"user32.dll".as_ptr() as *const _);
+            if user32.is_null() {
+                return Self::default();
+            }
+
+            let register: Option<unsafe extern "system" fn(HWND, u32) -> isize> = GetProcAddress(
+                user32,
+                c"RegisterSuspendResumeNotification".as_ptr() as *const _,
+            )
+            .map(|x| std::mem::transmute_copy(&x));
+            let unregister: Option<unsafe extern "system" fn(isize) -> BOOL> = GetProcAddress(
+                user32,
+                c"UnregisterSuspendResumeNotification".as_ptr() as *const _,
+            )
+            .map(|x| std::mem::transmute_copy(&x));
+
+            Self {
+                user32,
+                handle: register.map_or(0, |register| register(hwnd, DEVICE_NOTIFY_WINDOW_HANDLE)),
+                unregister,
+            }
+        }
+    }
+}
+
+impl Drop for ResumeNotification {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(unregister) = self.unregister
+                && self.handle != 0
+            {
+                unregister(self.handle);
+            }
+
+            if !self.user32.is_null() {
+                FreeLibrary(self.user32);
+            }
+        }
+    }
+}
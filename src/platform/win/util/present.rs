@@ -0,0 +1,128 @@
+use crate::Size;
+use crate::platform::win::util::error::Win32Error;
+use crate::platform::{Damage, copy_pixels};
+use std::ptr::null_mut;
+use std::slice::from_raw_parts_mut;
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::Graphics::Gdi::{
+    BI_RGB, BITMAPINFO, BITMAPINFOHEADER, BitBlt, CreateCompatibleDC, CreateDIBSection,
+    DIB_RGB_COLORS, DeleteDC, DeleteObject, GdiFlush, GetDC, HBITMAP, HDC, HGDIOBJ, ReleaseDC,
+    SRCCOPY, SelectObject,
+};
+
+/// A DIB section the pixels are staged in before they are blitted onto the
+/// window, see [`Window::present_pixels`](crate::Window::present_pixels).
+///
+/// Only the damaged regions are copied into it and blitted, so it has to be
+/// as big as the buffer, and is recreated whenever the buffer size changes.
+pub struct DibSection {
+    dc: HDC,
+    bitmap: HBITMAP,
+    previous: HGDIOBJ,
+    bits: *mut u32,
+    size: Size,
+}
+
+impl DibSection {
+    /// Create a DIB section of the given size, with 32-bit `0xAARRGGBB`
+    /// pixels stored from the top row to the bottom one.
+    pub fn new(size: Size) -> Result<Self, Win32Error> {
+        unsafe {
+            let dc = CreateCompatibleDC(null_mut());
+            if dc.is_null() {
+                return Err(Win32Error::last_error().with_context("CreateCompatibleDC"));
+            }
+
+            let info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: size.width.try_into().unwrap_or(i32::MAX),
+                    // negative for rows from top to bottom
+                    biHeight: -i32::try_from(size.height).unwrap_or(i32::MAX),
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+
+            let mut bits = null_mut();
+            let bitmap = CreateDIBSection(dc, &info, DIB_RGB_COLORS, &mut bits, null_mut(), 0);
+            if bitmap.is_null() || bits.is_null() {
+                let error = Win32Error::last_error().with_context("CreateDIBSection");
+                DeleteDC(dc);
+                return Err(error);
+            }
+
+            Ok(Self {
+                dc,
+                bitmap,
+                previous: SelectObject(dc, bitmap),
+                bits: bits as *mut u32,
+                size,
+            })
+        }
+    }
+
+    /// The size the section was created with.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Copy the `damage` regions of `buffer`, which has the size of the
+    /// section, onto the window at the same position.
+    ///
+    /// # Safety
+    /// - The `hwnd` must be a valid window handle.
+    pub unsafe fn present(
+        &self,
+        hwnd: HWND,
+        buffer: &[u32],
+        damage: Damage<'_>,
+    ) -> Result<(), Win32Error> {
+        unsafe {
+            // GDI batches calls, the last blit may still be reading the section
+            GdiFlush();
+
+            let pixels = self.size.width as usize * self.size.height as usize;
+            let bits = from_raw_parts_mut(self.bits, pixels);
+            for rect in damage.iter() {
+                copy_pixels(buffer, self.size, bits, self.size, rect);
+            }
+
+            let hdc = GetDC(hwnd);
+            if hdc.is_null() {
+                return Err(Win32Error::last_error().with_context("GetDC"));
+            }
+
+            for rect in damage.iter() {
+                let Size { width, height } = rect.size();
+                BitBlt(
+                    hdc,
+                    rect.left,
+                    rect.top,
+                    width as i32,
+                    height as i32,
+                    self.dc,
+                    rect.left,
+                    rect.top,
+                    SRCCOPY,
+                );
+            }
+
+            ReleaseDC(hwnd, hdc);
+            Ok(())
+        }
+    }
+}
+
+impl Drop for DibSection {
+    fn drop(&mut self) {
+        unsafe {
+            SelectObject(self.dc, self.previous);
+            DeleteObject(self.bitmap);
+            DeleteDC(self.dc);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::OpenUrlError;
+use crate::platform::win::util::error::Win32Error;
+use crate::platform::win::util::ole::OleApartment;
+use crate::platform::win::util::widestr::WideString;
+use std::ptr::{null, null_mut};
+use windows_sys::Win32::UI::Shell::{
+    SE_ERR_ASSOCINCOMPLETE, SE_ERR_FNF, SE_ERR_NOASSOC, SE_ERR_PNF, ShellExecuteW,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::SW_SHOWDEFAULT;
+
+/// Open the given URL with the default system handler. Blocks until the
+/// handler was started, so it should run on a worker thread.
+pub fn open_url(url: &str) -> Result<(), OpenUrlError> {
+    // shell extensions that handle the url may rely on COM
+    let _apartment = OleApartment::enter();
+
+    let path = WideString::from(url);
+    let verb = WideString::from("open");
+
+    let result = unsafe {
+        ShellExecuteW(
+            null_mut(),
+            verb.as_ptr(),
+            path.as_ptr(),
+            null(),
+            null(),
+            SW_SHOWDEFAULT,
+        ) as usize
+    };
+
+    // anything above 32 is success, the rest are error codes
+    if result > 32 {
+        return Ok(());
+    }
+
+    match result as u32 {
+        SE_ERR_NOASSOC | SE_ERR_ASSOCINCOMPLETE => Err(OpenUrlError::NoHandler),
+        SE_ERR_FNF | SE_ERR_PNF => Err(OpenUrlError::NotFound),
+        code => Err(OpenUrlError::Platform(
+            Win32Error {
+                code,
+                context: Some("ShellExecuteW".into()),
+            }
+            .to_string(),
+        )),
+    }
+}
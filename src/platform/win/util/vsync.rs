@@ -1,22 +1,43 @@
+use std::ptr::null_mut;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::{JoinHandle, sleep};
 use std::time::{Duration, Instant};
 use windows_sys::Win32::Foundation::HWND;
-use windows_sys::Win32::Graphics::Dwm::{DwmFlush, DwmIsCompositionEnabled};
+use windows_sys::Win32::Graphics::Dwm::{
+    DWM_TIMING_INFO, DwmFlush, DwmGetCompositionTimingInfo, DwmIsCompositionEnabled,
+};
 use windows_sys::Win32::Graphics::Gdi::{
     DEVMODEW, ENUM_CURRENT_SETTINGS, EnumDisplaySettingsW, GetMonitorInfoW,
     MONITOR_DEFAULTTOPRIMARY, MONITORINFOEXW, MonitorFromWindow,
 };
-use windows_sys::Win32::UI::WindowsAndMessaging::SendNotifyMessageW;
+use windows_sys::Win32::UI::WindowsAndMessaging::{PostMessageW, SendNotifyMessageW};
+
+use crate::platform::win::window::{WM_USER_FRAME_PACING, WM_USER_VSYNC};
 
-use crate::platform::win::window::WM_USER_VSYNC;
+/// How many [`DwmFlush`] calls in a row may return right away before we stop
+/// trusting it to wait for the vertical blank.
+const MAX_INSTANT_FLUSHES: u32 = 8;
+
+/// How much faster than the monitor (in Hz) the compositor has to run before
+/// we skip its blanks. The monitor rate is rounded down to a whole number
+/// (59 for a 59.94 Hz panel), so a compositor at the same rate can look up to
+/// a hertz faster.
+const SKIP_BLANKS_THRESHOLD: f64 = 2.0;
 
 /// A thread that waits for VSync blanks and sends a message to the window.
 ///
 /// Uses DWM flush ([`DwmFlush`]) if available, otherwise falls back to a timer
 /// based on the refresh rate of the monitor (queried using
-/// [`GetMonitorInfoW`] and [`EnumDisplaySettingsW`]).
+/// [`GetMonitorInfoW`] and [`EnumDisplaySettingsW`]). DWM flush is also
+/// abandoned if it fails or keeps returning without waiting (remote desktop
+/// sessions, odd drivers), until the display changes. Switching is reported
+/// with [`WM_USER_FRAME_PACING`].
+///
+/// Every window has its own thread. DWM flush follows the composition rate of
+/// the desktop, which can be faster than the monitor the window is on. If it
+/// clearly is (queried with [`DwmGetCompositionTimingInfo`]), blanks are
+/// skipped to stay at the refresh rate of the window's monitor.
 pub struct VSyncThread {
     inner: Arc<Inner>,
     thread: Option<JoinHandle<()>>,
@@ -55,6 +76,15 @@ impl VSyncThread {
             .store(true, Ordering::Relaxed);
     }
 
+    /// Notifies the thread that the system resumed from sleep. The last frame
+    /// message may have been lost while suspended, which would keep the thread
+    /// waiting for it forever, and DWM gets another chance like on a display
+    /// change.
+    pub fn notify_resume(&self) {
+        self.notify_display_change();
+        self.notify_frame_finished();
+    }
+
     /// Notifies the thread that the frame has finished and we are ready
     /// for the next frame.
     pub fn notify_frame_finished(&self) {
@@ -100,19 +130,62 @@ impl Inner {
             let hwnd = self.hwnd as HWND;
             let mut fallback_next_frame = Instant::now();
             let mut fallback_interval = Duration::from_millis(15);
+            let mut vsync_next_frame = Instant::now();
+            let mut skip_blanks = false;
+
+            let mut use_fallback = false;
+            let mut reported_fallback = false;
+            let mut instant_flushes = 0;
 
             while !self.notify_thread_destroy.load(Ordering::Relaxed) {
                 if self.notify_display_change.swap(false, Ordering::Relaxed) {
-                    fallback_interval = Duration::from_secs_f32(
-                        1.0 / get_refresh_rate(MonitorFromWindow(hwnd, MONITOR_DEFAULTTOPRIMARY))
-                            .unwrap_or(60) as f32,
-                    );
+                    let refresh_rate = get_refresh_rate(hwnd);
+                    fallback_interval =
+                        Duration::from_secs_f32(1.0 / refresh_rate.unwrap_or(60) as f32);
+
+                    // the monitor rate is too coarse to keep a grid in step with a compositor
+                    // running at that same rate, only skip when it is clearly faster
+                    skip_blanks = match (refresh_rate, get_composition_rate()) {
+                        (Some(monitor), Some(composition)) => {
+                            composition > monitor as f64 + SKIP_BLANKS_THRESHOLD
+                        }
+                        _ => false,
+                    };
+
+                    // new display, give vsync another chance
+                    use_fallback = false;
+                    instant_flushes = 0;
                 };
 
-                if !wait_dwm_flush() {
+                if !use_fallback {
+                    let start = Instant::now();
+                    if !wait_dwm_flush() {
+                        use_fallback = true;
+                    } else if start.elapsed() < fallback_interval / 4 {
+                        // did not wait for anything, would spin the cpu if it keeps doing that
+                        instant_flushes += 1;
+                        use_fallback = instant_flushes >= MAX_INSTANT_FLUSHES;
+                    } else {
+                        instant_flushes = 0;
+                    }
+                }
+
+                if use_fallback {
                     wait_fallback(&mut fallback_next_frame, fallback_interval);
                 }
 
+                if reported_fallback != use_fallback {
+                    reported_fallback = use_fallback;
+                    PostMessageW(hwnd, WM_USER_FRAME_PACING, use_fallback as usize, 0);
+                }
+
+                if !use_fallback
+                    && skip_blanks
+                    && !is_frame_due(&mut vsync_next_frame, fallback_interval)
+                {
+                    continue;
+                }
+
                 // this is so we do not get overlapping messages if the window is too slow to
                 // process them (otherwise we would enter a death spiral of sending more
                 // messages than we can process)
@@ -146,7 +219,22 @@ fn wait_dwm_flush() -> bool {
     }
 }
 
-/// Waits for the next VSync blank using a timer.
+/// Checks whether a blank of the compositor should produce a frame for a
+/// monitor with the given refresh interval. Frames are kept on a grid of that
+/// interval, with a quarter of it as slack for blanks that arrive early.
+fn is_frame_due(next_frame: &mut Instant, interval: Duration) -> bool {
+    let curr_frame = Instant::now();
+    if curr_frame + interval / 4 < *next_frame {
+        return false;
+    }
+
+    *next_frame = (*next_frame + interval).max(curr_frame);
+    true
+}
+
+/// Waits for the next VSync blank using a timer. Frames are scheduled on a
+/// fixed grid so sleep overshoot does not add up, unless we fell behind by
+/// more than a frame, then the grid restarts from now.
 fn wait_fallback(next_frame: &mut Instant, interval: Duration) {
     let curr_frame = Instant::now();
     let wait_time = next_frame.checked_duration_since(curr_frame);
@@ -157,6 +245,29 @@ fn wait_fallback(next_frame: &mut Instant, interval: Duration) {
     }
 }
 
+/// Returns the rate DWM composes the desktop at, in Hz, or None if it could not
+/// be determined.
+fn get_composition_rate() -> Option<f64> {
+    unsafe {
+        let mut info = DWM_TIMING_INFO {
+            cbSize: size_of::<DWM_TIMING_INFO>() as _,
+            ..Default::default()
+        };
+
+        // must be null since windows 8.1, the timing is the same for all windows
+        if DwmGetCompositionTimingInfo(null_mut(), &mut info) != 0 {
+            return None;
+        }
+
+        let rate = info.rateRefresh;
+        if rate.uiNumerator == 0 || rate.uiDenominator == 0 {
+            return None;
+        }
+
+        Some(rate.uiNumerator as f64 / rate.uiDenominator as f64)
+    }
+}
+
 /// Returns the refresh rate of the monitor the window is currently in, in Hz,
 /// or None if it could not be determined. This is used to determine the
 /// fallback interval for VSync when DWM is not available (should be rare, but
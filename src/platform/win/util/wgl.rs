@@ -320,7 +320,7 @@ impl WglExtensions {
         unsafe {
             let mut result = WglExtensions::default();
 
-            let _ = create_window::<(), Win32Error>(0, null_mut(), |hwnd| {
+            let _ = create_window::<(), Win32Error>(0, 0, None, null_mut(), |hwnd| {
                 let hdc = GetDC(hwnd);
                 let pfd = PIXELFORMATDESCRIPTOR {
                     nSize: std::mem::size_of::<PIXELFORMATDESCRIPTOR>() as u16,
@@ -2,13 +2,15 @@ use crate::platform::win::util::error::Win32Error;
 use crate::platform::win::util::widestr::WideString;
 use std::ptr::null_mut;
 use std::rc::Rc;
-use windows_sys::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::Foundation::{
+    ERROR_CLASS_ALREADY_EXISTS, HINSTANCE, HWND, LPARAM, LRESULT, WPARAM,
+};
 use windows_sys::Win32::System::Com::CoCreateGuid;
 use windows_sys::Win32::System::SystemServices::IMAGE_DOS_HEADER;
 use windows_sys::Win32::UI::WindowsAndMessaging::{
     CW_USEDEFAULT, CreateWindowExW, DefWindowProcW, DestroyWindow, GCW_ATOM, GWLP_USERDATA,
     GetClassLongW, GetWindowLongPtrW, IDC_ARROW, LoadCursorW, RegisterClassW, SetWindowLongPtrW,
-    UnregisterClassW, WINDOW_STYLE, WM_DESTROY, WNDCLASSW,
+    UnregisterClassW, WINDOW_EX_STYLE, WINDOW_STYLE, WM_DESTROY, WNDCLASSW,
 };
 use windows_sys::core::GUID;
 
@@ -33,8 +35,9 @@ pub fn hinstance() -> HINSTANCE {
     unsafe { &__ImageBase as *const IMAGE_DOS_HEADER as _ }
 }
 
-/// Creates a new window with the given style and parent, and calls the provided
-/// closure to create the window handler.
+/// Creates a new window with the given styles and parent, and calls the
+/// provided closure to create the window handler. The window class is named
+/// `class_name`, or gets a unique name if `None`.
 ///
 /// The handler will be dropped when the window itself is destroyed
 /// (`WM_DESTROY`).
@@ -44,6 +47,8 @@ pub fn hinstance() -> HINSTANCE {
 ///   null.
 pub unsafe fn create_window<W: WindowProc, E: From<Win32Error>>(
     dwstyle: WINDOW_STYLE,
+    dwexstyle: WINDOW_EX_STYLE,
+    class_name: Option<&str>,
     parent: HWND,
     f: impl FnOnce(HWND) -> Result<Rc<W>, E>,
 ) -> Result<Rc<W>, E> {
@@ -90,8 +95,12 @@ pub unsafe fn create_window<W: WindowProc, E: From<Win32Error>>(
         // our hinstance, its always the same
         let hinstance = hinstance();
 
-        // unique class name to avoid conflicts with other windows
-        let class_name = WideString::from(format!("picoview-{}", generate_guid()).as_str());
+        // unique class name to avoid conflicts with other windows, unless asked for one
+        let class_name = match class_name {
+            Some(class_name) => WideString::from(class_name),
+            None => WideString::from(format!("picoview-{}", generate_guid()).as_str()),
+        };
+
         let window_class = RegisterClassW(&WNDCLASSW {
             style: 0,
             lpfnWndProc: Some(wnd_proc::<W>),
@@ -105,17 +114,19 @@ pub unsafe fn create_window<W: WindowProc, E: From<Win32Error>>(
             lpszClassName: class_name.as_ptr(),
         });
 
-        // if failed, return an error
+        // if failed, return an error. a named class may still be registered by another
+        // window, which is fine, it is the same window proc
         if window_class == 0 {
-            return Err(Win32Error::last_error()
-                .with_context("RegisterClassW")
-                .into());
+            let error = Win32Error::last_error();
+            if error.code != ERROR_CLASS_ALREADY_EXISTS {
+                return Err(error.with_context("RegisterClassW").into());
+            }
         }
 
-        // new zero size zero style window (we can resize & set it later)
+        // new zero size window (we can resize & set it later)
         let window_hwnd = CreateWindowExW(
-            0,
-            window_class as _,
+            dwexstyle,
+            class_name.as_ptr(),
             [0].as_ptr() as _,
             dwstyle,
             CW_USEDEFAULT,
@@ -130,7 +141,7 @@ pub unsafe fn create_window<W: WindowProc, E: From<Win32Error>>(
 
         // if failed, unregister the class and return an error
         if window_hwnd.is_null() {
-            UnregisterClassW(window_class as _, hinstance);
+            UnregisterClassW(class_name.as_ptr(), hinstance);
             return Err(Win32Error::last_error()
                 .with_context("CreateWindowExW")
                 .into());
@@ -144,7 +155,7 @@ pub unsafe fn create_window<W: WindowProc, E: From<Win32Error>>(
             Err(e) => {
                 // initialization failed, cleanup and return the error
                 DestroyWindow(window_hwnd);
-                UnregisterClassW(window_class as _, hinstance);
+                UnregisterClassW(class_name.as_ptr(), hinstance);
                 return Err(e);
             }
         };
@@ -159,7 +170,7 @@ pub unsafe fn create_window<W: WindowProc, E: From<Win32Error>>(
         // SetWindowLongPtrW failed?
         if result != 0 {
             DestroyWindow(window_hwnd);
-            UnregisterClassW(window_class as _, hinstance);
+            UnregisterClassW(class_name.as_ptr(), hinstance);
             return Err(Win32Error::last_error()
                 .with_context("SetWindowLongPtrW")
                 .into());
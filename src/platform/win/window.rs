@@ -1,45 +1,69 @@
+#[cfg(feature = "opengl")]
 use super::gl::GlContext;
-use crate::platform::win::dnd::DropTargetImpl;
+use crate::platform::win::dnd::{DropSourceImpl, DropTargetImpl};
+use crate::platform::win::event_loop;
+use crate::platform::win::util::color::query_color_profile;
 use crate::platform::win::util::cursor::WinCursor;
 use crate::platform::win::util::dpi::DpiContext;
 use crate::platform::win::util::error::Win32Error;
 use crate::platform::win::util::exchange::{
-    Clipboard, decode_hdrop, encode_drop_effect, encode_hdrop,
+    Clipboard, decode_dib, decode_hdrop, encode_dib, encode_drop_effect, encode_hdrop,
+    register_format,
 };
-use crate::platform::win::util::keyboard::{KeyboardHook, query_modifiers, scan_code_to_key};
+use crate::platform::win::util::ime::{
+    composition_result, composition_string, set_ime_cursor_area,
+};
+use crate::platform::win::util::keyboard::{
+    KeyboardHook, query_keys_down, query_modifiers, scan_code_to_key, virtual_key_to_char,
+};
+use crate::platform::win::util::ole::OleApartment;
+use crate::platform::win::util::pointer::{
+    PointerContext, point_from_lparam, query_scroll_settings,
+};
+use crate::platform::win::util::power::ResumeNotification;
+use crate::platform::win::util::present::DibSection;
+use crate::platform::win::util::shell::open_url;
 use crate::platform::win::util::vsync::VSyncThread;
 use crate::platform::win::util::widestr::WideString;
 use crate::platform::win::util::window::{WindowProc, create_window, hinstance};
 use crate::platform::*;
 use raw_window_handle::RawWindowHandle;
+use std::any::{Any, TypeId};
 use std::cell::{Cell, RefCell};
-use std::collections::VecDeque;
+use std::ffi::c_void;
 use std::mem::{size_of, zeroed};
 use std::num::NonZeroIsize;
-use std::ptr::{null, null_mut};
+use std::ptr::{NonNull, null, null_mut};
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
-use windows_sys::Win32::Foundation::{
-    HWND, LPARAM, LRESULT, OLE_E_WRONGCOMPOBJ, POINT, RECT, RPC_E_CHANGED_MODE, WPARAM,
-};
+use std::time::{Duration, Instant};
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, WPARAM};
 use windows_sys::Win32::Graphics::Dwm::{
     DWM_BB_BLURREGION, DWM_BB_ENABLE, DWM_BLURBEHIND, DwmEnableBlurBehindWindow,
 };
 use windows_sys::Win32::Graphics::Gdi::{
-    ClientToScreen, CreateRectRgn, DeleteObject, GetUpdateRect, ScreenToClient, ValidateRgn,
+    ClientToScreen, CreateRectRgn, DeleteObject, GetMonitorInfoW, GetUpdateRect, HMONITOR,
+    MONITOR_DEFAULTTONEAREST, MONITOR_DEFAULTTOPRIMARY, MONITORINFO, MonitorFromPoint,
+    MonitorFromWindow, ScreenToClient, ValidateRgn,
 };
 use windows_sys::Win32::System::Ole::{
-    CF_HDROP, CF_UNICODETEXT, OleInitialize, RegisterDragDrop, RevokeDragDrop,
+    CF_DIBV5, CF_HDROP, CF_UNICODETEXT, RegisterDragDrop, RevokeDragDrop,
 };
+use windows_sys::Win32::System::Threading::GetCurrentThreadId;
 use windows_sys::Win32::UI::Controls::WM_MOUSELEAVE;
+use windows_sys::Win32::UI::Input::Ime::{GCS_COMPSTR, GCS_RESULTSTR, ISC_SHOWUICOMPOSITIONWINDOW};
 use windows_sys::Win32::UI::Input::KeyboardAndMouse::*;
-use windows_sys::Win32::UI::Shell::ShellExecuteW;
+use windows_sys::Win32::UI::Input::Pointer::*;
+use windows_sys::Win32::UI::Shell::{
+    DefSubclassProc, DragAcceptFiles, DragFinish, DragQueryPoint, HDROP, RemoveWindowSubclass,
+    SetWindowSubclass,
+};
 use windows_sys::Win32::UI::WindowsAndMessaging::*;
 
 /// Sent by Vsync thread, triggers [`WindowHandler::frame`] event
 pub const WM_USER_VSYNC: u32 = WM_USER + 1;
-/// Sent by [`PlatformWindow::close`] and received in the wnd_proc, closes the
-/// window
+/// Sent by [`PlatformWindow::close`] and [`PlatformWaker::close`], received in
+/// the wnd_proc, closes the window
 pub const WM_USER_CLOSE_WINDOW: u32 = WM_USER + 2;
 /// Sent by the [`KeyboardHook`] when a key event is captured
 /// Same wParam/lParam data as in native WM_KEYDOWN/WM_KEYUP messages
@@ -63,6 +87,19 @@ pub const WM_USER_DND_LEAVE: u32 = WM_USER + 9;
 /// Sent by [`DropTargetImpl`] when a drop is performed, triggers
 /// [`WindowHandler::drag_accept`] event.
 pub const WM_USER_DND_ACCEPT: u32 = WM_USER + 10;
+/// Sent by the [`VSyncThread`] when it switches between vsync and the fallback
+/// timer, wParam is 1 for the timer. Triggers
+/// [`WindowHandler::frame_pacing_changed`] event.
+pub const WM_USER_FRAME_PACING: u32 = WM_USER + 11;
+/// Posted by [`PlatformWindow::start_drag`], runs the modal drag and drop loop
+/// once the handler returned. Triggers [`WindowHandler::drag_finished`] event.
+pub const WM_USER_DRAG_START: u32 = WM_USER + 12;
+/// Posted by [`PlatformWindow::begin_drag_resize`], runs the modal sizing loop
+/// once the handler returned. wParam is the hit test code of the edge.
+pub const WM_USER_RESIZE_START: u32 = WM_USER + 13;
+/// Sent by [`owner_subclass_proc`] when the owner window moved, moves the
+/// window along with it. See [`WindowBuilder::with_follow_parent`].
+pub const WM_USER_OWNER_MOVED: u32 = WM_USER + 14;
 
 /// A Win32 implementation of a [`PlatformWindow`].
 pub struct WindowImpl {
@@ -71,9 +108,25 @@ pub struct WindowImpl {
     waker: Arc<WindowWakerImpl>,
     /// Current OpenGL context for this window, if requested. Or an error if the
     /// context could not be created.
+    #[cfg(feature = "opengl")]
     gl_context: Result<GlContext, OpenGlError>,
+    /// See [`WindowBuilder::with_external_opengl`].
+    #[cfg(feature = "opengl")]
+    external_gl_context: Option<ExternalGlContext>,
     /// Dynamically loaded DPI management functions, used for HiDPI support.
     dpi_context: DpiContext,
+    /// Dynamically loaded pointer input functions, used for pen events and
+    /// for the `WM_POINTER` input path.
+    pointer_context: PointerContext,
+    /// Is mouse input routed through `WM_POINTER` messages? See
+    /// [`WindowBuilder::with_pointer_input`].
+    pointer_input: bool,
+    /// See [`WindowBuilder::with_raw_scroll`].
+    raw_scroll: bool,
+    /// See [`WindowBuilder::with_unmatched_mouse_release`].
+    unmatched_mouse_release: bool,
+    /// See [`WindowBuilder::with_auto_dpi_resize`].
+    auto_dpi_resize: bool,
     /// Thread that waits for VSync blanks and sends a message to the window to
     /// trigger [`WindowHandler::frame`] event.
     vsync_thread: VSyncThread,
@@ -82,11 +135,29 @@ pub struct WindowImpl {
     _drop_target: Arc<DropTargetImpl>,
     /// Thread-local keyboard hook for this window.
     _keyboard_hook: KeyboardHook,
+    /// Asks for resume messages, which are only broadcast to top-level
+    /// windows
+    _resume_notification: ResumeNotification,
 
     /// The HWND for this window
     hwnd: HWND,
+    /// Registers the window for [`crate::window_at`], provides
+    /// [`PlatformWindow::id`].
+    entry: WindowEntry,
+    /// The [`WindowGroup`] this window is a member of, if any
+    group: Option<WindowGroup>,
     /// The mode in which the window was opened
     open_mode: OpenMode,
+    /// The top-level owner window the window moves along with, null if it
+    /// does not. See [`WindowBuilder::with_follow_parent`].
+    follow_owner: HWND,
+    /// The top-level ancestor of an embedded window, subclassed to forward
+    /// `WM_SETTINGCHANGE` (which only top-level windows get). Null if there is
+    /// none, or it belongs to another thread.
+    settings_owner: HWND,
+    /// The last position of [`Self::follow_owner`], the window moves by the
+    /// same distance when it changes.
+    last_owner_position: Cell<(i32, i32)>,
 
     /// Windows API is inherently reentrant, so we have to make sure that we
     /// don't call the event handler while it is already borrowed (otherwise
@@ -98,10 +169,29 @@ pub struct WindowImpl {
     /// Same queue is used to defer events that are sent while the event handler
     /// is being initialized, so that we can send events to the handler as
     /// soon as it is ready.
+    ///
+    /// Bounded, see [`EventQueue`].
     #[allow(clippy::type_complexity)]
-    event_deferred: RefCell<VecDeque<Box<dyn FnOnce(&Self, &mut dyn WindowHandler)>>>,
+    event_deferred: EventQueue<dyn FnOnce(&Self, &mut dyn WindowHandler)>,
     /// The event handler for this window, processes our events.
     event_handler: RefCell<Option<Box<dyn WindowHandler>>>,
+    /// Data attached with [`Window::set_user_data`], dropped after the handler.
+    user_data: RefCell<Option<Rc<dyn Any>>>,
+    /// Measures the time spent in the event handler, see
+    /// [`WindowBuilder::with_watchdog`].
+    watchdog: Watchdog,
+    /// See [`Window::frame_jitter`].
+    frame_jitter: FrameJitter,
+    /// See [`Window::is_key_down`].
+    keys_down: KeyState,
+    /// Set by [`Window::redraw_now`] while the handler is busy, the frame is
+    /// dispatched once the current event returns.
+    redraw_pending: Cell<bool>,
+    /// Set once the window is asked to close, anything else destroying it
+    /// (like the parent window going away) is reported as
+    /// [`DestroyReason::Destroyed`]. No events are dispatched after this is
+    /// set.
+    is_closing: Cell<bool>,
 
     /// The last size of the window, used to detect size changes
     current_window_size: Cell<Size>,
@@ -111,12 +201,20 @@ pub struct WindowImpl {
     ///
     /// Stored as (DW_STYLE, DW_EXSTYLE)
     current_window_style: Cell<(u32, u32)>,
+    /// The styles asked for with [`crate::PlatformOptions::windows_style`], kept when
+    /// the decorations are removed.
+    extra_window_style: u32,
     /// The last window visibility state.
     current_window_visibility: Cell<WindowVisibility>,
-    /// The current maximum size of the window, used to enforce size constraints
+    /// The current maximum client size of the window, used to enforce size
+    /// constraints
     current_max_window_size: Cell<Size>,
-    /// The current minimum size of the window, used to enforce size constraints
+    /// The current minimum client size of the window, used to enforce size
+    /// constraints
     current_min_window_size: Cell<Size>,
+    /// The current resize constraints of the window, see
+    /// [`PlatformWindow::set_resize_hints`]
+    current_resize_hints: Cell<ResizeHints>,
     /// The current focus state of the window, used to detect focus changes
     current_window_focused: Cell<bool>,
     /// The current modifiers state of the window, used to detect modifier
@@ -127,10 +225,52 @@ pub struct WindowImpl {
     /// The number of mouse button pressed - mouse button releases, used for
     /// automatic cursor capture and release.
     current_mouse_capture: Cell<u32>,
+    /// The mouse buttons pressed inside of the window and not yet released,
+    /// as a mask of `1 << button`.
+    current_buttons_held: Cell<u32>,
     /// The current mouse position of the window, used to detect mouse movement
     current_mouse_position: Cell<Option<Point>>,
+    /// The current cursor grab of the window, see
+    /// [`PlatformWindow::set_cursor_grab`]. The cursor is clipped to the
+    /// client area unless it is [`CursorGrab::None`].
+    current_cursor_grab: Cell<CursorGrab>,
+    /// While the cursor is locked, the point it is moved back to and the
+    /// virtual position reported instead of the real one.
+    current_cursor_lock: Cell<Option<(Point, Point)>>,
     /// The current system scale for the window (in DPI).
     current_dpi_scale: Cell<u32>,
+    /// The monitor the window was last seen on, null until the first check.
+    current_monitor: Cell<HMONITOR>,
+    /// The ICC profile of [`Self::current_monitor`], read again when the
+    /// window changes monitors or the display settings change.
+    current_color_profile: RefCell<Option<IccProfile>>,
+    /// The time of the last input message, see
+    /// [`PlatformWindow::last_event_token`]
+    current_event_token: Cell<EventToken>,
+    /// The area set with [`Window::set_ime_cursor_area`], applied again every
+    /// time a composition starts since the input context may have changed
+    current_ime_cursor_area: Cell<Option<Rect>>,
+    /// Is a composition in progress, between [`WindowHandler::composition_start`]
+    /// and [`WindowHandler::composition_end`]?
+    is_composing: Cell<bool>,
+    /// The data passed to [`PlatformWindow::start_drag`], waiting for
+    /// [`WM_USER_DRAG_START`]
+    drag_pending: RefCell<Option<Exchange>>,
+    /// Are we inside of the modal drag and drop loop?
+    is_dragging: Cell<bool>,
+    /// The edge, the cursor position and the window rectangle a resize of a
+    /// window without a sizing border started with, see
+    /// [`PlatformWindow::begin_drag_resize`]. `DefWindowProc` only runs its
+    /// sizing loop with `WS_THICKFRAME`, so the window follows the mouse
+    /// until the release.
+    resize_drag: Cell<Option<(ResizeEdge, POINT, RECT)>>,
+    /// Where [`PlatformWindow::present_pixels`] stages the pixels, created
+    /// on first use
+    dib_section: RefCell<Option<DibSection>>,
+
+    /// Keeps OLE initialized for drag and drop, if it could be. Declared last,
+    /// so it is released after everything else (fields are dropped in order).
+    ole_apartment: Option<OleApartment>,
 }
 
 /// Win32 implementation of a [`PlatformWaker`].
@@ -145,12 +285,43 @@ unsafe impl Send for WindowWakerImpl {}
 unsafe impl Sync for WindowWakerImpl {}
 
 impl WindowImpl {
+    /// See [`crate::window_at`]. The point hits the deepest child window, so
+    /// walk up until we find one of ours.
+    pub fn window_at(point: Point) -> Option<WindowId> {
+        unsafe {
+            // physical pixels, regardless of the awareness of the calling thread
+            let dpi_context = DpiContext::new();
+            let _dpi_awareness = dpi_context.enter_per_monitor_aware_v2();
+
+            let (x, y) = point.to_pixels();
+            let mut hwnd = WindowFromPoint(POINT { x, y });
+
+            while !hwnd.is_null() {
+                if let Some(id) = WindowEntry::find(hwnd as usize) {
+                    return Some(id);
+                }
+
+                hwnd = GetAncestor(hwnd, GA_PARENT);
+            }
+
+            None
+        }
+    }
+
     pub unsafe fn open(options: WindowBuilder, mode: OpenMode) -> Result<WindowWaker, WindowError> {
         unsafe {
+            // the top-level window of the group leader, top-level windows in the group are
+            // owned by it
+            let group_leader = match options.group.as_ref().and_then(|group| group.leader()) {
+                Some(RawWindowHandle::Win32(window)) => window.hwnd.get() as HWND,
+                _ => null_mut(),
+            };
+
             let parent = match mode {
-                OpenMode::Blocking => null_mut(),
+                OpenMode::TopLevel => group_leader,
                 OpenMode::Embedded(RawWindowHandle::Win32(window)) => window.hwnd.get() as HWND,
                 OpenMode::Transient(RawWindowHandle::Win32(window)) => window.hwnd.get() as HWND,
+                OpenMode::Popup(RawWindowHandle::Win32(window), _) => window.hwnd.get() as HWND,
                 _ => return Err(WindowError::InvalidParent),
             };
 
@@ -158,22 +329,31 @@ impl WindowImpl {
                 let mut dwstyle = 0;
 
                 match mode {
-                    OpenMode::Blocking | OpenMode::Transient(..) => {
+                    OpenMode::TopLevel | OpenMode::Transient(..) => {
                         dwstyle |= WS_OVERLAPPEDWINDOW;
                     }
 
+                    OpenMode::Popup(..) => {
+                        dwstyle |= WS_POPUP;
+                    }
+
                     OpenMode::Embedded(..) => {
                         dwstyle |= WS_CHILD;
                     }
                 }
 
-                dwstyle
+                dwstyle | options.platform_options.windows_style
             };
 
-            // S_FALSE is okay here if OleInitialize was already called on the current
-            // thread. OleInitialize is needed for things like Drag and Drop.
-            let ole_result = OleInitialize(null());
-            let ole_success = ole_result != OLE_E_WRONGCOMPOBJ && ole_result != RPC_E_CHANGED_MODE;
+            // tool windows stay out of the taskbar and the Alt+Tab list
+            let dwexstyle = match mode {
+                OpenMode::Popup(..) => WS_EX_TOOLWINDOW,
+                _ => 0,
+            } | options.platform_options.windows_ex_style;
+
+            // OLE is needed for drag and drop, the host might have initialized COM on this
+            // thread in a way that is incompatible with it
+            let ole_apartment = OleApartment::enter();
 
             // set dpi awareness for the window (well restore it later)
             // we need it here so the window becomes DPI aware and window factory runs in
@@ -181,80 +361,191 @@ impl WindowImpl {
             let dpi_context = DpiContext::new();
             let _dpi_awareness = dpi_context.enter_per_monitor_aware_v2();
 
-            let window = create_window(dwstyle, parent, |hwnd| {
-                // enable transparency if requested
-                if options.transparent {
-                    let region = CreateRectRgn(0, 0, -1, -1);
-                    let bb = DWM_BLURBEHIND {
-                        dwFlags: DWM_BB_ENABLE | DWM_BB_BLURREGION,
-                        fEnable: true.into(),
-                        hRgnBlur: region,
-                        fTransitionOnMaximized: false.into(),
+            // owned windows are hidden with their owner by the system, moving along with it
+            // takes watching it. a subclass can only be installed from the thread of the
+            // window, see `WindowBuilder::with_follow_parent`
+            let follow_owner = match mode {
+                OpenMode::Transient(..) | OpenMode::Popup(..) if options.follow_parent => {
+                    let owner = GetAncestor(parent, GA_ROOT);
+                    match GetWindowThreadProcessId(owner, null_mut()) == GetCurrentThreadId() {
+                        true => owner,
+                        false => null_mut(),
+                    }
+                }
+                _ => null_mut(),
+            };
+
+            // the same for embedded windows and the settings broadcast
+            let settings_owner = match mode {
+                OpenMode::Embedded(..) => {
+                    let owner = GetAncestor(parent, GA_ROOT);
+                    match GetWindowThreadProcessId(owner, null_mut()) == GetCurrentThreadId() {
+                        true => owner,
+                        false => null_mut(),
+                    }
+                }
+                _ => null_mut(),
+            };
+
+            let pointer_context = PointerContext::new();
+            let class_name = options.platform_options.windows_class_name.as_deref();
+            let window =
+                create_window::<_, WindowError>(dwstyle, dwexstyle, class_name, parent, |hwnd| {
+                    // enable transparency if requested
+                    if options.transparent {
+                        let region = CreateRectRgn(0, 0, -1, -1);
+                        let bb = DWM_BLURBEHIND {
+                            dwFlags: DWM_BB_ENABLE | DWM_BB_BLURREGION,
+                            fEnable: true.into(),
+                            hRgnBlur: region,
+                            fTransitionOnMaximized: false.into(),
+                        };
+
+                        if !region.is_null() {
+                            DwmEnableBlurBehindWindow(hwnd, &bb);
+                            DeleteObject(region);
+                        }
+                    }
+
+                    // accept drag and drop. without OLE we can still take plain file drops
+                    // through WM_DROPFILES, just without any feedback while hovering
+                    let drop_target = DropTargetImpl::new(hwnd);
+                    if ole_apartment.is_none()
+                        || RegisterDragDrop(hwnd, DropTargetImpl::as_raw(&drop_target) as _) != 0
+                    {
+                        DragAcceptFiles(hwnd, 1);
+                    }
+
+                    // new gl context if requested
+                    #[cfg(feature = "opengl")]
+                    let gl_context = {
+                        let chain_format = Cell::new(None);
+                        create_opengl(&options.opengl, |config, last_resort| {
+                            GlContext::new(hwnd, *config, last_resort, &chain_format)
+                        })
+                    };
+
+                    // construct our window data, here we store all our state accessible from
+                    // [`WindowProc::window_proc`]
+                    Ok(Rc::new(Self {
+                        waker: Arc::new(WindowWakerImpl {
+                            window_hwnd: RwLock::new(hwnd),
+                        }),
+                        group: options.group.clone(),
+
+                        current_dpi_scale: Cell::new(
+                            dpi_context
+                                .dpi_for_window(hwnd)
+                                .unwrap_or(USER_DEFAULT_SCREEN_DPI),
+                        ),
+                        current_mouse_capture: Cell::new(0),
+                        current_buttons_held: Cell::new(0),
+                        current_mouse_cursor: Cell::new((options.cursor, options.cursor.into())),
+                        current_key_modifiers: Cell::new(Modifiers::default()),
+                        current_window_focused: Cell::new(false),
+
+                        current_window_size: Cell::new(Size::default()),
+                        current_window_position: Cell::new(Point::default()),
+                        current_window_style: Cell::new((dwstyle, dwexstyle)),
+                        extra_window_style: options.platform_options.windows_style,
+                        current_window_visibility: Cell::new(WindowVisibility::Normal),
+                        current_min_window_size: Cell::new(Size::MIN),
+                        current_max_window_size: Cell::new(Size::MAX),
+                        current_resize_hints: Cell::new(ResizeHints::default()),
+                        current_mouse_position: Cell::new(None),
+                        current_cursor_grab: Cell::new(CursorGrab::None),
+                        current_cursor_lock: Cell::new(None),
+                        current_event_token: Cell::new(EventToken::default()),
+                        current_ime_cursor_area: Cell::new(None),
+                        is_composing: Cell::new(false),
+
+                        hwnd,
+                        entry: WindowEntry::new(hwnd as usize),
+                        open_mode: mode,
+                        follow_owner,
+                        settings_owner,
+                        last_owner_position: Cell::new({
+                            let mut owner = zeroed::<RECT>();
+                            GetWindowRect(follow_owner, &mut owner);
+                            (owner.left, owner.top)
+                        }),
+
+                        event_handler: RefCell::new(None),
+                        user_data: RefCell::new(None),
+                        watchdog: Watchdog::new(options.watchdog),
+                        frame_jitter: FrameJitter::new(),
+                        keys_down: KeyState::new(),
+                        redraw_pending: Cell::new(false),
+                        is_closing: Cell::new(false),
+                        event_deferred: EventQueue::new(),
+
+                        #[cfg(feature = "opengl")]
+                        gl_context,
+                        #[cfg(feature = "opengl")]
+                        external_gl_context: options.external_opengl,
+                        // the other one is in use, just make a new one, should be cheap
+                        dpi_context: DpiContext::new(),
+                        pointer_input: options.pointer_input
+                            && pointer_context.enable_mouse_in_pointer(),
+                        raw_scroll: options.raw_scroll,
+                        pointer_context,
+                        unmatched_mouse_release: options.unmatched_mouse_release,
+                        auto_dpi_resize: options.auto_dpi_resize,
+                        vsync_thread: VSyncThread::new(hwnd),
+                        _keyboard_hook: KeyboardHook::new(hwnd),
+                        _resume_notification: match mode {
+                            OpenMode::Embedded(..) => ResumeNotification::register(hwnd),
+                            _ => ResumeNotification::default(),
+                        },
+                        _drop_target: drop_target,
+                        drag_pending: RefCell::new(None),
+                        dib_section: RefCell::new(None),
+                        is_dragging: Cell::new(false),
+                        resize_drag: Cell::new(None),
+                        current_monitor: Cell::new(null_mut()),
+                        current_color_profile: RefCell::new(None),
+                        ole_apartment,
+                    }))
+                })?;
+
+            // counted until `WM_DESTROY`, the event loop of the thread runs until then
+            if let OpenMode::TopLevel = mode {
+                event_loop::add();
+            }
+
+            // child windows are created at (0, 0), move them where the host wants them.
+            // popups open right below their anchor
+            match mode {
+                OpenMode::Embedded(..) => window.set_position(options.parent_relative_position),
+                OpenMode::Popup(_, anchor) => {
+                    let mut point = POINT {
+                        x: anchor.left,
+                        y: anchor.bottom,
                     };
 
-                    if !region.is_null() {
-                        DwmEnableBlurBehindWindow(hwnd, &bb);
-                        DeleteObject(region);
-                    }
-                }
-
-                // accept drag and drop
-                let drop_target = DropTargetImpl::new(hwnd);
-                if ole_success {
-                    let result = RegisterDragDrop(hwnd, DropTargetImpl::as_raw(&drop_target) as _);
-                    if result != 0 {
-                        return Err(Win32Error::last_error().with_context("RegisterDragDrop"));
-                    }
-                }
-
-                // new gl context if requested
-                let gl_context = options
-                    .opengl
-                    .map(|config| GlContext::new(hwnd, config))
-                    .unwrap_or_else(|| Err(OpenGlError::NotRequested));
-
-                // construct our window data, here we store all our state accessible from
-                // [`WindowProc::window_proc`]
-                Ok(Rc::new(Self {
-                    waker: Arc::new(WindowWakerImpl {
-                        window_hwnd: RwLock::new(hwnd),
-                    }),
-
-                    current_dpi_scale: Cell::new(
-                        dpi_context
-                            .dpi_for_window(hwnd)
-                            .unwrap_or(USER_DEFAULT_SCREEN_DPI),
-                    ),
-                    current_mouse_capture: Cell::new(0),
-                    current_mouse_cursor: Cell::new((
-                        MouseCursor::Default,
-                        MouseCursor::Default.into(),
-                    )),
-                    current_key_modifiers: Cell::new(Modifiers::default()),
-                    current_window_focused: Cell::new(false),
-
-                    current_window_size: Cell::new(Size::default()),
-                    current_window_position: Cell::new(Point::default()),
-                    current_window_style: Cell::new((dwstyle, 0)),
-                    current_window_visibility: Cell::new(WindowVisibility::Normal),
-                    current_min_window_size: Cell::new(Size::MIN),
-                    current_max_window_size: Cell::new(Size::MAX),
-                    current_mouse_position: Cell::new(None),
-
-                    hwnd,
-                    open_mode: mode,
-
-                    event_handler: RefCell::new(None),
-                    event_deferred: RefCell::new(VecDeque::new()),
-
-                    gl_context,
-                    // the other one is in use, just make a new one, should be cheap
-                    dpi_context: DpiContext::new(),
-                    vsync_thread: VSyncThread::new(hwnd),
-                    _keyboard_hook: KeyboardHook::new(hwnd),
-                    _drop_target: drop_target,
-                }))
-            })?;
+                    ClientToScreen(parent, &mut point);
+                    window.set_position(Point::from((point.x, point.y)));
+                }
+                _ => {}
+            }
+
+            if !window.follow_owner.is_null() {
+                SetWindowSubclass(
+                    window.follow_owner,
+                    Some(owner_subclass_proc),
+                    window.hwnd as usize,
+                    window.hwnd as usize,
+                );
+            }
+
+            if !window.settings_owner.is_null() {
+                SetWindowSubclass(
+                    window.settings_owner,
+                    Some(settings_subclass_proc),
+                    window.hwnd as usize,
+                    window.hwnd as usize,
+                );
+            }
 
             // SAFETY: we erase the lifetime of WindowImpl; it should be safe to do so
             // because:
@@ -272,6 +563,19 @@ impl WindowImpl {
 
             // start accepting events
             window.event_handler.replace(Some(handler));
+
+            if options.initial_keyboard_focus && matches!(mode, OpenMode::Embedded(..)) {
+                SetFocus(window.hwnd);
+            }
+
+            if let Some(group) = &window.group
+                && let Some(top_level) = NonZeroIsize::new(GetAncestor(window.hwnd, GA_ROOT) as _)
+            {
+                group.insert(
+                    RawWindowHandle::Win32(rwh_06::Win32WindowHandle::new(top_level)),
+                    window.waker(),
+                );
+            }
             // pull any events that were queued during initialization
             window.deferred_event(|_, _| {});
 
@@ -279,43 +583,59 @@ impl WindowImpl {
             window.current_key_modifiers.set(query_modifiers());
             window.deferred_event(|window, e| e.key_modifiers(window.current_key_modifiers.get()));
 
-            if let OpenMode::Blocking = mode {
-                // our favorite - win32 event pump
-                let mut msg: MSG = std::mem::zeroed();
-                while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
-                    TranslateMessage(&msg);
-                    DispatchMessageW(&msg);
-                }
-            }
+            // and the monitor we start on
+            window.update_monitor();
 
             Ok(window.waker())
         }
     }
 
-    /// Run a closure with exclusive access to the window's event handler.
+    /// Run a closure with exclusive access to the window's event handler, then
+    /// run the tasks deferred while it was running.
+    ///
+    /// Returns `None` without running the closure if the handler is not set or
+    /// already running (see the reentrancy notes on [`WindowHandler`]). To
+    /// deliver an event later instead, use [`Self::deferred_event`].
     ///
-    /// Panics if [`Self::non_reentrant_event`] is called inside of another
-    /// [`Self::non_reentrant_event`]. To safely post a task, use
-    /// [`Self::deferred_event`].
+    /// Once the window is closing nothing is delivered anymore, the handler
+    /// only gets [`WindowHandler::destroyed`] before the window goes away.
     fn non_reentrant_event<R>(&self, call: impl FnOnce(&mut dyn WindowHandler) -> R) -> Option<R> {
-        let mut handler = self
-            .event_handler
-            .try_borrow_mut()
-            .expect("unhandled callback reentrancy");
+        if self.is_closing.get() {
+            return None;
+        }
+
+        let Ok(mut handler) = self.event_handler.try_borrow_mut() else {
+            report_reentrancy(false);
+            return None;
+        };
 
         // handler might be None if the window is being dropped, in which case we return
         // None
         if let Some(handler) = handler.as_mut() {
-            let result = Some(call(&mut **handler));
+            let result = Some(self.watchdog.measure(|| call(&mut **handler)));
 
-            loop {
+            // the handler may close the window at any point, stop right there
+            while !self.is_closing.get() {
                 // event_queue must NOT be borrowed while calling the handler, so we have to
                 // reborrow it every time
-                let Some(event) = self.event_deferred.borrow_mut().pop_front() else {
+                let Some(event) = self.event_deferred.pop() else {
                     break;
                 };
 
-                event(self, &mut **handler);
+                self.watchdog.measure(|| event(self, &mut **handler));
+            }
+
+            if self.is_closing.get() {
+                self.event_deferred.clear();
+                return result;
+            }
+
+            if self.redraw_pending.take() {
+                self.watchdog.measure(|| handler.frame());
+            }
+
+            if let Some(stall) = self.watchdog.take_stall() {
+                handler.stalled(stall);
             }
 
             result
@@ -324,24 +644,449 @@ impl WindowImpl {
         }
     }
 
+    /// Check which monitor the window is on, and send
+    /// [`WindowHandler::monitor_changed`] if it is a different one than last
+    /// time.
+    fn update_monitor(&self) {
+        let monitor = unsafe { MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST) };
+        if monitor.is_null() || self.current_monitor.replace(monitor) == monitor {
+            return;
+        }
+
+        // the refresh rate of the new monitor may be different
+        self.vsync_thread.notify_display_change();
+
+        let id = MonitorId::from_raw(monitor as u64);
+        self.deferred_event(move |_, e| e.monitor_changed(id));
+        self.update_color_profile();
+    }
+
+    /// Read the ICC profile of the current monitor, and send
+    /// [`WindowHandler::color_profile_changed`] if it differs from the last
+    /// one.
+    fn update_color_profile(&self) {
+        let monitor = self.current_monitor.get();
+        if monitor.is_null() {
+            return;
+        }
+
+        let profile = unsafe { query_color_profile(monitor) };
+        if *self.current_color_profile.borrow() != profile {
+            self.current_color_profile.replace(profile);
+            self.deferred_event(|_, e| e.color_profile_changed());
+        }
+    }
+
+    /// Is `hwnd` one of the windows a popup belongs to (its owner, the owner of
+    /// that, ...) or one of the popups it opened? Activating those does not
+    /// dismiss it.
+    fn is_popup_owner(&self, hwnd: HWND) -> bool {
+        let is_owned_by = |mut window: HWND, owner: HWND| {
+            while !window.is_null() {
+                if window == owner {
+                    return true;
+                }
+
+                window = unsafe { GetWindow(window, GW_OWNER) };
+            }
+
+            false
+        };
+
+        !hwnd.is_null() && (is_owned_by(self.hwnd, hwnd) || is_owned_by(hwnd, self.hwnd))
+    }
+
+    /// Move by the same distance the owner HWND moved since the last call, see
+    /// [`WindowBuilder::with_follow_parent`].
+    fn follow_owner(&self) {
+        unsafe {
+            // a minimized owner is parked off screen, it comes back to the same place
+            let mut owner = zeroed::<RECT>();
+            if IsIconic(self.follow_owner) != 0 || GetWindowRect(self.follow_owner, &mut owner) == 0
+            {
+                return;
+            }
+
+            let (last_x, last_y) = self.last_owner_position.replace((owner.left, owner.top));
+            if (owner.left, owner.top) == (last_x, last_y) {
+                return;
+            }
+
+            let mut window = zeroed::<RECT>();
+            GetWindowRect(self.hwnd, &mut window);
+            SetWindowPos(
+                self.hwnd,
+                null_mut(),
+                window
+                    .left
+                    .saturating_add(owner.left.saturating_sub(last_x)),
+                window.top.saturating_add(owner.top.saturating_sub(last_y)),
+                0,
+                0,
+                SWP_NOZORDER | SWP_NOSIZE | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    /// Tell the handler that the popup lost the focus and close it, see
+    /// [`WindowHandler::popup_dismissed`].
+    fn dismiss_popup(&self) {
+        self.deferred_event(|window, e| {
+            e.popup_dismissed();
+            window.close();
+        });
+    }
+
     /// Run a closure with exclusive access to the window's event handler.
     ///
-    /// Unlike [`Self::non_reentrant_event`], this function will not panic if
-    /// called inside of another [`Self::non_reentrant_event`]. Instead, the
-    /// closure will be deferred and run later.
+    /// Unlike [`Self::non_reentrant_event`], the closure is not skipped if
+    /// called inside of another [`Self::non_reentrant_event`]. Instead, it is
+    /// deferred and run in order once the handler returns.
     ///
     /// For that reason it cannot return a value, and the closure must be
     /// `'static`.
     fn deferred_event(&self, task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static) {
-        if self
-            .event_handler
-            .try_borrow_mut()
-            .is_ok_and(|x| x.is_some())
+        self.queue_event(QueueKind::Critical, task);
+    }
+
+    /// Like [`Self::deferred_event`], for events that only report the latest
+    /// state of something. If deferred, it replaces the same event right
+    /// before it in the queue, see [`EventQueue::push`].
+    fn deferred_latest_event<F>(&self, task: F)
+    where
+        F: FnOnce(&Self, &mut dyn WindowHandler) + 'static,
+    {
+        self.queue_event(QueueKind::Latest(TypeId::of::<F>()), task);
+    }
+
+    /// Like [`Self::deferred_event`], for events that can be lost without
+    /// the handler missing a change of state, like scrolls and timer ticks. If
+    /// deferred, it is dropped first when the queue is full, see
+    /// [`EventQueue`].
+    fn deferred_lossy_event(&self, task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static) {
+        self.queue_event(QueueKind::Lossy, task);
+    }
+
+    fn queue_event(
+        &self,
+        kind: QueueKind,
+        task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static,
+    ) {
+        match self.event_handler.try_borrow_mut().map(|x| x.is_some()) {
+            Ok(true) => {
+                self.non_reentrant_event(|handler| task(self, handler));
+            }
+            Ok(false) => {
+                // not initialized yet, delivered once the handler is set
+                self.event_deferred.push(kind, Box::new(task));
+            }
+            Err(_) => {
+                report_reentrancy(true);
+                self.event_deferred.push(kind, Box::new(task));
+            }
+        }
+    }
+
+    /// Emits a [`WindowHandler::composition_start`] event unless a composition
+    /// is already in progress.
+    fn start_composition(&self) {
+        if !self.is_composing.replace(true) {
+            self.deferred_event(|_, e| e.composition_start());
+        }
+    }
+
+    /// Emits a [`WindowHandler::key_modifiers`] event if the modifier state has
+    /// changed. Called before dispatching every input message, so the handler
+    /// receives the modifiers before the input event that depends on them.
+    fn handle_key_modifiers(&self) {
+        let modifiers = query_modifiers();
+        if self.current_key_modifiers.replace(modifiers) != modifiers {
+            self.deferred_event(move |window, e| {
+                e.key_modifiers(window.current_key_modifiers.get())
+            });
+        }
+    }
+
+    /// Emits a [`WindowHandler::mouse_move`] event if the cursor position has
+    /// changed, and starts tracking mouse leave events if the cursor just
+    /// entered the window.
+    unsafe fn handle_mouse_move(&self, mut point: Point) {
+        unsafe {
+            if let Some(drag) = self.resize_drag.get() {
+                self.update_resize_drag(drag);
+                return;
+            }
+
+            if let Some((anchor, position)) = self.current_cursor_lock.get() {
+                // the move of our own SetCursorPos back to the anchor
+                if point == anchor {
+                    return;
+                }
+
+                point = position + (point - anchor);
+                self.current_cursor_lock.set(Some((anchor, point)));
+                self.set_cursor_position(anchor);
+            }
+
+            if self.current_mouse_position.get().is_none() && !self.pointer_input {
+                // mouse just entered the window, start tracking mouse leave events
+                let _ = TrackMouseEvent(&mut TRACKMOUSEEVENT {
+                    cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_LEAVE,
+                    hwndTrack: self.hwnd,
+                    dwHoverTime: 0,
+                });
+            }
+
+            // update cursor position
+            if self.current_mouse_position.replace(Some(point)) != Some(point) {
+                self.deferred_latest_event(move |window, e| {
+                    if let Some(point) = window.current_mouse_position.get() {
+                        // fine if we miss an update and get a new value instead
+                        // because we do not capture anything, the closure will be
+                        // zero-sized and not allocate
+                        e.mouse_move(point)
+                    };
+                });
+            }
+        }
+    }
+
+    /// Resize the window by the edge the resize started with, keeping the
+    /// opposite sides in place.
+    unsafe fn update_resize_drag(&self, (edge, start, frame): (ResizeEdge, POINT, RECT)) {
+        unsafe {
+            // which way the dragged edges move the window sides
+            let (x, y) = match edge {
+                ResizeEdge::Top => (0, -1),
+                ResizeEdge::Bottom => (0, 1),
+                ResizeEdge::Left => (-1, 0),
+                ResizeEdge::Right => (1, 0),
+                ResizeEdge::TopLeft => (-1, -1),
+                ResizeEdge::TopRight => (1, -1),
+                ResizeEdge::BottomLeft => (-1, 1),
+                ResizeEdge::BottomRight => (1, 1),
+            };
+
+            let mut mouse = POINT { x: 0, y: 0 };
+            GetCursorPos(&mut mouse);
+
+            let hints = self.current_resize_hints.get();
+            let client = self.convert_client(
+                Rect {
+                    top: frame.top,
+                    left: frame.left,
+                    bottom: frame.bottom,
+                    right: frame.right,
+                },
+                false,
+            );
+            let (mut width, mut height) = (client.right - client.left, client.bottom - client.top);
+
+            if hints.can_resize_horizontally {
+                width = width.saturating_add(x * (mouse.x - start.x));
+            }
+
+            if hints.can_resize_vertically {
+                height = height.saturating_add(y * (mouse.y - start.y));
+            }
+
+            let size = hints.constrain(
+                Size {
+                    width: width.max(0) as u32,
+                    height: height.max(0) as u32,
+                },
+                self.current_min_window_size.get(),
+                self.current_max_window_size.get(),
+            );
+            let size = self.convert_client(Rect::from_size(size), true).size();
+            let width = size.width.try_into().unwrap_or(i32::MAX);
+            let height = size.height.try_into().unwrap_or(i32::MAX);
+
+            // keep the opposite sides in place
+            let left = if x < 0 {
+                frame.right - width
+            } else {
+                frame.left
+            };
+            let top = if y < 0 {
+                frame.bottom - height
+            } else {
+                frame.top
+            };
+
+            SetWindowPos(
+                self.hwnd,
+                null_mut(),
+                left,
+                top,
+                width,
+                height,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    /// Confines the cursor to the client area of the window.
+    unsafe fn clip_cursor(&self) {
+        unsafe {
+            let mut rect = zeroed::<RECT>();
+            if GetClientRect(self.hwnd, &mut rect) == 0 {
+                return;
+            }
+
+            let mut top_left = POINT {
+                x: rect.left,
+                y: rect.top,
+            };
+            let mut bottom_right = POINT {
+                x: rect.right,
+                y: rect.bottom,
+            };
+
+            ClientToScreen(self.hwnd, &mut top_left);
+            ClientToScreen(self.hwnd, &mut bottom_right);
+            ClipCursor(&RECT {
+                left: top_left.x,
+                top: top_left.y,
+                right: bottom_right.x,
+                bottom: bottom_right.y,
+            });
+        }
+    }
+
+    /// Lets go of the cursor held with [`PlatformWindow::set_cursor_grab`],
+    /// leaving it where it was locked.
+    unsafe fn release_cursor_grab(&self) {
+        unsafe {
+            if self.current_cursor_grab.replace(CursorGrab::None) == CursorGrab::None {
+                return;
+            }
+
+            self.unlock_cursor();
+            ClipCursor(null());
+        }
+    }
+
+    /// Stops reporting virtual positions, the cursor is back at the anchor.
+    fn unlock_cursor(&self) {
+        if let Some((anchor, _)) = self.current_cursor_lock.take() {
+            self.current_mouse_position.set(Some(anchor));
+        }
+    }
+
+    /// Emits a [`WindowHandler::mouse_press`] event and handles automatic mouse
+    /// capture. `button` is `None` for buttons we do not know about, those
+    /// still count towards the capture.
+    unsafe fn handle_mouse_press(&self, button: Option<MouseButton>, down: bool, point: Point) {
+        unsafe {
+            // the buttons were reported as released when the resize started
+            if self.resize_drag.get().is_some() {
+                if !down {
+                    self.resize_drag.take();
+                    self.current_mouse_capture.set(0);
+                    ReleaseCapture();
+                }
+                return;
+            }
+
+            // while locked, presses happen at the virtual position
+            let point = match self.current_cursor_lock.get() {
+                Some((_, position)) => position,
+                None => point,
+            };
+
+            if let Some(button) = button {
+                let mask = 1 << button as u32;
+                let held = self.current_buttons_held.get();
+                self.current_buttons_held
+                    .set(if down { held | mask } else { held & !mask });
+
+                // windows has no implicit capture, so a button pressed outside of the window
+                // can well be released inside of it
+                if down || held & mask != 0 || self.unmatched_mouse_release {
+                    self.deferred_event(move |_, e| e.mouse_press(button, down, point));
+                }
+            }
+
+            if down {
+                self.current_mouse_capture.update(|x| x + 1);
+                if self.current_mouse_capture.get() == 1 {
+                    SetCapture(self.hwnd);
+                    SetFocus(self.hwnd);
+                }
+            } else {
+                self.current_mouse_capture.update(|x| x.saturating_sub(1));
+                if self.current_mouse_capture.get() == 0 {
+                    ReleaseCapture();
+                    self.restore_hidden_cursor();
+                }
+            }
+        }
+    }
+
+    /// Show the cursor again if the handler hid it and it is no longer over the
+    /// window.
+    ///
+    /// The cursor is only hidden in `WM_SETCURSOR` for our client area, but the
+    /// window under it only gets to set its own cursor on the next mouse move.
+    /// A drag released outside of the window (or the window closing under the
+    /// cursor) would leave the host with a hidden cursor until then.
+    fn restore_hidden_cursor(&self) {
+        if self.current_mouse_cursor.get().0 != MouseCursor::Hidden
+            || self.current_mouse_capture.get() != 0
         {
-            self.non_reentrant_event(|handler| task(self, handler));
-        } else {
-            self.event_deferred.borrow_mut().push_back(Box::new(task));
+            return;
+        }
+
+        unsafe {
+            let mut point = POINT::default();
+            if !self.is_closing.get()
+                && GetCursorPos(&mut point) != 0
+                && WindowFromPoint(point) == self.hwnd
+            {
+                return;
+            }
+        }
+
+        WinCursor::from(MouseCursor::Default).apply();
+    }
+
+    /// Get the minimum and maximum client size the user can resize the window
+    /// to, with the axes that can not be resized locked to the current size.
+    fn user_size_limits(&self) -> (Size, Size) {
+        let hints = self.current_resize_hints.get();
+        let size = self.current_window_size.get();
+        let (mut min, mut max) = (
+            self.current_min_window_size.get(),
+            self.current_max_window_size.get(),
+        );
+
+        if !hints.can_resize_horizontally {
+            (min.width, max.width) = (size.width, size.width);
+        }
+
+        if !hints.can_resize_vertically {
+            (min.height, max.height) = (size.height, size.height);
         }
+
+        (min, max)
+    }
+
+    /// Get the size of the window frame (the difference between the window size
+    /// and the client size).
+    fn frame_size(&self) -> Size {
+        self.convert_client(Rect::default(), true).size()
+    }
+
+    /// Emits a [`WindowHandler::mouse_leave`] event if the cursor was inside the
+    /// window.
+    fn handle_mouse_leave(&self) {
+        if self.current_mouse_position.replace(None).is_some() {
+            self.deferred_event(move |_, e| e.mouse_leave());
+        }
+
+        self.restore_hidden_cursor();
     }
 
     /// Convert a client size to a window size or vice-versa, taking into
@@ -381,9 +1126,18 @@ impl Drop for WindowImpl {
         // subsequent wakeups should fail
         *self.waker.window_hwnd.write().expect("lock poisoned") = null_mut();
 
+        if let Some(group) = &self.group {
+            group.remove(&self.waker());
+        }
+
         // drop the handler here, so it could do clean up when the window is still alive
         // will ignore any events sent after this point, as the handler is gone
-        self.event_handler.take();
+        if let Some(mut handler) = self.event_handler.take() {
+            handler.destroyed(match self.is_closing.get() {
+                true => DestroyReason::Closed,
+                false => DestroyReason::Destroyed,
+            });
+        }
 
         // winapi cleanup stuff
         unsafe {
@@ -398,11 +1152,54 @@ impl WindowProc for WindowImpl {
         let _dpi_awareness = self.dpi_context.enter_per_monitor_aware_v2();
 
         unsafe {
+            let is_input = (WM_MOUSEFIRST..=WM_MOUSELAST).contains(&msg)
+                || matches!(
+                    msg,
+                    WM_POINTERUPDATE
+                        | WM_POINTERDOWN
+                        | WM_POINTERUP
+                        | WM_POINTERWHEEL
+                        | WM_POINTERHWHEEL
+                        | WM_USER_KEY_DOWN
+                        | WM_USER_KEY_UP
+                );
+
+            if is_input {
+                // windows has no event serials, only the message time
+                self.current_event_token.set(EventToken {
+                    time: GetMessageTime() as u32 as u64,
+                    serial: 0,
+                });
+
+                // the keyboard hook only sees modifier changes while the window has
+                // focus, resample them so the handler never sees stale modifiers
+                self.handle_key_modifiers();
+            }
+
             match msg {
                 WM_DESTROY => {
-                    // exit the event loop if we are in blocking mode
-                    if let OpenMode::Blocking = self.open_mode {
-                        PostQuitMessage(0);
+                    self.restore_hidden_cursor();
+                    self.release_cursor_grab();
+
+                    if !self.follow_owner.is_null() {
+                        RemoveWindowSubclass(
+                            self.follow_owner,
+                            Some(owner_subclass_proc),
+                            hwnd as usize,
+                        );
+                    }
+
+                    if !self.settings_owner.is_null() {
+                        RemoveWindowSubclass(
+                            self.settings_owner,
+                            Some(settings_subclass_proc),
+                            hwnd as usize,
+                        );
+                    }
+
+                    // exit the event loop once the last top-level window is gone
+                    if let OpenMode::TopLevel = self.open_mode {
+                        event_loop::remove();
                     }
 
                     return 0;
@@ -415,6 +1212,36 @@ impl WindowProc for WindowImpl {
 
                 WM_DISPLAYCHANGE => {
                     self.vsync_thread.notify_display_change();
+                    self.update_monitor();
+                    // the profile may change without changing monitors
+                    self.update_color_profile();
+                }
+
+                // popups are dismissed when another window is activated, unless it is the
+                // top-level window they belong to or one of their own popups
+                WM_ACTIVATE
+                    if matches!(self.open_mode, OpenMode::Popup(..))
+                        && (wparam & 0xFFFF) as u32 == WA_INACTIVE
+                        && !self.is_popup_owner(lparam as HWND) =>
+                {
+                    self.dismiss_popup();
+                }
+
+                // another application was activated
+                WM_ACTIVATEAPP if matches!(self.open_mode, OpenMode::Popup(..)) && wparam == 0 => {
+                    self.dismiss_popup();
+                }
+
+                // only sent to top-level windows, embedded ones get it from
+                // `settings_subclass_proc`
+                WM_SETTINGCHANGE => {
+                    self.deferred_event(|_, e| e.system_metrics_changed());
+                }
+
+                WM_POWERBROADCAST if wparam as u32 == PBT_APMRESUMEAUTOMATIC => {
+                    self.vsync_thread.notify_resume();
+                    self.deferred_event(|_, e| e.system_resumed());
+                    return 1;
                 }
 
                 WM_WINDOWPOSCHANGED => {
@@ -425,6 +1252,15 @@ impl WindowProc for WindowImpl {
                         self.vsync_thread.notify_display_change();
                     }
 
+                    self.update_monitor();
+
+                    // the clip rectangle is in screen coordinates, so it has to follow the window
+                    if (*info).flags & SWP_HIDEWINDOW != 0 {
+                        self.release_cursor_grab();
+                    } else if self.current_cursor_grab.get() != CursorGrab::None {
+                        self.clip_cursor();
+                    }
+
                     let visibility = if (*info).flags & SWP_HIDEWINDOW != 0 {
                         WindowVisibility::Hidden
                     } else if (*info).flags & SWP_SHOWWINDOW != 0 {
@@ -458,7 +1294,7 @@ impl WindowProc for WindowImpl {
                     if visibility != WindowVisibility::Minimized
                         && self.current_window_position.replace(rect.origin()) != rect.origin()
                     {
-                        self.deferred_event(move |window, e| {
+                        self.deferred_latest_event(move |window, e| {
                             // fine if we miss an update and get a new value instead
                             // because we do not capture anything, the closure will be zero-sized
                             // and not allocate
@@ -468,7 +1304,12 @@ impl WindowProc for WindowImpl {
 
                     // update window size
                     if self.current_window_size.replace(rect.size()) != rect.size() {
-                        self.deferred_event(move |window, e| {
+                        #[cfg(feature = "opengl")]
+                        if let Ok(gl) = &self.gl_context {
+                            gl.resize(rect.size());
+                        }
+
+                        self.deferred_latest_event(move |window, e| {
                             e.size_changed(window.current_window_size.get()) // same as with position
                         });
                     }
@@ -478,6 +1319,24 @@ impl WindowProc for WindowImpl {
 
                 WM_DPICHANGED => {
                     self.current_dpi_scale.set((wparam & 0xFFFF) as u32);
+
+                    // the scale is updated first, so the size event sent by WM_WINDOWPOSCHANGED
+                    // from inside SetWindowPos already sees the new scale, and the scale event
+                    // the new size
+                    let embedded = matches!(self.open_mode, OpenMode::Embedded(..));
+                    if self.auto_dpi_resize && !embedded && lparam != 0 {
+                        let rect = *(lparam as *const RECT);
+                        SetWindowPos(
+                            self.hwnd,
+                            null_mut(),
+                            rect.left,
+                            rect.top,
+                            rect.right - rect.left,
+                            rect.bottom - rect.top,
+                            SWP_NOZORDER | SWP_NOACTIVATE,
+                        );
+                    }
+
                     self.deferred_event(|window, e| e.scale_changed(window.scale()));
                     return 0;
                 }
@@ -490,32 +1349,8 @@ impl WindowProc for WindowImpl {
 
                 WM_MOUSEMOVE | WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN
                 | WM_XBUTTONDOWN | WM_LBUTTONUP | WM_RBUTTONUP | WM_MBUTTONUP | WM_XBUTTONUP => {
-                    if self.current_mouse_position.get().is_none() {
-                        // mouse just entered the window, start tracking mouse leave events
-                        let _ = TrackMouseEvent(&mut TRACKMOUSEEVENT {
-                            cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
-                            dwFlags: TME_LEAVE,
-                            hwndTrack: self.hwnd,
-                            dwHoverTime: 0,
-                        });
-                    }
-
-                    let point = Point {
-                        x: (lparam & 0xFFFF) as i16 as f64,
-                        y: ((lparam >> 16) & 0xFFFF) as i16 as f64,
-                    };
-
-                    // update cursor position
-                    if self.current_mouse_position.replace(Some(point)) != Some(point) {
-                        self.deferred_event(move |window, e| {
-                            if let Some(point) = window.current_mouse_position.get() {
-                                // fine if we miss an update and get a new value instead
-                                // because we do not capture anything, the closure will be
-                                // zero-sized and not allocate
-                                e.mouse_move(point)
-                            };
-                        });
-                    }
+                    let point = point_from_lparam(lparam);
+                    self.handle_mouse_move(point);
 
                     // if its a click event
                     if msg != WM_MOUSEMOVE {
@@ -537,38 +1372,114 @@ impl WindowProc for WindowImpl {
                             WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN | WM_XBUTTONDOWN
                         );
 
-                        if let Some(button) = button {
-                            self.deferred_event(move |_, e| e.mouse_press(button, down));
-                        }
-
-                        if down {
-                            self.current_mouse_capture.update(|x| x + 1);
-                            if self.current_mouse_capture.get() == 1 {
-                                SetCapture(self.hwnd);
-                                SetFocus(self.hwnd);
-                            }
-                        } else {
-                            self.current_mouse_capture.update(|x| x.saturating_sub(1));
-                            if self.current_mouse_capture.get() == 0 {
-                                ReleaseCapture();
-                            }
-                        }
+                        self.handle_mouse_press(button, down, point);
                     }
                 }
 
-                WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
+                WM_MOUSEWHEEL | WM_MOUSEHWHEEL | WM_POINTERWHEEL | WM_POINTERHWHEEL => {
+                    // same wParam layout for both the legacy and the pointer messages
                     let delta = (wparam >> 16) as i16;
                     let delta = delta as f64 / WHEEL_DELTA as f64;
 
-                    let x = if msg == WM_MOUSEWHEEL { 0.0 } else { delta };
-                    let y = if msg == WM_MOUSEWHEEL { -delta } else { 0.0 };
+                    let vertical = matches!(msg, WM_MOUSEWHEEL | WM_POINTERWHEEL);
+                    let (x, y) = ScrollDelta::Notches {
+                        x: if vertical { 0.0 } else { delta },
+                        y: if vertical { -delta } else { 0.0 },
+                    }
+                    .resolve(self.raw_scroll);
+
+                    self.deferred_lossy_event(move |_, e| e.mouse_scroll(x, y));
 
-                    self.deferred_event(move |_, e| e.mouse_scroll(x, y));
+                    // handled, don't let DefWindowProc turn it into a legacy message again
+                    if matches!(msg, WM_POINTERWHEEL | WM_POINTERHWHEEL) {
+                        return 0;
+                    }
                 }
 
                 WM_MOUSELEAVE => {
-                    self.current_mouse_position.set(None);
-                    self.deferred_event(move |_, e| e.mouse_leave());
+                    self.handle_mouse_leave();
+                }
+
+                // unified pointer input, only if opted in. otherwise we let DefWindowProc
+                // turn these into the legacy mouse messages above
+                WM_POINTERUPDATE | WM_POINTERDOWN | WM_POINTERUP if self.pointer_input => {
+                    let pointer_id = (wparam & 0xffff) as u32;
+                    let Some(info) = self.pointer_context.pointer_info(pointer_id) else {
+                        return DefWindowProcW(hwnd, msg, wparam, lparam);
+                    };
+
+                    // secondary touch contacts have nowhere to go (yet)
+                    if info.pointerFlags & POINTER_FLAG_PRIMARY == 0 {
+                        return DefWindowProcW(hwnd, msg, wparam, lparam);
+                    }
+
+                    let mut point = info.ptPixelLocation;
+                    ScreenToClient(self.hwnd, &mut point);
+                    let point = Point {
+                        x: point.x as f64,
+                        y: point.y as f64,
+                    };
+
+                    self.handle_mouse_move(point);
+
+                    let (button, down) = match info.ButtonChangeType {
+                        POINTER_CHANGE_FIRSTBUTTON_DOWN => (Some(MouseButton::Left), true),
+                        POINTER_CHANGE_FIRSTBUTTON_UP => (Some(MouseButton::Left), false),
+                        POINTER_CHANGE_SECONDBUTTON_DOWN => (Some(MouseButton::Right), true),
+                        POINTER_CHANGE_SECONDBUTTON_UP => (Some(MouseButton::Right), false),
+                        POINTER_CHANGE_THIRDBUTTON_DOWN => (Some(MouseButton::Middle), true),
+                        POINTER_CHANGE_THIRDBUTTON_UP => (Some(MouseButton::Middle), false),
+                        POINTER_CHANGE_FOURTHBUTTON_DOWN => (Some(MouseButton::Back), true),
+                        POINTER_CHANGE_FOURTHBUTTON_UP => (Some(MouseButton::Back), false),
+                        POINTER_CHANGE_FIFTHBUTTON_DOWN => (Some(MouseButton::Forward), true),
+                        POINTER_CHANGE_FIFTHBUTTON_UP => (Some(MouseButton::Forward), false),
+                        _ => (None, false),
+                    };
+
+                    if button.is_some() {
+                        self.handle_mouse_press(button, down, point);
+                    }
+
+                    return 0;
+                }
+
+                WM_POINTERENTER | WM_POINTERLEAVE => {
+                    let pointer_id = (wparam & 0xffff) as u32;
+                    if self.pointer_context.pointer_type(pointer_id) == Some(PT_PEN) {
+                        let inverted = self.pointer_context.is_pen_inverted(pointer_id);
+                        let entering = msg == WM_POINTERENTER;
+                        self.deferred_event(move |_, e| e.pen_proximity(entering, inverted));
+                    }
+
+                    // in pointer mode there is no WM_MOUSELEAVE
+                    if msg == WM_POINTERLEAVE && self.pointer_input {
+                        self.handle_mouse_leave();
+                    }
+                }
+
+                WM_CAPTURECHANGED
+                    if lparam as HWND != self.hwnd && self.current_mouse_capture.get() != 0 =>
+                {
+                    // something else (a menu, a dialog, the host) took the capture in the middle
+                    // of a drag, we will not see the buttons released
+                    self.resize_drag.take();
+                    let point = self.current_mouse_position.get().unwrap_or_default();
+                    let held = self.current_buttons_held.replace(0);
+                    self.current_mouse_capture.set(0);
+
+                    for button in [
+                        MouseButton::Left,
+                        MouseButton::Right,
+                        MouseButton::Middle,
+                        MouseButton::Forward,
+                        MouseButton::Back,
+                    ] {
+                        if held & (1 << button as u32) != 0 {
+                            self.deferred_event(move |_, e| e.mouse_press(button, false, point));
+                        }
+                    }
+
+                    self.restore_hidden_cursor();
                 }
 
                 WM_SETCURSOR if lparam as u32 & 0xffff == HTCLIENT => {
@@ -579,28 +1490,135 @@ impl WindowProc for WindowImpl {
 
                 WM_GETMINMAXINFO => {
                     let info = lparam as *mut MINMAXINFO;
-                    let min = self.current_min_window_size.get();
-                    let max = self.current_max_window_size.get();
-                    (*info).ptMinTrackSize = POINT {
-                        x: min.width.try_into().unwrap_or(i32::MAX),
-                        y: min.height.try_into().unwrap_or(i32::MAX),
-                    };
-                    (*info).ptMaxTrackSize = POINT {
-                        x: max.width.try_into().unwrap_or(i32::MAX),
-                        y: max.height.try_into().unwrap_or(i32::MAX),
+                    let frame = self.frame_size();
+                    let (min, max) = self.user_size_limits();
+                    let to_window_size = |size: Size| POINT {
+                        x: size
+                            .width
+                            .saturating_add(frame.width)
+                            .try_into()
+                            .unwrap_or(i32::MAX),
+                        y: size
+                            .height
+                            .saturating_add(frame.height)
+                            .try_into()
+                            .unwrap_or(i32::MAX),
                     };
+
+                    (*info).ptMinTrackSize = to_window_size(min);
+                    (*info).ptMaxTrackSize = to_window_size(max);
                     (*info).ptMaxSize = (*info).ptMaxTrackSize;
                     return 0;
                 }
 
+                WM_SIZING
+                    if self.current_resize_hints.get().step.is_some()
+                        || self.current_resize_hints.get().aspect_ratio.is_some() =>
+                {
+                    let hints = self.current_resize_hints.get();
+
+                    // snap the proposed client size, then grow or shrink the window from the edge
+                    // that is being dragged
+                    let rect = &mut *(lparam as *mut RECT);
+                    let frame = self.frame_size();
+                    let (min, max) = self.user_size_limits();
+                    let proposed = Rect {
+                        top: rect.top,
+                        left: rect.left,
+                        bottom: rect.bottom,
+                        right: rect.right,
+                    }
+                    .size();
+                    let size = hints.constrain(
+                        Size {
+                            width: proposed.width.saturating_sub(frame.width),
+                            height: proposed.height.saturating_sub(frame.height),
+                        },
+                        min,
+                        max,
+                    );
+
+                    let width = size.width.saturating_add(frame.width).min(i32::MAX as u32) as i32;
+                    let height = size
+                        .height
+                        .saturating_add(frame.height)
+                        .min(i32::MAX as u32) as i32;
+
+                    match wparam as u32 {
+                        WMSZ_LEFT | WMSZ_TOPLEFT | WMSZ_BOTTOMLEFT => {
+                            rect.left = rect.right.saturating_sub(width)
+                        }
+                        _ => rect.right = rect.left.saturating_add(width),
+                    }
+
+                    match wparam as u32 {
+                        WMSZ_TOP | WMSZ_TOPLEFT | WMSZ_TOPRIGHT => {
+                            rect.top = rect.bottom.saturating_sub(height)
+                        }
+                        _ => rect.bottom = rect.top.saturating_add(height),
+                    }
+
+                    return 1;
+                }
+
                 WM_SETFOCUS if !self.current_window_focused.replace(true) => {
+                    self.keys_down.reset(query_keys_down());
                     self.deferred_event(|_, e| e.focus_changed(true));
                 }
 
                 WM_KILLFOCUS if self.current_window_focused.replace(false) => {
+                    self.keys_down.reset([]);
+                    self.release_cursor_grab();
                     self.deferred_event(|_, e| e.focus_changed(false));
                 }
 
+                WM_IME_SETCONTEXT => {
+                    // the handler draws the composition string itself
+                    let lparam = lparam & !(ISC_SHOWUICOMPOSITIONWINDOW as LPARAM);
+                    return DefWindowProcW(hwnd, msg, wparam, lparam);
+                }
+
+                WM_IME_STARTCOMPOSITION => {
+                    if let Some(area) = self.current_ime_cursor_area.get() {
+                        set_ime_cursor_area(self.hwnd, area);
+                    }
+
+                    self.start_composition();
+                    return 0;
+                }
+
+                WM_IME_COMPOSITION => {
+                    let changes = lparam as u32;
+
+                    // the result comes first, a new composition may already be in progress
+                    if changes & GCS_RESULTSTR != 0
+                        && let Some(text) = composition_result(self.hwnd)
+                    {
+                        self.start_composition();
+                        self.is_composing.set(false);
+                        self.deferred_event(move |_, e| e.composition_end(Some(&text)));
+                    }
+
+                    if changes & GCS_COMPSTR != 0
+                        && let Some((text, cursor_range)) = composition_string(self.hwnd)
+                        && (self.is_composing.get() || !text.is_empty())
+                    {
+                        self.start_composition();
+                        self.deferred_event(move |_, e| e.composition_update(&text, cursor_range));
+                    }
+
+                    // the result is delivered above, not as WM_IME_CHAR messages
+                    return 0;
+                }
+
+                WM_IME_ENDCOMPOSITION => {
+                    if self.is_composing.replace(false) {
+                        self.deferred_event(|_, e| e.composition_end(None));
+                    }
+
+                    return 0;
+                }
+
                 WM_PAINT => {
                     let mut rect = RECT { ..zeroed() };
                     if GetUpdateRect(self.hwnd, &mut rect, 0) != 0 {
@@ -663,18 +1681,42 @@ impl WindowProc for WindowImpl {
                     return encode_drop_effect(effect) as _;
                 }
 
+                // the whole drag happens at once when the files are dropped, so the
+                // handler sees the same events as with OLE, minus the hovering
+                WM_DROPFILES => {
+                    let hdrop = wparam as HDROP;
+                    let mut point = POINT { x: 0, y: 0 };
+                    DragQueryPoint(hdrop, &mut point);
+                    let files = decode_hdrop(hdrop);
+                    DragFinish(hdrop);
+
+                    let point = Point {
+                        x: point.x as f64,
+                        y: point.y as f64,
+                    };
+
+                    self.deferred_event(move |_, e| {
+                        if e.drag_enter(Exchange::Files(files), point) == DropEffect::Reject {
+                            e.drag_leave();
+                        } else {
+                            e.drag_accept();
+                        }
+                    });
+                    return 0;
+                }
+
                 WM_USER_DND_LEAVE => {
                     self.deferred_event(|_, e| e.drag_leave());
                     return 0;
                 }
 
                 WM_USER_KEY_MODIFIERS => {
-                    let modifiers = query_modifiers();
-                    if self.current_key_modifiers.replace(modifiers) != modifiers {
-                        self.deferred_event(move |window, e| {
-                            e.key_modifiers(window.current_key_modifiers.get())
-                        });
-                    }
+                    self.handle_key_modifiers();
+                }
+
+                // keys that drive a composition belong to the input method
+                WM_USER_KEY_DOWN | WM_USER_KEY_UP if wparam == VK_PROCESSKEY as WPARAM => {
+                    return 0;
                 }
 
                 WM_USER_KEY_DOWN | WM_USER_KEY_UP => {
@@ -683,11 +1725,30 @@ impl WindowProc for WindowImpl {
                         return 0;
                     };
 
-                    let capture = self
-                        .non_reentrant_event(|handler| {
-                            handler.key_press(key, msg == WM_USER_KEY_DOWN)
-                        })
-                        .unwrap_or(false);
+                    // bit 30 is the previous key state, set when the press is an autorepeat
+                    let pressed = msg == WM_USER_KEY_DOWN;
+                    let is_repeat = pressed && lparam & (1 << 30) != 0;
+                    self.keys_down.set(key, pressed);
+                    let text = if pressed {
+                        virtual_key_to_char(wparam as u32, scan_code)
+                    } else {
+                        None
+                    };
+
+                    let capture = match self.event_handler.try_borrow().map(|x| x.is_none()) {
+                        // the factory is still running, replay the key once the handler is set
+                        Ok(true) => {
+                            self.deferred_event(move |_, handler| {
+                                handler.key_press(key, pressed, is_repeat, text);
+                            });
+                            false
+                        }
+                        _ => self
+                            .non_reentrant_event(|handler| {
+                                handler.key_press(key, pressed, is_repeat, text)
+                            })
+                            .unwrap_or(false),
+                    };
 
                     return if capture { 1 } else { 0 };
                 }
@@ -698,6 +1759,13 @@ impl WindowProc for WindowImpl {
                     // `SendNotifyMessage` and this could sometimes be called while the event
                     // handler is borrowed, which would panic.
                     self.deferred_event(|window, e| {
+                        // child windows are not told when the host moves them to another
+                        // monitor, so look every frame
+                        if matches!(window.open_mode, OpenMode::Embedded(..)) {
+                            window.update_monitor();
+                        }
+
+                        window.frame_jitter.measure(Instant::now());
                         e.frame();
                         window.vsync_thread.notify_frame_finished();
                     });
@@ -705,12 +1773,67 @@ impl WindowProc for WindowImpl {
                     return 0;
                 }
 
+                WM_TIMER => {
+                    // our timer ids are offset by one, see `set_timer`
+                    if let Some(id) = wparam.checked_sub(1)
+                        && let Ok(id) = u32::try_from(id)
+                    {
+                        self.deferred_lossy_event(move |_, e| e.timer(id));
+                        return 0;
+                    }
+                }
+
+                WM_USER_FRAME_PACING => {
+                    let pacing = match wparam {
+                        0 => FramePacing::VSync,
+                        _ => FramePacing::Timer,
+                    };
+
+                    self.deferred_event(move |_, e| e.frame_pacing_changed(pacing));
+                    return 0;
+                }
+
                 WM_USER_WAKEUP => {
-                    self.deferred_event(|_, e| e.wakeup());
+                    self.deferred_lossy_event(|_, e| e.wakeup());
+                    return 0;
+                }
+
+                // OLE takes the mouse capture for the drag, and WM_CAPTURECHANGED reports the
+                // held buttons as released
+                WM_USER_DRAG_START => {
+                    let Some(data) = self.drag_pending.take() else {
+                        return 0;
+                    };
+
+                    self.is_dragging.set(true);
+                    let effect = DropSourceImpl::drag(&data).unwrap_or(DropEffect::Reject);
+                    self.is_dragging.set(false);
+
+                    self.deferred_event(move |_, e| e.drag_finished(effect));
+                    return 0;
+                }
+
+                WM_USER_OWNER_MOVED => {
+                    self.follow_owner();
+                    return 0;
+                }
+
+                // the sizing loop takes the mouse capture, and WM_CAPTURECHANGED reports the held
+                // buttons as released
+                WM_USER_RESIZE_START => {
+                    let mut point = POINT { x: 0, y: 0 };
+                    GetCursorPos(&mut point);
+                    ReleaseCapture();
+
+                    // the same as pressing the mouse on the border of a decorated window
+                    let position =
+                        (point.x as u16 as u32 | (point.y as u16 as u32) << 16) as LPARAM;
+                    DefWindowProcW(hwnd, WM_NCLBUTTONDOWN, wparam, position);
                     return 0;
                 }
 
                 WM_USER_CLOSE_WINDOW => {
+                    self.is_closing.set(true);
                     DestroyWindow(self.hwnd);
                     return 0;
                 }
@@ -738,16 +1861,31 @@ impl PlatformWindow for WindowImpl {
     }
 
     fn close(&self) {
+        // stop dispatching right away, whatever is still queued before the close
+        // message is dropped
+        if self.is_closing.replace(true) {
+            return;
+        }
+
         unsafe {
             PostMessageW(self.hwnd, WM_USER_CLOSE_WINDOW, 0, 0);
         }
     }
 
+    fn is_closed(&self) -> bool {
+        self.is_closing.get()
+    }
+
     fn waker(&self) -> WindowWaker {
         WindowWaker(self.waker.clone())
     }
 
+    #[cfg(feature = "opengl")]
     fn opengl(&self) -> Result<&dyn PlatformOpenGl, OpenGlError> {
+        if let Some(gl) = &self.external_gl_context {
+            return Ok(gl);
+        }
+
         match &self.gl_context {
             Ok(gl) => Ok(gl),
             Err(err) => Err(err.clone()),
@@ -758,6 +1896,18 @@ impl PlatformWindow for WindowImpl {
         self.current_dpi_scale.get() as f64 / USER_DEFAULT_SCREEN_DPI as f64
     }
 
+    fn color_profile(&self) -> Option<IccProfile> {
+        self.current_color_profile.borrow().clone()
+    }
+
+    fn metal_layer(&self) -> Option<NonNull<c_void>> {
+        None
+    }
+
+    fn user_data(&self) -> &RefCell<Option<Rc<dyn Any>>> {
+        &self.user_data
+    }
+
     fn set_title(&self, title: &str) {
         unsafe {
             let title = WideString::from(title);
@@ -771,22 +1921,42 @@ impl PlatformWindow for WindowImpl {
                 return;
             }
 
-            let mut style = self.current_window_style.get().0;
-            if decorations {
-                style |= WS_OVERLAPPEDWINDOW;
-                style &= !WS_POPUP;
+            let (old_style, exstyle) = self.current_window_style.get();
+            let style = if decorations {
+                (old_style | WS_OVERLAPPEDWINDOW) & !WS_POPUP
             } else {
-                style &= !WS_OVERLAPPEDWINDOW;
-                style |= WS_POPUP;
+                // only remove the frame styles we added, not the ones asked for
+                (old_style & !(WS_OVERLAPPEDWINDOW & !self.extra_window_style)) | WS_POPUP
+            };
+
+            if style == old_style {
+                return;
             }
 
             SetWindowLongW(self.hwnd, GWL_STYLE, style as _);
-            self.current_window_style
-                .update(|(_, exstyle)| (style, exstyle));
+            self.current_window_style.set((style, exstyle));
+
+            // the cached frame is only recalculated with SWP_FRAMECHANGED. restyling keeps
+            // the outer size, so resize it to keep the client size the same (unless
+            // maximized, the frame then fills the monitor either way)
+            let size = self
+                .convert_client(Rect::from_size(self.current_window_size.get()), true)
+                .size();
+            let keep_size = if IsZoomed(self.hwnd) != 0 {
+                SWP_NOSIZE
+            } else {
+                0
+            };
 
-            // force a resize (restyling keeps the outer size while changing the inner size,
-            // so we need to resize the window to keep the client size the same)
-            self.set_size(self.current_window_size.replace(Size::default()));
+            SetWindowPos(
+                self.hwnd,
+                null_mut(),
+                0,
+                0,
+                size.width.try_into().unwrap_or(i32::MAX),
+                size.height.try_into().unwrap_or(i32::MAX),
+                SWP_NOZORDER | SWP_NOMOVE | SWP_NOACTIVATE | SWP_FRAMECHANGED | keep_size,
+            );
         }
     }
 
@@ -798,10 +1968,8 @@ impl PlatformWindow for WindowImpl {
 
     fn set_cursor_position(&self, point: Point) {
         unsafe {
-            let mut point = POINT {
-                x: point.x as i32,
-                y: point.y as i32,
-            };
+            let (x, y) = point.to_pixels();
+            let mut point = POINT { x, y };
 
             if ClientToScreen(self.hwnd, &mut point) != 0 {
                 SetCursorPos(point.x, point.y);
@@ -811,6 +1979,12 @@ impl PlatformWindow for WindowImpl {
 
     fn set_size(&self, size: Size) {
         unsafe {
+            let size = self.current_resize_hints.get().constrain(
+                size,
+                self.current_min_window_size.get(),
+                self.current_max_window_size.get(),
+            );
+
             // do nothing if the size doesnt change
             if self.current_window_size.get() == size {
                 return;
@@ -822,30 +1996,91 @@ impl PlatformWindow for WindowImpl {
                 self.hwnd,
                 0,
                 0,
-                size.width as i32,
-                size.height as i32,
+                size.width.try_into().unwrap_or(i32::MAX),
+                size.height.try_into().unwrap_or(i32::MAX),
                 SWP_NOZORDER | SWP_NOMOVE | SWP_NOACTIVATE,
             );
         }
     }
 
     fn set_min_size(&self, size: Size) {
-        let size = self.convert_client(Rect::from_size(size), true).size();
         self.current_min_window_size.set(size);
     }
 
     fn set_max_size(&self, size: Size) {
-        let size = self.convert_client(Rect::from_size(size), true).size();
         self.current_max_window_size.set(size);
     }
 
+    fn set_resize_hints(&self, hints: ResizeHints) {
+        self.current_resize_hints.set(hints);
+    }
+
+    fn resize_hints(&self) -> ResizeHints {
+        self.current_resize_hints.get()
+    }
+
     fn set_position(&self, point: Point) {
+        let (x, y) = point.to_pixels();
         unsafe {
             SetWindowPos(
                 self.hwnd,
                 self.hwnd,
-                point.x as i32,
-                point.y as i32,
+                x,
+                y,
+                0,
+                0,
+                SWP_NOZORDER | SWP_NOSIZE | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    fn center(&self, monitor: MonitorSelector) {
+        unsafe {
+            let mut window = zeroed::<RECT>();
+            GetWindowRect(self.hwnd, &mut window);
+
+            let area = if matches!(self.open_mode, OpenMode::Embedded(..)) {
+                let mut area = zeroed::<RECT>();
+                GetClientRect(GetParent(self.hwnd), &mut area);
+                area
+            } else {
+                let monitor = match monitor {
+                    MonitorSelector::Primary => {
+                        MonitorFromPoint(POINT { x: 0, y: 0 }, MONITOR_DEFAULTTOPRIMARY)
+                    }
+                    MonitorSelector::Cursor => {
+                        let mut point = zeroed::<POINT>();
+                        GetCursorPos(&mut point);
+                        MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST)
+                    }
+                    MonitorSelector::Current => {
+                        // transient windows and group members go on the monitor of their
+                        // owner, which is where the user is looking
+                        let owner = GetWindow(self.hwnd, GW_OWNER);
+                        let anchor = if owner.is_null() { self.hwnd } else { owner };
+                        MonitorFromWindow(anchor, MONITOR_DEFAULTTONEAREST)
+                    }
+                };
+
+                let mut info = MONITORINFO {
+                    cbSize: size_of::<MONITORINFO>() as u32,
+                    ..zeroed()
+                };
+
+                if GetMonitorInfoW(monitor, &mut info) == 0 {
+                    return;
+                }
+
+                info.rcWork
+            };
+
+            let width = window.right - window.left;
+            let height = window.bottom - window.top;
+            SetWindowPos(
+                self.hwnd,
+                self.hwnd,
+                area.left + (area.right - area.left - width) / 2,
+                area.top + (area.bottom - area.top - height) / 2,
                 0,
                 0,
                 SWP_NOZORDER | SWP_NOSIZE | SWP_NOACTIVATE,
@@ -875,66 +2110,329 @@ impl PlatformWindow for WindowImpl {
         }
     }
 
-    fn open_url(&self, url: &str) -> bool {
-        let path = WideString::from(url);
-        let verb = WideString::from("open");
+    fn open_url(&self, url: &str, on_done: OpenUrlCallback) {
+        open_url_in_background(url, on_done, open_url);
+    }
 
+    fn get_clipboard(&self, format: Option<&ClipboardFormat>) -> Result<Exchange, ClipboardError> {
         unsafe {
-            ShellExecuteW(
-                self.hwnd,
-                verb.as_ptr(),
-                path.as_ptr(),
-                null(),
-                null(),
-                SW_SHOWDEFAULT,
-            ) as usize
-                > 32
+            // another application has the clipboard open
+            let clipboard = match Clipboard::open(self.hwnd) {
+                Some(clipboard) => clipboard,
+                None => return Err(ClipboardError::AccessDenied),
+            };
+
+            for format in clipboard_formats(format) {
+                let exchange = match format {
+                    ClipboardFormat::Files => clipboard
+                        .get(CF_HDROP, |hdrop| decode_hdrop(hdrop.as_ptr() as _))
+                        .map(Exchange::Files),
+                    ClipboardFormat::Text => clipboard
+                        .get(CF_UNICODETEXT, |data| {
+                            WideString::from_iter(data.iter().copied()).to_string_lossy()
+                        })
+                        .map(Exchange::Text),
+                    // PNG keeps the alpha channel, the system provides any other bitmap as CF_DIBV5
+                    ClipboardFormat::Image => register_format("PNG")
+                        .and_then(|format| clipboard.get(format, png::decode))
+                        .flatten()
+                        .or_else(|| clipboard.get(CF_DIBV5, decode_dib).flatten())
+                        .map(Exchange::Image),
+                    ClipboardFormat::Custom(name) => register_format(name)
+                        .and_then(|format| clipboard.get(format, <[u8]>::to_vec))
+                        .map(|data| Exchange::Custom {
+                            format: name.clone(),
+                            data,
+                        }),
+                };
+
+                if let Some(exchange) = exchange {
+                    return Ok(exchange);
+                }
+            }
+
+            match clipboard.is_empty() {
+                true => Err(ClipboardError::Empty),
+                false => Err(ClipboardError::UnsupportedFormat),
+            }
         }
     }
 
-    fn get_clipboard(&self) -> Exchange {
+    fn set_clipboard(&self, data: Exchange) -> Result<(), ClipboardError> {
         unsafe {
+            // another application has the clipboard open
             let clipboard = match Clipboard::open(self.hwnd) {
                 Some(clipboard) => clipboard,
-                None => return Exchange::Empty,
+                None => return Err(ClipboardError::AccessDenied),
             };
 
-            if let Some(files) = clipboard.get(CF_HDROP, |hdrop| decode_hdrop(hdrop.as_ptr() as _))
-            {
-                return Exchange::Files(files);
+            // take ownership and drop the old formats, so they do not outlive the new data
+            if !clipboard.empty() {
+                return Err(ClipboardError::AccessDenied);
             }
 
-            if let Some(text) = clipboard.get(CF_UNICODETEXT, |data| {
-                WideString::from_iter(data.iter().copied()).to_string_lossy()
-            }) {
-                return Exchange::Text(text);
+            let is_set = match data {
+                Exchange::Empty => true,
+                Exchange::Files(files) => clipboard.set(CF_HDROP, &encode_hdrop(&files)),
+                Exchange::Text(text) => clipboard.set(
+                    CF_UNICODETEXT,
+                    WideString::from(text.as_str()).as_bytes_with_nul(),
+                ),
+                // the DIB for everyone, the PNG for the applications that keep the alpha
+                Exchange::Image(image) => {
+                    clipboard.set(CF_DIBV5, &encode_dib(&image))
+                        && register_format("PNG")
+                            .is_some_and(|format| clipboard.set(format, &png::encode(&image)))
+                }
+                Exchange::Custom { format, data } => match register_format(&format) {
+                    Some(format) => clipboard.set(format, &data),
+                    None => return Err(ClipboardError::UnsupportedFormat),
+                },
+            };
+
+            match is_set {
+                true => Ok(()),
+                false => Err(ClipboardError::Platform(
+                    Win32Error::last_error()
+                        .with_context("SetClipboardData")
+                        .to_string(),
+                )),
             }
+        }
+    }
+
+    fn start_drag(&self, data: Exchange) -> Result<(), DragError> {
+        if self.ole_apartment.is_none() {
+            return Err(DragError::Unsupported);
+        }
 
-            Exchange::Empty
+        if self.is_dragging.get() || self.drag_pending.borrow().is_some() {
+            return Err(DragError::InProgress);
         }
+
+        if self.current_buttons_held.get() == 0 {
+            return Err(DragError::NoButtonHeld);
+        }
+
+        // DoDragDrop blocks until the drop, so it has to wait for the handler to return
+        self.drag_pending.replace(Some(data));
+        unsafe {
+            PostMessageW(self.hwnd, WM_USER_DRAG_START, 0, 0);
+        }
+
+        Ok(())
     }
 
-    fn set_clipboard(&self, data: Exchange) -> bool {
+    fn set_cursor_grab(&self, mode: CursorGrab) -> Result<(), CursorGrabError> {
         unsafe {
-            let clipboard = match Clipboard::open(self.hwnd) {
-                Some(clipboard) => clipboard,
-                None => return false,
-            };
+            if mode == CursorGrab::None {
+                self.release_cursor_grab();
+                return Ok(());
+            }
+
+            if IsWindowVisible(self.hwnd) == 0 {
+                return Err(CursorGrabError::Unavailable);
+            }
+
+            self.current_cursor_grab.set(mode);
+            self.clip_cursor();
+
+            match mode {
+                CursorGrab::Locked if self.current_cursor_lock.get().is_none() => {
+                    // lock where the cursor is, or in the middle if it was outside of the window
+                    let point = self.current_mouse_position.get().unwrap_or_else(|| {
+                        let mut rect = zeroed::<RECT>();
+                        GetClientRect(self.hwnd, &mut rect);
+                        Point {
+                            x: (rect.right / 2) as f64,
+                            y: (rect.bottom / 2) as f64,
+                        }
+                    });
+
+                    let (x, y) = point.to_pixels();
+                    let anchor = Point {
+                        x: x as f64,
+                        y: y as f64,
+                    };
 
-            match data {
-                Exchange::Empty => clipboard.empty(),
-                Exchange::Files(files) => {
-                    clipboard.set(CF_HDROP, &encode_hdrop(&files));
+                    self.current_cursor_lock.set(Some((anchor, point)));
+                    self.current_mouse_position.set(Some(point));
+                    self.set_cursor_position(anchor);
                 }
-                Exchange::Text(text) => {
-                    clipboard.set(
-                        CF_UNICODETEXT,
-                        WideString::from(text.as_str()).as_bytes_with_nul(),
-                    );
+                CursorGrab::Locked => {}
+                _ => self.unlock_cursor(),
+            }
+
+            Ok(())
+        }
+    }
+
+    fn begin_drag_resize(&self, edge: ResizeEdge) -> Result<(), DragError> {
+        if matches!(self.open_mode, OpenMode::Embedded(..)) {
+            return Err(DragError::Unsupported);
+        }
+
+        if self.current_buttons_held.get() == 0 {
+            return Err(DragError::NoButtonHeld);
+        }
+
+        let hit = match edge {
+            ResizeEdge::Top => HTTOP,
+            ResizeEdge::Bottom => HTBOTTOM,
+            ResizeEdge::Left => HTLEFT,
+            ResizeEdge::Right => HTRIGHT,
+            ResizeEdge::TopLeft => HTTOPLEFT,
+            ResizeEdge::TopRight => HTTOPRIGHT,
+            ResizeEdge::BottomLeft => HTBOTTOMLEFT,
+            ResizeEdge::BottomRight => HTBOTTOMRIGHT,
+        };
+
+        unsafe {
+            if self.current_window_style.get().0 & WS_THICKFRAME == 0 {
+                let mut start = POINT { x: 0, y: 0 };
+                let mut frame = zeroed::<RECT>();
+                GetCursorPos(&mut start);
+                GetWindowRect(self.hwnd, &mut frame);
+
+                // keep the capture of the held button, and report it as released like the
+                // sizing loop does
+                self.resize_drag.set(Some((edge, start, frame)));
+                let point = self.current_mouse_position.get().unwrap_or_default();
+                let held = self.current_buttons_held.replace(0);
+                for button in [
+                    MouseButton::Left,
+                    MouseButton::Right,
+                    MouseButton::Middle,
+                    MouseButton::Forward,
+                    MouseButton::Back,
+                ] {
+                    if held & (1 << button as u32) != 0 {
+                        self.deferred_event(move |_, e| e.mouse_press(button, false, point));
+                    }
                 }
+
+                return Ok(());
             }
 
-            true
+            // the sizing loop blocks until the button is released, so it has to wait for the
+            // handler to return
+            PostMessageW(self.hwnd, WM_USER_RESIZE_START, hit as WPARAM, 0);
+        }
+
+        Ok(())
+    }
+
+    fn has_mouse_capture(&self) -> bool {
+        // ask the OS, the capture can be taken away from us at any time
+        unsafe { GetCapture() == self.hwnd }
+    }
+
+    fn has_keyboard_focus(&self) -> bool {
+        self.current_window_focused.get()
+    }
+
+    fn cursor_grab(&self) -> CursorGrab {
+        self.current_cursor_grab.get()
+    }
+
+    fn last_event_token(&self) -> EventToken {
+        self.current_event_token.get()
+    }
+
+    fn id(&self) -> WindowId {
+        self.entry.id()
+    }
+
+    fn to_screen(&self, point: Point) -> Point {
+        unsafe {
+            let _dpi_awareness = self.dpi_context.enter_per_monitor_aware_v2();
+            let mut origin = POINT { x: 0, y: 0 };
+            ClientToScreen(self.hwnd, &mut origin);
+
+            point + Point::from((origin.x, origin.y))
+        }
+    }
+
+    fn safe_area_insets(&self) -> Insets {
+        Insets::default()
+    }
+
+    fn inner_size(&self) -> Size {
+        self.current_window_size.get()
+    }
+
+    fn frame_insets(&self) -> Insets {
+        // the window rect of an empty client rect is the frame itself, child
+        // windows have no frame and come out empty
+        let frame = self.convert_client(Rect::default(), true);
+        Insets {
+            top: frame.top.unsigned_abs(),
+            left: frame.left.unsigned_abs(),
+            bottom: frame.bottom.unsigned_abs(),
+            right: frame.right.unsigned_abs(),
+        }
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            jitter: self.frame_jitter.get(),
+            dropped_events: self.event_deferred.dropped(),
+            coalesced_events: self.event_deferred.coalesced(),
+        }
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.is_down(key)
+    }
+
+    fn set_ime_cursor_area(&self, area: Rect) {
+        self.current_ime_cursor_area.set(Some(area));
+        set_ime_cursor_area(self.hwnd, area);
+    }
+
+    fn scroll_settings(&self) -> ScrollSettings {
+        query_scroll_settings()
+    }
+
+    fn present_pixels(
+        &self,
+        buffer: &[u32],
+        size: Size,
+        damage: Damage<'_>,
+    ) -> Result<(), PresentError> {
+        let mut section = self.dib_section.borrow_mut();
+        let section = match &mut *section {
+            Some(section) if section.size() == size => section,
+            section => section.insert(DibSection::new(size)?),
+        };
+
+        unsafe { Ok(section.present(self.hwnd, buffer, damage)?) }
+    }
+
+    fn redraw_now(&self) {
+        // from inside the handler, or from the factory before there is one
+        if matches!(self.event_handler.try_borrow().as_deref(), Ok(Some(_))) {
+            self.non_reentrant_event(|e| e.frame());
+        } else {
+            self.redraw_pending.set(true);
+        }
+    }
+
+    fn set_timer(&self, id: u32, interval: Duration) {
+        // zero is not a valid timer id, setting an existing one restarts it
+        unsafe {
+            SetTimer(
+                self.hwnd,
+                (id as usize).wrapping_add(1),
+                interval.as_millis().try_into().unwrap_or(u32::MAX),
+                None,
+            );
+        }
+    }
+
+    fn cancel_timer(&self, id: u32) {
+        unsafe {
+            KillTimer(self.hwnd, (id as usize).wrapping_add(1));
         }
     }
 }
@@ -953,4 +2451,77 @@ impl PlatformWaker for WindowWakerImpl {
 
         Ok(())
     }
+
+    fn close(&self) -> Result<(), WakeupError> {
+        let guard = self.window_hwnd.read().expect("lock poisoned");
+
+        if guard.is_null() {
+            return Err(WakeupError);
+        }
+
+        unsafe {
+            PostMessageW(*guard, WM_USER_CLOSE_WINDOW, 0, 0);
+        }
+
+        Ok(())
+    }
+
+    fn close_and_wait(&self) {
+        // the lock must be released before the window is destroyed, as that clears the
+        // handle
+        let hwnd = *self.window_hwnd.read().expect("lock poisoned");
+
+        if hwnd.is_null() {
+            return;
+        }
+
+        unsafe {
+            if GetWindowThreadProcessId(hwnd, null_mut()) == GetCurrentThreadId() {
+                // on the window's own thread, possibly from inside of its handler, where
+                // destroying the window right away would drop the handler while borrowed
+                PostMessageW(hwnd, WM_USER_CLOSE_WINDOW, 0, 0);
+            } else {
+                // blocks until the window's thread processes the message
+                SendMessageW(hwnd, WM_USER_CLOSE_WINDOW, 0, 0);
+            }
+        }
+    }
+}
+
+/// Subclass of the top-level owner of a window that moves along with it, see
+/// [`WindowBuilder::with_follow_parent`]. `data` is the window.
+unsafe extern "system" fn owner_subclass_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    _id: usize,
+    data: usize,
+) -> LRESULT {
+    unsafe {
+        if msg == WM_WINDOWPOSCHANGED && (*(lparam as *const WINDOWPOS)).flags & SWP_NOMOVE == 0 {
+            SendMessageW(data as HWND, WM_USER_OWNER_MOVED, 0, 0);
+        }
+
+        DefSubclassProc(hwnd, msg, wparam, lparam)
+    }
+}
+
+/// Subclass of the top-level ancestor of an embedded window that forwards
+/// `WM_SETTINGCHANGE` to it. `data` is the window.
+unsafe extern "system" fn settings_subclass_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    _id: usize,
+    data: usize,
+) -> LRESULT {
+    unsafe {
+        if msg == WM_SETTINGCHANGE {
+            SendMessageW(data as HWND, WM_SETTINGCHANGE, wparam, lparam);
+        }
+
+        DefSubclassProc(hwnd, msg, wparam, lparam)
+    }
 }
@@ -0,0 +1,120 @@
+use super::util::wait_for_events;
+use super::window::WindowImpl;
+use crate::WindowError;
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
+
+thread_local! {
+    /// Windows opened on this thread that the event loop did not pick up yet.
+    /// Boxed, as the handler refers to the window.
+    #[allow(clippy::vec_box)]
+    static PENDING: RefCell<Vec<Box<WindowImpl>>> = const { RefCell::new(Vec::new()) };
+    /// Is [`run`] running on this thread?
+    static IS_RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Check if an event loop runs on this thread, windows opened from it join
+/// the loop instead of spawning a thread.
+pub fn is_running() -> bool {
+    IS_RUNNING.get()
+}
+
+/// Hand a started window to the event loop of this thread. It is picked up
+/// at the start of the next iteration, or once [`run`] is called.
+pub fn add(window: Box<WindowImpl>) {
+    PENDING.with_borrow_mut(|pending| pending.push(window));
+}
+
+/// Run the windows of this thread until all of them are closed. Returns the
+/// first error any of them failed with.
+///
+/// Every window has a display connection of its own, the loop waits on all of
+/// them at once.
+pub fn run() -> Result<(), WindowError> {
+    /// Resets [`IS_RUNNING`], even if a handler panics.
+    struct Running;
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            IS_RUNNING.set(false);
+        }
+    }
+
+    // a nested loop would run the windows of the outer one from a handler
+    if IS_RUNNING.get() {
+        return Err(WindowError::EventLoopRunning);
+    }
+
+    IS_RUNNING.set(true);
+    let _running = Running;
+
+    let mut windows = Vec::<Box<WindowImpl>>::new();
+    let mut result = Ok(());
+
+    // kept across iterations, so that waiting for events does not allocate
+    let mut wakeups = Vec::new();
+    let mut connections = Vec::new();
+    let mut wakers = Vec::new();
+    let mut pollfds = Vec::new();
+
+    loop {
+        windows.extend(PENDING.take());
+
+        // closed windows are dropped here, which sends `destroyed` to their handler.
+        // windows it opens are picked up by the next iteration
+        windows.retain(|window| !window.is_finished());
+        if windows.is_empty() {
+            if PENDING.with_borrow(Vec::is_empty) {
+                break;
+            }
+
+            continue;
+        }
+
+        let now = Instant::now();
+        wakeups.clear();
+        wakeups.extend(windows.iter().map(|window| window.poll_prepare(now)));
+
+        // wait until events arrive for any of the windows, or the first of them has to
+        // wake up anyway
+        let deadline = wakeups
+            .iter()
+            .flatten()
+            .map(|(deadline, _)| *deadline)
+            .min();
+        connections.clear();
+        connections.extend(windows.iter().map(|window| window.connection().clone()));
+        wakers.clear();
+        wakers.extend(windows.iter().map(|window| window.waker_fd()));
+
+        let waited = wait_for_events(
+            &connections,
+            &wakers,
+            deadline.map(|deadline| deadline.saturating_duration_since(now)),
+            &mut pollfds,
+        );
+
+        // the handles must not keep the connection of a closed window open
+        connections.clear();
+
+        if let Err(error) = waited {
+            for window in &windows {
+                let error = window.fail(error.clone());
+                if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+
+        for (window, wakeup) in windows.iter().zip(wakeups.drain(..)) {
+            match wakeup {
+                Ok(_) if window.is_finished() => {}
+                Ok(wakeup) => window.poll_dispatch(wakeup),
+                Err(error) if result.is_ok() => result = Err(error),
+                Err(_) => {}
+            }
+        }
+    }
+
+    result
+}
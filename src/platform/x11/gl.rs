@@ -32,6 +32,9 @@ pub struct GlContext {
     /// The GLX context itself.
     context: GLXContext,
 
+    /// The version of the config the context was created with.
+    version: GlVersion,
+
     /// The X11 connection, used for keeping it alive (some drivers crash if the
     /// connection is closed before we destroy the GL context)
     connection: Connection,
@@ -162,12 +165,16 @@ impl GlContext {
     }
 
     /// Creates a GLX context for the given window and visual config.
+    ///
+    /// Falls back to a legacy context of any version if the requested one is
+    /// not available, but only if this is the `last_resort`.
     #[allow(non_snake_case)]
     pub unsafe fn new(
         connection: Connection,
         window: c_ulong,
         config: GlConfig,
-        visual: VisualConfig,
+        visual: &VisualConfig,
+        last_resort: bool,
     ) -> Result<GlContext, OpenGlError> {
         if visual.glx_config().is_null() {
             return Err(OpenGlError::FormatUnsupported);
@@ -239,6 +246,13 @@ impl GlContext {
                 null_mut()
             };
 
+            if context.is_null() && !last_resort {
+                // take the error the failed attempt raised, so it is not blamed on
+                // the next config in the chain
+                let _ = connection.last_error();
+                return Err(OpenGlError::VersionUnsupported);
+            }
+
             if context.is_null() {
                 context = glXCreateContext(
                     connection.as_raw(),
@@ -272,6 +286,7 @@ impl GlContext {
             Ok(GlContext {
                 window,
                 context,
+                version: config.version,
                 connection,
             })
         }
@@ -319,11 +334,17 @@ impl PlatformOpenGl for GlContext {
                 }
             };
 
+            // glXMakeCurrent fails when the window or context is gone, or does not match
+            // the context anymore
             if result == 0 {
-                Err(MakeCurrentError)
+                Err(MakeCurrentError::Lost)
             } else {
                 Ok(())
             }
         }
     }
+
+    fn version(&self) -> Option<GlVersion> {
+        Some(self.version)
+    }
 }
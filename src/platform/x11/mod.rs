@@ -1,3 +1,5 @@
+mod event_loop;
+#[cfg(feature = "opengl")]
 mod gl;
 mod util;
 mod window;
@@ -8,3 +10,21 @@ pub unsafe fn open_window(
 ) -> Result<crate::WindowWaker, crate::WindowError> {
     unsafe { window::WindowImpl::open(options, mode) }
 }
+
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub unsafe fn run_event_loop() -> Result<(), crate::WindowError> {
+    event_loop::run()
+}
+
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub fn is_event_loop_running() -> bool {
+    event_loop::is_running()
+}
+
+pub fn find_window_at(point: crate::Point) -> Option<crate::WindowId> {
+    window::WindowImpl::window_at(point)
+}
+
+pub fn query_system_metrics() -> crate::SystemMetrics {
+    window::WindowImpl::system_metrics()
+}
@@ -1,63 +1,77 @@
 pub mod connection;
 pub mod cursor;
+pub mod image;
+pub mod ime;
 pub mod info;
 pub mod input;
 pub mod visual;
+pub mod xdnd;
+pub mod xsettings;
 
-use crate::Point;
+use crate::{Insets, OpenUrlError, Point};
 use std::ffi::c_ulong;
+use std::io::ErrorKind;
 use std::os::unix::process::CommandExt;
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitStatus, Stdio};
 use x11::xlib::*;
 
-/// Open the given URL with the default system handler. Returns `true` if we
-/// successfully started a process.
+/// Open the given URL with the default system handler. Only waits until the
+/// opener has started, not until it exits: some keep running for as long as
+/// the application they launched.
 ///
-/// Tries a bunch of different `open` commands.
-pub fn open_url(path: &str) -> bool {
-    /// Spawns a process in a detached state, so it won't be killed when the
-    /// parent process exits.
-    fn spawn_detached(cmd: &mut Command) -> std::io::Result<()> {
+/// Tries a bunch of different `open` commands, the first one that is installed
+/// wins.
+pub fn open_url(path: &str) -> Result<(), OpenUrlError> {
+    /// Starts an opener in a session of its own, so the application it starts
+    /// is not killed along with our process group. The child forks again and
+    /// exits right away, leaving the opener to be reaped by init. Spawning
+    /// still fails if the opener can't be executed, the grandchild reports
+    /// that through the same pipe the child would.
+    fn spawn_detached(cmd: &mut Command) -> std::io::Result<ExitStatus> {
         cmd.stdin(Stdio::null())
             .stdout(Stdio::null())
             .stderr(Stdio::null());
 
         unsafe {
-            cmd.pre_exec(move || {
-                match libc::fork() {
-                    -1 => return Err(std::io::Error::last_os_error()),
-                    0 => (),
-                    _ => libc::_exit(0),
-                }
-
+            cmd.pre_exec(|| {
                 if libc::setsid() == -1 {
                     return Err(std::io::Error::last_os_error());
                 }
 
-                Ok(())
+                match libc::fork() {
+                    -1 => Err(std::io::Error::last_os_error()),
+                    0 => Ok(()),
+                    _ => libc::_exit(0),
+                }
             });
         }
 
-        cmd.spawn().map(|_| ())
-    }
-
-    if spawn_detached(Command::new("xdg-open").arg(path)).is_ok() {
-        return true;
-    }
-
-    if spawn_detached(Command::new("gio").args(["open", path])).is_ok() {
-        return true;
+        // only waits for the child, which exits as soon as it forked
+        cmd.status()
     }
 
-    if spawn_detached(Command::new("gnome-open").arg(path)).is_ok() {
-        return true;
-    }
-
-    if spawn_detached(Command::new("kde-open").arg(path)).is_ok() {
-        return true;
+    let commands: [&[&str]; 4] = [
+        &["xdg-open", path],
+        &["gio", "open", path],
+        &["gnome-open", path],
+        &["kde-open", path],
+    ];
+
+    let mut error = OpenUrlError::NoHandler;
+    for command in commands {
+        let Some((program, args)) = command.split_first() else {
+            continue;
+        };
+
+        match spawn_detached(Command::new(program).args(args)) {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => error = OpenUrlError::Platform(format!("{program}: {status}")),
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => error = OpenUrlError::Platform(format!("{program}: {err}")),
+        }
     }
 
-    false
+    Err(error)
 }
 
 /// Returns the position of the given window's client area relative to the root
@@ -89,29 +103,67 @@ pub fn window_position(conn: &Connection, window_id: c_ulong) -> Option<Point> {
     }
 }
 
+/// Returns the size of the frame the window manager put around the window,
+/// from `_NET_FRAME_EXTENTS`. `None` if the window manager did not set it
+/// (yet, it is set once the window is mapped) or does not support it.
+pub fn frame_extents(conn: &Connection, window_id: c_ulong) -> Option<Insets> {
+    let property = conn.get_property(
+        window_id,
+        conn.atom(c"_NET_FRAME_EXTENTS"),
+        XA_CARDINAL,
+        false,
+    )?;
+
+    // in the order left, right, top, bottom
+    match *property.items32() {
+        [left, right, top, bottom] => {
+            let edge = |value: c_ulong| value.try_into().unwrap_or(u32::MAX);
+            Some(Insets {
+                top: edge(top),
+                left: edge(left),
+                bottom: edge(bottom),
+                right: edge(right),
+            })
+        }
+        _ => None,
+    }
+}
+
 pub use connection::*;
 pub use cursor::*;
+pub use image::*;
+pub use ime::*;
 pub use info::*;
 pub use input::*;
 pub use selection::*;
 pub use visual::*;
+pub use xdnd::*;
+pub use xsettings::*;
 
 mod selection {
     use super::Connection;
-    use crate::{DropEffect, Exchange};
+    use crate::platform::png;
+    use crate::{ClipboardFormat, DropEffect, Exchange};
     use std::array::from_fn;
-    use std::ffi::{OsStr, OsString, c_char, c_int, c_ulong};
+    use std::ffi::{OsStr, OsString, c_char, c_int, c_long, c_ulong};
     use std::mem::zeroed;
     use std::os::unix::ffi::OsStrExt;
     use std::path::PathBuf;
-    use std::ptr::null_mut;
+    use std::time::{Duration, Instant};
     use x11::xlib::*;
 
+    /// How long the owner of a selection has to answer a request, or to send
+    /// the next part of a value sent in parts.
+    const SELECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
     /// An error that can occur when requesting a selection value.
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
     pub enum SelectionError {
         /// Selection is empty
         Empty,
+        /// Selection owner could not convert the selection to the requested
+        /// target
+        Refused,
         /// Selection is owned by the current window and must be handled
         /// separately to avoid a deadlock
         Reentrant,
@@ -168,8 +220,62 @@ mod selection {
             .collect()
     }
 
+    /// Wait for the first event `filter` accepts and take it out of the queue,
+    /// leaving the others. `None` if none arrived within
+    /// [`SELECTION_TIMEOUT`].
+    fn wait_for_event(
+        conn: &Connection,
+        mut filter: impl FnMut(&XEvent) -> bool,
+    ) -> Option<XEvent> {
+        unsafe extern "C" fn predicate(_: *mut Display, e: *mut XEvent, arg: *mut c_char) -> c_int {
+            unsafe {
+                let filter = &mut *(arg as *mut &mut dyn FnMut(&XEvent) -> bool);
+                filter(&*e) as _
+            }
+        }
+
+        let deadline = Instant::now() + SELECTION_TIMEOUT;
+        let mut filter: &mut dyn FnMut(&XEvent) -> bool = &mut filter;
+
+        unsafe {
+            loop {
+                // reads whatever arrived, so waiting on the socket afterwards does not miss
+                // anything
+                let mut event = zeroed();
+                if XCheckIfEvent(
+                    conn.as_raw(),
+                    &mut event,
+                    Some(predicate),
+                    &mut filter as *mut _ as *mut c_char,
+                ) != 0
+                {
+                    return Some(event);
+                }
+
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return None;
+                }
+
+                let mut fd = libc::pollfd {
+                    fd: XConnectionNumber(conn.as_raw()),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+
+                let timeout = remaining.as_millis().clamp(1, c_int::MAX as u128) as c_int;
+                if libc::poll(&mut fd, 1, timeout) == -1
+                    && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted
+                {
+                    return None;
+                }
+            }
+        }
+    }
+
     /// Request a selection value (clipboard/drag-n-drop) and wait for the
-    /// response.
+    /// response. Values too large for a single property arrive in parts
+    /// (the INCR protocol), which needs `PropertyChangeMask` on `window`.
     pub fn request_selection<R>(
         conn: &Connection,
         window: c_ulong,
@@ -179,123 +285,255 @@ mod selection {
         timestamp: c_ulong,
         f: impl FnOnce(&[u8]) -> R,
     ) -> Result<R, SelectionError> {
-        unsafe extern "C" fn event_filter(
-            _: *mut Display,
-            e: *mut XEvent,
-            _: *mut c_char,
-        ) -> c_int {
-            unsafe { ((*e).type_ == SelectionNotify) as _ }
-        }
-
         unsafe {
-            let owner = XGetSelectionOwner(conn.as_raw(), selection);
+            let owner = conn.selection_owner(selection);
             if owner == 0 {
                 return Err(SelectionError::Empty);
             } else if window == owner {
                 return Err(SelectionError::Reentrant);
             }
 
-            let result = XConvertSelection(
-                conn.as_raw(),
-                selection,
-                target,
-                property,
-                window,
-                timestamp,
-            );
+            conn.convert_selection(selection, target, property, window, timestamp);
+            XSync(conn.as_raw(), 0);
 
-            if result == 0 {
-                return Err(SelectionError::Empty);
+            let event = wait_for_event(conn, |event| {
+                event.type_ == SelectionNotify && event.selection.requestor == window
+            })
+            .ok_or(SelectionError::Refused)?
+            .selection;
+
+            if event.property == 0 || event.selection != selection || event.target != target {
+                return Err(SelectionError::Refused);
             }
 
-            XSync(conn.as_raw(), 0);
+            // the property is deleted once read, telling the owner we are done
+            let value = conn
+                .get_property(window, event.property, AnyPropertyType as _, true)
+                .ok_or(SelectionError::Empty)?;
 
-            let event = {
-                let mut event = zeroed();
-                XIfEvent(conn.as_raw(), &mut event, Some(event_filter), null_mut());
-                event.selection
-            };
+            if value.kind != conn.atom(c"INCR") {
+                return Ok(f(&value.data));
+            }
 
-            if event.property == 0 || event.selection != selection || event.target != target {
-                return Err(SelectionError::Empty);
+            // deleting the INCR property asked for the first part, every part is deleted
+            // to ask for the next, until an empty one ends the value
+            let mut value = Vec::new();
+            loop {
+                wait_for_event(conn, |e| {
+                    e.type_ == PropertyNotify
+                        && e.property.window == window
+                        && e.property.atom == event.property
+                        && e.property.state == PropertyNewValue
+                })
+                .ok_or(SelectionError::Refused)?;
+
+                let part = conn
+                    .get_property(window, event.property, AnyPropertyType as _, true)
+                    .ok_or(SelectionError::Refused)?
+                    .data;
+
+                if part.is_empty() {
+                    return Ok(f(&value));
+                }
+
+                value.extend(part);
             }
+        }
+    }
 
-            let mut target = 0;
-            let mut format = 0;
-            let mut size = 0;
-            let mut nitems = 0;
-            let mut data = null_mut();
+    /// A selection value sent in parts (the INCR protocol), because it is
+    /// larger than a single request can hold. The requestor deletes the
+    /// property every time it read a part, see [`IncrTransfer::send_next`].
+    pub struct IncrTransfer {
+        pub requestor: c_ulong,
+        pub property: c_ulong,
+        target: c_ulong,
+        data: Vec<u8>,
+        offset: usize,
+        /// The events we selected on the requestor before, restored at the
+        /// end
+        previous_mask: c_long,
+    }
 
-            let result = XGetWindowProperty(
-                conn.as_raw(),
-                event.requestor,
-                event.property,
-                0,
-                !0,
-                0,
-                AnyPropertyType as _,
-                &mut target,
-                &mut format,
-                &mut size,
-                &mut nitems,
-                &mut data,
-            );
+    impl IncrTransfer {
+        /// Announce a transfer of `data` by setting the property of the
+        /// requestor to INCR, and watch for it being deleted.
+        pub fn start(
+            conn: &Connection,
+            requestor: c_ulong,
+            property: c_ulong,
+            target: c_ulong,
+            data: Vec<u8>,
+        ) -> Self {
+            unsafe {
+                // keep whatever else we listen to on the window, it may be one we watch
+                let mut attributes = zeroed::<XWindowAttributes>();
+                let previous_mask =
+                    match XGetWindowAttributes(conn.as_raw(), requestor, &mut attributes) {
+                        0 => NoEventMask,
+                        _ => attributes.your_event_mask,
+                    };
+
+                XSelectInput(conn.as_raw(), requestor, previous_mask | PropertyChangeMask);
+
+                // a lower bound of the size, all we know without encoding it again
+                let len = data.len().min(u32::MAX as usize) as c_ulong;
+                conn.set_property32(requestor, property, conn.atom(c"INCR"), &[len]);
+
+                Self {
+                    requestor,
+                    property,
+                    target,
+                    data,
+                    offset: 0,
+                    previous_mask,
+                }
+            }
+        }
 
-            if result != 0 || data.is_null() {
-                return Err(SelectionError::Empty);
+        /// Send the next part, after the requestor deleted the last one.
+        /// Returns `false` once the empty part that ends the value was sent.
+        pub fn send_next(&mut self, conn: &Connection) -> bool {
+            let rest = self.data.get(self.offset..).unwrap_or_default();
+            let part = rest.get(..conn.max_property_size()).unwrap_or(rest);
+            self.offset += part.len();
+
+            conn.set_property8(self.requestor, self.property, self.target, part);
+            if part.is_empty() {
+                self.cancel(conn);
+            }
+
+            !part.is_empty()
+        }
+
+        /// Stop watching the requestor for the transfer.
+        pub fn cancel(&self, conn: &Connection) {
+            unsafe {
+                XSelectInput(conn.as_raw(), self.requestor, self.previous_mask);
             }
+        }
+    }
 
-            let result = f(std::slice::from_raw_parts(
-                data as *const u8,
-                size.try_into().unwrap_or(usize::MAX),
-            ));
+    /// The targets a format is offered and read as, in the order they are
+    /// read.
+    pub fn selection_targets(conn: &Connection, format: &ClipboardFormat) -> Vec<c_ulong> {
+        match format {
+            ClipboardFormat::Text => vec![
+                conn.atom(c"text/plain"),
+                conn.atom(c"UTF8_STRING"),
+                XA_STRING,
+            ],
+            ClipboardFormat::Files => vec![conn.atom(c"text/uri-list")],
+            ClipboardFormat::Image => vec![conn.atom(c"image/png")],
+            ClipboardFormat::Custom(name) => match conn.intern(name) {
+                0 => Vec::new(),
+                atom => vec![atom],
+            },
+        }
+    }
 
-            XFree(data as *mut _);
-            Ok(result)
+    /// Encode `data` for the selection `target`, `None` if it is not offered
+    /// as that target.
+    pub fn encode_selection(
+        conn: &Connection,
+        data: &Exchange,
+        target: c_ulong,
+    ) -> Option<Vec<u8>> {
+        if !selection_targets(conn, &data.format()?).contains(&target) {
+            return None;
         }
+
+        Some(match data {
+            Exchange::Empty => return None,
+            Exchange::Text(text) => text.as_bytes().to_vec(),
+            Exchange::Files(files) => encode_uri_list(files).as_bytes().to_vec(),
+            Exchange::Image(image) => png::encode(image),
+            Exchange::Custom { data, .. } => data.clone(),
+        })
     }
 
     /// Read a selection via [`request_selection`] and decode it into an
-    /// [`Exchange`] value.
+    /// [`Exchange`] value, in the first of `formats` the owner provides.
     pub fn parse_selection(
         conn: &Connection,
         window: c_ulong,
         selection: c_ulong,
         property: c_ulong,
         timestamp: c_ulong,
+        formats: &[ClipboardFormat],
     ) -> Result<Exchange, SelectionError> {
-        let a_utf8_string = conn.atom(c"UTF8_STRING");
-        let a_text_uri_list = conn.atom(c"text/uri-list");
-        let a_text_plain = conn.atom(c"text/plain");
-
-        for atom in [a_text_uri_list, a_text_plain, a_utf8_string, XA_STRING] {
-            let result = request_selection(
-                conn,
-                window,
-                selection,
-                property,
-                atom,
-                timestamp,
-                |slice| {
-                    if atom == a_text_uri_list {
-                        Exchange::Files(decode_uri_list(OsStr::from_bytes(slice)))
-                    } else {
-                        Exchange::Text(String::from_utf8_lossy(slice).to_string())
-                    }
-                },
-            );
+        // ask for the targets the owner offers once, instead of requesting every one we
+        // can read. owners that do not answer get asked for all of them in turn
+        let offered = request_selection(
+            conn,
+            window,
+            selection,
+            property,
+            conn.atom(c"TARGETS"),
+            timestamp,
+            |data| {
+                data.chunks_exact(size_of::<c_ulong>())
+                    .filter_map(|atom| Some(c_ulong::from_ne_bytes(atom.try_into().ok()?)))
+                    .collect::<Vec<_>>()
+            },
+        );
+
+        let offered = match offered {
+            Ok(offered) => Some(offered),
+            Err(SelectionError::Reentrant) => return Err(SelectionError::Reentrant),
+            Err(_) => None,
+        };
+
+        // the owner may refuse some targets, only report that if it refused all of them
+        let mut error = match offered {
+            Some(_) => SelectionError::Refused,
+            None => SelectionError::Empty,
+        };
+        for format in formats {
+            for target in selection_targets(conn, format) {
+                if offered
+                    .as_ref()
+                    .is_some_and(|offered| !offered.contains(&target))
+                {
+                    continue;
+                }
 
-            match result {
-                Ok(Exchange::Empty) => continue,
-                Ok(exchange) => return Ok(exchange),
-                Err(SelectionError::Empty) => continue,
-                Err(SelectionError::Reentrant) => {
-                    return Err(SelectionError::Reentrant);
+                let result = request_selection(
+                    conn,
+                    window,
+                    selection,
+                    property,
+                    target,
+                    timestamp,
+                    |slice| match format {
+                        ClipboardFormat::Text => {
+                            Some(Exchange::Text(String::from_utf8_lossy(slice).to_string()))
+                        }
+                        ClipboardFormat::Files => {
+                            Some(Exchange::Files(decode_uri_list(OsStr::from_bytes(slice))))
+                        }
+                        ClipboardFormat::Image => png::decode(slice).map(Exchange::Image),
+                        ClipboardFormat::Custom(name) => Some(Exchange::Custom {
+                            format: name.clone(),
+                            data: slice.to_vec(),
+                        }),
+                    },
+                );
+
+                match result {
+                    Ok(Some(exchange)) => return Ok(exchange),
+                    // the data could not be decoded, like a damaged image
+                    Ok(None) => error = SelectionError::Refused,
+                    Err(SelectionError::Empty) => continue,
+                    Err(SelectionError::Refused) => error = SelectionError::Refused,
+                    Err(SelectionError::Reentrant) => {
+                        return Err(SelectionError::Reentrant);
+                    }
                 }
             }
         }
 
-        Err(SelectionError::Empty)
+        Err(error)
     }
 
     pub fn send_xdnd_feedback(
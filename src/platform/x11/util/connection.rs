@@ -1,41 +1,89 @@
 use raw_window_handle::XlibDisplayHandle;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::ffi::{CStr, c_char, c_ulong};
+use std::ffi::{CStr, CString, c_char, c_int, c_long, c_short, c_ulong};
 use std::ptr::{NonNull, null_mut};
 use std::rc::Rc;
 use std::sync::Mutex;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use x11::xlib::*;
+#[cfg(feature = "xcb")]
+use x11rb::connection::Connection as _;
+#[cfg(feature = "xcb")]
+use x11rb::protocol::xproto::{
+    ConnectionExt as _, EventMask, PropMode, SELECTION_NOTIFY_EVENT, SelectionNotifyEvent,
+};
+#[cfg(feature = "xcb")]
+use x11rb::wrapper::ConnectionExt as _;
+#[cfg(feature = "xcb")]
+use x11rb::xcb_ffi::XCBConnection;
 
-/// Wait for events with an optional timeout and return the number of
-/// pending events after the wait.
-pub fn wait_for_events(conn: &Connection, timeout: Option<Duration>) -> Result<u32, String> {
+/// Wait until events arrive on any of the connections, or any of the `fds`
+/// becomes readable, with an optional timeout. Returns right away if events
+/// were already read into the queue of one of the connections.
+///
+/// `pollfds` is scratch space, kept by the caller so that waiting does not
+/// allocate.
+pub fn wait_for_events(
+    conns: &[Connection],
+    fds: &[c_int],
+    timeout: Option<Duration>,
+    pollfds: &mut Vec<libc::pollfd>,
+) -> Result<(), String> {
     unsafe {
-        let timespec = timeout.map(|timeout| libc::timespec {
-            tv_sec: timeout.as_secs().try_into().unwrap_or(i64::MAX),
-            tv_nsec: timeout.subsec_nanos().into(),
-        });
+        let timeout = match conns
+            .iter()
+            // `QueuedAlready`, not exported by the `x11` crate
+            .any(|conn| XEventsQueued(conn.as_raw(), 0) > 0)
+        {
+            true => Some(Duration::ZERO),
+            false => timeout,
+        };
 
-        let result = libc::ppoll(
-            &mut libc::pollfd {
-                fd: XConnectionNumber(conn.as_raw()) as _,
-                events: libc::POLLIN,
-                revents: 0,
-            },
-            1 as _,
-            timespec
-                .as_ref()
-                .map(|x| x as *const _)
-                .unwrap_or(null_mut()),
-            null_mut(),
+        pollfds.clear();
+        pollfds.extend(
+            conns
+                .iter()
+                .map(|conn| XConnectionNumber(conn.as_raw()))
+                .chain(fds.iter().copied())
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                }),
         );
 
-        if result == -1 {
-            return Err(std::io::Error::last_os_error().to_string());
-        }
+        let start = Instant::now();
+        loop {
+            let timespec = timeout.map(|timeout| {
+                let timeout = timeout.saturating_sub(start.elapsed());
+                libc::timespec {
+                    tv_sec: timeout.as_secs().try_into().unwrap_or(i64::MAX),
+                    tv_nsec: timeout.subsec_nanos().into(),
+                }
+            });
+
+            let result = libc::ppoll(
+                pollfds.as_mut_ptr(),
+                pollfds.len() as _,
+                timespec
+                    .as_ref()
+                    .map(|x| x as *const _)
+                    .unwrap_or(null_mut()),
+                null_mut(),
+            );
 
-        Ok(XPending(conn.as_raw()) as u32)
+            if result != -1 {
+                return Ok(());
+            }
+
+            // a signal arrived, like SIGCHLD in a standalone application. not
+            // an error, wait again for the rest of the timeout
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error.to_string());
+            }
+        }
     }
 }
 
@@ -54,15 +102,37 @@ impl Connection {
                 return None;
             }
 
+            // the XCB connection underneath Xlib, shared and not owned, so requests
+            // through either of them stay in order
+            #[cfg(feature = "xcb")]
+            let xcb = match XCBConnection::from_raw_xcb_connection(
+                x11::xlib_xcb::XGetXCBConnection(display),
+                false,
+            ) {
+                Ok(xcb) => xcb,
+                Err(_) => {
+                    XCloseDisplay(display);
+                    return None;
+                }
+            };
+
             GlobalState::with(|global| {
                 if !global.closed {
                     global.errors.insert(display.addr(), None);
                 }
             });
 
+            // with detectable auto-repeat the server stops sending a KeyRelease before
+            // every repeated KeyPress, so held keys do not flicker up and down
+            let mut supported = 0;
+            XkbSetDetectableAutoRepeat(display, 1, &mut supported);
+
             Some(Self(Rc::new(ConnectionInner {
                 display,
+                #[cfg(feature = "xcb")]
+                xcb,
                 atoms: RefCell::new(HashMap::new()),
+                detectable_autorepeat: supported != 0,
             })))
         }
     }
@@ -105,6 +175,12 @@ impl Connection {
         }
     }
 
+    /// Whether the server honors detectable auto-repeat for this connection,
+    /// in which case every `KeyRelease` is a physical release.
+    pub fn detectable_autorepeat(&self) -> bool {
+        self.0.detectable_autorepeat
+    }
+
     /// Get the raw `Display` pointer for this connection for `xlib` calls
     pub fn as_raw(&self) -> *mut Display {
         self.0.display
@@ -116,16 +192,421 @@ impl Connection {
             .0
             .atoms
             .borrow_mut()
-            .entry(name.as_ptr().addr())
+            .entry(name)
             .or_insert_with(|| unsafe { XInternAtom(self.as_raw(), name.as_ptr(), 0) })
     }
+
+    /// Get the atom for a name only known at runtime, like the name of a
+    /// custom clipboard format. These are not cached, every call is a round
+    /// trip. Zero if the name contains a NUL.
+    pub fn intern(&self, name: &str) -> c_ulong {
+        match CString::new(name) {
+            Ok(name) => unsafe { XInternAtom(self.as_raw(), name.as_ptr(), 0) },
+            Err(_) => 0,
+        }
+    }
+
+    /// The most bytes a single `XChangeProperty` request can hold. Larger
+    /// selection values are sent in parts, see `IncrTransfer`.
+    pub fn max_property_size(&self) -> usize {
+        unsafe {
+            let units = match XExtendedMaxRequestSize(self.as_raw()) {
+                0 => XMaxRequestSize(self.as_raw()),
+                units => units,
+            };
+
+            // minus the request header
+            (units as usize).saturating_sub(6) * 4
+        }
+    }
+
+    /// Also listen to the events of `mask` on a window of another client.
+    /// Selecting replaces the whole mask of this connection for the window,
+    /// which would silence the events we listen to there for other reasons
+    /// (like `PropertyChangeMask` on the root window).
+    pub fn add_event_mask(&self, window: c_ulong, mask: c_long) {
+        unsafe {
+            XSelectInput(self.as_raw(), window, self.event_mask(window) | mask);
+        }
+    }
+
+    /// Stop listening to the events of `mask` on a window, keeping the others,
+    /// see [`Connection::add_event_mask`].
+    pub fn remove_event_mask(&self, window: c_ulong, mask: c_long) {
+        unsafe {
+            XSelectInput(self.as_raw(), window, self.event_mask(window) & !mask);
+        }
+    }
+
+    /// The events this connection listens to on `window`.
+    fn event_mask(&self, window: c_ulong) -> c_long {
+        unsafe {
+            let mut attributes = std::mem::zeroed::<XWindowAttributes>();
+            match XGetWindowAttributes(self.as_raw(), window, &mut attributes) {
+                0 => NoEventMask,
+                _ => attributes.your_event_mask,
+            }
+        }
+    }
+
+    /// Intern all given atoms that are not cached yet in a single round trip,
+    /// so later calls to [`Connection::atom`] do not block on the server.
+    pub fn prefetch_atoms(&self, names: &[&'static CStr]) {
+        let mut atoms = self.0.atoms.borrow_mut();
+        let missing = names
+            .iter()
+            .copied()
+            .filter(|name| !atoms.contains_key(name))
+            .collect::<Vec<_>>();
+
+        if missing.is_empty() {
+            return;
+        }
+
+        let mut names = missing
+            .iter()
+            .map(|name| name.as_ptr() as *mut c_char)
+            .collect::<Vec<_>>();
+        let mut result = vec![0; missing.len()];
+
+        unsafe {
+            // a zero status means some atoms failed, those are left at zero and are
+            // interned on demand instead
+            XInternAtoms(
+                self.as_raw(),
+                names.as_mut_ptr(),
+                names.len() as c_int,
+                0,
+                result.as_mut_ptr(),
+            );
+        }
+
+        for (name, atom) in missing.into_iter().zip(result) {
+            if atom != 0 {
+                atoms.insert(name, atom);
+            }
+        }
+    }
+}
+
+/// The value of a window property, see [`Connection::get_property`].
+pub struct Property {
+    /// The type of the value
+    pub kind: c_ulong,
+    /// The size of the items in bits, 8, 16 or 32
+    pub format: c_int,
+    /// The items, those of format 16 and 32 as `c_short`s and `c_long`s in
+    /// memory, the way Xlib returns them
+    pub data: Vec<u8>,
+}
+
+impl Property {
+    /// The items of a property of format 32, empty for other formats.
+    pub fn items32(&self) -> Vec<c_ulong> {
+        match self.format {
+            32 => self
+                .data
+                .chunks_exact(size_of::<c_ulong>())
+                .filter_map(|item| Some(c_ulong::from_ne_bytes(item.try_into().ok()?)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Window properties and selections. These go through XCB with the `xcb`
+/// feature and through Xlib otherwise, on the same connection either way.
+///
+/// With XCB, errors of the requests that have no reply are dropped instead of
+/// reported by [`Connection::last_error`]. Those requests are made on our own
+/// windows, or on the window of a selection requestor that may well be gone
+/// already.
+impl Connection {
+    /// Read a property of `window` if it exists and is of type `kind`
+    /// (`AnyPropertyType` for any), deleting it afterwards if `delete` is set.
+    pub fn get_property(
+        &self,
+        window: c_ulong,
+        property: c_ulong,
+        kind: c_ulong,
+        delete: bool,
+    ) -> Option<Property> {
+        cfg_select! {
+            feature = "xcb" => {
+                let reply = self
+                    .0
+                    .xcb
+                    .get_property(delete, window as u32, property as u32, kind as u32, 0, u32::MAX)
+                    .ok()?
+                    .reply()
+                    .ok()?;
+
+                if reply.type_ == 0 || (kind != 0 && reply.type_ as c_ulong != kind) {
+                    return None;
+                }
+
+                let data = match reply.format {
+                    8 => reply.value,
+                    16 => reply
+                        .value16()?
+                        .flat_map(|item| (item as c_short).to_ne_bytes())
+                        .collect(),
+                    32 => reply
+                        .value32()?
+                        .flat_map(|item| (item as c_long).to_ne_bytes())
+                        .collect(),
+                    _ => return None,
+                };
+
+                Some(Property {
+                    kind: reply.type_ as c_ulong,
+                    format: reply.format as c_int,
+                    data,
+                })
+            }
+            _ => unsafe {
+                let mut actual_kind = 0;
+                let mut format = 0;
+                let mut len = 0;
+                let mut remaining = 0;
+                let mut data = null_mut();
+
+                let result = XGetWindowProperty(
+                    self.as_raw(),
+                    window,
+                    property,
+                    0,
+                    !0,
+                    delete as c_int,
+                    kind,
+                    &mut actual_kind,
+                    &mut format,
+                    &mut len,
+                    &mut remaining,
+                    &mut data,
+                );
+
+                if result != 0 || data.is_null() {
+                    return None;
+                }
+
+                let item_size = match format {
+                    16 => size_of::<c_short>(),
+                    32 => size_of::<c_long>(),
+                    _ => 1,
+                };
+
+                let len = (len as usize).saturating_mul(item_size);
+                let bytes = std::slice::from_raw_parts(data, len).to_vec();
+                XFree(data as *mut _);
+
+                (actual_kind != 0 && (kind == 0 || actual_kind == kind)).then_some(Property {
+                    kind: actual_kind,
+                    format,
+                    data: bytes,
+                })
+            }
+        }
+    }
+
+    /// Replace a property of `window` with `data` of format 8.
+    pub fn set_property8(&self, window: c_ulong, property: c_ulong, kind: c_ulong, data: &[u8]) {
+        cfg_select! {
+            feature = "xcb" => {
+                let _ = self
+                    .0
+                    .xcb
+                    .change_property8(
+                        PropMode::REPLACE,
+                        window as u32,
+                        property as u32,
+                        kind as u32,
+                        data,
+                    )
+                    .map(|cookie| cookie.ignore_error());
+                let _ = self.0.xcb.flush();
+            }
+            _ => unsafe {
+                XChangeProperty(
+                    self.as_raw(),
+                    window,
+                    property,
+                    kind,
+                    8,
+                    PropModeReplace,
+                    data.as_ptr(),
+                    data.len() as c_int,
+                );
+            }
+        }
+    }
+
+    /// Replace a property of `window` with `items` of format 32.
+    pub fn set_property32(
+        &self,
+        window: c_ulong,
+        property: c_ulong,
+        kind: c_ulong,
+        items: &[c_ulong],
+    ) {
+        cfg_select! {
+            feature = "xcb" => {
+                let items = items.iter().map(|&item| item as u32).collect::<Vec<_>>();
+                let _ = self
+                    .0
+                    .xcb
+                    .change_property32(
+                        PropMode::REPLACE,
+                        window as u32,
+                        property as u32,
+                        kind as u32,
+                        &items,
+                    )
+                    .map(|cookie| cookie.ignore_error());
+                let _ = self.0.xcb.flush();
+            }
+            // Xlib takes the items of format 32 as longs
+            _ => unsafe {
+                XChangeProperty(
+                    self.as_raw(),
+                    window,
+                    property,
+                    kind,
+                    32,
+                    PropModeReplace,
+                    items.as_ptr().cast(),
+                    items.len() as c_int,
+                );
+            }
+        }
+    }
+
+    /// The window that owns `selection`, zero if there is none.
+    pub fn selection_owner(&self, selection: c_ulong) -> c_ulong {
+        cfg_select! {
+            feature = "xcb" => {
+                self.0
+                    .xcb
+                    .get_selection_owner(selection as u32)
+                    .ok()
+                    .and_then(|cookie| cookie.reply().ok())
+                    .map_or(0, |reply| reply.owner as c_ulong)
+            }
+            _ => unsafe { XGetSelectionOwner(self.as_raw(), selection) },
+        }
+    }
+
+    /// Make `owner` the owner of `selection`, as of `time`. The server
+    /// ignores this if the selection changed hands after `time`, check with
+    /// [`Connection::selection_owner`].
+    pub fn set_selection_owner(&self, selection: c_ulong, owner: c_ulong, time: c_ulong) {
+        cfg_select! {
+            feature = "xcb" => {
+                let _ = self
+                    .0
+                    .xcb
+                    .set_selection_owner(owner as u32, selection as u32, time as u32)
+                    .map(|cookie| cookie.ignore_error());
+            }
+            _ => unsafe {
+                XSetSelectionOwner(self.as_raw(), selection, owner, time);
+            }
+        }
+    }
+
+    /// Ask the owner of `selection` to store it as `target` in `property` of
+    /// `requestor`, answered with a `SelectionNotify`.
+    pub fn convert_selection(
+        &self,
+        selection: c_ulong,
+        target: c_ulong,
+        property: c_ulong,
+        requestor: c_ulong,
+        time: c_ulong,
+    ) {
+        cfg_select! {
+            feature = "xcb" => {
+                let _ = self
+                    .0
+                    .xcb
+                    .convert_selection(
+                        requestor as u32,
+                        selection as u32,
+                        target as u32,
+                        property as u32,
+                        time as u32,
+                    )
+                    .map(|cookie| cookie.ignore_error());
+                let _ = self.0.xcb.flush();
+            }
+            _ => unsafe {
+                XConvertSelection(self.as_raw(), selection, target, property, requestor, time);
+            }
+        }
+    }
+
+    /// Tell `requestor` that its request for `selection` as `target` was
+    /// stored in `property`, or refused if `property` is zero.
+    pub fn send_selection_notify(
+        &self,
+        requestor: c_ulong,
+        selection: c_ulong,
+        target: c_ulong,
+        property: c_ulong,
+        time: c_ulong,
+    ) {
+        cfg_select! {
+            feature = "xcb" => {
+                let event = SelectionNotifyEvent {
+                    response_type: SELECTION_NOTIFY_EVENT,
+                    sequence: 0,
+                    time: time as u32,
+                    requestor: requestor as u32,
+                    selection: selection as u32,
+                    target: target as u32,
+                    property: property as u32,
+                };
+                let _ = self
+                    .0
+                    .xcb
+                    .send_event(false, requestor as u32, EventMask::NO_EVENT, event)
+                    .map(|cookie| cookie.ignore_error());
+                let _ = self.0.xcb.flush();
+            }
+            _ => unsafe {
+                XSendEvent(
+                    self.as_raw(),
+                    requestor,
+                    0,
+                    NoEventMask,
+                    &mut XEvent {
+                        selection: XSelectionEvent {
+                            type_: SelectionNotify,
+                            serial: 0,
+                            send_event: 1,
+                            display: self.as_raw(),
+                            requestor,
+                            selection,
+                            target,
+                            property,
+                            time,
+                        },
+                    },
+                );
+                XFlush(self.as_raw());
+            }
+        }
+    }
 }
 
 /// Internal data for a single connection. Drop is called when all
 /// [`Connection`] handles go out of scope.
 struct ConnectionInner {
     display: *mut Display,
-    atoms: RefCell<HashMap<usize, c_ulong>>,
+    #[cfg(feature = "xcb")]
+    xcb: XCBConnection,
+    atoms: RefCell<HashMap<&'static CStr, c_ulong>>,
+    detectable_autorepeat: bool,
 }
 
 impl Drop for ConnectionInner {
@@ -151,6 +632,10 @@ impl Drop for ConnectionInner {
 struct GlobalState {
     errors: HashMap<usize, Option<String>>,
 
+    /// The error handler installed before ours, usually by the host. Errors on
+    /// connections that are not ours go there, so we do not swallow them.
+    previous_handler: Option<unsafe extern "C" fn(*mut Display, *mut XErrorEvent) -> c_int>,
+
     // NOTE: this is a stupid workaround for an Xlib bug (?) where
     // libX11 calls XFreeThreads on dtor
     // which happens _before_ non-main threads are exited, causing
@@ -162,13 +647,14 @@ impl GlobalState {
     fn with<R>(f: impl FnOnce(&mut Self) -> R) -> R {
         static GLOBAL: Mutex<Option<GlobalState>> = Mutex::new(None);
         f(GLOBAL.lock().expect("poisoned").get_or_insert_with(|| {
-            unsafe {
-                XSetErrorHandler(Some(error_handler));
+            let previous_handler = unsafe {
                 libc::atexit(exit_handler);
-            }
+                XSetErrorHandler(Some(error_handler))
+            };
 
             Self {
                 errors: HashMap::new(),
+                previous_handler,
                 closed: false,
             }
         }))
@@ -186,13 +672,13 @@ extern "C" fn exit_handler() {
 }
 
 unsafe extern "C" fn error_handler(dpy: *mut Display, err: *mut XErrorEvent) -> i32 {
-    GlobalState::with(|global| {
+    let previous_handler = GlobalState::with(|global| {
         let Some(conn) = global.errors.get_mut(&(dpy as usize)) else {
-            return 0;
+            return global.previous_handler;
         };
 
         if conn.is_some() {
-            return 0;
+            return None;
         }
 
         unsafe {
@@ -209,6 +695,12 @@ unsafe extern "C" fn error_handler(dpy: *mut Display, err: *mut XErrorEvent) ->
             conn.replace(CStr::from_ptr(buf.as_mut_ptr()).to_string_lossy().into());
         }
 
-        0
-    })
+        None
+    });
+
+    // called outside of the lock, the handler may well make Xlib calls of its own
+    match previous_handler {
+        Some(handler) => unsafe { handler(dpy, err) },
+        None => 0,
+    }
 }
@@ -0,0 +1,131 @@
+use super::Connection;
+use crate::platform::Damage;
+use crate::{PresentError, Size};
+use std::ffi::{c_int, c_ulong};
+use std::ptr::null_mut;
+use x11::xlib::*;
+
+/// Copies pixel buffers onto a window with `XPutImage`, see
+/// [`Window::present_pixels`](crate::Window::present_pixels).
+///
+/// The buffer is wrapped into an `XImage` without copying it, Xlib splits the
+/// upload into as many requests as it needs.
+pub struct ImagePresenter {
+    conn: Connection,
+    gc: GC,
+    visual: *mut Visual,
+    depth: c_int,
+}
+
+impl ImagePresenter {
+    /// Create a presenter for a window with the given visual and depth. Fails
+    /// with [`PresentError::Unsupported`] unless the visual stores pixels as
+    /// `0xAARRGGBB` (or `0x00RRGGBB` without an alpha channel).
+    ///
+    /// # Safety
+    /// - The `visual` must be the valid visual of `window`.
+    pub unsafe fn new(
+        conn: &Connection,
+        window: c_ulong,
+        visual: *mut Visual,
+        depth: c_int,
+    ) -> Result<Self, PresentError> {
+        unsafe {
+            let masks = (
+                (*visual).red_mask,
+                (*visual).green_mask,
+                (*visual).blue_mask,
+            );
+            if masks != (0xff0000, 0xff00, 0xff) || !matches!(depth, 24 | 32) {
+                return Err(PresentError::Unsupported);
+            }
+
+            let gc = XCreateGC(conn.as_raw(), window, 0, null_mut());
+            if gc.is_null() {
+                return Err(PresentError::Platform("XCreateGC failed".into()));
+            }
+
+            Ok(Self {
+                conn: conn.clone(),
+                gc,
+                visual,
+                depth,
+            })
+        }
+    }
+
+    /// Copy the `damage` regions of `buffer` onto `window`, at the same
+    /// position.
+    pub fn present(
+        &self,
+        window: c_ulong,
+        buffer: &[u32],
+        size: Size,
+        damage: Damage<'_>,
+    ) -> Result<(), PresentError> {
+        unsafe {
+            let image = XCreateImage(
+                self.conn.as_raw(),
+                self.visual,
+                self.depth as _,
+                ZPixmap,
+                0,
+                buffer.as_ptr() as *mut _,
+                size.width,
+                size.height,
+                32,
+                0,
+            );
+
+            if image.is_null() {
+                return Err(PresentError::Platform("XCreateImage failed".into()));
+            }
+
+            // the image only borrows the buffer, it must not be freed with it
+            let destroy = |image: *mut XImage| {
+                (*image).data = null_mut();
+                XDestroyImage(image);
+            };
+
+            // rows are computed from the bits per pixel the server wants for the depth,
+            // they only line up with the buffer for 32 bits
+            if (*image).bits_per_pixel != 32 {
+                destroy(image);
+                return Err(PresentError::Unsupported);
+            }
+
+            (*image).byte_order = match cfg!(target_endian = "little") {
+                true => LSBFirst,
+                false => MSBFirst,
+            };
+
+            for rect in damage.iter() {
+                let Size { width, height } = rect.size();
+                XPutImage(
+                    self.conn.as_raw(),
+                    window,
+                    self.gc,
+                    image,
+                    rect.left,
+                    rect.top,
+                    rect.left,
+                    rect.top,
+                    width,
+                    height,
+                );
+            }
+
+            destroy(image);
+            XFlush(self.conn.as_raw());
+            Ok(())
+        }
+    }
+}
+
+impl Drop for ImagePresenter {
+    fn drop(&mut self) {
+        unsafe {
+            XFreeGC(self.conn.as_raw(), self.gc);
+        }
+    }
+}
@@ -0,0 +1,512 @@
+use super::Connection;
+use crate::Rect;
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString, c_char, c_int, c_ulong, c_ushort, c_void};
+use std::ops::Range;
+use std::ptr::{null, null_mut};
+use x11::xlib::*;
+
+/// Highlight styles of the preedit text, not exported by the `x11` crate.
+const XIM_REVERSE: XIMFeedback = 1;
+const XIM_HIGHLIGHT: XIMFeedback = 1 << 2;
+
+/// `XIMStyles` as returned by `XNQueryInputStyle`, not exported by the `x11`
+/// crate.
+#[repr(C)]
+struct XIMStyles {
+    count_styles: c_ushort,
+    supported_styles: *mut c_ulong,
+}
+
+/// A change of the composition state, reported by [`InputMethod`] and
+/// forwarded to the `composition_*` methods of the handler.
+pub enum Composition {
+    Start,
+    Update(String, Range<usize>),
+    End(Option<String>),
+}
+
+/// An XIM input context attached to a window.
+///
+/// Prefers on-the-spot preedit, where the input method hands us the text being
+/// composed through callbacks, so it can be drawn by the handler. Falls back to
+/// an input method that composes on its own, which only ever commits text.
+pub struct InputMethod {
+    /// Keeps the display open until the input method is closed.
+    _conn: Connection,
+    im: XIM,
+    ic: XIC,
+    /// Boxed so the pointer passed to the preedit callbacks stays valid.
+    state: Box<PreeditState>,
+}
+
+/// The composition as drawn by the preedit callbacks.
+#[derive(Default)]
+struct PreeditState {
+    /// The character set of the locale the input method was opened in, which
+    /// multi-byte preedit strings are encoded in. `None` for UTF-8.
+    codeset: Option<CString>,
+    /// The text being composed, along with the highlight of every character.
+    text: RefCell<Vec<(char, XIMFeedback)>>,
+    /// The caret, in characters.
+    caret: Cell<usize>,
+    is_composing: Cell<bool>,
+    /// Set by the done callback. The commit, if any, only arrives afterwards
+    /// as a key press, so the composition is not ended until we know whether
+    /// it was cancelled.
+    is_done_pending: Cell<bool>,
+    changes: RefCell<Vec<Composition>>,
+}
+
+impl InputMethod {
+    /// Open the input method of the locale the host set up and create an input
+    /// context for `window`. Returns `None` if there is no input method, the
+    /// host left the process in the C locale, or Xlib does not support the
+    /// locale, key presses are then only looked up with the keyboard layout.
+    ///
+    /// The locale belongs to the whole process, and embedded windows run on a
+    /// thread of their own, so it is only ever read here and never changed.
+    pub fn new(conn: &Connection, window: c_ulong) -> Option<Self> {
+        unsafe {
+            // input methods are picked by the locale, and there are none for the C locale
+            // every program starts in
+            let locale = libc::setlocale(libc::LC_CTYPE, null());
+            if locale.is_null() || matches!(CStr::from_ptr(locale).to_bytes(), b"C" | b"POSIX") {
+                return None;
+            }
+
+            if XSupportsLocale() == 0 {
+                return None;
+            }
+
+            // picks up `XMODIFIERS`, which selects the input method server
+            XSetLocaleModifiers(c"".as_ptr());
+
+            let codeset = CStr::from_ptr(libc::nl_langinfo(libc::CODESET));
+            let codeset = match codeset.to_bytes() {
+                b"UTF-8" | b"utf8" => None,
+                _ => Some(codeset.to_owned()),
+            };
+
+            let im = XOpenIM(conn.as_raw(), null_mut(), null_mut(), null_mut());
+            if im.is_null() {
+                return None;
+            }
+
+            let state = Box::new(PreeditState {
+                codeset,
+                ..PreeditState::default()
+            });
+            let ic = match query_styles(im) {
+                styles if styles.contains(&PREEDIT_CALLBACKS) => {
+                    create_ic_with_callbacks(im, window, &state)
+                }
+                _ => null_mut(),
+            };
+
+            // the input method composes in a window of its own
+            let ic = match ic.is_null() {
+                true => XCreateIC(
+                    im,
+                    XNInputStyle_0.as_ptr(),
+                    PREEDIT_NOTHING,
+                    XNClientWindow_0.as_ptr(),
+                    window,
+                    XNFocusWindow_0.as_ptr(),
+                    window,
+                    null::<c_void>(),
+                ),
+                false => ic,
+            };
+
+            if ic.is_null() {
+                XCloseIM(im);
+                return None;
+            }
+
+            Some(Self {
+                _conn: conn.clone(),
+                im,
+                ic,
+                state,
+            })
+        }
+    }
+
+    /// Let the input method see `event` first. Returns `true` if it consumed
+    /// the event, which must then be ignored.
+    pub fn filter_event(&self, event: &mut XEvent) -> bool {
+        unsafe { XFilterEvent(event, 0) != 0 }
+    }
+
+    /// Look up the text committed with a key press the input method generated,
+    /// marked by a keycode of 0, and end the composition with it.
+    #[allow(non_upper_case_globals)]
+    pub fn commit(&self, event: &XKeyEvent) {
+        unsafe {
+            let mut event = *event;
+            let mut buffer = vec![0u8; 64];
+            let mut status = 0;
+
+            let mut count = Xutf8LookupString(
+                self.ic,
+                &mut event,
+                buffer.as_mut_ptr().cast::<c_char>(),
+                buffer.len() as c_int,
+                null_mut(),
+                &mut status,
+            );
+
+            if status == XBufferOverflow {
+                buffer.resize(count.try_into().unwrap_or(0), 0);
+                count = Xutf8LookupString(
+                    self.ic,
+                    &mut event,
+                    buffer.as_mut_ptr().cast::<c_char>(),
+                    buffer.len() as c_int,
+                    null_mut(),
+                    &mut status,
+                );
+            }
+
+            if !matches!(status, XLookupChars | XLookupBoth) {
+                return;
+            }
+
+            buffer.truncate(count.try_into().unwrap_or(0));
+            let Ok(text) = String::from_utf8(buffer) else {
+                return;
+            };
+
+            let state = &self.state;
+            if !state.is_composing.replace(false) {
+                state.push(Composition::Start);
+            }
+
+            state.is_done_pending.set(false);
+            state.text.borrow_mut().clear();
+            state.push(Composition::End(Some(text)));
+        }
+    }
+
+    /// Tell the input method that the window gained or lost the keyboard
+    /// focus. Losing it cancels the composition.
+    pub fn set_focus(&self, focus: bool) {
+        unsafe {
+            match focus {
+                true => XSetICFocus(self.ic),
+                false => XUnsetICFocus(self.ic),
+            }
+        }
+
+        if !focus {
+            self.state.is_done_pending.set(true);
+            self.finish_batch();
+        }
+    }
+
+    /// Place the candidate list of the input method below `area`.
+    pub fn set_cursor_area(&self, area: Rect) {
+        unsafe {
+            let mut spot = XPoint {
+                x: area.left.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+                y: area.bottom.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+            };
+
+            let attributes = XVaCreateNestedList(
+                0,
+                XNSpotLocation_0.as_ptr(),
+                &mut spot as *mut XPoint,
+                null::<c_void>(),
+            );
+
+            XSetICValues(
+                self.ic,
+                XNPreeditAttributes_0.as_ptr(),
+                attributes,
+                null::<c_void>(),
+            );
+
+            XFree(attributes);
+        }
+    }
+
+    /// Cancel the composition if the input method finished it without
+    /// committing anything. Called once all the queued events have been
+    /// handled.
+    pub fn finish_batch(&self) {
+        let state = &self.state;
+        if state.is_done_pending.replace(false) && state.is_composing.replace(false) {
+            state.text.borrow_mut().clear();
+            state.push(Composition::End(None));
+        }
+    }
+
+    /// Take the composition changes since the last call.
+    pub fn take_changes(&self) -> Vec<Composition> {
+        self.state.changes.take()
+    }
+}
+
+impl Drop for InputMethod {
+    fn drop(&mut self) {
+        unsafe {
+            XDestroyIC(self.ic);
+            XCloseIM(self.im);
+        }
+    }
+}
+
+impl PreeditState {
+    fn push(&self, change: Composition) {
+        self.changes.borrow_mut().push(change);
+    }
+
+    /// Report the current text, with the highlighted part as the cursor range
+    /// or an empty range at the caret if nothing is highlighted.
+    fn push_update(&self) {
+        if !self.is_composing.replace(true) {
+            self.push(Composition::Start);
+        }
+
+        let text = self.text.borrow();
+        let offset = |index: usize| {
+            text.iter()
+                .take(index)
+                .map(|(ch, _)| ch.len_utf8())
+                .sum::<usize>()
+        };
+
+        let is_highlighted =
+            |(_, feedback): &&(char, XIMFeedback)| feedback & (XIM_REVERSE | XIM_HIGHLIGHT) != 0;
+        let range = match text.iter().position(|ch| is_highlighted(&ch)) {
+            Some(start) => {
+                let length = text
+                    .iter()
+                    .skip(start)
+                    .take_while(|ch| is_highlighted(ch))
+                    .count();
+                offset(start)..offset(start + length)
+            }
+            None => {
+                let caret = offset(self.caret.get());
+                caret..caret
+            }
+        };
+
+        let string = text.iter().map(|(ch, _)| ch).collect();
+        self.push(Composition::Update(string, range));
+    }
+}
+
+const PREEDIT_CALLBACKS: c_ulong = (XIMPreeditCallbacks | XIMStatusNothing) as c_ulong;
+const PREEDIT_NOTHING: c_ulong = (XIMPreeditNothing | XIMStatusNothing) as c_ulong;
+
+/// List the input styles supported by the input method.
+unsafe fn query_styles(im: XIM) -> Vec<c_ulong> {
+    unsafe {
+        let mut styles: *mut XIMStyles = null_mut();
+        let failed = XGetIMValues(
+            im,
+            XNQueryInputStyle_0.as_ptr(),
+            &mut styles as *mut *mut XIMStyles,
+            null::<c_void>(),
+        );
+
+        if !failed.is_null() || styles.is_null() {
+            return Vec::new();
+        }
+
+        let list = match (*styles).supported_styles.is_null() {
+            true => Vec::new(),
+            false => std::slice::from_raw_parts(
+                (*styles).supported_styles,
+                (*styles).count_styles.into(),
+            )
+            .to_vec(),
+        };
+
+        XFree(styles.cast());
+        list
+    }
+}
+
+/// Create an input context with on-the-spot preedit, reporting to `state`.
+unsafe fn create_ic_with_callbacks(im: XIM, window: c_ulong, state: &PreeditState) -> XIC {
+    unsafe {
+        let client_data = state as *const PreeditState as XPointer;
+        let callback = |callback| XIMCallback {
+            client_data,
+            callback: Some(callback),
+        };
+
+        // the start callback returns the maximum length of the preedit text, which
+        // `XIMProc` does not account for
+        let start = std::mem::transmute::<
+            unsafe extern "C" fn(XIM, XPointer, XPointer) -> c_int,
+            unsafe extern "C" fn(XIM, XPointer, XPointer),
+        >(preedit_start);
+
+        let mut start = callback(start);
+        let mut done = callback(preedit_done);
+        let mut draw = callback(preedit_draw);
+        let mut caret = callback(preedit_caret);
+
+        let attributes = XVaCreateNestedList(
+            0,
+            XNPreeditStartCallback_0.as_ptr(),
+            &mut start as *mut XIMCallback,
+            XNPreeditDoneCallback_0.as_ptr(),
+            &mut done as *mut XIMCallback,
+            XNPreeditDrawCallback_0.as_ptr(),
+            &mut draw as *mut XIMCallback,
+            XNPreeditCaretCallback_0.as_ptr(),
+            &mut caret as *mut XIMCallback,
+            null::<c_void>(),
+        );
+
+        // the callbacks are copied into the input context
+        let ic = XCreateIC(
+            im,
+            XNInputStyle_0.as_ptr(),
+            PREEDIT_CALLBACKS,
+            XNClientWindow_0.as_ptr(),
+            window,
+            XNFocusWindow_0.as_ptr(),
+            window,
+            XNPreeditAttributes_0.as_ptr(),
+            attributes,
+            null::<c_void>(),
+        );
+
+        XFree(attributes);
+        ic
+    }
+}
+
+unsafe extern "C" fn preedit_start(_: XIM, client_data: XPointer, _: XPointer) -> c_int {
+    let state = unsafe { &*(client_data as *const PreeditState) };
+    state.text.borrow_mut().clear();
+    state.caret.set(0);
+    state.is_done_pending.set(false);
+
+    // no limit on the length of the preedit text
+    -1
+}
+
+unsafe extern "C" fn preedit_done(_: XIM, client_data: XPointer, _: XPointer) {
+    let state = unsafe { &*(client_data as *const PreeditState) };
+    state.is_done_pending.set(true);
+}
+
+unsafe extern "C" fn preedit_draw(_: XIM, client_data: XPointer, call_data: XPointer) {
+    unsafe {
+        let state = &*(client_data as *const PreeditState);
+        let data = &*(call_data as *const XIMPreeditDrawCallbackStruct);
+
+        // text is `NULL` if the range is only deleted
+        let mut inserted = Vec::new();
+        if let Some(text) = data.text.as_ref() {
+            // the `x11` crate declares the wide string as a single `wchar_t`, but it is a
+            // pointer like the multi-byte one
+            let pointer = text.string.multi_byte;
+            let string = match text.encoding_is_wchar {
+                _ if pointer.is_null() => String::new(),
+                0 => {
+                    decode_multi_byte(CStr::from_ptr(pointer).to_bytes(), state.codeset.as_deref())
+                }
+                // `wchar_t` holds UTF-32 on the platforms X11 runs on
+                _ => std::slice::from_raw_parts(pointer.cast::<u32>(), text.length.into())
+                    .iter()
+                    .map(|&ch| char::from_u32(ch).unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect(),
+            };
+
+            let feedback = match text.feedback.is_null() {
+                true => &[][..],
+                false => std::slice::from_raw_parts(text.feedback, text.length.into()),
+            };
+
+            inserted = string
+                .chars()
+                .enumerate()
+                .map(|(i, ch)| (ch, feedback.get(i).copied().unwrap_or(0)))
+                .collect();
+        }
+
+        {
+            let mut current = state.text.borrow_mut();
+            let first = usize::try_from(data.chg_first)
+                .unwrap_or(0)
+                .min(current.len());
+            let last = first
+                .saturating_add(usize::try_from(data.chg_length).unwrap_or(0))
+                .min(current.len());
+
+            current.splice(first..last, inserted);
+            state
+                .caret
+                .set(usize::try_from(data.caret).unwrap_or(0).min(current.len()));
+        }
+
+        state.is_done_pending.set(false);
+        state.push_update();
+    }
+}
+
+unsafe extern "C" fn preedit_caret(_: XIM, client_data: XPointer, call_data: XPointer) {
+    unsafe {
+        let state = &*(client_data as *const PreeditState);
+        let data = &mut *(call_data as *mut XIMPreeditCaretCallbackStruct);
+
+        // only absolute positions are ever sent by the common input methods
+        if data.direction == XIMCaretDirection::XIMAbsolutePosition {
+            let length = state.text.borrow().len();
+            state
+                .caret
+                .set(usize::try_from(data.position).unwrap_or(0).min(length));
+
+            if state.is_composing.get() {
+                state.push_update();
+            }
+        }
+    }
+}
+
+/// Convert a preedit string from the character set of the locale the input
+/// method was opened in, `None` for UTF-8.
+fn decode_multi_byte(bytes: &[u8], codeset: Option<&CStr>) -> String {
+    let Some(codeset) = codeset else {
+        return String::from_utf8_lossy(bytes).into_owned();
+    };
+
+    unsafe {
+        let cd = libc::iconv_open(c"UTF-8".as_ptr(), codeset.as_ptr());
+        if cd as isize == -1 {
+            return String::from_utf8_lossy(bytes).into_owned();
+        }
+
+        // no character takes more than 4 bytes in UTF-8, nor less than 1 in any charset
+        let mut output = vec![0u8; bytes.len().saturating_mul(4)];
+        let mut input_ptr = bytes.as_ptr() as *mut c_char;
+        let mut input_left = bytes.len();
+        let mut output_ptr = output.as_mut_ptr().cast::<c_char>();
+        let mut output_left = output.len();
+
+        let result = libc::iconv(
+            cd,
+            &mut input_ptr,
+            &mut input_left,
+            &mut output_ptr,
+            &mut output_left,
+        );
+        libc::iconv_close(cd);
+
+        if result == usize::MAX {
+            return String::from_utf8_lossy(bytes).into_owned();
+        }
+
+        output.truncate(output.len() - output_left);
+        String::from_utf8_lossy(&output).into_owned()
+    }
+}
@@ -1,5 +1,6 @@
 use super::Connection;
-use std::ffi::CStr;
+use crate::{IccProfile, MonitorSelector};
+use std::ffi::{CStr, c_char, c_int, c_long};
 use std::mem::zeroed;
 use std::ptr::null_mut;
 use std::str::FromStr;
@@ -7,14 +8,19 @@ use x11::xlib::*;
 use x11::xrandr::*;
 
 /// Get the DPI scaling factor from X resources, if available.
+///
+/// Reads the `RESOURCE_MANAGER` property of the root window rather than the
+/// copy Xlib makes when the connection is opened, so changes made at runtime
+/// are picked up.
 pub fn query_scale_dpi(conn: &Connection) -> Option<f64> {
     unsafe {
-        let rms = XResourceManagerString(conn.as_raw());
-        if rms.is_null() {
-            return None;
-        }
+        let root = XDefaultRootWindow(conn.as_raw());
+        let mut data = conn
+            .get_property(root, XA_RESOURCE_MANAGER, XA_STRING, false)?
+            .data;
 
-        let db = XrmGetStringDatabase(rms);
+        data.push(0);
+        let db = XrmGetStringDatabase(data.as_ptr() as *const c_char);
         if db.is_null() {
             return None;
         }
@@ -44,6 +50,26 @@ pub fn query_scale_dpi(conn: &Connection) -> Option<f64> {
     }
 }
 
+/// Ask for `RRScreenChangeNotify` events on the root window, which are sent
+/// when the monitor layout changes. Returns the type of those events, if the
+/// XRandR extension is available.
+pub fn select_screen_changes(conn: &Connection) -> Option<c_int> {
+    unsafe {
+        let mut event_base = 0;
+        if XRRQueryExtension(conn.as_raw(), &mut event_base, &mut 0) == 0 {
+            return None;
+        }
+
+        XRRSelectInput(
+            conn.as_raw(),
+            XDefaultRootWindow(conn.as_raw()),
+            RRScreenChangeNotifyMask,
+        );
+
+        Some(event_base + RRScreenChangeNotify)
+    }
+}
+
 /// Get the current refresh rate of the default screen by querying the
 /// XRandR extension, if available.
 pub fn query_refresh_rate(conn: &Connection) -> Option<f64> {
@@ -88,3 +114,389 @@ pub fn query_refresh_rate(conn: &Connection) -> Option<f64> {
         max_rate
     }
 }
+
+/// An XRandR CRTC and its bounds as `(x, y, width, height)` in root window
+/// coordinates.
+pub type CrtcRect = (RRCrtc, (i32, i32, i32, i32));
+
+/// Get the bounds of the active XRandR CRTCs, empty if the extension is not
+/// available. They only change with an `RRScreenChangeNotify`, so they can be
+/// cached until then.
+pub fn query_crtc_rects(conn: &Connection) -> Vec<CrtcRect> {
+    unsafe {
+        if XRRQueryExtension(conn.as_raw(), &mut 0, &mut 0) == 0 {
+            return Vec::new();
+        }
+
+        let resources =
+            XRRGetScreenResourcesCurrent(conn.as_raw(), XDefaultRootWindow(conn.as_raw()));
+        if resources.is_null() {
+            return Vec::new();
+        }
+
+        let mut rects = Vec::new();
+        for crtc in 0..(*resources).ncrtc {
+            let crtc = (*resources).crtcs.add(crtc as usize).read();
+            let crtc_info = XRRGetCrtcInfo(conn.as_raw(), resources, crtc);
+            if crtc_info.is_null() {
+                continue;
+            }
+
+            let info = &*crtc_info;
+            if info.mode != 0 {
+                let width = info.width.try_into().unwrap_or(i32::MAX);
+                let height = info.height.try_into().unwrap_or(i32::MAX);
+                rects.push((crtc, (info.x, info.y, width, height)));
+            }
+
+            XRRFreeCrtcInfo(crtc_info);
+        }
+
+        XRRFreeScreenResources(resources);
+
+        rects
+    }
+}
+
+/// Get the CRTC of `rects` (see [`query_crtc_rects`]) showing the given
+/// point, if any does. Where outputs overlap, the first CRTC wins.
+pub fn crtc_at(rects: &[CrtcRect], x: i32, y: i32) -> Option<RRCrtc> {
+    rects
+        .iter()
+        .find(|(_, (left, top, width, height))| {
+            (*left..left.saturating_add(*width)).contains(&x)
+                && (*top..top.saturating_add(*height)).contains(&y)
+        })
+        .map(|(crtc, _)| *crtc)
+}
+
+/// The root window properties holding the ICC profiles of the monitors, as set
+/// by color management daemons following the "ICC Profiles in X"
+/// specification: `_ICC_PROFILE` for the first monitor, `_ICC_PROFILE_n` for
+/// the n-th one.
+const ICC_PROFILE_ATOMS: [&CStr; 8] = [
+    c"_ICC_PROFILE",
+    c"_ICC_PROFILE_1",
+    c"_ICC_PROFILE_2",
+    c"_ICC_PROFILE_3",
+    c"_ICC_PROFILE_4",
+    c"_ICC_PROFILE_5",
+    c"_ICC_PROFILE_6",
+    c"_ICC_PROFILE_7",
+];
+
+/// Get the ICC profile of the monitor containing the given point, in root
+/// window coordinates. Falls back to the profile of the first monitor if the
+/// point is not on a monitor, or if that monitor has no profile of its own.
+pub fn query_color_profile(conn: &Connection, x: i32, y: i32) -> Option<IccProfile> {
+    let index = query_monitor_index(conn, x, y).unwrap_or(0);
+    ICC_PROFILE_ATOMS
+        .get(index)
+        .and_then(|name| query_root_bytes(conn, name))
+        .or_else(|| query_root_bytes(conn, c"_ICC_PROFILE"))
+        .map(IccProfile::new)
+}
+
+/// Check if `atom` is one of the root window properties read by
+/// [`query_color_profile`].
+pub fn is_color_profile_atom(conn: &Connection, atom: Atom) -> bool {
+    ICC_PROFILE_ATOMS.iter().any(|name| conn.atom(name) == atom)
+}
+
+/// Get the position of the monitor containing the given point in the list of
+/// XRandR monitors, which is the order of the Xinerama screens.
+fn query_monitor_index(conn: &Connection, x: i32, y: i32) -> Option<usize> {
+    unsafe {
+        if XRRQueryExtension(conn.as_raw(), &mut 0, &mut 0) == 0 {
+            return None;
+        }
+
+        let mut count = 0;
+        let monitors = XRRGetMonitors(
+            conn.as_raw(),
+            XDefaultRootWindow(conn.as_raw()),
+            1,
+            &mut count,
+        );
+        if monitors.is_null() {
+            return None;
+        }
+
+        let list = std::slice::from_raw_parts(monitors, count.max(0) as usize);
+        let index = list
+            .iter()
+            .position(|m| x >= m.x && y >= m.y && x < m.x + m.width && y < m.y + m.height);
+
+        XRRFreeMonitors(monitors);
+        index
+    }
+}
+
+/// Get the contents of a root window property with 8-bit items, `None` if
+/// it is not set or empty.
+fn query_root_bytes(conn: &Connection, name: &'static CStr) -> Option<Vec<u8>> {
+    let root = unsafe { XDefaultRootWindow(conn.as_raw()) };
+    query_window_bytes(conn, root, conn.atom(name))
+}
+
+/// Like [`query_root_bytes`], for any property of any window.
+pub fn query_window_bytes(conn: &Connection, window: Window, property: Atom) -> Option<Vec<u8>> {
+    conn.get_property(window, property, AnyPropertyType as _, false)
+        .filter(|property| property.format == 8 && !property.data.is_empty())
+        .map(|property| property.data)
+}
+
+/// Get the top-level window (direct child of the root window) that contains
+/// the given window, or the window itself if it is top-level already.
+pub fn query_top_level_window(conn: &Connection, window: Window) -> Window {
+    unsafe {
+        let mut current = window;
+        loop {
+            let mut root = 0;
+            let mut parent = 0;
+            let mut children = null_mut();
+            let mut num_children = 0;
+
+            if XQueryTree(
+                conn.as_raw(),
+                current,
+                &mut root,
+                &mut parent,
+                &mut children,
+                &mut num_children,
+            ) == 0
+            {
+                return current;
+            }
+
+            if !children.is_null() {
+                XFree(children as *mut _);
+            }
+
+            if parent == root || parent == 0 {
+                return current;
+            }
+
+            current = parent;
+        }
+    }
+}
+
+/// Get the top-level window whose focus keeps the popups of `window` open: the
+/// top-level window containing it, or, if that is an override-redirect popup
+/// itself, the one of the window it is transient for.
+pub fn query_popup_owner(conn: &Connection, window: Window) -> Window {
+    unsafe {
+        let mut current = query_top_level_window(conn, window);
+
+        // bounded, in case the transient hints form a cycle
+        for _ in 0..16 {
+            let mut attributes = zeroed::<XWindowAttributes>();
+            let mut transient_for = 0;
+
+            if XGetWindowAttributes(conn.as_raw(), current, &mut attributes) == 0
+                || attributes.override_redirect == 0
+                || XGetTransientForHint(conn.as_raw(), current, &mut transient_for) == 0
+                || transient_for == 0
+            {
+                break;
+            }
+
+            current = query_top_level_window(conn, transient_for);
+        }
+
+        current
+    }
+}
+
+/// Get the mapped windows that contain the given point in root window
+/// coordinates, from the top-level window down to the deepest child.
+pub fn query_windows_at(conn: &Connection, x: i32, y: i32) -> Vec<Window> {
+    unsafe {
+        let root = XDefaultRootWindow(conn.as_raw());
+        let mut windows = Vec::new();
+        let mut current = root;
+
+        loop {
+            let mut child = 0;
+            let mut child_x = 0;
+            let mut child_y = 0;
+
+            if XTranslateCoordinates(
+                conn.as_raw(),
+                root,
+                current,
+                x,
+                y,
+                &mut child_x,
+                &mut child_y,
+                &mut child,
+            ) == 0
+                || child == 0
+            {
+                return windows;
+            }
+
+            windows.push(child);
+            current = child;
+        }
+    }
+}
+
+/// Get the area of the monitor picked by `monitor` that is not covered by
+/// panels or docks, as `(x, y, width, height)` in root window coordinates.
+///
+/// The monitor bounds come from XRandR and are clipped to the EWMH work area
+/// of the current desktop. `window` is the window whose monitor is used for
+/// [`MonitorSelector::Current`].
+pub fn query_work_area(
+    conn: &Connection,
+    monitor: MonitorSelector,
+    window: Window,
+) -> (i32, i32, i32, i32) {
+    unsafe {
+        let root = XDefaultRootWindow(conn.as_raw());
+        let point = match monitor {
+            MonitorSelector::Primary => None,
+            MonitorSelector::Cursor => {
+                let (mut x, mut y) = (0, 0);
+                XQueryPointer(
+                    conn.as_raw(),
+                    root,
+                    &mut 0,
+                    &mut 0,
+                    &mut x,
+                    &mut y,
+                    &mut 0,
+                    &mut 0,
+                    &mut 0,
+                );
+                Some((x, y))
+            }
+            MonitorSelector::Current => {
+                let (x, y, width, height) = query_window_rect(conn, window);
+                Some((x + width / 2, y + height / 2))
+            }
+        };
+
+        let screen = XDefaultScreen(conn.as_raw());
+        let bounds = query_monitor_at(conn, point).unwrap_or((
+            0,
+            0,
+            XDisplayWidth(conn.as_raw(), screen),
+            XDisplayHeight(conn.as_raw(), screen),
+        ));
+
+        query_net_workarea(conn)
+            .and_then(|area| intersect(bounds, area))
+            .unwrap_or(bounds)
+    }
+}
+
+/// Get the bounds of the monitor containing `point`, or of the primary monitor
+/// if `point` is `None` or not on any monitor.
+fn query_monitor_at(conn: &Connection, point: Option<(i32, i32)>) -> Option<(i32, i32, i32, i32)> {
+    unsafe {
+        if XRRQueryExtension(conn.as_raw(), &mut 0, &mut 0) == 0 {
+            return None;
+        }
+
+        let mut count = 0;
+        let monitors = XRRGetMonitors(
+            conn.as_raw(),
+            XDefaultRootWindow(conn.as_raw()),
+            1,
+            &mut count,
+        );
+        if monitors.is_null() {
+            return None;
+        }
+
+        let list = std::slice::from_raw_parts(monitors, count.max(0) as usize);
+        let contains = |m: &&XRRMonitorInfo| match point {
+            Some((x, y)) => x >= m.x && y >= m.y && x < m.x + m.width && y < m.y + m.height,
+            None => false,
+        };
+
+        let bounds = list
+            .iter()
+            .find(contains)
+            .or_else(|| list.iter().find(|m| m.primary != 0))
+            .or_else(|| list.first())
+            .map(|m| (m.x, m.y, m.width, m.height));
+
+        XRRFreeMonitors(monitors);
+        bounds
+    }
+}
+
+/// Get the `_NET_WORKAREA` of the current desktop, if the window manager
+/// provides one.
+fn query_net_workarea(conn: &Connection) -> Option<(i32, i32, i32, i32)> {
+    let desktop = query_cardinals(conn, c"_NET_CURRENT_DESKTOP")
+        .and_then(|values| values.first().copied())
+        .unwrap_or(0);
+
+    let areas = query_cardinals(conn, c"_NET_WORKAREA")?;
+    let offset = usize::try_from(desktop).ok()?.checked_mul(4)?;
+    match areas.get(offset..offset.checked_add(4)?)? {
+        &[x, y, width, height] => Some((x as i32, y as i32, width as i32, height as i32)),
+        _ => None,
+    }
+}
+
+/// Read a `CARDINAL` property of the root window.
+fn query_cardinals(conn: &Connection, name: &'static CStr) -> Option<Vec<c_long>> {
+    let root = unsafe { XDefaultRootWindow(conn.as_raw()) };
+    let property = conn.get_property(root, conn.atom(name), XA_CARDINAL, false)?;
+    (property.format == 32).then(|| {
+        property
+            .items32()
+            .into_iter()
+            .map(|item| item as c_long)
+            .collect()
+    })
+}
+
+/// Get the client area of a window as `(x, y, width, height)` in root window
+/// coordinates.
+pub fn query_window_rect(conn: &Connection, window: Window) -> (i32, i32, i32, i32) {
+    unsafe {
+        let (mut width, mut height) = (0, 0);
+        XGetGeometry(
+            conn.as_raw(),
+            window,
+            &mut 0,
+            &mut 0,
+            &mut 0,
+            &mut width,
+            &mut height,
+            &mut 0,
+            &mut 0,
+        );
+
+        let (mut x, mut y) = (0, 0);
+        XTranslateCoordinates(
+            conn.as_raw(),
+            window,
+            XDefaultRootWindow(conn.as_raw()),
+            0,
+            0,
+            &mut x,
+            &mut y,
+            &mut 0,
+        );
+
+        (x, y, width as i32, height as i32)
+    }
+}
+
+/// Get the overlap of two `(x, y, width, height)` rectangles, if any.
+fn intersect(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> Option<(i32, i32, i32, i32)> {
+    let left = a.0.max(b.0);
+    let top = a.1.max(b.1);
+    let right = (a.0 + a.2).min(b.0 + b.2);
+    let bottom = (a.1 + a.3).min(b.1 + b.3);
+
+    (right > left && bottom > top).then_some((left, top, right - left, bottom - top))
+}
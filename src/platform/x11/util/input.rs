@@ -1,19 +1,25 @@
 use super::Connection;
 use crate::{Key, Modifiers};
-use std::ffi::{c_int, c_uint};
+use std::ffi::{CStr, c_char, c_int, c_uint};
+use std::ptr::null_mut;
 use x11::xinput2::*;
 use x11::xlib::*;
 
 /// Check if the given [`KeyRelease`] event is an auto-repeat and not a
 /// physical release event.
+///
+/// Only needed when the server does not support detectable auto-repeat, the
+/// release is then matched with a [`KeyPress`] of the same key at the same
+/// time right after it.
 pub fn is_autorepeat_release(conn: &Connection, event: &XKeyEvent) -> bool {
-    if event.type_ != KeyRelease {
+    if event.type_ != KeyRelease || conn.detectable_autorepeat() {
         return false;
     }
 
     unsafe {
         let mut next = XEvent { type_: 0 };
-        if XEventsQueued(conn.as_raw(), 0 /* QueuedAlready */) == 0 {
+        // the pair is sent together, but might be split across reads
+        if XEventsQueued(conn.as_raw(), 1 /* QueuedAfterReading */) == 0 {
             return false;
         }
 
@@ -146,6 +152,24 @@ pub fn keycode_to_key(code: c_uint) -> Option<Key> {
     })
 }
 
+/// Query the keys currently held down on the keyboard with [`XQueryKeymap`].
+pub fn query_keys_down(conn: &Connection) -> impl Iterator<Item = Key> {
+    let mut keymap = [0 as c_char; 32];
+    unsafe {
+        XQueryKeymap(conn.as_raw(), keymap.as_mut_ptr());
+    }
+
+    keymap
+        .into_iter()
+        .enumerate()
+        .flat_map(|(byte, bits)| {
+            (0..8)
+                .filter(move |bit| bits as u8 & (1 << bit) != 0)
+                .map(move |bit| (byte * 8 + bit) as c_uint)
+        })
+        .filter_map(keycode_to_key)
+}
+
 /// Convert modifier mask to a set of `Modifiers` flags, if possible.
 pub fn keymask_to_mods(mods: c_uint) -> Modifiers {
     Modifiers {
@@ -156,6 +180,33 @@ pub fn keymask_to_mods(mods: c_uint) -> Modifiers {
         num_lock: (mods & Mod2Mask) != 0,
         caps_lock: (mods & LockMask) != 0,
         scroll_lock: (mods & Mod5Mask) != 0,
+        fn_key: false,
+    }
+}
+
+/// Get the printable character a key press event would type with the current
+/// keyboard layout, if any.
+pub fn key_event_to_char(event: &XKeyEvent) -> Option<char> {
+    unsafe {
+        let mut event = *event;
+        let mut buffer = [0 as c_char; 8];
+        let mut keysym = 0;
+        let count = XLookupString(
+            &mut event,
+            buffer.as_mut_ptr(),
+            buffer.len() as c_int,
+            &mut keysym,
+            null_mut(),
+        );
+
+        let ch = match keysym {
+            // unicode keysyms are not representable in the latin-1 buffer
+            0x0100_0100..=0x0110_ffff => char::from_u32((keysym & 0x00ff_ffff) as u32)?,
+            _ if count == 1 => buffer[0] as u8 as char,
+            _ => return None,
+        };
+
+        (!ch.is_control()).then_some(ch)
     }
 }
 
@@ -219,6 +270,15 @@ pub struct XI2DeviceAxis {
     pub position: Option<f64>,
 }
 
+/// A physical pen (stylus or eraser) input device, usually a graphics tablet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XI2Pen {
+    /// The source id of the physical device.
+    pub source_id: c_int,
+    /// Is this the eraser end of the pen?
+    pub inverted: bool,
+}
+
 /// Kind of axis for a physical input device.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum XI2AxisKind {
@@ -268,37 +328,86 @@ impl XI2Extension {
         }
     }
 
-    /// Get all available axes for physical devices.
-    pub fn list_axes(&self, conn: &Connection) -> Vec<XI2DeviceAxis> {
-        let mut result = Vec::new();
-        xi2_list_classes_for(conn, XIAllDevices, |device, class| {
-            if device.deviceid != class.sourceid {
-                return; // physical devices only
-            }
+    /// Start or stop receiving [`XI_RawMotion`] events, the motion of the mouse
+    /// itself, which keeps coming while the pointer is held in place or stuck
+    /// at the edge of the screen.
+    ///
+    /// Raw events are only delivered to the root window.
+    pub fn select_raw_motion(&self, conn: &Connection, enabled: bool) {
+        let mut mask = [0; 4];
+        if enabled {
+            XISetMask(&mut mask, XI_RawMotion);
+        }
 
-            if class._type == XIScrollClass {
-                let info = unsafe { &*(class as *const _ as *const XIScrollClassInfo) };
-                if info.scroll_type == XIScrollTypeHorizontal {
-                    result.push(XI2DeviceAxis {
-                        source_id: info.sourceid,
-                        valuator: info.number,
-                        inv_increment: info.increment.recip(),
-                        position: None,
-                        kind: XI2AxisKind::HorizontalScroll,
+        unsafe {
+            XISelectEvents(
+                conn.as_raw(),
+                XDefaultRootWindow(conn.as_raw()),
+                &mut XIEventMask {
+                    deviceid: XIAllMasterDevices,
+                    mask_len: mask.len() as _,
+                    mask: mask.as_mut_ptr(),
+                },
+                1,
+            );
+        }
+    }
+
+    /// Get all available axes and pens of physical devices with a single
+    /// device query.
+    ///
+    /// XInput2 has no device type for pens, so we guess by the device name
+    /// (`libinput` and `wacom` drivers name them "... Pen", "... Stylus" and
+    /// "... Eraser").
+    #[allow(non_upper_case_globals)]
+    pub fn list_devices(&self, conn: &Connection) -> (Vec<XI2DeviceAxis>, Vec<XI2Pen>) {
+        let mut axes = Vec::new();
+        let mut pens = Vec::new();
+
+        xi2_list_devices(conn, XIAllDevices, |device, classes| {
+            if device._use == XISlavePointer && !device.name.is_null() {
+                let name = unsafe { CStr::from_ptr(device.name) }
+                    .to_string_lossy()
+                    .to_lowercase();
+
+                if name.contains("eraser") {
+                    pens.push(XI2Pen {
+                        source_id: device.deviceid,
+                        inverted: true,
+                    });
+                } else if name.contains("stylus") || name.contains("pen") {
+                    pens.push(XI2Pen {
+                        source_id: device.deviceid,
+                        inverted: false,
                     });
-                } else if info.scroll_type == XIScrollTypeVertical {
-                    result.push(XI2DeviceAxis {
+                }
+            }
+
+            for class in classes {
+                if device.deviceid != class.sourceid {
+                    continue; // physical devices only
+                }
+
+                if class._type == XIScrollClass {
+                    let info = unsafe { &*(class as *const _ as *const XIScrollClassInfo) };
+                    let kind = match info.scroll_type {
+                        XIScrollTypeHorizontal => XI2AxisKind::HorizontalScroll,
+                        XIScrollTypeVertical => XI2AxisKind::VerticalScroll,
+                        _ => continue,
+                    };
+
+                    axes.push(XI2DeviceAxis {
                         source_id: info.sourceid,
                         valuator: info.number,
                         inv_increment: info.increment.recip(),
                         position: None,
-                        kind: XI2AxisKind::VerticalScroll,
+                        kind,
                     });
                 }
             }
         });
 
-        result
+        (axes, pens)
     }
 }
 
@@ -306,11 +415,13 @@ impl XI2DeviceAxis {
     /// Reset the position of the axis to the current value reported by the
     /// device.
     pub fn reset_position(&mut self, conn: &Connection) {
-        xi2_list_classes_for(conn, self.source_id, |_, class| {
-            if class._type == XIValuatorClass {
-                let info = unsafe { &*(class as *const _ as *const XIValuatorClassInfo) };
-                if info.sourceid == self.source_id && info.number == self.valuator {
-                    self.position.replace(info.value);
+        xi2_list_devices(conn, self.source_id, |_, classes| {
+            for class in classes {
+                if class._type == XIValuatorClass {
+                    let info = unsafe { &*(class as *const _ as *const XIValuatorClassInfo) };
+                    if info.sourceid == self.source_id && info.number == self.valuator {
+                        self.position.replace(info.value);
+                    }
                 }
             }
         });
@@ -323,12 +434,39 @@ impl XI2DeviceAxis {
     }
 }
 
-/// Enumerate all (device, class) pairs for the given device id or all
+/// The motion along the x and y axes reported by an [`XI_RawMotion`] event,
+/// with the pointer acceleration applied so it matches the motion of the
+/// cursor.
+///
+/// # Safety
+/// `event` must come from [`XI2Extension::query_event`].
+pub unsafe fn raw_motion_delta(event: &XIRawEvent) -> (f64, f64) {
+    let mut delta = [0.0; 2];
+    unsafe {
+        let mask = std::slice::from_raw_parts(event.valuators.mask, event.valuators.mask_len as _);
+        if mask.is_empty() {
+            return (0.0, 0.0);
+        }
+
+        // values are packed, one per set bit of the mask
+        let mut values = event.valuators.values;
+        for (axis, delta) in delta.iter_mut().enumerate() {
+            if XIMaskIsSet(mask, axis as _) {
+                *delta = *values;
+                values = values.add(1);
+            }
+        }
+    }
+
+    (delta[0], delta[1])
+}
+
+/// Enumerate all devices and their classes for the given device id or all
 /// devices if `XIAllDevices` is given.
-fn xi2_list_classes_for(
+fn xi2_list_devices(
     conn: &Connection,
     device_id: c_int,
-    mut f: impl FnMut(&XIDeviceInfo, &XIAnyClassInfo),
+    mut f: impl FnMut(&XIDeviceInfo, &[&XIAnyClassInfo]),
 ) {
     unsafe {
         let mut count = 0;
@@ -339,12 +477,12 @@ fn xi2_list_classes_for(
 
         for i in 0..count {
             let device = &*info.add(i as usize);
-            let classes = std::slice::from_raw_parts(device.classes, device.num_classes as usize);
+            let classes = std::slice::from_raw_parts(
+                device.classes as *const &XIAnyClassInfo,
+                device.num_classes as usize,
+            );
 
-            for class in classes {
-                let class = &**class;
-                f(device, class);
-            }
+            f(device, classes);
         }
 
         XIFreeDeviceInfo(info as *mut _);
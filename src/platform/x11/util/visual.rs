@@ -1,13 +1,17 @@
 use super::Connection;
 use std::mem::zeroed;
+#[cfg(feature = "opengl")]
 use std::ptr::null_mut;
+#[cfg(feature = "opengl")]
 use x11::glx::{GLXFBConfig, glXGetVisualFromFBConfig};
 use x11::xlib::*;
+#[cfg(feature = "opengl")]
 use x11::xrender::{XRenderFindVisualFormat, XRenderPictFormat};
 
 /// A visual config is used for creating a colormap/window
 /// (and optionally an OpenGl context)
 pub struct VisualConfig {
+    #[cfg(feature = "opengl")]
     fb_config: GLXFBConfig,
     info: XVisualInfo,
 }
@@ -31,6 +35,7 @@ impl VisualConfig {
 
         Some(Self {
             info,
+            #[cfg(feature = "opengl")]
             fb_config: null_mut(),
         })
     }
@@ -40,6 +45,7 @@ impl VisualConfig {
     /// # Safety
     /// - The `fb_config` must be a valid GLX framebuffer config obtained from
     ///   the given connection.
+    #[cfg(feature = "opengl")]
     pub unsafe fn from_glx(conn: &Connection, fb_config: GLXFBConfig) -> Option<Self> {
         unsafe {
             let info_ptr = glXGetVisualFromFBConfig(conn.as_raw(), fb_config);
@@ -60,11 +66,13 @@ impl VisualConfig {
     }
 
     /// GLX framebuffer config if present. Can be `null`.
+    #[cfg(feature = "opengl")]
     pub fn glx_config(&self) -> GLXFBConfig {
         self.fb_config
     }
 
     /// Get the XRender picture format for this visual.
+    #[cfg(feature = "opengl")]
     pub fn xrender_format(&self, conn: &Connection) -> Option<XRenderPictFormat> {
         unsafe {
             let format = XRenderFindVisualFormat(conn.as_raw(), self.info.visual);
@@ -0,0 +1,259 @@
+use super::Connection;
+use crate::{DropEffect, Exchange};
+use std::ffi::{CStr, c_int, c_long, c_ulong};
+use std::time::Duration;
+use x11::xlib::*;
+
+/// The newest version of the XDND protocol we speak.
+const XDND_VERSION: c_long = 5;
+
+/// The oldest version of the XDND protocol a target may speak, older ones
+/// expect a different layout of the messages.
+const XDND_MIN_VERSION: c_long = 3;
+
+/// How long a target may take to answer a drop with `XdndFinished` before the
+/// drop is taken to have failed.
+pub const XDND_FINISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The source side of the XDND protocol, for a drag started with
+/// [`Window::start_drag`](crate::Window::start_drag).
+///
+/// Follows the cursor across the XDND aware windows below it: each one is
+/// entered, told every new position, and left again once the cursor moves on.
+/// Positions are sent one at a time, the next one waits until the target
+/// answered the last one with its status.
+pub struct XdndSource {
+    /// Our window, the owner of the `XdndSelection` for the drag
+    source: c_ulong,
+    /// The types the data is offered as, zero for unused slots. Three is all
+    /// that fits into `XdndEnter`
+    types: [c_ulong; 3],
+    /// The window under the cursor, and the protocol version we agreed on
+    target: Option<(c_ulong, c_long)>,
+    /// Did the target accept the data in its last status?
+    accepted: bool,
+    /// The action the target would perform, from its last status
+    action: c_ulong,
+    /// Is the target yet to answer the last position we sent?
+    status_pending: bool,
+    /// The last position (in root coordinates) and time that was held back
+    /// while waiting for the status
+    position_queued: Option<(c_int, c_int, Time)>,
+}
+
+impl XdndSource {
+    pub fn new(conn: &Connection, source: c_ulong, data: &Exchange) -> Self {
+        let types = match data {
+            Exchange::Files(_) => [conn.atom(c"text/uri-list"), 0, 0],
+            // images and custom formats are never dragged
+            _ => [
+                conn.atom(c"UTF8_STRING"),
+                conn.atom(c"text/plain"),
+                XA_STRING,
+            ],
+        };
+
+        Self {
+            source,
+            types,
+            target: None,
+            accepted: false,
+            action: 0,
+            status_pending: false,
+            position_queued: None,
+        }
+    }
+
+    /// Did the window under the cursor accept the data?
+    pub fn is_accepted(&self) -> bool {
+        self.target.is_some() && self.accepted
+    }
+
+    /// The cursor moved to the given root coordinates.
+    pub fn motion(&mut self, conn: &Connection, x: c_int, y: c_int, time: Time) {
+        let target = find_target(conn, x, y);
+        if target.map(|(window, _)| window) != self.target.map(|(window, _)| window) {
+            if let Some((window, _)) = self.target.take() {
+                self.send(conn, window, c"XdndLeave", [0; 4]);
+            }
+
+            self.accepted = false;
+            self.status_pending = false;
+            self.position_queued = None;
+
+            if let Some((window, version)) = target {
+                let [first, second, third] = self.types.map(|atom| atom as c_long);
+                self.send(
+                    conn,
+                    window,
+                    c"XdndEnter",
+                    [version << 24, first, second, third],
+                );
+            }
+
+            self.target = target;
+        }
+
+        if self.status_pending {
+            self.position_queued = Some((x, y, time));
+        } else {
+            self.position(conn, x, y, time);
+        }
+    }
+
+    /// Handle an `XdndStatus` message sent to our window.
+    pub fn status(&mut self, conn: &Connection, event: &XClientMessageEvent) {
+        let Some((window, _)) = self.target else {
+            return;
+        };
+
+        // a late answer of a window we already left
+        if event.data.get_long(0) as c_ulong != window {
+            return;
+        }
+
+        self.accepted = event.data.get_long(1) & 1 != 0;
+        self.action = event.data.get_long(4) as c_ulong;
+        self.status_pending = false;
+
+        if let Some((x, y, time)) = self.position_queued.take() {
+            self.position(conn, x, y, time);
+        }
+    }
+
+    /// End the drag. Drops the data on the target if `drop` is set and it
+    /// accepted the data, leaves the target otherwise.
+    ///
+    /// Returns what happened to the data, or `None` if it was dropped. The
+    /// target then reads it and reports what it did with `XdndFinished`, see
+    /// [`Self::finished`].
+    pub fn finish(&self, conn: &Connection, time: Time, drop: bool) -> Option<DropEffect> {
+        let Some((window, _)) = self.target else {
+            return Some(DropEffect::Reject);
+        };
+
+        if drop && self.accepted {
+            self.send(conn, window, c"XdndDrop", [0, time as c_long, 0, 0]);
+            None
+        } else {
+            self.send(conn, window, c"XdndLeave", [0; 4]);
+            Some(DropEffect::Reject)
+        }
+    }
+
+    /// Handle an `XdndFinished` message sent to our window after the drop.
+    /// Returns what the target did with the data, `None` if the message is
+    /// not about our drop.
+    pub fn finished(&self, conn: &Connection, event: &XClientMessageEvent) -> Option<DropEffect> {
+        let (window, version) = self.target?;
+        if event.data.get_long(0) as c_ulong != window {
+            return None;
+        }
+
+        // before version 5 the target only tells that it is done, with the action of
+        // its last status
+        let (accepted, action) = match version >= 5 {
+            true => (
+                event.data.get_long(1) & 1 != 0,
+                event.data.get_long(2) as c_ulong,
+            ),
+            false => (true, self.action),
+        };
+
+        Some(match action {
+            _ if !accepted => DropEffect::Reject,
+            action if action == conn.atom(c"XdndActionCopy") => DropEffect::Copy,
+            action if action == conn.atom(c"XdndActionMove") => DropEffect::Move,
+            action if action == conn.atom(c"XdndActionLink") => DropEffect::Link,
+            _ => DropEffect::Generic,
+        })
+    }
+
+    fn position(&mut self, conn: &Connection, x: c_int, y: c_int, time: Time) {
+        let Some((window, _)) = self.target else {
+            return;
+        };
+
+        // root coordinates are packed into 16 bits each
+        let packed = ((x as c_long & 0xffff) << 16) | (y as c_long & 0xffff);
+        let action = conn.atom(c"XdndActionCopy") as c_long;
+        self.send(
+            conn,
+            window,
+            c"XdndPosition",
+            [0, packed, time as c_long, action],
+        );
+        self.status_pending = true;
+    }
+
+    /// Send an XDND message to `window`, the first field is always our window.
+    fn send(&self, conn: &Connection, window: c_ulong, message: &'static CStr, data: [c_long; 4]) {
+        let mut fields = ClientMessageData::default();
+        fields.set_long(0, self.source as c_long);
+        for (index, value) in data.into_iter().enumerate() {
+            fields.set_long(index + 1, value);
+        }
+
+        unsafe {
+            XSendEvent(
+                conn.as_raw(),
+                window,
+                0,
+                NoEventMask,
+                &mut XEvent {
+                    client_message: XClientMessageEvent {
+                        type_: ClientMessage,
+                        serial: 0,
+                        send_event: 1,
+                        display: conn.as_raw(),
+                        window,
+                        message_type: conn.atom(message),
+                        format: 32,
+                        data: fields,
+                    },
+                },
+            );
+        }
+    }
+}
+
+/// Find the XDND aware window at the given root coordinates, and the protocol
+/// version to speak with it. Walks down from the root window, so the window
+/// manager frames around top-level windows are skipped.
+fn find_target(conn: &Connection, x: c_int, y: c_int) -> Option<(c_ulong, c_long)> {
+    unsafe {
+        let root = XDefaultRootWindow(conn.as_raw());
+        let mut window = root;
+
+        loop {
+            let mut child = 0;
+            let status = XTranslateCoordinates(
+                conn.as_raw(),
+                root,
+                window,
+                x,
+                y,
+                &mut 0,
+                &mut 0,
+                &mut child,
+            );
+
+            if status == 0 || child == 0 {
+                return None;
+            }
+
+            window = child;
+            if let Some(version) = xdnd_version(conn, window) {
+                return (version >= XDND_MIN_VERSION)
+                    .then_some((window, version.min(XDND_VERSION)));
+            }
+        }
+    }
+}
+
+/// The XDND version a window supports, from its `XdndAware` property. `None`
+/// if the window does not take drops.
+fn xdnd_version(conn: &Connection, window: c_ulong) -> Option<c_long> {
+    let property = conn.get_property(window, conn.atom(c"XdndAware"), XA_ATOM, false)?;
+    property.items32().first().map(|&version| version as c_long)
+}
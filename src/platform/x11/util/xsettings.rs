@@ -0,0 +1,216 @@
+use super::{Connection, query_window_bytes};
+use crate::SystemMetrics;
+use std::ffi::c_int;
+use std::time::Duration;
+use x11::xlib::*;
+
+/// Get the window of the settings daemon that owns the `XSETTINGS` of the
+/// default screen, or 0 if none runs.
+///
+/// Its `_XSETTINGS_SETTINGS` property holds the settings, and changes when
+/// they do.
+pub fn query_xsettings_owner(conn: &Connection) -> Window {
+    unsafe {
+        let screen: c_int = XDefaultScreen(conn.as_raw());
+        let selection = conn.intern(&format!("_XSETTINGS_S{screen}"));
+        conn.selection_owner(selection)
+    }
+}
+
+/// Get the system metrics from the `XSETTINGS` held by `owner`, see
+/// [`query_xsettings_owner`]. The ones that are not set keep their defaults.
+pub fn query_system_metrics(conn: &Connection, owner: Window) -> SystemMetrics {
+    let mut metrics = SystemMetrics::default();
+    if owner == 0 {
+        return metrics;
+    }
+
+    let Some(data) = query_window_bytes(conn, owner, conn.atom(c"_XSETTINGS_SETTINGS")) else {
+        return metrics;
+    };
+
+    let mut blink = true;
+    for (name, value) in parse_int_settings(&data) {
+        let millis = || Duration::from_millis(value.max(0) as u64);
+        match name {
+            b"Net/DoubleClickTime" => metrics.double_click_time = millis(),
+            b"Net/DndDragThreshold" => metrics.drag_threshold = value.max(0) as f64,
+            b"Net/CursorBlink" => blink = value != 0,
+            // the time of a whole blink, on and off
+            b"Net/CursorBlinkTime" => metrics.caret_blink_interval = Some(millis() / 2),
+            _ => {}
+        }
+    }
+
+    if !blink {
+        metrics.caret_blink_interval = None;
+    }
+
+    metrics
+}
+
+/// Read the integer settings out of a `_XSETTINGS_SETTINGS` property, skipping
+/// the strings and colors. Stops at the first malformed setting.
+fn parse_int_settings(data: &[u8]) -> Vec<(&[u8], i32)> {
+    let msb_first = data.first() == Some(&1); // `MSBFirst`
+    let read = |at: usize, len: usize| data.get(at..at.checked_add(len)?);
+    let card16 = |at| {
+        let bytes = read(at, 2)?.try_into().ok()?;
+        Some(match msb_first {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    };
+    let card32 = |at| {
+        let bytes = read(at, 4)?.try_into().ok()?;
+        Some(match msb_first {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    };
+
+    let mut settings = Vec::new();
+    let mut parse = || {
+        // byte order, padding and serial come first
+        let count = card32(8)?;
+        let mut at = 12usize;
+
+        for _ in 0..count {
+            let kind = *data.get(at)?;
+            let name_len = card16(at + 2)? as usize;
+            let name = read(at + 4, name_len)?;
+            // the name is padded to 4 bytes, followed by the serial of its last change
+            at += 4 + name_len.next_multiple_of(4) + 4;
+
+            match kind {
+                0 => {
+                    settings.push((name, card32(at)? as i32));
+                    at += 4;
+                }
+                1 => at += 4 + (card32(at)? as usize).next_multiple_of(4),
+                2 => at += 8,
+                _ => return None,
+            }
+        }
+
+        Some(())
+    };
+
+    let _ = parse();
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A setting of a `_XSETTINGS_SETTINGS` property.
+    enum Value {
+        Int(i32),
+        Str(&'static str),
+        Color,
+    }
+
+    /// Build a property with `settings`, in either byte order.
+    fn settings(msb_first: bool, settings: &[(&str, Value)]) -> Vec<u8> {
+        let card16 = |value: u16| match msb_first {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        let card32 = |value: u32| match msb_first {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        let padded = |data: &mut Vec<u8>, bytes: &[u8]| {
+            data.extend_from_slice(bytes);
+            data.resize(data.len().next_multiple_of(4), 0);
+        };
+
+        let mut data = vec![msb_first as u8, 0, 0, 0];
+        data.extend(card32(7)); // serial
+        data.extend(card32(settings.len() as u32));
+
+        for (name, value) in settings {
+            let kind = match value {
+                Value::Int(_) => 0,
+                Value::Str(_) => 1,
+                Value::Color => 2,
+            };
+
+            data.extend([kind, 0]);
+            data.extend(card16(name.len() as u16));
+            padded(&mut data, name.as_bytes());
+            data.extend(card32(3)); // last change serial
+
+            match value {
+                Value::Int(value) => data.extend(card32(*value as u32)),
+                Value::Str(value) => {
+                    data.extend(card32(value.len() as u32));
+                    padded(&mut data, value.as_bytes());
+                }
+                Value::Color => data.extend([0xff; 8]),
+            }
+        }
+
+        data
+    }
+
+    fn sample(msb_first: bool) -> Vec<u8> {
+        settings(
+            msb_first,
+            &[
+                ("Net/DoubleClickTime", Value::Int(400)),
+                ("Net/ThemeName", Value::Str("Adwaita")),
+                ("Gtk/ColorScheme", Value::Color),
+                ("Net/CursorBlinkTime", Value::Int(-1)),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_parse_int_settings() {
+        let expected: [(&[u8], i32); 2] =
+            [(b"Net/DoubleClickTime", 400), (b"Net/CursorBlinkTime", -1)];
+
+        assert_eq!(parse_int_settings(&sample(false)), expected);
+        assert_eq!(parse_int_settings(&sample(true)), expected);
+    }
+
+    #[test]
+    fn test_parse_int_settings_truncated() {
+        let data = sample(false);
+
+        // everything up to the cut is kept
+        let cut = data.len() - 2;
+        assert_eq!(
+            parse_int_settings(data.get(..cut).unwrap_or_default()),
+            [(b"Net/DoubleClickTime".as_slice(), 400)]
+        );
+
+        // cut in the header or in the first setting
+        for cut in [0, 6, 13, 20, 40] {
+            assert!(parse_int_settings(data.get(..cut).unwrap_or_default()).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_int_settings_unknown_kind() {
+        let mut data = settings(
+            false,
+            &[
+                ("Net/DoubleClickTime", Value::Int(400)),
+                ("Net/Unknown", Value::Int(1)),
+            ],
+        );
+
+        // the second setting starts after the header (12 bytes) and the first (32 bytes)
+        if let Some(kind) = data.get_mut(44) {
+            *kind = 3;
+        }
+
+        assert_eq!(
+            parse_int_settings(&data),
+            [(b"Net/DoubleClickTime".as_slice(), 400)]
+        );
+    }
+}
@@ -1,31 +1,90 @@
+use super::event_loop;
+#[cfg(feature = "opengl")]
 use super::gl::GlContext;
 use super::util::*;
-use crate::platform::{OpenMode, PlatformOpenGl, PlatformWaker, PlatformWindow};
+use crate::platform::{
+    Damage, EventQueue, FrameJitter, KeyState, OpenMode, OpenUrlCallback, PlatformWaker,
+    PlatformWindow, QueueKind, Timers, Watchdog, WindowEntry, clipboard_formats, filter_clipboard,
+    open_url_in_background, report_reentrancy,
+};
+#[cfg(feature = "opengl")]
+use crate::platform::{PlatformOpenGl, create_opengl};
 use crate::*;
-use libc::c_ulong;
+use libc::{c_long, c_ulong};
 use raw_window_handle::RawWindowHandle;
+use std::any::{Any, TypeId};
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use std::ffi::{CStr, CString, c_int, c_uint, c_void};
 use std::mem::zeroed;
-use std::os::unix::ffi::OsStrExt;
-use std::ptr::null_mut;
-use std::sync::{Arc, RwLock};
-use std::thread;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr::{NonNull, null_mut};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 use x11::xinput2::{
-    XI_Enter, XI_HierarchyChanged, XI_Motion, XIAllDevices, XIDeviceEvent, XIEventMask,
-    XIMaskIsSet, XISelectEvents, XISetMask,
+    XI_Enter, XI_HierarchyChanged, XI_Motion, XI_RawMotion, XIAllDevices, XIDeviceEvent,
+    XIEnterEvent, XIEventMask, XIMaskIsSet, XIRawEvent, XISelectEvents, XISetMask,
 };
 use x11::xlib::*;
-
-/// The atom used for implementing [`PlatformWaker::wakeup`] and
-/// [`WindowHandler::wakeup`].
-///
-/// It is implemented by sending a custom [`ClientMessage`] event to the window,
-/// which will interrupt the wait immediately and get handled in
-/// [`WindowImpl::handle_event`].
-pub const ATOM_WAKEUP: &CStr = c"PICOVIEW_WAKEUP";
+use x11::xrandr::XRRUpdateConfiguration;
+
+/// The `flags` bit of `_MOTIF_WM_HINTS` that says the `decorations` field is
+/// set.
+const MWM_HINTS_DECORATIONS: c_ulong = 1 << 1;
+
+/// The core protocol button numbers of the buttons reported as [`MouseButton`].
+const MOUSE_BUTTONS: [(c_uint, MouseButton); 5] = [
+    (1, MouseButton::Left),
+    (2, MouseButton::Middle),
+    (3, MouseButton::Right),
+    (8, MouseButton::Back),
+    (9, MouseButton::Forward),
+];
+
+/// The pointer events we grab while dragging, see
+/// [`PlatformWindow::start_drag`].
+const DRAG_POINTER_MASK: c_uint =
+    (ButtonPressMask | ButtonReleaseMask | PointerMotionMask) as c_uint;
+
+/// The pointer events we grab while holding the cursor, see
+/// [`PlatformWindow::set_cursor_grab`].
+const CURSOR_GRAB_MASK: c_uint =
+    (ButtonPressMask | ButtonReleaseMask | PointerMotionMask | EnterWindowMask | LeaveWindowMask)
+        as c_uint;
+
+/// Atoms used during window creation and event handling, interned all at once
+/// when opening a window to avoid a round trip per atom.
+const ATOMS_PREFETCH: &[&CStr] = &[
+    c"ATOM",
+    c"CLIPBOARD",
+    c"INCR",
+    c"TARGETS",
+    c"UTF8_STRING",
+    c"WM_DELETE_WINDOW",
+    c"WM_PROTOCOLS",
+    c"WM_TAKE_FOCUS",
+    c"XSEL_DATA",
+    c"XdndActionCopy",
+    c"XdndActionLink",
+    c"XdndActionMove",
+    c"XdndActionPrivate",
+    c"XdndAware",
+    c"XdndDrop",
+    c"XdndEnter",
+    c"XdndFinished",
+    c"XdndLeave",
+    c"XdndPosition",
+    c"XdndSelection",
+    c"XdndStatus",
+    c"_MOTIF_WM_HINTS",
+    c"_NET_WM_NAME",
+    c"_XSETTINGS_SETTINGS",
+    c"text/plain",
+    c"text/uri-list",
+];
 
 /// X11 implementation of [`PlatformWindow`].
 pub struct WindowImpl {
@@ -33,6 +92,9 @@ pub struct WindowImpl {
     window_id: c_ulong,
     /// The X11 window parent ID.
     window_parent: Cell<c_ulong>,
+    /// Registers the window for [`crate::window_at`], provides
+    /// [`PlatformWindow::id`].
+    window_entry: WindowEntry,
     /// The X11 colormap for the window.
     ///
     /// Needs to be freed when the window is destroyed, otherwise it will leak.
@@ -43,21 +105,86 @@ pub struct WindowImpl {
     /// [`PlatformWaker`] for this window, used to wake up the event loop from
     /// another thread.
     waker: Arc<WindowWakerImpl>,
+    /// The [`WindowGroup`] this window is a member of, if any
+    group: Option<WindowGroup>,
 
     /// The refresh interval for the window, used to determine how often to call
     /// [`WindowHandler::frame`].
-    refresh_interval: Duration,
+    refresh_interval: Cell<Duration>,
+    /// When the next [`WindowHandler::frame`] is due.
+    next_frame: Cell<Instant>,
+    /// The longest time to wait for events before calling
+    /// [`WindowHandler::idle`], see [`WindowBuilder::with_max_poll_timeout`].
+    max_poll_timeout: Option<Duration>,
+    /// See [`Window::set_timer`], run by the main loop
+    timers: Timers<Instant>,
+    /// Measures the time spent in the handler, see
+    /// [`WindowBuilder::with_watchdog`].
+    watchdog: Watchdog,
+    /// See [`Window::frame_jitter`].
+    frame_jitter: FrameJitter,
+    /// See [`Window::is_key_down`].
+    keys_down: KeyState,
+    /// Set by [`Window::redraw_now`] while the handler is busy, the frame is
+    /// dispatched once the current event returns.
+    redraw_pending: Cell<bool>,
     /// The DPI scale for the window, used as a hint for the client to scale the
     /// content of the window. Provided via [`PlatformWindow::scale`].
-    dpi_scale: f64,
-
-    /// Is the window closing? If true, the event loop will exit as soon as
-    /// possible, and the window will be destroyed.
+    dpi_scale: Cell<f64>,
+    /// The type of XRandR screen change events, if available. Those and
+    /// `RESOURCE_MANAGER` changes on the root window update the refresh
+    /// interval and the DPI scale.
+    screen_change_event: Option<c_int>,
+    /// The window of the settings daemon, see [`query_xsettings_owner`]. Its
+    /// property changes are selected to emit
+    /// [`WindowHandler::system_metrics_changed`]. A daemon that starts later
+    /// is not picked up.
+    xsettings_owner: c_ulong,
+    /// The top-level window a popup belongs to, see [`query_popup_owner`], 0
+    /// for other windows. The popup is dismissed once it loses the focus.
+    popup_owner: c_ulong,
+    /// The top-level window of the parent of a transient window or popup, 0
+    /// for other windows. The window is hidden while it is unmapped (like
+    /// when it is minimized), see [`WindowBuilder::with_follow_parent`].
+    owner_window: c_ulong,
+    /// The parent a transient window or popup was opened for, 0 for other
+    /// windows. Unlike [`Self::window_parent`] it is not replaced by the frame
+    /// the window manager reparents us into.
+    transient_for: c_ulong,
+
+    /// Is the window closing? If true, no more events are dispatched, the event
+    /// loop exits as soon as possible, and the window is destroyed.
     is_closing: Cell<bool>,
 
     /// Is the window destroyed externally? If true, the window has been
     /// destroyed and should not be used/destroyed again.
     is_destroyed: Cell<bool>,
+    /// Was the window closed because its owner was destroyed? Reported as
+    /// [`DestroyReason::Destroyed`], like on the other platforms.
+    is_owner_destroyed: Cell<bool>,
+    /// Set when the event loop stopped because the connection failed, reported
+    /// with [`WindowHandler::destroyed`]
+    is_failed: Cell<bool>,
+
+    /// Is the window embedded into a host window? Uncaptured key events are
+    /// forwarded to the parent only in this case.
+    is_embedded: bool,
+    /// See [`WindowBuilder::with_unmatched_mouse_release`].
+    unmatched_mouse_release: bool,
+    /// See [`WindowBuilder::with_raw_scroll`].
+    raw_scroll: bool,
+    /// Does the window follow the size of its parent? See
+    /// [`WindowBuilder::with_fill_parent`].
+    is_filling_parent: bool,
+    /// Should the window take the keyboard focus once it is mapped? See
+    /// [`WindowBuilder::with_initial_keyboard_focus`].
+    is_focus_pending: Cell<bool>,
+    /// Does the window move along with [`Self::owner_window`]? See
+    /// [`WindowBuilder::with_follow_parent`].
+    is_following_owner: bool,
+    /// Was the window hidden because [`Self::owner_window`] was? It is shown
+    /// again along with it.
+    is_hidden_with_owner: Cell<bool>,
 
     /// Last key modifiers state provided by the server, used to check for
     /// changes.
@@ -68,6 +195,21 @@ pub struct WindowImpl {
     /// Last mouse cursor position provided by the server, used to check for
     /// changes.
     last_cursor_position: Cell<Option<Point>>,
+    /// Mouse buttons we reported as pressed and not yet released, as a mask
+    /// of `1 << button`. The pointer is implicitly grabbed while any of them is
+    /// held, so [`WindowHandler::mouse_leave`] is delayed until they are
+    /// released.
+    last_buttons_held: Cell<u32>,
+    /// The grab set with [`PlatformWindow::set_cursor_grab`]. Anything but
+    /// [`CursorGrab::None`] means we hold an active pointer grab confined to
+    /// our window.
+    cursor_grab: Cell<CursorGrab>,
+    /// While the cursor is locked, the point it is warped back to and the
+    /// virtual position reported instead of the real one.
+    cursor_lock: Cell<Option<(Point, Point)>>,
+    /// Time and serial of the last input event provided by the server, see
+    /// [`PlatformWindow::last_event_token`].
+    last_event_token: Cell<EventToken>,
     /// Last window position provided by the server, used to check
     /// for changes and for restoring the window state on a call to
     /// [`PlatformWindow::set_visible`].
@@ -76,9 +218,32 @@ pub struct WindowImpl {
     /// changes and for restoring the window state on a call to
     /// [`PlatformWindow::set_visible`].
     last_window_size: Cell<Option<Size>>,
+    /// The XRandR CRTC showing the center of the window when last checked.
+    /// Embedded windows are not told when the host moves them, so for those
+    /// it is only checked again when the pointer enters.
+    last_monitor: Cell<Option<c_ulong>>,
+    /// The bounds of the XRandR CRTCs, see [`query_crtc_rects`]. Read again on
+    /// `RRScreenChangeNotify`, so finding the monitor of the window only takes
+    /// a coordinate translation.
+    crtc_rects: RefCell<Vec<CrtcRect>>,
+    /// The ICC profile of the monitor in [`Self::last_monitor`], read again
+    /// when the window changes monitors or a profile on the root changes.
+    last_color_profile: RefCell<Option<IccProfile>>,
+    /// Last minimum and maximum client size set by the client, kept so
+    /// [`WindowImpl::update_size_hints`] can send all hints at once.
+    last_min_size: Cell<Size>,
+    last_max_size: Cell<Size>,
+    /// Last resize constraints set by the client, see
+    /// [`PlatformWindow::set_resize_hints`].
+    last_resize_hints: Cell<ResizeHints>,
     /// Last window visibility state provided by the server, used to check for
     /// changes.
     last_window_visible: Cell<bool>,
+    /// Last position of [`Self::owner_window`] provided by the server, the
+    /// window moves by the same distance when it changes.
+    last_owner_position: Cell<(c_int, c_int)>,
+    /// Last decorations setting, see [`PlatformWindow::set_decorations`].
+    last_decorations: Cell<bool>,
     /// Last window focus state provided by the server, used to check for
     /// changes.
     last_window_focused: Cell<bool>,
@@ -87,36 +252,90 @@ pub struct WindowImpl {
     /// Last gesture zoom level provided by the server, used for computing
     /// deltas.
     last_gesture_zoom: Cell<f64>,
+    /// Damage accumulated from a series of Expose events, reported as a single
+    /// [`WindowHandler::damage`] event once the series ends.
+    last_damage: Cell<Option<Rect>>,
+    /// Do we still owe the handler a full-window damage event after the window
+    /// got mapped? Some WMs don't send Expose until something overlaps us.
+    is_damage_pending: Cell<bool>,
 
     /// The current clipboard data, used to provide data to other
     /// applications.
     exchange_clipboard: RefCell<Exchange>,
-    /// The current drag and drop data, used to provide data to other
-    /// applications. TODO: implement drag into other windows.
+    /// The data of the last drag started with [`PlatformWindow::start_drag`],
+    /// served as the `XdndSelection` to the window it is dropped on.
     exchange_dragndrop: RefCell<Exchange>,
+    /// Selection values too large for a single property, sent in parts as
+    /// their requestors ask for them.
+    incr_transfers: RefCell<Vec<IncrTransfer>>,
+    /// The drag started with [`PlatformWindow::start_drag`], `Some` while we
+    /// hold the pointer grab for it.
+    drag_source: RefCell<Option<XdndSource>>,
+    /// A drag whose data was dropped, waiting until the deadline for the
+    /// target to report what it did with it.
+    drag_dropped: RefCell<Option<(XdndSource, Instant)>>,
+    /// Was a drag started during the current event? The held buttons are
+    /// released once the handler returns.
+    is_drag_starting: Cell<bool>,
 
     /// Cache of X11 cursor IDs for each supported mouse cursor icon.
     cursor_cache: RefCell<HashMap<MouseCursor, X11Cursor>>,
+    /// Created on the first [`PlatformWindow::present_pixels`].
+    image_presenter: RefCell<Option<ImagePresenter>>,
+    /// The XIM input context of the window, `None` if there is no input method.
+    input_method: Option<InputMethod>,
 
     /// XInput2 extension info, `None` if not available. Used for high precision
     /// mouse events and gestures.
     xi2_info: Option<XI2Extension>,
     /// List of XInput2 device axes, used for computing scroll deltas.
     xi2_axes: RefCell<Vec<XI2DeviceAxis>>,
+    /// List of XInput2 pen devices, used for pen proximity events.
+    xi2_pens: RefCell<Vec<XI2Pen>>,
+    /// The pen that is currently hovering over the window, if any.
+    last_pen: Cell<Option<XI2Pen>>,
 
     /// Our window handler, this is what handles all window events generated by
     /// the server.
     handler: RefCell<Option<Box<dyn WindowHandler>>>,
+    /// Events that arrived while the handler was running, delivered once it
+    /// returns. See [`Self::deferred_event`].
+    ///
+    /// Bounded, see [`EventQueue`].
+    #[allow(clippy::type_complexity)]
+    event_deferred: EventQueue<dyn FnOnce(&Self, &mut dyn WindowHandler)>,
+    /// Data attached with [`Window::set_user_data`], dropped after the handler.
+    user_data: RefCell<Option<Rc<dyn Any>>>,
 
     /// OpenGL context, or an error if the context could not be created.
     /// Used for [`PlatformWindow::opengl`].
+    #[cfg(feature = "opengl")]
     gl_context: Result<GlContext, OpenGlError>,
+    /// See [`WindowBuilder::with_external_opengl`].
+    #[cfg(feature = "opengl")]
+    external_gl_context: Option<ExternalGlContext>,
 }
 
 /// X11 implementation of [`PlatformWaker`].
+///
+/// Everything needed to send the wakeup message is resolved up front, so that
+/// [`PlatformWaker::wakeup`] does not allocate or do a server round-trip.
+///
+/// Wakeups are sent through an eventfd the event loop polls next to the
+/// display connection, rather than as a message to the window. Writing to it
+/// is a single syscall that neither locks (Xlib locks the display for every
+/// request) nor allocates, so waking up is safe from a realtime thread. The
+/// fd lives as long as the waker, so it can not be closed under a wakeup.
 pub struct WindowWakerImpl {
-    window_id: c_ulong,
-    display: RwLock<*mut Display>,
+    event_fd: OwnedFd,
+    /// Set once the window is dropped, later wakeups fail
+    is_closed: AtomicBool,
+    /// Set by [`PlatformWaker::close`], handled by the next wakeup instead of
+    /// calling [`WindowHandler::wakeup`]
+    is_close_requested: AtomicBool,
+    /// The thread running the event loop of an embedded or transient window,
+    /// joined by [`PlatformWaker::close_and_wait`]
+    thread: Mutex<Option<JoinHandle<()>>>,
 }
 
 // while it is not really Send, we promise to only send it to a different thread
@@ -124,34 +343,88 @@ pub struct WindowWakerImpl {
 // long as we move all instances of Connection to the other thread we should be
 // ok TODO: maybe remove that?
 unsafe impl Send for WindowImpl {}
-unsafe impl Send for WindowWakerImpl {}
-unsafe impl Sync for WindowWakerImpl {}
 
 impl WindowImpl {
+    /// See [`crate::window_at`]. Our windows live on their own connections, so
+    /// this asks the server through a temporary one.
+    pub fn window_at(point: Point) -> Option<WindowId> {
+        let connection = Connection::open()?;
+
+        let (x, y) = point.to_pixels();
+        query_windows_at(&connection, x, y)
+            .into_iter()
+            .rev()
+            .find_map(|window| WindowEntry::find(window as usize))
+    }
+
+    /// See [`crate::system_metrics`]. Shares a connection opened by the first
+    /// call, so asking for the metrics once per event stays cheap.
+    pub fn system_metrics() -> SystemMetrics {
+        /// The connection is only used while the lock is held, and never cloned.
+        struct Shared(Option<Connection>);
+        unsafe impl Send for Shared {}
+
+        // never dropped, like the other global state of the connections
+        static SHARED: Mutex<Shared> = Mutex::new(Shared(None));
+
+        let mut shared = SHARED.lock().unwrap_or_else(PoisonError::into_inner);
+        if shared.0.is_none() {
+            shared.0 = Connection::open();
+        }
+
+        let Some(connection) = &shared.0 else {
+            return SystemMetrics::default();
+        };
+
+        query_system_metrics(connection, query_xsettings_owner(connection))
+    }
+
     pub unsafe fn open(options: WindowBuilder, mode: OpenMode) -> Result<WindowWaker, WindowError> {
         unsafe {
+            let parent = match mode {
+                OpenMode::TopLevel => None,
+                OpenMode::Embedded(handle)
+                | OpenMode::Transient(handle)
+                | OpenMode::Popup(handle, _) => match handle {
+                    RawWindowHandle::Xlib(handle) => Some(handle.window),
+                    RawWindowHandle::Xcb(handle) => Some(handle.window.get() as u64),
+                    _ => return Err(WindowError::InvalidParent),
+                },
+            };
+
             // open a new connection first
             let connection = Connection::open().ok_or_else(|| {
                 WindowError::Platform("Failed to connect to X server".to_string())
             })?;
 
+            let event_fd = libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK);
+            if event_fd == -1 {
+                return Err(WindowError::Platform(
+                    std::io::Error::last_os_error().to_string(),
+                ));
+            }
+            let event_fd = OwnedFd::from_raw_fd(event_fd);
+
+            // atoms are interned in one batch, and the requests that set up the window are
+            // queued without waiting for replies. the queries that need an answer (the
+            // owner, monitors, refresh rate, scale) still take a round trip each, and
+            // errors are collected by a sync once everything is set up
+            connection.prefetch_atoms(ATOMS_PREFETCH);
+
             let default_root = XDefaultRootWindow(connection.as_raw());
-            let window_parent = match mode {
-                OpenMode::Blocking => default_root,
-                OpenMode::Embedded(RawWindowHandle::Xlib(handle)) => handle.window,
-                OpenMode::Embedded(RawWindowHandle::Xcb(handle)) => handle.window.get() as u64,
-                OpenMode::Transient(RawWindowHandle::Xlib(handle)) => handle.window,
-                OpenMode::Transient(RawWindowHandle::Xcb(handle)) => handle.window.get() as u64,
-                _ => return Err(WindowError::InvalidParent),
-            };
+            let window_parent = parent.unwrap_or(default_root);
+
+            // try to find the best config for the provided OpenGL config
+            #[cfg(feature = "opengl")]
+            // the visual is picked before the window exists, so every config in a
+            // fallback chain has to make do with the first one that has a match
+            let gl_visual_info = options.opengl.iter().find_map(|config| {
+                GlContext::find_best_config(&connection, config, options.transparent)
+            });
+            #[cfg(not(feature = "opengl"))]
+            let gl_visual_info = None;
 
-            let visual_info = options
-                .opengl
-                .as_ref()
-                .and_then(|config| {
-                    // try to find the best config for the provided OpenGL config
-                    GlContext::find_best_config(&connection, config, options.transparent)
-                })
+            let visual_info = gl_visual_info
                 .or_else(|| {
                     // try first our preferred config if an opengl one is unavailable
                     VisualConfig::try_new_true_color(
@@ -179,14 +452,33 @@ impl WindowImpl {
             });
 
             // finally, create our window
+            let (window_root, window_position) = match mode {
+                OpenMode::Embedded(..) => (window_parent, options.parent_relative_position),
+                // override-redirect windows stay where they are created, right below the anchor
+                OpenMode::Popup(_, anchor) => {
+                    let (mut x, mut y, mut child) = (0, 0, 0);
+                    XTranslateCoordinates(
+                        connection.as_raw(),
+                        window_parent,
+                        default_root,
+                        anchor.left,
+                        anchor.bottom,
+                        &mut x,
+                        &mut y,
+                        &mut child,
+                    );
+
+                    (default_root, Point::from((x, y)))
+                }
+                _ => (default_root, Point::default()),
+            };
+
+            let (window_x, window_y) = window_position.to_pixels();
             let window_id = XCreateWindow(
                 connection.as_raw(),
-                match mode {
-                    OpenMode::Embedded(..) => window_parent,
-                    _ => default_root,
-                },
-                0,
-                0,
+                window_root,
+                window_x,
+                window_y,
                 200,
                 200,
                 0,
@@ -195,10 +487,12 @@ impl WindowImpl {
                     .map_or(CopyFromParent, |x| x.info().depth),
                 InputOutput as u32,
                 visual_info.as_ref().map_or(null_mut(), |x| x.info().visual),
-                CWEventMask | CWColormap | CWBorderPixel,
+                CWEventMask | CWColormap | CWBorderPixel | CWOverrideRedirect,
                 &mut XSetWindowAttributes {
                     border_pixel: 0,
                     colormap: window_colormap,
+                    // popups are placed by us and never decorated by the window manager
+                    override_redirect: matches!(mode, OpenMode::Popup(..)) as Bool,
                     event_mask: ButtonPressMask
                         | ButtonReleaseMask
                         | StructureNotifyMask
@@ -207,7 +501,9 @@ impl WindowImpl {
                         | LeaveWindowMask
                         | PointerMotionMask
                         | FocusChangeMask
-                        | ExposureMask,
+                        | ExposureMask
+                        // large selection values arrive in parts, announced by property changes
+                        | PropertyChangeMask,
                     ..zeroed()
                 },
             );
@@ -227,22 +523,124 @@ impl WindowImpl {
                 ));
             }
 
+            // the top-level window of the group leader, top-level windows in the group are
+            // transient for it
+            let group_leader = options
+                .group
+                .as_ref()
+                .and_then(|group| group.leader())
+                .and_then(|leader| match leader {
+                    RawWindowHandle::Xlib(handle) => Some(handle.window),
+                    RawWindowHandle::Xcb(handle) => Some(handle.window.get() as u64),
+                    _ => None,
+                });
+
+            let popup_owner = match mode {
+                OpenMode::Popup(..) => query_popup_owner(&connection, window_parent),
+                _ => 0,
+            };
+
+            let owner_window = match mode {
+                OpenMode::Transient(..) | OpenMode::Popup(..) => {
+                    query_top_level_window(&connection, window_parent)
+                }
+                _ => 0,
+            };
+
+            let owner_position = match owner_window {
+                0 => (0, 0),
+                _ => {
+                    let (x, y, _, _) = query_window_rect(&connection, owner_window);
+                    (x, y)
+                }
+            };
+
             // transient hint (its not really a "parent" in the traditional sense)
-            if let OpenMode::Transient(..) = mode {
-                XSetTransientForHint(connection.as_raw(), window_id, window_parent);
+            match (mode, group_leader) {
+                (OpenMode::Transient(..) | OpenMode::Popup(..), _) => {
+                    XSetTransientForHint(connection.as_raw(), window_id, window_parent);
+
+                    // the window manager does not close us with the parent, watch it ourselves.
+                    // popups watch the focus of their owner too, and the top-level window is
+                    // watched to hide and move along with it. these may all be the same window,
+                    // and selecting replaces the mask
+                    let mut selections = Vec::<(c_ulong, c_long)>::new();
+                    for (window, mask) in [
+                        (window_parent, StructureNotifyMask),
+                        (popup_owner, FocusChangeMask),
+                        (owner_window, StructureNotifyMask),
+                    ] {
+                        match selections
+                            .iter_mut()
+                            .find(|(selected, _)| *selected == window)
+                        {
+                            Some((_, selected_mask)) => *selected_mask |= mask,
+                            None if window != 0 => selections.push((window, mask)),
+                            None => {}
+                        }
+                    }
+
+                    for (window, mask) in selections {
+                        XSelectInput(connection.as_raw(), window, mask);
+                    }
+                }
+                (OpenMode::TopLevel, Some(leader)) => {
+                    XSetTransientForHint(connection.as_raw(), window_id, leader);
+                }
+                _ => {}
             }
 
-            // ask for close window messages
+            // ask for close window and focus messages
+            let mut protocols = [
+                connection.atom(c"WM_DELETE_WINDOW"),
+                connection.atom(c"WM_TAKE_FOCUS"),
+            ];
+
             XSetWMProtocols(
                 connection.as_raw(),
                 window_id,
-                &mut connection.atom(c"WM_DELETE_WINDOW"),
-                1,
+                protocols.as_mut_ptr(),
+                protocols.len() as _,
+            );
+
+            // ICCCM "locally active" input model: we accept focus from the window manager
+            // (input hint) and also set it ourselves on click, because some hosts never
+            // assign focus to their child windows
+            //
+            // group members also share the ICCCM window group of the leader, so the window
+            // manager can treat them as a single application
+            XSetWMHints(
+                connection.as_raw(),
+                window_id,
+                &mut XWMHints {
+                    flags: match options.group {
+                        Some(..) => InputHint | WindowGroupHint,
+                        None => InputHint,
+                    },
+                    input: 1,
+                    window_group: group_leader.unwrap_or(window_id),
+                    ..zeroed()
+                },
             );
 
+            // the host we run on, so the window manager can tell remote clients apart
+            let mut hostname = [0u8; 256];
+            if libc::gethostname(hostname.as_mut_ptr() as *mut _, hostname.len()) == 0 {
+                let len = hostname
+                    .iter()
+                    .position(|&c| c == 0)
+                    .unwrap_or(hostname.len());
+                connection.set_property8(
+                    window_id,
+                    XA_WM_CLIENT_MACHINE,
+                    XA_STRING,
+                    hostname.get(..len).unwrap_or_default(),
+                );
+            }
+
             // check if we have xinput2 available, and if so, select for events we want to
             // receive
-            let (xi2_info, xi2_axes) = match XI2Extension::new(&connection) {
+            let (xi2_info, xi2_axes, xi2_pens) = match XI2Extension::new(&connection) {
                 Some(info) => {
                     let mut mask = [0; 4];
                     XISetMask(&mut mask, XI_Enter);
@@ -262,40 +660,37 @@ impl WindowImpl {
                         1,
                     );
 
-                    let axes = info.list_axes(&connection);
-                    (Some(info), axes)
+                    let (axes, pens) = info.list_devices(&connection);
+                    (Some(info), axes, pens)
                 }
 
-                None => (None, Vec::new()),
+                None => (None, Vec::new(), Vec::new()),
             };
 
             // mark our window as drag and drop aware, so we can receive drag and drop
             // events from other applications
-            {
-                let data = [5u32];
-                XChangeProperty(
-                    connection.as_raw(),
-                    window_id,
-                    connection.atom(c"XdndAware"),
-                    connection.atom(c"ATOM"),
-                    32,
-                    PropModeReplace,
-                    data.as_ptr() as *mut _,
-                    data.len() as _,
-                );
-            }
+            connection.set_property32(
+                window_id,
+                connection.atom(c"XdndAware"),
+                connection.atom(c"ATOM"),
+                &[5],
+            );
 
             // create our opengl context if we have a config provided
-            let gl_context = options
-                .opengl
-                .map(|config| {
-                    let Some(visual) = visual_info else {
-                        return Err(OpenGlError::FormatUnsupported);
-                    };
+            #[cfg(feature = "opengl")]
+            let gl_context = create_opengl(&options.opengl, |config, last_resort| {
+                let Some(visual) = &visual_info else {
+                    return Err(OpenGlError::FormatUnsupported);
+                };
 
-                    GlContext::new(connection.clone(), window_id as _, config, visual)
-                })
-                .unwrap_or_else(|| Err(OpenGlError::NotRequested));
+                GlContext::new(
+                    connection.clone(),
+                    window_id as _,
+                    *config,
+                    visual,
+                    last_resort,
+                )
+            });
 
             // get a refresh interval for our frame updates, default to 60hz if all else
             // fails
@@ -304,6 +699,21 @@ impl WindowImpl {
             // get a dpi scale for our window, default to 96dpi (1.0)
             let dpi_scale = query_scale_dpi(&connection).unwrap_or(96.0) / 96.0;
 
+            // and keep both up to date when the monitors or the resources (`Xft.dpi`)
+            // change
+            XSelectInput(
+                connection.as_raw(),
+                XDefaultRootWindow(connection.as_raw()),
+                PropertyChangeMask,
+            );
+            let screen_change_event = select_screen_changes(&connection);
+            let crtc_rects = query_crtc_rects(&connection);
+
+            let xsettings_owner = query_xsettings_owner(&connection);
+            if xsettings_owner != 0 {
+                XSelectInput(connection.as_raw(), xsettings_owner, PropertyChangeMask);
+            }
+
             // if we get an error here, it means the window creation failed
             if let Err(e) = connection.last_error() {
                 // cleanup so we dont leave a dangling window and colormap
@@ -321,58 +731,145 @@ impl WindowImpl {
             let window = Box::new(Self {
                 window_id,
                 window_parent: Cell::new(window_parent),
+                window_entry: WindowEntry::new(window_id as usize),
                 window_colormap,
 
                 waker: Arc::new(WindowWakerImpl {
-                    display: RwLock::new(connection.as_raw()),
-                    window_id,
+                    event_fd,
+                    is_closed: AtomicBool::new(false),
+                    is_close_requested: AtomicBool::new(false),
+                    thread: Mutex::new(None),
                 }),
+                group: options.group.clone(),
 
                 is_closing: Cell::new(false),
                 is_destroyed: Cell::new(false),
-                refresh_interval,
-                dpi_scale,
+                is_owner_destroyed: Cell::new(false),
+                is_failed: Cell::new(false),
+                is_embedded: matches!(mode, OpenMode::Embedded(..)),
+                unmatched_mouse_release: options.unmatched_mouse_release,
+                raw_scroll: options.raw_scroll,
+                is_filling_parent: options.fill_parent && matches!(mode, OpenMode::Embedded(..)),
+                is_focus_pending: Cell::new(
+                    options.initial_keyboard_focus && matches!(mode, OpenMode::Embedded(..)),
+                ),
+                is_following_owner: options.follow_parent && owner_window != 0,
+                is_hidden_with_owner: Cell::new(false),
+                refresh_interval: Cell::new(refresh_interval),
+                next_frame: Cell::new(Instant::now()),
+                max_poll_timeout: options.max_poll_timeout,
+                timers: Timers::new(),
+                watchdog: Watchdog::new(options.watchdog),
+                frame_jitter: FrameJitter::new(),
+                keys_down: KeyState::new(),
+                redraw_pending: Cell::new(false),
+                dpi_scale: Cell::new(dpi_scale),
+                screen_change_event,
+                xsettings_owner,
+                popup_owner,
+                owner_window,
+                transient_for: match mode {
+                    OpenMode::Transient(..) | OpenMode::Popup(..) => window_parent,
+                    _ => 0,
+                },
 
                 last_modifiers: Cell::new(Modifiers::default()),
                 last_cursor_icon: Cell::new(MouseCursor::Default),
                 last_cursor_position: Cell::new(None),
+                last_buttons_held: Cell::new(0),
+                cursor_grab: Cell::new(CursorGrab::None),
+                cursor_lock: Cell::new(None),
+                last_event_token: Cell::new(EventToken::default()),
                 last_window_position: Cell::new(None),
+                last_monitor: Cell::new(None),
+                crtc_rects: RefCell::new(crtc_rects),
+                last_color_profile: RefCell::new(None),
                 last_window_size: Cell::new(None),
+                last_min_size: Cell::new(Size::MIN),
+                last_max_size: Cell::new(Size::MAX),
+                last_resize_hints: Cell::new(ResizeHints::default()),
                 last_window_visible: Cell::new(false),
+                last_owner_position: Cell::new(owner_position),
+                last_decorations: Cell::new(true),
                 last_window_focused: Cell::new(false),
                 last_dragdrop_state: Cell::new(false),
                 last_gesture_zoom: Cell::new(1.0),
+                last_damage: Cell::new(None),
+                is_damage_pending: Cell::new(true),
 
                 exchange_clipboard: RefCell::new(Exchange::Empty),
                 exchange_dragndrop: RefCell::new(Exchange::Empty),
+                incr_transfers: RefCell::new(Vec::new()),
+                drag_source: RefCell::new(None),
+                drag_dropped: RefCell::new(None),
+                is_drag_starting: Cell::new(false),
 
                 xi2_info,
                 xi2_axes: RefCell::new(xi2_axes),
+                xi2_pens: RefCell::new(xi2_pens),
+                last_pen: Cell::new(None),
 
                 cursor_cache: RefCell::new(HashMap::new()),
+                image_presenter: RefCell::new(None),
+                input_method: InputMethod::new(&connection, window_id),
 
                 handler: RefCell::new(None),
+                event_deferred: EventQueue::new(),
+                user_data: RefCell::new(None),
+                #[cfg(feature = "opengl")]
                 gl_context,
+                #[cfg(feature = "opengl")]
+                external_gl_context: options.external_opengl,
                 connection,
             });
 
-            // finally, run our event loop
+            // apply the initial cursor before the window is ever shown, so there is no
+            // flash of the default one
+            window.set_cursor_icon(options.cursor);
+
+            if let Some(group) = &window.group {
+                let top_level = match mode {
+                    OpenMode::Embedded(..) => {
+                        query_top_level_window(&window.connection, window_parent)
+                    }
+                    _ => window_id,
+                };
+
+                group.insert(
+                    RawWindowHandle::Xlib(rwh_06::XlibWindowHandle::new(top_level)),
+                    window.waker(),
+                );
+            }
+
+            // finally, hand the window to an event loop. windows opened from a handler join
+            // the loop that runs it, windows opened by a host get a loop on a new thread
+            let waker = window.waker.clone();
             match mode {
-                OpenMode::Blocking => {
-                    window.run_event_loop(options.factory)?;
-                    Ok(WindowWaker::default())
+                OpenMode::Embedded(..) | OpenMode::Transient(..) | OpenMode::Popup(..)
+                    if !event_loop::is_running() =>
+                {
+                    let thread = thread::spawn(|| {
+                        if window.start(options.factory).is_ok() {
+                            event_loop::add(window);
+                            let _ = event_loop::run();
+                        }
+                    });
+
+                    *waker.thread.lock().expect("lock poisoned") = Some(thread);
                 }
-                OpenMode::Embedded(..) | OpenMode::Transient(..) => {
-                    let waker = window.waker();
-                    thread::spawn(|| window.run_event_loop(options.factory).ok());
-                    Ok(waker)
+                _ => {
+                    window.start(options.factory)?;
+                    event_loop::add(window);
                 }
             }
+
+            Ok(WindowWaker(waker))
         }
     }
 
-    /// Poll the events until our window is closed.
-    fn run_event_loop(self: Box<Self>, factory: WindowFactory) -> Result<(), WindowError> {
+    /// Call the factory and start delivering events to the handler. The window
+    /// is then ready to be run by [`event_loop::run`].
+    fn start(&self, factory: WindowFactory) -> Result<(), WindowError> {
         unsafe {
             // SAFETY: we erase the lifetime of WindowImpl; it should be safe to do so
             // because:
@@ -382,84 +879,364 @@ impl WindowImpl {
             //    drop impl)
             //  - we promise to not move WindowImpl (and by extension the handler) to a
             //    different thread (as that would violate the handler's !Send requirement)
-            let handler = match (factory)(Window(&*(&*self as *const Self))) {
+            let handler = match (factory)(Window(&*(self as *const Self))) {
                 Ok(handler) => handler,
                 Err(error) => return Err(WindowError::Factory(error)),
             };
 
-            // start accepting events
+            // start accepting events, and deliver what the factory left pending
             self.handler.replace(Some(handler));
+            self.event(|_| {});
+            self.update_monitor();
 
-            // main loop
-            // - use a fixed refresh interval to call into [`WindowHandler::frame`] at a
-            //   consistent rate
-            // - if an event happens, we will handle it immediately and continue waiting for
-            //   the next event/until the frame timer runs out.
-            let mut next_frame = Instant::now();
-
-            // stop the loop if [`Window::close`] is called, or if the window is
-            // destroyed externally
-            while !self.is_closing.get() {
-                let curr_frame = Instant::now();
-                let wait_time = match next_frame.checked_duration_since(curr_frame) {
-                    Some(wait_time) => wait_time,
-                    None => {
-                        self.event(|e| e.frame());
-                        next_frame = (next_frame + self.refresh_interval).max(curr_frame); //avoid death spiral by capping next_frame to the current time if we are behind schedule
-                        next_frame.saturating_duration_since(curr_frame) // return the time until the next frame, or 0 if we are behind schedule
-                    }
-                };
+            // without XRandR the monitor is never known, use the first profile
+            if self.last_monitor.get().is_none() {
+                self.update_color_profile();
+            }
+
+            if self.is_filling_parent {
+                self.track_parent(None);
+            }
 
-                // flush any pending messages we could have
-                XFlush(self.connection.as_raw());
+            self.next_frame.set(Instant::now());
+            Ok(())
+        }
+    }
 
-                // check for errors if we have any
-                self.connection
-                    .async_last_error()
-                    .map_err(WindowError::Platform)?;
+    /// The first half of an event loop iteration: send the frame and the timers
+    /// that are due at `now`, and flush our requests.
+    ///
+    /// Returns when the loop has to wake up for this window again, and whether
+    /// that is only to let the handler yield (see
+    /// [`WindowBuilder::with_max_poll_timeout`]).
+    ///
+    /// The frame is sent at a fixed refresh interval, events that arrive in
+    /// between are handled right away by [`Self::poll_dispatch`].
+    pub fn poll_prepare(&self, now: Instant) -> Result<(Instant, bool), WindowError> {
+        unsafe {
+            let next_frame = self.next_frame.get();
+            let wait_time = match next_frame.checked_duration_since(now) {
+                Some(wait_time) => wait_time,
+                None => {
+                    self.frame_jitter.measure(now);
+                    self.event(|e| e.frame());
+                    let next_frame = (next_frame + self.refresh_interval.get()).max(now); //avoid death spiral by capping next_frame to the current time if we are behind schedule
+                    self.next_frame.set(next_frame);
+                    next_frame.saturating_duration_since(now) // return the time until the next frame, or 0 if we are behind schedule
+                }
+            };
 
-                // wait until we get at least 1 event, or until the next frame timer runs out
-                let num_events = wait_for_events(&self.connection, Some(wait_time))
-                    .map_err(WindowError::Platform)?;
+            // run the expired timers, and wake up for the next one if it comes first
+            while let Some(id) = self.timers.take_expired(now) {
+                self.deferred_lossy_event(move |_, e| e.timer(id));
+            }
 
-                // process events if we have any
-                for _ in 0..num_events {
-                    let mut event = XEvent { type_: 0 };
+            let wait_time = match self.timers.next_deadline() {
+                Some(deadline) => wait_time.min(deadline.saturating_duration_since(now)),
+                None => wait_time,
+            };
 
-                    if XNextEvent(self.connection.as_raw(), &mut event) == 0 {
-                        self.handle_event(event);
+            // a target that never finishes the drop is given up on
+            let drop_deadline = self.drag_dropped.borrow().as_ref().map(|(_, at)| *at);
+            let wait_time = match drop_deadline {
+                Some(deadline) if deadline <= now => {
+                    self.drag_dropped.take();
+                    self.deferred_event(|_, e| e.drag_finished(DropEffect::Reject));
+                    wait_time
+                }
+                Some(deadline) => wait_time.min(deadline.saturating_duration_since(now)),
+                None => wait_time,
+            };
 
-                        // if we get a DestroyNotify event, exit immediately
-                        if self.is_destroyed.get() {
-                            break;
-                        }
+            // cap the wait time so cooperative hosts get a chance to run
+            let (wait_time, is_capped) = match self.max_poll_timeout {
+                Some(max_wait_time) if max_wait_time < wait_time => (max_wait_time, true),
+                _ => (wait_time, false),
+            };
+
+            // flush any pending messages we could have
+            XFlush(self.connection.as_raw());
+
+            // check for errors if we have any
+            self.connection
+                .async_last_error()
+                .map_err(|error| self.fail(error))?;
+
+            Ok((now + wait_time, is_capped))
+        }
+    }
+
+    /// The second half of an event loop iteration, after waiting for events on
+    /// any of the windows: handle the events that arrived for this one.
+    /// `wakeup` is what [`Self::poll_prepare`] returned.
+    pub fn poll_dispatch(&self, wakeup: (Instant, bool)) {
+        unsafe {
+            let num_events = XPending(self.connection.as_raw());
+
+            // if we woke up because of the poll timeout, let the handler yield
+            let (deadline, is_capped) = wakeup;
+            if num_events == 0 && is_capped && Instant::now() >= deadline {
+                self.deferred_event(|_, e| e.idle());
+            }
+
+            // process events if we have any
+            for _ in 0..num_events {
+                let mut event = XEvent { type_: 0 };
+
+                if XNextEvent(self.connection.as_raw(), &mut event) == 0 {
+                    self.handle_event(event);
+
+                    // if we get a DestroyNotify event or the handler closed the window,
+                    // exit immediately. the rest of the queue is never looked at
+                    if self.is_destroyed.get() || self.is_closing.get() {
+                        break;
                     }
                 }
             }
 
-            Ok(())
+            // several wakeups since the last iteration are reported once
+            if !self.is_destroyed.get() && !self.is_closing.get() {
+                match self.waker.receive() {
+                    Some(true) => self.close(),
+                    Some(false) => {
+                        self.deferred_lossy_event(|_, e| e.wakeup());
+                    }
+                    None => {}
+                }
+            }
+
+            // a finished composition without a commit key press was cancelled
+            if let Some(input_method) = &self.input_method {
+                input_method.finish_batch();
+                self.handle_compositions();
+            }
+        }
+    }
+
+    /// The display connection of the window, waited on by the event loop.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// The eventfd [`WindowWaker`]s of the window write to, waited on by the
+    /// event loop next to the connection.
+    pub fn waker_fd(&self) -> c_int {
+        self.waker.event_fd.as_raw_fd()
+    }
+
+    /// Check if the window is done, and can be dropped by the event loop.
+    pub fn is_finished(&self) -> bool {
+        self.is_closing.get()
+    }
+
+    /// Mark the window as failed, so it leaves the event loop and the handler
+    /// is told why it went away.
+    pub fn fail(&self, error: String) -> WindowError {
+        self.is_failed.set(true);
+        self.is_closing.set(true);
+        WindowError::Platform(error)
+    }
+
+    /// Start following the size of the current parent window, see
+    /// [`WindowBuilder::with_fill_parent`]. Stops listening to the `previous`
+    /// parent, if any.
+    fn track_parent(&self, previous: Option<c_ulong>) {
+        unsafe {
+            // the parent may be the root window, whose other events we still want
+            if let Some(previous) = previous {
+                self.connection
+                    .remove_event_mask(previous, StructureNotifyMask);
+            }
+
+            // the selection is per client, so this does not interfere with the host
+            let parent = self.window_parent.get();
+            self.connection.add_event_mask(parent, StructureNotifyMask);
+
+            let mut attributes = zeroed::<XWindowAttributes>();
+            if XGetWindowAttributes(self.connection.as_raw(), parent, &mut attributes) != 0 {
+                self.set_size(Size {
+                    width: attributes.width.try_into().unwrap_or(0),
+                    height: attributes.height.try_into().unwrap_or(0),
+                });
+            }
         }
     }
 
     /// Handle a single [`XEvent`] from the main event loop
     #[allow(non_upper_case_globals)]
-    fn handle_event(&self, event: XEvent) {
+    fn handle_event(&self, mut event: XEvent) {
         unsafe {
+            match event.type_ {
+                ButtonPress | ButtonRelease => {
+                    self.handle_event_token(event.button.time, event.button.serial)
+                }
+                KeyPress | KeyRelease => self.handle_event_token(event.key.time, event.key.serial),
+                MotionNotify => self.handle_event_token(event.motion.time, event.motion.serial),
+                EnterNotify | LeaveNotify => {
+                    self.handle_event_token(event.crossing.time, event.crossing.serial)
+                }
+                _ => {}
+            }
+
+            // the input method gets the first look at every event, and reports the
+            // composition through its callbacks while doing so
+            if let Some(input_method) = &self.input_method {
+                let filtered = input_method.filter_event(&mut event);
+                self.handle_compositions();
+                if filtered {
+                    return;
+                }
+            }
+
+            // root window changes, only selected to keep the screen metrics current
+            if Some(event.type_) == self.screen_change_event {
+                XRRUpdateConfiguration(&mut event);
+                self.crtc_rects.replace(query_crtc_rects(&self.connection));
+                self.update_screen_metrics();
+                return;
+            }
+
+            // the requestor of a large selection value read a part and asks for the next
+            if event.type_ == PropertyNotify && event.property.state == PropertyDelete {
+                let mut transfers = self.incr_transfers.borrow_mut();
+                if let Some(index) = transfers.iter().position(|transfer| {
+                    transfer.requestor == event.property.window
+                        && transfer.property == event.property.atom
+                }) {
+                    if let Some(transfer) = transfers.get_mut(index)
+                        && !transfer.send_next(&self.connection)
+                    {
+                        transfers.swap_remove(index);
+                    }
+
+                    return;
+                }
+            }
+
+            if event.type_ == PropertyNotify
+                && event.property.atom == XA_RESOURCE_MANAGER
+                && event.property.window == XDefaultRootWindow(self.connection.as_raw())
+            {
+                self.update_screen_metrics();
+                return;
+            }
+
+            if event.type_ == PropertyNotify
+                && event.property.window == XDefaultRootWindow(self.connection.as_raw())
+                && is_color_profile_atom(&self.connection, event.property.atom)
+            {
+                self.update_color_profile();
+                return;
+            }
+
+            if event.type_ == PropertyNotify
+                && event.property.window == self.xsettings_owner
+                && self.xsettings_owner != 0
+            {
+                if event.property.atom == self.connection.atom(c"_XSETTINGS_SETTINGS") {
+                    self.deferred_event(|_, e| e.system_metrics_changed());
+                }
+
+                return;
+            }
+
+            // focus changes of the window a popup belongs to. focus moving into one of its
+            // children or to a grab does not count
+            if matches!(event.type_, FocusIn | FocusOut)
+                && event.focus_change.window == self.popup_owner
+                && self.popup_owner != 0
+            {
+                if event.type_ == FocusOut
+                    && !matches!(event.focus_change.mode, NotifyGrab | NotifyUngrab)
+                    && !matches!(event.focus_change.detail, NotifyInferior | NotifyPointer)
+                {
+                    self.dismiss_popup();
+                }
+
+                return;
+            }
+
+            // structure events of the parent window, only selected when filling it or when
+            // closing with it. transient windows and popups hide and move along with the
+            // top-level window of their parent
+            if matches!(
+                event.type_,
+                ConfigureNotify
+                    | MapNotify
+                    | UnmapNotify
+                    | ReparentNotify
+                    | DestroyNotify
+                    | GravityNotify
+                    | CirculateNotify
+            ) && event.any.window != self.window_id
+            {
+                if event.type_ == ConfigureNotify
+                    && self.is_filling_parent
+                    && event.configure.window == self.window_parent.get()
+                {
+                    self.set_size(Size {
+                        width: event.configure.width.try_into().unwrap_or(0),
+                        height: event.configure.height.try_into().unwrap_or(0),
+                    });
+                }
+
+                if event.any.window == self.owner_window && self.owner_window != 0 {
+                    match event.type_ {
+                        UnmapNotify if self.last_window_visible.get() => {
+                            self.is_hidden_with_owner.set(true);
+                            XUnmapWindow(self.connection.as_raw(), self.window_id);
+                        }
+                        MapNotify if self.is_hidden_with_owner.replace(false) => {
+                            self.set_visible(true);
+                        }
+                        ConfigureNotify => {
+                            self.follow_owner(event.configure.x, event.configure.y);
+                        }
+                        _ => {}
+                    }
+                }
+
+                // embedded windows are destroyed along with the parent by the server
+                if event.type_ == DestroyNotify
+                    && self.transient_for != 0
+                    && (event.destroy_window.window == self.transient_for
+                        || event.destroy_window.window == self.owner_window)
+                {
+                    self.is_owner_destroyed.set(true);
+                    self.close();
+                }
+
+                return;
+            }
+
             match event.type_ {
                 GenericEvent => {
                     let mut event = event.generic_event_cookie;
 
                     if let Some(xi2) = self.xi2_info.as_ref() {
                         xi2.query_event(&self.connection, &mut event, |event| {
+                            if (*event).evtype != XI_HierarchyChanged {
+                                self.handle_event_token((*event).time, (*event).serial);
+                            }
+
                             match (*event).evtype {
                                 XI_Motion => {
                                     let event = &*(event as *mut _ as *const XIDeviceEvent);
+                                    self.handle_event_pen(event.sourceid);
 
                                     let mask = std::slice::from_raw_parts(
                                         event.valuators.mask,
                                         event.valuators.mask_len as usize,
                                     );
 
+                                    if self.handle_event_drag_motion(
+                                        event.root_x as c_int,
+                                        event.root_y as c_int,
+                                        event.time,
+                                    ) {
+                                        return;
+                                    }
+
                                     if event.sourceid == event.deviceid {
                                         self.handle_event_modifiers(keymask_to_mods(
                                             event.mods.effective as _,
@@ -505,19 +1282,45 @@ impl WindowImpl {
                                         }
 
                                         if scroll_x != 0.0 || scroll_y != 0.0 {
-                                            self.event(|e| e.mouse_scroll(scroll_x, scroll_y));
+                                            self.deferred_lossy_event(move |_, e| {
+                                                e.mouse_scroll(scroll_x, scroll_y)
+                                            });
                                         }
                                     }
                                 }
 
                                 XI_Enter => {
+                                    let event = &*(event as *mut _ as *const XIEnterEvent);
+                                    self.handle_event_pen(event.sourceid);
+
                                     for device in self.xi2_axes.borrow_mut().iter_mut() {
                                         device.reset_position(&self.connection);
                                     }
+
+                                    if self.is_embedded {
+                                        self.update_monitor();
+                                    }
+                                }
+
+                                XI_RawMotion => {
+                                    let event = &*(event as *mut _ as *const XIRawEvent);
+                                    let is_pen = self
+                                        .xi2_pens
+                                        .borrow()
+                                        .iter()
+                                        .any(|pen| pen.source_id == event.sourceid);
+
+                                    // pens report absolute positions, they go through the warp
+                                    if !is_pen {
+                                        let (x, y) = raw_motion_delta(event);
+                                        self.handle_event_raw_motion(x, y);
+                                    }
                                 }
 
                                 XI_HierarchyChanged => {
-                                    self.xi2_axes.replace(xi2.list_axes(&self.connection));
+                                    let (axes, pens) = xi2.list_devices(&self.connection);
+                                    self.xi2_axes.replace(axes);
+                                    self.xi2_pens.replace(pens);
                                 }
 
                                 XI_GesturePinchBegin
@@ -533,20 +1336,25 @@ impl WindowImpl {
                                     let old_zoom = self.last_gesture_zoom.replace(new_zoom);
 
                                     if new_zoom != old_zoom {
-                                        self.event(|e| e.gesture_zoom(new_zoom / old_zoom));
+                                        self.deferred_lossy_event(move |_, e| {
+                                            e.gesture_zoom(new_zoom / old_zoom)
+                                        });
                                     }
 
                                     if event.delta_angle != 0.0 {
-                                        self.event(|e| e.gesture_rotate(event.delta_angle));
+                                        self.deferred_lossy_event(move |_, e| {
+                                            e.gesture_rotate(event.delta_angle)
+                                        });
                                     }
 
                                     if event.delta_x != 0.0 || event.delta_y != 0.0 {
-                                        self.event(|e| {
-                                            e.mouse_scroll(
-                                                event.delta_x * 0.05,
-                                                event.delta_y * 0.05,
-                                            )
-                                        });
+                                        let (x, y) = ScrollDelta::Pixels {
+                                            x: event.delta_x,
+                                            y: event.delta_y,
+                                        }
+                                        .resolve(self.raw_scroll);
+
+                                        self.deferred_lossy_event(move |_, e| e.mouse_scroll(x, y));
                                     }
                                 }
 
@@ -562,13 +1370,21 @@ impl WindowImpl {
                         && event.message_type == self.connection.atom(c"WM_PROTOCOLS") as _
                         && event.data.get_long(0) == self.connection.atom(c"WM_DELETE_WINDOW") as _
                     {
-                        self.event(|e| e.close_requested());
+                        self.deferred_event(|_, e| e.close_requested());
                     }
 
                     if event.format == 32
-                        && event.message_type == self.connection.atom(ATOM_WAKEUP) as _
+                        && event.message_type == self.connection.atom(c"WM_PROTOCOLS") as _
+                        && event.data.get_long(0) == self.connection.atom(c"WM_TAKE_FOCUS") as _
                     {
-                        self.event(|e| e.wakeup());
+                        // the second field is the timestamp of the event that triggered the focus
+                        // change, ICCCM requires us to use it instead of `CurrentTime`
+                        XSetInputFocus(
+                            self.connection.as_raw(),
+                            self.window_id,
+                            RevertToParent,
+                            event.data.get_long(1) as _,
+                        );
                     }
 
                     if event.format == 32
@@ -578,9 +1394,11 @@ impl WindowImpl {
                             return;
                         };
 
+                        // root coordinates are never negative, but can go past 32767 on a
+                        // large enough screen, so these are unsigned
                         let (x, y) = {
                             let packed = event.data.get_long(2);
-                            ((packed >> 16) as u16 as i16, packed as u16 as i16)
+                            ((packed >> 16) as u16, packed as u16)
                         };
 
                         let point = Point {
@@ -596,9 +1414,12 @@ impl WindowImpl {
                                 self.connection.atom(c"XdndSelection"),
                                 self.connection.atom(c"XdndSelection"),
                                 timestamp,
+                                &[ClipboardFormat::Files, ClipboardFormat::Text],
                             ) {
                                 Ok(exchange) => exchange,
-                                Err(SelectionError::Empty) => Exchange::Empty,
+                                Err(SelectionError::Empty | SelectionError::Refused) => {
+                                    Exchange::Empty
+                                }
                                 Err(SelectionError::Reentrant) => {
                                     self.exchange_dragndrop.borrow().clone()
                                 }
@@ -618,10 +1439,44 @@ impl WindowImpl {
                         );
                     }
 
+                    if event.format == 32
+                        && event.message_type == self.connection.atom(c"XdndStatus") as _
+                        && let Some(source) = self.drag_source.borrow_mut().as_mut()
+                    {
+                        source.status(&self.connection, &event);
+
+                        let cursor = match source.is_accepted() {
+                            true => MouseCursor::Copy,
+                            false => MouseCursor::NotAllowed,
+                        };
+
+                        XChangeActivePointerGrab(
+                            self.connection.as_raw(),
+                            DRAG_POINTER_MASK,
+                            self.cursor(cursor),
+                            CurrentTime,
+                        );
+                    }
+
+                    if event.format == 32
+                        && event.message_type == self.connection.atom(c"XdndFinished") as _
+                    {
+                        let effect = self
+                            .drag_dropped
+                            .borrow()
+                            .as_ref()
+                            .and_then(|(source, _)| source.finished(&self.connection, &event));
+
+                        if let Some(effect) = effect {
+                            self.drag_dropped.take();
+                            self.deferred_event(move |_, e| e.drag_finished(effect));
+                        }
+                    }
+
                     if event.format == 32
                         && event.message_type == self.connection.atom(c"XdndLeave") as _
                     {
-                        self.event(|e| e.drag_leave());
+                        self.deferred_event(|_, e| e.drag_leave());
                         self.last_dragdrop_state.set(false);
                     }
 
@@ -648,45 +1503,77 @@ impl WindowImpl {
 
                 ReparentNotify => {
                     let event = event.reparent;
-                    self.window_parent.set(event.parent);
-                }
-
-                MapNotify if !self.last_window_visible.replace(true) => {
-                    self.event(|e| e.visibility_changed(WindowVisibility::Normal));
-                }
+                    let previous = self.window_parent.replace(event.parent);
 
-                UnmapNotify if self.last_window_visible.replace(false) => {
-                    // TODO: add minimize check
-                    self.event(|e| e.visibility_changed(WindowVisibility::Hidden));
+                    if self.is_filling_parent && previous != event.parent {
+                        self.track_parent(Some(previous));
+                    }
+                }
+
+                MapNotify if !self.last_window_visible.replace(true) => {
+                    if self.is_focus_pending.replace(false) {
+                        XSetInputFocus(
+                            self.connection.as_raw(),
+                            self.window_id,
+                            RevertToParent,
+                            CurrentTime,
+                        );
+                    }
+
+                    self.deferred_event(|_, e| e.visibility_changed(WindowVisibility::Normal));
+                    self.update_monitor();
+                    self.initial_damage();
+                }
+
+                UnmapNotify if self.last_window_visible.replace(false) => {
+                    // TODO: add minimize check
+                    self.release_cursor_grab();
+                    self.deferred_event(|_, e| e.visibility_changed(WindowVisibility::Hidden));
+                    // contents are lost when unmapped, so damage everything on the next map
+                    self.is_damage_pending.set(true);
                 }
 
                 ConfigureNotify => {
                     let event = event.configure;
                     let size = Size {
-                        width: event.width as u32,
-                        height: event.height as u32,
+                        width: event.width.try_into().unwrap_or(0),
+                        height: event.height.try_into().unwrap_or(0),
                     };
 
                     if let Some(point) = window_position(&self.connection, self.window_id)
                         && self.last_window_position.replace(Some(point)) != Some(point)
                     {
-                        self.event(|e| e.position_changed(point));
+                        self.deferred_latest_event(move |_, e| e.position_changed(point));
                     }
 
                     if self.last_window_size.replace(Some(size)) != Some(size) {
-                        self.event(|e| e.size_changed(size));
+                        self.deferred_latest_event(move |_, e| e.size_changed(size));
                     }
+
+                    self.update_monitor();
+                    self.initial_damage();
                 }
 
                 ButtonPress | ButtonRelease => {
                     let event = event.button;
 
-                    if event.type_ == ButtonPress {
+                    // no button is held during a drag, so any release drops the data
+                    if self.drag_source.borrow().is_some() {
+                        if event.type_ == ButtonRelease {
+                            self.finish_drag(event.time, true);
+                        }
+
+                        return;
+                    }
+
+                    // take the keyboard focus on click, using the event timestamp so we do not
+                    // steal focus back from a later focus change
+                    if event.type_ == ButtonPress && !self.last_window_focused.get() {
                         XSetInputFocus(
                             self.connection.as_raw(),
                             self.window_id,
                             RevertToParent,
-                            CurrentTime,
+                            event.time,
                         );
                     }
 
@@ -695,16 +1582,45 @@ impl WindowImpl {
 
                     match event.button {
                         1 | 2 | 3 | 8 | 9 => {
-                            let button = match event.button {
-                                1 => MouseButton::Left,
-                                2 => MouseButton::Middle,
-                                3 => MouseButton::Right,
-                                8 => MouseButton::Back,
-                                9 => MouseButton::Forward,
-                                _ => return,
+                            let Some(&(_, button)) =
+                                MOUSE_BUTTONS.iter().find(|(bit, _)| *bit == event.button)
+                            else {
+                                return;
                             };
 
-                            self.event(|e| e.mouse_press(button, event.type_ == ButtonPress));
+                            let mask = 1 << event.button;
+                            let pressed = event.type_ == ButtonPress;
+                            let held = self.last_buttons_held.get();
+                            match pressed {
+                                true => self.last_buttons_held.set(held | mask),
+                                false => self.last_buttons_held.set(held & !mask),
+                            }
+
+                            // while locked, presses happen at the virtual position
+                            let point = match self.cursor_lock.get() {
+                                Some((_, position)) => position,
+                                None => Point {
+                                    x: event.x as f64,
+                                    y: event.y as f64,
+                                },
+                            };
+
+                            // the implicit grab sends releases to the window that got the press,
+                            // so this only happens if the grab was not ours to begin with
+                            if pressed || held & mask != 0 || self.unmatched_mouse_release {
+                                self.deferred_event(move |_, e| {
+                                    e.mouse_press(button, pressed, point)
+                                });
+                            }
+
+                            // the drag ended outside of the window, the leave event we ignored
+                            // while the buttons were held will not come again
+                            if event.type_ == ButtonRelease
+                                && self.last_buttons_held.get() == 0
+                                && !self.is_inside_window(event.x, event.y)
+                            {
+                                self.handle_event_leave();
+                            }
                         }
 
                         4..=7 if event.type_ == ButtonPress && self.xi2_info.is_none() => {
@@ -716,7 +1632,7 @@ impl WindowImpl {
                                 _ => return,
                             };
 
-                            self.event(|e| e.mouse_scroll(x, y));
+                            self.deferred_lossy_event(move |_, e| e.mouse_scroll(x, y));
                         }
 
                         _ => {}
@@ -726,6 +1642,24 @@ impl WindowImpl {
                 KeyPress | KeyRelease => {
                     let event = event.key;
 
+                    // text committed by the input method comes as a key press without a key
+                    if let Some(input_method) = &self.input_method
+                        && event.type_ == KeyPress
+                        && event.keycode == 0
+                    {
+                        input_method.commit(&event);
+                        self.handle_compositions();
+                        return;
+                    }
+
+                    if event.type_ == KeyPress
+                        && keycode_to_key(event.keycode) == Some(Key::Escape)
+                        && self.drag_source.borrow().is_some()
+                    {
+                        self.finish_drag(event.time, false);
+                        return;
+                    }
+
                     self.handle_event_modifiers(keymask_to_mods(event.state));
 
                     // ignore auto-repeat release events, autorepeats will be reported as repeated
@@ -734,33 +1668,51 @@ impl WindowImpl {
                         return;
                     }
 
-                    if let Some(key) = keycode_to_key(event.keycode) {
-                        let capture = self
-                            .event(|e| e.key_press(key, event.type_ == KeyPress))
-                            .unwrap_or(false);
-
-                        if !capture {
-                            XSendEvent(
-                                self.connection.as_raw(),
-                                self.window_parent.get(),
-                                1,
-                                match event.type_ {
-                                    KeyPress => KeyPressMask,
-                                    _ => KeyReleaseMask,
-                                },
-                                &mut XEvent {
-                                    key: XKeyEvent {
-                                        window: self.window_parent.get(),
-                                        ..event
-                                    },
-                                },
-                            );
+                    // keys we do not know about are never captured, and are forwarded to the host
+                    // just like the ones the handler chose not to capture
+                    let capture = match keycode_to_key(event.keycode) {
+                        Some(key) => {
+                            let pressed = event.type_ == KeyPress;
+                            let is_repeat = pressed && self.keys_down.is_down(key);
+                            self.keys_down.set(key, pressed);
+
+                            let text = if pressed {
+                                key_event_to_char(&event)
+                            } else {
+                                None
+                            };
+
+                            self.event(|e| e.key_press(key, pressed, is_repeat, text))
+                                .unwrap_or(false)
                         }
+                        None => false,
+                    };
+
+                    if !capture && self.is_embedded {
+                        XSendEvent(
+                            self.connection.as_raw(),
+                            self.window_parent.get(),
+                            1,
+                            match event.type_ {
+                                KeyPress => KeyPressMask,
+                                _ => KeyReleaseMask,
+                            },
+                            &mut XEvent {
+                                key: XKeyEvent {
+                                    window: self.window_parent.get(),
+                                    ..event
+                                },
+                            },
+                        );
                     }
                 }
 
                 MotionNotify => {
                     let event = event.motion;
+                    if self.handle_event_drag_motion(event.x_root, event.y_root, event.time) {
+                        return;
+                    }
+
                     self.handle_event_modifiers(keymask_to_mods(event.state));
                     self.handle_event_motion(event.x as f64, event.y as f64, false);
                 }
@@ -769,15 +1721,32 @@ impl WindowImpl {
                     let event = event.crossing;
                     self.handle_event_modifiers(keymask_to_mods(event.state));
 
-                    const ANY_BUTTON: u32 =
-                        Button1Mask | Button2Mask | Button3Mask | Button4Mask | Button5Mask;
+                    let held = self.last_buttons_held.get();
+                    if held != 0 {
+                        // while a button is held we keep the implicit grab, so a leave event
+                        // either means the cursor was dragged outside (we keep tracking it
+                        // until release), or someone else grabbed the pointer and we will not
+                        // see the release, so we release the buttons ourselves
+                        if event.mode != NotifyGrab {
+                            return;
+                        }
 
-                    let grabbed = (event.state & ANY_BUTTON) != 0;
-                    if grabbed || self.last_cursor_position.replace(None).is_none() {
-                        return;
+                        let point = Point {
+                            x: event.x as f64,
+                            y: event.y as f64,
+                        };
+
+                        self.last_buttons_held.set(0);
+                        for (bit, button) in MOUSE_BUTTONS {
+                            if held & (1 << bit) != 0 {
+                                self.deferred_event(move |_, e| {
+                                    e.mouse_press(button, false, point)
+                                });
+                            }
+                        }
                     }
 
-                    self.event(|e| e.mouse_leave());
+                    self.handle_event_leave();
                 }
 
                 FocusIn | FocusOut => {
@@ -788,107 +1757,116 @@ impl WindowImpl {
                         return;
                     }
 
+                    if let Some(input_method) = &self.input_method {
+                        input_method.set_focus(focus);
+                        self.handle_compositions();
+                    }
+
                     if self.last_window_focused.replace(focus) != focus {
-                        self.event(|e| e.focus_changed(focus));
+                        // keys released while unfocused are never reported, start over
+                        match focus {
+                            true => self.keys_down.reset(query_keys_down(&self.connection)),
+                            false => {
+                                self.keys_down.reset([]);
+                                self.release_cursor_grab();
+                            }
+                        }
+
+                        self.deferred_event(move |_, e| e.focus_changed(focus));
                     }
                 }
 
                 Expose => {
                     let event = event.expose;
-                    self.event(|e| {
-                        e.damage(Rect::from_xywh(
-                            event.x,
-                            event.y,
-                            event.width.try_into().unwrap_or(0),
-                            event.height.try_into().unwrap_or(0),
-                        ))
-                    });
+                    let rect = Rect::from_xywh(
+                        event.x,
+                        event.y,
+                        event.width.try_into().unwrap_or(0),
+                        event.height.try_into().unwrap_or(0),
+                    );
+
+                    let damage = match self.last_damage.take() {
+                        Some(damage) => damage.union(&rect),
+                        None => rect,
+                    };
+
+                    // `count` is the number of Expose events that follow in this series,
+                    // coalesce them into a single damage event
+                    if event.count == 0 {
+                        self.is_damage_pending.set(false);
+                        self.deferred_event(move |_, e| e.damage(damage));
+                    } else {
+                        self.last_damage.set(Some(damage));
+                    }
                 }
 
                 SelectionRequest => {
-                    let exchange = &*self.exchange_clipboard.borrow();
                     let event = event.selection_request;
-
-                    if event.selection != self.connection.atom(c"CLIPBOARD") {
+                    let exchange = if event.selection == self.connection.atom(c"CLIPBOARD") {
+                        self.exchange_clipboard.borrow()
+                    } else if event.selection == self.connection.atom(c"XdndSelection") {
+                        self.exchange_dragndrop.borrow()
+                    } else {
                         return;
-                    }
+                    };
 
+                    let exchange = &*exchange;
                     let a_targets = self.connection.atom(c"TARGETS");
-                    let a_utf8_string = self.connection.atom(c"UTF8_STRING");
-                    let a_text_plain = self.connection.atom(c"text/plain");
-                    let a_text_uri_list = self.connection.atom(c"text/uri-list");
-
-                    if event.property != 0 {
-                        if event.target == a_targets {
-                            let atom = match exchange {
-                                Exchange::Files(_) => a_text_uri_list,
-                                Exchange::Empty | Exchange::Text(_) => a_utf8_string,
-                            };
 
-                            XChangeProperty(
-                                self.connection.as_raw(),
-                                event.requestor,
-                                event.property,
-                                XA_ATOM,
-                                32,
-                                PropModeReplace,
-                                &atom as *const _ as *const u8,
-                                1,
-                            );
-                        } else if (event.target == a_utf8_string
-                            || event.target == a_text_plain
-                            || event.target == XA_STRING)
-                            && let Exchange::Text(text) = exchange
-                        {
-                            XChangeProperty(
-                                self.connection.as_raw(),
+                    // a reply without a property refuses the target
+                    let mut property = event.property;
+                    if property != 0 && event.target == a_targets {
+                        let mut targets = vec![a_targets];
+                        if let Some(format) = exchange.format() {
+                            targets.extend(selection_targets(&self.connection, &format));
+                        }
+
+                        self.connection.set_property32(
+                            event.requestor,
+                            property,
+                            XA_ATOM,
+                            &targets,
+                        );
+                    } else if property != 0
+                        && let Some(data) =
+                            encode_selection(&self.connection, exchange, event.target)
+                    {
+                        if data.len() <= self.connection.max_property_size() {
+                            self.connection.set_property8(
                                 event.requestor,
-                                event.property,
+                                property,
                                 event.target,
-                                8,
-                                PropModeReplace,
-                                text.as_ptr(),
-                                text.len() as i32,
+                                &data,
                             );
-                        } else if event.target == a_text_uri_list
-                            && let Exchange::Files(files) = exchange
-                        {
-                            let list = encode_uri_list(files);
-                            XChangeProperty(
-                                self.connection.as_raw(),
+                        } else {
+                            let mut transfers = self.incr_transfers.borrow_mut();
+                            // a new request for the same property replaces the old one
+                            if let Some(index) = transfers.iter().position(|transfer| {
+                                (transfer.requestor, transfer.property)
+                                    == (event.requestor, property)
+                            }) {
+                                transfers.swap_remove(index).cancel(&self.connection);
+                            }
+
+                            transfers.push(IncrTransfer::start(
+                                &self.connection,
                                 event.requestor,
-                                event.property,
+                                property,
                                 event.target,
-                                8,
-                                PropModeReplace,
-                                list.as_bytes().as_ptr(),
-                                list.len() as i32,
-                            );
+                                data,
+                            ));
                         }
+                    } else {
+                        property = 0;
                     }
 
-                    XSendEvent(
-                        self.connection.as_raw(),
+                    self.connection.send_selection_notify(
                         event.requestor,
-                        0,
-                        NoEventMask,
-                        &mut XEvent {
-                            selection: x11::xlib::XSelectionEvent {
-                                type_: SelectionNotify,
-                                serial: 0,
-                                send_event: 1,
-                                display: event.display,
-                                requestor: event.requestor,
-                                selection: event.selection,
-                                target: event.target,
-                                property: event.property,
-                                time: event.time,
-                            },
-                        },
+                        event.selection,
+                        event.target,
+                        property,
+                        event.time,
                     );
-
-                    // just in case
-                    XFlush(self.connection.as_raw());
                 }
 
                 _ => {}
@@ -896,6 +1874,110 @@ impl WindowImpl {
         }
     }
 
+    /// Follows the cursor with the drag started with
+    /// [`PlatformWindow::start_drag`], if any. Returns `true` if the motion
+    /// belongs to the drag and is not reported to the handler.
+    fn handle_event_drag_motion(&self, x: c_int, y: c_int, time: Time) -> bool {
+        let mut source = self.drag_source.borrow_mut();
+        let Some(source) = source.as_mut() else {
+            return false;
+        };
+
+        source.motion(&self.connection, x, y, time);
+        true
+    }
+
+    /// Lets go of the cursor held with [`PlatformWindow::set_cursor_grab`],
+    /// leaving it where it was locked.
+    fn release_cursor_grab(&self) {
+        if self.cursor_grab.replace(CursorGrab::None) == CursorGrab::None {
+            return;
+        }
+
+        self.unlock_cursor();
+        unsafe {
+            XUngrabPointer(self.connection.as_raw(), CurrentTime);
+        }
+    }
+
+    /// Stops reporting virtual positions, the cursor is back at the anchor.
+    fn unlock_cursor(&self) {
+        if let Some((anchor, _)) = self.cursor_lock.take() {
+            self.last_cursor_position.set(Some(anchor));
+            if let Some(xi2) = &self.xi2_info {
+                xi2.select_raw_motion(&self.connection, false);
+            }
+        }
+    }
+
+    /// Ends the drag started with [`PlatformWindow::start_drag`], dropping the
+    /// data where the cursor is if `drop` is set, and reports the result with
+    /// [`WindowHandler::drag_finished`].
+    fn finish_drag(&self, time: Time, drop: bool) {
+        let Some(source) = self.drag_source.take() else {
+            return;
+        };
+
+        let effect = source.finish(&self.connection, time, drop);
+        unsafe {
+            XUngrabPointer(self.connection.as_raw(), time);
+            XUngrabKeyboard(self.connection.as_raw(), time);
+        }
+
+        match effect {
+            Some(effect) => {
+                self.deferred_event(move |_, e| e.drag_finished(effect));
+            }
+            // the target reads the data before it reports what it did with it
+            None => {
+                let deadline = Instant::now() + XDND_FINISH_TIMEOUT;
+                self.drag_dropped.replace(Some((source, deadline)));
+            }
+        }
+    }
+
+    /// Emits [`WindowHandler::pen_proximity`] events if the pointer is now
+    /// driven by a different pen (or no pen at all) than before.
+    ///
+    /// XInput2 has no proximity events, so a pen is considered in proximity
+    /// while it is moving the pointer over our window.
+    fn handle_event_pen(&self, source_id: c_int) {
+        let pen = self
+            .xi2_pens
+            .borrow()
+            .iter()
+            .find(|pen| pen.source_id == source_id)
+            .copied();
+
+        let last_pen = self.last_pen.replace(pen);
+        if last_pen == pen {
+            return;
+        }
+
+        if let Some(last_pen) = last_pen {
+            self.deferred_event(move |_, e| e.pen_proximity(false, last_pen.inverted));
+        }
+
+        if let Some(pen) = pen {
+            self.deferred_event(move |_, e| e.pen_proximity(true, pen.inverted));
+        }
+    }
+
+    /// Emits a full-window [`WindowHandler::damage`] event if the window is
+    /// mapped, its size is known and it hasn't been damaged since it was
+    /// mapped.
+    fn initial_damage(&self) {
+        if !self.last_window_visible.get() {
+            return;
+        }
+
+        if let Some(size) = self.last_window_size.get()
+            && self.is_damage_pending.replace(false)
+        {
+            self.deferred_event(move |_, e| e.damage(Rect::from_size(size)));
+        }
+    }
+
     /// Emits a [`WindowHandler::mouse_move`] event if the cursor position has
     /// changed.
     fn handle_event_motion(&self, x: f64, y: f64, is_precise: bool) {
@@ -912,41 +1994,408 @@ impl WindowImpl {
             }
         }
 
-        let point = Point { x, y };
+        let mut point = Point { x, y };
+        if let Some((anchor, position)) = self.cursor_lock.get() {
+            // the motion of our own warp back to the anchor
+            if point == anchor {
+                return;
+            }
+
+            self.set_cursor_position(anchor);
+
+            // raw motion moves the virtual position, it keeps coming when the pointer is
+            // stuck at the edge of the screen between two warps
+            if self.xi2_info.is_some() && self.last_pen.get().is_none() {
+                return;
+            }
+
+            point = position + (point - anchor);
+            self.cursor_lock.set(Some((anchor, point)));
+        }
+
         if self.last_cursor_position.replace(Some(point)) != Some(point) {
-            self.event(|e| e.mouse_move(point)); // TODO: absolute?
+            self.deferred_latest_event(move |_, e| e.mouse_move(point)); // TODO: absolute?
         }
     }
 
+    /// Moves the virtual position of a locked cursor by the motion of the mouse,
+    /// see [`XI2Extension::select_raw_motion`].
+    fn handle_event_raw_motion(&self, x: f64, y: f64) {
+        let Some((anchor, position)) = self.cursor_lock.get() else {
+            return;
+        };
+
+        if x == 0.0 && y == 0.0 {
+            return;
+        }
+
+        let point = position + Point { x, y };
+        self.cursor_lock.set(Some((anchor, point)));
+        self.last_cursor_position.set(Some(point));
+        self.deferred_latest_event(move |_, e| e.mouse_move(point));
+    }
+
+    /// Re-read the refresh rate and the DPI scale after the monitor layout or
+    /// the X resources changed, emitting [`WindowHandler::scale_changed`] if the
+    /// scale is different now.
+    fn update_screen_metrics(&self) {
+        let refresh_rate = query_refresh_rate(&self.connection).unwrap_or(60.0);
+        self.refresh_interval
+            .set(Duration::from_secs_f64(1.0 / refresh_rate));
+
+        let scale = query_scale_dpi(&self.connection).unwrap_or(96.0) / 96.0;
+        if self.dpi_scale.replace(scale) != scale {
+            self.deferred_event(move |_, e| e.scale_changed(scale));
+        }
+
+        self.update_monitor();
+    }
+
+    /// Check which XRandR CRTC shows the center of the window, emitting
+    /// [`WindowHandler::monitor_changed`] if it is a different one than last
+    /// time. Nothing is reported without XRandR, or while the window is off
+    /// screen.
+    fn update_monitor(&self) {
+        let Some((x, y)) = self.window_center() else {
+            return;
+        };
+
+        let Some(crtc) = crtc_at(&self.crtc_rects.borrow(), x, y) else {
+            return;
+        };
+
+        if self.last_monitor.replace(Some(crtc)) != Some(crtc) {
+            self.deferred_event(move |_, e| e.monitor_changed(MonitorId::from_raw(crtc as _)));
+            self.update_color_profile();
+        }
+    }
+
+    /// The center of the window in root window coordinates, `None` if the
+    /// window is gone. Embedded windows move with their host, so the position
+    /// is translated again instead of using [`Self::last_window_position`].
+    fn window_center(&self) -> Option<(c_int, c_int)> {
+        let (x, y) = window_position(&self.connection, self.window_id)?.to_pixels();
+        let size = self.last_window_size.get().unwrap_or_default();
+        let (width, height) = (
+            c_int::try_from(size.width).unwrap_or(c_int::MAX),
+            c_int::try_from(size.height).unwrap_or(c_int::MAX),
+        );
+
+        Some((x.saturating_add(width / 2), y.saturating_add(height / 2)))
+    }
+
+    /// Read the ICC profile of the monitor showing the center of the window,
+    /// emitting [`WindowHandler::color_profile_changed`] if it differs from
+    /// the last one.
+    fn update_color_profile(&self) {
+        let (x, y) = self.window_center().unwrap_or_default();
+        let profile = query_color_profile(&self.connection, x, y);
+
+        if *self.last_color_profile.borrow() != profile {
+            self.last_color_profile.replace(profile);
+            self.deferred_event(|_, e| e.color_profile_changed());
+        }
+    }
+
+    /// Send the minimum/maximum size and the resize hints to the window
+    /// manager in a single `WM_NORMAL_HINTS` update. Axes that can not be
+    /// resized are locked to `size`, if known, which is also published as the
+    /// preferred size.
+    ///
+    /// Embedded windows get the same hints, some hosts read them to size the
+    /// container of the plugin window.
+    fn update_size_hints(&self, size: Option<Size>) {
+        fn clamp(value: u32) -> c_int {
+            value.try_into().unwrap_or(c_int::MAX)
+        }
+
+        let resize_hints = self.last_resize_hints.get();
+        let (mut min, mut max) = (self.last_min_size.get(), self.last_max_size.get());
+
+        if let Some(size) = size {
+            if !resize_hints.can_resize_horizontally {
+                (min.width, max.width) = (size.width, size.width);
+            }
+
+            if !resize_hints.can_resize_vertically {
+                (min.height, max.height) = (size.height, size.height);
+            }
+        }
+
+        unsafe {
+            let mut hints = XSizeHints {
+                flags: PMinSize | PMaxSize,
+                min_width: clamp(min.width),
+                min_height: clamp(min.height),
+                max_width: clamp(max.width),
+                max_height: clamp(max.height),
+                ..zeroed()
+            };
+
+            if let Some(size) = size {
+                hints.flags |= PSize;
+                hints.width = clamp(size.width);
+                hints.height = clamp(size.height);
+            }
+
+            if let Some(step) = resize_hints.step {
+                // increments are relative to the base size, which would default to the
+                // minimum size. it is set to zero, so sizes are whole multiples of the step
+                // like in `ResizeHints::constrain`
+                hints.flags |= PResizeInc | PBaseSize;
+                hints.base_width = 0;
+                hints.base_height = 0;
+                hints.width_inc = clamp(step.width.max(1));
+                hints.height_inc = clamp(step.height.max(1));
+            }
+
+            if let Some((width, height)) = resize_hints.aspect_ratio {
+                hints.flags |= PAspect;
+                hints.min_aspect = AspectRatio {
+                    x: clamp(width),
+                    y: clamp(height),
+                };
+                hints.max_aspect = hints.min_aspect;
+            }
+
+            XSetWMNormalHints(self.connection.as_raw(), self.window_id, &mut hints);
+        }
+    }
+
+    /// Forward the composition changes reported by the input method to the
+    /// handler.
+    fn handle_compositions(&self) {
+        let Some(input_method) = &self.input_method else {
+            return;
+        };
+
+        for change in input_method.take_changes() {
+            match change {
+                Composition::Start => self.deferred_event(|_, e| e.composition_start()),
+                Composition::Update(text, range) => {
+                    self.deferred_event(move |_, e| e.composition_update(&text, range))
+                }
+                Composition::End(text) => {
+                    self.deferred_event(move |_, e| e.composition_end(text.as_deref()))
+                }
+            }
+        }
+    }
+
+    /// Move by the same distance as [`Self::owner_window`] did, if following
+    /// it. See [`WindowBuilder::with_follow_parent`].
+    fn follow_owner(&self, x: c_int, y: c_int) {
+        let (last_x, last_y) = self.last_owner_position.replace((x, y));
+        if !self.is_following_owner || (x, y) == (last_x, last_y) {
+            return;
+        }
+
+        if let Some(point) = self.last_window_position.get() {
+            self.set_position(Point {
+                x: point.x + f64::from(x.saturating_sub(last_x)),
+                y: point.y + f64::from(y.saturating_sub(last_y)),
+            });
+        }
+    }
+
+    /// Tell the handler that the popup lost the focus and close it, see
+    /// [`WindowHandler::popup_dismissed`].
+    fn dismiss_popup(&self) {
+        self.deferred_event(|_, e| e.popup_dismissed());
+        self.close();
+    }
+
+    /// Remembers the time and serial of an input event, see
+    /// [`PlatformWindow::last_event_token`].
+    #[allow(clippy::unnecessary_cast)] // `c_ulong` is 32 bits on some targets
+    fn handle_event_token(&self, time: Time, serial: c_ulong) {
+        self.last_event_token.set(EventToken {
+            time: time as u64,
+            serial: serial as u64,
+        });
+    }
+
+    /// Emits a [`WindowHandler::mouse_leave`] event (and ends the pen
+    /// proximity) if the cursor was inside the window.
+    fn handle_event_leave(&self) {
+        if self.last_cursor_position.replace(None).is_none() {
+            return;
+        }
+
+        if let Some(pen) = self.last_pen.take() {
+            self.deferred_event(move |_, e| e.pen_proximity(false, pen.inverted));
+        }
+
+        self.deferred_event(|_, e| e.mouse_leave());
+    }
+
+    /// Is the given point (relative to the window) inside the window bounds?
+    fn is_inside_window(&self, x: c_int, y: c_int) -> bool {
+        let size = self.last_window_size.get().unwrap_or_default();
+        x >= 0 && y >= 0 && (x as u32) < size.width && (y as u32) < size.height
+    }
+
     /// Emits a [`WindowHandler::key_modifiers`] event if the modifiers have
     /// changed.
     fn handle_event_modifiers(&self, modifiers: Modifiers) {
         if self.last_modifiers.replace(modifiers) != modifiers {
-            self.event(|e| e.key_modifiers(modifiers));
+            self.deferred_event(move |_, e| e.key_modifiers(modifiers));
         }
     }
 
-    /// Access the [`WindowHandler`] if available.
+    /// The X11 cursor for the given icon, loaded on first use. Falls back to
+    /// the default cursor if the theme does not have it.
+    fn cursor(&self, cursor: MouseCursor) -> c_ulong {
+        self.cursor_cache
+            .borrow_mut()
+            .entry(cursor)
+            .or_insert_with(|| {
+                X11Cursor::load(self.connection.clone(), cursor).unwrap_or_else(|| {
+                    X11Cursor::load(self.connection.clone(), MouseCursor::Default)
+                        .unwrap_or_else(|| X11Cursor::empty(self.connection.clone()))
+                })
+            })
+            .as_raw()
+    }
+
+    /// Access the [`WindowHandler`] if available, then run the events deferred
+    /// while it was running.
+    ///
+    /// Returns `None` without running the closure if the handler is not set or
+    /// already running (see the reentrancy notes on [`WindowHandler`]), like
+    /// when a call into the window from the handler produces an event. To
+    /// deliver an event later instead, use [`Self::deferred_event`].
+    ///
+    /// Once the window is closing nothing is delivered anymore, the handler
+    /// only gets [`WindowHandler::destroyed`] before the window goes away.
     fn event<R>(&self, f: impl FnOnce(&mut dyn WindowHandler) -> R) -> Option<R> {
-        (*self.handler.borrow_mut())
-            .as_mut()
-            .map(|handler| f(handler.as_mut()))
+        if self.is_closing.get() {
+            return None;
+        }
+
+        let Ok(mut handler) = self.handler.try_borrow_mut() else {
+            report_reentrancy(false);
+            return None;
+        };
+        let handler = handler.as_mut()?;
+        let result = self.watchdog.measure(|| f(handler.as_mut()));
+
+        // the handler may close the window at any point, stop right there
+        while !self.is_closing.get() {
+            // event_deferred must NOT be borrowed while calling the handler, so we have
+            // to reborrow it every time
+            let Some(event) = self.event_deferred.pop() else {
+                break;
+            };
+
+            self.watchdog.measure(|| event(self, handler.as_mut()));
+        }
+
+        if self.is_closing.get() {
+            self.event_deferred.clear();
+            return Some(result);
+        }
+
+        // the buttons belong to the drag now, and their releases go to it
+        if self.is_drag_starting.take() {
+            let point = self.last_cursor_position.get().unwrap_or_default();
+            let held = self.last_buttons_held.replace(0);
+            for (bit, button) in MOUSE_BUTTONS {
+                if held & (1 << bit) != 0 {
+                    self.watchdog
+                        .measure(|| handler.mouse_press(button, false, point));
+                }
+            }
+        }
+
+        if self.redraw_pending.take() {
+            self.watchdog.measure(|| handler.frame());
+        }
+
+        if let Some(stall) = self.watchdog.take_stall() {
+            handler.stalled(stall);
+        }
+
+        Some(result)
+    }
+
+    /// Run a closure with exclusive access to the window's event handler.
+    ///
+    /// Unlike [`Self::event`], the closure is not skipped if the handler is
+    /// running. Instead, it is deferred and run in order once the handler
+    /// returns. For that reason it cannot return a value, and the closure
+    /// must be `'static`.
+    fn deferred_event(&self, task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static) {
+        self.queue_event(QueueKind::Critical, task);
+    }
+
+    /// Like [`Self::deferred_event`], for events that only report the latest
+    /// state of something. If deferred, it replaces the same event right
+    /// before it in the queue, see [`EventQueue::push`].
+    fn deferred_latest_event<F>(&self, task: F)
+    where
+        F: FnOnce(&Self, &mut dyn WindowHandler) + 'static,
+    {
+        self.queue_event(QueueKind::Latest(TypeId::of::<F>()), task);
+    }
+
+    /// Like [`Self::deferred_event`], for events that can be lost without
+    /// the handler missing a change of state, like scrolls and timer ticks. If
+    /// deferred, it is dropped first when the queue is full, see
+    /// [`EventQueue`].
+    fn deferred_lossy_event(&self, task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static) {
+        self.queue_event(QueueKind::Lossy, task);
+    }
+
+    fn queue_event(
+        &self,
+        kind: QueueKind,
+        task: impl FnOnce(&Self, &mut dyn WindowHandler) + 'static,
+    ) {
+        match self.handler.try_borrow_mut().map(|x| x.is_some()) {
+            Ok(true) => {
+                self.event(|handler| task(self, handler));
+            }
+            Ok(false) => {
+                // not initialized yet, delivered once the handler is set
+                self.event_deferred.push(kind, Box::new(task));
+            }
+            Err(_) => {
+                report_reentrancy(true);
+                self.event_deferred.push(kind, Box::new(task));
+            }
+        }
     }
 }
 
 impl Drop for WindowImpl {
     fn drop(&mut self) {
-        // we set waker display to null before sending the close event, to ensure that
-        // any pending wakeups that get triggered by the close event will be
-        // ignored, preventing a potential use-after-free in the
-        // `WindowWakerImpl::wakeup` method
-        if let Ok(mut display) = self.waker.display.write() {
-            *display = std::ptr::null_mut();
+        // wakeups fail from now on, including the ones triggered by `destroyed`
+        self.waker.is_closed.store(true, Ordering::Release);
+
+        if let Some(group) = &self.group {
+            group.remove(&self.waker());
         }
 
         // handler MUST be dropped BEFORE `WindowImpl` gets dropped, as handler depends
         // on WindowImpl
-        self.handler.take();
+        if let Some(mut handler) = self.handler.take() {
+            let is_destroyed = self.is_destroyed.get() || self.is_owner_destroyed.get();
+            handler.destroyed(match (self.is_failed.get(), is_destroyed) {
+                (true, _) => DestroyReason::Failed,
+                (false, true) => DestroyReason::Destroyed,
+                (false, false) => DestroyReason::Closed,
+            });
+        }
+
+        // the context has to go before the window it draws to
+        #[cfg(feature = "opengl")]
+        {
+            self.gl_context = Err(OpenGlError::NotRequested);
+        }
+
+        // the input context refers to the window
+        self.input_method = None;
 
         unsafe {
             // kill the window itself
@@ -976,11 +2425,20 @@ impl PlatformWindow for WindowImpl {
         self.is_closing.set(true);
     }
 
+    fn is_closed(&self) -> bool {
+        self.is_closing.get()
+    }
+
     fn waker(&self) -> WindowWaker {
         WindowWaker(self.waker.clone())
     }
 
+    #[cfg(feature = "opengl")]
     fn opengl(&self) -> Result<&dyn PlatformOpenGl, OpenGlError> {
+        if let Some(gl) = &self.external_gl_context {
+            return Ok(gl);
+        }
+
         match &self.gl_context {
             Ok(gl) => Ok(gl),
             Err(e) => Err(e.clone()),
@@ -988,16 +2446,45 @@ impl PlatformWindow for WindowImpl {
     }
 
     fn scale(&self) -> f64 {
-        self.dpi_scale
+        self.dpi_scale.get()
+    }
+
+    fn color_profile(&self) -> Option<IccProfile> {
+        self.last_color_profile.borrow().clone()
+    }
+
+    fn metal_layer(&self) -> Option<NonNull<c_void>> {
+        None
+    }
+
+    fn user_data(&self) -> &RefCell<Option<Rc<dyn Any>>> {
+        &self.user_data
     }
 
     fn set_title(&self, title: &str) {
-        if let Ok(title) = CString::new(title.to_owned()) {
-            unsafe {
+        unsafe {
+            // _NET_WM_NAME is plain UTF-8 and preferred by any modern window manager
+            self.connection.set_property8(
+                self.window_id,
+                self.connection.atom(c"_NET_WM_NAME"),
+                self.connection.atom(c"UTF8_STRING"),
+                title.as_bytes(),
+            );
+
+            // WM_NAME for the rest, Xlib encodes it as Latin-1 if possible and as compound
+            // text otherwise. if the locale has no converter, fall back to the raw bytes
+            if let Ok(title) = CString::new(title) {
                 let mut text = XTextProperty { ..zeroed() };
-                let status =
-                    XStringListToTextProperty(&mut (title.as_ptr() as *mut _), 1, &mut text);
-                if status != 0 {
+                let mut list = title.as_ptr() as *mut _;
+                let status = Xutf8TextListToTextProperty(
+                    self.connection.as_raw(),
+                    &mut list,
+                    1,
+                    XStdICCTextStyle,
+                    &mut text,
+                );
+
+                if status >= 0 || XStringListToTextProperty(&mut list, 1, &mut text) != 0 {
                     XSetWMName(self.connection.as_raw(), self.window_id, &mut text);
                     XFree(text.value as *mut _);
                 }
@@ -1006,49 +2493,16 @@ impl PlatformWindow for WindowImpl {
     }
 
     fn set_decorations(&self, decorations: bool) {
-        // _NET_WM_WINDOW_TYPE
-        unsafe {
-            let data: [u32; 1] = [match decorations {
-                true => self.connection.atom(c"_NET_WM_WINDOW_TYPE_NORMAL") as u32,
-                false => self.connection.atom(c"_NET_WM_WINDOW_TYPE_DOCK") as u32,
-            }];
-
-            XChangeProperty(
-                self.connection.as_raw(),
-                self.window_id,
-                self.connection.atom(c"_NET_WM_WINDOW_TYPE"),
-                self.connection.atom(c"ATOM"),
-                32,
-                PropModeReplace,
-                data.as_ptr() as *mut _,
-                data.len() as _,
-            );
+        if self.is_embedded || self.last_decorations.replace(decorations) == decorations {
+            return;
         }
 
-        // _MOTIF_WM_HINTS
-        unsafe {
-            let data: [u32; 5] = [
-                0b10,
-                0,
-                match decorations {
-                    true => 1,
-                    false => 0,
-                },
-                0,
-                0,
-            ];
-
-            XChangeProperty(
-                self.connection.as_raw(),
-                self.window_id,
-                self.connection.atom(c"_MOTIF_WM_HINTS"),
-                self.connection.atom(c"ATOM"),
-                32,
-                PropModeReplace,
-                data.as_ptr() as *mut _,
-                data.len() as _,
-            );
-        }
+        // _MOTIF_WM_HINTS is flags, functions, decorations, input mode and status, we
+        // only set the decorations
+        let data: [c_ulong; 5] = [MWM_HINTS_DECORATIONS, 0, decorations as c_ulong, 0, 0];
+        let hints = self.connection.atom(c"_MOTIF_WM_HINTS");
+        self.connection
+            .set_property32(self.window_id, hints, hints, &data);
 
         // re-map the window to apply the changes
         if self.last_window_visible.get() {
@@ -1067,28 +2521,20 @@ impl PlatformWindow for WindowImpl {
         }
 
         unsafe {
-            let cursor = self
-                .cursor_cache
-                .borrow_mut()
-                .entry(cursor)
-                .or_insert_with(|| {
-                    X11Cursor::load(self.connection.clone(), cursor).unwrap_or_else(|| {
-                        X11Cursor::load(self.connection.clone(), MouseCursor::Default)
-                            .unwrap_or_else(|| X11Cursor::empty(self.connection.clone()))
-                    })
-                })
-                .as_raw();
-
             XChangeWindowAttributes(
                 self.connection.as_raw(),
                 self.window_id,
                 CWCursor,
-                &mut XSetWindowAttributes { cursor, ..zeroed() },
+                &mut XSetWindowAttributes {
+                    cursor: self.cursor(cursor),
+                    ..zeroed()
+                },
             );
         }
     }
 
     fn set_cursor_position(&self, point: Point) {
+        let (x, y) = point.to_pixels();
         unsafe {
             XWarpPointer(
                 self.connection.as_raw(),
@@ -1098,17 +2544,27 @@ impl PlatformWindow for WindowImpl {
                 0,
                 0,
                 0,
-                point.x.round() as i32,
-                point.y.round() as i32,
+                x,
+                y,
             );
         }
     }
 
     fn set_size(&self, size: Size) {
+        let size = self.last_resize_hints.get().constrain(
+            size,
+            self.last_min_size.get(),
+            self.last_max_size.get(),
+        );
+
         if self.last_window_size.get() == Some(size) {
             return;
         }
 
+        // locked axes follow the requested size, otherwise the window manager
+        // could refuse the resize
+        self.update_size_hints(Some(size));
+
         let (width, height) = (
             size.width.try_into().unwrap_or(i32::MAX),
             size.height.try_into().unwrap_or(i32::MAX),
@@ -1129,39 +2585,22 @@ impl PlatformWindow for WindowImpl {
     }
 
     fn set_min_size(&self, size: Size) {
-        let (min_width, min_height) = (
-            size.width.try_into().unwrap_or(i32::MAX),
-            size.height.try_into().unwrap_or(i32::MAX),
-        );
-
-        unsafe {
-            let mut hints = XSizeHints {
-                flags: PMinSize,
-                min_width,
-                min_height,
-                ..zeroed()
-            };
-
-            XSetWMNormalHints(self.connection.as_raw(), self.window_id, &mut hints);
-        }
+        self.last_min_size.set(size);
+        self.update_size_hints(self.last_window_size.get());
     }
 
     fn set_max_size(&self, size: Size) {
-        let (max_width, max_height) = (
-            size.width.try_into().unwrap_or(i32::MAX),
-            size.height.try_into().unwrap_or(i32::MAX),
-        );
+        self.last_max_size.set(size);
+        self.update_size_hints(self.last_window_size.get());
+    }
 
-        unsafe {
-            let mut hints = XSizeHints {
-                flags: PMaxSize,
-                max_width,
-                max_height,
-                ..zeroed()
-            };
+    fn set_resize_hints(&self, hints: ResizeHints) {
+        self.last_resize_hints.set(hints);
+        self.update_size_hints(self.last_window_size.get());
+    }
 
-            XSetWMNormalHints(self.connection.as_raw(), self.window_id, &mut hints);
-        }
+    fn resize_hints(&self) -> ResizeHints {
+        self.last_resize_hints.get()
     }
 
     fn set_position(&self, point: Point) {
@@ -1169,21 +2608,43 @@ impl PlatformWindow for WindowImpl {
             return;
         }
 
+        let (x, y) = point.to_pixels();
         unsafe {
             XConfigureWindow(
                 self.connection.as_raw(),
                 self.window_id,
                 (CWX | CWY) as _,
-                &mut XWindowChanges {
-                    x: point.x as i32,
-                    y: point.y as i32,
-                    ..zeroed()
-                },
+                &mut XWindowChanges { x, y, ..zeroed() },
             );
         }
     }
 
+    fn center(&self, monitor: MonitorSelector) {
+        let (_, _, width, height) = query_window_rect(&self.connection, self.window_id);
+        let (x, y, area_width, area_height) = if self.is_embedded {
+            let parent = self.window_parent.get();
+            let (_, _, width, height) = query_window_rect(&self.connection, parent);
+            (0, 0, width, height)
+        } else {
+            // transient windows go on the monitor of their owner, which is where the
+            // user is looking. Not the current parent, that is the window manager's
+            // frame once we are reparented.
+            let anchor = match self.transient_for {
+                0 => self.window_id,
+                owner => owner,
+            };
+            query_work_area(&self.connection, monitor, anchor)
+        };
+
+        self.set_position(Point::from((
+            x + (area_width - width) / 2,
+            y + (area_height - height) / 2,
+        )));
+    }
+
     fn set_visible(&self, visible: bool) {
+        // the handler decides from now on, even while the owner is hidden
+        self.is_hidden_with_owner.set(false);
         if self.last_window_visible.get() == visible {
             return;
         }
@@ -1191,15 +2652,12 @@ impl PlatformWindow for WindowImpl {
         unsafe {
             if visible {
                 if let Some(point) = self.last_window_position.get() {
+                    let (x, y) = point.to_pixels();
                     XConfigureWindow(
                         self.connection.as_raw(),
                         self.window_id,
                         (CWX | CWY) as _,
-                        &mut XWindowChanges {
-                            x: point.x as i32,
-                            y: point.y as i32,
-                            ..zeroed()
-                        },
+                        &mut XWindowChanges { x, y, ..zeroed() },
                     );
                 }
 
@@ -1225,11 +2683,11 @@ impl PlatformWindow for WindowImpl {
         }
     }
 
-    fn open_url(&self, url: &str) -> bool {
-        open_url(url)
+    fn open_url(&self, url: &str, on_done: OpenUrlCallback) {
+        open_url_in_background(url, on_done, open_url);
     }
 
-    fn get_clipboard(&self) -> Exchange {
+    fn get_clipboard(&self, format: Option<&ClipboardFormat>) -> Result<Exchange, ClipboardError> {
         let a_clipboard = self.connection.atom(c"CLIPBOARD");
         let a_xsel_data = self.connection.atom(c"XSEL_DATA");
 
@@ -1239,61 +2697,428 @@ impl PlatformWindow for WindowImpl {
             a_clipboard,
             a_xsel_data,
             CurrentTime,
+            clipboard_formats(format),
         ) {
-            Ok(exchange) => exchange,
-            Err(SelectionError::Empty) => Exchange::Empty,
-            Err(SelectionError::Reentrant) => self.exchange_clipboard.borrow().clone(),
+            Ok(exchange) => Ok(exchange),
+            Err(SelectionError::Empty) => Err(ClipboardError::Empty),
+            Err(SelectionError::Refused) => Err(ClipboardError::UnsupportedFormat),
+            Err(SelectionError::Reentrant) => {
+                filter_clipboard(self.exchange_clipboard.borrow().clone(), format)
+            }
         }
     }
 
-    fn set_clipboard(&self, data: Exchange) -> bool {
-        let is_empty = matches!(data, Exchange::Empty);
+    fn set_clipboard(&self, data: Exchange) -> Result<(), ClipboardError> {
+        let owner = match data {
+            Exchange::Empty => 0,
+            _ => self.window_id,
+        };
 
         *self.exchange_clipboard.borrow_mut() = data;
 
+        let a_clipboard = self.connection.atom(c"CLIPBOARD");
+        self.connection
+            .set_selection_owner(a_clipboard, owner, CurrentTime);
+
+        // the server silently ignores the request if someone took the selection after
+        // the given time, so check that we actually own it now
+        match self.connection.selection_owner(a_clipboard) == owner {
+            true => Ok(()),
+            false => Err(ClipboardError::AccessDenied),
+        }
+    }
+
+    fn start_drag(&self, data: Exchange) -> Result<(), DragError> {
+        if self.drag_source.borrow().is_some()
+            || self.drag_dropped.borrow().is_some()
+            || self.is_drag_starting.get()
+        {
+            return Err(DragError::InProgress);
+        }
+
+        if self.last_buttons_held.get() == 0 {
+            return Err(DragError::NoButtonHeld);
+        }
+
+        // the press that started the drag, so we do not take over a later grab
+        let time = self.last_event_token.get().time as Time;
+
         unsafe {
-            XSetSelectionOwner(
+            let a_selection = self.connection.atom(c"XdndSelection");
+            self.connection
+                .set_selection_owner(a_selection, self.window_id, time);
+            if self.connection.selection_owner(a_selection) != self.window_id {
+                return Err(DragError::Platform(
+                    "could not take the XdndSelection".into(),
+                ));
+            }
+
+            // replaces the implicit grab of the held button, so we keep getting the motion
+            // and the release while the cursor is over other windows
+            let result = XGrabPointer(
                 self.connection.as_raw(),
-                self.connection.atom(c"CLIPBOARD"),
-                if is_empty { 0 } else { self.window_id },
-                CurrentTime,
+                self.window_id,
+                False,
+                DRAG_POINTER_MASK,
+                GrabModeAsync,
+                GrabModeAsync,
+                0,
+                self.cursor(MouseCursor::NotAllowed),
+                time,
+            );
+
+            if result != GrabSuccess {
+                return Err(DragError::Platform(format!(
+                    "failed to grab the pointer ({result})"
+                )));
+            }
+
+            // Escape cancels the drag, even if the keyboard focus is elsewhere. without
+            // the keyboard grab it only works while we have the focus
+            XGrabKeyboard(
+                self.connection.as_raw(),
+                self.window_id,
+                False,
+                GrabModeAsync,
+                GrabModeAsync,
+                time,
             );
         }
 
-        true
+        // the drag grab replaced ours
+        self.cursor_grab.set(CursorGrab::None);
+        self.unlock_cursor();
+
+        let source = XdndSource::new(&self.connection, self.window_id, &data);
+        self.drag_source.replace(Some(source));
+        self.exchange_dragndrop.replace(data);
+        self.is_drag_starting.set(true);
+
+        Ok(())
     }
-}
 
-impl PlatformWaker for WindowWakerImpl {
-    fn wakeup(&self) -> Result<(), WakeupError> {
-        let display = self.display.read().map_err(|_| WakeupError)?;
+    fn set_cursor_grab(&self, mode: CursorGrab) -> Result<(), CursorGrabError> {
+        if mode == CursorGrab::None {
+            self.release_cursor_grab();
+            return Ok(());
+        }
 
-        if display.is_null() {
-            return Err(WakeupError);
+        if !self.last_window_visible.get() {
+            return Err(CursorGrabError::Unavailable);
+        }
+
+        if self.cursor_grab.get() == CursorGrab::None {
+            // the event that asked for the grab, like the press in start_drag
+            let time = self.last_event_token.get().time as Time;
+            let result = unsafe {
+                XGrabPointer(
+                    self.connection.as_raw(),
+                    self.window_id,
+                    True,
+                    CURSOR_GRAB_MASK,
+                    GrabModeAsync,
+                    GrabModeAsync,
+                    self.window_id,
+                    0,
+                    time,
+                )
+            };
+
+            if result != GrabSuccess {
+                return Err(CursorGrabError::Unavailable);
+            }
         }
 
+        self.cursor_grab.set(mode);
+        match mode {
+            CursorGrab::Locked if self.cursor_lock.get().is_none() => {
+                // lock where the cursor is, or in the middle if it was outside of the window
+                let point = self.last_cursor_position.get().unwrap_or_else(|| {
+                    let size = self.last_window_size.get().unwrap_or_default();
+                    Point {
+                        x: (size.width / 2) as f64,
+                        y: (size.height / 2) as f64,
+                    }
+                });
+
+                // warps land on whole pixels
+                let (x, y) = point.to_pixels();
+                let anchor = Point {
+                    x: x as f64,
+                    y: y as f64,
+                };
+
+                self.cursor_lock.set(Some((anchor, point)));
+                self.last_cursor_position.set(Some(point));
+                self.set_cursor_position(anchor);
+
+                if let Some(xi2) = &self.xi2_info {
+                    xi2.select_raw_motion(&self.connection, true);
+                }
+            }
+            CursorGrab::Locked => {}
+            _ => self.unlock_cursor(),
+        }
+
+        Ok(())
+    }
+
+    fn begin_drag_resize(&self, edge: ResizeEdge) -> Result<(), DragError> {
+        if self.is_embedded {
+            return Err(DragError::Unsupported);
+        }
+
+        let held = self.last_buttons_held.get();
+        let Some(&(button, _)) = MOUSE_BUTTONS.iter().find(|(bit, _)| held & (1 << bit) != 0)
+        else {
+            return Err(DragError::NoButtonHeld);
+        };
+
+        // directions of the _NET_WM_MOVERESIZE message, clockwise from the top-left
+        let direction = match edge {
+            ResizeEdge::TopLeft => 0,
+            ResizeEdge::Top => 1,
+            ResizeEdge::TopRight => 2,
+            ResizeEdge::Right => 3,
+            ResizeEdge::BottomRight => 4,
+            ResizeEdge::Bottom => 5,
+            ResizeEdge::BottomLeft => 6,
+            ResizeEdge::Left => 7,
+        };
+
+        let point = self.to_screen(self.last_cursor_position.get().unwrap_or_default());
+        let time = self.last_event_token.get().time as Time;
+
         unsafe {
+            // the window manager grabs the pointer for the resize, the LeaveNotify of that
+            // grab reports the held buttons as released
+            self.cursor_grab.set(CursorGrab::None);
+            self.unlock_cursor();
+            XUngrabPointer(self.connection.as_raw(), time);
+
+            let root = XDefaultRootWindow(self.connection.as_raw());
             XSendEvent(
-                *display,
-                self.window_id,
-                1,
-                NoEventMask,
+                self.connection.as_raw(),
+                root,
+                False,
+                SubstructureRedirectMask | SubstructureNotifyMask,
                 &mut XEvent {
                     client_message: XClientMessageEvent {
                         type_: ClientMessage,
                         serial: 0,
                         send_event: 1,
-                        display: *display,
+                        display: self.connection.as_raw(),
                         window: self.window_id,
-                        message_type: XInternAtom(*display, ATOM_WAKEUP.as_ptr(), 0),
+                        message_type: self.connection.atom(c"_NET_WM_MOVERESIZE"),
                         format: 32,
-                        data: ClientMessageData::new(),
+                        data: {
+                            let mut data = ClientMessageData::new();
+                            data.set_long(0, point.x.round() as c_long);
+                            data.set_long(1, point.y.round() as c_long);
+                            data.set_long(2, direction);
+                            data.set_long(3, button as c_long);
+                            data.set_long(4, 1); // source: a normal application
+                            data
+                        },
                     },
                 },
             );
-            XFlush(*display);
+            XFlush(self.connection.as_raw());
         }
 
         Ok(())
     }
+
+    fn has_mouse_capture(&self) -> bool {
+        self.last_buttons_held.get() != 0
+    }
+
+    fn has_keyboard_focus(&self) -> bool {
+        self.last_window_focused.get()
+    }
+
+    fn cursor_grab(&self) -> CursorGrab {
+        self.cursor_grab.get()
+    }
+
+    fn last_event_token(&self) -> EventToken {
+        self.last_event_token.get()
+    }
+
+    fn id(&self) -> WindowId {
+        self.window_entry.id()
+    }
+
+    fn to_screen(&self, point: Point) -> Point {
+        unsafe {
+            let mut x = 0;
+            let mut y = 0;
+            let mut child = 0;
+
+            XTranslateCoordinates(
+                self.connection.as_raw(),
+                self.window_id,
+                XDefaultRootWindow(self.connection.as_raw()),
+                0,
+                0,
+                &mut x,
+                &mut y,
+                &mut child,
+            );
+
+            point + Point::from((x, y))
+        }
+    }
+
+    fn safe_area_insets(&self) -> Insets {
+        Insets::default()
+    }
+
+    fn inner_size(&self) -> Size {
+        self.last_window_size.get().unwrap_or_default()
+    }
+
+    fn frame_insets(&self) -> Insets {
+        if self.is_embedded {
+            return Insets::default();
+        }
+
+        // the frame belongs to the window manager, which reports it once the
+        // window is mapped
+        frame_extents(&self.connection, self.window_id).unwrap_or_default()
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            jitter: self.frame_jitter.get(),
+            dropped_events: self.event_deferred.dropped(),
+            coalesced_events: self.event_deferred.coalesced(),
+        }
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.is_down(key)
+    }
+
+    fn set_ime_cursor_area(&self, area: Rect) {
+        if let Some(input_method) = &self.input_method {
+            input_method.set_cursor_area(area);
+        }
+    }
+
+    fn scroll_settings(&self) -> ScrollSettings {
+        ScrollSettings::default()
+    }
+
+    fn present_pixels(
+        &self,
+        buffer: &[u32],
+        size: Size,
+        damage: Damage<'_>,
+    ) -> Result<(), PresentError> {
+        let mut presenter = self.image_presenter.borrow_mut();
+        let presenter = match &mut *presenter {
+            Some(presenter) => presenter,
+            None => unsafe {
+                // embedded windows inherit the visual of the host, ask the server for it
+                let mut attributes: XWindowAttributes = zeroed();
+                if XGetWindowAttributes(self.connection.as_raw(), self.window_id, &mut attributes)
+                    == 0
+                {
+                    return Err(PresentError::Platform("XGetWindowAttributes failed".into()));
+                }
+
+                presenter.insert(ImagePresenter::new(
+                    &self.connection,
+                    self.window_id,
+                    attributes.visual,
+                    attributes.depth,
+                )?)
+            },
+        };
+
+        presenter.present(self.window_id, buffer, size, damage)
+    }
+
+    fn redraw_now(&self) {
+        // from inside the handler, or from the factory before there is one
+        if matches!(self.handler.try_borrow().as_deref(), Ok(Some(_))) {
+            self.event(|e| e.frame());
+        } else {
+            self.redraw_pending.set(true);
+        }
+    }
+
+    fn set_timer(&self, id: u32, interval: Duration) {
+        self.timers.set(id, interval, Instant::now());
+    }
+
+    fn cancel_timer(&self, id: u32) {
+        self.timers.cancel(id);
+    }
+}
+
+impl WindowWakerImpl {
+    /// Wake up the event loop of the window, which then closes it if `close`
+    /// is set, or calls [`WindowHandler::wakeup`] otherwise.
+    fn send(&self, close: bool) -> Result<(), WakeupError> {
+        if self.is_closed.load(Ordering::Acquire) {
+            return Err(WakeupError);
+        }
+
+        if close {
+            self.is_close_requested.store(true, Ordering::Release);
+        }
+
+        // the counter only saturates after 2^64 - 2 wakeups that were never read, a
+        // failed write would have woken the loop up already
+        let count = 1u64;
+        unsafe {
+            libc::write(
+                self.event_fd.as_raw_fd(),
+                &count as *const u64 as *const c_void,
+                size_of::<u64>(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Take the wakeups sent since the last call, `None` if there were none.
+    /// Otherwise, whether one of them asked to close the window.
+    fn receive(&self) -> Option<bool> {
+        let mut count = 0u64;
+        let read = unsafe {
+            libc::read(
+                self.event_fd.as_raw_fd(),
+                &mut count as *mut u64 as *mut c_void,
+                size_of::<u64>(),
+            )
+        };
+
+        (read == size_of::<u64>() as isize)
+            .then(|| self.is_close_requested.swap(false, Ordering::AcqRel))
+    }
+}
+
+impl PlatformWaker for WindowWakerImpl {
+    fn wakeup(&self) -> Result<(), WakeupError> {
+        self.send(false)
+    }
+
+    fn close(&self) -> Result<(), WakeupError> {
+        self.send(true)
+    }
+
+    fn close_and_wait(&self) {
+        let _ = self.close();
+
+        let thread = self.thread.lock().expect("lock poisoned").take();
+        if let Some(thread) = thread {
+            // joining ourselves would deadlock, the loop exits once we return anyway
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
 }
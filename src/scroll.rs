@@ -0,0 +1,183 @@
+use crate::*;
+use std::ops::Range;
+use std::time::Duration;
+
+/// How much one scroll step zooms with Ctrl held, see
+/// [`WindowBuilder::with_modifier_scroll`]
+const ZOOM_PER_STEP: f64 = 1.1;
+
+/// Wraps a [`WindowHandler`] and rewrites its scroll events depending on the
+/// held modifiers, see [`WindowBuilder::with_modifier_scroll`].
+pub(crate) struct ModifierScroll<'a> {
+    handler: Box<dyn WindowHandler + 'a>,
+    modifiers: Modifiers,
+}
+
+impl<'a> ModifierScroll<'a> {
+    pub(crate) fn new(handler: Box<dyn WindowHandler + 'a>) -> Self {
+        Self {
+            handler,
+            modifiers: Modifiers::default(),
+        }
+    }
+}
+
+impl WindowHandler for ModifierScroll<'_> {
+    fn mouse_scroll(&mut self, x: f64, y: f64) {
+        if self.modifiers.ctrl && y != 0.0 {
+            self.handler.gesture_zoom(ZOOM_PER_STEP.powf(y));
+        } else if self.modifiers.shift && x == 0.0 {
+            // some platforms (macOS) already turn shift+wheel into horizontal scroll
+            self.handler.mouse_scroll(y, 0.0);
+        } else {
+            self.handler.mouse_scroll(x, y);
+        }
+    }
+
+    fn key_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+        self.handler.key_modifiers(modifiers);
+    }
+
+    // everything else is forwarded as is
+
+    fn frame(&mut self) {
+        self.handler.frame();
+    }
+
+    fn wakeup(&mut self) {
+        self.handler.wakeup();
+    }
+
+    fn idle(&mut self) {
+        self.handler.idle();
+    }
+
+    fn timer(&mut self, id: u32) {
+        self.handler.timer(id);
+    }
+
+    fn stalled(&mut self, duration: Duration) {
+        self.handler.stalled(duration);
+    }
+
+    fn frame_pacing_changed(&mut self, pacing: FramePacing) {
+        self.handler.frame_pacing_changed(pacing);
+    }
+
+    fn system_resumed(&mut self) {
+        self.handler.system_resumed();
+    }
+
+    fn system_metrics_changed(&mut self) {
+        self.handler.system_metrics_changed();
+    }
+
+    fn damage(&mut self, region: Rect) {
+        self.handler.damage(region);
+    }
+
+    fn close_requested(&mut self) {
+        self.handler.close_requested();
+    }
+
+    fn popup_dismissed(&mut self) {
+        self.handler.popup_dismissed();
+    }
+
+    fn destroyed(&mut self, reason: DestroyReason) {
+        self.handler.destroyed(reason);
+    }
+
+    fn focus_changed(&mut self, focus: bool) {
+        self.handler.focus_changed(focus);
+    }
+
+    fn size_changed(&mut self, size: Size) {
+        self.handler.size_changed(size);
+    }
+
+    fn scale_changed(&mut self, scale: f64) {
+        self.handler.scale_changed(scale);
+    }
+
+    fn monitor_changed(&mut self, monitor: MonitorId) {
+        self.handler.monitor_changed(monitor);
+    }
+
+    fn color_profile_changed(&mut self) {
+        self.handler.color_profile_changed();
+    }
+
+    fn position_changed(&mut self, position: Point) {
+        self.handler.position_changed(position);
+    }
+
+    fn visibility_changed(&mut self, state: WindowVisibility) {
+        self.handler.visibility_changed(state);
+    }
+
+    fn safe_area_changed(&mut self, insets: Insets) {
+        self.handler.safe_area_changed(insets);
+    }
+
+    fn mouse_leave(&mut self) {
+        self.handler.mouse_leave();
+    }
+
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        self.handler.mouse_press(button, pressed, point);
+    }
+
+    fn mouse_move(&mut self, point: Point) {
+        self.handler.mouse_move(point);
+    }
+
+    fn gesture_rotate(&mut self, angle: f64) {
+        self.handler.gesture_rotate(angle);
+    }
+
+    fn gesture_zoom(&mut self, scale: f64) {
+        self.handler.gesture_zoom(scale);
+    }
+
+    fn pen_proximity(&mut self, entering: bool, inverted: bool) {
+        self.handler.pen_proximity(entering, inverted);
+    }
+
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        self.handler.key_press(key, pressed, is_repeat, text)
+    }
+
+    fn composition_start(&mut self) {
+        self.handler.composition_start();
+    }
+
+    fn composition_update(&mut self, text: &str, cursor_range: Range<usize>) {
+        self.handler.composition_update(text, cursor_range);
+    }
+
+    fn composition_end(&mut self, text: Option<&str>) {
+        self.handler.composition_end(text);
+    }
+
+    fn drag_enter(&mut self, data: Exchange, point: Point) -> DropEffect {
+        self.handler.drag_enter(data, point)
+    }
+
+    fn drag_move(&mut self, point: Point) -> DropEffect {
+        self.handler.drag_move(point)
+    }
+
+    fn drag_leave(&mut self) {
+        self.handler.drag_leave();
+    }
+
+    fn drag_accept(&mut self) -> DropEffect {
+        self.handler.drag_accept()
+    }
+
+    fn drag_finished(&mut self, effect: DropEffect) {
+        self.handler.drag_finished(effect);
+    }
+}
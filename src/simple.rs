@@ -0,0 +1,356 @@
+use crate::*;
+use std::error::Error;
+use std::ops::Range;
+use std::time::Duration;
+
+/// An event delivered to an [`EventHandler`], to the callback passed to
+/// `run_simple` with the `standalone` feature, or to a `HeadlessWindow` with
+/// the `headless` feature.
+///
+/// Mirrors the methods of [`WindowHandler`], see their documentation for
+/// details. Events that require a response (like drag-and-drop) are not
+/// delivered, use [`WindowBuilder`] directly if you need them.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// See [`WindowHandler::frame`]
+    Frame,
+    /// See [`WindowHandler::wakeup`]
+    Wakeup,
+    /// See [`WindowHandler::idle`]
+    Idle,
+    /// See [`WindowHandler::timer`]
+    Timer {
+        /// The id the timer was set with
+        id: u32,
+    },
+    /// See [`WindowHandler::stalled`]
+    Stalled(Duration),
+    /// See [`WindowHandler::frame_pacing_changed`]
+    FramePacingChanged(FramePacing),
+    /// See [`WindowHandler::system_resumed`]
+    SystemResumed,
+    /// See [`WindowHandler::system_metrics_changed`]
+    SystemMetricsChanged,
+    /// See [`WindowHandler::damage`]
+    Damage(Rect),
+    /// See [`WindowHandler::close_requested`]
+    CloseRequested,
+    /// See [`WindowHandler::popup_dismissed`]
+    PopupDismissed,
+    /// See [`WindowHandler::destroyed`]
+    Destroyed(DestroyReason),
+    /// See [`WindowHandler::focus_changed`]
+    FocusChanged(bool),
+    /// See [`WindowHandler::size_changed`]
+    SizeChanged(Size),
+    /// See [`WindowHandler::scale_changed`]
+    ScaleChanged(f64),
+    /// See [`WindowHandler::monitor_changed`]
+    MonitorChanged {
+        /// The monitor the window is on now
+        monitor_id: MonitorId,
+    },
+    /// See [`WindowHandler::color_profile_changed`]
+    ColorProfileChanged,
+    /// See [`WindowHandler::position_changed`]
+    PositionChanged(Point),
+    /// See [`WindowHandler::visibility_changed`]
+    VisibilityChanged(WindowVisibility),
+    /// See [`WindowHandler::safe_area_changed`]
+    SafeAreaChanged(Insets),
+    /// See [`WindowHandler::mouse_leave`]
+    MouseLeave,
+    /// See [`WindowHandler::mouse_press`]
+    MousePress {
+        /// The button that was pressed or released
+        button: MouseButton,
+        /// `true` if pressed, `false` if released
+        pressed: bool,
+        /// Where the cursor was when the button changed
+        position: Point,
+    },
+    /// See [`WindowHandler::mouse_move`]
+    MouseMove(Point),
+    /// See [`WindowHandler::mouse_scroll`]
+    MouseScroll {
+        /// Horizontal scroll amount
+        x: f64,
+        /// Vertical scroll amount
+        y: f64,
+    },
+    /// See [`WindowHandler::gesture_rotate`]
+    GestureRotate(f64),
+    /// See [`WindowHandler::gesture_zoom`]
+    GestureZoom(f64),
+    /// See [`WindowHandler::pen_proximity`]
+    PenProximity {
+        /// `true` if the pen came into proximity, `false` if it left
+        entering: bool,
+        /// `true` if the eraser end of the pen is used
+        inverted: bool,
+    },
+    /// See [`WindowHandler::key_modifiers`]
+    KeyModifiers(Modifiers),
+    /// See [`WindowHandler::key_press`]. The key is never captured.
+    KeyPress {
+        /// The key that was pressed or released
+        key: Key,
+        /// `true` if pressed, `false` if released
+        pressed: bool,
+        /// `true` if the press was repeated by holding the key down
+        is_repeat: bool,
+        /// The printable character the key would type, if any
+        text: Option<char>,
+    },
+    /// See [`WindowHandler::composition_start`]
+    CompositionStart,
+    /// See [`WindowHandler::composition_update`]
+    CompositionUpdate {
+        /// All of the text being composed
+        text: String,
+        /// The byte range of `text` the input method is working on
+        cursor_range: Range<usize>,
+    },
+    /// See [`WindowHandler::composition_end`]
+    CompositionEnd {
+        /// The committed text, `None` if the composition was cancelled
+        text: Option<String>,
+    },
+    /// See [`WindowHandler::drag_finished`]
+    DragFinished(DropEffect),
+}
+
+/// A handler that gets all events of a window through a single method, an
+/// alternative to implementing [`WindowHandler`] and writing a factory for it.
+///
+/// See [`WindowBuilder::with_handler`]. Events are delivered the same way as to
+/// `run_simple`: key presses are never captured and events that require a
+/// response (like drag-and-drop) are not delivered.
+pub trait EventHandler: Send + 'static {
+    /// Called once the window is created, before any event is delivered. This
+    /// is the place to set the title, size and visibility of the window.
+    ///
+    /// # Errors
+    /// An error returned here fails the opening of the window with
+    /// [`WindowError::Factory`].
+    fn opened(&mut self, window: Window<'_>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let _ = window;
+        Ok(())
+    }
+
+    /// Handle an event of the window.
+    fn event(&mut self, window: Window<'_>, event: Event);
+}
+
+/// Adapts an [`EventHandler`] to [`WindowHandler`].
+pub(crate) struct EventAdapter<'a, H> {
+    window: Window<'a>,
+    handler: H,
+}
+
+impl<'a, H: EventHandler> EventAdapter<'a, H> {
+    pub(crate) fn new(window: Window<'a>, handler: H) -> Self {
+        Self { window, handler }
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.handler.event(self.window, event);
+    }
+}
+
+impl<H: EventHandler> WindowHandler for EventAdapter<'_, H> {
+    fn frame(&mut self) {
+        self.emit(Event::Frame);
+    }
+
+    fn wakeup(&mut self) {
+        self.emit(Event::Wakeup);
+    }
+
+    fn idle(&mut self) {
+        self.emit(Event::Idle);
+    }
+
+    fn timer(&mut self, id: u32) {
+        self.emit(Event::Timer { id });
+    }
+
+    fn stalled(&mut self, duration: Duration) {
+        self.emit(Event::Stalled(duration));
+    }
+
+    fn frame_pacing_changed(&mut self, pacing: FramePacing) {
+        self.emit(Event::FramePacingChanged(pacing));
+    }
+
+    fn system_resumed(&mut self) {
+        self.emit(Event::SystemResumed);
+    }
+
+    fn system_metrics_changed(&mut self) {
+        self.emit(Event::SystemMetricsChanged);
+    }
+
+    fn damage(&mut self, region: Rect) {
+        self.emit(Event::Damage(region));
+    }
+
+    fn close_requested(&mut self) {
+        self.emit(Event::CloseRequested);
+    }
+
+    fn popup_dismissed(&mut self) {
+        self.emit(Event::PopupDismissed);
+    }
+
+    fn destroyed(&mut self, reason: DestroyReason) {
+        self.emit(Event::Destroyed(reason));
+    }
+
+    fn focus_changed(&mut self, focus: bool) {
+        self.emit(Event::FocusChanged(focus));
+    }
+
+    fn size_changed(&mut self, size: Size) {
+        self.emit(Event::SizeChanged(size));
+    }
+
+    fn scale_changed(&mut self, scale: f64) {
+        self.emit(Event::ScaleChanged(scale));
+    }
+
+    fn monitor_changed(&mut self, monitor_id: MonitorId) {
+        self.emit(Event::MonitorChanged { monitor_id });
+    }
+
+    fn color_profile_changed(&mut self) {
+        self.emit(Event::ColorProfileChanged);
+    }
+
+    fn position_changed(&mut self, position: Point) {
+        self.emit(Event::PositionChanged(position));
+    }
+
+    fn visibility_changed(&mut self, state: WindowVisibility) {
+        self.emit(Event::VisibilityChanged(state));
+    }
+
+    fn safe_area_changed(&mut self, insets: Insets) {
+        self.emit(Event::SafeAreaChanged(insets));
+    }
+
+    fn mouse_leave(&mut self) {
+        self.emit(Event::MouseLeave);
+    }
+
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        self.emit(Event::MousePress {
+            button,
+            pressed,
+            position: point,
+        });
+    }
+
+    fn mouse_move(&mut self, point: Point) {
+        self.emit(Event::MouseMove(point));
+    }
+
+    fn mouse_scroll(&mut self, x: f64, y: f64) {
+        self.emit(Event::MouseScroll { x, y });
+    }
+
+    fn gesture_rotate(&mut self, angle: f64) {
+        self.emit(Event::GestureRotate(angle));
+    }
+
+    fn gesture_zoom(&mut self, scale: f64) {
+        self.emit(Event::GestureZoom(scale));
+    }
+
+    fn pen_proximity(&mut self, entering: bool, inverted: bool) {
+        self.emit(Event::PenProximity { entering, inverted });
+    }
+
+    fn key_modifiers(&mut self, modifiers: Modifiers) {
+        self.emit(Event::KeyModifiers(modifiers));
+    }
+
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        self.emit(Event::KeyPress {
+            key,
+            pressed,
+            is_repeat,
+            text,
+        });
+        false
+    }
+
+    fn composition_start(&mut self) {
+        self.emit(Event::CompositionStart);
+    }
+
+    fn composition_update(&mut self, text: &str, cursor_range: Range<usize>) {
+        self.emit(Event::CompositionUpdate {
+            text: text.to_owned(),
+            cursor_range,
+        });
+    }
+
+    fn composition_end(&mut self, text: Option<&str>) {
+        self.emit(Event::CompositionEnd {
+            text: text.map(str::to_owned),
+        });
+    }
+
+    fn drag_finished(&mut self, effect: DropEffect) {
+        self.emit(Event::DragFinished(effect));
+    }
+}
+
+/// Open a visible top-level window with the given title and size and call
+/// `callback` for every event. Blocks until the window is closed.
+///
+/// This is a shortcut for quick experiments, the window closes itself on
+/// [`Event::CloseRequested`] after the callback has seen it. Use
+/// [`WindowBuilder`] for anything more involved.
+///
+/// # Errors
+/// - [`WindowError::Platform`] if a platform-specific error occurred.
+#[cfg(feature = "standalone")]
+pub fn run_simple(
+    title: &str,
+    size: impl Into<Size>,
+    callback: impl for<'a> FnMut(Event, Window<'a>) + Send + 'static,
+) -> Result<(), WindowError> {
+    struct Callback<F> {
+        title: String,
+        size: Size,
+        callback: F,
+    }
+
+    impl<F: for<'a> FnMut(Event, Window<'a>) + Send + 'static> EventHandler for Callback<F> {
+        fn opened(&mut self, window: Window<'_>) -> Result<(), Box<dyn Error + Send + Sync>> {
+            window.set_title(&self.title);
+            window.set_size(self.size);
+            window.set_visible(true);
+            Ok(())
+        }
+
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            let close = event == Event::CloseRequested;
+            (self.callback)(event, window);
+
+            if close {
+                window.close();
+            }
+        }
+    }
+
+    WindowBuilder::with_handler(Callback {
+        title: title.to_owned(),
+        size: size.into(),
+        callback,
+    })
+    .open_blocking()
+}
@@ -1,22 +1,110 @@
+use crate::compress::GeometryCompression;
+use crate::scroll::ModifierScroll;
 use crate::*;
+use std::any::Any;
 use std::error::Error;
+use std::ffi::c_void;
 use std::fmt::Debug;
+use std::ops::Range;
+use std::ptr::NonNull;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// A window handler, the object that processes all incoming events for a single
 /// window.
+///
+/// # Reentrancy
+///
+/// The handler is never called while it is already running. Some calls into
+/// [`Window`] make the system send events right away (a resize after
+/// [`Window::set_size`] on Windows), and modal loops started by the handler (a
+/// native file dialog) keep delivering events. Such events are queued and
+/// delivered in order as soon as the current call returns.
+///
+/// Events that need a response (like [`WindowHandler::key_press`] and
+/// drag-and-drop) can not wait, they get the default response without
+/// reaching the handler. Frame events are skipped, the next one follows
+/// shortly. Enable the `debug-reentrancy` feature to print a backtrace
+/// whenever any of this happens.
 pub trait WindowHandler {
     /// Frame event. You should redraw the window in response to this event.
     ///
     /// This event is sent at the refresh rate of the display (typically 60 Hz),
     /// on a best-effort basis (might use an unsynchronized timer depending on
     /// the platform).
+    ///
+    /// Nothing is presented on the handler's behalf: if nothing changed, skip
+    /// rendering and do not call `GlContext::swap_buffers` (or present the
+    /// graphics surface), and the window keeps showing the previous frame.
     fn frame(&mut self) {}
 
     /// A wakeup event triggered by a call to
     /// [`WindowWaker::wakeup`]
+    ///
+    /// Wakeups sent in quick succession may be delivered as a single call
+    /// (on X11 and macOS).
     fn wakeup(&mut self) {}
 
+    /// Idle event. The event loop woke up after waiting for the timeout set
+    /// with [`WindowBuilder::with_max_poll_timeout`] without receiving any
+    /// events.
+    ///
+    /// Only sent on X11, other platforms ignore the poll timeout.
+    fn idle(&mut self) {}
+
+    /// A timer set with [`Window::set_timer`] expired, sent every interval
+    /// until the timer is cancelled.
+    ///
+    /// Expirations missed while the handler is busy are dropped rather than
+    /// queued, like frames.
+    fn timer(&mut self, id: u32) {
+        let _ = id;
+    }
+
+    /// The handler took `duration` to handle an event, which is longer than
+    /// the threshold set with [`WindowBuilder::with_watchdog`].
+    ///
+    /// Sent right after the slow event, use it to log diagnostics. Frame
+    /// events missed in the meantime are dropped rather than queued, so the
+    /// window catches up on its own.
+    fn stalled(&mut self, duration: Duration) {
+        let _ = duration;
+    }
+
+    /// The way [`Self::frame`] events are paced changed.
+    ///
+    /// Only sent on Windows, where frames start out paced by the vertical
+    /// blank. Under remote desktop sessions or with some drivers waiting for it
+    /// fails or returns right away, frames then fall back to
+    /// [`FramePacing::Timer`] until the display changes. Use it to log
+    /// diagnostics.
+    fn frame_pacing_changed(&mut self, pacing: FramePacing) {
+        let _ = pacing;
+    }
+
+    /// The system woke up from sleep.
+    ///
+    /// The source of [`Self::frame`] events is restarted before this is sent,
+    /// so frames keep coming without doing anything. Use it to refresh
+    /// anything that went stale while the system was asleep, like clocks or
+    /// device lists.
+    ///
+    /// Sent on Windows (`WM_POWERBROADCAST`) and macOS
+    /// (`NSWorkspaceDidWakeNotification`).
+    fn system_resumed(&mut self) {}
+
+    /// The user changed one of the [`SystemMetrics`], query them again with
+    /// [`system_metrics`].
+    ///
+    /// Sent on Windows (`WM_SETTINGCHANGE`, forwarded from the top-level
+    /// window of the host to embedded windows if it runs on the same thread),
+    /// macOS (when the scrollbar style changes) and X11 (when the `XSETTINGS`
+    /// of the desktop change). Not
+    /// sent for every change on every platform, so do not rely on it for
+    /// correctness.
+    fn system_metrics_changed(&mut self) {}
+
     /// Damage event. Request to redraw the specificed region as soon as
     /// possible.
     fn damage(&mut self, region: Rect) {
@@ -26,10 +114,46 @@ pub trait WindowHandler {
     /// User requested to close the window (by clicking the close button, or
     /// pressing Alt+F4, etc)
     ///
+    /// Embedded macOS views also get this when the host removes them from
+    /// their superview for good, as the last chance to save state while the
+    /// window is still usable. The handler is dropped once the host releases
+    /// the view.
+    ///
     /// To actually close the window, you have to call
     /// [`Window::close`].
     fn close_requested(&mut self) {}
 
+    /// A popup lost the focus, and closes right after this returns. See
+    /// [`WindowBuilder::open_popup`].
+    ///
+    /// Popups never take the focus from the window they were opened for, and
+    /// are dismissed once the top-level window containing that one loses it.
+    /// On Windows, where clicking a popup activates it, a popup is dismissed
+    /// when another application is activated, or when another window is
+    /// activated after the popup, except for the top-level window it belongs
+    /// to and its own popups.
+    fn popup_dismissed(&mut self) {}
+
+    /// The last event the handler receives, sent right before it is dropped.
+    ///
+    /// Sent exactly once for every handler the factory created, however the
+    /// window went away, see [`DestroyReason`]. Events requested from here are
+    /// not delivered.
+    ///
+    /// Whether the window is still usable depends on the platform and reason:
+    /// - **Windows, macOS**: the window (or view) and its OpenGL context are
+    ///   still alive, so this is the place to free GPU resources.
+    /// - **X11**: the window and its OpenGL context are still alive after
+    ///   [`DestroyReason::Closed`], and for transient windows whose owner was
+    ///   destroyed. An embedded window reported as [`DestroyReason::Destroyed`]
+    ///   is already gone, the server destroyed it along with its parent, and
+    ///   after [`DestroyReason::Failed`] so is the connection. The context can
+    ///   not be made current in either case, GPU resources are freed when it is
+    ///   destroyed right after this returns.
+    fn destroyed(&mut self, reason: DestroyReason) {
+        let _ = reason;
+    }
+
     /// The window gained or lost focus.
     fn focus_changed(&mut self, focus: bool) {
         let _ = focus;
@@ -38,6 +162,9 @@ pub trait WindowHandler {
     /// Size of a window has changed.
     ///
     /// The size provided is the new size of the client area in physical pixels.
+    ///
+    /// Unless disabled with [`WindowBuilder::with_geometry_compression`],
+    /// only the latest size is delivered, right before the next other event.
     fn size_changed(&mut self, size: Size) {
         let _ = size;
     }
@@ -49,11 +176,31 @@ pub trait WindowHandler {
         let _ = scale;
     }
 
+    /// The window moved to a different monitor.
+    ///
+    /// Also sent once the monitor the window starts on is known. Monitors can
+    /// share a scale factor and still differ in refresh rate and color
+    /// profile, query those again for the new monitor.
+    fn monitor_changed(&mut self, monitor: MonitorId) {
+        let _ = monitor;
+    }
+
+    /// The color profile of the monitor the window is on has changed, or the
+    /// window moved to a monitor with a different one.
+    ///
+    /// Also sent once the first profile is known. Query the new one with
+    /// [`Window::color_profile`].
+    fn color_profile_changed(&mut self) {}
+
     /// The position of a window has changed.
     ///
     /// The position provided is the new position of the client area in physical
     /// pixels relative to the origin (top-left corner) of the coordinate
     /// system (screen or parent window).
+    ///
+    /// Unless disabled with [`WindowBuilder::with_geometry_compression`],
+    /// only the latest position is delivered, right before the next other
+    /// event.
     fn position_changed(&mut self, position: Point) {
         let _ = position;
     }
@@ -63,6 +210,14 @@ pub trait WindowHandler {
         let _ = state;
     }
 
+    /// The safe area of the window has changed, see
+    /// [`Window::safe_area_insets`].
+    ///
+    /// Only sent on macOS, other platforms have no safe area insets.
+    fn safe_area_changed(&mut self, insets: Insets) {
+        let _ = insets;
+    }
+
     /// The mouse cursor left the window.
     ///
     /// Note that there is no corresponding event for when the mouse enters the
@@ -70,13 +225,22 @@ pub trait WindowHandler {
     /// events.
     fn mouse_leave(&mut self) {}
 
-    /// A mouse button was pressed or released at position provided by the last
-    /// call to [`Self::mouse_move`]
-    fn mouse_press(&mut self, button: MouseButton, pressed: bool) {
-        let _ = (button, pressed);
+    /// A mouse button was pressed or released at `point`.
+    ///
+    /// `point` is taken from the button event itself, so it is exact even if
+    /// the preceding [`Self::mouse_move`] was coalesced away. It is usually
+    /// also delivered as a [`Self::mouse_move`] right before this event.
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        let _ = (button, pressed, point);
     }
 
     /// The mouse cursor was moved within the window.
+    ///
+    /// While the cursor is locked with [`Window::set_cursor_grab`], `point` is
+    /// a virtual position that keeps moving past the edges of the window.
+    /// There is no separate delta: the relative motion is the difference to
+    /// the previous `point`, so code that follows a drag by its position
+    /// works the same with and without the lock.
     fn mouse_move(&mut self, point: Point) {
         let _ = point;
     }
@@ -84,7 +248,9 @@ pub trait WindowHandler {
     /// The mouse wheel was scrolled (can also represent touchpad scrolling).
     ///
     /// `picoview` normalizes scroll events to a consistent unit across
-    /// platforms.
+    /// platforms: one notch of the wheel is `1.0`, see [`ScrollDelta`] for
+    /// how. See [`Window::scroll_settings`] for how far the user wants that to
+    /// scroll.
     fn mouse_scroll(&mut self, x: f64, y: f64) {
         let _ = (x, y);
     }
@@ -101,20 +267,80 @@ pub trait WindowHandler {
         let _ = scale;
     }
 
+    /// A pen (for example, a graphics tablet stylus) came into or went out of
+    /// proximity of the window.
+    ///
+    /// `inverted` is `true` if the pen is used with its eraser end.
+    fn pen_proximity(&mut self, entering: bool, inverted: bool) {
+        let _ = (entering, inverted);
+    }
+
     /// The state of the modifier keys (Shift, Ctrl, Alt, etc.) has changed.
+    ///
+    /// Always delivered before the mouse or keyboard event that observed the
+    /// change, so a Ctrl+Click is seen as `key_modifiers` followed by
+    /// `mouse_press`.
     fn key_modifiers(&mut self, modifiers: Modifiers) {
         let _ = modifiers;
     }
 
     /// A key was pressed or released.
     ///
+    /// `text` is the character the key would type with the current keyboard
+    /// layout and modifiers, if it is printable and the key is pressed. It is
+    /// a preview for simple widgets and not a replacement for proper text
+    /// input: dead keys and input methods are not composed, and the result
+    /// depends on the keyboard layout. Text fields should also handle the
+    /// composition events, starting with [`Self::composition_start`].
+    ///
+    /// Holding a key down repeats the press at the rate set by the system,
+    /// without any releases in between, until the key is physically released.
+    /// `is_repeat` is `true` for these repeated presses and `false` for the
+    /// first one and for releases.
+    ///
     /// Return `true` if the event was handled and should not be propagated to
-    /// the parent (if this window is embedded in another window)
-    fn key_press(&mut self, key: Key, pressed: bool) -> bool {
-        let _ = (key, pressed);
+    /// the parent (if this window is embedded in another window). Keys that
+    /// `picoview` does not recognize are always propagated.
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        let _ = (key, pressed, is_repeat, text);
         false
     }
 
+    /// An input method started composing text, like a Japanese word before
+    /// it is converted to kanji, or an accent waiting for the letter it goes
+    /// on.
+    ///
+    /// The handler draws the text being composed (the preedit), reported with
+    /// [`Self::composition_update`], at the caret until
+    /// [`Self::composition_end`]. While composing, key presses go to the input
+    /// method and are not reported with [`Self::key_press`]. Use
+    /// [`Window::set_ime_cursor_area`] to place the candidate list next to the
+    /// caret.
+    ///
+    /// On X11 the input method is picked by the locale of the process, which
+    /// is left to the application: call `setlocale(LC_CTYPE, "")` before
+    /// opening a window. In the C locale no input method is used.
+    fn composition_start(&mut self) {}
+
+    /// The text being composed changed. `text` is all of it, replacing the
+    /// last update. `cursor_range` is the byte range of `text` the input
+    /// method is working on (like the clause being converted), usually drawn
+    /// highlighted, or an empty range at the caret.
+    fn composition_update(&mut self, text: &str, cursor_range: Range<usize>) {
+        let _ = (text, cursor_range);
+    }
+
+    /// The composition ended and its text should be removed. `text` is the
+    /// text to insert in its place, or `None` if the composition was
+    /// cancelled.
+    ///
+    /// Input methods may commit text without a composition in progress (like
+    /// an emoji picker), which is reported as a composition that ends right
+    /// after it starts.
+    fn composition_end(&mut self, text: Option<&str>) {
+        let _ = text;
+    }
+
     /// Drag-and-drop data was dragged into the window, the position will be
     /// reported via [`Self::drag_move`] events until the drag-and-drop
     /// operation is cancelled or completed.
@@ -142,6 +368,13 @@ pub trait WindowHandler {
     fn drag_accept(&mut self) -> DropEffect {
         DropEffect::Reject
     }
+
+    /// A drag started with [`Window::start_drag`] ended, with the effect the
+    /// drop target chose. [`DropEffect::Reject`] if the data was not dropped
+    /// anywhere, or the drag was cancelled.
+    fn drag_finished(&mut self, effect: DropEffect) {
+        let _ = effect;
+    }
 }
 
 impl WindowHandler for () {}
@@ -159,6 +392,17 @@ impl WindowHandler for () {}
 /// Optionally, the factory can return an error if it fails to initialize for
 /// some reason. The error will be propagated to the caller as
 /// [`WindowError::Factory`].
+///
+/// Events that arrive while the factory is running (like a quick click on a
+/// window that was just shown) are delivered in order once the handler is
+/// returned, and key presses received that way are never captured.
+///
+/// The factory runs on the thread that runs the event loop of the window (on
+/// X11, embedded and transient windows opened by a host get a thread of
+/// their own, those opened from a handler join the loop that runs it), so
+/// every [`Window`] method can be called from it.
+/// [`Window::redraw_now`] delivers its frame right after the handler is
+/// returned.
 pub type WindowFactory = Box<
     dyn for<'a> FnOnce(
             Window<'a>,
@@ -180,21 +424,105 @@ pub struct WindowBuilder {
     /// Whether the window client area is transparent (premultiplied alpha)
     pub transparent: bool,
 
-    /// The requested OpenGL configuration for the window, if any
-    pub opengl: Option<GlConfig>,
+    /// The requested OpenGL configurations for the window, tried in order
+    /// until a context can be created. Empty if OpenGL is not requested
+    #[cfg(feature = "opengl")]
+    pub opengl: Vec<GlConfig>,
+
+    /// The host-provided OpenGL context returned by [`Window::opengl`], takes
+    /// precedence over [`WindowBuilder::opengl`]
+    #[cfg(feature = "opengl")]
+    pub external_opengl: Option<ExternalGlContext>,
+
+    /// The cursor icon shown when hovering over the window before any call to
+    /// [`Window::set_cursor_icon`]
+    pub cursor: MouseCursor,
+
+    /// Whether to use the unified pointer input path where available (Windows
+    /// only)
+    pub pointer_input: bool,
+
+    /// Whether the window takes the size the system suggests when it moves to
+    /// a monitor with a different scale (Windows only)
+    pub auto_dpi_resize: bool,
+
+    /// Tweaks of how the native window is created
+    pub platform_options: PlatformOptions,
+
+    /// Whether a `CAMetalLayer` is attached to the window, see
+    /// [`Window::metal_layer`] (macOS only)
+    pub metal_layer: bool,
+
+    /// The longest time the event loop waits for events before yielding with
+    /// [`WindowHandler::idle`], if any (X11 only)
+    pub max_poll_timeout: Option<Duration>,
+
+    /// Whether an embedded window follows the size of its parent window (X11
+    /// only)
+    pub fill_parent: bool,
+
+    /// Whether a transient window or a popup moves along with the top-level
+    /// window of its parent
+    pub follow_parent: bool,
+
+    /// Whether an embedded window takes the keyboard focus when it is opened
+    pub initial_keyboard_focus: bool,
+
+    /// The initial position of an embedded window or a popup relative to its
+    /// parent
+    pub parent_relative_position: Point,
+
+    /// Whether scroll events are rewritten depending on the held modifiers
+    pub modifier_scroll: bool,
+
+    /// Whether scroll events are reported as the platform sends them, see
+    /// [`ScrollDelta::raw`]
+    pub raw_scroll: bool,
+
+    /// Whether releases of buttons pressed outside of the window are delivered
+    pub unmatched_mouse_release: bool,
+
+    /// Whether size and position changes are held back until the next other
+    /// event, delivering only the latest
+    pub geometry_compression: bool,
+
+    /// The event handling time after which [`WindowHandler::stalled`] is
+    /// sent, if any
+    pub watchdog: Option<Duration>,
+
+    /// The group the window joins when opened, if any
+    pub group: Option<WindowGroup>,
 
     /// The factory function that creates the event handler for the window
     pub factory: WindowFactory,
 }
 
 /// A thread-safe handle that can be used to wake up an associated event loop.
+///
+/// `WindowWaker` is `Send + Sync` and cheap to clone, so it can be moved to (or
+/// shared with) any other thread. It may outlive its window, in which case
+/// [`WindowWaker::wakeup`] returns an error.
 #[derive(Clone)]
 pub struct WindowWaker(pub(crate) Arc<dyn platform::PlatformWaker>);
 
+/// Closes its window when dropped, see [`WindowWaker::into_guard`].
+#[must_use = "dropping the guard closes the window"]
+#[derive(Debug)]
+pub struct WindowGuard {
+    waker: WindowWaker,
+    armed: bool,
+}
+
 /// A handle to an open window.
 ///
 /// It is only valid while the window is open and only accessible from the event
 /// loop of that window.
+///
+/// The raw window and display handles (see [`rwh_06::HasWindowHandle`]) stay
+/// the same from the call to the factory until the handler is dropped, even if
+/// the window is resized, hidden or reparented by the host. Graphics surfaces
+/// (wgpu, softbuffer, ...) can be created in the factory and kept in the
+/// handler, as long as they are resized in [`WindowHandler::size_changed`].
 #[derive(Clone, Copy)]
 pub struct Window<'a>(pub(crate) &'a dyn platform::PlatformWindow);
 
@@ -218,17 +546,56 @@ impl<'a> Window<'a> {
     ///   not supported on this device.
     /// - [`OpenGlError::Platform`] if the context could not be created for a
     ///   platform-specific reason.
+    ///
+    /// With [`WindowBuilder::with_opengl_fallback_chain`] the error is the one
+    /// of the last config in the chain.
+    #[cfg(feature = "opengl")]
     #[inline]
     pub fn opengl(&self) -> Result<GlContext<'a>, OpenGlError> {
         self.0.opengl().map(GlContext)
     }
 
+    /// Get the `CAMetalLayer` requested with
+    /// [`WindowBuilder::with_metal_layer`], as a `CAMetalLayer *` for wgpu
+    /// (`SurfaceTargetUnsafe::CoreAnimationLayer`) or
+    /// `metal::MetalLayerRef::from_ptr`.
+    ///
+    /// The layer covers the window, and its drawable size follows the size
+    /// of the window in physical pixels. Render into it on
+    /// [`WindowHandler::frame`], which is driven by the display link.
+    ///
+    /// Returns `None` if the layer was not requested, and on platforms other
+    /// than macOS.
+    #[must_use]
+    #[inline]
+    pub fn metal_layer(&self) -> Option<NonNull<c_void>> {
+        self.0.metal_layer()
+    }
+
     /// Close the window and exit its event loop.
+    ///
+    /// Takes effect right away: events queued up to this point are dropped and
+    /// the only call the handler gets afterwards is
+    /// [`WindowHandler::destroyed`], before the native window and its OpenGL
+    /// context are torn down.
+    ///
+    /// Calling it again does nothing. The window stays usable until the
+    /// current event returns: getters return the last known state, setters,
+    /// [`Window::redraw_now`] and [`Window::set_timer`] do nothing, and
+    /// methods that can fail return their `Closed` error.
     #[inline]
     pub fn close(&self) {
         self.0.close();
     }
 
+    /// Check if [`Window::close`] was called, or the window is being closed
+    /// by the user, the host or the system.
+    #[must_use]
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
     /// Get the current scale factor of the window, which is the ratio of
     /// physical pixels to logical pixels.
     ///
@@ -250,9 +617,48 @@ impl<'a> Window<'a> {
         self.0.scale()
     }
 
+    /// Get the ICC color profile of the monitor the window is on, so colors
+    /// can be converted from the color space they were authored in to the one
+    /// of the display.
+    ///
+    /// Returns `None` if the monitor has no profile assigned, or if the
+    /// platform doesn't report one. Assume sRGB in that case. On X11 this is
+    /// the `_ICC_PROFILE` property set on the root window by color management
+    /// daemons.
+    ///
+    /// If changed, a call [`WindowHandler::color_profile_changed`] will be
+    /// emitted.
+    #[must_use]
+    pub fn color_profile(&self) -> Option<IccProfile> {
+        self.0.color_profile()
+    }
+
+    /// Attach arbitrary data to the window, replacing (and dropping) any data
+    /// attached before.
+    ///
+    /// The data lives as long as the window does and is dropped after the
+    /// [`WindowHandler`].
+    pub fn set_user_data(&self, data: Box<dyn Any>) {
+        // drop the old data outside of the borrow, in case its drop impl accesses the
+        // window again
+        let _ = self.0.user_data().replace(Some(Rc::from(data)));
+    }
+
+    /// Get the data attached with [`Window::set_user_data`], if there is any
+    /// and it is of type `T`.
+    #[must_use]
+    pub fn user_data<T: Any>(&self) -> Option<Rc<T>> {
+        let data = self.0.user_data().borrow().clone()?;
+        data.downcast().ok()
+    }
+
     /// Set the window title.
     #[inline]
     pub fn set_title(&self, title: &str) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_title(title);
     }
 
@@ -260,16 +666,31 @@ impl<'a> Window<'a> {
     ///
     /// Safe to call every frame, the backend will only update the cursor if it
     /// has changed.
+    ///
+    /// [`MouseCursor::Hidden`] only hides the cursor while it is over the
+    /// window, or while dragging with a button pressed inside of it. It is
+    /// shown again once the mouse leaves or the window closes, so a hidden
+    /// cursor never leaks into the host.
     #[inline]
     pub fn set_cursor_icon(&self, icon: MouseCursor) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_cursor_icon(icon);
     }
 
     /// Set whether the window has decorations (title bar, borders, etc)
     ///
-    /// Does nothing when opened with [`WindowBuilder::open_embedded`].
+    /// Can be toggled at any time, the client area keeps its size (the window
+    /// grows or shrinks around it). Does nothing when opened with
+    /// [`WindowBuilder::open_embedded`].
     #[inline]
     pub fn set_decorations(&self, decorations: bool) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_decorations(decorations);
     }
 
@@ -282,33 +703,139 @@ impl<'a> Window<'a> {
     /// [`WindowHandler::mouse_leave`] event being emitted.
     #[inline]
     pub fn set_cursor_position(&self, pos: impl Into<Point>) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_cursor_position(pos.into());
     }
 
+    /// Hold the mouse cursor inside of the window, for widgets like knobs and
+    /// sliders that are dragged further than the edges of the screen allow.
+    ///
+    /// With [`CursorGrab::Confined`] the cursor can not leave the client
+    /// area. With [`CursorGrab::Locked`] it stays where it is (hide it with
+    /// [`MouseCursor::Hidden`]), and [`WindowHandler::mouse_move`] and
+    /// [`WindowHandler::mouse_press`] report a virtual position instead, which
+    /// keeps moving with the mouse past the edges of the window and the
+    /// screen. The difference between two such positions is the relative
+    /// motion of the mouse. [`CursorGrab::None`] lets go of the cursor, which
+    /// shows up again where it was locked.
+    ///
+    /// The grab ends on its own once the window loses the keyboard focus (see
+    /// [`WindowHandler::focus_changed`]), so it never traps the cursor in a
+    /// window the user switched away from. Windows get the focus when
+    /// clicked, so grabbing from [`WindowHandler::mouse_press`] is safe.
+    ///
+    /// # Errors
+    /// [`CursorGrabError::Unavailable`] if the window is not visible, or on
+    /// X11 if another application holds the mouse, and
+    /// [`CursorGrabError::Closed`] after [`Window::close`].
+    #[inline]
+    pub fn set_cursor_grab(&self, mode: CursorGrab) -> Result<(), CursorGrabError> {
+        if self.is_closed() {
+            return Err(CursorGrabError::Closed);
+        }
+
+        self.0.set_cursor_grab(mode)
+    }
+
+    /// Tell the input method where the text caret is, so its composition and
+    /// candidate windows show up next to the text being edited instead of in
+    /// the corner of the window.
+    ///
+    /// The area is in physical pixels relative to the client area, usually
+    /// the caret itself or the whole line being edited. Call again whenever
+    /// the caret moves.
+    ///
+    /// How much of it is used depends on the platform:
+    /// - **Windows**: the composition window starts at the top left corner, and
+    ///   the candidate window is placed below the area without covering it
+    ///   (`ImmSetCompositionWindow` and `ImmSetCandidateWindow`).
+    /// - **macOS**: the area is reported through `firstRectForCharacterRange:`,
+    ///   the input method places its windows next to it.
+    /// - **X11**: only the bottom left corner is passed on, as the spot
+    ///   location of the XIM input context. The composition is reported to the
+    ///   handler (or shown by the input method in a window of its own), so the
+    ///   spot is only a hint: input methods like IBus and Fcitx put their
+    ///   candidate window there, others ignore it.
+    #[inline]
+    pub fn set_ime_cursor_area(&self, area: Rect) {
+        if self.is_closed() {
+            return;
+        }
+
+        self.0.set_ime_cursor_area(area);
+    }
+
     /// Set the size of the client area in physical pixels.
     ///
+    /// The size is adjusted to the minimum and maximum size and the resize
+    /// hints of the window first, see [`ResizeHints::constrain`].
+    ///
     /// Will result in a [`WindowHandler::size_changed`] event being emitted.
     #[inline]
     pub fn set_size(&self, size: impl Into<Size>) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_size(size.into());
     }
 
     /// Sets the minimum size of the window's client area in physical pixels.
     ///
     /// Used to restrict the user from resizing the window below a certain size.
+    /// Embedded windows publish it to the host as well (`WM_NORMAL_HINTS` on
+    /// X11, `WM_GETMINMAXINFO` on Windows).
     #[inline]
     pub fn set_min_size(&self, min: impl Into<Size>) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_min_size(min.into());
     }
 
     /// Sets the maximum size of the window's client area in physical pixels.
     ///
     /// Used to restrict the user from resizing the window above a certain size.
+    /// Embedded windows publish it to the host as well, like
+    /// [`Window::set_min_size`].
     #[inline]
     pub fn set_max_size(&self, max: impl Into<Size>) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_max_size(max.into());
     }
 
+    /// Sets the resize constraints of the window (resizable axes, aspect
+    /// ratio and resize step).
+    ///
+    /// Axes that can not be resized are locked to the current size for the
+    /// user, but [`Window::set_size`] can still change them. The aspect ratio
+    /// and step are applied to both (see [`ResizeHints::constrain`]).
+    ///
+    /// Embedded views on macOS report the size of locked axes to the host as
+    /// their intrinsic content size.
+    #[inline]
+    pub fn set_resize_hints(&self, hints: ResizeHints) {
+        if self.is_closed() {
+            return;
+        }
+
+        self.0.set_resize_hints(hints);
+    }
+
+    /// Get the resize constraints last set with [`Window::set_resize_hints`].
+    #[must_use]
+    #[inline]
+    pub fn resize_hints(&self) -> ResizeHints {
+        self.0.resize_hints()
+    }
+
     /// Set the window position (position of client area) in physical pixels
     /// relative to the origin (top-left corner) of the coordinate system.
     ///
@@ -321,15 +848,30 @@ impl<'a> Window<'a> {
     ///   client area.
     ///
     /// If not specified, the window will be centered on the screen or parent
-    /// window (or positioned at (0, 0) if embedded)
+    /// window (or positioned at
+    /// [`WindowBuilder::with_parent_relative_position`] if embedded). Embedded
+    /// windows keep their top-left corner in place when resized.
     ///
     /// The coordinate system is X+ right, Y+ down
     ///
+    /// Pass [`Position::Centered`] (or just a [`MonitorSelector`]) to center
+    /// the window in the work area of a monitor instead, taking the current
+    /// size of the window and its decorations into account, so set the size
+    /// first. Embedded windows are centered in their parent, headless windows
+    /// are not moved.
+    ///
     /// Will result in a [`WindowHandler::position_changed`] event being
     /// emitted.
     #[inline]
-    pub fn set_position(&self, pos: impl Into<Point>) {
-        self.0.set_position(pos.into());
+    pub fn set_position(&self, pos: impl Into<Position>) {
+        if self.is_closed() {
+            return;
+        }
+
+        match pos.into() {
+            Position::At(point) => self.0.set_position(point),
+            Position::Centered(monitor) => self.0.center(monitor),
+        }
     }
 
     /// Set whether the window is visible.
@@ -338,43 +880,591 @@ impl<'a> Window<'a> {
     /// emitted.
     #[inline]
     pub fn set_visible(&self, visible: bool) {
+        if self.is_closed() {
+            return;
+        }
+
         self.0.set_visible(visible);
     }
 
     /// Open the given URL or file path in the system's default application.
     ///
-    /// Returns `true` if the action was handled by the OS
+    /// Any well formed URI scheme is accepted, including `mailto:` and custom
+    /// schemes registered by other applications; anything without a scheme is
+    /// treated as a file path. The application is launched on a worker thread,
+    /// so this never blocks the event loop.
+    ///
+    /// `on_done` is called exactly once: from the worker thread once the
+    /// application was launched or failed to, or before this returns if the URL
+    /// is rejected with [`OpenUrlError::InvalidUrl`]. Use a [`WindowWaker`] to
+    /// get back to the event loop from there.
     #[inline]
-    pub fn open_url(&self, url: &str) -> bool {
-        self.0.open_url(url)
+    pub fn open_url(
+        &self,
+        url: &str,
+        on_done: impl FnOnce(Result<(), OpenUrlError>) + Send + 'static,
+    ) {
+        self.0.open_url(url, Box::new(on_done));
     }
 
-    /// Set the current text contents of the system clipboard.
+    /// Open a top-level window owned by this one, like a floating tool window
+    /// or an about box. It stays on top of this window, is minimized with it,
+    /// and is closed along with it.
+    ///
+    /// Works like [`WindowBuilder::open_transient`] with this window as the
+    /// parent: the new window runs on this thread, from the event loop that
+    /// runs this window (the host's for embedded windows), without spawning a
+    /// thread of its own. The factory may run before this returns.
     ///
-    /// Returns `true` if the action was handled by the OS
+    /// # Errors
+    /// - [`WindowError::InvalidParent`] after [`Window::close`], and for
+    ///   headless windows.
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
+    /// - [`WindowError::Platform`] if a platform-specific error occurred.
+    /// - [`WindowError::Factory`] if the factory function returned an error.
+    pub fn open_child(&self, builder: WindowBuilder) -> Result<WindowWaker, WindowError> {
+        if self.is_closed() {
+            return Err(WindowError::InvalidParent);
+        }
+
+        builder.open_transient(self)
+    }
+
+    /// Open a popup, an undecorated window for menus, tooltips and such that
+    /// floats above this window, at
+    /// [`WindowBuilder::with_parent_relative_position`].
+    ///
+    /// Works like [`WindowBuilder::open_popup`] with this window as the parent
+    /// and an empty anchor at that position. Like a child window (see
+    /// [`Window::open_child`]) it runs on this thread and is closed along with
+    /// this window.
+    ///
+    /// # Errors
+    /// - [`WindowError::InvalidParent`] after [`Window::close`], and for
+    ///   headless windows.
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
+    /// - [`WindowError::Platform`] if a platform-specific error occurred.
+    /// - [`WindowError::Factory`] if the factory function returned an error.
+    pub fn open_popup(&self, builder: WindowBuilder) -> Result<WindowWaker, WindowError> {
+        if self.is_closed() {
+            return Err(WindowError::InvalidParent);
+        }
+
+        let (left, top) = builder.parent_relative_position.to_pixels();
+        let anchor = Rect {
+            top,
+            left,
+            bottom: top,
+            right: left,
+        };
+
+        builder.open_popup(self, anchor)
+    }
+
+    /// Replace the contents of the system clipboard, [`Exchange::Empty`]
+    /// clears it.
+    ///
+    /// # Errors
+    /// [`ClipboardError::AccessDenied`] if the clipboard is held by another
+    /// application or sandboxed by the host (try again from a key or mouse
+    /// press), [`ClipboardError::UnsupportedFormat`] if the data can not be
+    /// put on the clipboard (like a custom format with an empty name, or on
+    /// macOS a list of files none of which exist), and
+    /// [`ClipboardError::Closed`] after [`Window::close`].
     #[inline]
-    pub fn set_clipboard(&self, data: impl Into<Exchange>) -> bool {
-        self.0.set_clipboard(data.into())
+    pub fn set_clipboard(&self, data: impl Into<Exchange>) -> Result<(), ClipboardError> {
+        if self.is_closed() {
+            return Err(ClipboardError::Closed);
+        }
+
+        match data.into() {
+            Exchange::Custom { format, .. } if !platform::is_valid_format_name(&format) => {
+                Err(ClipboardError::UnsupportedFormat)
+            }
+            data => self.0.set_clipboard(data),
+        }
     }
 
-    /// Get the current text contents of the system clipboard, if any.
+    /// Get the current contents of the system clipboard, as files, text or an
+    /// image, whichever comes first. This is never [`Exchange::Empty`], an
+    /// empty clipboard is reported as an error.
+    ///
+    /// # Errors
+    /// [`ClipboardError::Empty`] if the clipboard holds nothing,
+    /// [`ClipboardError::UnsupportedFormat`] if it holds neither files, text
+    /// nor an image, and [`ClipboardError::AccessDenied`] and
+    /// [`ClipboardError::Closed`] as for [`Window::set_clipboard`].
+    #[inline]
+    pub fn get_clipboard(&self) -> Result<Exchange, ClipboardError> {
+        if self.is_closed() {
+            return Err(ClipboardError::Closed);
+        }
+
+        match self.0.get_clipboard(None)? {
+            Exchange::Empty => Err(ClipboardError::Empty),
+            exchange => Ok(exchange),
+        }
+    }
+
+    /// Get the current contents of the system clipboard in the given format,
+    /// for clipboards holding more than one (like an image along with the
+    /// name of its file) or data in a [`ClipboardFormat::Custom`] format.
+    ///
+    /// # Errors
+    /// [`ClipboardError::UnsupportedFormat`] if the clipboard does not hold
+    /// the format, otherwise as for [`Window::get_clipboard`].
+    #[inline]
+    pub fn get_clipboard_as(&self, format: ClipboardFormat) -> Result<Exchange, ClipboardError> {
+        if self.is_closed() {
+            return Err(ClipboardError::Closed);
+        }
+
+        if let ClipboardFormat::Custom(name) = &format
+            && !platform::is_valid_format_name(name)
+        {
+            return Err(ClipboardError::UnsupportedFormat);
+        }
+
+        match self.0.get_clipboard(Some(&format))? {
+            Exchange::Empty => Err(ClipboardError::Empty),
+            exchange => Ok(exchange),
+        }
+    }
+
+    /// Start dragging data out of the window, into the host, a file manager or
+    /// another window of this application (like a sample or a preset file).
+    ///
+    /// Only possible while a mouse button pressed inside of the window is
+    /// held, usually from [`WindowHandler::mouse_move`] once the cursor moved
+    /// far enough away from where the button was pressed. Once the current
+    /// event returns the system takes over the mouse: the held buttons are
+    /// reported as released, and no more mouse events arrive until the drag
+    /// ends with [`WindowHandler::drag_finished`].
+    ///
+    /// The data is only offered to be copied, so a drop target never moves
+    /// (deletes) the dragged files.
+    ///
+    /// # Errors
+    /// [`DragError::Empty`] if there is nothing to drag,
+    /// [`DragError::NoButtonHeld`] if no mouse button pressed inside of the
+    /// window is held, [`DragError::InProgress`] if the last drag did not
+    /// finish yet, and [`DragError::Unsupported`] for images and custom
+    /// formats (which only the clipboard supports) or if the host put the
+    /// thread into a state that does not allow drag and drop, and
+    /// [`DragError::Closed`] after [`Window::close`].
+    pub fn start_drag(&self, data: impl Into<Exchange>) -> Result<(), DragError> {
+        if self.is_closed() {
+            return Err(DragError::Closed);
+        }
+
+        match data.into() {
+            Exchange::Empty => Err(DragError::Empty),
+            Exchange::Files(files) if files.is_empty() => Err(DragError::Empty),
+            Exchange::Image(_) | Exchange::Custom { .. } => Err(DragError::Unsupported),
+            data => self.0.start_drag(data),
+        }
+    }
+
+    /// Let the system resize the window by the given edge or corner, following
+    /// the mouse until the button is released, for windows without
+    /// decorations that draw their own resize grips.
+    ///
+    /// Only possible while a mouse button pressed inside of the window is
+    /// held, usually from [`WindowHandler::mouse_press`] of the left button
+    /// on the grip. Like with [`Window::start_drag`] the system takes over the
+    /// mouse: the held buttons are reported as released, and no more mouse
+    /// events arrive until the resize ends. The new sizes are reported with
+    /// [`WindowHandler::size_changed`], within the minimum and maximum size
+    /// and the [`ResizeHints`].
+    ///
+    /// On X11 this needs a window manager that supports
+    /// `_NET_WM_MOVERESIZE`, others ignore the request.
+    ///
+    /// # Errors
+    /// [`DragError::NoButtonHeld`] if no mouse button pressed inside of the
+    /// window is held, [`DragError::Unsupported`] for embedded windows,
+    /// whose size is up to the host, and [`DragError::Closed`] after
+    /// [`Window::close`].
+    pub fn begin_drag_resize(&self, edge: ResizeEdge) -> Result<(), DragError> {
+        if self.is_closed() {
+            return Err(DragError::Closed);
+        }
+
+        self.0.begin_drag_resize(edge)
+    }
+
+    /// Check if the window currently captures the mouse.
+    ///
+    /// The mouse is captured implicitly while a button that was pressed inside
+    /// of the window is held: mouse events keep going to the window even if the
+    /// cursor leaves it, and [`WindowHandler::mouse_leave`] is delayed until
+    /// the buttons are released.
+    #[must_use]
+    #[inline]
+    pub fn has_mouse_capture(&self) -> bool {
+        self.0.has_mouse_capture()
+    }
+
+    /// Check if the cursor is held by [`Window::set_cursor_grab`], either
+    /// [`CursorGrab::Confined`] or [`CursorGrab::Locked`].
+    ///
+    /// Turns `false` once the grab ends on its own, when the window loses the
+    /// keyboard focus or is hidden.
     #[must_use]
     #[inline]
-    pub fn get_clipboard(&self) -> Exchange {
-        self.0.get_clipboard()
+    pub fn is_cursor_confined(&self) -> bool {
+        self.0.cursor_grab() != CursorGrab::None
+    }
+
+    /// Check if the window has the keyboard focus, and gets the key events.
+    ///
+    /// Follows [`WindowHandler::focus_changed`], so it is the state the
+    /// handler was last told about.
+    #[must_use]
+    #[inline]
+    pub fn has_keyboard_focus(&self) -> bool {
+        self.0.has_keyboard_focus()
+    }
+
+    /// Get the metadata of the most recent native input event (mouse, pen or
+    /// keyboard) received by the window.
+    ///
+    /// Useful for forwarding events to the host or calling platform APIs that
+    /// need the timestamp of the triggering event. Returns
+    /// [`EventToken::default`] if no input event was received yet.
+    #[must_use]
+    #[inline]
+    pub fn last_event_token(&self) -> EventToken {
+        self.0.last_event_token()
+    }
+
+    /// Get the insets from the edges of the client area to the area that is
+    /// not obscured by the system, in physical pixels.
+    ///
+    /// On macOS this is the safe area of the view, which avoids the camera
+    /// housing of notched displays in fullscreen and the title bar of windows
+    /// with a full-size content view. Always zero on other platforms.
+    #[must_use]
+    #[inline]
+    pub fn safe_area_insets(&self) -> Insets {
+        self.0.safe_area_insets()
+    }
+
+    /// Get the current size of the client area in physical pixels, as the
+    /// platform reports it. Size changes are compressed while the handler is
+    /// busy, so [`WindowHandler::size_changed`] can lag behind this until the
+    /// next event is delivered.
+    #[must_use]
+    #[inline]
+    pub fn inner_size(&self) -> Size {
+        self.0.inner_size()
+    }
+
+    /// Get the size of the frame the system draws around the client area (the
+    /// title bar and borders), in physical pixels.
+    ///
+    /// Always zero for embedded and undecorated windows. On X11 the frame is
+    /// drawn by the window manager, which reports it with `_NET_FRAME_EXTENTS`
+    /// once the window is mapped; this is zero before that, or if the window
+    /// manager does not support it.
+    #[must_use]
+    #[inline]
+    pub fn frame_insets(&self) -> Insets {
+        self.0.frame_insets()
+    }
+
+    /// Get the size of the window including its frame, in physical pixels.
+    /// See [`Self::inner_size`] and [`Self::frame_insets`].
+    #[must_use]
+    pub fn outer_size(&self) -> Size {
+        let size = self.inner_size();
+        let frame = self.frame_insets();
+        Size {
+            width: size
+                .width
+                .saturating_add(frame.left)
+                .saturating_add(frame.right),
+            height: size
+                .height
+                .saturating_add(frame.top)
+                .saturating_add(frame.bottom),
+        }
+    }
+
+    /// Get the identifier of the window, unique within the process. See
+    /// [`window_at`].
+    #[must_use]
+    #[inline]
+    pub fn id(&self) -> WindowId {
+        self.0.id()
+    }
+
+    /// Convert a point in physical pixels relative to the client area of the
+    /// window to screen coordinates, as used by [`window_at`].
+    ///
+    /// Screen coordinates are physical pixels with (0, 0) being the top-left
+    /// corner of the primary monitor. On macOS they are derived from the
+    /// backing scale of the primary screen.
+    #[must_use]
+    #[inline]
+    pub fn to_screen(&self, point: impl Into<Point>) -> Point {
+        self.0.to_screen(point.into())
+    }
+
+    /// Get how much the time between [`WindowHandler::frame`] calls varies,
+    /// smoothed over the last few frames.
+    ///
+    /// Frames are coalesced, so a handler that is too slow for the refresh
+    /// rate skips frames instead of falling behind, which shows up here. Zero
+    /// until a few frames were delivered, and always zero for headless
+    /// windows.
+    #[must_use]
+    #[inline]
+    pub fn frame_jitter(&self) -> Duration {
+        self.0.frame_stats().jitter
+    }
+
+    /// Get diagnostics of how events and frames reached the window, to find
+    /// out why a GUI feels sluggish.
+    ///
+    /// Events that arrive while the handler is busy (like during a modal drag
+    /// loop) wait in a queue of limited size. There, consecutive mouse moves
+    /// are merged, and a full queue first drops the oldest mouse move or size
+    /// change that a later one makes up for, then the oldest scroll, gesture,
+    /// timer tick or wakeup. Key and button events are only dropped once the
+    /// queue holds a few times more events. Both are counted here. Rare on
+    /// X11, which reads events from the server only once the handler returns,
+    /// so only the events a call into the window produces are queued.
+    #[must_use]
+    #[inline]
+    pub fn frame_stats(&self) -> FrameStats {
+        self.0.frame_stats()
+    }
+
+    /// Check whether `key` is held down.
+    ///
+    /// Follows the key events of the window, and is seeded with the keyboard
+    /// state of the system when the window gains focus, so keys pressed before
+    /// that are included. All keys count as released while the window is not
+    /// focused. On macOS only the modifier keys are seeded.
+    #[must_use]
+    #[inline]
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.0.is_key_down(key)
+    }
+
+    /// Get the scroll wheel settings of the system, read every time this is
+    /// called so changes made while the window is open are picked up.
+    ///
+    /// Windows reports `SPI_GETWHEELSCROLLLINES` and `SPI_GETWHEELSCROLLCHARS`,
+    /// macOS reports the natural scrolling setting. Everything else has no
+    /// system-wide setting and gets the defaults.
+    #[must_use]
+    #[inline]
+    pub fn scroll_settings(&self) -> ScrollSettings {
+        self.0.scroll_settings()
+    }
+
+    /// Dispatch a [`WindowHandler::frame`] right away instead of waiting for
+    /// the next display refresh, for things like capturing a screenshot or
+    /// rendering a thumbnail for the host.
+    ///
+    /// The frame is delivered like any other: the handler makes its
+    /// `GlContext` current (or draws to its surface) and presents it itself.
+    /// It is not counted towards [`Window::frame_jitter`] and does not shift
+    /// the regular frame schedule.
+    ///
+    /// Called from inside the handler, the frame can not be delivered
+    /// reentrantly, so it is dispatched as soon as the current event returns,
+    /// before control goes back to the event loop. Several calls during the
+    /// same event result in a single frame.
+    #[inline]
+    pub fn redraw_now(&self) {
+        if self.is_closed() {
+            return;
+        }
+
+        self.0.redraw_now();
+    }
+
+    /// Start a repeating timer that sends [`WindowHandler::timer`] with `id`
+    /// every `interval`, replacing the timer with the same `id` if there is
+    /// one.
+    ///
+    /// Meant for coarse periodic work, like polling parameters at 30 Hz,
+    /// that should not depend on the refresh rate of the display. Timers are
+    /// not precise: the first expiration is one interval from now, the
+    /// interval is at least 1 ms, and Windows rounds it up to 10 ms.
+    #[inline]
+    pub fn set_timer(&self, id: u32, interval: Duration) {
+        if self.is_closed() {
+            return;
+        }
+
+        self.0.set_timer(id, interval.max(Duration::from_millis(1)));
+    }
+
+    /// Stop the timer set with [`Window::set_timer`] with the given `id`, if
+    /// there is one.
+    #[inline]
+    pub fn cancel_timer(&self, id: u32) {
+        self.0.cancel_timer(id);
+    }
+
+    /// Copy pixels onto the window, for drawing in software without OpenGL or
+    /// another graphics API.
+    ///
+    /// `buffer` holds the rows of `size` from top to bottom, each pixel as
+    /// `0xAARRGGBB` with the color premultiplied by the alpha. The alpha is
+    /// only used by windows created with [`WindowBuilder::with_transparency`].
+    /// The buffer should be the size of the window, in physical pixels: a
+    /// smaller one leaves the rest of the window as it was, a bigger one is
+    /// cropped. macOS stretches it to fit the window instead.
+    ///
+    /// Only the `damage` regions of the buffer are copied, or all of it if
+    /// `damage` is empty. macOS always copies all of it.
+    ///
+    /// Usually called from [`WindowHandler::frame`], after drawing what
+    /// [`WindowHandler::damage`] asked for. The pixels are copied before this
+    /// returns, so the buffer can be drawn to again right away.
+    ///
+    /// Uses GDI on Windows, a `CALayer` on macOS and `XPutImage` on X11. X11
+    /// has no shared memory (MIT-SHM) path yet, every damaged pixel is sent
+    /// through the display connection, so keep the damage small there.
+    ///
+    /// # Errors
+    /// [`PresentError::BufferTooSmall`] if `buffer` holds fewer pixels than
+    /// `size` needs, [`PresentError::OpenGlInUse`] if the window has an OpenGL
+    /// context, [`PresentError::Unsupported`] if the X11 visual of the
+    /// window does not store pixels as 32-bit `0xAARRGGBB`, and
+    /// [`PresentError::Closed`] after [`Window::close`].
+    pub fn present_pixels(
+        &self,
+        buffer: &[u32],
+        size: Size,
+        damage: &[Rect],
+    ) -> Result<(), PresentError> {
+        if self.is_closed() {
+            return Err(PresentError::Closed);
+        }
+
+        let pixels = (size.width as usize).checked_mul(size.height as usize);
+        if pixels.is_none_or(|pixels| buffer.len() < pixels) {
+            return Err(PresentError::BufferTooSmall);
+        }
+
+        #[cfg(feature = "opengl")]
+        if self.0.opengl().is_ok() {
+            return Err(PresentError::OpenGlInUse);
+        }
+
+        let damage = platform::Damage::new(damage, size);
+        if damage.is_empty() {
+            return Ok(());
+        }
+
+        self.0.present_pixels(buffer, size, damage)
     }
 }
 
+/// Find the window opened by this process under the given point in screen
+/// coordinates, see [`Window::to_screen`].
+///
+/// Useful for drag and drop between windows of the same process (e.g. two
+/// editors of one plugin): the window that received the mouse press keeps
+/// getting mouse events while the button is held, and can convert them to
+/// screen coordinates to find the window they are over.
+///
+/// Returns `None` if the point is not over a window opened by this crate, or
+/// if it is covered by a window that does not belong to this process. On
+/// macOS windows can only be found from the main thread (where their handlers
+/// run). On X11 this opens a temporary display connection, so it should not
+/// be called more than once per event.
+#[must_use]
+pub fn window_at(point: impl Into<Point>) -> Option<WindowId> {
+    platform::find_window_at(point.into())
+}
+
+/// Query the metrics of the native controls, like the scrollbar width or the
+/// double click time, see [`SystemMetrics`].
+///
+/// Windows reports all of them (`GetSystemMetrics`, `GetDoubleClickTime` and
+/// `GetCaretBlinkTime`). macOS reports the scrollbar style and width, the
+/// double click time and the caret blink period from the user defaults. X11
+/// reports the `XSETTINGS` of the desktop (`Net/DoubleClickTime`,
+/// `Net/CursorBlink`, `Net/CursorBlinkTime` and `Net/DndDragThreshold`) if a
+/// settings daemon runs. Everything else gets the defaults.
+///
+/// Changes are reported with [`WindowHandler::system_metrics_changed`]. On
+/// macOS the scrollbars can only be queried from the main thread (where the
+/// handlers run). On X11 this asks the server through a display connection
+/// shared by all calls, which takes a round trip.
+#[must_use]
+pub fn system_metrics() -> SystemMetrics {
+    platform::query_system_metrics()
+}
+
 impl WindowWaker {
     /// Wake up the associated window in a fire-and-forget fashion (without
     /// waiting for the event handler to actually process the event). Emits a
     /// [`WindowHandler::wakeup`] call as soon as possible.
     ///
+    /// This function does not allocate and does not block on the event loop,
+    /// so it is safe to call from a realtime (audio) thread.
+    ///
     /// # Errors
     /// - [`WakeupError`] if the window has already been closed.
     pub fn wakeup(&self) -> Result<(), WakeupError> {
         self.0.wakeup()
     }
+
+    /// Turn the waker into a [`WindowGuard`] that closes the window when
+    /// dropped, and waits for it to close where possible.
+    ///
+    /// Useful for tearing down the UI before the plugin library is unloaded
+    /// (for example, when hot-reloading it during development), so no window
+    /// code runs after that.
+    pub fn into_guard(self) -> WindowGuard {
+        WindowGuard {
+            waker: self,
+            armed: true,
+        }
+    }
+}
+
+impl WindowGuard {
+    /// Get the [`WindowWaker`] of the guarded window.
+    #[must_use]
+    pub fn waker(&self) -> &WindowWaker {
+        &self.waker
+    }
+
+    /// Release the window without closing it.
+    #[must_use]
+    pub fn into_waker(mut self) -> WindowWaker {
+        self.armed = false;
+        self.waker.clone()
+    }
+}
+
+impl Drop for WindowGuard {
+    /// Close the window, and wait until it is closed and its handler is
+    /// dropped.
+    ///
+    /// The wait happens on X11 (the event loop thread is joined) and on
+    /// Windows (the window is destroyed on its thread, which must be pumping
+    /// messages, usually the host UI thread). On macOS the window is closed
+    /// right away when dropped on the main thread. In all other cases the
+    /// window is only asked to close, like with [`WindowGroup::close_all`].
+    ///
+    /// Dropping the guard from the window's own handler does not wait, and
+    /// neither does dropping it anywhere else on the window's thread on
+    /// Windows: the window closes once that thread pumps messages again.
+    fn drop(&mut self) {
+        if self.armed {
+            self.waker.0.close_and_wait();
+        }
+    }
 }
 
 impl WindowBuilder {
@@ -391,11 +1481,40 @@ impl WindowBuilder {
     ) -> Self {
         Self {
             transparent: false,
-            opengl: None,
+            #[cfg(feature = "opengl")]
+            opengl: Vec::new(),
+            #[cfg(feature = "opengl")]
+            external_opengl: None,
+            cursor: MouseCursor::Default,
+            pointer_input: false,
+            auto_dpi_resize: true,
+            platform_options: PlatformOptions::default(),
+            metal_layer: false,
+            max_poll_timeout: None,
+            fill_parent: false,
+            follow_parent: false,
+            initial_keyboard_focus: false,
+            parent_relative_position: Point::default(),
+            modifier_scroll: false,
+            raw_scroll: false,
+            unmatched_mouse_release: false,
+            geometry_compression: true,
+            watchdog: None,
+            group: None,
             factory: Box::new(factory),
         }
     }
 
+    /// Create a new [`WindowBuilder`] that delivers the events of the window
+    /// to `handler` and default parameters, see [`EventHandler`].
+    pub fn with_handler(handler: impl EventHandler) -> Self {
+        Self::new(move |window| {
+            let mut handler = handler;
+            handler.opened(window)?;
+            Ok(Box::new(EventAdapter::new(window, handler)))
+        })
+    }
+
     /// Set whether the window client area is transparent (premultiplied alpha)
     ///
     /// `false` by default
@@ -407,9 +1526,302 @@ impl WindowBuilder {
     }
 
     /// Set the OpenGL configuration for the window, if any
+    #[cfg(feature = "opengl")]
     pub fn with_opengl(self, config: GlConfig) -> Self {
         Self {
-            opengl: Some(config),
+            opengl: vec![config],
+            ..self
+        }
+    }
+
+    /// Set OpenGL configurations to try in order, the first one a context can
+    /// be created with is used. Which one that was is reported by
+    /// [`GlContext::version`].
+    ///
+    /// ```no_run
+    /// # use picoview::*;
+    /// # fn builder(builder: WindowBuilder) -> WindowBuilder {
+    /// builder.with_opengl_fallback_chain([
+    ///     GlVersion::Core(4, 1),
+    ///     GlVersion::Core(3, 3),
+    ///     GlVersion::Compat(2, 1),
+    /// ].map(|version| GlConfig { version, ..Default::default() }))
+    /// # }
+    /// ```
+    ///
+    /// Unlike with a single config, a driver that does not support the
+    /// requested version fails that config rather than handing out a legacy
+    /// context, only the last config in the chain may end up with one.
+    ///
+    /// On Windows a window gets a pixel format only once, so after a config
+    /// set it the later ones keep its [`GlConfig::format`] and only fall back
+    /// on the version and the debug flag.
+    #[cfg(feature = "opengl")]
+    pub fn with_opengl_fallback_chain(self, configs: impl IntoIterator<Item = GlConfig>) -> Self {
+        Self {
+            opengl: configs.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Use an OpenGL context provided by the host instead of creating one,
+    /// see [`ExternalGlContext`].
+    ///
+    /// The window does not create a context of its own, everything else
+    /// (including input) works as usual. Drawing is up to the handler and the
+    /// host, for example during [`WindowHandler::frame`] if the host keeps its
+    /// context current then.
+    #[cfg(feature = "opengl")]
+    pub fn with_external_opengl(self, context: ExternalGlContext) -> Self {
+        Self {
+            external_opengl: Some(context),
+            ..self
+        }
+    }
+
+    /// Set the cursor icon that is shown when hovering over the window, until
+    /// it is changed with [`Window::set_cursor_icon`]
+    ///
+    /// [`MouseCursor::Default`] by default
+    pub fn with_cursor(self, cursor: MouseCursor) -> Self {
+        Self { cursor, ..self }
+    }
+
+    /// Set whether mouse, pen and touch input is received through the unified
+    /// pointer input path (`WM_POINTER` messages) on Windows 8 and newer.
+    ///
+    /// This enables `EnableMouseInPointer` for the whole process, which can not
+    /// be undone, so only use it if you own the process or know the host
+    /// does not mind. Older versions of Windows and other platforms ignore
+    /// this option.
+    ///
+    /// `false` by default
+    pub fn with_pointer_input(self, pointer_input: bool) -> Self {
+        Self {
+            pointer_input,
+            ..self
+        }
+    }
+
+    /// Set whether the window is resized to the size the system suggests when
+    /// it is dragged to a monitor with a different scale, so it keeps its size
+    /// in logical units.
+    ///
+    /// The new size and [`WindowHandler::scale_changed`] are delivered
+    /// together, the handler never sees the new scale with the old size or
+    /// the other way around. Disable this to keep the physical size and
+    /// resize the window yourself. Embedded windows are never resized, that
+    /// is up to the host. Other platforms ignore this option.
+    ///
+    /// `true` by default
+    pub fn with_auto_dpi_resize(self, auto_dpi_resize: bool) -> Self {
+        Self {
+            auto_dpi_resize,
+            ..self
+        }
+    }
+
+    /// Set the window class, styles or autoresizing mask the native window is
+    /// created with, for hosts that probe them or need them for tab order
+    /// and layout, see [`PlatformOptions`].
+    ///
+    /// The options are validated when the window is opened.
+    ///
+    /// [`PlatformOptions::default`] by default
+    pub fn with_platform_options(self, platform_options: PlatformOptions) -> Self {
+        Self {
+            platform_options,
+            ..self
+        }
+    }
+
+    /// Set whether a `CAMetalLayer` is attached to the window on macOS, for
+    /// renderers that take a layer instead of a view. See
+    /// [`Window::metal_layer`].
+    ///
+    /// Do not combine it with OpenGL, the layer is drawn on top of the
+    /// context. Other platforms ignore this option.
+    ///
+    /// `false` by default
+    pub fn with_metal_layer(self, metal_layer: bool) -> Self {
+        Self {
+            metal_layer,
+            ..self
+        }
+    }
+
+    /// Set the longest time the event loop waits for events before waking up
+    /// and calling [`WindowHandler::idle`].
+    ///
+    /// Useful for hosts that need the loop to yield periodically to do their
+    /// own work on the same thread. Only supported on X11, other platforms
+    /// ignore this option.
+    ///
+    /// `None` by default (wait until the next event or frame)
+    pub fn with_max_poll_timeout(self, timeout: Duration) -> Self {
+        Self {
+            max_poll_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Set whether an embedded window is resized to fill its parent window
+    /// whenever the parent is resized.
+    ///
+    /// Some hosts resize the window they give to the plugin without telling
+    /// the plugin window. Only supported on X11, other platforms ignore this
+    /// option.
+    ///
+    /// `false` by default
+    pub fn with_fill_parent(self, fill_parent: bool) -> Self {
+        Self {
+            fill_parent,
+            ..self
+        }
+    }
+
+    /// Set whether a transient window or a popup moves along with the
+    /// top-level window of its parent, keeping its place relative to it.
+    ///
+    /// Useful for floating tool windows that belong to a spot of the host
+    /// window. Regardless of this option they are hidden while the parent is
+    /// (like when it is minimized), and shown again with it. On macOS they
+    /// always move along, this option is ignored there. Other windows ignore
+    /// this option.
+    ///
+    /// On Windows the parent has to belong to the thread the window is opened
+    /// on, as hosts do when opening editors from their UI thread. A window
+    /// whose parent runs on another thread does not move along.
+    ///
+    /// `false` by default
+    pub fn with_follow_parent(self, follow_parent: bool) -> Self {
+        Self {
+            follow_parent,
+            ..self
+        }
+    }
+
+    /// Set whether an embedded window takes the keyboard focus as soon as it
+    /// is opened.
+    ///
+    /// Some hosts expect the plugin editor to be ready for typing right away,
+    /// others want to keep the focus themselves. Without it, an embedded
+    /// window only takes the focus when it is clicked. Top-level windows are
+    /// focused by the system and ignore this option.
+    ///
+    /// `false` by default
+    pub fn with_initial_keyboard_focus(self, initial_keyboard_focus: bool) -> Self {
+        Self {
+            initial_keyboard_focus,
+            ..self
+        }
+    }
+
+    /// Set the initial position of an embedded window or a popup opened with
+    /// [`Window::open_popup`], in physical pixels relative to the top-left
+    /// corner of the parent window's client area.
+    ///
+    /// Useful when the host draws something of its own above the plugin
+    /// editor, like a header. Embedded windows are placed there before the
+    /// factory is called, which can still move them with
+    /// [`Window::set_position`]. Top-level and transient windows ignore this
+    /// option, and so do popups opened with [`WindowBuilder::open_popup`].
+    ///
+    /// `(0, 0)` by default
+    pub fn with_parent_relative_position(self, position: impl Into<Point>) -> Self {
+        Self {
+            parent_relative_position: position.into(),
+            ..self
+        }
+    }
+
+    /// Set whether scroll events follow the common host conventions for
+    /// modifiers: with Shift held, vertical scrolling is turned into
+    /// horizontal scrolling, and with Ctrl held (Command on macOS) it is
+    /// turned into [`WindowHandler::gesture_zoom`] events instead of
+    /// [`WindowHandler::mouse_scroll`], zooming by 10% per step.
+    ///
+    /// `false` by default
+    pub fn with_modifier_scroll(self, modifier_scroll: bool) -> Self {
+        Self {
+            modifier_scroll,
+            ..self
+        }
+    }
+
+    /// Set whether [`WindowHandler::mouse_scroll`] reports scroll deltas in
+    /// the unit the platform uses, with its acceleration applied, instead of
+    /// normalizing them to wheel notches. See [`ScrollDelta`] for the units.
+    ///
+    /// `false` by default
+    pub fn with_raw_scroll(self, raw_scroll: bool) -> Self {
+        Self { raw_scroll, ..self }
+    }
+
+    /// Set whether [`WindowHandler::mouse_press`] reports the release of a
+    /// button that was pressed outside of the window.
+    ///
+    /// By default a release is only delivered for a button whose press was
+    /// delivered too, so the handler never sees a release it can not pair up.
+    /// The other way around holds regardless of this option: a button pressed
+    /// inside of the window is always released, even if the mouse is released
+    /// outside of it or something else takes over the mouse mid-drag.
+    ///
+    /// Only Windows reports such releases in the first place, other platforms
+    /// send them to the window the button was pressed in.
+    ///
+    /// `false` by default
+    pub fn with_unmatched_mouse_release(self, unmatched_mouse_release: bool) -> Self {
+        Self {
+            unmatched_mouse_release,
+            ..self
+        }
+    }
+
+    /// Set whether [`WindowHandler::size_changed`] and
+    /// [`WindowHandler::position_changed`] are compressed: held back until
+    /// the next frame (or any other event), and delivered once with the
+    /// latest value.
+    ///
+    /// Moving or resizing a window interactively sends hundreds of these per
+    /// second, more than a handler that lays out its UI on every change can
+    /// keep up with. They are never held back past another event, so the
+    /// handler still sees every change before the frame or input that
+    /// follows it. [`Window::inner_size`] is up to date regardless.
+    ///
+    /// `true` by default
+    pub fn with_geometry_compression(self, geometry_compression: bool) -> Self {
+        Self {
+            geometry_compression,
+            ..self
+        }
+    }
+
+    /// Measure how long the handler takes to handle each event, and send
+    /// [`WindowHandler::stalled`] if it takes `threshold` or longer.
+    ///
+    /// Useful to find out why the host UI appears to hang (file IO on the UI
+    /// thread and such).
+    ///
+    /// `None` by default (no measurements)
+    pub fn with_watchdog(self, threshold: Duration) -> Self {
+        Self {
+            watchdog: Some(threshold),
+            ..self
+        }
+    }
+
+    /// Add the window to the given [`WindowGroup`] when it is opened.
+    ///
+    /// A top-level window (see `WindowBuilder::open_blocking`) is owned by
+    /// the group leader if there is one. Transient and embedded windows keep
+    /// their parent, but are still listed in the group and closed by
+    /// [`WindowGroup::close_all`].
+    ///
+    /// `None` by default
+    pub fn with_group(self, group: WindowGroup) -> Self {
+        Self {
+            group: Some(group),
             ..self
         }
     }
@@ -419,43 +1831,110 @@ impl WindowBuilder {
     /// Returns `Err` if the window could not be created or if an error occurred
     /// during the lifetime of the window.
     ///
+    /// A shorthand for an [`EventLoop`] with a single window. Windows opened
+    /// from the handler with [`Window::open_child`] and such are run by the
+    /// same loop, this returns once all of them are closed.
+    ///
     /// # Errors
+    /// - [`WindowError::EventLoopRunning`] if called from a window handler,
+    ///   use [`Window::open_child`] there.
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
     /// - [`WindowError::Factory`] if the factory function returned an error.
     /// - [`WindowError::Platform`] if a platform-specific error occurred.
+    #[cfg(feature = "standalone")]
     pub fn open_blocking(self) -> Result<(), WindowError> {
-        unsafe { platform::open_window(self, platform::OpenMode::Blocking).map(|_| ()) }
+        if platform::is_event_loop_running() {
+            return Err(WindowError::EventLoopRunning);
+        }
+
+        let event_loop = EventLoop::new();
+        event_loop.open(self)?;
+        event_loop.run()
+    }
+
+    /// Open a window that exists only in memory, for testing handlers without
+    /// a display server. See [`HeadlessWindow`].
+    ///
+    /// # Errors
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
+    /// - [`WindowError::Factory`] if the factory function returned an error.
+    #[cfg(feature = "headless")]
+    pub fn open_headless(self) -> Result<HeadlessWindow, WindowError> {
+        self.platform_options.validate()?;
+
+        HeadlessWindow::open(self.prepare())
     }
 
     /// Open a transient window attached to the given parent window. Unlike
-    /// [`WindowBuilder::open_blocking`] this function does not block, this is
+    /// `WindowBuilder::open_blocking` this function does not block, this is
     /// achieved by hooking into the parent's OS event loop.
     ///
     /// A transient window is a window that can be moved independently of its
     /// parent window (like a popup or a dialog) and does not get clipped by it.
     /// It is always on top of its parent window and is hidden with the parent
-    /// window.
+    /// window, see [`WindowBuilder::with_follow_parent`] to move along with it
+    /// as well.
     ///
     /// If the parent window is closed, the transient window will also be
     /// closed, even without explicitly calling [`Window::close`].
     ///
     /// # Errors
     /// - [`WindowError::InvalidParent`] if the parent window handle is invalid.
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
     /// - [`WindowError::Platform`] if a platform-specific error occurred.
     /// - [`WindowError::Factory`] if the factory function returned an error.
     pub fn open_transient<W>(self, parent: W) -> Result<WindowWaker, WindowError>
     where
         W: rwh_06::HasWindowHandle,
     {
+        self.platform_options.validate()?;
+
         let handle = parent
             .window_handle()
             .map_err(|_| WindowError::InvalidParent)?
             .as_raw();
 
-        unsafe { platform::open_window(self, platform::OpenMode::Transient(handle)) }
+        unsafe { platform::open_window(self.prepare(), platform::OpenMode::Transient(handle)) }
+    }
+
+    /// Open a popup for the given parent window, an undecorated window for
+    /// menus, tooltips and such that floats above it. Like a transient window
+    /// (see [`WindowBuilder::open_transient`]) it does not block, and is closed
+    /// along with the parent.
+    ///
+    /// The window manager leaves popups alone: they have no title bar, do not
+    /// show up in the taskbar, and are placed exactly where asked. The popup
+    /// opens right below `anchor`, a rectangle in physical pixels relative to
+    /// the client area of the parent (like the button that opened a menu):
+    /// the top-left corner of the popup is the bottom-left corner of the
+    /// anchor. Moving it later with [`Window::set_position`] takes screen
+    /// coordinates, see [`Window::to_screen`].
+    ///
+    /// On X11 and macOS popups never get the keyboard focus, keys keep going
+    /// to the parent. The popup closes itself once the parent loses the focus,
+    /// see [`WindowHandler::popup_dismissed`].
+    ///
+    /// # Errors
+    /// - [`WindowError::InvalidParent`] if the parent window handle is invalid.
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
+    /// - [`WindowError::Platform`] if a platform-specific error occurred.
+    /// - [`WindowError::Factory`] if the factory function returned an error.
+    pub fn open_popup<W>(self, parent: W, anchor: Rect) -> Result<WindowWaker, WindowError>
+    where
+        W: rwh_06::HasWindowHandle,
+    {
+        self.platform_options.validate()?;
+
+        let handle = parent
+            .window_handle()
+            .map_err(|_| WindowError::InvalidParent)?
+            .as_raw();
+
+        unsafe { platform::open_window(self.prepare(), platform::OpenMode::Popup(handle, anchor)) }
     }
 
     /// Open an embedded window attached to the given parent window. Unlike
-    /// [`WindowBuilder::open_blocking`] this function does not block, this is
+    /// `WindowBuilder::open_blocking` this function does not block, this is
     /// achieved by hooking into the parent's OS event loop.
     ///
     /// It is used for embedding a window inside another window (for example,
@@ -467,18 +1946,57 @@ impl WindowBuilder {
     ///
     /// # Errors
     /// - [`WindowError::InvalidParent`] if the parent window handle is invalid.
+    /// - [`WindowError::InvalidOptions`] if the platform options are invalid.
     /// - [`WindowError::Platform`] if a platform-specific error occurred.
     /// - [`WindowError::Factory`] if the factory function returned an error.
     pub fn open_embedded<W>(self, parent: W) -> Result<WindowWaker, WindowError>
     where
         W: rwh_06::HasWindowHandle,
     {
+        self.platform_options.validate()?;
+
         let handle = parent
             .window_handle()
             .map_err(|_| WindowError::InvalidParent)?
             .as_raw();
 
-        unsafe { platform::open_window(self, platform::OpenMode::Embedded(handle)) }
+        unsafe { platform::open_window(self.prepare(), platform::OpenMode::Embedded(handle)) }
+    }
+
+    /// Apply the options that are implemented on top of the handler, so the
+    /// backends do not have to know about them.
+    pub(crate) fn prepare(mut self) -> Self {
+        // an external context replaces our own
+        #[cfg(feature = "opengl")]
+        if self.external_opengl.is_some() {
+            self.opengl.clear();
+        }
+
+        if self.modifier_scroll {
+            let factory = self.factory;
+            self = Self {
+                factory: Box::new(move |window| {
+                    let handler = factory(window)?;
+                    Ok(Box::new(ModifierScroll::new(handler)))
+                }),
+                modifier_scroll: false,
+                ..self
+            };
+        }
+
+        if self.geometry_compression {
+            let factory = self.factory;
+            self = Self {
+                factory: Box::new(move |window| {
+                    let handler = factory(window)?;
+                    Ok(Box::new(GeometryCompression::new(handler)))
+                }),
+                geometry_compression: false,
+                ..self
+            };
+        }
+
+        self
     }
 }
 
@@ -504,10 +2022,15 @@ impl<'a> Debug for Window<'a> {
 
 impl Debug for WindowBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("WindowBuilder")
-            .field("transparent", &self.transparent)
-            .field("opengl", &self.opengl)
-            .finish_non_exhaustive()
+        let mut f = f.debug_struct("WindowBuilder");
+        f.field("transparent", &self.transparent);
+        #[cfg(feature = "opengl")]
+        f.field("opengl", &self.opengl);
+        #[cfg(feature = "opengl")]
+        f.field("external_opengl", &self.external_opengl);
+        f.field("metal_layer", &self.metal_layer);
+        f.field("platform_options", &self.platform_options);
+        f.finish_non_exhaustive()
     }
 }
 
@@ -0,0 +1,82 @@
+use picoview::{Point, Rect, Size};
+
+/// Coordinates around the edges of the ranges platform APIs use.
+const EDGES: [i32; 13] = [
+    i32::MIN,
+    i32::MIN + 1,
+    -65536,
+    -32769,
+    -32768,
+    -1,
+    0,
+    1,
+    32767,
+    32768,
+    65536,
+    i32::MAX - 1,
+    i32::MAX,
+];
+
+#[test]
+fn test_point_to_pixels() {
+    for x in EDGES {
+        for y in EDGES {
+            let point = Point::from((x, y));
+            assert_eq!(point.to_pixels(), (x, y), "{point:?} should round trip");
+        }
+    }
+
+    assert_eq!(Point { x: -0.6, y: 0.6 }.to_pixels(), (-1, 1));
+    assert_eq!(Point { x: -1.5, y: 1.5 }.to_pixels(), (-2, 2));
+    assert_eq!(
+        Point { x: -1e12, y: 1e12 }.to_pixels(),
+        (i32::MIN, i32::MAX)
+    );
+    assert_eq!(
+        Point {
+            x: f64::NAN,
+            y: f64::INFINITY
+        }
+        .to_pixels(),
+        (0, i32::MAX)
+    );
+}
+
+#[test]
+fn test_rect_size() {
+    for left in EDGES {
+        for right in EDGES {
+            let rect = Rect {
+                top: left,
+                left,
+                bottom: right,
+                right,
+            };
+
+            let expected = (i64::from(right) - i64::from(left)).clamp(0, u32::MAX.into());
+            let expected = u32::try_from(expected).unwrap();
+            assert_eq!(rect.size(), Size::from((expected, expected)), "{rect:?}");
+        }
+    }
+}
+
+#[test]
+fn test_rect_from_size() {
+    for size in [0, 1, 32768, i32::MAX as u32, i32::MAX as u32 + 1, u32::MAX] {
+        let rect = Rect::from_size(Size::from((size, size)));
+        let expected = size.min(i32::MAX as u32);
+        assert_eq!(rect.size(), Size::from((expected, expected)), "{size}");
+    }
+
+    for x in EDGES {
+        let rect = Rect::from_xywh(x, x, u32::MAX, 0);
+        assert!(rect.right >= rect.left, "{rect:?} should saturate");
+        assert_eq!(rect.origin(), Point::from((x, x)));
+
+        for offset in EDGES {
+            let moved = rect.offset(offset, offset);
+            assert!(moved.right >= moved.left, "{moved:?} should saturate");
+            assert!(moved.size().width <= rect.size().width, "{moved:?}");
+        }
+    }
+}
@@ -0,0 +1,1785 @@
+use picoview::{
+    ClipboardError, ClipboardFormat, CursorGrab, CursorGrabError, DestroyReason, DragError,
+    DropEffect, Event, EventHandler, Exchange, FrameStats, HeadlessWindow, IccProfile, Image, Key,
+    Modifiers, MonitorId, MouseButton, MouseCursor, OpenUrlError, PlatformOptions, Point,
+    PresentError, Rect, ResizeEdge, Size, Window, WindowBuilder, WindowError, WindowHandler,
+};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Records every event it gets, closes itself on Escape.
+struct Recorder<'a> {
+    window: Window<'a>,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl WindowHandler for Recorder<'_> {
+    fn frame(&mut self) {
+        self.push(Event::Frame);
+    }
+
+    fn wakeup(&mut self) {
+        self.push(Event::Wakeup);
+    }
+
+    fn timer(&mut self, id: u32) {
+        self.push(Event::Timer { id });
+    }
+
+    fn size_changed(&mut self, size: Size) {
+        self.push(Event::SizeChanged(size));
+    }
+
+    fn position_changed(&mut self, position: Point) {
+        self.push(Event::PositionChanged(position));
+    }
+
+    fn mouse_press(&mut self, button: MouseButton, pressed: bool, point: Point) {
+        self.push(Event::MousePress {
+            button,
+            pressed,
+            position: point,
+        });
+
+        if button == MouseButton::Right && pressed {
+            self.window.set_size((300, 100));
+        }
+    }
+
+    fn mouse_scroll(&mut self, x: f64, y: f64) {
+        self.push(Event::MouseScroll { x, y });
+    }
+
+    fn gesture_zoom(&mut self, scale: f64) {
+        self.push(Event::GestureZoom(scale));
+    }
+
+    fn key_press(&mut self, key: Key, pressed: bool, is_repeat: bool, text: Option<char>) -> bool {
+        self.push(Event::KeyPress {
+            key,
+            pressed,
+            is_repeat,
+            text,
+        });
+
+        if key == Key::Escape {
+            self.window.close();
+        }
+
+        key == Key::Space
+    }
+
+    fn monitor_changed(&mut self, monitor: MonitorId) {
+        self.push(Event::MonitorChanged {
+            monitor_id: monitor,
+        });
+    }
+
+    fn system_metrics_changed(&mut self) {
+        self.push(Event::SystemMetricsChanged);
+    }
+
+    fn popup_dismissed(&mut self) {
+        self.push(Event::PopupDismissed);
+    }
+
+    fn destroyed(&mut self, reason: DestroyReason) {
+        self.push(Event::Destroyed(reason));
+    }
+}
+
+impl Recorder<'_> {
+    fn push(&self, event: Event) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+/// Takes the events recorded so far.
+fn take(events: &Mutex<Vec<Event>>) -> Vec<Event> {
+    std::mem::take(&mut events.lock().unwrap())
+}
+
+fn open() -> (HeadlessWindow, Arc<Mutex<Vec<Event>>>) {
+    open_with(|builder| builder)
+}
+
+fn open_with(
+    configure: impl FnOnce(WindowBuilder) -> WindowBuilder,
+) -> (HeadlessWindow, Arc<Mutex<Vec<Event>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+
+    let builder = WindowBuilder::new(move |window| {
+        window.set_title("headless");
+        window.set_size((200, 100));
+        window.set_visible(true);
+
+        Ok(Box::new(Recorder {
+            window,
+            events: recorded,
+        }))
+    });
+
+    // most tests check exactly when events arrive, see
+    // test_headless_geometry_compression
+    let builder = builder.with_geometry_compression(false);
+    let window = configure(builder)
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    (window, events)
+}
+
+#[test]
+fn test_headless_state() {
+    let (window, events) = open();
+
+    assert_eq!(window.title(), "headless");
+    assert!(window.is_visible());
+    assert_eq!(window.size(), Size::from((200, 100)));
+    assert_eq!(
+        take(&events),
+        [Event::SizeChanged(Size::from((200, 100)))],
+        "the initial size is delivered after the factory"
+    );
+
+    window.dispatch(Event::MousePress {
+        button: MouseButton::Right,
+        pressed: true,
+        position: Point { x: 5.0, y: 6.0 },
+    });
+    assert_eq!(window.size(), Size::from((300, 100)));
+    assert_eq!(window.cursor_position(), Some(Point { x: 5.0, y: 6.0 }));
+    assert_eq!(
+        take(&events),
+        [
+            Event::MousePress {
+                button: MouseButton::Right,
+                pressed: true,
+                position: Point { x: 5.0, y: 6.0 },
+            },
+            Event::SizeChanged(Size::from((300, 100))),
+        ]
+    );
+
+    window.dispatch(Event::MouseMove(Point { x: 10.0, y: 20.0 }));
+    assert_eq!(window.cursor_position(), Some(Point { x: 10.0, y: 20.0 }));
+}
+
+#[test]
+fn test_headless_geometry_compression() {
+    let (window, events) = open_with(|builder| builder.with_geometry_compression(true));
+    assert!(
+        take(&events).is_empty(),
+        "the initial size waits for a frame"
+    );
+
+    for width in [250, 300, 350] {
+        window.dispatch(Event::SizeChanged(Size::from((width, 100))));
+        window.dispatch(Event::PositionChanged(Point {
+            x: width as f64,
+            y: 0.0,
+        }));
+    }
+    assert_eq!(
+        window.size(),
+        Size::from((350, 100)),
+        "the state is current"
+    );
+    assert!(take(&events).is_empty());
+
+    window.dispatch(Event::Frame);
+    assert_eq!(
+        take(&events),
+        [
+            Event::SizeChanged(Size::from((350, 100))),
+            Event::PositionChanged(Point { x: 350.0, y: 0.0 }),
+            Event::Frame,
+        ],
+        "only the latest changes, before the frame"
+    );
+
+    // any other event delivers them as well, keeping the order
+    window.dispatch(Event::PositionChanged(Point { x: 1.0, y: 2.0 }));
+    window.dispatch(Event::MouseScroll { x: 0.0, y: 1.0 });
+    assert_eq!(
+        take(&events),
+        [
+            Event::PositionChanged(Point { x: 1.0, y: 2.0 }),
+            Event::MouseScroll { x: 0.0, y: 1.0 },
+        ]
+    );
+}
+
+#[test]
+fn test_headless_clock() {
+    let (window, events) = open();
+    take(&events);
+
+    window.advance(Duration::from_millis(10));
+    assert!(take(&events).is_empty());
+
+    window.advance(Duration::from_millis(50));
+    assert_eq!(window.now(), Duration::from_millis(60));
+    assert_eq!(take(&events), [Event::Frame, Event::Frame, Event::Frame]);
+
+    let waker = window.waker();
+    waker.wakeup().expect("failed to wake up");
+    assert!(take(&events).is_empty(), "wakeups wait for the clock");
+
+    window.advance(Duration::ZERO);
+    assert_eq!(take(&events), [Event::Wakeup]);
+}
+
+#[test]
+fn test_headless_close() {
+    let (window, events) = open();
+
+    assert!(window.dispatch(Event::KeyPress {
+        key: Key::Space,
+        pressed: true,
+        is_repeat: false,
+        text: Some(' '),
+    }));
+    assert_eq!(window.last_event_token().serial, 1);
+
+    window.dispatch(Event::KeyPress {
+        key: Key::Escape,
+        pressed: true,
+        is_repeat: false,
+        text: None,
+    });
+    assert!(window.is_closed());
+    assert!(window.waker().wakeup().is_err());
+    assert_eq!(
+        take(&events).last(),
+        Some(&Event::Destroyed(DestroyReason::Closed)),
+        "the handler is told last, once the close went through"
+    );
+
+    window.dispatch(Event::Frame);
+    window.advance(Duration::from_secs(1));
+    assert!(take(&events).is_empty(), "closed windows get no events");
+}
+
+#[test]
+fn test_headless_modifier_scroll() {
+    let (window, events) = open_with(|builder| builder.with_modifier_scroll(true));
+    take(&events);
+
+    let mut modifiers = Modifiers::default();
+    window.dispatch(Event::MouseScroll { x: 0.0, y: 1.0 });
+
+    modifiers.shift = true;
+    window.dispatch(Event::KeyModifiers(modifiers));
+    window.dispatch(Event::MouseScroll { x: 0.0, y: 1.0 });
+
+    modifiers.ctrl = true;
+    window.dispatch(Event::KeyModifiers(modifiers));
+    window.dispatch(Event::MouseScroll { x: 0.0, y: -1.0 });
+
+    assert_eq!(
+        take(&events),
+        [
+            Event::MouseScroll { x: 0.0, y: 1.0 },
+            Event::MouseScroll { x: 1.0, y: 0.0 },
+            Event::GestureZoom(1.0 / 1.1),
+        ]
+    );
+}
+
+#[test]
+fn test_headless_monitor_changed() {
+    // forwarded through the scroll adapter as well
+    let (window, events) = open_with(|builder| builder.with_modifier_scroll(true));
+    take(&events);
+
+    let monitor_id = MonitorId::from_raw(0x2a);
+    assert_eq!(monitor_id.get(), 0x2a);
+
+    window.dispatch(Event::MonitorChanged { monitor_id });
+    assert_eq!(take(&events), [Event::MonitorChanged { monitor_id }]);
+}
+
+#[test]
+fn test_headless_system_metrics() {
+    // forwarded through both adapters
+    let (window, events) = open_with(|builder| {
+        builder
+            .with_geometry_compression(true)
+            .with_modifier_scroll(true)
+    });
+    window.advance(Duration::from_millis(20));
+    take(&events);
+
+    window.dispatch(Event::SystemMetricsChanged);
+    assert_eq!(take(&events), [Event::SystemMetricsChanged]);
+
+    // without a desktop (or a backend) these are the defaults
+    let metrics = picoview::system_metrics();
+    assert!(metrics.scrollbar_width > 0.0);
+    assert!(metrics.drag_threshold > 0.0);
+    assert!(metrics.double_click_time > Duration::ZERO);
+}
+
+#[test]
+fn test_headless_color_profile() {
+    /// Queries the profile whenever it changes.
+    struct Handler(Arc<Mutex<Vec<Option<IccProfile>>>>);
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if event == Event::ColorProfileChanged {
+                self.0.lock().unwrap().push(window.color_profile());
+            }
+        }
+    }
+
+    let profiles = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler(profiles.clone()))
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    let profile = IccProfile::new(vec![0u8; 128]);
+    assert_eq!(profile.as_bytes().len(), 128);
+
+    window.set_color_profile(Some(profile.clone()));
+    window.set_color_profile(Some(profile.clone()));
+    window.set_color_profile(None);
+    assert_eq!(*profiles.lock().unwrap(), [Some(profile), None]);
+}
+
+#[test]
+fn test_headless_metal_layer() {
+    // only created on macOS, even when requested
+    for requested in [false, true] {
+        let layer = Arc::new(Mutex::new(None));
+        let recorded = layer.clone();
+
+        let window = WindowBuilder::new(move |window| {
+            *recorded.lock().unwrap() = Some(window.metal_layer().is_some());
+            Ok(Box::new(()))
+        })
+        .with_metal_layer(requested)
+        .open_headless()
+        .expect("failed to open a headless window");
+
+        assert!(!window.is_closed());
+        assert_eq!(
+            *layer.lock().unwrap(),
+            Some(false),
+            "requested: {requested}"
+        );
+    }
+}
+
+#[test]
+fn test_headless_frame_stats() {
+    /// Reads the stats on every frame.
+    struct Handler(Arc<Mutex<Vec<FrameStats>>>);
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if event == Event::Frame {
+                self.0.lock().unwrap().push(window.frame_stats());
+            }
+        }
+    }
+
+    let stats = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler(stats.clone()))
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    window.dispatch(Event::MouseMove(Point { x: 1.0, y: 1.0 }));
+    window.dispatch(Event::MouseMove(Point { x: 2.0, y: 2.0 }));
+    window.advance(Duration::from_millis(100));
+
+    // nothing queues up behind a handler that keeps up, on the virtual clock
+    let stats = stats.lock().unwrap();
+    assert!(!stats.is_empty());
+    assert!(stats.iter().all(|stats| *stats == FrameStats::default()));
+}
+
+#[test]
+fn test_headless_guard() {
+    let (window, _) = open();
+
+    let guard = window.waker().into_guard();
+    let waker = guard.into_waker();
+    assert!(!window.is_closed(), "released guards keep the window open");
+
+    drop(waker.into_guard());
+    window.advance(Duration::ZERO);
+    assert!(window.is_closed());
+}
+
+#[test]
+fn test_headless_id() {
+    let (first, _) = open();
+    let (second, _) = open();
+
+    assert_ne!(first.id(), second.id());
+    assert_eq!(
+        picoview::window_at((0, 0)),
+        None,
+        "headless windows are not on any screen"
+    );
+}
+
+#[test]
+fn test_headless_event_handler() {
+    struct Handler {
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn opened(&mut self, window: Window<'_>) -> Result<(), Box<dyn Error + Send + Sync>> {
+            window.set_size((200, 100));
+            Ok(())
+        }
+
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if event == Event::CloseRequested {
+                window.close();
+            }
+
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        events: events.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    assert_eq!(window.size(), Size::from((200, 100)));
+    assert!(
+        !window.dispatch(Event::KeyPress {
+            key: Key::Space,
+            pressed: true,
+            is_repeat: true,
+            text: Some(' '),
+        }),
+        "event handlers never capture keys"
+    );
+
+    window.dispatch(Event::CloseRequested);
+    assert!(window.is_closed());
+    assert_eq!(
+        take(&events),
+        [
+            Event::SizeChanged(Size::from((200, 100))),
+            Event::KeyPress {
+                key: Key::Space,
+                pressed: true,
+                is_repeat: true,
+                text: Some(' '),
+            },
+            Event::CloseRequested,
+            Event::Destroyed(DestroyReason::Closed),
+        ]
+    );
+}
+
+#[cfg(feature = "opengl")]
+#[test]
+fn test_headless_external_opengl() {
+    use picoview::ExternalGlContext;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Draws into the host context on every frame.
+    struct Handler;
+
+    impl EventHandler for Handler {
+        fn opened(&mut self, window: Window<'_>) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let gl = window.opengl()?;
+            assert_eq!(gl.get_proc_address(c"glClear") as usize, 0x1000);
+            assert!(gl.get_proc_address(c"glFoo").is_null());
+            assert_eq!(gl.version(), None, "the host picked the version");
+            Ok(())
+        }
+
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if event == Event::Frame {
+                let gl = window.opengl().unwrap();
+                gl.make_current(true).unwrap();
+                gl.swap_buffers().unwrap();
+            }
+        }
+    }
+
+    let swaps = Arc::new(AtomicUsize::new(0));
+    let context = ExternalGlContext::new(|name| {
+        if name == c"glClear" {
+            0x1000 as *const _
+        } else {
+            std::ptr::null()
+        }
+    })
+    .with_swap_buffers({
+        let swaps = swaps.clone();
+        move || {
+            swaps.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    });
+
+    let window = WindowBuilder::with_handler(Handler)
+        .with_external_opengl(context)
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    window.advance(Duration::from_millis(60));
+    assert_eq!(swaps.load(Ordering::Relaxed), 3, "one swap per frame");
+}
+
+#[cfg(feature = "opengl")]
+#[test]
+fn test_headless_make_current_retry() {
+    use picoview::{ExternalGlContext, MakeCurrentError};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Makes the context current on every frame, recording the results.
+    struct Handler(Arc<Mutex<Vec<Result<(), MakeCurrentError>>>>);
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if event == Event::Frame {
+                let result = window.opengl().unwrap().make_current(true);
+                self.0.lock().unwrap().push(result);
+            }
+        }
+    }
+
+    // fails twice, then works, then is lost for good
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let context = ExternalGlContext::new(|_| std::ptr::null()).with_make_current({
+        let attempts = attempts.clone();
+        move |_| match attempts.fetch_add(1, Ordering::Relaxed) {
+            0 | 1 => Err(MakeCurrentError::Transient),
+            2 => Ok(()),
+            _ => Err(MakeCurrentError::Lost),
+        }
+    });
+
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler(results.clone()))
+        .with_external_opengl(context)
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    window.advance(Duration::from_millis(40));
+    assert_eq!(
+        *results.lock().unwrap(),
+        [Ok(()), Err(MakeCurrentError::Lost)],
+        "transient failures are retried, a lost context is not"
+    );
+    assert_eq!(attempts.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn test_headless_keys_down() {
+    /// Records whether the key is down while its press is delivered.
+    struct Handler {
+        down: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if let Event::KeyPress { key, .. } = event {
+                self.down.lock().unwrap().push(window.is_key_down(key));
+            }
+
+            if let Event::FocusChanged(false) = event {
+                let down = window.is_key_down(Key::A) || window.is_key_down(Key::ShiftLeft);
+                self.down.lock().unwrap().push(down);
+            }
+        }
+    }
+
+    let down = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler { down: down.clone() })
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    let press = |key, pressed| {
+        window.dispatch(Event::KeyPress {
+            key,
+            pressed,
+            is_repeat: false,
+            text: None,
+        })
+    };
+
+    press(Key::ShiftLeft, true);
+    press(Key::A, true);
+    press(Key::A, false);
+    press(Key::A, true);
+    window.dispatch(Event::FocusChanged(false));
+
+    assert_eq!(
+        *down.lock().unwrap(),
+        [true, true, false, true, false],
+        "the set is updated before the handler runs and cleared on focus loss"
+    );
+}
+
+#[test]
+fn test_headless_open_url() {
+    /// Opens a url on every key press, records the results.
+    struct Handler {
+        urls: Vec<&'static str>,
+        results: Arc<Mutex<Vec<Result<(), OpenUrlError>>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if let Event::KeyPress { .. } = event {
+                let results = self.results.clone();
+                window.open_url(self.urls.remove(0), move |result| {
+                    results.lock().unwrap().push(result);
+                });
+            }
+        }
+    }
+
+    let urls = vec![
+        "mailto:someone@example.com",
+        "my-app+v2:open",
+        r"C:\Users\file.txt",
+        "",
+        "--help",
+        "https:",
+        "1http://example.com",
+        "http://example.com/\nfoo",
+    ];
+
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        urls: urls.clone(),
+        results: results.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    for _ in &urls {
+        window.dispatch(Event::KeyPress {
+            key: Key::Enter,
+            pressed: true,
+            is_repeat: false,
+            text: None,
+        });
+    }
+
+    let valid = results
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|result| !matches!(result, Err(OpenUrlError::InvalidUrl)))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        valid,
+        [true, true, true, false, false, false, false, false],
+        "urls are validated before launching"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_headless_x11_ext() {
+    use picoview::WindowExtX11;
+
+    let handles = Arc::new(Mutex::new(None));
+    let recorded = handles.clone();
+
+    let _window = WindowBuilder::new(move |window| {
+        *recorded.lock().unwrap() = Some((window.xid(), window.display().is_null()));
+        Ok(Box::new(()))
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    assert_eq!(
+        *handles.lock().unwrap(),
+        Some((0, true)),
+        "headless windows have no X11 objects"
+    );
+}
+
+#[test]
+fn test_headless_redraw_now() {
+    /// Asks for a frame twice while handling a key press.
+    struct Handler {
+        events: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            match event {
+                Event::Frame => self.events.lock().unwrap().push("frame"),
+                Event::KeyPress { .. } => {
+                    window.redraw_now();
+                    window.redraw_now();
+                    self.events.lock().unwrap().push("key");
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        events: events.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    window.dispatch(Event::KeyPress {
+        key: Key::A,
+        pressed: true,
+        is_repeat: false,
+        text: None,
+    });
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        ["key", "frame"],
+        "a frame requested by the handler follows the current event once"
+    );
+}
+
+#[test]
+fn test_headless_clipboard() {
+    /// Copies on `C` and pastes on `V`, keeping every paste result.
+    struct Handler {
+        copied: Arc<Mutex<Vec<Result<(), ClipboardError>>>>,
+        pasted: Arc<Mutex<Vec<Result<Exchange, ClipboardError>>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            match event {
+                Event::KeyPress { key: Key::C, .. } => {
+                    let result = window.set_clipboard("copied");
+                    self.copied.lock().unwrap().push(result);
+                }
+                Event::KeyPress { key: Key::V, .. } => {
+                    let result = window.get_clipboard();
+                    self.pasted.lock().unwrap().push(result);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let copied = Arc::new(Mutex::new(Vec::new()));
+    let pasted = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        copied: copied.clone(),
+        pasted: pasted.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    let press = |key| {
+        window.dispatch(Event::KeyPress {
+            key,
+            pressed: true,
+            is_repeat: false,
+            text: None,
+        })
+    };
+
+    press(Key::V);
+    press(Key::C);
+    press(Key::V);
+    window.set_clipboard_denied(true);
+    press(Key::C);
+    press(Key::V);
+
+    let copied = copied.lock().unwrap();
+    assert!(matches!(
+        copied.as_slice(),
+        [Ok(()), Err(ClipboardError::AccessDenied)]
+    ));
+
+    let pasted = pasted.lock().unwrap();
+    assert!(
+        matches!(
+            pasted.as_slice(),
+            [
+                Err(ClipboardError::Empty),
+                Ok(Exchange::Text(text)),
+                Err(ClipboardError::AccessDenied),
+            ] if text == "copied"
+        ),
+        "{pasted:?}"
+    );
+    assert!(matches!(window.clipboard(), Exchange::Text(text) if text == "copied"));
+}
+
+#[test]
+fn test_headless_clipboard_formats() {
+    /// Copies an image on `C`, pastes it as each of the formats on `V`.
+    struct Handler {
+        pasted: Arc<Mutex<Vec<Result<Exchange, ClipboardError>>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            let image = Image::new((1, 2), vec![255, 0, 0, 255, 0, 0, 255, 128]).unwrap();
+            match event {
+                Event::KeyPress { key: Key::C, .. } => {
+                    window.set_clipboard(image).unwrap();
+                    assert!(matches!(
+                        window.set_clipboard(Exchange::Custom {
+                            format: String::new(),
+                            data: vec![1],
+                        }),
+                        Err(ClipboardError::UnsupportedFormat)
+                    ));
+                }
+                Event::KeyPress { key: Key::V, .. } => {
+                    let mut pasted = self.pasted.lock().unwrap();
+                    pasted.push(window.get_clipboard());
+                    pasted.push(window.get_clipboard_as(ClipboardFormat::Text));
+                    pasted.push(window.get_clipboard_as(ClipboardFormat::Image));
+                    pasted
+                        .push(window.get_clipboard_as(ClipboardFormat::Custom(
+                            "com.example.preset".into(),
+                        )));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let pasted = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        pasted: pasted.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    let press = |key| {
+        window.dispatch(Event::KeyPress {
+            key,
+            pressed: true,
+            is_repeat: false,
+            text: None,
+        })
+    };
+
+    press(Key::C);
+    press(Key::V);
+    window.set_clipboard(Exchange::Custom {
+        format: "com.example.preset".into(),
+        data: vec![1, 2, 3],
+    });
+    press(Key::V);
+
+    let pasted = pasted.lock().unwrap();
+    assert!(
+        matches!(
+            pasted.as_slice(),
+            [
+                Ok(Exchange::Image(first)),
+                Err(ClipboardError::UnsupportedFormat),
+                Ok(Exchange::Image(second)),
+                Err(ClipboardError::UnsupportedFormat),
+                // custom formats are only read by name
+                Err(ClipboardError::UnsupportedFormat),
+                Err(ClipboardError::UnsupportedFormat),
+                Err(ClipboardError::UnsupportedFormat),
+                Ok(Exchange::Custom { format, data }),
+            ] if first == second
+                && first.size() == Size::from((1, 2))
+                && format == "com.example.preset"
+                && data == &[1, 2, 3]
+        ),
+        "{pasted:?}"
+    );
+
+    assert!(Image::new((2, 2), vec![0; 12]).is_none());
+    assert!(Image::new((0, 0), Vec::new()).is_none());
+}
+
+#[test]
+fn test_headless_signed_coordinates() {
+    let (window, events) = open();
+    take(&events);
+
+    // a captured mouse dragged past the top-left corner, or far beyond the
+    // range of the 16-bit coordinates of some platform messages
+    for position in [
+        Point { x: -1.0, y: -1.0 },
+        Point {
+            x: -40000.5,
+            y: 12.0,
+        },
+        Point {
+            x: 40000.0,
+            y: -32769.0,
+        },
+    ] {
+        window.dispatch(Event::MousePress {
+            button: MouseButton::Left,
+            pressed: true,
+            position,
+        });
+
+        assert_eq!(window.cursor_position(), Some(position));
+        assert_eq!(
+            take(&events),
+            [Event::MousePress {
+                button: MouseButton::Left,
+                pressed: true,
+                position,
+            }]
+        );
+    }
+}
+
+#[test]
+fn test_headless_ime_cursor_area() {
+    /// Puts the caret wherever the mouse is.
+    struct Handler;
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if let Event::MouseMove(point) = event {
+                let (x, y) = point.to_pixels();
+                window.set_ime_cursor_area(Rect {
+                    left: x,
+                    top: y,
+                    right: x + 2,
+                    bottom: y + 16,
+                });
+            }
+        }
+    }
+
+    let window = WindowBuilder::with_handler(Handler)
+        .open_headless()
+        .expect("failed to open a headless window");
+    assert_eq!(window.ime_cursor_area(), None);
+
+    window.dispatch(Event::MouseMove(Point { x: 10.0, y: 20.0 }));
+    assert_eq!(
+        window.ime_cursor_area(),
+        Some(Rect {
+            left: 10,
+            top: 20,
+            right: 12,
+            bottom: 36,
+        })
+    );
+}
+
+#[test]
+fn test_headless_composition() {
+    struct Handler {
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, _: Window<'_>, event: Event) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        events: events.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    // a composition that is committed, then one that is cancelled
+    let composition = [
+        Event::CompositionStart,
+        Event::CompositionUpdate {
+            text: "ni".into(),
+            cursor_range: 2..2,
+        },
+        Event::CompositionUpdate {
+            text: "你".into(),
+            cursor_range: 0..3,
+        },
+        Event::CompositionEnd {
+            text: Some("你".into()),
+        },
+        Event::CompositionStart,
+        Event::CompositionUpdate {
+            text: "h".into(),
+            cursor_range: 1..1,
+        },
+        Event::CompositionEnd { text: None },
+    ];
+
+    for event in composition.clone() {
+        assert!(!window.dispatch(event));
+    }
+
+    assert_eq!(take(&events), composition);
+}
+
+#[test]
+fn test_headless_open_child() {
+    /// Opens a child and two popups on a mouse press.
+    struct Handler {
+        opened: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if let Event::MousePress { pressed: true, .. } = event {
+                let child = window.open_child(WindowBuilder::new(|_| unreachable!()));
+                let popup = window.open_popup(
+                    WindowBuilder::new(|_| unreachable!()).with_parent_relative_position((10, 10)),
+                );
+                let anchored = WindowBuilder::new(|_| unreachable!()).open_popup(
+                    window,
+                    Rect {
+                        top: 10,
+                        left: 10,
+                        bottom: 30,
+                        right: 80,
+                    },
+                );
+
+                let mut opened = self.opened.lock().unwrap();
+                opened.push(child.is_ok());
+                opened.push(popup.is_ok());
+                opened.push(anchored.is_ok());
+            }
+        }
+    }
+
+    let opened = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        opened: opened.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    window.dispatch(Event::MousePress {
+        button: MouseButton::Left,
+        pressed: true,
+        position: Point { x: 50.0, y: 50.0 },
+    });
+
+    // headless windows have no native window to own others, the factories never run
+    assert_eq!(*opened.lock().unwrap(), [false, false, false]);
+    assert!(!window.is_closed());
+}
+
+#[test]
+fn test_headless_popup_dismissed() {
+    let (window, events) = open_with(|builder| builder.with_geometry_compression(true));
+    window.advance(Duration::from_millis(20));
+    take(&events);
+
+    // only popups close themselves, and a headless window is never one
+    window.dispatch(Event::PopupDismissed);
+    assert!(!window.is_closed());
+    assert_eq!(take(&events), [Event::PopupDismissed]);
+}
+
+#[test]
+fn test_headless_close_drops_pending() {
+    /// Resizes, asks for a frame and closes, all while handling a key press.
+    struct Handler {
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if let Event::KeyPress { .. } = event {
+                window.set_size((300, 100));
+                window.redraw_now();
+                window.close();
+            }
+
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        events: events.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    window.dispatch(Event::KeyPress {
+        key: Key::Escape,
+        pressed: true,
+        is_repeat: false,
+        text: None,
+    });
+
+    assert_eq!(
+        take(&events),
+        [
+            Event::KeyPress {
+                key: Key::Escape,
+                pressed: true,
+                is_repeat: false,
+                text: None,
+            },
+            Event::Destroyed(DestroyReason::Closed),
+        ],
+        "events queued before the close are dropped, the handler is only told it is gone"
+    );
+}
+
+#[test]
+fn test_headless_use_after_close() {
+    /// Closes twice on a mouse press, then calls every method of the window.
+    struct Handler {
+        results: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if !matches!(event, Event::MousePress { pressed: true, .. }) {
+                return;
+            }
+
+            assert!(!window.is_closed());
+            window.close();
+            window.close();
+            assert!(window.is_closed());
+
+            window.set_title("closed");
+            window.set_cursor_icon(MouseCursor::Hand);
+            window.set_decorations(false);
+            window.set_cursor_position((10, 10));
+            window.set_ime_cursor_area(Rect::from_size(Size::from((10, 10))));
+            window.set_size((300, 300));
+            window.set_min_size((300, 300));
+            window.set_max_size((300, 300));
+            window.set_position((10, 10));
+            window.set_visible(false);
+            window.redraw_now();
+            window.set_timer(1, Duration::from_millis(1));
+            window.cancel_timer(1);
+
+            // getters keep returning the last known state
+            assert_ne!(window.inner_size(), Size::from((300, 300)));
+            assert!(window.scale() > 0.0);
+
+            let mut results = self.results.lock().unwrap();
+            results.push(format!("{:?}", window.set_clipboard("text")));
+            results.push(format!("{:?}", window.get_clipboard()));
+            results.push(format!(
+                "{:?}",
+                window.get_clipboard_as(ClipboardFormat::Text)
+            ));
+            results.push(format!("{:?}", window.start_drag("text")));
+            results.push(format!(
+                "{:?}",
+                window.begin_drag_resize(ResizeEdge::BottomRight)
+            ));
+            results.push(format!(
+                "{:?}",
+                window.present_pixels(&[0; 4], Size::from((2, 2)), &[])
+            ));
+            results.push(format!(
+                "{:?}",
+                window.open_child(WindowBuilder::new(|_| unreachable!()))
+            ));
+            results.push(format!(
+                "{:?}",
+                window.open_popup(WindowBuilder::new(|_| unreachable!()))
+            ));
+        }
+    }
+
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        results: results.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+    window.set_clipboard("before");
+    let size = window.size();
+    let visible = window.is_visible();
+    let decorations = window.has_decorations();
+
+    window.dispatch(Event::MousePress {
+        button: MouseButton::Left,
+        pressed: true,
+        position: Point { x: 50.0, y: 50.0 },
+    });
+
+    assert!(window.is_closed());
+    assert_eq!(
+        *results.lock().unwrap(),
+        [
+            "Err(Closed)",
+            "Err(Closed)",
+            "Err(Closed)",
+            "Err(Closed)",
+            "Err(Closed)",
+            "Err(Closed)",
+            "Err(InvalidParent)",
+            "Err(InvalidParent)",
+        ]
+    );
+
+    assert_ne!(window.title(), "closed");
+    assert_eq!(window.size(), size);
+    assert_eq!(window.min_size(), Size::default());
+    assert_ne!(window.position(), Point { x: 10.0, y: 10.0 });
+    assert_eq!(window.is_visible(), visible);
+    assert_eq!(window.has_decorations(), decorations);
+    assert_eq!(window.cursor_icon(), MouseCursor::Default);
+    assert_eq!(window.cursor_position(), Some(Point { x: 50.0, y: 50.0 }));
+    assert_eq!(window.ime_cursor_area(), None);
+    assert!(matches!(window.clipboard(), Exchange::Text(text) if text == "before"));
+    assert!(window.drag_data().is_none());
+    assert_eq!(window.last_resize_edge(), None);
+
+    window.advance(Duration::from_secs(1));
+    assert!(window.is_closed());
+}
+
+#[test]
+fn test_headless_present_pixels() {
+    /// A buffer to present on the next frame, with its size and damage.
+    type Job = (Vec<u32>, Size, Vec<Rect>);
+
+    /// Presents the pending buffer on every frame, keeping the results.
+    struct Handler {
+        job: Arc<Mutex<Option<Job>>>,
+        results: Arc<Mutex<Vec<Result<(), PresentError>>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if event == Event::Frame
+                && let Some((buffer, size, damage)) = self.job.lock().unwrap().take()
+            {
+                let result = window.present_pixels(&buffer, size, &damage);
+                self.results.lock().unwrap().push(result);
+            }
+        }
+    }
+
+    let job = Arc::new(Mutex::new(None));
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        job: job.clone(),
+        results: results.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    let present = |buffer: Vec<u32>, size: Size, damage: &[Rect]| {
+        job.lock().unwrap().replace((buffer, size, damage.to_vec()));
+        window.dispatch(Event::Frame);
+        results
+            .lock()
+            .unwrap()
+            .pop()
+            .expect("nothing was presented")
+    };
+
+    window.dispatch(Event::SizeChanged(Size::from((20, 10))));
+    let size = window.size();
+    let pixels = (size.width * size.height) as usize;
+    let index = |x: u32, y: u32| (y * size.width + x) as usize;
+    assert_eq!(window.pixels(), vec![0; pixels]);
+
+    // an empty damage list presents everything
+    let red = vec![0xffff0000; pixels];
+    assert!(present(red.clone(), size, &[]).is_ok());
+    assert_eq!(window.pixels(), red);
+
+    // only damaged pixels are copied, damage outside of the buffer is ignored
+    let blue = vec![0xff0000ff; pixels];
+    let damage = [
+        Rect::from_xywh(10, 5, 2, 1),
+        Rect::from_xywh(-5, 50, 10, 10),
+    ];
+    assert!(present(blue, size, &damage).is_ok());
+
+    let shown = window.pixels();
+    assert_eq!(shown[index(10, 5)], 0xff0000ff);
+    assert_eq!(shown[index(11, 5)], 0xff0000ff);
+    assert_eq!(shown[index(12, 5)], 0xffff0000);
+    assert_eq!(shown[index(10, 6)], 0xffff0000);
+    assert_eq!(
+        shown.iter().filter(|&&pixel| pixel == 0xff0000ff).count(),
+        2
+    );
+
+    // a smaller buffer covers the top-left corner
+    let green = vec![0xff00ff00; 4];
+    assert!(present(green.clone(), Size::from((2, 2)), &[]).is_ok());
+    let shown = window.pixels();
+    assert_eq!(shown[index(1, 1)], 0xff00ff00);
+    assert_eq!(shown[index(2, 1)], 0xffff0000);
+
+    assert!(matches!(
+        present(green, Size::from((3, 2)), &[]),
+        Err(PresentError::BufferTooSmall)
+    ));
+}
+
+#[test]
+fn test_headless_frame_metrics() {
+    /// Records the metrics of the window on every frame.
+    struct Handler(Arc<Mutex<Vec<(Size, Size)>>>);
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if event == Event::Frame {
+                window.set_size((320, 240));
+                let metrics = (window.inner_size(), window.outer_size());
+                self.0.lock().unwrap().push(metrics);
+            }
+        }
+    }
+
+    let metrics = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler(metrics.clone()))
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    window.advance(Duration::from_millis(20));
+    assert_eq!(
+        metrics.lock().unwrap().last(),
+        Some(&(Size::from((320, 240)), Size::from((320, 240)))),
+        "a headless window has no frame"
+    );
+}
+
+#[test]
+fn test_headless_unmatched_mouse_release() {
+    let release = |button| Event::MousePress {
+        button,
+        pressed: false,
+        position: Point { x: 1.0, y: 2.0 },
+    };
+    let press = |button| Event::MousePress {
+        button,
+        pressed: true,
+        position: Point { x: 1.0, y: 2.0 },
+    };
+
+    // pressed outside, released inside: dropped
+    let (window, events) = open();
+    take(&events);
+    window.dispatch(release(MouseButton::Left));
+    assert!(
+        take(&events).is_empty(),
+        "a release without a press is dropped"
+    );
+
+    // pressed and released inside: both delivered
+    window.dispatch(press(MouseButton::Left));
+    window.dispatch(release(MouseButton::Left));
+    assert_eq!(
+        take(&events),
+        [press(MouseButton::Left), release(MouseButton::Left)]
+    );
+
+    // only the buttons that are held count
+    window.dispatch(press(MouseButton::Left));
+    window.dispatch(release(MouseButton::Middle));
+    window.dispatch(release(MouseButton::Left));
+    assert_eq!(
+        take(&events),
+        [press(MouseButton::Left), release(MouseButton::Left)]
+    );
+
+    // opted in to releases of buttons pressed elsewhere
+    let (window, events) = open_with(|builder| builder.with_unmatched_mouse_release(true));
+    take(&events);
+    window.dispatch(release(MouseButton::Left));
+    assert_eq!(take(&events), [release(MouseButton::Left)]);
+}
+
+#[test]
+fn test_headless_factory_redraw_now() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+
+    let _window = WindowBuilder::new(move |window| {
+        window.set_size((200, 100));
+        window.redraw_now();
+
+        Ok(Box::new(Recorder {
+            window,
+            events: recorded,
+        }))
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    assert_eq!(
+        take(&events),
+        [Event::SizeChanged(Size::from((200, 100))), Event::Frame],
+        "the frame asked for by the factory is delivered once there is a handler"
+    );
+}
+
+#[test]
+fn test_headless_timers() {
+    /// Records like [`Recorder`], the second timer cancels the first one.
+    struct Handler<'a>(Recorder<'a>);
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            self.0.frame();
+        }
+
+        fn timer(&mut self, id: u32) {
+            self.0.timer(id);
+
+            if id == 2 {
+                self.0.window.cancel_timer(1);
+            }
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+
+    let window = WindowBuilder::new(move |window| {
+        window.set_timer(1, Duration::from_millis(40));
+        window.set_timer(2, Duration::from_millis(100));
+        window.set_timer(3, Duration::from_millis(10));
+        window.cancel_timer(3);
+
+        Ok(Box::new(Handler(Recorder {
+            window,
+            events: recorded,
+        })))
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    let timers = |events: Vec<Event>| {
+        events
+            .into_iter()
+            .filter(|event| matches!(event, Event::Timer { .. }))
+            .collect::<Vec<_>>()
+    };
+
+    window.advance(Duration::from_millis(90));
+    assert_eq!(
+        timers(take(&events)),
+        [Event::Timer { id: 1 }, Event::Timer { id: 1 }]
+    );
+
+    // the second timer cancels the first one
+    window.advance(Duration::from_millis(160));
+    assert_eq!(
+        timers(take(&events)),
+        [Event::Timer { id: 2 }, Event::Timer { id: 2 }]
+    );
+
+    // timers expire between frames, in the order they are due
+    window.advance(Duration::from_millis(60));
+    assert_eq!(
+        take(&events),
+        [
+            Event::Frame,
+            Event::Frame,
+            Event::Frame,
+            Event::Timer { id: 2 },
+            Event::Frame
+        ]
+    );
+}
+
+#[test]
+fn test_headless_platform_options() {
+    let open = |platform_options: PlatformOptions| {
+        WindowBuilder::new(|_| Ok(Box::new(())))
+            .with_platform_options(platform_options)
+            .open_headless()
+    };
+
+    // non-exhaustive, so built by changing the defaults
+    let options = |change: fn(&mut PlatformOptions)| {
+        let mut options = PlatformOptions::default();
+        change(&mut options);
+        options
+    };
+
+    // every platform validates every option, even the ones it ignores
+    open(options(|options| {
+        options.windows_class_name = Some("HostPluginView".into());
+        options.windows_style = 0x0200_0000; // WS_CLIPCHILDREN
+        options.windows_ex_style = 0x0001_0000; // WS_EX_CONTROLPARENT
+        options.macos_autoresizing_mask = 0x12; // NSViewWidthSizable | NSViewHeightSizable
+    }))
+    .expect("valid options were rejected");
+
+    let invalid = [
+        options(|options| options.windows_class_name = Some(String::new())),
+        options(|options| options.windows_class_name = Some("a".repeat(256))),
+        options(|options| options.windows_style = 0x4000_0000), // WS_CHILD
+        options(|options| options.windows_ex_style = 0x0008_0000), // WS_EX_LAYERED
+        options(|options| options.macos_autoresizing_mask = 0x40),
+    ];
+
+    for options in invalid {
+        assert!(
+            matches!(open(options.clone()), Err(WindowError::InvalidOptions(_))),
+            "{options:?} was accepted"
+        );
+    }
+}
+
+#[test]
+fn test_headless_start_drag() {
+    /// Starts a drag on every mouse move, keeping the results and the events.
+    struct Handler {
+        started: Arc<Mutex<Vec<Result<(), DragError>>>>,
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if let Event::MouseMove(_) = event {
+                let result = window.start_drag(vec![PathBuf::from("/tmp/sample.wav")]);
+                self.started.lock().unwrap().push(result);
+            }
+
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        started: started.clone(),
+        events: events.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    let point = Point { x: 5.0, y: 5.0 };
+    let press = |pressed| Event::MousePress {
+        button: MouseButton::Left,
+        pressed,
+        position: point,
+    };
+
+    // a drag needs a button held
+    window.dispatch(Event::MouseMove(point));
+    assert!(matches!(
+        started.lock().unwrap().as_slice(),
+        [Err(DragError::NoButtonHeld)]
+    ));
+    assert!(window.drag_data().is_none());
+
+    // the button is released as soon as the drag starts
+    window.dispatch(press(true));
+    take(&events);
+    window.dispatch(Event::MouseMove(point));
+    assert_eq!(take(&events), [Event::MouseMove(point), press(false)]);
+    assert!(matches!(
+        window.drag_data(),
+        Some(Exchange::Files(files)) if files == [PathBuf::from("/tmp/sample.wav")]
+    ));
+
+    // one drag at a time, until it is finished
+    window.dispatch(press(true));
+    window.dispatch(Event::MouseMove(point));
+    window.dispatch(Event::DragFinished(DropEffect::Copy));
+    assert!(window.drag_data().is_none());
+    assert_eq!(
+        take(&events),
+        [
+            press(true),
+            Event::MouseMove(point),
+            Event::DragFinished(DropEffect::Copy)
+        ]
+    );
+    assert!(matches!(
+        started.lock().unwrap().as_slice(),
+        [
+            Err(DragError::NoButtonHeld),
+            Ok(()),
+            Err(DragError::InProgress)
+        ]
+    ));
+}
+
+#[test]
+fn test_headless_begin_drag_resize() {
+    /// Resizes by the bottom-right corner on every press and mouse move.
+    struct Handler {
+        started: Arc<Mutex<Vec<Result<(), DragError>>>>,
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            if matches!(
+                event,
+                Event::MouseMove(_) | Event::MousePress { pressed: true, .. }
+            ) {
+                let result = window.begin_drag_resize(ResizeEdge::BottomRight);
+                self.started.lock().unwrap().push(result);
+            }
+
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler {
+        started: started.clone(),
+        events: events.clone(),
+    })
+    .open_headless()
+    .expect("failed to open a headless window");
+
+    let point = Point { x: 195.0, y: 95.0 };
+    let press = |pressed| Event::MousePress {
+        button: MouseButton::Left,
+        pressed,
+        position: point,
+    };
+
+    window.dispatch(Event::MouseMove(point));
+    assert!(window.last_resize_edge().is_none());
+
+    // the system takes over the button
+    take(&events);
+    window.dispatch(press(true));
+    assert_eq!(take(&events), [press(true), press(false)]);
+    assert_eq!(window.last_resize_edge(), Some(ResizeEdge::BottomRight));
+    assert!(matches!(
+        started.lock().unwrap().as_slice(),
+        [Err(DragError::NoButtonHeld), Ok(())]
+    ));
+}
+
+#[test]
+fn test_headless_cursor_grab() {
+    /// Locks the cursor while the left button is held, shows itself on Space.
+    struct Handler(Arc<Mutex<Vec<Result<(), CursorGrabError>>>>);
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            match event {
+                Event::MousePress { pressed, .. } => {
+                    let mode = if pressed {
+                        CursorGrab::Locked
+                    } else {
+                        CursorGrab::None
+                    };
+                    self.0.lock().unwrap().push(window.set_cursor_grab(mode));
+                }
+                Event::KeyPress {
+                    key: Key::Space, ..
+                } => window.set_visible(true),
+                _ => {}
+            }
+        }
+    }
+
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler(results.clone()))
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    let press = |pressed| Event::MousePress {
+        button: MouseButton::Left,
+        pressed,
+        position: Point { x: 10.0, y: 10.0 },
+    };
+
+    // hidden windows can not grab the mouse
+    window.dispatch(press(true));
+    assert_eq!(window.cursor_grab(), CursorGrab::None);
+    assert!(matches!(
+        results.lock().unwrap().as_slice(),
+        [Err(CursorGrabError::Unavailable)]
+    ));
+
+    window.dispatch(Event::KeyPress {
+        key: Key::Space,
+        pressed: true,
+        is_repeat: false,
+        text: None,
+    });
+    window.dispatch(press(true));
+    assert_eq!(window.cursor_grab(), CursorGrab::Locked);
+    window.dispatch(press(false));
+    assert_eq!(window.cursor_grab(), CursorGrab::None);
+
+    // the grab ends with the focus
+    window.dispatch(press(true));
+    window.dispatch(Event::FocusChanged(false));
+    assert_eq!(window.cursor_grab(), CursorGrab::None);
+    assert!(results.lock().unwrap().iter().skip(1).all(Result::is_ok));
+}
+
+#[test]
+fn test_headless_input_state() {
+    /// Shows itself once focused, confines the cursor on a press, and records
+    /// the input state the handler sees after every event.
+    struct Handler(Arc<Mutex<Vec<(bool, bool, bool)>>>);
+
+    impl EventHandler for Handler {
+        fn event(&mut self, window: Window<'_>, event: Event) {
+            match event {
+                Event::FocusChanged(true) => window.set_visible(true),
+                Event::MousePress { pressed: true, .. } => {
+                    window.set_cursor_grab(CursorGrab::Confined).unwrap();
+                }
+                _ => {}
+            }
+
+            self.0.lock().unwrap().push((
+                window.is_cursor_confined(),
+                window.has_keyboard_focus(),
+                window.has_mouse_capture(),
+            ));
+        }
+    }
+
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let window = WindowBuilder::with_handler(Handler(states.clone()))
+        .open_headless()
+        .expect("failed to open a headless window");
+
+    let press = |pressed| Event::MousePress {
+        button: MouseButton::Left,
+        pressed,
+        position: Point { x: 10.0, y: 10.0 },
+    };
+
+    window.dispatch(Event::FocusChanged(true));
+    window.dispatch(press(true));
+    window.dispatch(press(false));
+    window.dispatch(Event::FocusChanged(false));
+
+    // the grab outlives the press, and ends with the focus
+    assert_eq!(
+        *states.lock().unwrap(),
+        [
+            (false, true, false),
+            (true, true, true),
+            (true, true, false),
+            (false, false, false),
+        ]
+    );
+}
@@ -0,0 +1,386 @@
+use picoview::{Insets, Point, Size, Window, WindowBuilder, WindowHandler};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+/// How long an observation may take before the window is woken up and the
+/// observation is taken to be `false`.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The kind of behavior a quirk describes, printed in the report.
+#[derive(Debug, Clone, Copy)]
+enum Category {
+    /// Sizes and insets of the window
+    Geometry,
+    /// What happens while the window is hidden
+    Visibility,
+    /// When the window gains or loses keyboard focus
+    FocusPolicy,
+    /// The order in which the handler sees events
+    EventOrder,
+    /// Which origin reported positions are relative to
+    CoordinateSpace,
+}
+
+/// A behavior that differs between the platforms, or could start to.
+///
+/// Every row is checked against the platform the test runs on, so a backend
+/// that starts to behave differently fails here, and an intentional change
+/// has to be made explicit by updating the table.
+struct Quirk {
+    /// Printed in the report
+    name: &'static str,
+    category: Category,
+    /// What the observation means, `true` if the description holds
+    description: &'static str,
+    /// The expected observation on each platform, `None` if it depends on the
+    /// environment (window manager, compositor) and is not checked
+    windows: Option<bool>,
+    macos: Option<bool>,
+    x11: Option<bool>,
+    /// Opens a window and observes the behavior
+    observe: fn() -> bool,
+}
+
+impl Quirk {
+    /// The expected observation on the platform the test runs on.
+    fn expected(&self) -> Option<bool> {
+        if cfg!(target_os = "windows") {
+            self.windows
+        } else if cfg!(target_os = "macos") {
+            self.macos
+        } else {
+            self.x11
+        }
+    }
+}
+
+const QUIRKS: [Quirk; 9] = [
+    Quirk {
+        name: "set_size_is_synchronous",
+        category: Category::Geometry,
+        description: "Window::inner_size returns the new size right after Window::set_size",
+        // the size is applied by SetWindowPos and setFrameSize before they return,
+        // X11 waits for the ConfigureNotify from the server (or window manager)
+        windows: Some(true),
+        macos: Some(true),
+        x11: Some(false),
+        observe: || {
+            observe(|window, seen| {
+                if seen.sizes.is_empty() {
+                    return None;
+                }
+
+                window.set_size((300, 200));
+                Some(window.inner_size() == Size::from((300, 200)))
+            })
+        },
+    },
+    Quirk {
+        name: "size_changed_after_set_size",
+        category: Category::Geometry,
+        description: "Window::set_size is followed by WindowHandler::size_changed",
+        windows: Some(true),
+        macos: Some(true),
+        x11: Some(true),
+        observe: || {
+            let mut resized = false;
+            observe(move |window, seen| {
+                if !resized {
+                    resized = true;
+                    window.set_size((300, 200));
+                }
+
+                seen.sizes.contains(&Size::from((300, 200))).then_some(true)
+            })
+        },
+    },
+    Quirk {
+        name: "frames_while_hidden",
+        category: Category::Visibility,
+        description: "WindowHandler::frame keeps being called after Window::set_visible(false)",
+        // none of the frame sources (VSync thread, CVDisplayLink, X11 loop timer) look
+        // at the visibility
+        windows: Some(true),
+        macos: Some(true),
+        x11: Some(true),
+        observe: || {
+            let mut hidden_at = None;
+            observe(move |window, seen| {
+                let hidden_at = *hidden_at.get_or_insert_with(|| {
+                    window.set_visible(false);
+                    seen.frames
+                });
+
+                (seen.frames > hidden_at + 10).then_some(true)
+            })
+        },
+    },
+    Quirk {
+        name: "decorations_have_insets",
+        category: Category::Geometry,
+        description: "Window::frame_insets is non-zero for a decorated top-level window",
+        // the frame belongs to the window manager on X11, CI runs without one
+        windows: Some(true),
+        macos: Some(true),
+        x11: None,
+        observe: || {
+            observe(|window, seen| {
+                (seen.frames > 10).then(|| window.frame_insets() != Insets::default())
+            })
+        },
+    },
+    Quirk {
+        name: "shown_window_gains_focus",
+        category: Category::FocusPolicy,
+        description: "a top-level window shown with Window::set_visible(true) reports focus",
+        // windows are shown with SWP_NOACTIVATE, macOS makes the window key and the
+        // view is its first responder, X11 leaves the decision to the window manager
+        windows: Some(false),
+        macos: Some(true),
+        x11: None,
+        observe: || observe(|_, seen| (seen.frames > 10).then(|| seen.focus.contains(&true))),
+    },
+    Quirk {
+        name: "hidden_window_loses_focus",
+        category: Category::FocusPolicy,
+        description: "a focused window hidden with Window::set_visible(false) reports losing focus",
+        windows: None,
+        macos: Some(true),
+        x11: None,
+        observe: || {
+            let mut hidden = false;
+            observe(move |window, seen| {
+                if !hidden {
+                    if seen.focus.last() != Some(&true) {
+                        return (seen.frames > 10).then_some(false);
+                    }
+
+                    hidden = true;
+                    window.set_visible(false);
+                }
+
+                (seen.focus.last() == Some(&false)).then_some(true)
+            })
+        },
+    },
+    Quirk {
+        name: "size_changed_before_first_frame",
+        category: Category::EventOrder,
+        description: "the size set in the factory is reported before the first frame",
+        // SetWindowPos and setFrameSize queue the event before they return, X11 reports
+        // the size once the ConfigureNotify arrives, which can be after the first frame
+        windows: Some(true),
+        macos: Some(true),
+        x11: None,
+        observe: || observe(|_, seen| Some(seen.sized_before_first_frame)),
+    },
+    Quirk {
+        name: "set_position_round_trips",
+        category: Category::CoordinateSpace,
+        description: "WindowHandler::position_changed reports the point passed to Window::set_position",
+        // Windows moves the outer frame but reports the client area origin, macOS moves
+        // and reports the frame origin, X11 window managers may place the window anyway
+        windows: Some(false),
+        macos: Some(true),
+        x11: None,
+        observe: || {
+            let mut moved = false;
+            observe(move |window, seen| {
+                if !moved {
+                    moved = true;
+                    window.set_position((200, 150));
+                }
+
+                if seen.positions.last() == Some(&Point::from((200, 150))) {
+                    Some(true)
+                } else {
+                    (seen.frames > 20).then_some(false)
+                }
+            })
+        },
+    },
+    Quirk {
+        name: "embedded_position_is_parent_relative",
+        category: Category::CoordinateSpace,
+        description: "an embedded window reports its parent relative position",
+        // macOS only reports positions of its own windows, X11 reports positions relative
+        // to the root window and only after a ConfigureNotify
+        windows: Some(true),
+        macos: Some(false),
+        x11: Some(false),
+        observe: || {
+            let positions = Arc::new(Mutex::new(Vec::new()));
+            let mut opened = false;
+            observe(move |window, seen| {
+                if !opened {
+                    opened = true;
+                    // keep the parent away from the origin, so screen and parent
+                    // coordinates differ
+                    window.set_position((200, 150));
+                    open_embedded_child(window, (30, 40), positions.clone());
+                }
+
+                if positions.lock().unwrap().contains(&Point::from((30, 40))) {
+                    Some(true)
+                } else {
+                    (seen.frames > 20).then_some(false)
+                }
+            })
+        },
+    },
+];
+
+/// Opens a visible embedded window at `position` that records every position
+/// it reports.
+fn open_embedded_child(parent: Window, position: (i32, i32), positions: Arc<Mutex<Vec<Point>>>) {
+    struct Handler {
+        positions: Arc<Mutex<Vec<Point>>>,
+    }
+
+    impl WindowHandler for Handler {
+        fn position_changed(&mut self, position: Point) {
+            self.positions.lock().unwrap().push(position);
+        }
+    }
+
+    WindowBuilder::new(move |window| {
+        window.set_title("picoview test - quirks (embedded)");
+        window.set_size((64, 64));
+        window.set_visible(true);
+
+        Ok(Box::new(Handler { positions }))
+    })
+    .with_parent_relative_position(position)
+    .open_embedded(parent)
+    .unwrap();
+}
+
+/// What the window has seen so far, passed to the observer on every frame.
+#[derive(Default)]
+struct Seen {
+    frames: usize,
+    sizes: Vec<Size>,
+    positions: Vec<Point>,
+    focus: Vec<bool>,
+    /// Whether a size was reported before the first frame
+    sized_before_first_frame: bool,
+}
+
+type Step = Box<dyn FnMut(Window, &Seen) -> Option<bool> + Send>;
+
+/// Opens a decorated top-level window and calls `step` on every frame until
+/// it returns an observation. Gives up with `false` after [`TIMEOUT`].
+fn observe(step: impl FnMut(Window, &Seen) -> Option<bool> + Send + 'static) -> bool {
+    struct Handler<'a> {
+        window: Window<'a>,
+        seen: Seen,
+        step: Step,
+        observed: Arc<Mutex<Option<bool>>>,
+    }
+
+    impl Handler<'_> {
+        fn finish(&mut self, observed: bool) {
+            self.observed.lock().unwrap().get_or_insert(observed);
+            self.window.close();
+        }
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            if self.seen.frames == 0 {
+                self.seen.sized_before_first_frame = !self.seen.sizes.is_empty();
+            }
+
+            if let Some(observed) = (self.step)(self.window, &self.seen) {
+                self.finish(observed);
+            }
+
+            self.seen.frames += 1;
+        }
+
+        fn wakeup(&mut self) {
+            self.finish(false);
+        }
+
+        fn size_changed(&mut self, size: Size) {
+            self.seen.sizes.push(size);
+        }
+
+        fn position_changed(&mut self, position: Point) {
+            self.seen.positions.push(position);
+        }
+
+        fn focus_changed(&mut self, focus: bool) {
+            self.seen.focus.push(focus);
+        }
+    }
+
+    let observed = Arc::new(Mutex::new(None));
+    WindowBuilder::new({
+        let observed = observed.clone();
+        move |window| {
+            window.set_title("picoview test - quirks");
+            window.set_size((256, 256));
+            window.set_decorations(true);
+            window.set_visible(true);
+
+            // frames might stop, the waker is delivered regardless
+            let waker = window.waker();
+            spawn(move || {
+                sleep(TIMEOUT);
+                let _ = waker.wakeup();
+            });
+
+            Ok(Box::new(Handler {
+                window,
+                seen: Seen::default(),
+                step: Box::new(step),
+                observed,
+            }))
+        }
+    })
+    .open_blocking()
+    .unwrap();
+
+    let observed = *observed.lock().unwrap();
+    observed.expect("the window closed without an observation")
+}
+
+/// Because some OSes require the windows to be created on the main-thread
+/// we have to run the tests with `harness = false`.
+fn main() {
+    let mut diverged = Vec::new();
+    for quirk in &QUIRKS {
+        let Some(expected) = quirk.expected() else {
+            println!(
+                "quirk {:<36} {:<16} not checked on this platform",
+                quirk.name,
+                format!("{:?}", quirk.category)
+            );
+            continue;
+        };
+
+        let observed = (quirk.observe)();
+        println!(
+            "quirk {:<36} {:<16} expected {expected:<5} observed {observed}",
+            quirk.name,
+            format!("{:?}", quirk.category)
+        );
+
+        if observed != expected {
+            diverged.push(format!(
+                "{}: expected {expected}, observed {observed} ({})",
+                quirk.name, quirk.description
+            ));
+        }
+
+        sleep(Duration::from_millis(100));
+    }
+
+    assert!(
+        diverged.is_empty(),
+        "behavior diverged from the quirk table, update QUIRKS if this is intentional:\n{}",
+        diverged.join("\n")
+    );
+}
@@ -0,0 +1,88 @@
+use picoview::ScrollDelta;
+
+/// What each platform would report for a single notch of the wheel, turned
+/// slowly and quickly, and for a touchpad moved by the same distance.
+///
+/// These are synthetic: written by hand from how each backend converts its
+/// native events (noted above each one), not recorded from real devices. A
+/// backend that changes its conversion has to update them.
+const ONE_NOTCH: [(&str, ScrollDelta); 8] = [
+    // WM_MOUSEWHEEL with WHEEL_DELTA, divided by WHEEL_DELTA
+    ("windows wheel", ScrollDelta::Notches { x: 0.0, y: 1.0 }),
+    // precision touchpads send fractions of WHEEL_DELTA, summing up to a notch
+    ("windows touchpad", ScrollDelta::Notches { x: 0.0, y: 1.0 }),
+    // core button 5, or XInput2 valuator movement divided by its increment
+    ("x11 wheel", ScrollDelta::Notches { x: 0.0, y: 1.0 }),
+    (
+        "x11 touchpad gesture",
+        ScrollDelta::Pixels {
+            x: 0.0,
+            y: ScrollDelta::PIXELS_PER_NOTCH,
+        },
+    ),
+    ("macos wheel", ScrollDelta::Lines { x: 0.0, y: 1.0 }),
+    ("macos fast wheel", ScrollDelta::Lines { x: 0.0, y: 7.5 }),
+    (
+        "macos touchpad",
+        ScrollDelta::Pixels {
+            x: 0.0,
+            y: ScrollDelta::PIXELS_PER_NOTCH,
+        },
+    ),
+    (
+        "macos horizontal touchpad",
+        ScrollDelta::Pixels {
+            x: -ScrollDelta::PIXELS_PER_NOTCH,
+            y: 0.0,
+        },
+    ),
+];
+
+#[test]
+fn test_scroll_notch_is_one_everywhere() {
+    for (source, delta) in ONE_NOTCH {
+        let (x, y) = delta.normalized();
+        assert_eq!(x.abs().max(y.abs()), 1.0, "{source} (synthetic): {delta:?}");
+    }
+}
+
+#[test]
+fn test_scroll_raw_keeps_platform_units() {
+    assert_eq!(ScrollDelta::Notches { x: 0.5, y: -2.0 }.raw(), (0.5, -2.0));
+    assert_eq!(ScrollDelta::Lines { x: 0.0, y: 7.5 }.raw(), (0.0, 7.5));
+    assert_eq!(ScrollDelta::Pixels { x: -3.0, y: 96.0 }.raw(), (-3.0, 96.0));
+}
+
+#[test]
+fn test_scroll_normalization() {
+    // notches are never changed, including fractions and multiple notches
+    for value in [-3.0, -1.0, -0.25, 0.0, 0.125, 1.0, 2.0] {
+        let delta = ScrollDelta::Notches { x: value, y: value };
+        assert_eq!(delta.normalized(), (value, value));
+    }
+
+    // acceleration is removed, but slow smooth scrolling keeps its fractions
+    assert_eq!(
+        ScrollDelta::Lines { x: -12.0, y: 3.0 }.normalized(),
+        (-1.0, 1.0)
+    );
+    assert_eq!(
+        ScrollDelta::Lines { x: 0.0, y: -0.5 }.normalized(),
+        (0.0, -0.5)
+    );
+
+    // pixels scale linearly, so touchpad scrolling follows the fingers
+    let (_, half) = ScrollDelta::Pixels {
+        x: 0.0,
+        y: ScrollDelta::PIXELS_PER_NOTCH / 2.0,
+    }
+    .normalized();
+    assert_eq!(half, 0.5);
+
+    let (_, many) = ScrollDelta::Pixels {
+        x: 0.0,
+        y: -ScrollDelta::PIXELS_PER_NOTCH * 10.0,
+    }
+    .normalized();
+    assert_eq!(many, -10.0);
+}
@@ -0,0 +1,199 @@
+use picoview::{MouseCursor, Window, WindowBuilder, WindowGuard, WindowHandler, WindowWaker};
+
+/// Cycles run before the baseline is taken, so lazily created resources
+/// (cursor caches, the first window class, drivers) do not count as leaks.
+const WARMUP: usize = 20;
+/// Cycles run after the baseline. A leak of one resource per cycle shows up as
+/// this many, well above [`SLACK`].
+const CYCLES: usize = 200;
+/// How much any resource may grow over [`CYCLES`], the OS may hold on to a few
+/// things for reasons of its own.
+const SLACK: usize = 10;
+/// How many frames a closed child may take to be destroyed.
+const CLOSE_FRAMES: usize = 600;
+
+/// Hosts open and close plugin editors hundreds of times per session, so
+/// anything a window leaks adds up. This opens and closes an embedded window
+/// over and over and checks that the resources of the process stay flat.
+///
+/// Because some OSes require the windows to be created on the main-thread
+/// we have to run the tests with `harness = false`.
+fn main() {
+    test_soak_embedded();
+}
+
+fn test_soak_embedded() {
+    struct Handler<'a> {
+        window: Window<'a>,
+        child: Option<WindowGuard>,
+        closing: Option<(WindowWaker, usize)>,
+        cycles: usize,
+        baseline: Vec<(&'static str, usize)>,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            // dropping the guard from the parent's thread does not wait on
+            // every platform, the child is only gone once its waker fails
+            if let Some(child) = self.child.take() {
+                self.closing = Some((child.waker().clone(), 0));
+                drop(child);
+                return;
+            }
+
+            if let Some((waker, frames)) = &mut self.closing {
+                if waker.wakeup().is_ok() {
+                    *frames += 1;
+                    assert!(*frames < CLOSE_FRAMES, "the child was never destroyed");
+                    return;
+                }
+
+                self.closing = None;
+                self.cycles += 1;
+            }
+
+            if self.cycles == WARMUP {
+                self.baseline = sample(self.window);
+            }
+
+            if self.cycles == WARMUP + CYCLES {
+                let current = sample(self.window);
+                for ((name, before), (_, after)) in self.baseline.iter().zip(&current) {
+                    assert!(
+                        *after <= before + SLACK,
+                        "{name} grew from {before} to {after} over {CYCLES} open/close cycles"
+                    );
+                }
+
+                self.window.close();
+                return;
+            }
+
+            self.child = Some(open_child(self.window));
+        }
+    }
+
+    WindowBuilder::new(|window| {
+        window.set_title("picoview test - soak");
+        window.set_size((512, 256));
+        window.set_position((100, 200));
+        window.set_visible(true);
+
+        Ok(Box::new(Handler {
+            window,
+            child: None,
+            closing: None,
+            cycles: 0,
+            baseline: Vec::new(),
+        }))
+    })
+    .open_blocking()
+    .unwrap();
+}
+
+/// Opens an embedded window that touches the resources a typical editor
+/// would: a cursor, a title and an OpenGL context.
+fn open_child(parent: Window) -> WindowGuard {
+    let builder = WindowBuilder::new(|window| {
+        window.set_title("picoview test - soak child");
+        window.set_size((256, 256));
+        window.set_cursor_icon(MouseCursor::Hand);
+        window.set_visible(true);
+
+        Ok(Box::new(()))
+    });
+
+    #[cfg(feature = "opengl")]
+    let builder = builder.with_opengl(picoview::GlConfig::default());
+
+    builder.open_embedded(parent).unwrap().into_guard()
+}
+
+/// Counts the resources held by the process that a leaking window would keep
+/// adding to.
+#[cfg(target_os = "windows")]
+fn sample(_: Window) -> Vec<(&'static str, usize)> {
+    use windows_sys::Win32::System::Threading::{
+        GR_GDIOBJECTS, GR_USEROBJECTS, GetCurrentProcess, GetGuiResources, GetProcessHandleCount,
+    };
+
+    unsafe {
+        let process = GetCurrentProcess();
+        let mut handles = 0;
+        GetProcessHandleCount(process, &mut handles);
+
+        vec![
+            (
+                "GDI objects",
+                GetGuiResources(process, GR_GDIOBJECTS) as usize,
+            ),
+            (
+                "USER objects",
+                GetGuiResources(process, GR_USEROBJECTS) as usize,
+            ),
+            ("kernel handles", handles as usize),
+        ]
+    }
+}
+
+/// Counts the resources held by the process that a leaking window would keep
+/// adding to.
+#[cfg(target_os = "linux")]
+fn sample(parent: Window) -> Vec<(&'static str, usize)> {
+    use picoview::WindowExtX11;
+    use std::ptr::null_mut;
+    use x11::xlib::{Display, XFree, XQueryTree};
+
+    let count = |path: &str| std::fs::read_dir(path).map_or(0, |entries| entries.count());
+
+    // embedded windows are children of the parent, a window that is never
+    // destroyed stays there
+    let children = unsafe {
+        let mut root = 0;
+        let mut parent_of_parent = 0;
+        let mut children = null_mut();
+        let mut len = 0;
+        XQueryTree(
+            parent.display() as *mut Display,
+            parent.xid(),
+            &mut root,
+            &mut parent_of_parent,
+            &mut children,
+            &mut len,
+        );
+
+        if !children.is_null() {
+            XFree(children as *mut _);
+        }
+
+        len as usize
+    };
+
+    vec![
+        ("threads", count("/proc/self/task")),
+        ("file descriptors", count("/proc/self/fd")),
+        ("child windows", children),
+    ]
+}
+
+/// Counts the resources held by the process that a leaking window would keep
+/// adding to.
+#[cfg(target_os = "macos")]
+fn sample(_: Window) -> Vec<(&'static str, usize)> {
+    use std::ffi::{c_int, c_void};
+
+    #[link(name = "objc")]
+    unsafe extern "C" {
+        fn objc_getClassList(buffer: *mut *const c_void, count: c_int) -> c_int;
+    }
+
+    let count = |path: &str| std::fs::read_dir(path).map_or(0, |entries| entries.count());
+
+    // every view registers a class of its own, and disposes of it once released
+    let classes = unsafe { objc_getClassList(std::ptr::null_mut(), 0) };
+
+    vec![
+        ("objc classes", classes as usize),
+        ("file descriptors", count("/dev/fd")),
+    ]
+}
@@ -1,7 +1,42 @@
-use picoview::{Exchange, MouseCursor, Window, WindowBuilder, WindowHandler};
-use std::thread::sleep;
+use picoview::rwh_06::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use picoview::{
+    EventLoop, Exchange, Key, MouseCursor, Point, Rect, ResizeHints, Size, Window, WindowBuilder,
+    WindowError, WindowGroup, WindowHandler, WindowWaker,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::mpsc;
+use std::thread::{sleep, spawn};
 use std::time::{Duration, Instant};
 
+/// Counts heap allocations per thread, so we can check that the realtime-safe
+/// paths (like [`WindowWaker::wakeup`]) do not allocate.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// [`WindowWaker`] must be usable from any thread.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<WindowWaker>();
+};
+
 /// Because some OSes require the windows to be created on the main-thread
 /// we have to run the tests with `harness = false`.
 fn main() {
@@ -11,9 +46,44 @@ fn main() {
     sleep(Duration::from_millis(100));
     test_startup_transient();
     sleep(Duration::from_millis(100));
+    test_startup_event_loop();
+    sleep(Duration::from_millis(100));
     test_startup_embedded();
     sleep(Duration::from_millis(100));
+    test_startup_embedded_relative_position();
+    sleep(Duration::from_millis(100));
+    test_startup_factory_calls();
+    sleep(Duration::from_millis(100));
+    test_startup_use_after_close();
+    sleep(Duration::from_millis(100));
     test_startup_error();
+    sleep(Duration::from_millis(100));
+    test_startup_wakeup_allocation_free();
+    sleep(Duration::from_millis(100));
+    test_startup_surface_handles_stable();
+    sleep(Duration::from_millis(100));
+    test_startup_window_group();
+    #[cfg(not(target_os = "macos"))]
+    {
+        sleep(Duration::from_millis(100));
+        test_startup_frames_slow_handler();
+    }
+    #[cfg(target_os = "windows")]
+    {
+        sleep(Duration::from_millis(100));
+        test_startup_keyboard_hook_chaining();
+    }
+    #[cfg(target_os = "linux")]
+    {
+        sleep(Duration::from_millis(100));
+        test_startup_x11_unicode_title();
+        sleep(Duration::from_millis(100));
+        test_startup_x11_no_leave_while_held();
+        sleep(Duration::from_millis(100));
+        test_startup_x11_fill_root_keeps_events();
+        sleep(Duration::from_millis(100));
+        test_startup_x11_cursor_grab_focus_loss();
+    }
 }
 
 fn test_startup_blocking() {
@@ -35,7 +105,7 @@ fn test_startup_blocking() {
         // (or at least that it doesn't crash or cause UB to call into window)
         fn drop(&mut self) {
             let _ = self.window.get_clipboard();
-            self.window.set_clipboard(Exchange::Text("test".into()));
+            let _ = self.window.set_clipboard(Exchange::Text("test".into()));
             self.window.set_decorations(false);
             self.window.set_position((100, 200));
             self.window.set_size((512, 512));
@@ -52,6 +122,10 @@ fn test_startup_blocking() {
         window.set_position((100, 200));
         window.set_visible(true);
 
+        window.set_user_data(Box::new(42u32));
+        assert_eq!(window.user_data::<u32>().as_deref(), Some(&42));
+        assert!(window.user_data::<i32>().is_none());
+
         Ok(Box::new(Handler {
             window,
             instant: Instant::now(),
@@ -80,7 +154,7 @@ fn test_startup_blocking_undecorated() {
         // (or at least that it doesn't crash or cause UB to call into window)
         fn drop(&mut self) {
             let _ = self.window.get_clipboard();
-            self.window.set_clipboard(Exchange::Text("test".into()));
+            let _ = self.window.set_clipboard(Exchange::Text("test".into()));
             self.window.set_decorations(false);
             self.window.set_position((100, 200));
             self.window.set_size((512, 512));
@@ -108,6 +182,257 @@ fn test_startup_blocking_undecorated() {
     .unwrap();
 }
 
+/// Every [`Window`] method must be safe to call after [`Window::close`], and do
+/// nothing.
+fn test_startup_use_after_close() {
+    /// Closes twice on the first frame, then calls every method of the window.
+    struct Handler<'a> {
+        window: Window<'a>,
+        frames: usize,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            self.frames += 1;
+            assert_eq!(self.frames, 1, "no frames after the close");
+
+            let window = self.window;
+            let size = window.inner_size();
+            window.close();
+            window.close();
+            assert!(window.is_closed());
+
+            window.set_title("picoview test - use after close (closed)");
+            window.set_decorations(false);
+            window.set_cursor_icon(MouseCursor::Hand);
+            window.set_cursor_position((10, 10));
+            window.set_ime_cursor_area(Rect::from_size(Size::from((2, 16))));
+            window.set_min_size((64, 64));
+            window.set_max_size((512, 512));
+            window.set_resize_hints(ResizeHints::default());
+            window.set_size((512, 512));
+            window.set_position((100, 200));
+            window.set_visible(false);
+            window.redraw_now();
+            window.set_timer(1, Duration::from_millis(1));
+            window.cancel_timer(1);
+            assert_eq!(window.inner_size(), size, "setters do nothing");
+
+            assert!(window.set_clipboard(Exchange::Text("test".into())).is_err());
+            assert!(window.get_clipboard().is_err());
+            assert!(window.start_drag(Exchange::Text("test".into())).is_err());
+            assert!(
+                window
+                    .present_pixels(&[0; 4], Size::from((2, 2)), &[])
+                    .is_err()
+            );
+
+            let _ = window.scale();
+            let _ = window.to_screen(Point { x: 0.0, y: 0.0 });
+            let _ = window.window_handle();
+            let _ = window.display_handle();
+        }
+    }
+
+    WindowBuilder::new(|window| {
+        window.set_title("picoview test - use after close");
+        window.set_size((256, 256));
+        window.set_visible(true);
+        Ok(Box::new(Handler { window, frames: 0 }))
+    })
+    .open_blocking()
+    .unwrap();
+}
+
+/// Embedded windows opened with
+/// [`WindowBuilder::with_parent_relative_position`] start at that offset
+/// inside of their parent, before the factory moves them.
+fn test_startup_embedded_relative_position() {
+    use std::sync::{Arc, Mutex};
+
+    const OFFSET: Point = Point { x: 256.0, y: 32.0 };
+    const ORIGIN: Point = Point { x: 0.0, y: 0.0 };
+
+    /// Reports the screen position of its top-left corner.
+    struct Child<'a> {
+        window: Window<'a>,
+        origin: Arc<Mutex<Option<Point>>>,
+    }
+
+    impl WindowHandler for Child<'_> {
+        fn frame(&mut self) {
+            *self.origin.lock().unwrap() = Some(self.window.to_screen(ORIGIN));
+        }
+    }
+
+    /// Opens the child, and closes once it reported where it is.
+    struct Handler<'a> {
+        window: Window<'a>,
+        child: Arc<Mutex<Option<Point>>>,
+        offset: Arc<Mutex<Option<Point>>>,
+        instant: Instant,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            if let Some(child) = *self.child.lock().unwrap() {
+                *self.offset.lock().unwrap() = Some(child - self.window.to_screen(ORIGIN));
+                self.window.close();
+            }
+
+            if self.instant.elapsed() > Duration::from_secs(2) {
+                self.window.close();
+            }
+        }
+    }
+
+    let offset = Arc::new(Mutex::new(None));
+    let recorded = offset.clone();
+
+    WindowBuilder::new(move |window| {
+        window.set_title("picoview test - embed relative position");
+        window.set_size((512, 256));
+        window.set_visible(true);
+
+        let child = Arc::new(Mutex::new(None));
+        let origin = child.clone();
+        WindowBuilder::new(move |window| {
+            window.set_title("picoview test - embed relative position (child)");
+            window.set_size((128, 128));
+            window.set_visible(true);
+
+            Ok(Box::new(Child { window, origin }))
+        })
+        .with_parent_relative_position(OFFSET)
+        .open_embedded(window)
+        .unwrap();
+
+        Ok(Box::new(Handler {
+            window,
+            child,
+            offset: recorded,
+            instant: Instant::now(),
+        }))
+    })
+    .open_blocking()
+    .unwrap();
+
+    assert_eq!(*offset.lock().unwrap(), Some(OFFSET));
+}
+
+/// Every [`Window`] method must be usable from the factory, including on the
+/// X11 threads of embedded and transient windows, before their event loop
+/// runs.
+fn test_startup_factory_calls() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Calls everything there is to call, in the order a plugin editor might.
+    fn call_everything(window: Window) {
+        window.set_title("picoview test - factory calls");
+        window.set_decorations(true);
+        window.set_cursor_icon(MouseCursor::Hand);
+        window.set_min_size((64, 64));
+        window.set_max_size((1024, 1024));
+        window.set_resize_hints(ResizeHints::default());
+        window.set_size((256, 256));
+        window.set_position((256, 0));
+        window.set_visible(true);
+        window.set_ime_cursor_area(Rect {
+            left: 0,
+            top: 0,
+            right: 2,
+            bottom: 16,
+        });
+
+        let _ = window.set_clipboard(Exchange::Text("test".into()));
+        let _ = window.get_clipboard();
+        let _ = window.scale();
+        let _ = window.resize_hints();
+        let _ = window.has_mouse_capture();
+        let _ = window.last_event_token();
+        let _ = window.safe_area_insets();
+        let _ = window.inner_size();
+        let _ = window.frame_insets();
+        let _ = window.outer_size();
+        let _ = window.id();
+        let _ = window.to_screen(Point { x: 0.0, y: 0.0 });
+        let _ = window.frame_jitter();
+        let _ = window.is_key_down(Key::Space);
+        let _ = window.scroll_settings();
+        let _ = window.window_handle();
+        let _ = window.display_handle();
+
+        window.waker().wakeup().unwrap();
+        window.redraw_now();
+    }
+
+    /// Counts its frames into `frames`, closes itself after the first one.
+    struct Child<'a> {
+        window: Window<'a>,
+        frames: Arc<AtomicUsize>,
+    }
+
+    impl WindowHandler for Child<'_> {
+        fn frame(&mut self) {
+            self.frames.fetch_add(1, Ordering::Relaxed);
+            self.window.close();
+        }
+    }
+
+    struct Handler<'a> {
+        window: Window<'a>,
+        frames: usize,
+        children: Arc<AtomicUsize>,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            if self.frames == 0 {
+                for embedded in [true, false] {
+                    let frames = self.children.clone();
+                    let builder = WindowBuilder::new(move |window| {
+                        call_everything(window);
+                        Ok(Box::new(Child { window, frames }))
+                    });
+
+                    match embedded {
+                        true => builder.open_embedded(self.window).unwrap(),
+                        false => builder.open_transient(self.window).unwrap(),
+                    };
+                }
+            }
+
+            if self.children.load(Ordering::Relaxed) == 2 || self.frames > 100 {
+                self.window.close();
+            }
+
+            self.frames += 1;
+        }
+    }
+
+    let children = Arc::new(AtomicUsize::new(0));
+    WindowBuilder::new({
+        let children = children.clone();
+        move |window| {
+            call_everything(window);
+            Ok(Box::new(Handler {
+                window,
+                frames: 0,
+                children,
+            }))
+        }
+    })
+    .open_blocking()
+    .unwrap();
+
+    assert_eq!(
+        children.load(Ordering::Relaxed),
+        2,
+        "both children got a frame after their factory"
+    );
+}
+
 fn test_startup_transient() {
     struct Handler<'a> {
         window: Window<'a>,
@@ -127,6 +452,23 @@ fn test_startup_transient() {
                 })
                 .open_transient(self.window)
                 .unwrap();
+
+                WindowBuilder::new(|window| {
+                    window.set_title("picoview test - transient child (follow)");
+                    window.set_size((256, 256));
+                    window.set_position((512, 0));
+                    window.set_visible(true);
+
+                    Ok(Box::new(()))
+                })
+                .with_follow_parent(true)
+                .open_transient(self.window)
+                .unwrap();
+            }
+
+            // the following child moves along, the other one stays
+            if self.frames == 5 {
+                self.window.set_position((150, 250));
             }
 
             if self.frames > 10 {
@@ -142,7 +484,7 @@ fn test_startup_transient() {
         // (or at least that it doesn't crash or cause UB to call into window)
         fn drop(&mut self) {
             let _ = self.window.get_clipboard();
-            self.window.set_clipboard(Exchange::Text("test".into()));
+            let _ = self.window.set_clipboard(Exchange::Text("test".into()));
             self.window.set_decorations(false);
             self.window.set_position((100, 200));
             self.window.set_size((512, 512));
@@ -165,6 +507,71 @@ fn test_startup_transient() {
     .unwrap();
 }
 
+fn test_startup_event_loop() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts its frames into `frames`, the top-level ones open a child and a
+    /// popup on their first frame.
+    struct Handler<'a> {
+        window: Window<'a>,
+        frames: usize,
+        total: Arc<AtomicUsize>,
+        is_top_level: bool,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            self.total.fetch_add(1, Ordering::Relaxed);
+
+            if self.frames == 0 && self.is_top_level {
+                // the loop is running already, a nested one must not start
+                assert!(matches!(
+                    EventLoop::new().run(),
+                    Err(WindowError::EventLoopRunning)
+                ));
+
+                self.window.open_child(counted(&self.total, false)).unwrap();
+                self.window
+                    .open_popup(counted(&self.total, false).with_parent_relative_position((16, 16)))
+                    .unwrap();
+            }
+
+            // the child and the popup are closed along with their parent
+            if self.is_top_level && self.frames > 10 {
+                self.window.close();
+            }
+
+            self.frames += 1;
+        }
+    }
+
+    fn counted(total: &Arc<AtomicUsize>, is_top_level: bool) -> WindowBuilder {
+        let total = total.clone();
+        WindowBuilder::new(move |window| {
+            window.set_size((128, 128));
+            window.set_visible(true);
+            Ok(Box::new(Handler {
+                window,
+                frames: 0,
+                total,
+                is_top_level,
+            }))
+        })
+    }
+
+    let total = Arc::new(AtomicUsize::new(0));
+    let event_loop = EventLoop::new();
+    event_loop.open(counted(&total, true)).unwrap();
+    event_loop.open(counted(&total, true)).unwrap();
+    event_loop.run().unwrap();
+
+    assert!(
+        total.load(Ordering::Relaxed) > 2 * 12,
+        "the children got frames next to both top-level windows"
+    );
+}
+
 fn test_startup_embedded() {
     struct Handler<'a> {
         window: Window<'a>,
@@ -219,7 +626,7 @@ fn test_startup_embedded() {
         // (or at least that it doesn't crash or cause UB to call into window)
         fn drop(&mut self) {
             let _ = self.window.get_clipboard();
-            self.window.set_clipboard(Exchange::Text("test".into()));
+            let _ = self.window.set_clipboard(Exchange::Text("test".into()));
             self.window.set_decorations(false);
             self.window.set_position((100, 200));
             self.window.set_size((512, 512));
@@ -242,6 +649,197 @@ fn test_startup_embedded() {
     .unwrap();
 }
 
+fn test_startup_window_group() {
+    struct Handler<'a> {
+        window: Window<'a>,
+        group: WindowGroup,
+        frames: usize,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            if self.frames == 0 {
+                assert_eq!(self.group.len(), 1);
+
+                WindowBuilder::new(|window| {
+                    window.set_title("picoview test - group embed");
+                    window.set_size((256, 256));
+                    window.set_visible(true);
+
+                    Ok(Box::new(()))
+                })
+                .with_group(self.group.clone())
+                .open_embedded(self.window)
+                .unwrap();
+
+                WindowBuilder::new(|window| {
+                    window.set_title("picoview test - group transient");
+                    window.set_size((256, 256));
+                    window.set_position((256, 0));
+                    window.set_visible(true);
+
+                    Ok(Box::new(()))
+                })
+                .with_group(self.group.clone())
+                .open_transient(self.window)
+                .unwrap();
+
+                assert_eq!(self.group.len(), 3);
+            }
+
+            // closes this window too
+            if self.frames > 10 {
+                self.group.close_all();
+            }
+
+            self.frames += 1;
+        }
+    }
+
+    let group = WindowGroup::new();
+
+    WindowBuilder::new({
+        let group = group.clone();
+        move |window| {
+            window.set_title("picoview test - group");
+            window.set_size((512, 256));
+            window.set_position((100, 200));
+            window.set_visible(true);
+
+            Ok(Box::new(Handler {
+                window,
+                group,
+                frames: 0,
+            }))
+        }
+    })
+    .with_group(group.clone())
+    .open_blocking()
+    .unwrap();
+
+    // the other windows might close on their own threads
+    let start = Instant::now();
+    while !group.is_empty() && start.elapsed() < Duration::from_secs(1) {
+        sleep(Duration::from_millis(10));
+    }
+
+    assert!(group.is_empty());
+}
+
+/// Opens windows on several threads, one of them with a handler that is too
+/// slow for the refresh rate. Its frames must be coalesced instead of queued
+/// up, and the other windows must keep getting frames at the usual rate.
+#[cfg(not(target_os = "macos"))]
+fn test_startup_frames_slow_handler() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    const SLOW_FRAME: Duration = Duration::from_millis(40);
+    const RUN_TIME: Duration = Duration::from_millis(600);
+
+    /// Counts its frames from the moment all windows are running until the
+    /// slow one ran for [`RUN_TIME`], then reports its frame time, how many
+    /// frames it got and for how long.
+    struct Handler<'a> {
+        window: Window<'a>,
+        frame_time: Duration,
+        start: Arc<Barrier>,
+        done: Arc<AtomicBool>,
+        started: Option<Instant>,
+        frames: usize,
+        result: mpsc::Sender<(Duration, usize, Duration)>,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            let Some(started) = self.started else {
+                self.start.wait();
+                self.started = Some(Instant::now());
+                return;
+            };
+
+            self.frames += 1;
+            sleep(self.frame_time);
+
+            if self.frame_time == SLOW_FRAME && started.elapsed() > RUN_TIME {
+                self.done.store(true, Ordering::Relaxed);
+            }
+
+            if self.done.load(Ordering::Relaxed) && !self.window.is_closed() {
+                let result = (self.frame_time, self.frames, started.elapsed());
+                self.result.send(result).unwrap();
+                self.window.close();
+            }
+        }
+    }
+
+    let frame_times = [SLOW_FRAME, Duration::ZERO, Duration::ZERO];
+    let start = Arc::new(Barrier::new(frame_times.len()));
+    let done = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+
+    let threads: Vec<_> = frame_times
+        .into_iter()
+        .map(|frame_time| {
+            let (start, done, result) = (start.clone(), done.clone(), sender.clone());
+            spawn(move || {
+                WindowBuilder::new(move |window| {
+                    window.set_title("picoview test - slow handler");
+                    window.set_size((256, 256));
+                    window.set_visible(true);
+
+                    Ok(Box::new(Handler {
+                        window,
+                        frame_time,
+                        start,
+                        done,
+                        started: None,
+                        frames: 0,
+                        result,
+                    }))
+                })
+                .open_blocking()
+                .unwrap();
+            })
+        })
+        .collect();
+
+    drop(sender);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let results: Vec<_> = receiver.iter().collect();
+    assert_eq!(
+        results.len(),
+        frame_times.len(),
+        "not every window finished"
+    );
+
+    let (_, slow_frames, elapsed) = results
+        .iter()
+        .copied()
+        .find(|(frame_time, ..)| *frame_time == SLOW_FRAME)
+        .unwrap();
+    let max_frames = (elapsed.as_millis() / SLOW_FRAME.as_millis()) as usize + 2;
+
+    assert!(slow_frames >= 3, "slow window got {slow_frames} frames");
+    assert!(
+        slow_frames <= max_frames,
+        "slow window got {slow_frames} frames, they were not coalesced"
+    );
+
+    for (_, frames, _) in results
+        .iter()
+        .filter(|(frame_time, ..)| frame_time.is_zero())
+    {
+        assert!(
+            *frames > max_frames,
+            "fast window got {frames} frames while the slow one got {slow_frames}"
+        );
+    }
+}
+
 fn test_startup_error() {
     let err = WindowBuilder::new(|window| {
         window.set_title("picoview test - error");
@@ -256,3 +854,717 @@ fn test_startup_error() {
 
     assert_eq!(err.to_string(), "test error");
 }
+
+fn test_startup_wakeup_allocation_free() {
+    struct Handler<'a> {
+        window: Window<'a>,
+        instant: Instant,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            if self.instant.elapsed() > Duration::from_millis(500) {
+                self.window.close();
+            }
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel::<WindowWaker>();
+    let thread = spawn(move || {
+        let waker = receiver.recv().unwrap();
+        let before = ALLOCATIONS.with(Cell::get);
+        for _ in 0..100 {
+            waker.wakeup().unwrap();
+        }
+
+        ALLOCATIONS.with(Cell::get) - before
+    });
+
+    WindowBuilder::new(move |window| {
+        window.set_title("picoview test - wakeup");
+        window.set_size((512, 256));
+        window.set_visible(true);
+
+        sender.send(window.waker()).unwrap();
+
+        Ok(Box::new(Handler {
+            window,
+            instant: Instant::now(),
+        }))
+    })
+    .open_blocking()
+    .unwrap();
+
+    assert_eq!(thread.join().unwrap(), 0, "WindowWaker::wakeup allocated");
+}
+
+/// Graphics surfaces (wgpu, softbuffer) are created from the raw handles in the
+/// factory and kept in the handler, so the handles must not change while the
+/// window is resized or hidden, and must still be readable when the handler is
+/// dropped.
+fn test_startup_surface_handles_stable() {
+    struct Handler<'a> {
+        window: Window<'a>,
+        instant: Instant,
+        handles: (RawWindowHandle, RawDisplayHandle),
+    }
+
+    impl Handler<'_> {
+        fn check_handles(&self) {
+            assert_eq!(raw_handles(self.window), self.handles);
+        }
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            self.check_handles();
+
+            let elapsed = self.instant.elapsed();
+            if elapsed > Duration::from_millis(500) {
+                self.window.close();
+            } else if elapsed > Duration::from_millis(250) {
+                self.window.set_visible(false);
+            } else {
+                self.window
+                    .set_size((256 + elapsed.as_millis() as u32, 256));
+            }
+        }
+
+        fn size_changed(&mut self, _: Size) {
+            self.check_handles();
+        }
+    }
+
+    impl Drop for Handler<'_> {
+        fn drop(&mut self) {
+            self.check_handles();
+        }
+    }
+
+    fn raw_handles(window: Window) -> (RawWindowHandle, RawDisplayHandle) {
+        (
+            window.window_handle().unwrap().as_raw(),
+            window.display_handle().unwrap().as_raw(),
+        )
+    }
+
+    WindowBuilder::new(|window| {
+        window.set_title("picoview test - surface handles");
+        window.set_size((256, 256));
+        window.set_visible(true);
+
+        Ok(Box::new(Handler {
+            window,
+            instant: Instant::now(),
+            handles: raw_handles(window),
+        }))
+    })
+    .open_blocking()
+    .unwrap();
+}
+
+/// Opens multiple windows on one thread, with a foreign `WH_GETMESSAGE` hook
+/// installed (emulating another plugin on the same host thread), and checks
+/// that key events are dispatched to the right window and that the foreign
+/// hook is still called.
+#[cfg(target_os = "windows")]
+fn test_startup_keyboard_hook_chaining() {
+    use picoview::Key;
+    use picoview::rwh_06::{HasWindowHandle, RawWindowHandle};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use windows_sys::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::System::Threading::GetCurrentThreadId;
+    use windows_sys::Win32::UI::WindowsAndMessaging::*;
+
+    /// Key messages that reached the foreign hook.
+    static FOREIGN_KEYS: AtomicUsize = AtomicUsize::new(0);
+    /// Captured (zeroed) messages that reached the foreign hook.
+    static FOREIGN_CAPTURED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "system" fn foreign_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        unsafe {
+            if code == HC_ACTION as i32 && wparam == PM_REMOVE as usize {
+                let message = &*(lparam as *const MSG);
+                if message.message == WM_KEYDOWN {
+                    FOREIGN_KEYS.fetch_add(1, Ordering::Relaxed);
+                } else if message.message == WM_USER && message.hwnd.is_null() {
+                    FOREIGN_CAPTURED.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            CallNextHookEx(std::ptr::null_mut(), code, wparam, lparam)
+        }
+    }
+
+    struct KeyCounter {
+        presses: Arc<AtomicUsize>,
+        capture: bool,
+    }
+
+    impl WindowHandler for KeyCounter {
+        fn key_press(&mut self, key: Key, down: bool, _: bool, _: Option<char>) -> bool {
+            if key == Key::A && down {
+                self.presses.fetch_add(1, Ordering::Relaxed);
+            }
+
+            self.capture
+        }
+    }
+
+    struct Handler<'a> {
+        window: Window<'a>,
+        counter: KeyCounter,
+        children: [Arc<AtomicUsize>; 2],
+        frames: usize,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            if self.frames == 0 {
+                for (presses, capture) in self.children.iter().zip([true, false]) {
+                    let presses = presses.clone();
+                    WindowBuilder::new(move |window| {
+                        window.set_size((128, 128));
+                        window.set_visible(true);
+
+                        // post a physical-looking `A` key press to the child
+                        if let Ok(handle) = window.window_handle()
+                            && let RawWindowHandle::Win32(handle) = handle.as_raw()
+                        {
+                            unsafe {
+                                PostMessageW(handle.hwnd.get() as _, WM_KEYDOWN, 0x41, 0x1E << 16);
+                            }
+                        }
+
+                        Ok(Box::new(KeyCounter { presses, capture }))
+                    })
+                    .open_embedded(self.window)
+                    .unwrap();
+                }
+            }
+
+            if self.frames > 10 {
+                self.window.close();
+            }
+
+            self.frames += 1;
+        }
+
+        fn key_press(&mut self, key: Key, down: bool, repeat: bool, text: Option<char>) -> bool {
+            self.counter.key_press(key, down, repeat, text)
+        }
+    }
+
+    let hhook = unsafe {
+        SetWindowsHookExW(
+            WH_GETMESSAGE,
+            Some(foreign_hook),
+            std::ptr::null_mut(),
+            GetCurrentThreadId(),
+        )
+    };
+    assert!(!hhook.is_null(), "failed to install the foreign hook");
+
+    let parent = Arc::new(AtomicUsize::new(0));
+    let children = [Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0))];
+
+    WindowBuilder::new({
+        let parent = parent.clone();
+        let children = children.clone();
+        move |window| {
+            window.set_title("picoview test - keyboard hook chaining");
+            window.set_size((512, 256));
+            window.set_visible(true);
+
+            Ok(Box::new(Handler {
+                window,
+                counter: KeyCounter {
+                    presses: parent,
+                    capture: true,
+                },
+                children,
+                frames: 0,
+            }))
+        }
+    })
+    .open_blocking()
+    .unwrap();
+
+    unsafe { UnhookWindowsHookEx(hhook) };
+
+    // the capturing child keeps its key, the other one forwards it to the parent
+    assert_eq!(children[0].load(Ordering::Relaxed), 1);
+    assert_eq!(children[1].load(Ordering::Relaxed), 1);
+    assert_eq!(parent.load(Ordering::Relaxed), 1);
+
+    // both keys were captured by us, the foreign hook was still called for them
+    assert_eq!(FOREIGN_KEYS.load(Ordering::Relaxed), 0);
+    assert_eq!(FOREIGN_CAPTURED.load(Ordering::Relaxed), 2);
+}
+
+#[cfg(target_os = "linux")]
+fn test_startup_x11_unicode_title() {
+    use picoview::WindowExtX11;
+    use std::ffi::CStr;
+    use std::ptr::null_mut;
+    use x11::xlib::{AnyPropertyType, Display, XFree, XGetWindowProperty, XInternAtom};
+
+    const TITLES: [&str; 2] = ["picoview テスト 🪟", "picoview 测试 – ünïcödé 🎉"];
+
+    /// Reads a property of the window as bytes.
+    fn read_property(window: Window, property: &CStr) -> Vec<u8> {
+        unsafe {
+            let display = window.display() as *mut Display;
+            let property = XInternAtom(display, property.as_ptr(), 0);
+
+            let mut kind = 0;
+            let mut format = 0;
+            let mut len = 0;
+            let mut remaining = 0;
+            let mut data = null_mut();
+            XGetWindowProperty(
+                display,
+                window.xid(),
+                property,
+                0,
+                1024,
+                0,
+                AnyPropertyType as _,
+                &mut kind,
+                &mut format,
+                &mut len,
+                &mut remaining,
+                &mut data,
+            );
+
+            if data.is_null() {
+                return Vec::new();
+            }
+
+            let bytes = std::slice::from_raw_parts(data, len as usize).to_vec();
+            XFree(data as *mut _);
+            bytes
+        }
+    }
+
+    struct Handler<'a> {
+        window: Window<'a>,
+        frames: usize,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            // headless windows have nothing to read back
+            if self.window.xid() == 0 {
+                self.window.close();
+                return;
+            }
+
+            let title = TITLES[self.frames % TITLES.len()];
+            assert_eq!(
+                read_property(self.window, c"_NET_WM_NAME"),
+                title.as_bytes()
+            );
+            assert!(!read_property(self.window, c"WM_NAME").is_empty());
+            assert!(!read_property(self.window, c"WM_CLIENT_MACHINE").is_empty());
+
+            self.frames += 1;
+            if self.frames > 4 {
+                self.window.close();
+            } else {
+                self.window.set_title(TITLES[self.frames % TITLES.len()]);
+            }
+        }
+    }
+
+    WindowBuilder::new(|window| {
+        window.set_title(TITLES[0]);
+        window.set_size((256, 256));
+        window.set_visible(true);
+
+        Ok(Box::new(Handler { window, frames: 0 }))
+    })
+    .open_blocking()
+    .unwrap();
+}
+
+/// Dragging out of the window must not report a leave or a release until the
+/// button is let go. Only a grab of another client, which takes the release
+/// away from us, ends the drag early.
+#[cfg(target_os = "linux")]
+fn test_startup_x11_no_leave_while_held() {
+    use picoview::{MouseButton, WindowExtX11};
+    use std::ffi::c_int;
+    use std::mem::zeroed;
+    use std::ptr::null;
+    use std::sync::{Arc, Mutex};
+    use x11::xlib::{
+        ButtonPress, ButtonRelease, Display, LeaveNotify, MotionNotify, NoEventMask, NotifyGrab,
+        NotifyNormal, XCloseDisplay, XEvent, XFlush, XOpenDisplay, XSendEvent,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Seen {
+        Press(bool),
+        Leave,
+    }
+
+    /// Send a pointer event to the window, the way the server would.
+    #[allow(non_upper_case_globals)]
+    fn send(display: *mut Display, window: u64, kind: c_int, (x, y): (c_int, c_int), mode: c_int) {
+        unsafe {
+            let mut event = zeroed::<XEvent>();
+            match kind {
+                ButtonPress | ButtonRelease => {
+                    event.button.button = 1;
+                    event.button.x = x;
+                    event.button.y = y;
+                    event.button.same_screen = 1;
+                }
+                MotionNotify => {
+                    event.motion.x = x;
+                    event.motion.y = y;
+                    event.motion.same_screen = 1;
+                }
+                _ => {
+                    event.crossing.x = x;
+                    event.crossing.y = y;
+                    event.crossing.mode = mode;
+                    event.crossing.same_screen = 1;
+                }
+            }
+
+            event.any.type_ = kind;
+            event.any.window = window as _;
+            XSendEvent(display, window as _, 0, NoEventMask, &mut event);
+            XFlush(display);
+        }
+    }
+
+    struct Handler<'a> {
+        window: Window<'a>,
+        /// A connection of its own, like the one of the server would be
+        display: *mut Display,
+        seen: Arc<Mutex<Vec<Seen>>>,
+        step: usize,
+        waited: usize,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn mouse_press(&mut self, button: MouseButton, pressed: bool, _: Point) {
+            if button == MouseButton::Left {
+                self.seen.lock().unwrap().push(Seen::Press(pressed));
+            }
+        }
+
+        fn mouse_leave(&mut self) {
+            self.seen.lock().unwrap().push(Seen::Leave);
+        }
+
+        fn frame(&mut self) {
+            let (inside, outside) = ((10, 10), (-5, -5));
+            let window = self.window.xid();
+            let leaves = self
+                .seen
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|seen| **seen == Seen::Leave)
+                .count();
+
+            // every step ends with a leave, the next one starts once it arrived
+            if leaves < self.step {
+                self.waited += 1;
+                // give up, the test body reports what arrived
+                if self.waited > 300 {
+                    self.window.close();
+                }
+
+                return;
+            }
+
+            match self.step {
+                0 => {
+                    // dragged out and released there
+                    send(self.display, window, MotionNotify, inside, 0);
+                    send(self.display, window, ButtonPress, inside, 0);
+                    send(self.display, window, LeaveNotify, outside, NotifyNormal);
+                    send(self.display, window, MotionNotify, outside, 0);
+                    send(self.display, window, ButtonRelease, outside, 0);
+                }
+                1 => {
+                    // another client grabbed the pointer mid-drag
+                    send(self.display, window, MotionNotify, inside, 0);
+                    send(self.display, window, ButtonPress, inside, 0);
+                    send(self.display, window, LeaveNotify, outside, NotifyGrab);
+                }
+                _ => self.window.close(),
+            }
+
+            self.step += 1;
+        }
+    }
+
+    impl Drop for Handler<'_> {
+        fn drop(&mut self) {
+            unsafe {
+                XCloseDisplay(self.display);
+            }
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let handler_seen = seen.clone();
+    WindowBuilder::new(move |window| {
+        window.set_title("picoview test - leave while held");
+        window.set_size((256, 256));
+        window.set_visible(true);
+
+        let display = unsafe { XOpenDisplay(null()) };
+        assert!(!display.is_null());
+
+        Ok(Box::new(Handler {
+            window,
+            display,
+            seen: handler_seen,
+            step: 0,
+            waited: 0,
+        }))
+    })
+    .open_blocking()
+    .unwrap();
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            Seen::Press(true),
+            Seen::Press(false),
+            Seen::Leave,
+            Seen::Press(true),
+            Seen::Press(false),
+            Seen::Leave,
+        ]
+    );
+}
+
+/// Filling the root window must add to the events we listen to on it, not
+/// replace the `PropertyChangeMask` that keeps the screen metrics current.
+#[cfg(target_os = "linux")]
+fn test_startup_x11_fill_root_keeps_events() {
+    use picoview::WindowExtX11;
+    use picoview::rwh_06::{HandleError, WindowHandle, XlibWindowHandle};
+    use std::ptr::null;
+    use x11::xlib::{
+        Display, PropertyChangeMask, StructureNotifyMask, XCloseDisplay, XDefaultRootWindow,
+        XGetWindowAttributes, XOpenDisplay, XWindowAttributes,
+    };
+
+    struct Root(u64);
+
+    impl HasWindowHandle for Root {
+        fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+            let handle = RawWindowHandle::Xlib(XlibWindowHandle::new(self.0 as _));
+            Ok(unsafe { WindowHandle::borrow_raw(handle) })
+        }
+    }
+
+    struct Handler<'a> {
+        window: Window<'a>,
+        root: u64,
+        sender: mpsc::Sender<i64>,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn frame(&mut self) {
+            // through the connection of the window, the mask is per client
+            let mask = unsafe {
+                let mut attributes = std::mem::zeroed::<XWindowAttributes>();
+                XGetWindowAttributes(
+                    self.window.display() as *mut Display,
+                    self.root as _,
+                    &mut attributes,
+                );
+                attributes.your_event_mask
+            };
+
+            let _ = self.sender.send(mask);
+            self.window.close();
+        }
+    }
+
+    let root = unsafe {
+        let display = XOpenDisplay(null());
+        if display.is_null() {
+            return;
+        }
+
+        let root = XDefaultRootWindow(display);
+        XCloseDisplay(display);
+        root as u64
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let guard = WindowBuilder::new(move |window| {
+        window.set_title("picoview test - fill root");
+        Ok(Box::new(Handler {
+            window,
+            root,
+            sender,
+        }))
+    })
+    .with_fill_parent(true)
+    .open_embedded(Root(root))
+    .unwrap()
+    .into_guard();
+
+    let mask = receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("the embedded window runs a frame");
+    drop(guard);
+
+    assert_ne!(mask & PropertyChangeMask, 0);
+    assert_ne!(mask & StructureNotifyMask, 0);
+}
+
+#[cfg(target_os = "linux")]
+fn test_startup_x11_cursor_grab_focus_loss() {
+    use picoview::{CursorGrab, WindowExtX11, WindowVisibility};
+    use std::ffi::c_int;
+    use std::mem::zeroed;
+    use std::ptr::null;
+    use std::sync::{Arc, Mutex};
+    use x11::xlib::{
+        AlreadyGrabbed, CurrentTime, Display, FocusIn, FocusOut, GrabModeAsync, GrabSuccess,
+        NoEventMask, NotifyNonlinear, NotifyNormal, PointerMotionMask, XCloseDisplay,
+        XDefaultRootWindow, XEvent, XFlush, XGrabPointer, XOpenDisplay, XSendEvent, XUngrabPointer,
+    };
+
+    /// What the test saw, checked once the window is closed.
+    #[derive(Debug, Default)]
+    struct Seen {
+        grabbed: Option<bool>,
+        /// The result of grabbing the pointer from another client while held
+        while_held: Option<c_int>,
+        /// The same after the window lost the focus
+        after_focus_loss: Option<c_int>,
+    }
+
+    /// Grab the pointer from another client and let go of it right away.
+    fn probe(display: *mut Display) -> c_int {
+        unsafe {
+            let root = XDefaultRootWindow(display);
+            let result = XGrabPointer(
+                display,
+                root,
+                0,
+                PointerMotionMask as _,
+                GrabModeAsync,
+                GrabModeAsync,
+                0,
+                0,
+                CurrentTime,
+            );
+
+            if result == GrabSuccess {
+                XUngrabPointer(display, CurrentTime);
+            }
+
+            XFlush(display);
+            result
+        }
+    }
+
+    /// Send a focus change to the window, the way the server would.
+    fn send_focus(display: *mut Display, window: u64, kind: c_int) {
+        unsafe {
+            let mut event = zeroed::<XEvent>();
+            event.focus_change.type_ = kind;
+            event.focus_change.window = window as _;
+            event.focus_change.mode = NotifyNormal;
+            event.focus_change.detail = NotifyNonlinear;
+            XSendEvent(display, window as _, 0, NoEventMask, &mut event);
+            XFlush(display);
+        }
+    }
+
+    struct Handler<'a> {
+        window: Window<'a>,
+        /// A connection of its own, like the one of another application
+        display: *mut Display,
+        seen: Arc<Mutex<Seen>>,
+        visible: bool,
+        focus_lost: bool,
+        frames: usize,
+    }
+
+    impl WindowHandler for Handler<'_> {
+        fn visibility_changed(&mut self, visibility: WindowVisibility) {
+            self.visible = visibility == WindowVisibility::Normal;
+        }
+
+        fn focus_changed(&mut self, focus: bool) {
+            self.focus_lost = !focus;
+        }
+
+        fn frame(&mut self) {
+            self.frames += 1;
+            // give up, the test body reports what arrived
+            if self.frames > 300 {
+                self.window.close();
+                return;
+            }
+
+            let mut seen = self.seen.lock().unwrap();
+            if seen.grabbed.is_none() {
+                if self.visible {
+                    let grabbed = self.window.set_cursor_grab(CursorGrab::Confined).is_ok();
+                    seen.grabbed = Some(grabbed);
+                    seen.while_held = Some(probe(self.display));
+
+                    let window = self.window.xid();
+                    send_focus(self.display, window, FocusIn);
+                    send_focus(self.display, window, FocusOut);
+                }
+            } else if self.focus_lost {
+                seen.after_focus_loss = Some(probe(self.display));
+                self.window.close();
+            }
+        }
+    }
+
+    impl Drop for Handler<'_> {
+        fn drop(&mut self) {
+            unsafe {
+                XCloseDisplay(self.display);
+            }
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Seen::default()));
+    let handler_seen = seen.clone();
+    WindowBuilder::new(move |window| {
+        window.set_title("picoview test - cursor grab focus loss");
+        window.set_size((256, 256));
+        window.set_visible(true);
+
+        let display = unsafe { XOpenDisplay(null()) };
+        assert!(!display.is_null());
+
+        Ok(Box::new(Handler {
+            window,
+            display,
+            seen: handler_seen,
+            visible: false,
+            focus_lost: false,
+            frames: 0,
+        }))
+    })
+    .open_blocking()
+    .unwrap();
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.grabbed, Some(true), "{seen:?}");
+    assert_eq!(seen.while_held, Some(AlreadyGrabbed), "{seen:?}");
+    assert_eq!(seen.after_focus_loss, Some(GrabSuccess), "{seen:?}");
+}